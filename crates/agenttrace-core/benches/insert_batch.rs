@@ -0,0 +1,118 @@
+//! Span insert throughput: one statement per span vs. a single UNNEST insert
+//!
+//! Requires a migrated TimescaleDB instance:
+//!
+//! ```sh
+//! DATABASE_URL=postgres://localhost/agenttrace cargo bench --bench insert_batch
+//! ```
+//!
+//! The benchmark is skipped when `DATABASE_URL` is not set.
+
+use chrono::{Duration, Utc};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use uuid::Uuid;
+
+use agenttrace::config::DatabaseConfig;
+use agenttrace::db::{PostgresPool, SpanRepository};
+use agenttrace::models::{Span, SpanKind, SpanPriority, SpanStatus};
+
+fn create_test_spans(count: usize) -> Vec<Span> {
+    let trace_id = Uuid::new_v4().simple().to_string();
+    let started_at = Utc::now();
+
+    (0..count)
+        .map(|i| Span {
+            id: Uuid::new_v4(),
+            span_id: Uuid::new_v4().simple().to_string(),
+            trace_id: trace_id.clone(),
+            parent_span_id: None,
+            session_id: None,
+            user_id: None,
+            operation_name: "llm_call".to_string(),
+            service_name: "bench".to_string(),
+            span_kind: SpanKind::Client,
+            started_at: started_at + Duration::microseconds(i as i64),
+            ended_at: Some(started_at + Duration::milliseconds(250)),
+            duration_ms: Some(250.0),
+            status: SpanStatus::Ok,
+            status_message: None,
+            model_name: Some("claude-sonnet-4-20250514".to_string()),
+            model_provider: Some("anthropic".to_string()),
+            tokens_in: Some(1200),
+            tokens_cached_in: None,
+            tokens_out: Some(300),
+            tokens_reasoning: None,
+            cost_usd: Some(0.0081),
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: Some("Summarize the following document".to_string()),
+            completion_preview: Some("The document describes".to_string()),
+            attributes: serde_json::json!({"bench": true, "iteration": i}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::Normal,
+        })
+        .collect()
+}
+
+fn bench_insert(c: &mut Criterion) {
+    if std::env::var("DATABASE_URL").is_err() {
+        eprintln!("DATABASE_URL not set, skipping insert_batch benchmark");
+        return;
+    }
+
+    let rt = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let pool = rt
+        .block_on(PostgresPool::new(&DatabaseConfig::default()))
+        .expect("connect to database");
+    let repo = SpanRepository::new(&pool);
+
+    let mut group = c.benchmark_group("span_insert");
+    group.sample_size(10);
+
+    for size in [100usize, 1000] {
+        group.throughput(Throughput::Elements(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("per_row", size), &size, |b, &size| {
+            b.to_async(&rt).iter_batched(
+                || create_test_spans(size),
+                |spans| {
+                    let repo = repo.clone();
+                    async move {
+                        for span in &spans {
+                            repo.insert(span).await.expect("insert span");
+                        }
+                    }
+                },
+                criterion::BatchSize::LargeInput,
+            );
+        });
+
+        group.bench_with_input(BenchmarkId::new("unnest", size), &size, |b, &size| {
+            b.to_async(&rt).iter_batched(
+                || create_test_spans(size),
+                |spans| {
+                    let repo = repo.clone();
+                    async move {
+                        let inserted = repo.insert_batch(&spans).await.expect("insert batch");
+                        assert_eq!(inserted, spans.len());
+                    }
+                },
+                criterion::BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+
+    rt.block_on(async {
+        let _ = sqlx::query("DELETE FROM spans WHERE service_name = 'bench'")
+            .execute(pool.pool())
+            .await;
+    });
+}
+
+criterion_group!(benches, bench_insert);
+criterion_main!(benches);
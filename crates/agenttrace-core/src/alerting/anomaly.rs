@@ -0,0 +1,128 @@
+//! Baseline-based anomaly detection
+//!
+//! Anomaly rules compare the current window against the same metric over the
+//! windows just before it. The rule's `threshold` is the number of standard
+//! deviations from the baseline mean that counts as anomalous, and its
+//! operator picks the direction: `gt`/`gte` fire on spikes, `lt`/`lte` on
+//! drops, and `eq`/`ne` on either.
+
+use crate::models::alert::{AlertRule, Operator};
+
+/// Prior windows pulled to build a baseline
+pub(crate) const BASELINE_WINDOWS: i64 = 12;
+
+/// Fewest prior windows with data before a baseline is trusted
+const MIN_BASELINE_WINDOWS: usize = 3;
+
+/// Sigmas used when an anomaly rule has no threshold
+const DEFAULT_SIGMAS: f64 = 3.0;
+
+/// Smallest spread, relative to the mean, a baseline is given so that a flat
+/// history doesn't turn every small wobble into an anomaly
+const MIN_RELATIVE_STDDEV: f64 = 0.01;
+
+/// Rolling mean and spread of a metric over recent windows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Baseline {
+    pub mean: f64,
+    pub stddev: f64,
+    pub windows: usize,
+}
+
+impl Baseline {
+    /// Baseline over the values of prior windows, or `None` if too few had data
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.len() < MIN_BASELINE_WINDOWS {
+            return None;
+        }
+
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let stddev = variance
+            .sqrt()
+            .max(mean.abs() * MIN_RELATIVE_STDDEV)
+            .max(f64::EPSILON);
+
+        Some(Self {
+            mean,
+            stddev,
+            windows: values.len(),
+        })
+    }
+
+    /// Signed distance of `value` from the mean, in standard deviations
+    pub fn sigmas(&self, value: f64) -> f64 {
+        (value - self.mean) / self.stddev
+    }
+
+    /// Whether `value` deviates from the baseline far enough, in the
+    /// direction the rule watches, to be anomalous
+    pub fn is_anomalous(&self, rule: &AlertRule, value: f64) -> bool {
+        let limit = sigma_limit(rule);
+        let sigmas = self.sigmas(value);
+
+        match rule.operator {
+            Operator::Gt => sigmas > limit,
+            Operator::Gte => sigmas >= limit,
+            Operator::Lt => sigmas < -limit,
+            Operator::Lte => sigmas <= -limit,
+            Operator::Eq | Operator::Ne => sigmas.abs() >= limit,
+        }
+    }
+}
+
+/// Number of standard deviations an anomaly rule tolerates
+pub(crate) fn sigma_limit(rule: &AlertRule) -> f64 {
+    rule.threshold.filter(|t| *t > 0.0).unwrap_or(DEFAULT_SIGMAS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::ConditionType;
+
+    fn create_test_rule(operator: Operator, sigmas: f64) -> AlertRule {
+        AlertRule {
+            condition_type: ConditionType::Anomaly,
+            ..AlertRule::new("Latency anomaly", "latency_p95", operator, sigmas)
+        }
+    }
+
+    #[test]
+    fn test_baseline_needs_enough_windows() {
+        assert!(Baseline::from_values(&[100.0, 110.0]).is_none());
+
+        let baseline = Baseline::from_values(&[90.0, 100.0, 110.0, 100.0]).unwrap();
+        assert_eq!(baseline.mean, 100.0);
+        assert!((baseline.stddev - 50f64.sqrt()).abs() < 1e-9);
+        assert_eq!(baseline.windows, 4);
+    }
+
+    #[test]
+    fn test_direction_follows_operator() {
+        let baseline = Baseline::from_values(&[90.0, 100.0, 110.0, 100.0]).unwrap();
+        let spikes = create_test_rule(Operator::Gt, 3.0);
+        let drops = create_test_rule(Operator::Lt, 3.0);
+        let either = create_test_rule(Operator::Ne, 3.0);
+
+        assert!(baseline.is_anomalous(&spikes, 130.0));
+        assert!(!baseline.is_anomalous(&spikes, 115.0));
+        assert!(!baseline.is_anomalous(&spikes, 60.0));
+
+        assert!(baseline.is_anomalous(&drops, 60.0));
+        assert!(!baseline.is_anomalous(&drops, 130.0));
+
+        assert!(baseline.is_anomalous(&either, 60.0));
+        assert!(baseline.is_anomalous(&either, 130.0));
+    }
+
+    #[test]
+    fn test_flat_history_tolerates_small_changes() {
+        let baseline = Baseline::from_values(&[200.0; 6]).unwrap();
+        let rule = create_test_rule(Operator::Gt, 3.0);
+
+        assert!(!baseline.is_anomalous(&rule, 204.0));
+        assert!(baseline.is_anomalous(&rule, 210.0));
+    }
+}
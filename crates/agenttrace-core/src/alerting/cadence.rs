@@ -0,0 +1,168 @@
+//! Per-rule evaluation cadence
+//!
+//! Each rule is evaluated every `evaluation_interval_seconds`, so cheap rules
+//! can run every few seconds and expensive ones hourly. Rules wait in a
+//! min-heap keyed by when they are next due.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::models::alert::AlertRule;
+
+/// Rules waiting for their next evaluation
+#[derive(Default)]
+pub(crate) struct EvaluationQueue {
+    /// (due, rule) pairs; entries whose due time no longer matches `due` are
+    /// stale and skipped
+    heap: BinaryHeap<Reverse<(DateTime<Utc>, Uuid)>>,
+    /// When each queued rule is next due
+    due: HashMap<Uuid, DateTime<Utc>>,
+    /// Latest definition of each queued rule
+    rules: HashMap<Uuid, AlertRule>,
+}
+
+impl EvaluationQueue {
+    /// Replace the queued rules with the currently enabled ones
+    ///
+    /// New rules are due straight away and removed rules are dropped. A rule
+    /// whose interval was shortened is brought forward to at most one new
+    /// interval from now.
+    pub fn sync(&mut self, rules: Vec<AlertRule>, now: DateTime<Utc>) {
+        let mut current = HashMap::with_capacity(rules.len());
+
+        for rule in rules {
+            let due = match self.due.get(&rule.id) {
+                Some(&due) => due.min(now + interval(&rule)),
+                None => now,
+            };
+            if self.due.get(&rule.id) != Some(&due) {
+                self.schedule(rule.id, due);
+            }
+            current.insert(rule.id, rule);
+        }
+
+        self.due.retain(|id, _| current.contains_key(id));
+        self.rules = current;
+    }
+
+    /// When the next rule is due, if any are queued
+    pub fn next_due(&mut self) -> Option<DateTime<Utc>> {
+        self.drop_stale();
+        self.heap.peek().map(|Reverse((due, _))| *due)
+    }
+
+    /// Take the rules due by `now`, earliest first, queueing each again one
+    /// interval later
+    ///
+    /// A rule that has fallen more than an interval behind is rescheduled
+    /// from now rather than evaluated repeatedly to catch up.
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<AlertRule> {
+        let mut due_rules = Vec::new();
+
+        while let Some(due) = self.next_due().filter(|due| *due <= now) {
+            let Some(Reverse((_, id))) = self.heap.pop() else { break };
+            let Some(rule) = self.rules.get(&id).cloned() else {
+                self.due.remove(&id);
+                continue;
+            };
+
+            let mut next = due + interval(&rule);
+            if next <= now {
+                next = now + interval(&rule);
+            }
+            self.schedule(id, next);
+            due_rules.push(rule);
+        }
+
+        due_rules
+    }
+
+    fn schedule(&mut self, id: Uuid, due: DateTime<Utc>) {
+        self.due.insert(id, due);
+        self.heap.push(Reverse((due, id)));
+    }
+
+    /// Pop entries left behind by rescheduled or removed rules
+    fn drop_stale(&mut self) {
+        while let Some(Reverse((due, id))) = self.heap.peek() {
+            if self.due.get(id) == Some(due) {
+                break;
+            }
+            self.heap.pop();
+        }
+    }
+}
+
+fn interval(rule: &AlertRule) -> Duration {
+    Duration::seconds(i64::from(rule.evaluation_interval_seconds.max(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::Operator;
+
+    fn create_test_rule(name: &str, interval_secs: i32) -> AlertRule {
+        AlertRule {
+            evaluation_interval_seconds: interval_secs,
+            ..AlertRule::new(name, "error_rate", Operator::Gt, 0.05)
+        }
+    }
+
+    fn names(rules: &[AlertRule]) -> Vec<&str> {
+        rules.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_rules_run_on_their_own_interval() {
+        let start = Utc::now();
+        let mut queue = EvaluationQueue::default();
+        queue.sync(vec![create_test_rule("fast", 15), create_test_rule("slow", 3600)], start);
+
+        assert_eq!(queue.take_due(start).len(), 2);
+        assert_eq!(queue.next_due(), Some(start + Duration::seconds(15)));
+
+        let mut runs = Vec::new();
+        for tick in 1..=8 {
+            let now = start + Duration::seconds(tick * 15);
+            runs.extend(queue.take_due(now).into_iter().map(|r| r.name));
+        }
+        assert_eq!(runs, vec!["fast"; 8]);
+
+        assert_eq!(names(&queue.take_due(start + Duration::seconds(3600))), vec!["fast", "slow"]);
+    }
+
+    #[test]
+    fn test_sync_adds_removes_and_reschedules() {
+        let start = Utc::now();
+        let mut fast = create_test_rule("fast", 60);
+        let slow = create_test_rule("slow", 600);
+        let mut queue = EvaluationQueue::default();
+        queue.sync(vec![fast.clone(), slow.clone()], start);
+        queue.take_due(start);
+
+        // Dropping a rule removes it; shortening an interval brings it forward
+        fast.evaluation_interval_seconds = 10;
+        let later = start + Duration::seconds(5);
+        queue.sync(vec![fast, create_test_rule("new", 60)], later);
+
+        assert_eq!(names(&queue.take_due(later)), vec!["new"]);
+        assert_eq!(queue.next_due(), Some(later + Duration::seconds(10)));
+        assert!(queue.take_due(start + Duration::seconds(600)).iter().all(|r| r.name != "slow"));
+    }
+
+    #[test]
+    fn test_late_rules_do_not_catch_up() {
+        let start = Utc::now();
+        let mut queue = EvaluationQueue::default();
+        queue.sync(vec![create_test_rule("rule", 10)], start);
+        queue.take_due(start);
+
+        let late = start + Duration::seconds(95);
+        assert_eq!(queue.take_due(late).len(), 1);
+        assert_eq!(queue.next_due(), Some(late + Duration::seconds(10)));
+    }
+}
@@ -0,0 +1,487 @@
+//! Batched notification digests
+//!
+//! Lower-severity alerts are queued per notification channel instead of being
+//! sent one by one. When a channel's digest falls due, its pending events are
+//! collapsed into one group per rule (or per service, model or environment)
+//! and delivered as a single message.
+//!
+//! During a flood, alerts that would normally be sent immediately are queued
+//! the same way; see [`FloodGuard`].
+//!
+//! Incident tools open one incident per digest. The queue keeps each
+//! delivered digest until every alert in it has resolved, so that incident
+//! can be closed; digests delivered before a restart are forgotten and
+//! their incidents stay open.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::config::AlertGroupBy;
+use crate::models::alert::{AlertEvent, AlertRule, NotificationChannel, Severity};
+
+/// Alerts sharing a grouping key within a digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestGroup {
+    /// What the group's alerts have in common: a rule name, or a service,
+    /// model or environment
+    pub label: String,
+    /// First rule in the group
+    pub rule_id: Uuid,
+    pub rule_name: String,
+    pub severity: Severity,
+    pub count: usize,
+    pub first_triggered_at: DateTime<Utc>,
+    pub last_triggered_at: DateTime<Utc>,
+    pub latest_message: String,
+    pub latest_metric_value: f64,
+    pub threshold_value: f64,
+    pub service_name: Option<String>,
+    pub event_ids: Vec<Uuid>,
+    /// One entry per alert, oldest first
+    pub items: Vec<DigestItem>,
+    #[serde(skip)]
+    key: String,
+}
+
+/// Summary of one alert within a digest group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DigestItem {
+    pub rule_name: String,
+    pub message: String,
+    pub metric_value: f64,
+    pub triggered_at: DateTime<Utc>,
+}
+
+impl DigestItem {
+    fn new(rule: &AlertRule, event: &AlertEvent) -> Self {
+        Self {
+            rule_name: rule.name.clone(),
+            message: event.message.clone(),
+            metric_value: event.metric_value,
+            triggered_at: event.triggered_at,
+        }
+    }
+}
+
+/// A digest ready to be delivered to one channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Digest {
+    pub channel: NotificationChannel,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub groups: Vec<DigestGroup>,
+}
+
+impl Digest {
+    /// Total number of alert events in the digest
+    pub fn event_count(&self) -> usize {
+        self.groups.iter().map(|g| g.count).sum()
+    }
+
+    /// Highest severity among the grouped alerts
+    pub fn severity(&self) -> Severity {
+        self.groups
+            .iter()
+            .map(|g| g.severity)
+            .max_by_key(|s| severity_rank(*s))
+            .unwrap_or_default()
+    }
+
+    /// IDs of every event included in the digest
+    pub fn event_ids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.groups.iter().flat_map(|g| g.event_ids.iter().copied())
+    }
+}
+
+struct PendingDigest {
+    channel: NotificationChannel,
+    opened_at: DateTime<Utc>,
+    due_at: DateTime<Utc>,
+    groups: Vec<DigestGroup>,
+    /// Queued events that resolved before delivery
+    resolved: HashSet<Uuid>,
+}
+
+/// A delivered digest with alerts still firing
+struct OpenDigest {
+    digest: Digest,
+    firing: HashSet<Uuid>,
+}
+
+/// Pending digests keyed by notification channel
+#[derive(Default)]
+pub struct DigestQueue {
+    pending: Mutex<HashMap<String, PendingDigest>>,
+    open: Mutex<Vec<OpenDigest>>,
+    group_by: AlertGroupBy,
+}
+
+impl DigestQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Group alerts within each digest by `group_by` instead of by rule
+    pub fn with_group_by(mut self, group_by: AlertGroupBy) -> Self {
+        self.group_by = group_by;
+        self
+    }
+
+    /// Queue an event on each of the rule's channels
+    ///
+    /// A channel's digest is due `interval` after its first queued event, or
+    /// sooner if a later event has a shorter interval.
+    pub fn enqueue(&self, rule: &AlertRule, event: &AlertEvent, interval: Duration) {
+        let mut pending = self.pending.lock();
+        let (key, label) = group_key(self.group_by, rule);
+
+        for channel in &rule.notification_channels {
+            let digest = pending
+                .entry(channel_key(channel))
+                .or_insert_with(|| PendingDigest {
+                    channel: channel.clone(),
+                    opened_at: event.triggered_at,
+                    due_at: event.triggered_at + interval,
+                    groups: Vec::new(),
+                    resolved: HashSet::new(),
+                });
+
+            digest.due_at = digest.due_at.min(event.triggered_at + interval);
+
+            match digest.groups.iter_mut().find(|g| g.key == key) {
+                Some(group) => {
+                    group.count += 1;
+                    group.first_triggered_at = group.first_triggered_at.min(event.triggered_at);
+                    if event.triggered_at >= group.last_triggered_at {
+                        group.last_triggered_at = event.triggered_at;
+                        group.latest_message = event.message.clone();
+                        group.latest_metric_value = event.metric_value;
+                    }
+                    if severity_rank(event.severity) > severity_rank(group.severity) {
+                        group.severity = event.severity;
+                    }
+                    group.event_ids.push(event.id);
+                    let at = group.items.partition_point(|i| i.triggered_at <= event.triggered_at);
+                    group.items.insert(at, DigestItem::new(rule, event));
+                }
+                None => digest.groups.push(DigestGroup {
+                    label: label.clone(),
+                    rule_id: rule.id,
+                    rule_name: rule.name.clone(),
+                    severity: event.severity,
+                    count: 1,
+                    first_triggered_at: event.triggered_at,
+                    last_triggered_at: event.triggered_at,
+                    latest_message: event.message.clone(),
+                    latest_metric_value: event.metric_value,
+                    threshold_value: event.threshold_value,
+                    service_name: event.service_name.clone(),
+                    event_ids: vec![event.id],
+                    items: vec![DigestItem::new(rule, event)],
+                    key: key.clone(),
+                }),
+            }
+        }
+    }
+
+    /// Remove and return the digests that are due at `now`
+    ///
+    /// Digests with alerts still firing are kept open until they resolve;
+    /// see [`DigestQueue::resolve`].
+    pub fn take_due(&self, now: DateTime<Utc>) -> Vec<Digest> {
+        let mut pending = self.pending.lock();
+        let mut open = self.open.lock();
+
+        let due: Vec<String> = pending
+            .iter()
+            .filter(|(_, d)| d.due_at <= now)
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        due.into_iter()
+            .filter_map(|key| pending.remove(&key))
+            .map(|d| {
+                let digest = Digest {
+                    channel: d.channel,
+                    window_start: d.opened_at,
+                    window_end: now,
+                    groups: d.groups,
+                };
+                let firing: HashSet<Uuid> = digest.event_ids().filter(|id| !d.resolved.contains(id)).collect();
+                if !firing.is_empty() {
+                    open.push(OpenDigest {
+                        digest: digest.clone(),
+                        firing,
+                    });
+                }
+                digest
+            })
+            .collect()
+    }
+
+    /// Whether a delivered digest still has alerts firing
+    pub fn is_firing(&self, digest: &Digest) -> bool {
+        let channel = channel_key(&digest.channel);
+        self.open
+            .lock()
+            .iter()
+            .any(|o| o.digest.window_start == digest.window_start && channel_key(&o.digest.channel) == channel)
+    }
+
+    /// Mark a queued or delivered event resolved
+    ///
+    /// Returns `None` if the event isn't in a digest, else the delivered
+    /// digests it was the last firing alert of, whose incidents can now be
+    /// closed.
+    pub fn resolve(&self, event_id: Uuid) -> Option<Vec<Digest>> {
+        let mut digested = false;
+        for digest in self.pending.lock().values_mut() {
+            if digest.groups.iter().any(|g| g.event_ids.contains(&event_id)) {
+                digest.resolved.insert(event_id);
+                digested = true;
+            }
+        }
+
+        let mut closed = Vec::new();
+        self.open.lock().retain_mut(|open| {
+            if !open.firing.remove(&event_id) {
+                return true;
+            }
+            digested = true;
+            if open.firing.is_empty() {
+                closed.push(open.digest.clone());
+                return false;
+            }
+            true
+        });
+        digested.then_some(closed)
+    }
+
+    /// Number of events waiting across all channels
+    pub fn pending_events(&self) -> usize {
+        self.pending
+            .lock()
+            .values()
+            .flat_map(|d| d.groups.iter())
+            .map(|g| g.count)
+            .sum()
+    }
+}
+
+/// Holds back alerts that would be sent immediately once too many have been
+/// sent recently
+pub(crate) struct FloodGuard {
+    limit: usize,
+    window: Duration,
+    sent: Mutex<VecDeque<DateTime<Utc>>>,
+}
+
+impl FloodGuard {
+    /// Allow `limit` immediate alerts per `window`
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// How long to hold an alert triggering at `at` until the flood window
+    /// rolls over, or `None` to send it now, counting it against the limit
+    pub fn hold_for(&self, at: DateTime<Utc>) -> Option<Duration> {
+        let mut sent = self.sent.lock();
+        while sent.front().is_some_and(|t| *t + self.window <= at) {
+            sent.pop_front();
+        }
+
+        if sent.len() < self.limit {
+            sent.push_back(at);
+            return None;
+        }
+
+        sent.front().map(|oldest| *oldest + self.window - at)
+    }
+}
+
+/// Key and display label of the group an alert from `rule` belongs to
+fn group_key(group_by: AlertGroupBy, rule: &AlertRule) -> (String, String) {
+    let scope = |value: &Option<String>, all: &str| {
+        let label = value.clone().unwrap_or_else(|| all.to_string());
+        (format!("{:?}:{}", group_by, value.as_deref().unwrap_or("")), label)
+    };
+
+    match group_by {
+        AlertGroupBy::Rule => (rule.id.to_string(), rule.name.clone()),
+        AlertGroupBy::Service => scope(&rule.service_name, "All services"),
+        AlertGroupBy::Model => scope(&rule.model_name, "All models"),
+        AlertGroupBy::Environment => scope(&rule.environment, "All environments"),
+    }
+}
+
+fn channel_key(channel: &NotificationChannel) -> String {
+    serde_json::to_string(channel).unwrap_or_default()
+}
+
+fn severity_rank(severity: Severity) -> u8 {
+    match severity {
+        Severity::Info => 0,
+        Severity::Warning => 1,
+        Severity::Critical => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::{AlertStatus, Operator};
+
+    fn create_test_rule(name: &str, channels: Vec<NotificationChannel>) -> AlertRule {
+        AlertRule {
+            service_name: Some("agent".to_string()),
+            notification_channels: channels,
+            ..AlertRule::new(name, "error_rate", Operator::Gt, 5.0)
+        }
+    }
+
+    fn create_test_event(rule: &AlertRule, triggered_at: DateTime<Utc>, value: f64) -> AlertEvent {
+        AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            triggered_at,
+            resolved_at: None,
+            status: AlertStatus::Active,
+            severity: rule.severity,
+            message: format!("error_rate exceeded threshold (current value: {:.2})", value),
+            metric_value: value,
+            threshold_value: 5.0,
+            service_name: rule.service_name.clone(),
+            trace_ids: vec![],
+            notifications_sent: vec![],
+            metadata: serde_json::json!({}),
+            incident_id: None,
+            acknowledgement: None,
+        }
+    }
+
+    fn slack(webhook: &str) -> NotificationChannel {
+        NotificationChannel::Slack {
+            webhook_url: webhook.to_string(),
+            channel: None,
+        }
+    }
+
+    #[test]
+    fn test_digest_closes_once_its_alerts_resolve() {
+        let queue = DigestQueue::new();
+        let t0 = Utc::now();
+        let rule = create_test_rule("a", vec![slack("https://hooks/1")]);
+        let early = create_test_event(&rule, t0, 6.0);
+        let first = create_test_event(&rule, t0, 7.0);
+        let second = create_test_event(&rule, t0, 8.0);
+        for event in [&early, &first, &second] {
+            queue.enqueue(&rule, event, Duration::minutes(5));
+        }
+
+        // Resolved while still queued
+        assert_eq!(queue.resolve(early.id).map(|closed| closed.len()), Some(0));
+        let digest = queue.take_due(t0 + Duration::minutes(5)).pop().unwrap();
+        assert_eq!(digest.event_count(), 3);
+        assert!(queue.is_firing(&digest));
+
+        assert_eq!(queue.resolve(first.id).map(|closed| closed.len()), Some(0));
+        let closed = queue.resolve(second.id).unwrap();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].window_start, digest.window_start);
+        assert!(!queue.is_firing(&digest));
+
+        // Never digested
+        assert!(queue.resolve(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn test_groups_events_by_rule_per_channel() {
+        let queue = DigestQueue::new();
+        let t0 = Utc::now();
+        let a = create_test_rule("a", vec![slack("https://hooks/1")]);
+        let b = create_test_rule("b", vec![slack("https://hooks/1"), slack("https://hooks/2")]);
+
+        queue.enqueue(&a, &create_test_event(&a, t0, 6.0), Duration::minutes(5));
+        queue.enqueue(&a, &create_test_event(&a, t0 + Duration::minutes(1), 8.0), Duration::minutes(5));
+        queue.enqueue(&b, &create_test_event(&b, t0, 7.0), Duration::minutes(5));
+
+        assert_eq!(queue.pending_events(), 4);
+        assert!(queue.take_due(t0 + Duration::minutes(4)).is_empty());
+
+        let mut digests = queue.take_due(t0 + Duration::minutes(5));
+        digests.sort_by_key(|d| d.groups.len());
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests[0].event_count(), 1);
+        assert_eq!(digests[1].event_count(), 3);
+
+        let group_a = digests[1].groups.iter().find(|g| g.rule_id == a.id).unwrap();
+        assert_eq!(group_a.count, 2);
+        assert_eq!(group_a.latest_metric_value, 8.0);
+        assert_eq!(group_a.first_triggered_at, t0);
+
+        assert_eq!(queue.pending_events(), 0);
+    }
+
+    #[test]
+    fn test_shorter_interval_pulls_digest_forward() {
+        let queue = DigestQueue::new();
+        let t0 = Utc::now();
+        let mut info = create_test_rule("info", vec![slack("https://hooks/1")]);
+        info.severity = Severity::Info;
+        let warning = create_test_rule("warning", vec![slack("https://hooks/1")]);
+
+        queue.enqueue(&info, &create_test_event(&info, t0, 6.0), Duration::minutes(15));
+        queue.enqueue(&warning, &create_test_event(&warning, t0 + Duration::minutes(1), 6.0), Duration::minutes(5));
+
+        let digests = queue.take_due(t0 + Duration::minutes(6));
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].event_count(), 2);
+        assert_eq!(digests[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_groups_by_service_with_item_summaries() {
+        let queue = DigestQueue::new().with_group_by(AlertGroupBy::Service);
+        let t0 = Utc::now();
+        let errors = create_test_rule("errors", vec![slack("https://hooks/1")]);
+        let latency = create_test_rule("latency", vec![slack("https://hooks/1")]);
+        let mut other = create_test_rule("other", vec![slack("https://hooks/1")]);
+        other.service_name = Some("search".to_string());
+
+        queue.enqueue(&latency, &create_test_event(&latency, t0 + Duration::minutes(1), 7.0), Duration::minutes(5));
+        queue.enqueue(&errors, &create_test_event(&errors, t0, 6.0), Duration::minutes(5));
+        queue.enqueue(&other, &create_test_event(&other, t0, 9.0), Duration::minutes(5));
+
+        let digests = queue.take_due(t0 + Duration::minutes(5));
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].groups.len(), 2);
+
+        let agent = digests[0].groups.iter().find(|g| g.label == "agent").unwrap();
+        assert_eq!(agent.count, 2);
+        let items: Vec<&str> = agent.items.iter().map(|i| i.rule_name.as_str()).collect();
+        assert_eq!(items, vec!["errors", "latency"]);
+    }
+
+    #[test]
+    fn test_flood_guard_holds_alerts_past_limit() {
+        let guard = FloodGuard::new(2, Duration::minutes(5));
+        let t0 = Utc::now();
+
+        assert_eq!(guard.hold_for(t0), None);
+        assert_eq!(guard.hold_for(t0 + Duration::minutes(1)), None);
+        assert_eq!(guard.hold_for(t0 + Duration::minutes(2)), Some(Duration::minutes(3)));
+
+        // Once the first alert ages out of the window, one more goes through
+        assert_eq!(guard.hold_for(t0 + Duration::minutes(5)), None);
+        assert_eq!(guard.hold_for(t0 + Duration::minutes(5)), Some(Duration::minutes(1)));
+    }
+}
@@ -0,0 +1,108 @@
+//! Escalation policies
+//!
+//! A rule's `notification_channels` are notified as soon as it triggers. Its
+//! `escalation` steps follow while the alert stays unacknowledged, e.g. page
+//! PagerDuty after 15 minutes and email management after an hour:
+//!
+//! ```yaml
+//! escalation:
+//!   - after_minutes: 15
+//!     channels: [{type: pager_duty, routing_key: "..."}]
+//!   - after_minutes: 60
+//!     channels: [{type: email, to: [eng-managers@example.com]}]
+//! ```
+//!
+//! Acknowledging or resolving the alert stops escalation.
+
+use std::ops::Range;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::error::{Error, Result};
+use crate::models::alert::{AlertEvent, EscalationStep};
+
+/// Event metadata key holding how many escalation steps have been sent
+pub(crate) const ESCALATION_STEP_METADATA: &str = "escalation_step";
+
+/// Check that escalation steps have channels and come in increasing order
+pub fn validate_escalation(steps: &[EscalationStep]) -> Result<()> {
+    for (i, step) in steps.iter().enumerate() {
+        if step.channels.is_empty() {
+            return Err(Error::validation(format!(
+                "Escalation step {} has no channels",
+                i + 1
+            )));
+        }
+        if i > 0 && step.after_minutes <= steps[i - 1].after_minutes {
+            return Err(Error::validation(format!(
+                "Escalation step {} must come later than step {}",
+                i + 1,
+                i
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Steps an event has already escalated through, from its metadata
+pub(crate) fn steps_sent(event: &AlertEvent) -> usize {
+    event
+        .metadata
+        .get(ESCALATION_STEP_METADATA)
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize
+}
+
+/// Indexes of the steps that are due by `now` and haven't been sent
+pub(crate) fn due_steps(
+    steps: &[EscalationStep],
+    triggered_at: DateTime<Utc>,
+    sent: usize,
+    now: DateTime<Utc>,
+) -> Range<usize> {
+    let due = steps
+        .iter()
+        .take_while(|step| triggered_at + Duration::minutes(i64::from(step.after_minutes)) <= now)
+        .count();
+
+    sent.min(due)..due
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::NotificationChannel;
+
+    fn step(after_minutes: u32) -> EscalationStep {
+        EscalationStep {
+            after_minutes,
+            channels: vec![NotificationChannel::Email {
+                to: vec!["oncall@example.com".to_string()],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_due_steps() {
+        let steps = vec![step(15), step(60)];
+        let t0 = Utc::now();
+
+        assert_eq!(due_steps(&steps, t0, 0, t0 + Duration::minutes(5)), 0..0);
+        assert_eq!(due_steps(&steps, t0, 0, t0 + Duration::minutes(15)), 0..1);
+        assert_eq!(due_steps(&steps, t0, 1, t0 + Duration::minutes(30)), 1..1);
+        // A collector down through both steps sends them together
+        assert_eq!(due_steps(&steps, t0, 0, t0 + Duration::minutes(90)), 0..2);
+        assert_eq!(due_steps(&steps, t0, 2, t0 + Duration::minutes(90)), 2..2);
+    }
+
+    #[test]
+    fn test_validate_escalation() {
+        assert!(validate_escalation(&[step(15), step(60)]).is_ok());
+        assert!(validate_escalation(&[step(60), step(15)]).is_err());
+
+        let mut empty = step(15);
+        empty.channels.clear();
+        assert!(validate_escalation(&[empty]).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 //! Alert rule evaluation engine
 
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -8,14 +8,24 @@ use tokio::time::{interval, Interval};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::db::SpanRepository;
+use crate::annotations::AnnotationRepository;
+use crate::config::{AlertingConfig, DigestConfig};
+use crate::db::SpanStore;
+use crate::models::{WindowStats, UPSTREAM_INCIDENTS_METADATA};
 use crate::models::alert::{
-    AlertEvent, AlertRule, AlertRuleInput, AlertStatus, ConditionType, NotificationRecord,
-    Operator, Severity,
+    AlertEvent, AlertRule, AlertStatus, ConditionType, IncidentMetrics,
+    EscalationStep, NotificationRecord, Operator, RetryMessage, RuleLintReport, RuleState, Severity,
 };
 
-use super::notifier::NotificationSender;
+use super::anomaly::{self, Baseline, BASELINE_WINDOWS};
+use super::cadence::EvaluationQueue;
+use super::digest::{Digest, DigestQueue, FloodGuard};
+use super::escalation;
+use super::lint::{self, LintContext};
+use super::notifier::{Notification, NotificationResult, NotificationSender};
 use super::repository::AlertRepository;
+use super::retry::RetryPolicy;
+use super::template::{self, MessageContext};
 
 /// Metric value with metadata
 #[derive(Debug, Clone)]
@@ -25,48 +35,361 @@ pub struct MetricValue {
     pub timestamp: DateTime<Utc>,
 }
 
+/// An alert that has triggered and not yet resolved
+struct ActiveAlert {
+    event: AlertEvent,
+    /// Most severe metric value seen since the alert triggered
+    peak_value: f64,
+    /// The rule's escalation steps, refreshed each time it is evaluated
+    escalation: Vec<EscalationStep>,
+    /// Escalation steps already sent
+    escalated: usize,
+    /// Set once the alert is seen acknowledged, which stops escalation
+    acknowledged: bool,
+}
+
 /// Alert evaluator that periodically checks rules against metrics
 pub struct AlertEvaluator {
     /// Alert rule repository
     alert_repo: AlertRepository,
     /// Span repository for querying metrics
-    span_repo: SpanRepository,
+    span_repo: Arc<dyn SpanStore>,
     /// Notification sender
     notifier: NotificationSender,
+    /// How failed notifications are retried
+    retry_policy: RetryPolicy,
     /// State tracking for consecutive failures
     failure_counts: Arc<RwLock<HashMap<Uuid, i32>>>,
     /// Currently active alerts (rule_id -> event)
-    active_alerts: Arc<RwLock<HashMap<Uuid, AlertEvent>>>,
-    /// Default evaluation interval
-    default_interval_secs: u64,
+    active_alerts: Arc<RwLock<HashMap<Uuid, ActiveAlert>>>,
+    /// How often the rule list is reloaded to pick up changes
+    refresh_interval_secs: u64,
+    /// Per-severity digest intervals
+    digest_config: DigestConfig,
+    /// Alerts waiting to be delivered as digests
+    digests: DigestQueue,
+    /// Holds back immediate alerts during a flood, if enabled
+    flood_guard: Option<FloodGuard>,
+    /// Base URL for links back to AgentTrace in notifications
+    public_url: Option<String>,
+    /// Provider incidents to mark alerts with, when recorded
+    annotations: Option<AnnotationRepository>,
+    /// How close together events must fire to share an incident, if grouped
+    correlation_window: Option<Duration>,
+    /// Message template for rules without their own
+    message_template: Option<String>,
 }
 
+/// How often pending digests are checked for delivery
+const DIGEST_FLUSH_INTERVAL_SECS: u64 = 10;
+
+/// How often active alerts are checked for due escalation steps
+const ESCALATION_CHECK_INTERVAL_SECS: u64 = 30;
+
 impl AlertEvaluator {
     /// Create a new alert evaluator
-    pub fn new(alert_repo: AlertRepository, span_repo: SpanRepository) -> Self {
+    pub fn new(alert_repo: AlertRepository, span_repo: Arc<dyn SpanStore>) -> Self {
         Self {
             alert_repo,
             span_repo,
             notifier: NotificationSender::new(),
+            retry_policy: RetryPolicy::default(),
             failure_counts: Arc::new(RwLock::new(HashMap::new())),
             active_alerts: Arc::new(RwLock::new(HashMap::new())),
-            default_interval_secs: 60,
+            refresh_interval_secs: 60,
+            digest_config: DigestConfig::default(),
+            digests: DigestQueue::new(),
+            flood_guard: None,
+            public_url: None,
+            annotations: None,
+            correlation_window: None,
+            message_template: None,
+        }
+    }
+
+    /// Create an alert evaluator from alerting configuration
+    pub fn with_config(
+        alert_repo: AlertRepository,
+        span_repo: Arc<dyn SpanStore>,
+        config: &AlertingConfig,
+    ) -> Self {
+        Self {
+            refresh_interval_secs: config.check_interval_seconds.max(1),
+            digest_config: config.digest.clone(),
+            digests: DigestQueue::new().with_group_by(config.grouping.group_by),
+            flood_guard: config.grouping.flood_limit().and_then(|(limit, window)| {
+                Some(FloodGuard::new(limit, Duration::from_std(window).ok()?))
+            }),
+            public_url: config.public_url.clone(),
+            notifier: NotificationSender::new().with_public_url(config.public_url.clone()),
+            retry_policy: RetryPolicy::new(&config.delivery),
+            correlation_window: (config.correlation_window_minutes > 0)
+                .then(|| Duration::minutes(config.correlation_window_minutes as i64)),
+            message_template: config.message_template.clone(),
+            ..Self::new(alert_repo, span_repo)
         }
     }
 
+    /// Mark alerts that overlap a recorded provider incident
+    pub fn with_annotations(mut self, annotations: AnnotationRepository) -> Self {
+        self.annotations = Some(annotations);
+        self
+    }
+
     /// Start the evaluation loop
+    ///
+    /// Each rule is evaluated on its own `evaluation_interval_seconds`; the
+    /// rule list itself is reloaded every `check_interval_seconds`.
     pub async fn start(&self) {
         info!("Starting alert evaluator");
 
-        let mut ticker = interval(std::time::Duration::from_secs(self.default_interval_secs));
+        if let Err(e) = self.restore_state().await {
+            error!(error = %e, "Error restoring alert evaluator state");
+        }
+
+        let mut queue = EvaluationQueue::default();
+        let mut refresh_ticker = interval(std::time::Duration::from_secs(self.refresh_interval_secs));
+        let mut digest_ticker = interval(std::time::Duration::from_secs(DIGEST_FLUSH_INTERVAL_SECS));
+        let mut escalation_ticker =
+            interval(std::time::Duration::from_secs(ESCALATION_CHECK_INTERVAL_SECS));
 
         loop {
-            ticker.tick().await;
+            let idle = std::time::Duration::from_secs(self.refresh_interval_secs);
+            let wait = queue
+                .next_due()
+                .map_or(idle, |due| (due - Utc::now()).to_std().unwrap_or_default());
+
+            tokio::select! {
+                _ = refresh_ticker.tick() => {
+                    match self.alert_repo.list_enabled().await {
+                        Ok(rules) => queue.sync(rules, Utc::now()),
+                        Err(e) => error!(error = %e, "Error loading alert rules"),
+                    }
+                }
+                _ = tokio::time::sleep(wait) => {
+                    let now = Utc::now();
+                    for rule in queue.take_due(now) {
+                        self.evaluate_scheduled(&rule, now).await;
+                    }
+                }
+                _ = digest_ticker.tick() => {
+                    if let Err(e) = self.flush_digests().await {
+                        error!(error = %e, "Error delivering alert digests");
+                    }
+                }
+                _ = escalation_ticker.tick() => {
+                    if let Err(e) = self.escalate_due().await {
+                        error!(error = %e, "Error escalating alerts");
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pick up consecutive-failure counts and active alerts from before a
+    /// restart, so alerts still firing aren't sent again and alerts that
+    /// recover while the collector is down still resolve
+    pub async fn restore_state(&self) -> crate::error::Result<()> {
+        let states: HashMap<Uuid, RuleState> = self
+            .alert_repo
+            .list_rule_states()
+            .await?
+            .into_iter()
+            .map(|state| (state.rule_id, state))
+            .collect();
+
+        let mut counts = self.failure_counts.write().await;
+        for state in states.values().filter(|s| s.consecutive_failures > 0) {
+            counts.insert(state.rule_id, state.consecutive_failures);
+        }
+
+        // Events come newest first; only the latest open one per rule is tracked
+        let mut active = self.active_alerts.write().await;
+        for event in self.alert_repo.list_open_events().await? {
+            if active.contains_key(&event.rule_id) {
+                continue;
+            }
+            let peak_value = states
+                .get(&event.rule_id)
+                .and_then(|s| s.peak_value)
+                .unwrap_or(event.metric_value);
+            // Escalation steps are picked up when the rule is next evaluated
+            let escalated = escalation::steps_sent(&event);
+            let acknowledged = event.status == AlertStatus::Acknowledged;
+            active.insert(
+                event.rule_id,
+                ActiveAlert {
+                    event,
+                    peak_value,
+                    escalation: vec![],
+                    escalated,
+                    acknowledged,
+                },
+            );
+        }
+
+        info!(
+            pending_rules = counts.len(),
+            active_alerts = active.len(),
+            "Restored alert evaluator state"
+        );
+
+        Ok(())
+    }
+
+    /// Save a rule's state so it survives a restart
+    ///
+    /// Failing to save only risks a repeat notification after a restart, so it
+    /// doesn't hold up evaluation.
+    async fn save_state(&self, rule_id: Uuid, consecutive_failures: i32, peak_value: Option<f64>) {
+        let state = RuleState {
+            rule_id,
+            consecutive_failures,
+            peak_value,
+        };
+        if let Err(e) = self.alert_repo.save_rule_state(&state).await {
+            warn!(rule_id = %rule_id, error = %e, "Failed to save alert rule state");
+        }
+    }
+
+    /// Forget a rule's saved state once it is no longer failing
+    async fn clear_state(&self, rule_id: Uuid) {
+        if let Err(e) = self.alert_repo.clear_rule_state(rule_id).await {
+            warn!(rule_id = %rule_id, error = %e, "Failed to clear alert rule state");
+        }
+    }
+
+    /// Queue a message's failed notifications for retry
+    ///
+    /// The first attempt is already recorded, so failing to queue is logged
+    /// rather than failing the alert.
+    async fn queue_retries(&self, message: &RetryMessage, results: &[NotificationResult]) {
+        if let Err(e) = self
+            .retry_policy
+            .queue_failures(&self.alert_repo, message, results)
+            .await
+        {
+            warn!(error = %e, "Failed to queue notification retries");
+        }
+    }
+
+    /// Deliver any digests whose interval has elapsed
+    pub async fn flush_digests(&self) -> crate::error::Result<()> {
+        for digest in self.digests.take_due(Utc::now()) {
+            let result = self.notifier.send(&digest.channel, &Notification::digest(&digest)).await;
+
+            if !result.success {
+                warn!(
+                    channel = %result.channel_type,
+                    alerts = digest.event_count(),
+                    error = ?result.error,
+                    "Failed to deliver alert digest"
+                );
+            }
+            let results = std::slice::from_ref(&result);
+            self.queue_retries(&RetryMessage::Digest(digest.clone()), results).await;
+            self.record_digest_notification(&digest, result).await?;
+
+            // Every alert in it resolved while it was queued
+            if !self.digests.is_firing(&digest) {
+                self.resolve_digest(&digest).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close the incident a digest opened, once every alert in it resolved
+    async fn resolve_digest(&self, digest: &Digest) -> crate::error::Result<()> {
+        let notification = Notification::digest_resolution(digest);
+        // Channels without incidents aren't sent resolutions
+        if !notification.is_for(&digest.channel) {
+            return Ok(());
+        }
+        let result = self.notifier.send(&digest.channel, &notification).await;
+        self.queue_retries(&RetryMessage::DigestResolution(digest.clone()), std::slice::from_ref(&result))
+            .await;
+        self.record_digest_notification(digest, result).await
+    }
+
+    /// Record a digest's delivery on each event in it
+    async fn record_digest_notification(
+        &self,
+        digest: &Digest,
+        result: NotificationResult,
+    ) -> crate::error::Result<()> {
+        let record: NotificationRecord = result.into();
+        for event_id in digest.event_ids() {
+            self.alert_repo
+                .append_event_notifications(event_id, std::slice::from_ref(&record))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Notify the next escalation steps of active alerts that have gone
+    /// unacknowledged for long enough
+    pub async fn escalate_due(&self) -> crate::error::Result<()> {
+        let now = Utc::now();
+        let due: Vec<(AlertEvent, usize, Vec<EscalationStep>)> = {
+            let active = self.active_alerts.read().await;
+            active
+                .values()
+                .filter(|alert| !alert.acknowledged)
+                .filter_map(|alert| {
+                    let steps = escalation::due_steps(
+                        &alert.escalation,
+                        alert.event.triggered_at,
+                        alert.escalated,
+                        now,
+                    );
+                    (!steps.is_empty()).then(|| {
+                        (alert.event.clone(), steps.start, alert.escalation[steps].to_vec())
+                    })
+                })
+                .collect()
+        };
 
-            if let Err(e) = self.evaluate_all().await {
-                error!(error = %e, "Error evaluating alerts");
+        for (event, mut sent, steps) in due {
+            // Alerts are acknowledged through the API, so check the stored event
+            let stored = self.alert_repo.get_event(event.id).await?;
+            let still_active = stored.is_some_and(|e| e.status == AlertStatus::Active);
+            let rule = if still_active {
+                self.alert_repo.get_rule(event.rule_id).await?
+            } else {
+                None
+            };
+
+            let Some(rule) = rule else {
+                if let Some(alert) = self.active_alerts.write().await.get_mut(&event.rule_id) {
+                    alert.acknowledged = true;
+                }
+                continue;
+            };
+
+            for step in &steps {
+                sent += 1;
+                let results = self.notifier.send_all(&step.channels, &self.notifier.alert(&rule, &event)).await;
+                self.queue_retries(&RetryMessage::alert(&event), &results).await;
+                let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+                self.alert_repo.record_escalation(event.id, sent, &records).await?;
+
+                info!(
+                    rule_id = %rule.id,
+                    event_id = %event.id,
+                    step = sent,
+                    after_minutes = step.after_minutes,
+                    "Alert escalated"
+                );
+            }
+
+            let mut active = self.active_alerts.write().await;
+            if let Some(alert) = active.get_mut(&event.rule_id).filter(|a| a.event.id == event.id) {
+                alert.escalated = sent;
             }
         }
+
+        Ok(())
     }
 
     /// Evaluate all enabled rules
@@ -75,15 +398,31 @@ impl AlertEvaluator {
 
         debug!(count = rules.len(), "Evaluating alert rules");
 
+        let now = Utc::now();
         for rule in rules {
-            if let Err(e) = self.evaluate_rule(&rule).await {
-                error!(rule_id = %rule.id, error = %e, "Error evaluating rule");
-            }
+            self.evaluate_scheduled(&rule, now).await;
         }
 
         Ok(())
     }
 
+    /// Evaluate a rule unless it's outside its schedule, logging failures
+    async fn evaluate_scheduled(&self, rule: &AlertRule, now: DateTime<Utc>) {
+        // Trace rules are checked by the pipeline as spans arrive
+        if rule.condition_type == ConditionType::Trace {
+            return;
+        }
+
+        if rule.is_scheduled_off(now) {
+            debug!(rule_id = %rule.id, "Skipping rule outside its schedule");
+            return;
+        }
+
+        if let Err(e) = self.evaluate_rule(rule).await {
+            error!(rule_id = %rule.id, error = %e, "Error evaluating rule");
+        }
+    }
+
     /// Evaluate a single rule
     pub async fn evaluate_rule(&self, rule: &AlertRule) -> crate::error::Result<()> {
         // Calculate time window
@@ -100,20 +439,21 @@ impl AlertEvaluator {
             return Ok(());
         };
 
-        // Check if threshold is breached
-        let is_breached = rule.check(metric.value);
+        // Check if threshold is breached, or the value strays from its baseline
+        let (is_breached, baseline) = self.check_breach(rule, &metric, window_start).await?;
 
         debug!(
             rule_id = %rule.id,
             metric = rule.metric,
             value = metric.value,
             threshold = ?rule.threshold,
+            baseline_mean = baseline.map(|b| b.mean),
             breached = is_breached,
             "Evaluated rule"
         );
 
         if is_breached {
-            self.handle_breach(rule, metric).await?;
+            self.handle_breach(rule, metric, baseline).await?;
         } else {
             self.handle_recovery(rule).await?;
         }
@@ -124,6 +464,54 @@ impl AlertEvaluator {
         Ok(())
     }
 
+    /// Whether a rule's metric value breaches it, with the baseline it was
+    /// compared against for anomaly rules
+    ///
+    /// An anomaly rule with too little history to build a baseline never
+    /// breaches.
+    async fn check_breach(
+        &self,
+        rule: &AlertRule,
+        metric: &MetricValue,
+        window_start: DateTime<Utc>,
+    ) -> crate::error::Result<(bool, Option<Baseline>)> {
+        if rule.condition_type != ConditionType::Anomaly {
+            return Ok((rule.check(metric.value), None));
+        }
+
+        let baseline = self.get_baseline(rule, window_start).await?;
+        let is_breached = baseline.is_some_and(|b| b.is_anomalous(rule, metric.value));
+        Ok((is_breached, baseline))
+    }
+
+    /// Baseline of a rule's metric over the windows just before `before`
+    async fn get_baseline(
+        &self,
+        rule: &AlertRule,
+        before: DateTime<Utc>,
+    ) -> crate::error::Result<Option<Baseline>> {
+        let window = Duration::minutes(rule.window_minutes as i64);
+        let since = before - window * BASELINE_WINDOWS as i32;
+        let stats = self
+            .span_repo
+            .get_window_stats(rule.service_name.as_deref(), rule.model_name.as_deref(), since, before, window)
+            .await?;
+
+        let values: Vec<f64> = (0..BASELINE_WINDOWS)
+            .filter_map(|i| {
+                let start = since + window * i as i32;
+                let empty = WindowStats {
+                    window_start: start,
+                    ..WindowStats::default()
+                };
+                let stats = stats.iter().find(|s| s.window_start == start).unwrap_or(&empty);
+                window_metric(&rule.metric, stats, window)
+            })
+            .collect();
+
+        Ok(Baseline::from_values(&values))
+    }
+
     /// Get metric value for a rule
     async fn get_metric_value(
         &self,
@@ -362,7 +750,12 @@ impl AlertEvaluator {
     }
 
     /// Handle a threshold breach
-    async fn handle_breach(&self, rule: &AlertRule, metric: MetricValue) -> crate::error::Result<()> {
+    async fn handle_breach(
+        &self,
+        rule: &AlertRule,
+        metric: MetricValue,
+        baseline: Option<Baseline>,
+    ) -> crate::error::Result<()> {
         // Increment failure count
         let mut counts = self.failure_counts.write().await;
         let count = counts.entry(rule.id).or_insert(0);
@@ -376,32 +769,44 @@ impl AlertEvaluator {
         );
 
         // Check if we've hit the consecutive failure threshold
-        if *count < rule.consecutive_failures {
+        let count = *count;
+        if count < rule.consecutive_failures {
+            self.save_state(rule.id, count, None).await;
             return Ok(());
         }
 
-        // Check if alert is already active
-        let active = self.active_alerts.read().await;
-        if active.contains_key(&rule.id) {
+        // If the alert is already active, just track how bad it has got
+        let mut active = self.active_alerts.write().await;
+        if let Some(alert) = active.get_mut(&rule.id) {
+            alert.peak_value = rule.worse_value(alert.peak_value, metric.value);
+            alert.escalation = rule.escalation.clone();
+            let peak_value = alert.peak_value;
+            drop(active);
+            self.save_state(rule.id, count, Some(peak_value)).await;
             return Ok(());
         }
         drop(active);
 
+        let triggered_at = Utc::now();
+        let metadata = self.upstream_metadata(rule, triggered_at).await;
+
         // Create alert event
-        let event = AlertEvent {
+        let mut event = AlertEvent {
             id: Uuid::new_v4(),
             rule_id: rule.id,
-            triggered_at: Utc::now(),
+            triggered_at,
             resolved_at: None,
             status: AlertStatus::Active,
             severity: rule.severity,
-            message: self.format_alert_message(rule, &metric),
+            message: self.format_alert_message(rule, &metric, baseline.as_ref()),
             metric_value: metric.value,
-            threshold_value: rule.threshold.unwrap_or(0.0),
+            threshold_value: threshold_value(rule, baseline.as_ref()),
             service_name: rule.service_name.clone(),
             trace_ids: metric.sample_trace_ids,
             notifications_sent: vec![],
-            metadata: serde_json::json!({}),
+            metadata,
+            incident_id: None,
+            acknowledgement: None,
         };
 
         info!(
@@ -411,52 +816,219 @@ impl AlertEvaluator {
             "Alert triggered"
         );
 
-        // Store alert event
-        self.alert_repo.create_event(&event).await?;
+        // Store alert event, grouped with others firing for the service
+        match self.correlation_window {
+            Some(window) => self.alert_repo.create_correlated_event(&mut event, window).await?,
+            None => self.alert_repo.create_event(&event).await?,
+        }
 
         // Update last triggered time
         self.alert_repo.update_last_triggered(rule.id).await?;
 
-        // Send notifications
-        let results = self.notifier.send_all(rule, &event).await;
-
-        // Update event with notification records
-        let records: Vec<NotificationRecord> = results.into_iter().map(|r| r.into()).collect();
-        self.alert_repo.update_event_notifications(event.id, &records).await?;
+        // Send notifications now, or hold them for the severity's digest, or
+        // for a grouped digest while alerts are flooding in
+        let digest_interval = self.digest_interval(event.severity);
+        let flood_hold = match (&self.flood_guard, digest_interval) {
+            (Some(guard), None) => guard.hold_for(triggered_at),
+            _ => None,
+        };
+        match digest_interval.or(flood_hold) {
+            Some(interval) => {
+                debug!(
+                    rule_id = %rule.id,
+                    event_id = %event.id,
+                    flood = flood_hold.is_some(),
+                    "Queued alert for digest"
+                );
+                self.digests.enqueue(rule, &event, interval);
+            }
+            None => {
+                let notification = self.notifier.alert(rule, &event);
+                let results = self.notifier.send_all(&rule.notification_channels, &notification).await;
+                self.queue_retries(&RetryMessage::alert(&event), &results).await;
+
+                // Update event with notification records
+                let records: Vec<NotificationRecord> =
+                    results.into_iter().map(|r| r.into()).collect();
+                self.alert_repo.update_event_notifications(event.id, &records).await?;
+            }
+        }
 
         // Mark as active
         let mut active = self.active_alerts.write().await;
-        active.insert(rule.id, event);
+        let peak_value = event.metric_value;
+        active.insert(
+            rule.id,
+            ActiveAlert {
+                event,
+                peak_value,
+                escalation: rule.escalation.clone(),
+                escalated: 0,
+                acknowledged: false,
+            },
+        );
+        drop(active);
+        self.save_state(rule.id, count, Some(peak_value)).await;
 
         Ok(())
     }
 
+    /// Event metadata listing provider incidents that overlap the rule's
+    /// window, so responders check the provider before the agent
+    ///
+    /// Failing to look them up doesn't hold up the alert.
+    async fn upstream_metadata(&self, rule: &AlertRule, until: DateTime<Utc>) -> serde_json::Value {
+        let Some(annotations) = &self.annotations else {
+            return serde_json::json!({});
+        };
+
+        let since = until - Duration::minutes(rule.window_minutes as i64);
+        match annotations.provider_incidents(since, until).await {
+            Ok(incidents) if !incidents.is_empty() => {
+                serde_json::json!({ UPSTREAM_INCIDENTS_METADATA: incidents })
+            }
+            Ok(_) => serde_json::json!({}),
+            Err(e) => {
+                warn!(rule_id = %rule.id, error = %e, "Failed to look up provider incidents");
+                serde_json::json!({})
+            }
+        }
+    }
+
+    /// Digest interval for a severity, or `None` to send immediately
+    fn digest_interval(&self, severity: Severity) -> Option<Duration> {
+        self.digest_config
+            .interval_for(severity)
+            .and_then(|d| Duration::from_std(d).ok())
+    }
+
     /// Handle recovery (no longer breaching)
     async fn handle_recovery(&self, rule: &AlertRule) -> crate::error::Result<()> {
         // Reset failure count
-        let mut counts = self.failure_counts.write().await;
-        counts.remove(&rule.id);
+        let was_failing = self.failure_counts.write().await.remove(&rule.id).is_some();
+
+        // Check if there's an active alert to resolve; it stays active until
+        // the incident's metrics are in, so a failed query is retried on the
+        // next evaluation
+        let alert = self
+            .active_alerts
+            .read()
+            .await
+            .get(&rule.id)
+            .map(|alert| (alert.event.clone(), alert.peak_value));
+
+        let Some((mut event, peak_value)) = alert else {
+            if was_failing {
+                self.clear_state(rule.id).await;
+            }
+            return Ok(());
+        };
 
-        // Check if there's an active alert to resolve
-        let mut active = self.active_alerts.write().await;
-        if let Some(mut event) = active.remove(&rule.id) {
-            info!(
-                rule_id = %rule.id,
-                event_id = %event.id,
-                "Alert resolved"
-            );
+        let resolved_at = Utc::now();
+        let affected_traces = self
+            .span_repo
+            .get_metrics_summary(
+                rule.service_name.as_deref(),
+                rule.model_name.as_deref(),
+                event.triggered_at,
+                resolved_at,
+            )
+            .await?
+            .total_traces;
+
+        self.active_alerts.write().await.remove(&rule.id);
+        self.clear_state(rule.id).await;
+
+        info!(
+            rule_id = %rule.id,
+            event_id = %event.id,
+            "Alert resolved"
+        );
+
+        event.status = AlertStatus::Resolved;
+        event.resolved_at = Some(resolved_at);
+
+        let incident = IncidentMetrics {
+            duration_secs: (resolved_at - event.triggered_at).num_seconds(),
+            peak_value,
+            affected_traces,
+            search_url: self
+                .public_url
+                .as_deref()
+                .and_then(|base| span_search_url(base, rule, event.triggered_at, resolved_at)),
+        };
 
-            event.status = AlertStatus::Resolved;
-            event.resolved_at = Some(Utc::now());
+        self.alert_repo
+            .resolve_event(event.id, resolved_at, &incident)
+            .await?;
 
-            self.alert_repo.resolve_event(event.id).await?;
+        // Digested alerts were sent under their digest's key, whose incident
+        // is closed once every alert in the digest has resolved
+        if let Some(closed) = self.digests.resolve(event.id) {
+            for digest in &closed {
+                self.resolve_digest(digest).await?;
+            }
+        } else {
+            let notification = Notification::resolution(rule, &event, &incident);
+            let results = self.notifier.send_all(&rule.notification_channels, &notification).await;
+            self.queue_retries(&RetryMessage::resolution(&event), &results).await;
+
+            let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+            if !records.is_empty() {
+                self.alert_repo.append_event_notifications(event.id, &records).await?;
+            }
         }
 
         Ok(())
     }
 
-    /// Format alert message
-    fn format_alert_message(&self, rule: &AlertRule, metric: &MetricValue) -> String {
+    /// Format alert message from the rule's template, else the configured
+    /// default template, else the built-in message
+    ///
+    /// A template that fails to render falls back to the built-in message.
+    fn format_alert_message(
+        &self,
+        rule: &AlertRule,
+        metric: &MetricValue,
+        baseline: Option<&Baseline>,
+    ) -> String {
+        let message = self.builtin_alert_message(rule, metric, baseline);
+        let Some(template) = rule.message_template.as_deref().or(self.message_template.as_deref())
+        else {
+            return message;
+        };
+
+        let since = metric.timestamp - Duration::minutes(rule.window_minutes as i64);
+        let trace_link = self
+            .public_url
+            .as_deref()
+            .and_then(|base| span_search_url(base, rule, since, metric.timestamp));
+        let ctx = MessageContext {
+            rule,
+            value: metric.value,
+            threshold: threshold_value(rule, baseline),
+            message: &message,
+            trace_link: trace_link.as_deref(),
+            trace_ids: &metric.sample_trace_ids,
+        };
+
+        match template::render(template, &ctx) {
+            Ok(rendered) if !rendered.is_empty() => rendered,
+            Ok(_) => message,
+            Err(e) => {
+                warn!(rule_id = %rule.id, error = %e, "Falling back to built-in alert message");
+                message
+            }
+        }
+    }
+
+    /// Built-in alert message
+    fn builtin_alert_message(
+        &self,
+        rule: &AlertRule,
+        metric: &MetricValue,
+        baseline: Option<&Baseline>,
+    ) -> String {
         let operator_str = match rule.operator {
             Operator::Gt => "exceeded",
             Operator::Lt => "fell below",
@@ -473,6 +1045,19 @@ impl AlertEvaluator {
             (None, None) => String::new(),
         };
 
+        if let Some(baseline) = baseline {
+            return format!(
+                "{} is {:.1}σ {} its baseline of {:.2}{} (current value: {:.2}, limit {:.1}σ)",
+                rule.metric,
+                baseline.sigmas(metric.value).abs(),
+                if metric.value >= baseline.mean { "above" } else { "below" },
+                baseline.mean,
+                scope,
+                metric.value,
+                anomaly::sigma_limit(rule)
+            );
+        }
+
         format!(
             "{} {} threshold of {:.2}{} (current value: {:.2})",
             rule.metric,
@@ -494,7 +1079,7 @@ impl AlertEvaluator {
             return Ok(None);
         };
 
-        let is_breached = rule.check(metric.value);
+        let (is_breached, baseline) = self.check_breach(rule, &metric, window_start).await?;
 
         if !is_breached {
             return Ok(None);
@@ -507,15 +1092,198 @@ impl AlertEvaluator {
             resolved_at: None,
             status: AlertStatus::Active,
             severity: rule.severity,
-            message: self.format_alert_message(rule, &metric),
+            message: self.format_alert_message(rule, &metric, baseline.as_ref()),
             metric_value: metric.value,
-            threshold_value: rule.threshold.unwrap_or(0.0),
+            threshold_value: threshold_value(rule, baseline.as_ref()),
             service_name: rule.service_name.clone(),
             trace_ids: metric.sample_trace_ids,
             notifications_sent: vec![],
             metadata: serde_json::json!({"test": true}),
+            incident_id: None,
+            acknowledgement: None,
         };
 
         Ok(Some(event))
     }
+
+    /// Lint a rule against the other rules and the last `lookback_minutes` of data
+    pub async fn lint_rule(
+        &self,
+        rule: &AlertRule,
+        others: &[AlertRule],
+        lookback_minutes: i64,
+    ) -> crate::error::Result<RuleLintReport> {
+        let end = Utc::now();
+        let start = end - Duration::minutes(lookback_minutes);
+        let service = rule.service_name.as_deref();
+        let model = rule.model_name.as_deref();
+
+        let scope_span_count = self.span_repo.get_span_count(service, model, start, end).await?;
+
+        let lookback_total = match rule.metric.as_str() {
+            "cost_sum" | "cost_rate" => self.span_repo.get_cost_sum(service, model, start, end).await?,
+            "token_sum" => self
+                .span_repo
+                .get_token_sum(service, model, start, end)
+                .await?
+                .map(|t| t as f64),
+            "span_count" | "throughput" => Some(scope_span_count as f64),
+            _ => None,
+        };
+
+        let ctx = LintContext {
+            lookback_minutes,
+            scope_span_count: Some(scope_span_count),
+            lookback_total,
+        };
+
+        Ok(RuleLintReport {
+            rule_id: rule.id,
+            rule_name: rule.name.clone(),
+            checked_at: end,
+            lookback_minutes,
+            warnings: lint::lint_rule(rule, others, &ctx),
+        })
+    }
+}
+
+/// A rule's metric over one baseline window, computed as the live metric is;
+/// `None` where the live metric would have no value
+fn window_metric(metric: &str, stats: &WindowStats, window: Duration) -> Option<f64> {
+    let has_spans = stats.total > 0;
+    match metric {
+        "error_rate" => has_spans.then(|| stats.error_count as f64 / stats.total as f64 * 100.0),
+        "latency_p50" => stats.latency_p50,
+        "latency_p95" => stats.latency_p95,
+        "latency_p99" => stats.latency_p99,
+        "latency_avg" => stats.latency_avg,
+        "cost_sum" => has_spans.then_some(stats.cost_sum),
+        "cost_rate" => {
+            let hours = window.num_minutes() as f64 / 60.0;
+            (has_spans && hours > 0.0).then(|| stats.cost_sum / hours)
+        }
+        "token_sum" => has_spans.then_some(stats.token_sum as f64),
+        "span_count" => Some(stats.total as f64),
+        "throughput" => {
+            let minutes = window.num_minutes() as f64;
+            (minutes > 0.0).then(|| stats.total as f64 / minutes)
+        }
+        _ => None,
+    }
+}
+
+/// Threshold recorded on an event: the rule's own, or for anomaly rules the
+/// edge of the band around the baseline that the value crossed
+fn threshold_value(rule: &AlertRule, baseline: Option<&Baseline>) -> f64 {
+    let Some(baseline) = baseline else {
+        return rule.threshold.unwrap_or(0.0);
+    };
+
+    let band = anomaly::sigma_limit(rule) * baseline.stddev;
+    match rule.operator {
+        Operator::Lt | Operator::Lte => baseline.mean - band,
+        _ => baseline.mean + band,
+    }
+}
+
+/// Span search covering a rule's scope during `[since, until]`, narrowed to
+/// errors for error metrics and slowest-first for latency metrics
+fn span_search_url(
+    base: &str,
+    rule: &AlertRule,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Option<String> {
+    let mut url = url::Url::parse(base.trim_end_matches('/')).ok()?;
+    url.path_segments_mut().ok()?.pop_if_empty().extend(["api", "v1", "search"]);
+
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(service) = &rule.service_name {
+            query.append_pair("service", service);
+        }
+        if let Some(model) = &rule.model_name {
+            query.append_pair("model", model);
+        }
+        query.append_pair("since", &since.to_rfc3339_opts(SecondsFormat::Secs, true));
+        query.append_pair("until", &until.to_rfc3339_opts(SecondsFormat::Secs, true));
+
+        if rule.metric.starts_with("error_") {
+            query.append_pair("status", "error");
+        } else if rule.metric.starts_with("latency_") {
+            query.append_pair("sort_by", "duration_ms");
+        }
+    }
+
+    Some(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_window_metric() {
+        let window = Duration::minutes(30);
+        let stats = WindowStats {
+            total: 40,
+            error_count: 2,
+            latency_p95: Some(800.0),
+            cost_sum: 3.0,
+            token_sum: 5000,
+            ..WindowStats::default()
+        };
+        assert_eq!(window_metric("error_rate", &stats, window), Some(5.0));
+        assert_eq!(window_metric("latency_p95", &stats, window), Some(800.0));
+        assert_eq!(window_metric("cost_rate", &stats, window), Some(6.0));
+        assert_eq!(window_metric("token_sum", &stats, window), Some(5000.0));
+        assert_eq!(window_metric("throughput", &stats, window), Some(40.0 / 30.0));
+        assert_eq!(window_metric("unknown", &stats, window), None);
+
+        // A window without spans has no rate, but does have a count of zero
+        let empty = WindowStats::default();
+        assert_eq!(window_metric("error_rate", &empty, window), None);
+        assert_eq!(window_metric("cost_sum", &empty, window), None);
+        assert_eq!(window_metric("span_count", &empty, window), Some(0.0));
+    }
+
+    fn create_test_rule(metric: &str, service_name: Option<&str>, model_name: Option<&str>) -> AlertRule {
+        AlertRule {
+            service_name: service_name.map(String::from),
+            model_name: model_name.map(String::from),
+            ..AlertRule::new("Test rule", metric, Operator::Gt, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_span_search_url() {
+        let since = Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2026, 10, 16, 9, 5, 30).unwrap();
+
+        let rule = create_test_rule("error_rate", Some("support bot"), Some("gpt-4o"));
+        assert_eq!(
+            span_search_url("https://agenttrace.example.com/", &rule, since, until).as_deref(),
+            Some(
+                "https://agenttrace.example.com/api/v1/search?service=support+bot&model=gpt-4o\
+                 &since=2026-10-16T09%3A00%3A00Z&until=2026-10-16T09%3A05%3A30Z&status=error"
+            )
+        );
+
+        // Dashboards served under a path keep it
+        let rule = create_test_rule("latency_p95", None, None);
+        assert_eq!(
+            span_search_url("https://example.com/agenttrace", &rule, since, until).as_deref(),
+            Some(
+                "https://example.com/agenttrace/api/v1/search\
+                 ?since=2026-10-16T09%3A00%3A00Z&until=2026-10-16T09%3A05%3A30Z&sort_by=duration_ms"
+            )
+        );
+
+        let rule = create_test_rule("cost_sum", None, None);
+        let url = span_search_url("https://example.com", &rule, since, until).unwrap();
+        assert!(!url.contains("status=") && !url.contains("sort_by="));
+
+        assert!(span_search_url("not a url", &rule, since, until).is_none());
+    }
 }
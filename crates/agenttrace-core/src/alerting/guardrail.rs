@@ -0,0 +1,506 @@
+//! Per-trace guardrails
+//!
+//! Window metrics average a runaway agent loop into the rest of the traffic,
+//! so a single trace burning $50 can go unnoticed. Rules with
+//! `condition_type: trace` are instead checked by the pipeline as spans
+//! arrive: each span adds to its trace's running cost, token and span totals,
+//! kept in Redis so every collector instance sees the whole trace, and the
+//! rule fires the moment one trace crosses its threshold:
+//!
+//! ```yaml
+//! - name: Runaway trace
+//!   condition_type: trace
+//!   metric: trace_cost
+//!   operator: gt
+//!   threshold: 50
+//! ```
+//!
+//! A rule fires at most once per trace. Scoped rules are checked when a span
+//! from their service or model arrives, against the whole trace's totals.
+//! Nothing clears a trace's breach, so its alert resolves once the trace's
+//! totals expire, `guardrail_trace_ttl_seconds` after it fired.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use deadpool_redis::Pool;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::db::RedisPool;
+use crate::error::{Error, Result};
+use crate::models::alert::{
+    AlertEvent, AlertRule, AlertStatus, ConditionType, IncidentMetrics, NotificationRecord,
+    RetryMessage,
+};
+use crate::models::Span;
+
+use super::notifier::{Notification, NotificationResult, NotificationSender};
+use super::repository::AlertRepository;
+use super::retry::RetryPolicy;
+use super::template::{self, MessageContext};
+
+/// Metrics trace rules can watch
+pub const TRACE_METRICS: &[&str] = &["trace_cost", "trace_tokens", "trace_span_count"];
+
+/// Spans waiting to be added to their trace's totals before new ones are
+/// dropped
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Default time a trace's totals are kept after its last span
+const DEFAULT_TRACE_TTL_SECS: u64 = 3600;
+
+/// How often alerts whose trace totals expired are resolved
+const RESOLVE_INTERVAL_SECS: u64 = 60;
+
+/// Redis hash of a trace's running totals, followed by the trace ID
+const TOTALS_KEY_PREFIX: &str = "agenttrace:guardrail:trace:";
+
+/// Redis key set when a rule fires for a trace, followed by `{rule}:{trace}`
+const FIRED_KEY_PREFIX: &str = "agenttrace:guardrail:fired:";
+
+/// Running totals of one trace
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct TraceTotals {
+    pub cost_usd: f64,
+    pub tokens: i64,
+    pub spans: i64,
+}
+
+impl TraceTotals {
+    /// The total a trace metric watches
+    #[allow(clippy::cast_precision_loss)]
+    pub fn value(&self, metric: &str) -> Option<f64> {
+        match metric {
+            "trace_cost" => Some(self.cost_usd),
+            "trace_tokens" => Some(self.tokens as f64),
+            "trace_span_count" => Some(self.spans as f64),
+            _ => None,
+        }
+    }
+}
+
+/// A span's contribution to its trace's totals
+#[derive(Debug, Clone)]
+struct TraceIncrement {
+    trace_id: String,
+    service_name: String,
+    model_name: Option<String>,
+    cost_usd: f64,
+    tokens: i64,
+}
+
+impl TraceIncrement {
+    fn from_span(span: &Span) -> Self {
+        Self {
+            trace_id: span.trace_id.clone(),
+            service_name: span.service_name.clone(),
+            model_name: span.model_name.clone(),
+            cost_usd: span.cost_usd.unwrap_or(0.0),
+            tokens: i64::from(span.tokens_in.unwrap_or(0)) + i64::from(span.tokens_out.unwrap_or(0)),
+        }
+    }
+}
+
+/// Whether a trace rule's scope covers a span
+pub(crate) fn applies_to(rule: &AlertRule, service_name: &str, model_name: Option<&str>) -> bool {
+    rule.service_name.as_deref().is_none_or(|s| s == service_name)
+        && rule.model_name.as_deref().is_none_or(|m| model_name == Some(m))
+}
+
+/// Trace rules, backed by the database, with the queue spans are added to
+/// their traces' totals from
+#[derive(Clone)]
+pub struct GuardrailRegistry {
+    repo: AlertRepository,
+    redis: Pool,
+    notifier: Arc<NotificationSender>,
+    retry_policy: RetryPolicy,
+    rules: Arc<RwLock<Vec<AlertRule>>>,
+    queue: mpsc::Sender<TraceIncrement>,
+    receiver: Arc<Mutex<Option<mpsc::Receiver<TraceIncrement>>>>,
+    trace_ttl_secs: u64,
+}
+
+impl GuardrailRegistry {
+    /// Create an empty registry; call [`reload`](Self::reload) to load
+    /// rules and [`start`](Self::start) to check them
+    pub fn new(repo: AlertRepository, redis: &RedisPool) -> Self {
+        let (queue, receiver) = mpsc::channel(QUEUE_CAPACITY);
+
+        Self {
+            repo,
+            redis: redis.pool().clone(),
+            notifier: Arc::new(NotificationSender::new()),
+            retry_policy: RetryPolicy::default(),
+            rules: Arc::new(RwLock::new(Vec::new())),
+            queue,
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+            trace_ttl_secs: DEFAULT_TRACE_TTL_SECS,
+        }
+    }
+
+    /// Send notifications with this sender
+    pub fn with_notifier(mut self, notifier: NotificationSender) -> Self {
+        self.notifier = Arc::new(notifier);
+        self
+    }
+
+    /// Retry failed notifications with this policy
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Keep a trace's totals this long after its last span
+    pub fn with_trace_ttl(mut self, secs: u64) -> Self {
+        self.trace_ttl_secs = secs.max(1);
+        self
+    }
+
+    /// Reload enabled trace rules from the database, returning how many were
+    /// loaded
+    pub async fn reload(&self) -> Result<usize> {
+        let rules: Vec<AlertRule> = self
+            .repo
+            .list_enabled()
+            .await?
+            .into_iter()
+            .filter(|r| r.condition_type == ConditionType::Trace)
+            .collect();
+        let count = rules.len();
+
+        *self.rules.write() = rules;
+        Ok(count)
+    }
+
+    /// Queue a span to be added to its trace's totals, if there are any
+    /// trace rules; returns whether it was queued
+    ///
+    /// Never waits: when the queue is full the span is dropped and logged,
+    /// so Redis latency can't hold up ingestion.
+    pub fn observe(&self, span: &Span) -> bool {
+        if self.rules.read().is_empty() {
+            return false;
+        }
+
+        match self.queue.try_send(TraceIncrement::from_span(span)) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Dropping span from trace guardrails, queue is full or closed: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Spawn the worker that updates trace totals and fires rules
+    ///
+    /// Returns None if the worker was already started.
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        let mut receiver = self.receiver.lock().take()?;
+        let registry = self.clone();
+
+        Some(tokio::spawn(async move {
+            let mut resolve = tokio::time::interval(std::time::Duration::from_secs(RESOLVE_INTERVAL_SECS));
+
+            loop {
+                tokio::select! {
+                    increment = receiver.recv() => {
+                        let Some(increment) = increment else { break };
+                        if let Err(e) = registry.check(&increment).await {
+                            warn!(trace_id = %increment.trace_id, "Failed to check trace guardrails: {}", e);
+                        }
+                    }
+                    _ = resolve.tick() => {
+                        if let Err(e) = registry.resolve_expired(Utc::now()).await {
+                            warn!("Failed to resolve expired trace guardrail alerts: {}", e);
+                        }
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Resolve open trace alerts whose trace totals have expired, returning
+    /// how many were resolved
+    async fn resolve_expired(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut resolved = 0;
+
+        for event in self.repo.list_open_events().await? {
+            let Some(resolved_at) = expires_at(&event, self.trace_ttl_secs).filter(|at| *at <= now)
+            else {
+                continue;
+            };
+            let Some(rule) = self.repo.get_rule(event.rule_id).await? else {
+                continue;
+            };
+            if rule.condition_type != ConditionType::Trace {
+                continue;
+            }
+
+            let incident = incident_metrics(&event, resolved_at);
+            self.repo.resolve_event(event.id, resolved_at, &incident).await?;
+            let event = AlertEvent {
+                status: AlertStatus::Resolved,
+                resolved_at: Some(resolved_at),
+                ..event
+            };
+
+            let notification = Notification::resolution(&rule, &event, &incident);
+            let results = self.notifier.send_all(&rule.notification_channels, &notification).await;
+            self.queue_retries(&RetryMessage::resolution(&event), &results).await;
+            let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+            if !records.is_empty() {
+                self.repo.append_event_notifications(event.id, &records).await?;
+            }
+
+            debug!(rule_id = %rule.id, event_id = %event.id, "Trace guardrail alert resolved");
+            resolved += 1;
+        }
+
+        Ok(resolved)
+    }
+
+    /// Add a span to its trace's totals and fire the rules it pushed over
+    /// their threshold
+    async fn check(&self, increment: &TraceIncrement) -> Result<()> {
+        let totals = self.add(increment).await?;
+
+        let breached: Vec<(AlertRule, f64)> = self
+            .rules
+            .read()
+            .iter()
+            .filter(|r| applies_to(r, &increment.service_name, increment.model_name.as_deref()))
+            .filter_map(|r| {
+                let value = totals.value(&r.metric)?;
+                r.check(value).then(|| (r.clone(), value))
+            })
+            .collect();
+
+        for (rule, value) in breached {
+            if self.claim(rule.id, &increment.trace_id).await? {
+                self.fire(&rule, increment, &totals, value).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a span to its trace's running totals, returning the new totals
+    async fn add(&self, increment: &TraceIncrement) -> Result<TraceTotals> {
+        let mut conn = self.redis.get().await.map_err(|e| Error::Redis(e.to_string()))?;
+        let key = format!("{}{}", TOTALS_KEY_PREFIX, increment.trace_id);
+
+        let (cost_usd, tokens, spans): (f64, i64, i64) = redis::pipe()
+            .atomic()
+            .hincr(&key, "cost_usd", increment.cost_usd)
+            .hincr(&key, "tokens", increment.tokens)
+            .hincr(&key, "spans", 1)
+            .expire(&key, i64::try_from(self.trace_ttl_secs).unwrap_or(i64::MAX))
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| Error::Redis(e.to_string()))?;
+
+        Ok(TraceTotals { cost_usd, tokens, spans })
+    }
+
+    /// Mark a rule as fired for a trace, returning false if it already had,
+    /// here or on another collector
+    async fn claim(&self, rule_id: Uuid, trace_id: &str) -> Result<bool> {
+        let mut conn = self.redis.get().await.map_err(|e| Error::Redis(e.to_string()))?;
+        let set: Option<String> = redis::cmd("SET")
+            .arg(format!("{}{}:{}", FIRED_KEY_PREFIX, rule_id, trace_id))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.trace_ttl_secs)
+            .query_async(&mut conn)
+            .await
+            .map_err(|e| Error::Redis(e.to_string()))?;
+
+        Ok(set.is_some())
+    }
+
+    /// Record and send an alert for a trace over a rule's threshold
+    async fn fire(
+        &self,
+        rule: &AlertRule,
+        increment: &TraceIncrement,
+        totals: &TraceTotals,
+        value: f64,
+    ) -> Result<()> {
+        let threshold = rule.threshold.unwrap_or_default();
+        let trace_ids = vec![increment.trace_id.clone()];
+        let builtin = format!(
+            "Trace {} reached {} of {:.2} (limit {:.2}) after {} spans",
+            increment.trace_id, rule.metric, value, threshold, totals.spans
+        );
+        let message = match &rule.message_template {
+            Some(template) => {
+                let ctx = MessageContext {
+                    rule,
+                    value,
+                    threshold,
+                    message: &builtin,
+                    trace_link: None,
+                    trace_ids: &trace_ids,
+                };
+                template::render(template, &ctx).unwrap_or_else(|e| {
+                    warn!(rule_id = %rule.id, "{}", e);
+                    builtin.clone()
+                })
+            }
+            None => builtin.clone(),
+        };
+
+        let event = AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            triggered_at: Utc::now(),
+            resolved_at: None,
+            status: AlertStatus::Active,
+            severity: rule.severity,
+            message,
+            metric_value: value,
+            threshold_value: threshold,
+            service_name: Some(increment.service_name.clone()),
+            trace_ids,
+            notifications_sent: vec![],
+            metadata: serde_json::json!({
+                "trace_id": increment.trace_id,
+                "trace_cost_usd": totals.cost_usd,
+                "trace_tokens": totals.tokens,
+                "trace_span_count": totals.spans,
+            }),
+            incident_id: None,
+            acknowledgement: None,
+        };
+
+        info!(
+            rule_id = %rule.id,
+            event_id = %event.id,
+            trace_id = %increment.trace_id,
+            value,
+            "Trace guardrail triggered"
+        );
+
+        self.repo.create_event(&event).await?;
+        self.repo.update_last_triggered(rule.id).await?;
+
+        let notification = self.notifier.alert(rule, &event);
+        let results = self.notifier.send_all(&rule.notification_channels, &notification).await;
+        self.queue_retries(&RetryMessage::alert(&event), &results).await;
+        let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+        self.repo.update_event_notifications(event.id, &records).await?;
+
+        debug!(rule_id = %rule.id, notifications = records.len(), "Trace guardrail notified");
+        Ok(())
+    }
+
+    /// Queue a message's failed notifications for retry, logging rather
+    /// than failing if they can't be queued
+    async fn queue_retries(&self, message: &RetryMessage, results: &[NotificationResult]) {
+        if let Err(e) = self.retry_policy.queue_failures(&self.repo, message, results).await {
+            warn!(error = %e, "Failed to queue notification retries");
+        }
+    }
+}
+
+/// When a trace alert's totals expire, and with them the alert
+///
+/// Alerts from window rules expire too, so callers check the rule.
+fn expires_at(event: &AlertEvent, trace_ttl_secs: u64) -> Option<DateTime<Utc>> {
+    let ttl = Duration::seconds(i64::try_from(trace_ttl_secs).unwrap_or(i64::MAX / 1000));
+    event.triggered_at.checked_add_signed(ttl)
+}
+
+/// Incident metrics of a trace alert; the breach was its one trace's value
+fn incident_metrics(event: &AlertEvent, resolved_at: DateTime<Utc>) -> IncidentMetrics {
+    IncidentMetrics {
+        duration_secs: (resolved_at - event.triggered_at).num_seconds(),
+        peak_value: event.metric_value,
+        affected_traces: event.trace_ids.len() as i64,
+        search_url: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::{Operator, Severity};
+
+    fn create_test_rule(service_name: Option<&str>, model_name: Option<&str>) -> AlertRule {
+        AlertRule {
+            service_name: service_name.map(String::from),
+            model_name: model_name.map(String::from),
+            condition_type: ConditionType::Trace,
+            severity: Severity::Critical,
+            ..AlertRule::new("Runaway trace", "trace_cost", Operator::Gt, 50.0)
+        }
+    }
+
+    #[test]
+    fn test_rule_scope() {
+        let unscoped = create_test_rule(None, None);
+        let planner = create_test_rule(Some("planner"), None);
+        let gpt4o = create_test_rule(None, Some("gpt-4o"));
+
+        assert!(applies_to(&unscoped, "planner", None));
+        assert!(applies_to(&planner, "planner", Some("gpt-4o")));
+        assert!(!applies_to(&planner, "executor", None));
+        assert!(applies_to(&gpt4o, "executor", Some("gpt-4o")));
+        assert!(!applies_to(&gpt4o, "executor", None));
+    }
+
+    #[test]
+    fn test_totals_by_metric() {
+        let totals = TraceTotals {
+            cost_usd: 51.5,
+            tokens: 1_200_000,
+            spans: 340,
+        };
+        let rule = create_test_rule(None, None);
+
+        assert_eq!(totals.value("trace_cost"), Some(51.5));
+        assert_eq!(totals.value("trace_span_count"), Some(340.0));
+        assert_eq!(totals.value("cost_sum"), None);
+        assert!(rule.check(totals.value(&rule.metric).unwrap()));
+    }
+
+    #[test]
+    fn test_alerts_resolve_when_totals_expire() {
+        let triggered_at = Utc::now() - Duration::minutes(90);
+        let event = AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: Uuid::new_v4(),
+            triggered_at,
+            resolved_at: None,
+            status: AlertStatus::Active,
+            severity: Severity::Critical,
+            message: "Trace reached trace_cost of 51.50".to_string(),
+            metric_value: 51.5,
+            threshold_value: 50.0,
+            service_name: Some("planner".to_string()),
+            trace_ids: vec!["0af7651916cd43dd8448eb211c80319c".to_string()],
+            notifications_sent: vec![],
+            metadata: serde_json::json!({}),
+            incident_id: None,
+            acknowledgement: None,
+        };
+
+        let expired = expires_at(&event, 3600).unwrap();
+        assert_eq!(expired, triggered_at + Duration::hours(1));
+        assert!(expired <= Utc::now());
+        assert!(expires_at(&event, 4 * 3600).unwrap() > Utc::now());
+        assert!(expires_at(&event, u64::MAX).is_none());
+
+        let incident = incident_metrics(&event, expired);
+        assert_eq!(incident.duration_secs, 3600);
+        assert_eq!(incident.peak_value, 51.5);
+        assert_eq!(incident.affected_traces, 1);
+    }
+}
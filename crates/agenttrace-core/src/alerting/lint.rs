@@ -0,0 +1,431 @@
+//! Static and data-driven checks for alert rules
+//!
+//! Linting catches rules that can never fire, leave gaps between evaluations,
+//! watch scopes that produce no data, or duplicate other rules.
+
+use crate::models::alert::{AlertRule, ConditionType, LintLevel, Operator, RuleLintWarning};
+
+use super::guardrail::TRACE_METRICS;
+
+/// Metrics understood by the evaluator
+pub const SUPPORTED_METRICS: &[&str] = &[
+    "error_rate",
+    "latency_p50",
+    "latency_p95",
+    "latency_p99",
+    "latency_avg",
+    "cost_sum",
+    "cost_rate",
+    "token_sum",
+    "span_count",
+    "throughput",
+];
+
+/// Recent data gathered for a rule's scope
+#[derive(Debug, Clone, Default)]
+pub struct LintContext {
+    /// How far back data was inspected, in minutes
+    pub lookback_minutes: i64,
+    /// Number of spans matching the rule's scope over the lookback period
+    pub scope_span_count: Option<i64>,
+    /// Total of the rule's additive metric over the lookback period
+    /// (cost for cost metrics, tokens for token_sum, spans for counts)
+    pub lookback_total: Option<f64>,
+}
+
+/// Lint a rule, comparing it against the other configured rules and recent data
+pub fn lint_rule(rule: &AlertRule, others: &[AlertRule], ctx: &LintContext) -> Vec<RuleLintWarning> {
+    let mut warnings = Vec::new();
+
+    let metrics = if rule.condition_type == ConditionType::Trace {
+        TRACE_METRICS
+    } else {
+        SUPPORTED_METRICS
+    };
+    if !metrics.contains(&rule.metric.as_str()) {
+        warnings.push(warning(
+            "unknown_metric",
+            LintLevel::Warning,
+            format!(
+                "Metric '{}' is not supported for {:?} rules",
+                rule.metric, rule.condition_type
+            ),
+            format!("Use one of: {}", metrics.join(", ")),
+        ));
+    }
+
+    match rule.condition_type {
+        ConditionType::Threshold => check_threshold(rule, ctx, &mut warnings),
+        ConditionType::Anomaly => check_sigmas(rule, &mut warnings),
+        ConditionType::RateChange | ConditionType::Absence => {
+            warnings.push(warning(
+                "unsupported_condition",
+                LintLevel::Warning,
+                format!(
+                    "Condition type {:?} is evaluated as a plain threshold",
+                    rule.condition_type
+                ),
+                "Use condition_type 'threshold' or 'anomaly', or expect threshold semantics"
+                    .to_string(),
+            ));
+            check_threshold(rule, ctx, &mut warnings);
+        }
+        ConditionType::Trace => check_trace_limit(rule, &mut warnings),
+    }
+
+    // Trace rules are checked per span, not per window
+    if rule.condition_type != ConditionType::Trace
+        && i64::from(rule.window_minutes) * 60 < i64::from(rule.evaluation_interval_seconds)
+    {
+        warnings.push(warning(
+            "window_shorter_than_interval",
+            LintLevel::Warning,
+            format!(
+                "Window of {}m is shorter than the {}s evaluation interval, so data between evaluations is never checked",
+                rule.window_minutes, rule.evaluation_interval_seconds
+            ),
+            format!(
+                "Increase window_minutes to at least {} or lower evaluation_interval_seconds",
+                (rule.evaluation_interval_seconds + 59) / 60
+            ),
+        ));
+    }
+
+    if ctx.scope_span_count == Some(0) {
+        warnings.push(warning(
+            "empty_scope",
+            LintLevel::Warning,
+            format!(
+                "No spans matched {} in the last {}",
+                describe_scope(rule),
+                format_lookback(ctx.lookback_minutes)
+            ),
+            "Check the service_name/model_name spelling or remove the scope".to_string(),
+        ));
+    }
+
+    for other in others.iter().filter(|o| o.id != rule.id && o.metric == rule.metric) {
+        if !same_direction(rule.operator, other.operator) {
+            continue;
+        }
+
+        if scope_eq(rule, other) {
+            let duplicate = rule.operator == other.operator
+                && rule.threshold == other.threshold
+                && rule.window_minutes == other.window_minutes;
+
+            let mut finding = if duplicate {
+                warning(
+                    "duplicate_rule",
+                    LintLevel::Warning,
+                    format!("Rule '{}' checks the same condition on the same scope", other.name),
+                    "Delete one of the rules or give them different severities".to_string(),
+                )
+            } else {
+                warning(
+                    "overlapping_rule",
+                    LintLevel::Info,
+                    format!(
+                        "Rule '{}' also watches {} on the same scope with a different threshold or window",
+                        other.name, rule.metric
+                    ),
+                    "Make sure both are intended (e.g., warning and critical tiers)".to_string(),
+                )
+            };
+            finding.related_rule_id = Some(other.id);
+            warnings.push(finding);
+        } else if scope_covers(other, rule) {
+            let mut finding = warning(
+                "overlapping_rule",
+                LintLevel::Info,
+                format!(
+                    "Rule '{}' watches {} on a broader scope that includes this one",
+                    other.name, rule.metric
+                ),
+                "Expect both rules to fire for the same incident".to_string(),
+            );
+            finding.related_rule_id = Some(other.id);
+            warnings.push(finding);
+        }
+    }
+
+    warnings
+}
+
+/// Check the number of standard deviations an anomaly rule allows
+fn check_sigmas(rule: &AlertRule, warnings: &mut Vec<RuleLintWarning>) {
+    match rule.threshold {
+        Some(sigmas) if sigmas <= 0.0 => warnings.push(warning(
+            "invalid_sigmas",
+            LintLevel::Warning,
+            format!("Anomaly threshold {} is not a positive number of standard deviations", sigmas),
+            "Set threshold to the sigmas that count as anomalous, e.g. 3".to_string(),
+        )),
+        Some(sigmas) if sigmas < 2.0 => warnings.push(warning(
+            "noisy_anomaly",
+            LintLevel::Info,
+            format!("Values {}σ from the baseline are common and will fire often", sigmas),
+            "Use a threshold of 2-4 standard deviations".to_string(),
+        )),
+        _ => {}
+    }
+}
+
+/// Check that a trace rule has a limit its totals can grow past
+fn check_trace_limit(rule: &AlertRule, warnings: &mut Vec<RuleLintWarning>) {
+    if rule.threshold.is_none() {
+        warnings.push(warning(
+            "missing_threshold",
+            LintLevel::Warning,
+            "Rule has no threshold and will never trigger".to_string(),
+            "Set a threshold value".to_string(),
+        ));
+    }
+
+    if !matches!(rule.operator, Operator::Gt | Operator::Gte) {
+        warnings.push(warning(
+            "trace_limit_direction",
+            LintLevel::Warning,
+            format!(
+                "Trace totals only grow, so '{}' fires on a trace's first span or never",
+                operator_symbol(rule.operator)
+            ),
+            "Use operator 'gt' or 'gte' for trace rules".to_string(),
+        ));
+    }
+}
+
+/// Check for thresholds that cannot be reached by the metric
+fn check_threshold(rule: &AlertRule, ctx: &LintContext, warnings: &mut Vec<RuleLintWarning>) {
+    let Some(threshold) = rule.threshold else {
+        warnings.push(warning(
+            "missing_threshold",
+            LintLevel::Warning,
+            "Rule has no threshold and will never trigger".to_string(),
+            "Set a threshold value".to_string(),
+        ));
+        return;
+    };
+
+    let upper_bound = if rule.metric == "error_rate" { Some(100.0) } else { None };
+
+    let unreachable = match rule.operator {
+        Operator::Lt => threshold <= 0.0,
+        Operator::Gt => upper_bound.is_some_and(|max| threshold >= max),
+        Operator::Gte | Operator::Eq => {
+            threshold < 0.0 || upper_bound.is_some_and(|max| threshold > max)
+        }
+        Operator::Lte => threshold < 0.0,
+        Operator::Ne => false,
+    };
+
+    if unreachable {
+        warnings.push(warning(
+            "unreachable_threshold",
+            LintLevel::Warning,
+            format!(
+                "{} can never be {} {}",
+                rule.metric,
+                operator_symbol(rule.operator),
+                threshold
+            ),
+            "Pick a threshold inside the metric's possible range".to_string(),
+        ));
+        return;
+    }
+
+    // For additive metrics the total over the lookback period bounds every window
+    // inside it, so a threshold above that total was never reached recently.
+    let Some(total) = ctx.lookback_total else {
+        return;
+    };
+    let window_minutes = f64::from(rule.window_minutes.max(1));
+    let max_observed = match rule.metric.as_str() {
+        "cost_sum" | "token_sum" | "span_count" => total,
+        "cost_rate" => total / (window_minutes / 60.0),
+        "throughput" => total / window_minutes,
+        _ => return,
+    };
+
+    let never_reached = match rule.operator {
+        Operator::Gt => threshold >= max_observed,
+        Operator::Gte => threshold > max_observed,
+        _ => false,
+    };
+
+    if never_reached {
+        warnings.push(warning(
+            "threshold_not_reached",
+            LintLevel::Info,
+            format!(
+                "{} never exceeded {:.2} in any {}m window over the last {} (at most {:.2})",
+                rule.metric,
+                threshold,
+                rule.window_minutes,
+                format_lookback(ctx.lookback_minutes),
+                max_observed
+            ),
+            "Lower the threshold if the rule is meant to catch regressions at current volume".to_string(),
+        ));
+    }
+}
+
+fn warning(code: &str, level: LintLevel, message: String, recommendation: String) -> RuleLintWarning {
+    RuleLintWarning {
+        code: code.to_string(),
+        level,
+        message,
+        recommendation,
+        related_rule_id: None,
+    }
+}
+
+fn operator_symbol(op: Operator) -> &'static str {
+    match op {
+        Operator::Gt => ">",
+        Operator::Lt => "<",
+        Operator::Eq => "==",
+        Operator::Gte => ">=",
+        Operator::Lte => "<=",
+        Operator::Ne => "!=",
+    }
+}
+
+/// Whether two operators alert in the same direction
+fn same_direction(a: Operator, b: Operator) -> bool {
+    let direction = |op: Operator| match op {
+        Operator::Gt | Operator::Gte => 1,
+        Operator::Lt | Operator::Lte => -1,
+        Operator::Eq | Operator::Ne => 0,
+    };
+    direction(a) == direction(b)
+}
+
+fn scope_eq(a: &AlertRule, b: &AlertRule) -> bool {
+    a.service_name == b.service_name
+        && a.model_name == b.model_name
+        && a.environment == b.environment
+}
+
+/// Whether `outer`'s scope includes everything in `inner`'s scope
+fn scope_covers(outer: &AlertRule, inner: &AlertRule) -> bool {
+    fn covers(outer: &Option<String>, inner: &Option<String>) -> bool {
+        outer.is_none() || outer == inner
+    }
+
+    covers(&outer.service_name, &inner.service_name)
+        && covers(&outer.model_name, &inner.model_name)
+        && covers(&outer.environment, &inner.environment)
+}
+
+fn describe_scope(rule: &AlertRule) -> String {
+    match (&rule.service_name, &rule.model_name) {
+        (Some(s), Some(m)) => format!("service '{}' with model '{}'", s, m),
+        (Some(s), None) => format!("service '{}'", s),
+        (None, Some(m)) => format!("model '{}'", m),
+        (None, None) => "the rule's scope".to_string(),
+    }
+}
+
+fn format_lookback(minutes: i64) -> String {
+    if minutes % (24 * 60) == 0 {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes % 60 == 0 {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_rule(metric: &str, operator: Operator, threshold: f64) -> AlertRule {
+        AlertRule {
+            service_name: Some("agent".to_string()),
+            ..AlertRule::new(format!("{} rule", metric), metric, operator, threshold)
+        }
+    }
+
+    fn codes(warnings: &[RuleLintWarning]) -> Vec<&str> {
+        warnings.iter().map(|w| w.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_healthy_rule_has_no_warnings() {
+        let rule = create_test_rule("error_rate", Operator::Gt, 5.0);
+        let warnings = lint_rule(&rule, &[], &LintContext::default());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_error_rate() {
+        let rule = create_test_rule("error_rate", Operator::Gt, 100.0);
+        let warnings = lint_rule(&rule, &[], &LintContext::default());
+        assert_eq!(codes(&warnings), vec!["unreachable_threshold"]);
+    }
+
+    #[test]
+    fn test_threshold_above_lookback_total() {
+        let rule = create_test_rule("cost_sum", Operator::Gt, 50.0);
+        let ctx = LintContext {
+            lookback_minutes: 7 * 24 * 60,
+            scope_span_count: Some(10),
+            lookback_total: Some(12.5),
+        };
+        let warnings = lint_rule(&rule, &[], &ctx);
+        assert_eq!(codes(&warnings), vec!["threshold_not_reached"]);
+    }
+
+    #[test]
+    fn test_trace_rule_metrics_and_direction() {
+        let mut rule = create_test_rule("trace_cost", Operator::Gt, 50.0);
+        rule.condition_type = ConditionType::Trace;
+        rule.window_minutes = 1;
+        rule.evaluation_interval_seconds = 300;
+        assert!(lint_rule(&rule, &[], &LintContext::default()).is_empty());
+
+        rule.metric = "cost_sum".to_string();
+        rule.operator = Operator::Lt;
+        let warnings = lint_rule(&rule, &[], &LintContext::default());
+        assert_eq!(codes(&warnings), vec!["unknown_metric", "trace_limit_direction"]);
+    }
+
+    #[test]
+    fn test_window_shorter_than_interval_and_empty_scope() {
+        let mut rule = create_test_rule("latency_p99", Operator::Gt, 1000.0);
+        rule.window_minutes = 1;
+        rule.evaluation_interval_seconds = 300;
+        let ctx = LintContext {
+            lookback_minutes: 7 * 24 * 60,
+            scope_span_count: Some(0),
+            lookback_total: None,
+        };
+        let warnings = lint_rule(&rule, &[], &ctx);
+        assert_eq!(codes(&warnings), vec!["window_shorter_than_interval", "empty_scope"]);
+    }
+
+    #[test]
+    fn test_duplicate_and_overlapping_rules() {
+        let rule = create_test_rule("error_rate", Operator::Gt, 5.0);
+
+        let mut duplicate = create_test_rule("error_rate", Operator::Gt, 5.0);
+        duplicate.name = "copy".to_string();
+
+        let mut broader = create_test_rule("error_rate", Operator::Gte, 10.0);
+        broader.service_name = None;
+
+        let opposite = create_test_rule("error_rate", Operator::Lt, 1.0);
+
+        let warnings = lint_rule(
+            &rule,
+            &[rule.clone(), duplicate.clone(), broader.clone(), opposite],
+            &LintContext::default(),
+        );
+
+        assert_eq!(codes(&warnings), vec!["duplicate_rule", "overlapping_rule"]);
+        assert_eq!(warnings[0].related_rule_id, Some(duplicate.id));
+        assert_eq!(warnings[1].related_rule_id, Some(broader.id));
+    }
+}
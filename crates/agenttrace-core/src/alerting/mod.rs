@@ -1,11 +1,28 @@
 //! Alerting system for AgentTrace
 //!
-//! Provides cost threshold alerts, error rate monitoring, and notification delivery.
+//! Provides cost threshold alerts, error rate monitoring, per-trace guardrails
+//! checked as spans arrive, and notification delivery with retries.
 
+mod anomaly;
+mod cadence;
+mod digest;
+mod escalation;
 mod evaluator;
+mod guardrail;
+mod lint;
 mod notifier;
 mod repository;
+mod retry;
+mod rule_file;
+mod template;
 
+pub use digest::{Digest, DigestGroup, DigestItem, DigestQueue};
+pub use escalation::validate_escalation;
 pub use evaluator::AlertEvaluator;
-pub use notifier::{NotificationSender, NotificationResult};
+pub use guardrail::{GuardrailRegistry, TRACE_METRICS};
+pub use lint::{lint_rule, LintContext, SUPPORTED_METRICS};
+pub use notifier::{Notification, NotificationSender, NotificationResult};
 pub use repository::AlertRepository;
+pub use retry::{DeliveryFailure, NotificationRetrier, RetryPolicy};
+pub use rule_file::{plan_rule_changes, RuleChange, RuleFile};
+pub use template::validate_template;
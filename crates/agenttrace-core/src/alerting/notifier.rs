@@ -6,16 +6,48 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
-use crate::models::alert::{AlertEvent, AlertRule, NotificationChannel, NotificationRecord, Severity};
+use super::digest::{Digest, DigestGroup};
+use super::retry::DeliveryFailure;
+use crate::models::alert::{
+    Acknowledgement, AlertEvent, AlertRule, AlertStatus, IncidentMetrics, NotificationChannel,
+    NotificationRecord, Operator, Severity,
+};
+use crate::models::budget::BudgetAlert;
+use crate::models::slo::{format_minutes, SloBurnAlert, SloResolution};
 
 /// Result of sending a notification
 #[derive(Debug, Clone)]
 pub struct NotificationResult {
+    /// Channel the notification was sent to
+    pub channel: NotificationChannel,
     pub channel_type: String,
     pub success: bool,
     pub error: Option<String>,
     pub sent_at: DateTime<Utc>,
+    /// Whether a failed send might succeed if tried again later
+    pub retryable: bool,
+    /// HTTP status of a service that refused the notification
+    pub status: Option<u16>,
+}
+
+impl NotificationResult {
+    fn new(
+        channel: &NotificationChannel,
+        sent_at: DateTime<Utc>,
+        result: Result<(), NotificationError>,
+    ) -> Self {
+        Self {
+            channel: channel.clone(),
+            channel_type: channel.channel_type().to_string(),
+            success: result.is_ok(),
+            retryable: result.as_ref().is_err_and(NotificationError::is_retryable),
+            status: result.as_ref().err().and_then(NotificationError::status),
+            error: result.err().map(|e| e.to_string()),
+            sent_at,
+        }
+    }
 }
 
 impl From<NotificationResult> for NotificationRecord {
@@ -25,166 +57,600 @@ impl From<NotificationResult> for NotificationRecord {
             sent_at: result.sent_at,
             success: result.success,
             error: result.error,
+            attempt: None,
         }
     }
 }
 
-/// Sends notifications through various channels
-pub struct NotificationSender {
-    client: Client,
-}
+/// PagerDuty Events API v2 endpoint
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
 
-impl NotificationSender {
-    /// Create a new notification sender
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
+/// Default Opsgenie alerts API host
+const OPSGENIE_API_URL: &str = "https://api.opsgenie.com";
 
-        Self { client }
-    }
+/// Longest alert message Opsgenie accepts
+const OPSGENIE_MESSAGE_LIMIT: usize = 130;
 
-    /// Send notifications for an alert event
-    pub async fn send_all(
-        &self,
-        rule: &AlertRule,
-        event: &AlertEvent,
-    ) -> Vec<NotificationResult> {
-        let mut results = Vec::new();
+/// Splunk On-Call REST endpoint, followed by `/{api_key}/{routing_key}`
+const SPLUNK_ON_CALL_URL: &str =
+    "https://alert.victorops.com/integrations/generic/20131114/alert";
 
-        for channel in &rule.notification_channels {
-            let result = self.send(channel, rule, event).await;
-            results.push(result);
-        }
+/// Most sample traces linked from a single notification
+const MAX_TRACE_LINKS: usize = 5;
 
-        results
-    }
+/// Most alerts listed individually for a digest group
+const MAX_GROUP_SUMMARY_LINES: usize = 10;
 
-    /// Send a single notification
-    pub async fn send(
-        &self,
-        channel: &NotificationChannel,
-        rule: &AlertRule,
-        event: &AlertEvent,
-    ) -> NotificationResult {
-        let sent_at = Utc::now();
-
-        let result = match channel {
-            NotificationChannel::Slack { webhook_url, channel: slack_channel } => {
-                self.send_slack(webhook_url, slack_channel.as_deref(), rule, event).await
-            }
-            NotificationChannel::Webhook { url, headers } => {
-                self.send_webhook(url, headers.as_ref(), rule, event).await
-            }
-            NotificationChannel::PagerDuty { routing_key } => {
-                self.send_pagerduty(routing_key, rule, event).await
-            }
-            NotificationChannel::Email { to } => {
-                self.send_email(to, rule, event).await
-            }
-        };
-
-        let channel_type = match channel {
-            NotificationChannel::Slack { .. } => "slack",
-            NotificationChannel::Webhook { .. } => "webhook",
-            NotificationChannel::PagerDuty { .. } => "pagerduty",
-            NotificationChannel::Email { .. } => "email",
-        };
+/// A message rendered for each type of channel it goes to
+///
+/// Channel types a message has nothing for are not sent it; resolutions,
+/// for one, only go to the incident tools that opened an incident.
+#[derive(Debug, Default)]
+pub struct Notification {
+    /// Names the message in logs
+    subject: String,
+    slack: Option<SlackMessage>,
+    /// JSON body posted to webhooks
+    webhook: Option<serde_json::Value>,
+    pagerduty: Option<PagerDutyEvent>,
+    opsgenie: Option<OpsgenieRequest>,
+    splunk_on_call: Option<SplunkOnCallAlert>,
+    /// Whether email recipients should get it, once email is supported
+    email: bool,
+}
 
-        NotificationResult {
-            channel_type: channel_type.to_string(),
-            success: result.is_ok(),
-            error: result.err().map(|e| e.to_string()),
-            sent_at,
+impl Notification {
+    /// Whether the message goes to a channel
+    pub fn is_for(&self, channel: &NotificationChannel) -> bool {
+        match channel {
+            NotificationChannel::Slack { .. } => self.slack.is_some(),
+            NotificationChannel::Webhook { .. } => self.webhook.is_some(),
+            NotificationChannel::PagerDuty { .. } => self.pagerduty.is_some(),
+            NotificationChannel::Opsgenie { .. } => self.opsgenie.is_some(),
+            NotificationChannel::SplunkOnCall { .. } => self.splunk_on_call.is_some(),
+            NotificationChannel::Email { .. } => self.email,
         }
     }
 
-    /// Send Slack notification
-    async fn send_slack(
-        &self,
-        webhook_url: &str,
-        channel: Option<&str>,
-        rule: &AlertRule,
-        event: &AlertEvent,
-    ) -> Result<(), NotificationError> {
-        let color = match event.severity {
-            Severity::Critical => "#dc3545",
-            Severity::Warning => "#ffc107",
-            Severity::Info => "#17a2b8",
-        };
-
-        let severity_emoji = match event.severity {
-            Severity::Critical => "🚨",
-            Severity::Warning => "⚠️",
-            Severity::Info => "ℹ️",
-        };
+    /// Close the incidents an alert opened, with the incident's metrics and
+    /// a link to the matching spans
+    ///
+    /// Only PagerDuty, Opsgenie and Splunk On-Call keep open incidents; other
+    /// channels are not sent it.
+    pub fn resolution(rule: &AlertRule, event: &AlertEvent, incident: &IncidentMetrics) -> Self {
+        Self {
+            subject: format!("resolution of alert {}", event.id),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "resolve",
+                // Must match the trigger's key for PagerDuty to close the incident
+                dedup_key: alert_key(rule, event),
+                payload: PagerDutyEventPayload {
+                    summary: format!(
+                        "[RESOLVED] {} after {} (peak {:.2}, {} traces affected)",
+                        rule.name,
+                        format_duration(incident.duration_secs),
+                        incident.peak_value,
+                        incident.affected_traces
+                    ),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(event.severity).to_string(),
+                    timestamp: event.resolved_at.map(|t| t.to_rfc3339()),
+                    custom_details: Some(serde_json::json!({
+                        "rule_id": rule.id.to_string(),
+                        "duration_secs": incident.duration_secs,
+                        "peak_value": incident.peak_value,
+                        "threshold_value": event.threshold_value,
+                        "affected_traces": incident.affected_traces,
+                        "service_name": event.service_name,
+                        "trace_ids": event.trace_ids,
+                    })),
+                },
+                links: incident
+                    .search_url
+                    .iter()
+                    .map(|url| PagerDutyLink {
+                        href: url.clone(),
+                        text: "Matching spans in AgentTrace".to_string(),
+                    })
+                    .collect(),
+            }),
+            // Must match the trigger's alias for Opsgenie to close the alert
+            opsgenie: Some(OpsgenieRequest::Close(
+                alert_key(rule, event),
+                OpsgenieClose {
+                    source: "AgentTrace".to_string(),
+                    note: resolution_summary(rule, incident),
+                },
+            )),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: "RECOVERY".to_string(),
+                // Must match the trigger's entity for Splunk On-Call to resolve it
+                entity_id: alert_key(rule, event),
+                entity_display_name: format!("[RESOLVED] {}", rule.name),
+                state_message: resolution_summary(rule, incident),
+                state_start_time: event.resolved_at.unwrap_or_else(Utc::now).timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(incident).ok(),
+            }),
+            ..Self::default()
+        }
+    }
 
-        let payload = SlackPayload {
-            channel: channel.map(String::from),
-            username: Some("AgentTrace".to_string()),
-            icon_emoji: Some(":robot_face:".to_string()),
-            attachments: vec![SlackAttachment {
-                color: color.to_string(),
-                title: format!("{} Alert: {}", severity_emoji, rule.name),
-                text: event.message.clone(),
+    /// A digest of batched alerts: one Slack attachment per group, or a
+    /// single event or alert for incident tools
+    ///
+    /// Incident tools key it by the digest window, so the next digest opens
+    /// a new incident; [`Notification::digest_resolution`] closes it.
+    pub fn digest(digest: &Digest) -> Self {
+        let summary = digest_summary(digest);
+        let key = digest_key(digest);
+        let attachments = digest
+            .groups
+            .iter()
+            .map(|group| SlackAttachment {
+                color: severity_color(group.severity).to_string(),
+                title: format!(
+                    "{} {} ({}x)",
+                    severity_emoji(group.severity),
+                    group.label,
+                    group.count
+                ),
+                text: group_summary(group),
                 fields: vec![
                     SlackField {
-                        title: "Severity".to_string(),
-                        value: format!("{:?}", event.severity),
+                        title: "First Triggered".to_string(),
+                        value: group.first_triggered_at.format("%H:%M:%S UTC").to_string(),
                         short: true,
                     },
                     SlackField {
-                        title: "Metric Value".to_string(),
-                        value: format!("{:.2}", event.metric_value),
-                        short: true,
-                    },
-                    SlackField {
-                        title: "Threshold".to_string(),
-                        value: format!("{:.2}", event.threshold_value),
+                        title: "Last Triggered".to_string(),
+                        value: group.last_triggered_at.format("%H:%M:%S UTC").to_string(),
                         short: true,
                     },
                     SlackField {
                         title: "Service".to_string(),
-                        value: event.service_name.clone().unwrap_or_else(|| "All".to_string()),
+                        value: group.service_name.clone().unwrap_or_else(|| "All".to_string()),
                         short: true,
                     },
                 ],
-                footer: Some("AgentTrace Alerting".to_string()),
-                ts: Some(event.triggered_at.timestamp()),
-            }],
-        };
+                footer: None,
+                ts: Some(group.last_triggered_at.timestamp()),
+            })
+            .collect();
 
-        let response = self
-            .client
-            .post(webhook_url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| NotificationError::HttpError(e.to_string()))?;
+        Self {
+            subject: format!("digest of {} alerts", digest.event_count()),
+            slack: Some(SlackMessage {
+                text: Some(summary.clone()),
+                attachments,
+            }),
+            webhook: serde_json::to_value(WebhookDigestPayload {
+                kind: "digest",
+                window_start: digest.window_start,
+                window_end: digest.window_end,
+                alert_count: digest.event_count(),
+                groups: &digest.groups,
+            })
+            .ok(),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "trigger",
+                dedup_key: key.clone(),
+                payload: PagerDutyEventPayload {
+                    summary: summary.clone(),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(digest.severity()).to_string(),
+                    timestamp: Some(digest.window_end.to_rfc3339()),
+                    custom_details: serde_json::to_value(&digest.groups).ok(),
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Create(OpsgenieAlert {
+                message: truncate_chars(&summary, OPSGENIE_MESSAGE_LIMIT),
+                alias: key.clone(),
+                description: summary.clone(),
+                priority: opsgenie_priority(digest.severity()).to_string(),
+                source: "AgentTrace".to_string(),
+                entity: None,
+                tags: vec!["agenttrace".to_string(), "digest".to_string()],
+                details: digest
+                    .groups
+                    .iter()
+                    .map(|g| (g.label.clone(), format!("{} alerts", g.count)))
+                    .collect(),
+            })),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: splunk_message_type(digest.severity()).to_string(),
+                entity_id: key,
+                entity_display_name: summary.clone(),
+                state_message: summary,
+                state_start_time: digest.window_end.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(&digest.groups).ok(),
+            }),
+            email: true,
+        }
+    }
+
+    /// Close the incident a digest opened, once every alert in it resolved
+    ///
+    /// Only PagerDuty, Opsgenie and Splunk On-Call keep open incidents; other
+    /// channels are not sent it.
+    pub fn digest_resolution(digest: &Digest) -> Self {
+        let summary = format!("[RESOLVED] {}", digest_summary(digest));
+        let note = "Every alert in the digest has resolved".to_string();
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(NotificationError::HttpError(format!(
-                "Slack returned {}: {}",
-                status, body
-            )));
+        Self {
+            subject: format!("resolution of digest of {} alerts", digest.event_count()),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "resolve",
+                dedup_key: digest_key(digest),
+                payload: PagerDutyEventPayload {
+                    summary: summary.clone(),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(digest.severity()).to_string(),
+                    timestamp: Some(Utc::now().to_rfc3339()),
+                    custom_details: None,
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Close(
+                digest_key(digest),
+                OpsgenieClose {
+                    source: "AgentTrace".to_string(),
+                    note: note.clone(),
+                },
+            )),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: "RECOVERY".to_string(),
+                entity_id: digest_key(digest),
+                entity_display_name: summary,
+                state_message: note,
+                state_start_time: Utc::now().timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: None,
+            }),
+            ..Self::default()
         }
+    }
 
-        info!(rule_id = %rule.id, "Slack notification sent");
-        Ok(())
+    /// A budget crossing a threshold, keyed by budget, period and threshold
+    ///
+    /// Webhooks get the alert as JSON, with an `event` of `budget.threshold`.
+    pub fn budget_alert(alert: &BudgetAlert) -> Self {
+        let scope = [
+            alert.service_name.as_deref(),
+            alert.model_name.as_deref(),
+            alert.project.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        let severity = alert.severity();
+
+        Self {
+            subject: format!("budget {} at {}%", alert.budget_name, alert.threshold_percent),
+            slack: Some(SlackMessage {
+                text: None,
+                attachments: vec![SlackAttachment {
+                    color: severity_color(severity).to_string(),
+                    title: format!(
+                        "{} Budget {}% reached: {}",
+                        severity_emoji(severity),
+                        alert.threshold_percent,
+                        alert.budget_name
+                    ),
+                    text: alert.message.clone(),
+                    fields: vec![
+                        SlackField {
+                            title: "Spent".to_string(),
+                            value: format!("${:.2} of ${:.2}", alert.spent_usd, alert.amount_usd),
+                            short: true,
+                        },
+                        SlackField {
+                            title: "Period".to_string(),
+                            value: format!(
+                                "{} to {}",
+                                alert.period_start.format("%Y-%m-%d"),
+                                alert.period_end.format("%Y-%m-%d")
+                            ),
+                            short: true,
+                        },
+                        SlackField {
+                            title: "Scope".to_string(),
+                            value: if scope.is_empty() { "All".to_string() } else { scope.join(", ") },
+                            short: true,
+                        },
+                    ],
+                    footer: Some("AgentTrace Budgets".to_string()),
+                    ts: Some(alert.triggered_at.timestamp()),
+                }],
+            }),
+            webhook: event_json(alert, "budget.threshold"),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "trigger",
+                dedup_key: alert.key(),
+                payload: PagerDutyEventPayload {
+                    summary: format!("[{}] {}", pagerduty_severity(severity).to_uppercase(), alert.message),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(severity).to_string(),
+                    timestamp: Some(alert.triggered_at.to_rfc3339()),
+                    custom_details: serde_json::to_value(alert).ok(),
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Create(OpsgenieAlert {
+                message: truncate_chars(&alert.message, OPSGENIE_MESSAGE_LIMIT),
+                alias: alert.key(),
+                description: alert.message.clone(),
+                priority: opsgenie_priority(severity).to_string(),
+                source: "AgentTrace".to_string(),
+                entity: alert.service_name.clone(),
+                tags: vec!["agenttrace".to_string(), "budget".to_string()],
+                details: HashMap::from([
+                    ("budget_id".to_string(), alert.budget_id.to_string()),
+                    ("spent_usd".to_string(), format!("{:.2}", alert.spent_usd)),
+                    ("amount_usd".to_string(), format!("{:.2}", alert.amount_usd)),
+                    ("period".to_string(), alert.period.as_str().to_string()),
+                ]),
+            })),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: splunk_message_type(severity).to_string(),
+                entity_id: alert.key(),
+                entity_display_name: alert.message.clone(),
+                state_message: alert.message.clone(),
+                state_start_time: alert.triggered_at.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(alert).ok(),
+            }),
+            email: true,
+        }
     }
 
-    /// Send generic webhook notification
-    async fn send_webhook(
-        &self,
-        url: &str,
-        headers: Option<&serde_json::Value>,
-        rule: &AlertRule,
-        event: &AlertEvent,
-    ) -> Result<(), NotificationError> {
-        let payload = WebhookPayload {
+    /// An SLO burn-rate alert starting to fire, keyed by SLO and window pair
+    ///
+    /// Webhooks get the alert as JSON, with an `event` of `slo.burn_rate`.
+    pub fn slo_alert(alert: &SloBurnAlert) -> Self {
+        Self {
+            subject: format!("SLO {} burn-rate alert", alert.slo_name),
+            slack: Some(SlackMessage {
+                text: None,
+                attachments: vec![SlackAttachment {
+                    color: severity_color(alert.severity).to_string(),
+                    title: format!(
+                        "{} SLO burning {:.1}x: {}",
+                        severity_emoji(alert.severity),
+                        alert.long_burn_rate,
+                        alert.slo_name
+                    ),
+                    text: alert.message.clone(),
+                    fields: vec![
+                        SlackField {
+                            title: "Objective".to_string(),
+                            value: format!("{}% {}", alert.objective_percent, alert.indicator.as_str()),
+                            short: true,
+                        },
+                        SlackField {
+                            title: "Burn Rate".to_string(),
+                            value: format!(
+                                "{:.1}x over {}, {:.1}x over {}",
+                                alert.long_burn_rate,
+                                format_minutes(alert.long_window_minutes),
+                                alert.short_burn_rate,
+                                format_minutes(alert.short_window_minutes)
+                            ),
+                            short: true,
+                        },
+                        SlackField {
+                            title: "Budget Spent".to_string(),
+                            value: format!("{:.1}%", alert.budget_spent_percent),
+                            short: true,
+                        },
+                    ],
+                    footer: Some("AgentTrace SLOs".to_string()),
+                    ts: Some(alert.triggered_at.timestamp()),
+                }],
+            }),
+            webhook: event_json(alert, "slo.burn_rate"),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "trigger",
+                dedup_key: alert.key(),
+                payload: PagerDutyEventPayload {
+                    summary: format!(
+                        "[{}] {}",
+                        pagerduty_severity(alert.severity).to_uppercase(),
+                        alert.message
+                    ),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(alert.severity).to_string(),
+                    timestamp: Some(alert.triggered_at.to_rfc3339()),
+                    custom_details: serde_json::to_value(alert).ok(),
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Create(OpsgenieAlert {
+                message: truncate_chars(&alert.message, OPSGENIE_MESSAGE_LIMIT),
+                alias: alert.key(),
+                description: alert.message.clone(),
+                priority: opsgenie_priority(alert.severity).to_string(),
+                source: "AgentTrace".to_string(),
+                entity: alert.service_name.clone(),
+                tags: vec!["agenttrace".to_string(), "slo".to_string()],
+                details: HashMap::from([
+                    ("slo_id".to_string(), alert.slo_id.to_string()),
+                    ("objective_percent".to_string(), alert.objective_percent.to_string()),
+                    ("long_burn_rate".to_string(), format!("{:.2}", alert.long_burn_rate)),
+                    ("short_burn_rate".to_string(), format!("{:.2}", alert.short_burn_rate)),
+                    ("budget_spent_percent".to_string(), format!("{:.1}", alert.budget_spent_percent)),
+                ]),
+            })),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: splunk_message_type(alert.severity).to_string(),
+                entity_id: alert.key(),
+                entity_display_name: alert.message.clone(),
+                state_message: alert.message.clone(),
+                state_start_time: alert.triggered_at.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(alert).ok(),
+            }),
+            email: true,
+        }
+    }
+
+    /// Close the incidents an SLO burn-rate alert opened, now that it
+    /// cleared
+    ///
+    /// Like [`resolution`](Self::resolution), only incident tools are sent it.
+    pub fn slo_resolution(resolution: &SloResolution) -> Self {
+        Self {
+            subject: format!("SLO {} resolution", resolution.slo_name),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "resolve",
+                // Must match the trigger's key for PagerDuty to close the incident
+                dedup_key: resolution.key(),
+                payload: PagerDutyEventPayload {
+                    summary: format!("[RESOLVED] {}", resolution.message),
+                    source: "AgentTrace".to_string(),
+                    severity: "info".to_string(),
+                    timestamp: Some(resolution.resolved_at.to_rfc3339()),
+                    custom_details: serde_json::to_value(resolution).ok(),
+                },
+                links: vec![],
+            }),
+            // Must match the trigger's alias for Opsgenie to close the alert
+            opsgenie: Some(OpsgenieRequest::Close(
+                resolution.key(),
+                OpsgenieClose {
+                    source: "AgentTrace".to_string(),
+                    note: resolution.message.clone(),
+                },
+            )),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: "RECOVERY".to_string(),
+                entity_id: resolution.key(),
+                entity_display_name: format!("[RESOLVED] {}", resolution.slo_name),
+                state_message: resolution.message.clone(),
+                state_start_time: resolution.resolved_at.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(resolution).ok(),
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// Another channel's notifications failing, keyed by that channel
+    ///
+    /// Webhooks get the failure as JSON, with an `event` of
+    /// `notification.delivery_failed`.
+    pub fn delivery_failure(failure: &DeliveryFailure) -> Self {
+        Self {
+            subject: format!("delivery failure of {}", failure.channel),
+            slack: Some(SlackMessage {
+                text: None,
+                attachments: vec![SlackAttachment {
+                    color: severity_color(Severity::Critical).to_string(),
+                    title: format!(
+                        "{} Notification channel failing: {}",
+                        severity_emoji(Severity::Critical),
+                        failure.channel
+                    ),
+                    text: failure.message.clone(),
+                    fields: vec![SlackField {
+                        title: "Last Error".to_string(),
+                        value: failure.last_error.clone().unwrap_or_else(|| "Unknown".to_string()),
+                        short: false,
+                    }],
+                    footer: Some("AgentTrace Alerts".to_string()),
+                    ts: Some(Utc::now().timestamp()),
+                }],
+            }),
+            webhook: event_json(failure, "notification.delivery_failed"),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "trigger",
+                dedup_key: failure.key(),
+                payload: PagerDutyEventPayload {
+                    summary: format!("[CRITICAL] {}", failure.message),
+                    source: "AgentTrace".to_string(),
+                    severity: "critical".to_string(),
+                    timestamp: Some(Utc::now().to_rfc3339()),
+                    custom_details: serde_json::to_value(failure).ok(),
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Create(OpsgenieAlert {
+                message: truncate_chars(&failure.message, OPSGENIE_MESSAGE_LIMIT),
+                alias: failure.key(),
+                description: failure.message.clone(),
+                priority: opsgenie_priority(Severity::Critical).to_string(),
+                source: "AgentTrace".to_string(),
+                entity: None,
+                tags: vec!["agenttrace".to_string(), "delivery".to_string()],
+                details: HashMap::from([
+                    ("channel".to_string(), failure.channel.clone()),
+                    ("consecutive_failures".to_string(), failure.consecutive_failures.to_string()),
+                    ("last_error".to_string(), failure.last_error.clone().unwrap_or_default()),
+                ]),
+            })),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: splunk_message_type(Severity::Critical).to_string(),
+                entity_id: failure.key(),
+                entity_display_name: failure.message.clone(),
+                state_message: failure.message.clone(),
+                state_start_time: failure.failing_since.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(failure).ok(),
+            }),
+            email: true,
+        }
+    }
+}
+
+/// Sends notifications through various channels
+pub struct NotificationSender {
+    client: Client,
+    /// Server base URL that sample traces link to
+    public_url: Option<String>,
+}
+
+impl NotificationSender {
+    /// Create a new notification sender
+    pub fn new() -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            public_url: None,
+        }
+    }
+
+    /// Link sample traces to their page in the web dashboard at `public_url`
+    pub fn with_public_url(mut self, public_url: Option<String>) -> Self {
+        self.public_url = public_url.map(|url| url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Links to an event's first sample traces
+    fn trace_links(&self, event: &AlertEvent) -> Vec<TraceLink> {
+        event
+            .trace_ids
+            .iter()
+            .take(MAX_TRACE_LINKS)
+            .map(|trace_id| TraceLink {
+                trace_id: trace_id.clone(),
+                url: self
+                    .public_url
+                    .as_ref()
+                    .map(|base| format!("{}/traces/{}", base, trace_id)),
+                command: format!("agenttrace traces show {}", trace_id),
+            })
+            .collect()
+    }
+
+    /// The event as webhooks get it, for the alert and its acknowledgement
+    fn webhook_payload(&self, rule: &AlertRule, event: &AlertEvent) -> Option<serde_json::Value> {
+        serde_json::to_value(WebhookPayload {
             alert_id: event.id.to_string(),
             rule_id: rule.id.to_string(),
             rule_name: rule.name.clone(),
@@ -196,110 +662,397 @@ impl NotificationSender {
             service_name: event.service_name.clone(),
             triggered_at: event.triggered_at,
             trace_ids: event.trace_ids.clone(),
+            trace_links: self.trace_links(event),
             metadata: event.metadata.clone(),
-        };
+            acknowledgement: event.acknowledgement.clone(),
+        })
+        .ok()
+    }
 
-        let mut request = self.client.post(url).json(&payload);
+    /// An alert event, linking its sample traces and any upstream provider
+    /// incidents
+    ///
+    /// Incident tools key it by rule and event, so its resolution closes the
+    /// incident it opens.
+    pub fn alert(&self, rule: &AlertRule, event: &AlertEvent) -> Notification {
+        let trace_links = self.trace_links(event);
+        let incidents = event.upstream_incidents();
 
-        // Add custom headers if provided
-        if let Some(headers_obj) = headers {
-            if let Some(headers_map) = headers_obj.as_object() {
-                for (key, value) in headers_map {
-                    if let Some(value_str) = value.as_str() {
-                        request = request.header(key, value_str);
-                    }
-                }
-            }
+        let mut fields = vec![
+            SlackField {
+                title: "Severity".to_string(),
+                value: format!("{:?}", event.severity),
+                short: true,
+            },
+            SlackField {
+                title: "Metric Value".to_string(),
+                value: format!("{:.2}", event.metric_value),
+                short: true,
+            },
+            SlackField {
+                title: "Threshold".to_string(),
+                value: format!("{:.2}", event.threshold_value),
+                short: true,
+            },
+            SlackField {
+                title: "Service".to_string(),
+                value: event.service_name.clone().unwrap_or_else(|| "All".to_string()),
+                short: true,
+            },
+        ];
+        if !trace_links.is_empty() {
+            fields.push(SlackField {
+                title: "Sample traces".to_string(),
+                value: trace_links
+                    .iter()
+                    .map(|link| match &link.url {
+                        Some(url) => format!("<{}|{}>", url, short_id(&link.trace_id)),
+                        None => format!("`{}`", link.command),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                short: false,
+            });
         }
-
-        let response = request
-            .send()
-            .await
-            .map_err(|e| NotificationError::HttpError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(NotificationError::HttpError(format!(
-                "Webhook returned {}: {}",
-                status, body
-            )));
+        if !incidents.is_empty() {
+            fields.push(SlackField {
+                title: "Upstream provider incident".to_string(),
+                value: incidents
+                    .iter()
+                    .map(|i| match &i.url {
+                        Some(url) => format!("<{}|{}>", url, i.summary()),
+                        None => i.summary(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                short: false,
+            });
         }
 
-        info!(rule_id = %rule.id, url = %url, "Webhook notification sent");
-        Ok(())
-    }
-
-    /// Send PagerDuty notification
-    async fn send_pagerduty(
-        &self,
-        routing_key: &str,
-        rule: &AlertRule,
-        event: &AlertEvent,
-    ) -> Result<(), NotificationError> {
-        let severity = match event.severity {
-            Severity::Critical => "critical",
-            Severity::Warning => "warning",
-            Severity::Info => "info",
-        };
+        let mut opsgenie_details = HashMap::from([
+            ("rule_id".to_string(), rule.id.to_string()),
+            ("metric".to_string(), rule.metric.clone()),
+            ("metric_value".to_string(), format!("{:.4}", event.metric_value)),
+            ("threshold_value".to_string(), format!("{:.4}", event.threshold_value)),
+        ]);
+        if let Some(service) = &event.service_name {
+            opsgenie_details.insert("service_name".to_string(), service.clone());
+        }
+        if !event.trace_ids.is_empty() {
+            opsgenie_details.insert("trace_ids".to_string(), event.trace_ids.join(", "));
+        }
 
-        let payload = PagerDutyPayload {
-            routing_key: routing_key.to_string(),
-            event_action: "trigger".to_string(),
-            dedup_key: Some(format!("{}:{}", rule.id, event.id)),
-            payload: PagerDutyEventPayload {
-                summary: format!("[{}] {}: {}", severity.to_uppercase(), rule.name, event.message),
+        let severity = pagerduty_severity(event.severity);
+        Notification {
+            subject: format!("alert {}", event.id),
+            slack: Some(SlackMessage {
+                text: None,
+                attachments: vec![SlackAttachment {
+                    color: severity_color(event.severity).to_string(),
+                    title: format!("{} Alert: {}", severity_emoji(event.severity), rule.name),
+                    text: event.message.clone(),
+                    fields,
+                    footer: Some("AgentTrace Alerting".to_string()),
+                    ts: Some(event.triggered_at.timestamp()),
+                }],
+            }),
+            webhook: self.webhook_payload(rule, event),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "trigger",
+                dedup_key: alert_key(rule, event),
+                payload: PagerDutyEventPayload {
+                    summary: format!("[{}] {}: {}", severity.to_uppercase(), rule.name, event.message),
+                    source: "AgentTrace".to_string(),
+                    severity: severity.to_string(),
+                    timestamp: Some(event.triggered_at.to_rfc3339()),
+                    custom_details: Some(serde_json::json!({
+                        "rule_id": rule.id.to_string(),
+                        "metric_value": event.metric_value,
+                        "threshold_value": event.threshold_value,
+                        "service_name": event.service_name,
+                        "trace_ids": event.trace_ids,
+                        "trace_commands": trace_links.iter().map(|l| &l.command).collect::<Vec<_>>(),
+                        "upstream_incidents": incidents.iter().map(|i| i.summary()).collect::<Vec<_>>(),
+                    })),
+                },
+                links: trace_links
+                    .iter()
+                    .filter_map(|link| {
+                        link.url.as_ref().map(|url| PagerDutyLink {
+                            href: url.clone(),
+                            text: format!("Trace {}", short_id(&link.trace_id)),
+                        })
+                    })
+                    .chain(incidents.iter().filter_map(|i| {
+                        i.url.as_ref().map(|url| PagerDutyLink {
+                            href: url.clone(),
+                            text: format!("Upstream incident: {}", i.summary()),
+                        })
+                    }))
+                    .collect(),
+            }),
+            opsgenie: Some(OpsgenieRequest::Create(OpsgenieAlert {
+                message: truncate_chars(&format!("{}: {}", rule.name, event.message), OPSGENIE_MESSAGE_LIMIT),
+                alias: alert_key(rule, event),
+                description: event.message.clone(),
+                priority: opsgenie_priority(event.severity).to_string(),
                 source: "AgentTrace".to_string(),
-                severity: severity.to_string(),
-                timestamp: Some(event.triggered_at.to_rfc3339()),
-                custom_details: Some(serde_json::json!({
+                entity: event.service_name.clone(),
+                tags: vec![
+                    "agenttrace".to_string(),
+                    format!("severity:{:?}", event.severity).to_lowercase(),
+                ],
+                details: opsgenie_details,
+            })),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: splunk_message_type(event.severity).to_string(),
+                entity_id: alert_key(rule, event),
+                entity_display_name: format!("{}: {}", rule.name, event.message),
+                state_message: event.message.clone(),
+                state_start_time: event.triggered_at.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: Some(serde_json::json!({
                     "rule_id": rule.id.to_string(),
                     "metric_value": event.metric_value,
                     "threshold_value": event.threshold_value,
                     "service_name": event.service_name,
                     "trace_ids": event.trace_ids,
                 })),
-            },
-        };
+            }),
+            email: true,
+        }
+    }
 
-        let response = self
-            .client
-            .post("https://events.pagerduty.com/v2/enqueue")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| NotificationError::HttpError(e.to_string()))?;
+    /// Who acknowledged an alert and is handling it, or None if nobody has
+    ///
+    /// Incident tools acknowledge the incident the alert opened; Slack gets
+    /// a follow-up message and webhooks the acknowledged event. Incoming
+    /// Slack webhooks can't reply in a thread, so the follow-up names the
+    /// alert it is about.
+    pub fn acknowledgement(&self, rule: &AlertRule, event: &AlertEvent) -> Option<Notification> {
+        let ack = event.acknowledgement.as_ref()?;
+
+        Some(Notification {
+            subject: format!("acknowledgement of alert {}", event.id),
+            slack: Some(SlackMessage {
+                text: None,
+                attachments: vec![SlackAttachment {
+                    color: "#808080".to_string(),
+                    title: format!(":eyes: Acknowledged: {}", rule.name),
+                    text: ack.summary(),
+                    fields: vec![SlackField {
+                        title: "Alert".to_string(),
+                        value: event.message.clone(),
+                        short: false,
+                    }],
+                    footer: Some("AgentTrace Alerting".to_string()),
+                    ts: Some(ack.acknowledged_at.timestamp()),
+                }],
+            }),
+            webhook: self.webhook_payload(rule, event),
+            pagerduty: Some(PagerDutyEvent {
+                event_action: "acknowledge",
+                // Must match the trigger's key for PagerDuty to find the incident
+                dedup_key: alert_key(rule, event),
+                payload: PagerDutyEventPayload {
+                    summary: format!("[ACKNOWLEDGED] {}: {}", rule.name, ack.summary()),
+                    source: "AgentTrace".to_string(),
+                    severity: pagerduty_severity(event.severity).to_string(),
+                    timestamp: Some(ack.acknowledged_at.to_rfc3339()),
+                    custom_details: serde_json::to_value(ack).ok(),
+                },
+                links: vec![],
+            }),
+            opsgenie: Some(OpsgenieRequest::Acknowledge(
+                alert_key(rule, event),
+                OpsgenieAcknowledge {
+                    user: ack.acknowledged_by.clone(),
+                    source: "AgentTrace".to_string(),
+                    note: ack.summary(),
+                },
+            )),
+            splunk_on_call: Some(SplunkOnCallAlert {
+                message_type: "ACKNOWLEDGEMENT".to_string(),
+                entity_id: alert_key(rule, event),
+                entity_display_name: format!("[ACKNOWLEDGED] {}", rule.name),
+                state_message: ack.summary(),
+                state_start_time: ack.acknowledged_at.timestamp(),
+                monitoring_tool: "AgentTrace".to_string(),
+                details: serde_json::to_value(ack).ok(),
+            }),
+            email: false,
+        })
+    }
+
+    /// Send a notification to each of `channels` it goes to
+    pub async fn send_all(
+        &self,
+        channels: &[NotificationChannel],
+        notification: &Notification,
+    ) -> Vec<NotificationResult> {
+        let mut results = Vec::new();
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(NotificationError::HttpError(format!(
-                "PagerDuty returned {}: {}",
-                status, body
-            )));
+        for channel in channels.iter().filter(|c| notification.is_for(c)) {
+            results.push(self.send(channel, notification).await);
         }
 
-        info!(rule_id = %rule.id, "PagerDuty notification sent");
-        Ok(())
+        results
     }
 
-    /// Send email notification (placeholder - requires SMTP configuration)
-    async fn send_email(
+    /// Send a notification to one channel
+    pub async fn send(&self, channel: &NotificationChannel, notification: &Notification) -> NotificationResult {
+        let sent_at = Utc::now();
+        let result = self.deliver(channel, notification).await;
+
+        if result.is_ok() {
+            info!(
+                channel = channel.channel_type(),
+                subject = %notification.subject,
+                "Notification sent"
+            );
+        }
+        NotificationResult::new(channel, sent_at, result)
+    }
+
+    async fn deliver(
         &self,
-        to: &[String],
-        rule: &AlertRule,
-        event: &AlertEvent,
+        channel: &NotificationChannel,
+        notification: &Notification,
     ) -> Result<(), NotificationError> {
-        // Email sending would require SMTP configuration
-        // For now, just log the intent
-        warn!(
-            rule_id = %rule.id,
-            recipients = ?to,
-            "Email notifications not yet implemented"
-        );
+        let not_for = || {
+            NotificationError::ConfigError(format!(
+                "{} is not sent to {} channels",
+                notification.subject,
+                channel.channel_type()
+            ))
+        };
+
+        let (service, request) = match channel {
+            NotificationChannel::Slack { webhook_url, channel: slack_channel } => {
+                let message = notification.slack.as_ref().ok_or_else(not_for)?;
+                let payload = SlackPayload {
+                    channel: slack_channel.as_deref(),
+                    username: "AgentTrace",
+                    icon_emoji: ":robot_face:",
+                    message,
+                };
+                ("Slack", self.client.post(webhook_url).json(&payload))
+            }
+            NotificationChannel::Webhook { url, headers } => {
+                let body = notification.webhook.as_ref().ok_or_else(not_for)?;
+                let mut request = self.client.post(url).json(body);
+                if let Some(headers_map) = headers.as_ref().and_then(|h| h.as_object()) {
+                    for (key, value) in headers_map {
+                        if let Some(value_str) = value.as_str() {
+                            request = request.header(key, value_str);
+                        }
+                    }
+                }
+                ("Webhook", request)
+            }
+            NotificationChannel::PagerDuty { routing_key } => {
+                let event = notification.pagerduty.as_ref().ok_or_else(not_for)?;
+                let payload = PagerDutyPayload { routing_key, event };
+                ("PagerDuty", self.client.post(PAGERDUTY_EVENTS_URL).json(&payload))
+            }
+            NotificationChannel::Opsgenie { api_key, api_url } => {
+                let base = opsgenie_base(api_url.as_deref());
+                let request = match notification.opsgenie.as_ref().ok_or_else(not_for)? {
+                    OpsgenieRequest::Create(alert) => self.client.post(format!("{}/v2/alerts", base)).json(alert),
+                    OpsgenieRequest::Close(alias, close) => self
+                        .client
+                        .post(format!("{}/v2/alerts/{}/close", base, alias))
+                        .query(&[("identifierType", "alias")])
+                        .json(close),
+                    OpsgenieRequest::Acknowledge(alias, ack) => self
+                        .client
+                        .post(format!("{}/v2/alerts/{}/acknowledge", base, alias))
+                        .query(&[("identifierType", "alias")])
+                        .json(ack),
+                };
+                ("Opsgenie", request.header("Authorization", format!("GenieKey {}", api_key)))
+            }
+            NotificationChannel::SplunkOnCall { api_key, routing_key } => {
+                let alert = notification.splunk_on_call.as_ref().ok_or_else(not_for)?;
+                let url = format!("{}/{}/{}", SPLUNK_ON_CALL_URL, api_key, routing_key);
+                ("Splunk On-Call", self.client.post(url).json(alert))
+            }
+            // Email isn't supported yet; report the notification as failed
+            // rather than pretend it was sent
+            NotificationChannel::Email { to } => {
+                warn!(
+                    recipients = ?to,
+                    subject = %notification.subject,
+                    "Email notifications not yet implemented"
+                );
+                return Err(NotificationError::ConfigError(
+                    "Email notifications are not supported yet".to_string(),
+                ));
+            }
+        };
 
-        // Return success to not block other notifications
-        Ok(())
+        let response = request
+            .send()
+            .await
+            .map_err(|e| NotificationError::HttpError(e.to_string()))?;
+        ensure_success(response, service).await
+    }
+
+    /// Send a test alert through a channel, to check its URL and
+    /// credentials before relying on it
+    ///
+    /// The alert is marked as a test and isn't stored. Channels that open
+    /// incidents, like PagerDuty, are sent the matching resolution straight
+    /// away so the test doesn't page anyone for long.
+    pub async fn send_test(&self, channel: &NotificationChannel) -> NotificationResult {
+        let now = Utc::now();
+        let rule = AlertRule {
+            id: Uuid::nil(),
+            description: Some("Sent to check a notification channel".to_string()),
+            severity: Severity::Info,
+            notification_channels: vec![channel.clone()],
+            ..AlertRule::new("AgentTrace test notification", "cost_sum", Operator::Gt, 0.0)
+        };
+        let mut event = AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            triggered_at: now,
+            resolved_at: None,
+            status: AlertStatus::Active,
+            severity: Severity::Info,
+            message: "This is a test notification from AgentTrace; no action is needed".to_string(),
+            metric_value: 0.0,
+            threshold_value: 0.0,
+            service_name: None,
+            trace_ids: vec![],
+            notifications_sent: vec![],
+            metadata: serde_json::json!({ "test": true }),
+            incident_id: None,
+            acknowledgement: None,
+        };
+
+        let result = self.send(channel, &self.alert(&rule, &event)).await;
+
+        if result.success && channel.tracks_incidents() {
+            event.resolved_at = Some(Utc::now());
+            event.status = AlertStatus::Resolved;
+            let incident = IncidentMetrics {
+                duration_secs: 0,
+                peak_value: 0.0,
+                affected_traces: 0,
+                search_url: None,
+            };
+            let resolution = self.send(channel, &Notification::resolution(&rule, &event, &incident)).await;
+            if !resolution.success {
+                warn!(
+                    channel = %resolution.channel_type,
+                    error = resolution.error.as_deref().unwrap_or_default(),
+                    "Failed to resolve test notification"
+                );
+            }
+        }
+
+        result
     }
 }
 
@@ -309,6 +1062,164 @@ impl Default for NotificationSender {
     }
 }
 
+/// First 8 characters of a trace ID, enough to tell traces apart in a list
+fn short_id(trace_id: &str) -> &str {
+    trace_id.get(..8).unwrap_or(trace_id)
+}
+
+/// Key identifying an alert to incident tools, so its resolution closes the
+/// incident its trigger opened
+fn alert_key(rule: &AlertRule, event: &AlertEvent) -> String {
+    format!("{}:{}", rule.id, event.id)
+}
+
+/// PagerDuty severity for a severity
+fn pagerduty_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "critical",
+        Severity::Warning => "warning",
+        Severity::Info => "info",
+    }
+}
+
+/// Opsgenie priority for a severity; P2 and P4 are left for manual triage
+fn opsgenie_priority(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "P1",
+        Severity::Warning => "P3",
+        Severity::Info => "P5",
+    }
+}
+
+fn opsgenie_base(api_url: Option<&str>) -> &str {
+    api_url.unwrap_or(OPSGENIE_API_URL).trim_end_matches('/')
+}
+
+/// Splunk On-Call message type for a severity
+fn splunk_message_type(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "CRITICAL",
+        Severity::Warning => "WARNING",
+        Severity::Info => "INFO",
+    }
+}
+
+/// One-line account of a resolved incident, with a link to its spans
+fn resolution_summary(rule: &AlertRule, incident: &IncidentMetrics) -> String {
+    let mut summary = format!(
+        "{} resolved after {} (peak {:.2}, {} traces affected)",
+        rule.name,
+        format_duration(incident.duration_secs),
+        incident.peak_value,
+        incident.affected_traces
+    );
+    if let Some(url) = &incident.search_url {
+        summary.push_str(&format!(". Matching spans: {}", url));
+    }
+    summary
+}
+
+/// Shorten `s` to at most `max` characters, marking the cut with an ellipsis
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Fail with the service's response body unless it accepted the request
+async fn ensure_success(
+    response: reqwest::Response,
+    service: &str,
+) -> Result<(), NotificationError> {
+    if response.status().is_success() {
+        return Ok(());
+    }
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(NotificationError::Status {
+        status: status.as_u16(),
+        message: format!("{} returned {}: {}", service, status, body),
+    })
+}
+
+/// A value as webhooks get it, tagged with the `event` it is about
+fn event_json(value: &impl Serialize, event: &str) -> Option<serde_json::Value> {
+    let mut json = serde_json::to_value(value).ok()?;
+    json["event"] = serde_json::json!(event);
+    Some(json)
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "#dc3545",
+        Severity::Warning => "#ffc107",
+        Severity::Info => "#17a2b8",
+    }
+}
+
+fn severity_emoji(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "🚨",
+        Severity::Warning => "⚠️",
+        Severity::Info => "ℹ️",
+    }
+}
+
+/// Key incident tools track a digest's incident by
+fn digest_key(digest: &Digest) -> String {
+    format!("digest:{}", digest.window_start.timestamp())
+}
+
+fn digest_summary(digest: &Digest) -> String {
+    format!(
+        "AgentTrace digest: {} alert{} from {} rule{} since {}",
+        digest.event_count(),
+        if digest.event_count() == 1 { "" } else { "s" },
+        digest.groups.len(),
+        if digest.groups.len() == 1 { "" } else { "s" },
+        digest.window_start.format("%Y-%m-%d %H:%M UTC")
+    )
+}
+
+/// A group's alerts, one line each, or just the latest message if it has one
+fn group_summary(group: &DigestGroup) -> String {
+    if group.items.len() <= 1 {
+        return group.latest_message.clone();
+    }
+
+    let mut lines: Vec<String> = group
+        .items
+        .iter()
+        .take(MAX_GROUP_SUMMARY_LINES)
+        .map(|item| {
+            format!(
+                "{} {}: {}",
+                item.triggered_at.format("%H:%M"),
+                item.rule_name,
+                item.message
+            )
+        })
+        .collect();
+    if group.items.len() > MAX_GROUP_SUMMARY_LINES {
+        lines.push(format!("…and {} more", group.items.len() - MAX_GROUP_SUMMARY_LINES));
+    }
+    lines.join("\n")
+}
+
+/// Format a duration in seconds as e.g. `1h 5m` or `42s`
+fn format_duration(secs: i64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m {}s", minutes, seconds),
+        _ => format!("{}h {}m", hours, minutes),
+    }
+}
+
 /// Notification errors
 #[derive(Debug, thiserror::Error)]
 pub enum NotificationError {
@@ -318,19 +1229,49 @@ pub enum NotificationError {
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
-    #[error("Serialization error: {0}")]
-    SerializationError(String),
+    /// The service answered with an error status
+    #[error("{message}")]
+    Status { status: u16, message: String },
+}
+
+impl NotificationError {
+    /// Whether sending again later might succeed
+    ///
+    /// Failed requests, server errors and rate limiting are worth retrying;
+    /// requests the service rejected and bad configuration are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::HttpError(_) => true,
+            Self::Status { status, .. } => *status == 429 || *status >= 500,
+            Self::ConfigError(_) => false,
+        }
+    }
+
+    /// HTTP status the service answered with
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
 }
 
 // Slack payload types
 #[derive(Debug, Serialize)]
-struct SlackPayload {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    channel: Option<String>,
+struct SlackPayload<'a> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    username: Option<String>,
+    channel: Option<&'a str>,
+    username: &'static str,
+    icon_emoji: &'static str,
+    #[serde(flatten)]
+    message: &'a SlackMessage,
+}
+
+/// What a notification says in Slack, whichever channel it goes to
+#[derive(Debug, Serialize)]
+struct SlackMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
-    icon_emoji: Option<String>,
+    text: Option<String>,
     attachments: Vec<SlackAttachment>,
 }
 
@@ -367,17 +1308,55 @@ struct WebhookPayload {
     service_name: Option<String>,
     triggered_at: DateTime<Utc>,
     trace_ids: Vec<String>,
+    trace_links: Vec<TraceLink>,
     metadata: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acknowledgement: Option<Acknowledgement>,
 }
 
-// PagerDuty payload types
+/// Where to look at one of an alert's sample traces
 #[derive(Debug, Serialize)]
-struct PagerDutyPayload {
-    routing_key: String,
-    event_action: String,
+struct TraceLink {
+    trace_id: String,
+    /// Trace page in the web dashboard, when `public_url` is configured
     #[serde(skip_serializing_if = "Option::is_none")]
-    dedup_key: Option<String>,
+    url: Option<String>,
+    /// CLI command that shows the trace
+    command: String,
+}
+
+// Webhook digest payload
+#[derive(Debug, Serialize)]
+struct WebhookDigestPayload<'a> {
+    kind: &'static str,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    alert_count: usize,
+    groups: &'a [DigestGroup],
+}
+
+// PagerDuty payload types
+#[derive(Debug, Serialize)]
+struct PagerDutyPayload<'a> {
+    routing_key: &'a str,
+    #[serde(flatten)]
+    event: &'a PagerDutyEvent,
+}
+
+/// A PagerDuty event, whichever routing key it goes to
+#[derive(Debug, Serialize)]
+struct PagerDutyEvent {
+    event_action: &'static str,
+    dedup_key: String,
     payload: PagerDutyEventPayload,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    links: Vec<PagerDutyLink>,
+}
+
+#[derive(Debug, Serialize)]
+struct PagerDutyLink {
+    href: String,
+    text: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -390,3 +1369,233 @@ struct PagerDutyEventPayload {
     #[serde(skip_serializing_if = "Option::is_none")]
     custom_details: Option<serde_json::Value>,
 }
+
+// Opsgenie payload types
+
+/// An Opsgenie request: a new alert, or closing or acknowledging the alert
+/// with an alias
+#[derive(Debug)]
+enum OpsgenieRequest {
+    Create(OpsgenieAlert),
+    Close(String, OpsgenieClose),
+    Acknowledge(String, OpsgenieAcknowledge),
+}
+
+#[derive(Debug, Serialize)]
+struct OpsgenieAlert {
+    message: String,
+    alias: String,
+    description: String,
+    priority: String,
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity: Option<String>,
+    tags: Vec<String>,
+    details: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OpsgenieClose {
+    source: String,
+    note: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OpsgenieAcknowledge {
+    user: String,
+    source: String,
+    note: String,
+}
+
+// Splunk On-Call payload
+#[derive(Debug, Serialize)]
+struct SplunkOnCallAlert {
+    message_type: String,
+    entity_id: String,
+    entity_display_name: String,
+    state_message: String,
+    state_start_time: i64,
+    monitoring_tool: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerting::digest::DigestQueue;
+
+    fn create_test_event(rule: &AlertRule) -> AlertEvent {
+        AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            triggered_at: Utc::now(),
+            resolved_at: None,
+            status: AlertStatus::Active,
+            severity: rule.severity,
+            message: "error_rate exceeded threshold (current value: 7.00)".to_string(),
+            metric_value: 7.0,
+            threshold_value: 5.0,
+            service_name: Some("agent".to_string()),
+            trace_ids: vec![],
+            notifications_sent: vec![],
+            metadata: serde_json::json!({}),
+            incident_id: None,
+            acknowledgement: None,
+        }
+    }
+
+    fn opsgenie_alias(notification: &Notification) -> Option<&str> {
+        match notification.opsgenie.as_ref()? {
+            OpsgenieRequest::Create(alert) => Some(&alert.alias),
+            OpsgenieRequest::Close(alias, _) | OpsgenieRequest::Acknowledge(alias, _) => Some(alias),
+        }
+    }
+
+    #[test]
+    fn test_incident_payloads_share_keys() {
+        let rule = AlertRule {
+            severity: Severity::Critical,
+            ..AlertRule::new("High error rate", "error_rate", Operator::Gt, 5.0)
+        };
+        let event = create_test_event(&rule);
+        let key = alert_key(&rule, &event);
+
+        let alert = NotificationSender::new().alert(&rule, &event);
+        let Some(OpsgenieRequest::Create(created)) = &alert.opsgenie else {
+            panic!("alerts create Opsgenie alerts");
+        };
+        assert_eq!(created.alias, key);
+        assert_eq!(created.priority, "P1");
+        assert_eq!(created.message, "High error rate: error_rate exceeded threshold (current value: 7.00)");
+        let splunk = alert.splunk_on_call.as_ref().unwrap();
+        assert_eq!(splunk.message_type, "CRITICAL");
+        assert_eq!(splunk.entity_id, key);
+
+        let incident = IncidentMetrics {
+            duration_secs: 65,
+            peak_value: 9.0,
+            affected_traces: 3,
+            search_url: None,
+        };
+        let resolution = Notification::resolution(&rule, &event, &incident);
+        let Some(OpsgenieRequest::Close(alias, close)) = &resolution.opsgenie else {
+            panic!("resolutions close Opsgenie alerts");
+        };
+        assert_eq!(alias, &key);
+        assert_eq!(close.note, "High error rate resolved after 1m 5s (peak 9.00, 3 traces affected)");
+        let splunk = resolution.splunk_on_call.as_ref().unwrap();
+        assert_eq!(splunk.message_type, "RECOVERY");
+        assert_eq!(splunk.entity_id, key);
+        assert!(resolution.slack.is_none());
+    }
+
+    #[test]
+    fn test_acknowledgement_updates_the_alert_incident() {
+        let rule = AlertRule::new("High error rate", "error_rate", Operator::Gt, 5.0);
+        let mut event = create_test_event(&rule);
+        let notifier = NotificationSender::new();
+        assert!(notifier.acknowledgement(&rule, &event).is_none());
+
+        event.status = AlertStatus::Acknowledged;
+        event.acknowledgement = Some(Acknowledgement {
+            acknowledged_by: "dana".to_string(),
+            acknowledged_at: Utc::now(),
+            comment: Some("rolling back".to_string()),
+            assignee: Some("sam".to_string()),
+        });
+        let key = alert_key(&rule, &event);
+        let ack = notifier.acknowledgement(&rule, &event).unwrap();
+
+        let pagerduty = ack.pagerduty.as_ref().unwrap();
+        assert_eq!(pagerduty.event_action, "acknowledge");
+        assert_eq!(pagerduty.dedup_key, key);
+        let Some(OpsgenieRequest::Acknowledge(alias, opsgenie)) = &ack.opsgenie else {
+            panic!("acknowledgements acknowledge Opsgenie alerts");
+        };
+        assert_eq!(alias, &key);
+        assert_eq!(opsgenie.user, "dana");
+        assert_eq!(opsgenie.note, "acked by dana, assigned to sam: rolling back");
+        let splunk = ack.splunk_on_call.as_ref().unwrap();
+        assert_eq!(splunk.message_type, "ACKNOWLEDGEMENT");
+        assert_eq!(splunk.entity_id, key);
+        assert_eq!(ack.slack.as_ref().unwrap().attachments[0].text, opsgenie.note);
+    }
+
+    #[test]
+    fn test_trace_links_use_the_public_url() {
+        let rule = AlertRule::new("High error rate", "error_rate", Operator::Gt, 5.0);
+        let event = AlertEvent {
+            trace_ids: vec!["t1".to_string()],
+            ..create_test_event(&rule)
+        };
+
+        let links = NotificationSender::new()
+            .with_public_url(Some("https://agenttrace.example.com/".to_string()))
+            .trace_links(&event);
+        assert_eq!(links[0].url.as_deref(), Some("https://agenttrace.example.com/traces/t1"));
+        assert_eq!(links[0].command, "agenttrace traces show t1");
+
+        // Without a public URL only the CLI hint is given
+        let links = NotificationSender::new().trace_links(&event);
+        assert_eq!(links[0].url, None);
+    }
+
+    #[test]
+    fn test_digest_resolution_closes_the_digest_incident() {
+        let rule = AlertRule {
+            severity: Severity::Warning,
+            notification_channels: vec![NotificationChannel::SplunkOnCall {
+                api_key: "key".to_string(),
+                routing_key: "team".to_string(),
+            }],
+            ..AlertRule::new("Slow agent", "p99_latency", Operator::Gt, 5.0)
+        };
+        let event = create_test_event(&rule);
+        let queue = DigestQueue::new();
+        queue.enqueue(&rule, &event, chrono::Duration::zero());
+        let digest = queue.take_due(Utc::now()).pop().unwrap();
+
+        let sent = Notification::digest(&digest);
+        let closed = Notification::digest_resolution(&digest);
+        assert_eq!(opsgenie_alias(&sent), opsgenie_alias(&closed));
+        assert!(matches!(sent.opsgenie, Some(OpsgenieRequest::Create(ref a)) if a.priority == "P3"));
+        assert!(matches!(closed.opsgenie, Some(OpsgenieRequest::Close(..))));
+
+        let (sent, closed) = (sent.splunk_on_call.unwrap(), closed.splunk_on_call.unwrap());
+        assert_eq!(sent.entity_id, closed.entity_id);
+        assert_eq!(sent.message_type, "WARNING");
+        assert_eq!(closed.message_type, "RECOVERY");
+        assert_eq!(closed.entity_display_name, format!("[RESOLVED] {}", sent.entity_display_name));
+        assert!(!Notification::digest_resolution(&digest).is_for(&NotificationChannel::Slack {
+            webhook_url: "https://hooks.slack.com/x".to_string(),
+            channel: None,
+        }));
+    }
+
+    #[test]
+    fn test_opsgenie_priority() {
+        assert_eq!(opsgenie_priority(Severity::Critical), "P1");
+        assert_eq!(opsgenie_priority(Severity::Warning), "P3");
+        assert_eq!(opsgenie_priority(Severity::Info), "P5");
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("exactly10!", 10), "exactly10!");
+        assert_eq!(truncate_chars("a longer message", 8), "a longe…");
+        // Counts characters, so multi-byte text isn't cut mid-character
+        assert_eq!(truncate_chars("ééééé", 3), "éé…");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(42), "42s");
+        assert_eq!(format_duration(65), "1m 5s");
+        assert_eq!(format_duration(3599), "59m 59s");
+        assert_eq!(format_duration(3900), "1h 5m");
+        assert_eq!(format_duration(90_061), "25h 1m");
+    }
+}
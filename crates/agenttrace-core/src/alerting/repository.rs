@@ -1,14 +1,21 @@
 //! Alert repository for storing and querying alert rules and events
 
-use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, QueryBuilder};
 use uuid::Uuid;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::models::alert::{
-    AlertEvent, AlertRule, AlertRuleInput, AlertStatus, ConditionType, NotificationChannel,
-    NotificationRecord, Operator, Severity,
+    Acknowledgement, AlertEvent, AlertIncident, AlertRule, AlertRuleInput, AlertRuleRevision, AlertRuleUpdate,
+    AlertStatus, ConditionType,
+    EscalationStep, IncidentMetrics, NotificationChannel, NotificationRecord, NotificationRetry, Operator, RuleSchedule, RuleState,
+    RetryMessage, Severity, DEFAULT_CONSECUTIVE_FAILURES, DEFAULT_EVALUATION_INTERVAL_SECONDS,
+    DEFAULT_WINDOW_MINUTES,
 };
+use crate::models::{AuditAction, AuditChange};
+
+/// Times a revision insert is tried while concurrent changes take its number
+const REVISION_ATTEMPTS: u32 = 5;
 
 /// Repository for alert rules and events
 #[derive(Clone)]
@@ -40,11 +47,14 @@ impl AlertRepository {
             metric: input.metric,
             operator: input.operator,
             threshold: input.threshold,
-            window_minutes: input.window_minutes.unwrap_or(5),
-            evaluation_interval_seconds: input.evaluation_interval_seconds.unwrap_or(60),
-            consecutive_failures: input.consecutive_failures.unwrap_or(1),
+            window_minutes: input.window_minutes.unwrap_or(DEFAULT_WINDOW_MINUTES),
+            evaluation_interval_seconds: input.evaluation_interval_seconds.unwrap_or(DEFAULT_EVALUATION_INTERVAL_SECONDS),
+            consecutive_failures: input.consecutive_failures.unwrap_or(DEFAULT_CONSECUTIVE_FAILURES),
+            schedule: input.schedule,
             severity: input.severity.unwrap_or_default(),
             notification_channels: input.notification_channels.unwrap_or_default(),
+            escalation: input.escalation.unwrap_or_default(),
+            message_template: input.message_template,
             enabled: input.enabled.unwrap_or(true),
             last_evaluated_at: None,
             last_triggered_at: None,
@@ -54,6 +64,8 @@ impl AlertRepository {
         };
 
         let channels_json = serde_json::to_value(&rule.notification_channels)?;
+        let escalation_json = serde_json::to_value(&rule.escalation)?;
+        let schedule_json = rule.schedule.as_ref().map(serde_json::to_value).transpose()?;
 
         sqlx::query(
             r#"
@@ -62,9 +74,12 @@ impl AlertRepository {
                 condition_type, metric, operator, threshold,
                 window_minutes, evaluation_interval_seconds, consecutive_failures,
                 severity, notification_channels, enabled,
-                created_at, updated_at
+                created_at, updated_at, schedule, message_template, escalation
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20, $21
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
             "#,
         )
         .bind(rule.id)
@@ -85,6 +100,9 @@ impl AlertRepository {
         .bind(rule.enabled)
         .bind(rule.created_at)
         .bind(rule.updated_at)
+        .bind(&schedule_json)
+        .bind(&rule.message_template)
+        .bind(&escalation_json)
         .execute(&self.pool)
         .await?;
 
@@ -105,6 +123,16 @@ impl AlertRepository {
         Ok(row.map(|r| r.into()))
     }
 
+    /// Get several rules by ID, skipping any that don't exist
+    pub async fn get_rules(&self, ids: &[Uuid]) -> Result<Vec<AlertRule>> {
+        let rows = sqlx::query_as::<_, AlertRuleRow>("SELECT * FROM alert_rules WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     /// List all rules
     pub async fn list_rules(&self) -> Result<Vec<AlertRule>> {
         let rows = sqlx::query_as::<_, AlertRuleRow>(
@@ -132,46 +160,65 @@ impl AlertRepository {
     }
 
     /// Update a rule
-    pub async fn update_rule(&self, id: Uuid, input: AlertRuleInput) -> Result<Option<AlertRule>> {
-        let channels_json = input
-            .notification_channels
-            .as_ref()
-            .map(|c| serde_json::to_value(c).ok())
-            .flatten();
+    ///
+    /// The name, condition and metric are always replaced; other fields left
+    /// out of the update keep their current values.
+    pub async fn update_rule(&self, id: Uuid, update: AlertRuleUpdate) -> Result<Option<AlertRule>> {
+        let mut query = QueryBuilder::<Postgres>::new("UPDATE alert_rules SET updated_at = ");
+        query.push_bind(Utc::now());
+        query.push(", name = ").push_bind(update.name);
+        query.push(", condition_type = ").push_bind(format!("{:?}", update.condition_type).to_lowercase());
+        query.push(", metric = ").push_bind(update.metric);
+        query.push(", operator = ").push_bind(format!("{:?}", update.operator).to_lowercase());
 
-        let result = sqlx::query(
-            r#"
-            UPDATE alert_rules SET
-                name = COALESCE($2, name),
-                description = COALESCE($3, description),
-                service_name = COALESCE($4, service_name),
-                environment = COALESCE($5, environment),
-                model_name = COALESCE($6, model_name),
-                threshold = COALESCE($7, threshold),
-                window_minutes = COALESCE($8, window_minutes),
-                evaluation_interval_seconds = COALESCE($9, evaluation_interval_seconds),
-                consecutive_failures = COALESCE($10, consecutive_failures),
-                notification_channels = COALESCE($11, notification_channels),
-                enabled = COALESCE($12, enabled),
-                updated_at = $13
-            WHERE id = $1
-            "#,
-        )
-        .bind(id)
-        .bind(&input.name)
-        .bind(&input.description)
-        .bind(&input.service_name)
-        .bind(&input.environment)
-        .bind(&input.model_name)
-        .bind(input.threshold)
-        .bind(input.window_minutes)
-        .bind(input.evaluation_interval_seconds)
-        .bind(input.consecutive_failures)
-        .bind(&channels_json)
-        .bind(input.enabled)
-        .bind(Utc::now())
-        .execute(&self.pool)
-        .await?;
+        // Fields left out keep their value; `null` clears one
+        if !update.description.is_keep() {
+            query.push(", description = ").push_bind(update.description.into_value());
+        }
+        if !update.service_name.is_keep() {
+            query.push(", service_name = ").push_bind(update.service_name.into_value());
+        }
+        if !update.environment.is_keep() {
+            query.push(", environment = ").push_bind(update.environment.into_value());
+        }
+        if !update.model_name.is_keep() {
+            query.push(", model_name = ").push_bind(update.model_name.into_value());
+        }
+        if !update.threshold.is_keep() {
+            query.push(", threshold = ").push_bind(update.threshold.into_value());
+        }
+        if let Some(window_minutes) = update.window_minutes {
+            query.push(", window_minutes = ").push_bind(window_minutes);
+        }
+        if let Some(interval) = update.evaluation_interval_seconds {
+            query.push(", evaluation_interval_seconds = ").push_bind(interval);
+        }
+        if let Some(failures) = update.consecutive_failures {
+            query.push(", consecutive_failures = ").push_bind(failures);
+        }
+        if !update.schedule.is_keep() {
+            let schedule_json = update.schedule.value().map(serde_json::to_value).transpose()?;
+            query.push(", schedule = ").push_bind(schedule_json);
+        }
+        if let Some(severity) = update.severity {
+            query.push(", severity = ").push_bind(format!("{:?}", severity).to_lowercase());
+        }
+        if let Some(channels) = update.notification_channels {
+            query.push(", notification_channels = ").push_bind(serde_json::to_value(channels)?);
+        }
+        if !update.escalation.is_keep() {
+            let escalation = update.escalation.into_value().unwrap_or_default();
+            query.push(", escalation = ").push_bind(serde_json::to_value(escalation)?);
+        }
+        if !update.message_template.is_keep() {
+            query.push(", message_template = ").push_bind(update.message_template.into_value());
+        }
+        if let Some(enabled) = update.enabled {
+            query.push(", enabled = ").push_bind(enabled);
+        }
+        query.push(" WHERE id = ").push_bind(id);
+
+        let result = query.build().execute(&self.pool).await?;
 
         if result.rows_affected() == 0 {
             return Ok(None);
@@ -190,6 +237,79 @@ impl AlertRepository {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Write a rule exactly as given, recreating it if it was deleted
+    ///
+    /// Used to revert a rule to an earlier revision. The rule keeps its
+    /// creation time and evaluation history.
+    pub async fn restore_rule(&self, rule: &AlertRule) -> Result<AlertRule> {
+        let channels_json = serde_json::to_value(&rule.notification_channels)?;
+        let escalation_json = serde_json::to_value(&rule.escalation)?;
+        let schedule_json = rule.schedule.as_ref().map(serde_json::to_value).transpose()?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO alert_rules (
+                id, name, description, service_name, environment, model_name,
+                condition_type, metric, operator, threshold,
+                window_minutes, evaluation_interval_seconds, consecutive_failures,
+                severity, notification_channels, enabled,
+                created_at, updated_at, schedule, message_template, escalation
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20, $21
+            )
+            ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                service_name = EXCLUDED.service_name,
+                environment = EXCLUDED.environment,
+                model_name = EXCLUDED.model_name,
+                condition_type = EXCLUDED.condition_type,
+                metric = EXCLUDED.metric,
+                operator = EXCLUDED.operator,
+                threshold = EXCLUDED.threshold,
+                window_minutes = EXCLUDED.window_minutes,
+                evaluation_interval_seconds = EXCLUDED.evaluation_interval_seconds,
+                consecutive_failures = EXCLUDED.consecutive_failures,
+                severity = EXCLUDED.severity,
+                notification_channels = EXCLUDED.notification_channels,
+                enabled = EXCLUDED.enabled,
+                updated_at = EXCLUDED.updated_at,
+                schedule = EXCLUDED.schedule,
+                message_template = EXCLUDED.message_template,
+                escalation = EXCLUDED.escalation
+            "#,
+        )
+        .bind(rule.id)
+        .bind(&rule.name)
+        .bind(&rule.description)
+        .bind(&rule.service_name)
+        .bind(&rule.environment)
+        .bind(&rule.model_name)
+        .bind(format!("{:?}", rule.condition_type).to_lowercase())
+        .bind(&rule.metric)
+        .bind(format!("{:?}", rule.operator).to_lowercase())
+        .bind(rule.threshold)
+        .bind(rule.window_minutes)
+        .bind(rule.evaluation_interval_seconds)
+        .bind(rule.consecutive_failures)
+        .bind(format!("{:?}", rule.severity).to_lowercase())
+        .bind(&channels_json)
+        .bind(rule.enabled)
+        .bind(rule.created_at)
+        .bind(Utc::now())
+        .bind(&schedule_json)
+        .bind(&rule.message_template)
+        .bind(&escalation_json)
+        .execute(&self.pool)
+        .await?;
+
+        self.get_rule(rule.id)
+            .await?
+            .ok_or_else(|| Error::internal(format!("Rule {} missing after restore", rule.id)))
+    }
+
     /// Update last evaluated time
     pub async fn update_last_evaluated(&self, id: Uuid) -> Result<()> {
         sqlx::query("UPDATE alert_rules SET last_evaluated_at = $2 WHERE id = $1")
@@ -212,41 +332,218 @@ impl AlertRepository {
         Ok(())
     }
 
+    // --- Rule Revisions ---
+
+    /// Record a change to a rule as its next revision
+    ///
+    /// Concurrent changes to one rule can both read the same latest
+    /// revision; the loser of the insert retries with the next number.
+    pub async fn record_rule_revision(
+        &self,
+        rule_id: Uuid,
+        action: AuditAction,
+        actor: &str,
+        before: Option<&AlertRule>,
+        after: Option<&AlertRule>,
+        reverted_to: Option<i32>,
+    ) -> Result<AlertRuleRevision> {
+        let before = before.map(serde_json::to_value).transpose()?;
+        let after = after.map(serde_json::to_value).transpose()?;
+        let changed_at = Utc::now();
+
+        let mut attempt = 1;
+        loop {
+            let inserted = sqlx::query_as::<_, AlertRuleRevisionRow>(
+                r#"
+                INSERT INTO alert_rule_revisions (id, rule_id, revision, action, actor, changed_at, before, after, reverted_to)
+                SELECT $1, $2, COALESCE(MAX(revision), 0) + 1, $3, $4, $5, $6, $7, $8
+                FROM alert_rule_revisions WHERE rule_id = $2
+                RETURNING id, rule_id, revision, action, actor, changed_at, before, after, reverted_to
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(rule_id)
+            .bind(action.as_str())
+            .bind(actor)
+            .bind(changed_at)
+            .bind(&before)
+            .bind(&after)
+            .bind(reverted_to)
+            .fetch_one(&self.pool)
+            .await;
+
+            match inserted {
+                Ok(row) => return Ok(row.into()),
+                Err(sqlx::Error::Database(db)) if db.is_unique_violation() && attempt < REVISION_ATTEMPTS => {
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// List a rule's revisions, newest first; kept after the rule is deleted
+    pub async fn list_rule_revisions(&self, rule_id: Uuid) -> Result<Vec<AlertRuleRevision>> {
+        let rows = sqlx::query_as::<_, AlertRuleRevisionRow>(
+            r#"
+            SELECT id, rule_id, revision, action, actor, changed_at, before, after, reverted_to
+            FROM alert_rule_revisions
+            WHERE rule_id = $1
+            ORDER BY revision DESC
+            "#,
+        )
+        .bind(rule_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Get one of a rule's revisions
+    pub async fn get_rule_revision(&self, rule_id: Uuid, revision: i32) -> Result<Option<AlertRuleRevision>> {
+        let row = sqlx::query_as::<_, AlertRuleRevisionRow>(
+            r#"
+            SELECT id, rule_id, revision, action, actor, changed_at, before, after, reverted_to
+            FROM alert_rule_revisions
+            WHERE rule_id = $1 AND revision = $2
+            "#,
+        )
+        .bind(rule_id)
+        .bind(revision)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(Into::into))
+    }
+
+    // --- Evaluator State ---
+
+    /// Load the saved evaluator state of every rule
+    pub async fn list_rule_states(&self) -> Result<Vec<RuleState>> {
+        let rows: Vec<(Uuid, i32, Option<f64>)> = sqlx::query_as(
+            "SELECT rule_id, consecutive_failures, peak_value FROM alert_rule_state",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(rule_id, consecutive_failures, peak_value)| RuleState {
+                rule_id,
+                consecutive_failures,
+                peak_value,
+            })
+            .collect())
+    }
+
+    /// Save a rule's evaluator state
+    pub async fn save_rule_state(&self, state: &RuleState) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO alert_rule_state (rule_id, consecutive_failures, peak_value, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (rule_id) DO UPDATE SET
+                consecutive_failures = EXCLUDED.consecutive_failures,
+                peak_value = EXCLUDED.peak_value,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(state.rule_id)
+        .bind(state.consecutive_failures)
+        .bind(state.peak_value)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forget a rule's evaluator state once it has recovered
+    pub async fn clear_rule_state(&self, rule_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM alert_rule_state WHERE rule_id = $1")
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     // --- Alert Events ---
 
     /// Create an alert event
     pub async fn create_event(&self, event: &AlertEvent) -> Result<()> {
-        let trace_ids_json = serde_json::to_value(&event.trace_ids)?;
-        let notifications_json = serde_json::to_value(&event.notifications_sent)?;
+        insert_event(&self.pool, event).await
+    }
 
-        sqlx::query(
+    /// Create an alert event, grouping it into the open incident for its
+    /// service that last saw an event within `window`, or into a new one
+    ///
+    /// Sets `event.incident_id` to the incident it joined.
+    pub async fn create_correlated_event(&self, event: &mut AlertEvent, window: Duration) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // Locking the open incident serializes events racing to join it
+        let open = sqlx::query_as::<_, (Uuid, String)>(
             r#"
-            INSERT INTO alert_events (
-                id, rule_id, triggered_at, status, severity, message,
-                metric_value, threshold_value, service_name, trace_ids,
-                notifications_sent, metadata
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            SELECT id, severity FROM alert_incidents
+            WHERE status <> 'resolved'
+              AND service_name IS NOT DISTINCT FROM $1
+              AND last_event_at >= $2
+            ORDER BY last_event_at DESC
+            LIMIT 1
+            FOR UPDATE
             "#,
         )
-        .bind(event.id)
-        .bind(event.rule_id)
-        .bind(event.triggered_at)
-        .bind(format!("{:?}", event.status).to_lowercase())
-        .bind(format!("{:?}", event.severity).to_lowercase())
-        .bind(&event.message)
-        .bind(event.metric_value)
-        .bind(event.threshold_value)
         .bind(&event.service_name)
-        .bind(&trace_ids_json)
-        .bind(&notifications_json)
-        .bind(&event.metadata)
-        .execute(&self.pool)
+        .bind(event.triggered_at - window)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        let incident_id = match open {
+            Some((id, severity)) => {
+                let severity = parse_severity(&severity).max(event.severity);
+                sqlx::query(
+                    r#"
+                    UPDATE alert_incidents SET
+                        status = 'active',
+                        severity = $2,
+                        last_event_at = GREATEST(last_event_at, $3),
+                        event_count = event_count + 1
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(id)
+                .bind(format!("{:?}", severity).to_lowercase())
+                .bind(event.triggered_at)
+                .execute(&mut *tx)
+                .await?;
+                id
+            }
+            None => {
+                let id = Uuid::new_v4();
+                sqlx::query(
+                    r#"
+                    INSERT INTO alert_incidents (
+                        id, service_name, status, severity, started_at, last_event_at, event_count
+                    )
+                    VALUES ($1, $2, 'active', $3, $4, $4, 1)
+                    "#,
+                )
+                .bind(id)
+                .bind(&event.service_name)
+                .bind(format!("{:?}", event.severity).to_lowercase())
+                .bind(event.triggered_at)
+                .execute(&mut *tx)
+                .await?;
+                id
+            }
+        };
+
+        event.incident_id = Some(incident_id);
+        insert_event(&mut *tx, event).await?;
+        tx.commit().await?;
+
         Ok(())
     }
-
     /// Get an event by ID
     pub async fn get_event(&self, id: Uuid) -> Result<Option<AlertEvent>> {
         let row = sqlx::query_as::<_, AlertEventRow>(
@@ -281,6 +578,56 @@ impl AlertRepository {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    /// List the most recent events of each of several rules, up to `limit`
+    /// per rule
+    pub async fn list_events_for_rules(&self, rule_ids: &[Uuid], limit: i64) -> Result<Vec<AlertEvent>> {
+        let rows = sqlx::query_as::<_, AlertEventRow>(
+            r#"
+            SELECT e.* FROM unnest($1::uuid[]) AS r(id)
+            CROSS JOIN LATERAL (
+                SELECT * FROM alert_events
+                WHERE rule_id = r.id
+                ORDER BY triggered_at DESC
+                LIMIT $2
+            ) e
+            ORDER BY e.triggered_at DESC
+            "#,
+        )
+        .bind(rule_ids)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// List the events sampling each of several traces, up to `limit` per
+    /// trace, paired with the trace they sampled
+    pub async fn list_events_for_traces(
+        &self,
+        trace_ids: &[String],
+        limit: i64,
+    ) -> Result<Vec<(String, AlertEvent)>> {
+        let rows = sqlx::query_as::<_, TraceEventRow>(
+            r#"
+            SELECT t.trace_id AS sampled_trace_id, e.* FROM unnest($1::text[]) AS t(trace_id)
+            CROSS JOIN LATERAL (
+                SELECT * FROM alert_events
+                WHERE trace_ids @> jsonb_build_array(t.trace_id)
+                ORDER BY triggered_at DESC
+                LIMIT $2
+            ) e
+            ORDER BY e.triggered_at DESC
+            "#,
+        )
+        .bind(trace_ids)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| (r.sampled_trace_id, r.event.into())).collect())
+    }
+
     /// List active events
     pub async fn list_active_events(&self) -> Result<Vec<AlertEvent>> {
         let rows = sqlx::query_as::<_, AlertEventRow>(
@@ -296,6 +643,21 @@ impl AlertRepository {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
+    /// List events that haven't resolved, including acknowledged ones
+    pub async fn list_open_events(&self) -> Result<Vec<AlertEvent>> {
+        let rows = sqlx::query_as::<_, AlertEventRow>(
+            r#"
+            SELECT * FROM alert_events
+            WHERE status <> 'resolved'
+            ORDER BY triggered_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
     /// List recent events
     pub async fn list_recent_events(
         &self,
@@ -318,31 +680,193 @@ impl AlertRepository {
         Ok(rows.into_iter().map(|r| r.into()).collect())
     }
 
-    /// Resolve an event
-    pub async fn resolve_event(&self, id: Uuid) -> Result<()> {
+    /// Count events triggered in a window, by severity
+    pub async fn count_events_by_severity(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<(Severity, i64)>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            r#"
+            SELECT severity, COUNT(*) FROM alert_events
+            WHERE triggered_at >= $1 AND triggered_at < $2
+            GROUP BY severity
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(severity, count)| (parse_severity(&severity), count))
+            .collect())
+    }
+
+    /// Resolve an event, recording the incident's metrics in its metadata
+    pub async fn resolve_event(
+        &self,
+        id: Uuid,
+        resolved_at: DateTime<Utc>,
+        incident: &IncidentMetrics,
+    ) -> Result<()> {
         sqlx::query(
             r#"
             UPDATE alert_events
-            SET status = 'resolved', resolved_at = $2
+            SET status = 'resolved',
+                resolved_at = $2,
+                metadata = metadata || jsonb_build_object('incident', $3::jsonb)
             WHERE id = $1
             "#,
         )
         .bind(id)
-        .bind(Utc::now())
+        .bind(resolved_at)
+        .bind(serde_json::to_value(incident)?)
+        .execute(&self.pool)
+        .await?;
+
+        // The incident resolves with its last open event
+        sqlx::query(
+            r#"
+            UPDATE alert_incidents i
+            SET status = 'resolved', resolved_at = $2
+            FROM alert_events e
+            WHERE e.id = $1
+              AND i.id = e.incident_id
+              AND i.status <> 'resolved'
+              AND NOT EXISTS (
+                  SELECT 1 FROM alert_events o
+                  WHERE o.incident_id = i.id AND o.status <> 'resolved'
+              )
+            "#,
+        )
+        .bind(id)
+        .bind(resolved_at)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    /// Acknowledge an event
-    pub async fn acknowledge_event(&self, id: Uuid) -> Result<()> {
-        sqlx::query("UPDATE alert_events SET status = 'acknowledged' WHERE id = $1")
+    /// Acknowledge an event that hasn't resolved, recording who did and
+    /// what they said
+    ///
+    /// Acknowledging again replaces the comment and assignee. Returns false
+    /// if the event doesn't exist or has resolved.
+    pub async fn acknowledge_event(&self, id: Uuid, ack: &Acknowledgement) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE alert_events SET
+                status = 'acknowledged',
+                acknowledged_by = $2,
+                acknowledged_at = $3,
+                ack_comment = $4,
+                assignee = $5
+            WHERE id = $1 AND status <> 'resolved'
+            "#,
+        )
+        .bind(id)
+        .bind(&ack.acknowledged_by)
+        .bind(ack.acknowledged_at)
+        .bind(&ack.comment)
+        .bind(&ack.assignee)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // --- Alert Incidents ---
+
+    /// List incidents started since a time, newest first
+    pub async fn list_incidents(&self, since: DateTime<Utc>, limit: i64) -> Result<Vec<AlertIncident>> {
+        let rows = sqlx::query_as::<_, AlertIncidentRow>(
+            r#"
+            SELECT * FROM alert_incidents
+            WHERE started_at >= $1 OR status <> 'resolved'
+            ORDER BY started_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Get an incident by ID
+    pub async fn get_incident(&self, id: Uuid) -> Result<Option<AlertIncident>> {
+        let row = sqlx::query_as::<_, AlertIncidentRow>("SELECT * FROM alert_incidents WHERE id = $1")
             .bind(id)
-            .execute(&self.pool)
+            .fetch_optional(&self.pool)
             .await?;
 
-        Ok(())
+        Ok(row.map(|r| r.into()))
+    }
+
+    /// List the events grouped into an incident, oldest first
+    pub async fn list_incident_events(&self, id: Uuid) -> Result<Vec<AlertEvent>> {
+        let rows = sqlx::query_as::<_, AlertEventRow>(
+            r#"
+            SELECT * FROM alert_events
+            WHERE incident_id = $1
+            ORDER BY triggered_at ASC
+            "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Acknowledge an incident and its active events
+    ///
+    /// Returns the events acknowledged, or None if the incident doesn't
+    /// exist.
+    pub async fn acknowledge_incident(&self, id: Uuid, ack: &Acknowledgement) -> Result<Option<Vec<Uuid>>> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE alert_incidents SET status = 'acknowledged' WHERE id = $1 AND status = 'active'",
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            let exists: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM alert_incidents WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            return Ok(exists.map(|_| Vec::new()));
+        }
+
+        let acknowledged: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            UPDATE alert_events SET
+                status = 'acknowledged',
+                acknowledged_by = $2,
+                acknowledged_at = $3,
+                ack_comment = $4,
+                assignee = $5
+            WHERE incident_id = $1 AND status = 'active'
+            RETURNING id
+            "#,
+        )
+        .bind(id)
+        .bind(&ack.acknowledged_by)
+        .bind(ack.acknowledged_at)
+        .bind(&ack.comment)
+        .bind(&ack.assignee)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Some(acknowledged.into_iter().map(|(id,)| id).collect()))
     }
 
     /// Update event notifications
@@ -361,6 +885,186 @@ impl AlertRepository {
 
         Ok(())
     }
+
+    /// Append notification records to an event, keeping earlier ones
+    pub async fn append_event_notifications(
+        &self,
+        id: Uuid,
+        notifications: &[NotificationRecord],
+    ) -> Result<()> {
+        let json = serde_json::to_value(notifications)?;
+
+        sqlx::query(
+            "UPDATE alert_events SET notifications_sent = COALESCE(notifications_sent, '[]'::jsonb) || $2 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(&json)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record that an event escalated through `step`, appending the
+    /// notifications sent for it
+    pub async fn record_escalation(
+        &self,
+        id: Uuid,
+        step: usize,
+        notifications: &[NotificationRecord],
+    ) -> Result<()> {
+        let json = serde_json::to_value(notifications)?;
+
+        sqlx::query(
+            r#"
+            UPDATE alert_events
+            SET notifications_sent = COALESCE(notifications_sent, '[]'::jsonb) || $2,
+                metadata = metadata || jsonb_build_object('escalation_step', $3::int)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&json)
+        .bind(step as i32)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // --- Notification Retries ---
+
+    /// Queue a failed notification to be sent again
+    pub async fn queue_retry(&self, retry: &NotificationRetry) -> Result<()> {
+        // Alert and resolution retries reference their rule so they go with it
+        let (rule_id, event_id, message) = match &retry.message {
+            RetryMessage::Alert { rule_id, event_id } => (Some(*rule_id), Some(*event_id), None),
+            message @ RetryMessage::Resolution { rule_id, event_id } => {
+                (Some(*rule_id), Some(*event_id), Some(serde_json::to_value(message)?))
+            }
+            message => (None, None, Some(serde_json::to_value(message)?)),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO notification_retries (
+                id, rule_id, event_id, message, channel, channel_type, attempts, next_attempt_at,
+                last_error, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(retry.id)
+        .bind(rule_id)
+        .bind(event_id)
+        .bind(message)
+        .bind(serde_json::to_value(&retry.channel)?)
+        .bind(retry.channel.channel_type())
+        .bind(i32::try_from(retry.attempts).unwrap_or(i32::MAX))
+        .bind(retry.next_attempt_at)
+        .bind(&retry.last_error)
+        .bind(retry.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Claim retries due by `now`, holding them until `lease_until` so
+    /// other collectors don't send them at the same time
+    pub async fn claim_due_retries(
+        &self,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<NotificationRetry>> {
+        let rows = sqlx::query_as::<_, NotificationRetryRow>(
+            r#"
+            UPDATE notification_retries SET next_attempt_at = $2
+            WHERE id IN (
+                SELECT id FROM notification_retries
+                WHERE next_attempt_at <= $1
+                ORDER BY next_attempt_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, rule_id, event_id, message, channel, attempts, next_attempt_at, last_error, created_at
+            "#,
+        )
+        .bind(now)
+        .bind(lease_until)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+
+    /// Record a failed retry and when to try again
+    pub async fn reschedule_retry(
+        &self,
+        id: Uuid,
+        attempts: u32,
+        next_attempt_at: DateTime<Utc>,
+        error: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE notification_retries SET attempts = $2, next_attempt_at = $3, last_error = $4 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(i32::try_from(attempts).unwrap_or(i32::MAX))
+        .bind(next_attempt_at)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove a retry once it was delivered or given up on
+    pub async fn delete_retry(&self, id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM notification_retries WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+
+/// Insert an event on the pool or within a transaction
+async fn insert_event<'e>(executor: impl PgExecutor<'e>, event: &AlertEvent) -> Result<()> {
+    let trace_ids_json = serde_json::to_value(&event.trace_ids)?;
+    let notifications_json = serde_json::to_value(&event.notifications_sent)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO alert_events (
+            id, rule_id, triggered_at, status, severity, message,
+            metric_value, threshold_value, service_name, trace_ids,
+            notifications_sent, metadata, incident_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#,
+    )
+    .bind(event.id)
+    .bind(event.rule_id)
+    .bind(event.triggered_at)
+    .bind(format!("{:?}", event.status).to_lowercase())
+    .bind(format!("{:?}", event.severity).to_lowercase())
+    .bind(&event.message)
+    .bind(event.metric_value)
+    .bind(event.threshold_value)
+    .bind(&event.service_name)
+    .bind(&trace_ids_json)
+    .bind(&notifications_json)
+    .bind(&event.metadata)
+    .bind(event.incident_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
 }
 
 // Database row types for mapping
@@ -380,8 +1084,11 @@ struct AlertRuleRow {
     window_minutes: i32,
     evaluation_interval_seconds: i32,
     consecutive_failures: i32,
+    schedule: Option<serde_json::Value>,
     severity: String,
     notification_channels: serde_json::Value,
+    escalation: serde_json::Value,
+    message_template: Option<String>,
     enabled: bool,
     last_evaluated_at: Option<DateTime<Utc>>,
     last_triggered_at: Option<DateTime<Utc>>,
@@ -397,6 +1104,7 @@ impl From<AlertRuleRow> for AlertRule {
             "anomaly" => ConditionType::Anomaly,
             "rate_change" => ConditionType::RateChange,
             "absence" => ConditionType::Absence,
+            "trace" => ConditionType::Trace,
             _ => ConditionType::Threshold,
         };
 
@@ -420,6 +1128,11 @@ impl From<AlertRuleRow> for AlertRule {
         let notification_channels: Vec<NotificationChannel> =
             serde_json::from_value(row.notification_channels).unwrap_or_default();
 
+        let schedule: Option<RuleSchedule> = row.schedule.and_then(|s| serde_json::from_value(s).ok());
+
+        let escalation: Vec<EscalationStep> =
+            serde_json::from_value(row.escalation).unwrap_or_default();
+
         AlertRule {
             id: row.id,
             name: row.name,
@@ -434,8 +1147,11 @@ impl From<AlertRuleRow> for AlertRule {
             window_minutes: row.window_minutes,
             evaluation_interval_seconds: row.evaluation_interval_seconds,
             consecutive_failures: row.consecutive_failures,
+            schedule,
             severity,
             notification_channels,
+            escalation,
+            message_template: row.message_template,
             enabled: row.enabled,
             last_evaluated_at: row.last_evaluated_at,
             last_triggered_at: row.last_triggered_at,
@@ -446,6 +1162,14 @@ impl From<AlertRuleRow> for AlertRule {
     }
 }
 
+/// An event along with the trace it was found by
+#[derive(sqlx::FromRow)]
+struct TraceEventRow {
+    sampled_trace_id: String,
+    #[sqlx(flatten)]
+    event: AlertEventRow,
+}
+
 #[derive(sqlx::FromRow)]
 struct AlertEventRow {
     id: Uuid,
@@ -461,6 +1185,11 @@ struct AlertEventRow {
     trace_ids: serde_json::Value,
     notifications_sent: serde_json::Value,
     metadata: serde_json::Value,
+    incident_id: Option<Uuid>,
+    acknowledged_by: Option<String>,
+    acknowledged_at: Option<DateTime<Utc>>,
+    ack_comment: Option<String>,
+    assignee: Option<String>,
 }
 
 impl From<AlertEventRow> for AlertEvent {
@@ -497,6 +1226,248 @@ impl From<AlertEventRow> for AlertEvent {
             trace_ids,
             notifications_sent,
             metadata: row.metadata,
+            incident_id: row.incident_id,
+            acknowledgement: row.acknowledged_by.zip(row.acknowledged_at).map(
+                |(acknowledged_by, acknowledged_at)| Acknowledgement {
+                    acknowledged_by,
+                    acknowledged_at,
+                    comment: row.ack_comment,
+                    assignee: row.assignee,
+                },
+            ),
         }
     }
 }
+
+#[derive(sqlx::FromRow)]
+struct AlertIncidentRow {
+    id: Uuid,
+    service_name: Option<String>,
+    status: String,
+    severity: String,
+    started_at: DateTime<Utc>,
+    last_event_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+    event_count: i32,
+}
+
+impl From<AlertIncidentRow> for AlertIncident {
+    fn from(row: AlertIncidentRow) -> Self {
+        let status = match row.status.as_str() {
+            "acknowledged" => AlertStatus::Acknowledged,
+            "resolved" => AlertStatus::Resolved,
+            _ => AlertStatus::Active,
+        };
+
+        AlertIncident {
+            id: row.id,
+            service_name: row.service_name,
+            status,
+            severity: parse_severity(&row.severity),
+            started_at: row.started_at,
+            last_event_at: row.last_event_at,
+            resolved_at: row.resolved_at,
+            event_count: row.event_count,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AlertRuleRevisionRow {
+    id: Uuid,
+    rule_id: Uuid,
+    revision: i32,
+    action: String,
+    actor: String,
+    changed_at: DateTime<Utc>,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+    reverted_to: Option<i32>,
+}
+
+impl From<AlertRuleRevisionRow> for AlertRuleRevision {
+    fn from(row: AlertRuleRevisionRow) -> Self {
+        let action = AuditAction::parse(&row.action).unwrap_or(AuditAction::Update);
+        let changes = AuditChange::new(action, "alert_rule", row.rule_id, row.before.as_ref(), row.after.as_ref())
+            .diff();
+
+        AlertRuleRevision {
+            id: row.id,
+            rule_id: row.rule_id,
+            revision: row.revision,
+            action,
+            actor: row.actor,
+            changed_at: row.changed_at,
+            before: row.before,
+            after: row.after,
+            changes,
+            reverted_to: row.reverted_to,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct NotificationRetryRow {
+    id: Uuid,
+    rule_id: Option<Uuid>,
+    event_id: Option<Uuid>,
+    message: Option<serde_json::Value>,
+    channel: serde_json::Value,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<NotificationRetryRow> for NotificationRetry {
+    type Error = Error;
+
+    fn try_from(row: NotificationRetryRow) -> Result<Self> {
+        let message = match (row.message, row.rule_id, row.event_id) {
+            (Some(message), _, _) => serde_json::from_value(message)?,
+            (None, Some(rule_id), Some(event_id)) => RetryMessage::Alert { rule_id, event_id },
+            _ => return Err(Error::internal("Notification retry has no message")),
+        };
+
+        Ok(NotificationRetry {
+            id: row.id,
+            message,
+            channel: serde_json::from_value(row.channel)?,
+            attempts: u32::try_from(row.attempts).unwrap_or(0),
+            next_attempt_at: row.next_attempt_at,
+            last_error: row.last_error,
+            created_at: row.created_at,
+        })
+    }
+}
+
+fn parse_severity(s: &str) -> Severity {
+    match s {
+        "info" => Severity::Info,
+        "critical" => Severity::Critical,
+        _ => Severity::Warning,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry_row(
+        rule_id: Option<Uuid>,
+        event_id: Option<Uuid>,
+        message: Option<serde_json::Value>,
+    ) -> NotificationRetryRow {
+        NotificationRetryRow {
+            id: Uuid::new_v4(),
+            rule_id,
+            event_id,
+            message,
+            channel: serde_json::json!({"type": "pager_duty", "routing_key": "R0UT1NG"}),
+            attempts: 2,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn revision_row(
+        revision: i32,
+        action: AuditAction,
+        before: Option<&AlertRule>,
+        after: Option<&AlertRule>,
+    ) -> AlertRuleRevisionRow {
+        AlertRuleRevisionRow {
+            id: Uuid::new_v4(),
+            rule_id: after.or(before).map_or_else(Uuid::new_v4, |rule| rule.id),
+            revision,
+            action: action.as_str().to_string(),
+            actor: "dana".to_string(),
+            changed_at: Utc::now(),
+            before: before.map(|rule| serde_json::to_value(rule).unwrap()),
+            after: after.map(|rule| serde_json::to_value(rule).unwrap()),
+            reverted_to: None,
+        }
+    }
+
+    #[test]
+    fn test_rule_history_lists_changed_fields() {
+        let created = AlertRule::new("Errors", "error_rate", Operator::Gt, 0.05);
+        let raised = AlertRule { threshold: Some(0.1), ..created.clone() };
+
+        let update = AlertRuleRevision::from(revision_row(2, AuditAction::Update, Some(&created), Some(&raised)));
+        assert_eq!(update.action, AuditAction::Update);
+        assert_eq!(
+            update.changes,
+            Some(serde_json::json!({ "threshold": { "before": 0.05, "after": 0.1 } }))
+        );
+
+        let deletion = AlertRuleRevision::from(revision_row(3, AuditAction::Delete, Some(&raised), None));
+        assert_eq!(deletion.action, AuditAction::Delete);
+        assert!(deletion.changes.unwrap()["threshold"]["after"].is_null());
+    }
+
+    #[test]
+    fn test_revert_restores_the_revision() {
+        let created = AlertRule::new("Errors", "error_rate", Operator::Gt, 0.05);
+        let raised = AlertRule { threshold: Some(0.1), ..created.clone() };
+
+        let creation = AlertRuleRevision::from(revision_row(1, AuditAction::Create, None, Some(&created)));
+        let restored = creation.restored_rule().unwrap();
+        assert_eq!(restored.id, created.id);
+        assert_eq!(restored.threshold, Some(0.05));
+        assert_eq!(restored.created_at, created.created_at);
+
+        // A deletion leaves nothing to restore
+        let deletion = AlertRuleRevision::from(revision_row(2, AuditAction::Delete, Some(&raised), None));
+        let err = deletion.restored_rule().unwrap_err();
+        assert!(err.to_string().contains("revert to the revision before it"));
+    }
+
+    #[test]
+    fn test_retry_rows_to_messages() {
+        let (rule_id, event_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let retry = NotificationRetry::try_from(retry_row(Some(rule_id), Some(event_id), None)).unwrap();
+        assert!(matches!(
+            retry.message,
+            RetryMessage::Alert { rule_id: r, event_id: e } if r == rule_id && e == event_id
+        ));
+        assert_eq!(retry.attempts, 2);
+
+        let resolution = serde_json::json!({
+            "kind": "slo_resolution",
+            "alert_id": Uuid::new_v4(),
+            "slo_id": Uuid::new_v4(),
+            "slo_name": "support-bot-availability",
+            "service_name": null,
+            "long_window_minutes": 60,
+            "short_window_minutes": 5,
+            "triggered_at": Utc::now(),
+            "resolved_at": Utc::now(),
+            "message": "cleared",
+        });
+        let retry = NotificationRetry::try_from(retry_row(None, None, Some(resolution))).unwrap();
+        assert!(matches!(retry.message, RetryMessage::SloResolution(r) if r.long_window_minutes == 60));
+
+        // Resolutions reference their rule like alerts, but keep their kind
+        let message = serde_json::to_value(RetryMessage::Resolution { rule_id, event_id }).unwrap();
+        let retry = NotificationRetry::try_from(retry_row(Some(rule_id), Some(event_id), Some(message))).unwrap();
+        assert!(matches!(
+            retry.message,
+            RetryMessage::Resolution { rule_id: r, event_id: e } if r == rule_id && e == event_id
+        ));
+
+        let digest = serde_json::json!({
+            "kind": "digest",
+            "channel": {"type": "pager_duty", "routing_key": "R0UT1NG"},
+            "window_start": Utc::now(),
+            "window_end": Utc::now(),
+            "groups": [],
+        });
+        let retry = NotificationRetry::try_from(retry_row(None, None, Some(digest))).unwrap();
+        assert!(matches!(retry.message, RetryMessage::Digest(d) if d.groups.is_empty()));
+
+        assert!(NotificationRetry::try_from(retry_row(None, Some(event_id), None)).is_err());
+    }
+}
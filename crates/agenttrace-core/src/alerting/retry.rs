@@ -0,0 +1,573 @@
+//! Notification delivery retries
+//!
+//! A notification that fails with a network error, a server error or rate
+//! limiting is queued in the database and sent again with exponential
+//! backoff until it is delivered or runs out of attempts, so a Slack outage
+//! of a few minutes doesn't lose the alert. Backoff is jittered so retries
+//! queued during an outage don't all hit the recovering service at once.
+//! Retries are only sent while they are still current: alerts while they
+//! are active, resolutions while they stay resolved, SLO burn-rate alerts
+//! while they are firing and budget alerts while the budget exists.
+//! Digests are always sent.
+//!
+//! A channel whose retries keep failing is reported to the configured
+//! failure alert channels, once until it delivers again.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::notifier::{Notification, NotificationResult, NotificationSender};
+use super::repository::AlertRepository;
+use crate::budgets::BudgetRepository;
+use crate::config::DeliveryConfig;
+use crate::error::Result;
+use crate::models::alert::{
+    AlertStatus, IncidentMetrics, NotificationChannel, NotificationRecord, NotificationRetry, RetryMessage,
+};
+use crate::slos::SloRepository;
+
+/// How often due retries are checked
+const RETRY_POLL_INTERVAL_SECS: u64 = 10;
+
+/// Most retries sent per check
+const RETRY_BATCH_SIZE: i64 = 50;
+
+/// How long a claimed retry is held before another collector may take it;
+/// longer than a send can take with the notifier's request timeout
+const RETRY_LEASE_SECS: i64 = 120;
+
+/// How many attempts a notification gets and how long to wait between them
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a policy from delivery configuration
+    pub fn new(config: &DeliveryConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            initial_backoff: Duration::from_secs(config.initial_backoff_seconds),
+            max_backoff: Duration::from_secs(config.max_backoff_seconds.max(config.initial_backoff_seconds)),
+        }
+    }
+
+    /// Whether a notification that has been attempted `attempts` times gets
+    /// another try
+    pub fn should_retry(&self, attempts: u32) -> bool {
+        attempts < self.max_attempts
+    }
+
+    /// Wait after the `attempts`th failed attempt
+    ///
+    /// Doubles from the initial backoff up to the maximum, then picks a
+    /// random wait between half and all of that.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let doublings = attempts.saturating_sub(1).min(31);
+        let full = self
+            .initial_backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff);
+        let half = full / 2;
+        let jitter_ms = rand::thread_rng().gen_range(0..=u64::try_from(half.as_millis()).unwrap_or(u64::MAX));
+
+        half + Duration::from_millis(jitter_ms)
+    }
+
+    /// When to try again after the `attempts`th failed attempt at `now`
+    pub fn next_attempt_at(&self, attempts: u32, now: DateTime<Utc>) -> DateTime<Utc> {
+        now + chrono::Duration::from_std(self.backoff(attempts)).unwrap_or_else(|_| chrono::Duration::zero())
+    }
+
+    /// Queue a message's failed notifications for retry, returning how
+    /// many were queued
+    ///
+    /// Failures that won't succeed on retry, like a rejected request, are
+    /// not queued.
+    pub async fn queue_failures(
+        &self,
+        repo: &AlertRepository,
+        message: &RetryMessage,
+        results: &[NotificationResult],
+    ) -> Result<usize> {
+        if !self.should_retry(1) {
+            return Ok(0);
+        }
+
+        let mut queued = 0;
+        for result in results {
+            if result.success || !result.retryable {
+                continue;
+            }
+
+            let retry = NotificationRetry {
+                id: Uuid::new_v4(),
+                message: message.clone(),
+                channel: result.channel.clone(),
+                attempts: 1,
+                next_attempt_at: self.next_attempt_at(1, result.sent_at),
+                last_error: result.error.clone(),
+                created_at: result.sent_at,
+            };
+            repo.queue_retry(&retry).await?;
+            queued += 1;
+
+            debug!(
+                retry_id = %retry.id,
+                channel = retry.channel.channel_type(),
+                next_attempt_at = %retry.next_attempt_at,
+                "Queued notification retry"
+            );
+        }
+
+        Ok(queued)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(&DeliveryConfig::default())
+    }
+}
+
+/// A notification channel whose deliveries keep failing
+#[derive(Debug, Clone, Serialize)]
+pub struct DeliveryFailure {
+    /// Type of the failing channel
+    pub channel_type: String,
+    /// The failing channel, described without its credentials
+    pub channel: String,
+    /// Failed attempts in a row
+    pub consecutive_failures: u32,
+    /// When the first of those attempts failed
+    pub failing_since: DateTime<Utc>,
+    /// Error from the latest attempt
+    pub last_error: Option<String>,
+    /// Human-readable summary
+    pub message: String,
+}
+
+impl DeliveryFailure {
+    fn new(
+        channel: &NotificationChannel,
+        consecutive_failures: u32,
+        failing_since: DateTime<Utc>,
+        last_error: Option<String>,
+    ) -> Self {
+        let description = describe_channel(channel);
+        let message = format!(
+            "Notifications to {} have failed {} times in a row since {}",
+            description,
+            consecutive_failures,
+            failing_since.format("%Y-%m-%d %H:%M UTC")
+        );
+
+        Self {
+            channel_type: channel.channel_type().to_string(),
+            channel: description,
+            consecutive_failures,
+            failing_since,
+            last_error,
+            message,
+        }
+    }
+
+    /// Deduplication key, one per failing channel
+    pub fn key(&self) -> String {
+        format!("agenttrace-delivery-{}", self.channel.replace(' ', "-").to_lowercase())
+    }
+}
+
+/// A channel's run of failed attempts
+struct ChannelHealth {
+    failures: u32,
+    since: DateTime<Utc>,
+    reported: bool,
+}
+
+/// Sends queued notification retries and reports channels that keep failing
+pub struct NotificationRetrier {
+    repo: AlertRepository,
+    slos: Option<SloRepository>,
+    budgets: Option<BudgetRepository>,
+    notifier: NotificationSender,
+    policy: RetryPolicy,
+    failure_threshold: u32,
+    failure_channels: Vec<NotificationChannel>,
+    /// Failing channels by fingerprint
+    health: Mutex<HashMap<String, ChannelHealth>>,
+}
+
+impl NotificationRetrier {
+    /// Create a retrier from delivery configuration
+    pub fn new(repo: AlertRepository, config: &DeliveryConfig) -> Self {
+        Self {
+            repo,
+            slos: None,
+            budgets: None,
+            notifier: NotificationSender::new(),
+            policy: RetryPolicy::new(config),
+            failure_threshold: config.failure_alert_threshold,
+            failure_channels: config.failure_alert_channels.clone(),
+            health: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Send notifications with this sender
+    pub fn with_notifier(mut self, notifier: NotificationSender) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Retry SLO burn-rate alert notifications from this repository;
+    /// without one they are dropped
+    pub fn with_slos(mut self, slos: SloRepository) -> Self {
+        self.slos = Some(slos);
+        self
+    }
+
+    /// Retry budget alert notifications from this repository; without one
+    /// they are dropped
+    pub fn with_budgets(mut self, budgets: BudgetRepository) -> Self {
+        self.budgets = Some(budgets);
+        self
+    }
+
+    /// Send due retries until the task is dropped
+    pub async fn start(&self) {
+        info!("Starting notification retrier");
+
+        let mut ticker = tokio::time::interval(Duration::from_secs(RETRY_POLL_INTERVAL_SECS));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.retry_due().await {
+                warn!("Failed to retry notifications: {}", e);
+            }
+        }
+    }
+
+    /// Send every retry that is due, returning how many were delivered
+    pub async fn retry_due(&self) -> Result<usize> {
+        let now = Utc::now();
+        let lease_until = now + chrono::Duration::seconds(RETRY_LEASE_SECS);
+        let mut delivered = 0;
+
+        for retry in self.repo.claim_due_retries(now, lease_until, RETRY_BATCH_SIZE).await? {
+            match self.retry(&retry).await {
+                Ok(true) => delivered += 1,
+                Ok(false) => {}
+                Err(e) => warn!(retry_id = %retry.id, "Failed to retry notification: {}", e),
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Send one retry, rescheduling it if it fails and has attempts left
+    async fn retry(&self, retry: &NotificationRetry) -> Result<bool> {
+        let Some(result) = self.resend(retry).await? else {
+            debug!(retry_id = %retry.id, "Dropping retry for a notification that is no longer current");
+            self.repo.delete_retry(retry.id).await?;
+            return Ok(false);
+        };
+
+        let attempt = retry.attempts + 1;
+        self.track(&retry.channel, &result).await;
+
+        if result.success {
+            info!(
+                retry_id = %retry.id,
+                channel = retry.channel.channel_type(),
+                attempt,
+                "Notification delivered on retry"
+            );
+        } else if result.retryable && self.policy.should_retry(attempt) {
+            let next_attempt_at = self.policy.next_attempt_at(attempt, result.sent_at);
+            debug!(
+                retry_id = %retry.id,
+                channel = retry.channel.channel_type(),
+                attempt,
+                next_attempt_at = %next_attempt_at,
+                "Notification retry failed"
+            );
+            self.repo
+                .reschedule_retry(retry.id, attempt, next_attempt_at, result.error.as_deref())
+                .await?;
+            return Ok(false);
+        } else {
+            warn!(
+                retry_id = %retry.id,
+                channel = retry.channel.channel_type(),
+                attempt,
+                error = result.error.as_deref().unwrap_or_default(),
+                "Giving up on notification"
+            );
+        }
+
+        let success = result.success;
+        let record = NotificationRecord {
+            attempt: Some(attempt),
+            ..result.into()
+        };
+        self.record(&retry.message, record).await?;
+        self.repo.delete_retry(retry.id).await?;
+
+        Ok(success)
+    }
+
+    /// Send a retry's message again, or None if it is no longer current,
+    /// like an alert that resolved
+    async fn resend(&self, retry: &NotificationRetry) -> Result<Option<NotificationResult>> {
+        let notification = match &retry.message {
+            RetryMessage::Alert { rule_id, event_id } => {
+                let event = self.repo.get_event(*event_id).await?;
+                let rule = self.repo.get_rule(*rule_id).await?;
+                let (Some(event), Some(rule)) = (event.filter(|e| e.status == AlertStatus::Active), rule) else {
+                    return Ok(None);
+                };
+                self.notifier.alert(&rule, &event)
+            }
+            RetryMessage::Resolution { rule_id, event_id } => {
+                let event = self.repo.get_event(*event_id).await?;
+                let rule = self.repo.get_rule(*rule_id).await?;
+                let (Some(event), Some(rule)) = (event.filter(|e| e.status == AlertStatus::Resolved), rule)
+                else {
+                    return Ok(None);
+                };
+                // Resolving stored the incident's metrics on the event
+                let Some(incident) = event
+                    .metadata
+                    .get("incident")
+                    .and_then(|i| serde_json::from_value::<IncidentMetrics>(i.clone()).ok())
+                else {
+                    return Ok(None);
+                };
+                Notification::resolution(&rule, &event, &incident)
+            }
+            RetryMessage::Digest(digest) => Notification::digest(digest),
+            RetryMessage::DigestResolution(digest) => Notification::digest_resolution(digest),
+            RetryMessage::SloAlert(alert) => {
+                let Some(slos) = &self.slos else {
+                    return Ok(None);
+                };
+                let firing = slos
+                    .firing_alert(alert.slo_id, alert.long_window_minutes, alert.short_window_minutes)
+                    .await?;
+                if firing != Some(alert.id) {
+                    return Ok(None);
+                }
+                Notification::slo_alert(alert)
+            }
+            RetryMessage::SloResolution(resolution) => {
+                let Some(slos) = &self.slos else {
+                    return Ok(None);
+                };
+                // Closing the incident would close the one it fired again with
+                let firing = slos
+                    .firing_alert(
+                        resolution.slo_id,
+                        resolution.long_window_minutes,
+                        resolution.short_window_minutes,
+                    )
+                    .await?;
+                if firing.is_some() {
+                    return Ok(None);
+                }
+                Notification::slo_resolution(resolution)
+            }
+            RetryMessage::BudgetAlert(alert) => {
+                let Some(budgets) = &self.budgets else {
+                    return Ok(None);
+                };
+                if budgets.get(alert.budget_id).await?.is_none() {
+                    return Ok(None);
+                }
+                Notification::budget_alert(alert)
+            }
+        };
+
+        Ok(Some(self.notifier.send(&retry.channel, &notification).await))
+    }
+
+    /// Record a retry's outcome with the notifications of what it sent
+    async fn record(&self, message: &RetryMessage, record: NotificationRecord) -> Result<()> {
+        let records = std::slice::from_ref(&record);
+        match message {
+            RetryMessage::Alert { event_id, .. } | RetryMessage::Resolution { event_id, .. } => {
+                self.repo.append_event_notifications(*event_id, records).await
+            }
+            RetryMessage::Digest(digest) | RetryMessage::DigestResolution(digest) => {
+                for event_id in digest.event_ids() {
+                    self.repo.append_event_notifications(event_id, records).await?;
+                }
+                Ok(())
+            }
+            RetryMessage::SloAlert(alert) => match &self.slos {
+                Some(slos) => slos.append_notifications(alert.id, records).await,
+                None => Ok(()),
+            },
+            RetryMessage::SloResolution(resolution) => match &self.slos {
+                Some(slos) => slos.append_notifications(resolution.alert_id, records).await,
+                None => Ok(()),
+            },
+            RetryMessage::BudgetAlert(alert) => match &self.budgets {
+                Some(budgets) => budgets.append_notifications(alert, records).await,
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Count a channel's failures, reporting it once they reach the threshold
+    async fn track(&self, channel: &NotificationChannel, result: &NotificationResult) {
+        let key = fingerprint(channel);
+
+        let failure = {
+            let mut health = self.health.lock();
+            if result.success {
+                if health.remove(&key).is_some_and(|h| h.reported) {
+                    info!(channel = %describe_channel(channel), "Notification channel recovered");
+                }
+                None
+            } else {
+                let entry = health.entry(key.clone()).or_insert(ChannelHealth {
+                    failures: 0,
+                    since: result.sent_at,
+                    reported: false,
+                });
+                entry.failures += 1;
+
+                let report = self.failure_threshold > 0
+                    && entry.failures >= self.failure_threshold
+                    && !entry.reported;
+                report.then(|| {
+                    entry.reported = true;
+                    DeliveryFailure::new(channel, entry.failures, entry.since, result.error.clone())
+                })
+            }
+        };
+
+        let Some(failure) = failure else {
+            return;
+        };
+
+        error!(
+            channel = %failure.channel,
+            failures = failure.consecutive_failures,
+            "Notification channel is failing"
+        );
+
+        // Never report a channel's failure through that same channel
+        let channels: Vec<NotificationChannel> = self
+            .failure_channels
+            .iter()
+            .filter(|c| fingerprint(c) != key)
+            .cloned()
+            .collect();
+        for result in self.notifier.send_all(&channels, &Notification::delivery_failure(&failure)).await {
+            if !result.success {
+                warn!(
+                    channel = %result.channel_type,
+                    error = result.error.as_deref().unwrap_or_default(),
+                    "Failed to report failing notification channel"
+                );
+            }
+        }
+    }
+}
+
+/// Short stable identifier for a channel, without revealing its credentials
+fn fingerprint(channel: &NotificationChannel) -> String {
+    let json = serde_json::to_string(channel).unwrap_or_default();
+    hex::encode(&Sha256::digest(json.as_bytes())[..4])
+}
+
+/// Describe a channel for people, e.g. `Slack #alerts` or `webhook to
+/// hooks.example.com`, with a fingerprint where the type alone would be
+/// ambiguous
+fn describe_channel(channel: &NotificationChannel) -> String {
+    match channel {
+        NotificationChannel::Slack { channel: Some(name), .. } => format!("Slack {}", name),
+        NotificationChannel::Webhook { url, .. } => match url::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+        {
+            Some(host) => format!("webhook to {}", host),
+            None => format!("webhook {}", fingerprint(channel)),
+        },
+        NotificationChannel::Email { to } => format!("email to {}", to.join(", ")),
+        NotificationChannel::Slack { channel: None, .. }
+        | NotificationChannel::PagerDuty { .. }
+        | NotificationChannel::Opsgenie { .. }
+        | NotificationChannel::SplunkOnCall { .. } => {
+            format!("{} {}", channel.channel_type(), fingerprint(channel))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: u32, initial: u64, max: u64) -> RetryPolicy {
+        RetryPolicy::new(&DeliveryConfig {
+            max_attempts,
+            initial_backoff_seconds: initial,
+            max_backoff_seconds: max,
+            ..DeliveryConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter_up_to_max() {
+        let policy = policy(10, 30, 300);
+
+        for _ in 0..50 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_secs(15) && first <= Duration::from_secs(30));
+
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_secs(60) && third <= Duration::from_secs(120));
+
+            let capped = policy.backoff(40);
+            assert!(capped >= Duration::from_secs(150) && capped <= Duration::from_secs(300));
+        }
+    }
+
+    #[test]
+    fn test_should_retry_counts_first_attempt() {
+        let three = policy(3, 30, 300);
+        assert!(three.should_retry(1));
+        assert!(three.should_retry(2));
+        assert!(!three.should_retry(3));
+
+        // A single attempt means no retries
+        assert!(!policy(1, 30, 300).should_retry(1));
+    }
+
+    #[test]
+    fn test_describe_channel_hides_credentials() {
+        let webhook = NotificationChannel::Webhook {
+            url: "https://hooks.example.com/secret-path?token=abc".to_string(),
+            headers: None,
+        };
+        assert_eq!(describe_channel(&webhook), "webhook to hooks.example.com");
+
+        let pagerduty = NotificationChannel::PagerDuty {
+            routing_key: "R0UT1NGK3Y".to_string(),
+        };
+        let described = describe_channel(&pagerduty);
+        assert!(described.starts_with("pagerduty "));
+        assert!(!described.contains("R0UT1NGK3Y"));
+        assert_eq!(described, describe_channel(&pagerduty.clone()));
+    }
+}
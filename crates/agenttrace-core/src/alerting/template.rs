@@ -0,0 +1,121 @@
+//! Alert message templates
+//!
+//! A rule's `message_template`, or `[alerting] message_template` for every
+//! rule without one, replaces the built-in alert message with a Handlebars
+//! template:
+//!
+//! ```text
+//! {{rule.name}}: {{metric}} is {{value}} (limit {{threshold}}) for {{service}} {{trace_link}}
+//! ```
+//!
+//! The rendered text becomes the event's message, so it is what Slack,
+//! webhook, email and incident tool notifications show. Variables that don't
+//! apply to a rule, such as `service` on an unscoped rule, render empty.
+
+use handlebars::{no_escape, Handlebars, Template};
+use serde_json::{json, Value};
+
+use crate::error::{Error, Result};
+use crate::models::alert::AlertRule;
+
+/// Check that a template parses
+pub fn validate_template(template: &str) -> Result<()> {
+    Template::compile(template)
+        .map(|_| ())
+        .map_err(|e| Error::validation(format!("Invalid message template: {}", e)))
+}
+
+/// Values a template can refer to for one breach
+pub(crate) struct MessageContext<'a> {
+    pub rule: &'a AlertRule,
+    pub value: f64,
+    pub threshold: f64,
+    /// The built-in message, available as `{{message}}`
+    pub message: &'a str,
+    pub trace_link: Option<&'a str>,
+    pub trace_ids: &'a [String],
+}
+
+impl MessageContext<'_> {
+    fn to_json(&self) -> Value {
+        let rule = self.rule;
+        json!({
+            "rule": {
+                "id": rule.id.to_string(),
+                "name": rule.name,
+                "description": rule.description,
+                "metric": rule.metric,
+                "window_minutes": rule.window_minutes,
+            },
+            "metric": rule.metric,
+            "severity": format!("{:?}", rule.severity).to_lowercase(),
+            "value": format!("{:.2}", self.value),
+            "threshold": format!("{:.2}", self.threshold),
+            "service": rule.service_name,
+            "model": rule.model_name,
+            "environment": rule.environment,
+            "message": self.message,
+            "trace_link": self.trace_link,
+            "trace_ids": self.trace_ids,
+        })
+    }
+}
+
+/// Render a template for a breach
+///
+/// Output is not HTML-escaped; channels escape for their own formats.
+pub(crate) fn render(template: &str, ctx: &MessageContext<'_>) -> Result<String> {
+    let mut registry = Handlebars::new();
+    registry.register_escape_fn(no_escape);
+
+    registry
+        .render_template(template, &ctx.to_json())
+        .map(|s| s.trim().to_string())
+        .map_err(|e| Error::validation(format!("Failed to render message template: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::alert::{Operator, Severity};
+
+    fn create_test_rule() -> AlertRule {
+        AlertRule {
+            service_name: Some("planner".to_string()),
+            severity: Severity::Critical,
+            ..AlertRule::new("Planner errors", "error_rate", Operator::Gt, 0.05)
+        }
+    }
+
+    #[test]
+    fn test_render_variables() {
+        let rule = create_test_rule();
+        let ctx = MessageContext {
+            rule: &rule,
+            value: 0.1234,
+            threshold: 0.05,
+            message: "built-in",
+            trace_link: Some("https://agenttrace.example.com/api/v1/search?status=error&a=b"),
+            trace_ids: &[],
+        };
+
+        let rendered = render(
+            "[{{severity}}] {{rule.name}} on {{service}}{{#if model}} ({{model}}){{/if}}: \
+             {{value}} > {{threshold}} {{trace_link}}",
+            &ctx,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rendered,
+            "[critical] Planner errors on planner: 0.12 > 0.05 \
+             https://agenttrace.example.com/api/v1/search?status=error&a=b"
+        );
+    }
+
+    #[test]
+    fn test_invalid_template_rejected() {
+        assert!(validate_template("{{rule.name}} at {{value}}").is_ok());
+        assert!(validate_template("{{#if service}}unclosed").is_err());
+    }
+}
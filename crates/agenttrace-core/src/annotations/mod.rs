@@ -0,0 +1,191 @@
+//! Timeline and trace annotations
+//!
+//! Annotations mark windows of time that explain what the metrics show
+//! without being caused by the instrumented agents. Provider incidents are
+//! polled from the OpenAI and Anthropic Statuspage feeds (or any other
+//! configured `incidents.json` feed), so error spikes and alerts during an
+//! upstream outage are marked as such instead of sending people digging
+//! through agent code.
+//!
+//! Trace annotations are notes people leave on a single trace, or a span in
+//! it, while reviewing an incident.
+
+mod repository;
+
+pub use repository::AnnotationRepository;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::{ProviderFeed, ProviderStatusConfig};
+use crate::error::{Error, Result};
+use crate::models::{Annotation, AnnotationKind};
+
+/// Timeout for a single feed request
+const FEED_TIMEOUT_SECS: u64 = 15;
+
+/// Records incidents from provider status feeds as annotations
+pub struct ProviderStatusPoller {
+    feeds: Vec<ProviderFeed>,
+    client: Client,
+    annotations: AnnotationRepository,
+}
+
+impl ProviderStatusPoller {
+    /// Create a poller for the configured feeds
+    pub fn new(config: &ProviderStatusConfig, annotations: AnnotationRepository) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(FEED_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            feeds: config.feeds.clone(),
+            client,
+            annotations,
+        }
+    }
+
+    /// Fetch every feed and record its incidents, returning how many were
+    /// recorded or updated
+    ///
+    /// A feed that cannot be fetched is logged and skipped so one provider's
+    /// status page being down doesn't hide the others' incidents.
+    pub async fn poll(&self) -> Result<usize> {
+        let mut recorded = 0;
+
+        for feed in &self.feeds {
+            let incidents = match self.fetch(feed).await {
+                Ok(incidents) => incidents,
+                Err(e) => {
+                    warn!(provider = %feed.provider, error = %e, "Failed to fetch provider status feed");
+                    continue;
+                }
+            };
+
+            for incident in &incidents {
+                self.annotations.upsert(incident).await?;
+            }
+            recorded += incidents.len();
+        }
+
+        Ok(recorded)
+    }
+
+    async fn fetch(&self, feed: &ProviderFeed) -> Result<Vec<Annotation>> {
+        let response = self
+            .client
+            .get(&feed.url)
+            .send()
+            .await
+            .map_err(|e| Error::Http(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(Error::Http(format!("{} returned {}", feed.url, response.status())));
+        }
+
+        let body = response.text().await.map_err(|e| Error::Http(e.to_string()))?;
+        parse_incidents(&feed.provider, &body)
+    }
+}
+
+/// Statuspage `incidents.json` response
+#[derive(Debug, Deserialize)]
+struct IncidentsFeed {
+    incidents: Vec<FeedIncident>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedIncident {
+    id: String,
+    name: String,
+    impact: Option<String>,
+    shortlink: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Parse a Statuspage `incidents.json` body into provider incident
+/// annotations
+///
+/// Incidents the provider rated as having no impact are skipped.
+pub fn parse_incidents(provider: &str, body: &str) -> Result<Vec<Annotation>> {
+    let feed: IncidentsFeed = serde_json::from_str(body)?;
+
+    Ok(feed
+        .incidents
+        .into_iter()
+        .filter(|incident| incident.impact.as_deref() != Some("none"))
+        .map(|incident| Annotation {
+            id: Uuid::new_v4(),
+            kind: AnnotationKind::ProviderIncident,
+            source: provider.to_string(),
+            external_id: incident.id,
+            title: incident.name,
+            impact: incident.impact,
+            url: incident.shortlink,
+            starts_at: incident.started_at.unwrap_or(incident.created_at),
+            ends_at: incident.resolved_at,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_incidents() {
+        let body = r#"{
+            "page": {"id": "abc", "name": "OpenAI"},
+            "incidents": [
+                {
+                    "id": "inc1",
+                    "name": "Elevated error rates on GPT-4o",
+                    "status": "resolved",
+                    "impact": "major",
+                    "shortlink": "https://stspg.io/inc1",
+                    "created_at": "2025-03-04T10:02:11.000Z",
+                    "started_at": "2025-03-04T09:55:00.000Z",
+                    "resolved_at": "2025-03-04T11:30:45.000Z"
+                },
+                {
+                    "id": "inc2",
+                    "name": "Increased latency",
+                    "status": "investigating",
+                    "impact": "minor",
+                    "shortlink": null,
+                    "created_at": "2025-03-05T08:00:00.000Z",
+                    "resolved_at": null
+                },
+                {
+                    "id": "inc3",
+                    "name": "Dashboard styling issue",
+                    "status": "resolved",
+                    "impact": "none",
+                    "created_at": "2025-03-01T08:00:00.000Z",
+                    "resolved_at": "2025-03-01T09:00:00.000Z"
+                }
+            ]
+        }"#;
+
+        let incidents = parse_incidents("openai", body).unwrap();
+        assert_eq!(incidents.len(), 2);
+
+        assert_eq!(incidents[0].source, "openai");
+        assert_eq!(incidents[0].external_id, "inc1");
+        assert_eq!(incidents[0].starts_at.to_rfc3339(), "2025-03-04T09:55:00+00:00");
+        assert!(incidents[0].ends_at.is_some());
+
+        // Falls back to the creation time and stays open until resolved
+        assert_eq!(incidents[1].starts_at.to_rfc3339(), "2025-03-05T08:00:00+00:00");
+        assert!(incidents[1].ends_at.is_none());
+        assert!(incidents[1].url.is_none());
+    }
+}
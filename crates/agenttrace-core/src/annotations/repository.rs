@@ -0,0 +1,237 @@
+//! Annotation repository
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{Annotation, AnnotationKind, TraceAnnotation, TraceAnnotationInput};
+
+const TRACE_ANNOTATION_COLUMNS: &str = "id, trace_id, span_id, author, text, labels, created_at, updated_at";
+
+/// Repository for timeline and trace annotations
+#[derive(Clone)]
+pub struct AnnotationRepository {
+    pool: PgPool,
+}
+
+impl AnnotationRepository {
+    /// Create a new annotation repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert an annotation, or update the one with the same source and
+    /// external ID
+    pub async fn upsert(&self, annotation: &Annotation) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO annotations (
+                id, kind, source, external_id, title, impact, url, starts_at, ends_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (source, external_id) DO UPDATE SET
+                title = EXCLUDED.title,
+                impact = EXCLUDED.impact,
+                url = EXCLUDED.url,
+                starts_at = EXCLUDED.starts_at,
+                ends_at = EXCLUDED.ends_at,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(annotation.id)
+        .bind(annotation.kind.as_str())
+        .bind(&annotation.source)
+        .bind(&annotation.external_id)
+        .bind(&annotation.title)
+        .bind(&annotation.impact)
+        .bind(&annotation.url)
+        .bind(annotation.starts_at)
+        .bind(annotation.ends_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List annotations overlapping `[since, until)`, optionally of one kind
+    pub async fn list(
+        &self,
+        kind: Option<AnnotationKind>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, kind, source, external_id, title, impact, url, starts_at, ends_at
+            FROM annotations
+            WHERE starts_at < $2
+              AND (ends_at IS NULL OR ends_at > $1)
+              AND ($3::text IS NULL OR kind = $3)
+            ORDER BY starts_at
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .bind(kind.map(AnnotationKind::as_str))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_annotation).collect()
+    }
+
+    /// Provider incidents overlapping `[since, until)`
+    pub async fn provider_incidents(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Annotation>> {
+        self.list(Some(AnnotationKind::ProviderIncident), since, until).await
+    }
+
+    /// List the notes on a trace, oldest first
+    pub async fn list_for_trace(&self, trace_id: &str) -> Result<Vec<TraceAnnotation>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM trace_annotations WHERE trace_id = $1 ORDER BY created_at, id",
+            TRACE_ANNOTATION_COLUMNS
+        ))
+        .bind(trace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_trace_annotation).collect()
+    }
+
+    /// List the notes on several traces, oldest first
+    pub async fn list_for_traces(&self, trace_ids: &[String]) -> Result<Vec<TraceAnnotation>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM trace_annotations WHERE trace_id = ANY($1) ORDER BY created_at, id",
+            TRACE_ANNOTATION_COLUMNS
+        ))
+        .bind(trace_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_trace_annotation).collect()
+    }
+
+    /// Get a note on a trace
+    pub async fn get_for_trace(&self, trace_id: &str, id: Uuid) -> Result<Option<TraceAnnotation>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM trace_annotations WHERE trace_id = $1 AND id = $2",
+            TRACE_ANNOTATION_COLUMNS
+        ))
+        .bind(trace_id)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(row_to_trace_annotation).transpose()
+    }
+
+    /// Write a note on a trace
+    pub async fn create_for_trace(
+        &self,
+        trace_id: &str,
+        mut input: TraceAnnotationInput,
+    ) -> Result<TraceAnnotation> {
+        input.validate()?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO trace_annotations (id, trace_id, span_id, author, text, labels)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING {}
+            "#,
+            TRACE_ANNOTATION_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(trace_id)
+        .bind(&input.span_id)
+        .bind(input.author.trim())
+        .bind(input.text.trim())
+        .bind(&input.labels)
+        .fetch_one(&self.pool)
+        .await?;
+
+        row_to_trace_annotation(&row)
+    }
+
+    /// Edit a note on a trace
+    ///
+    /// Returns None if the trace has no note with that ID.
+    pub async fn update_for_trace(
+        &self,
+        trace_id: &str,
+        id: Uuid,
+        mut input: TraceAnnotationInput,
+    ) -> Result<Option<TraceAnnotation>> {
+        input.validate()?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            UPDATE trace_annotations SET
+                span_id = $3,
+                author = $4,
+                text = $5,
+                labels = $6,
+                updated_at = NOW()
+            WHERE trace_id = $1 AND id = $2
+            RETURNING {}
+            "#,
+            TRACE_ANNOTATION_COLUMNS
+        ))
+        .bind(trace_id)
+        .bind(id)
+        .bind(&input.span_id)
+        .bind(input.author.trim())
+        .bind(input.text.trim())
+        .bind(&input.labels)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(row_to_trace_annotation).transpose()
+    }
+
+    /// Delete a note on a trace
+    pub async fn delete_for_trace(&self, trace_id: &str, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM trace_annotations WHERE trace_id = $1 AND id = $2")
+            .bind(trace_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+fn row_to_trace_annotation(row: &PgRow) -> Result<TraceAnnotation> {
+    Ok(TraceAnnotation {
+        id: row.try_get("id")?,
+        trace_id: row.try_get("trace_id")?,
+        span_id: row.try_get("span_id")?,
+        author: row.try_get("author")?,
+        text: row.try_get("text")?,
+        labels: row.try_get("labels")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn row_to_annotation(row: &PgRow) -> Result<Annotation> {
+    let kind: String = row.try_get("kind")?;
+
+    Ok(Annotation {
+        id: row.try_get("id")?,
+        kind: AnnotationKind::parse(&kind)
+            .ok_or_else(|| Error::Database(format!("Unknown annotation kind: {}", kind)))?,
+        source: row.try_get("source")?,
+        external_id: row.try_get("external_id")?,
+        title: row.try_get("title")?,
+        impact: row.try_get("impact")?,
+        url: row.try_get("url")?,
+        starts_at: row.try_get("starts_at")?,
+        ends_at: row.try_get("ends_at")?,
+    })
+}
@@ -0,0 +1,218 @@
+//! Structured API errors
+//!
+//! Every failed request gets the same JSON body, so clients can branch on
+//! `code` instead of parsing messages:
+//!
+//! ```json
+//! {
+//!   "error": {
+//!     "code": "query_limit",
+//!     "message": "Query spans 2000 buckets, more than the 1000 bucket limit",
+//!     "details": {"hint": "Use a wider bucket or a shorter time range"},
+//!     "request_id": "5f0c6c1e-8a4e-4f7e-9a63-2b1d2f7c9e10"
+//!   }
+//! }
+//! ```
+//!
+//! `request_id` is filled in by the [request ID middleware](super::middleware)
+//! and matches the response's `X-Request-Id` header and the server's logs.
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+use crate::error::Error;
+
+/// Machine-readable kind of API error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Malformed or invalid parameters (400)
+    InvalidRequest,
+    /// Missing or wrong admin token (401)
+    Unauthorized,
+    /// The resource doesn't exist (404)
+    NotFound,
+    /// The resource isn't in a state that allows the request (409)
+    Conflict,
+    /// The query is over the server's limits; see `details.hint` (422)
+    QueryLimit,
+    /// Too many requests or a full job queue (429)
+    RateLimited,
+    /// The server doesn't support the request yet (501)
+    NotImplemented,
+    /// The feature isn't available with this server's configuration (503)
+    Unavailable,
+    /// Anything else (500)
+    Internal,
+}
+
+impl ErrorCode {
+    /// HTTP status sent with the code
+    pub fn status(self) -> StatusCode {
+        match self {
+            Self::InvalidRequest => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Conflict => StatusCode::CONFLICT,
+            Self::QueryLimit => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+            Self::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Code for an error status produced outside the handlers, such as an
+    /// extractor rejection
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::NOT_IMPLEMENTED => Self::NotImplemented,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            s if s.is_client_error() => Self::InvalidRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// Error returned by API handlers
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiError {
+    /// HTTP status; the code's status except for rejections wrapped by the
+    /// middleware, which keep their own
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub code: ErrorCode,
+    pub message: String,
+    /// Extra context for the code, e.g. `hint` for `query_limit`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+    /// ID of the request, set by the request ID middleware
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Body of an error response
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a ApiError,
+}
+
+impl ApiError {
+    /// Create an error with a code and message
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            status: code.status(),
+            code,
+            message: message.into(),
+            details: None,
+            request_id: None,
+        }
+    }
+
+    /// Malformed or invalid parameters
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    /// Missing or wrong credentials
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unauthorized, message)
+    }
+
+    /// Missing resource
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    /// Resource in the wrong state for the request
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    /// Request the server can't handle yet
+    pub fn not_implemented(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotImplemented, message)
+    }
+
+    /// Feature not available with this configuration
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unavailable, message)
+    }
+
+    /// Unexpected failure
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    /// Attach extra context
+    #[must_use]
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Render the error as a response
+    ///
+    /// The error is also stored in the response's extensions so the request
+    /// ID middleware can add the request ID to the body.
+    pub fn to_response(&self) -> Response {
+        let mut response = (self.status, Json(ErrorBody { error: self })).into_response();
+        response.extensions_mut().insert(self.clone());
+        response
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        self.to_response()
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Validation(message) => Self::invalid(message),
+            Error::RateLimit => Self::new(ErrorCode::RateLimited, Error::RateLimit.to_string()),
+            Error::QueryLimit { message, hint } => {
+                Self::new(ErrorCode::QueryLimit, message).with_details(serde_json::json!({ "hint": hint }))
+            }
+            e => Self::internal(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_map_to_codes() {
+        assert_eq!(ApiError::from(Error::validation("bad")).status, StatusCode::BAD_REQUEST);
+        assert_eq!(ApiError::from(Error::RateLimit).code, ErrorCode::RateLimited);
+        assert_eq!(ApiError::from(Error::internal("boom")).status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let limited = ApiError::from(Error::query_limit("Too wide", "Narrow it"));
+        assert_eq!(limited.status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(limited.details, Some(serde_json::json!({ "hint": "Narrow it" })));
+    }
+
+    #[test]
+    fn test_error_body_shape() {
+        let mut error = ApiError::not_found("Trace not found");
+        error.request_id = Some("req-1".to_string());
+
+        let body = serde_json::to_value(ErrorBody { error: &error }).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "error": { "code": "not_found", "message": "Trace not found", "request_id": "req-1" }
+            })
+        );
+    }
+}
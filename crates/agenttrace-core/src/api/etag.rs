@@ -0,0 +1,146 @@
+//! ETag validation for polled query endpoints
+//!
+//! Dashboards poll trace details and metric summaries that rarely change
+//! between polls. Handlers derive an ETag from a cheap description of what a
+//! response depends on (e.g. span count and latest span timestamp) and answer
+//! a matching `If-None-Match` with `304 Not Modified` instead of the payload.
+//!
+//! ETags are weak, since the same payload is sent with different
+//! `Content-Encoding`s by the compression layer.
+
+use std::fmt::Display;
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+
+/// Builds an [`ETag`] from the values a response depends on
+pub struct ETagBuilder(Sha256);
+
+impl ETagBuilder {
+    /// An empty builder
+    pub fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    /// Add a value the response depends on
+    #[must_use]
+    pub fn part(mut self, value: impl Display) -> Self {
+        self.0.update(value.to_string().as_bytes());
+        // Separator so ("ab", "c") and ("a", "bc") differ
+        self.0.update([0]);
+        self
+    }
+
+    /// The weak ETag of the values added
+    pub fn finish(self) -> ETag {
+        let digest = self.0.finalize();
+        ETag(format!("W/\"{}\"", hex::encode(&digest[..16])))
+    }
+}
+
+impl Default for ETagBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Weak entity tag of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ETag(String);
+
+impl ETag {
+    /// Header value, including the `W/` prefix and quotes
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the request's `If-None-Match` matches this tag, using the weak
+    /// comparison `If-None-Match` calls for
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        let opaque = opaque_tag(&self.0);
+        headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || opaque_tag(tag) == opaque)
+    }
+
+    /// `304 Not Modified` if the request already has this version, otherwise
+    /// the response from `render` with the tag attached
+    pub fn respond<R: IntoResponse>(self, headers: &HeaderMap, render: impl FnOnce() -> R) -> Response {
+        if self.matches(headers) {
+            self.not_modified()
+        } else {
+            self.attach(render().into_response())
+        }
+    }
+
+    /// `304 Not Modified` with the tag
+    pub fn not_modified(self) -> Response {
+        self.attach(StatusCode::NOT_MODIFIED.into_response())
+    }
+
+    fn attach(self, mut response: Response) -> Response {
+        if let Ok(value) = HeaderValue::from_str(&self.0) {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+        response
+    }
+}
+
+/// A tag without its weakness indicator
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_etag_depends_on_every_part() {
+        let a = ETagBuilder::new().part("trace-1").part(3).finish();
+        assert_eq!(a, ETagBuilder::new().part("trace-1").part(3).finish());
+        assert_ne!(a, ETagBuilder::new().part("trace-1").part(4).finish());
+        assert_ne!(
+            ETagBuilder::new().part("ab").part("c").finish(),
+            ETagBuilder::new().part("a").part("bc").finish()
+        );
+        assert!(a.as_str().starts_with("W/\""));
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let tag = ETagBuilder::new().part("trace-1").finish();
+        let strong = tag.as_str().trim_start_matches("W/").to_string();
+
+        assert!(tag.matches(&if_none_match(tag.as_str())));
+        assert!(tag.matches(&if_none_match(&strong)));
+        assert!(tag.matches(&if_none_match(&format!("\"other\", {}", tag.as_str()))));
+        assert!(tag.matches(&if_none_match("*")));
+        assert!(!tag.matches(&if_none_match("\"other\"")));
+        assert!(!tag.matches(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_respond() {
+        let tag = ETagBuilder::new().part("trace-1").finish();
+
+        let fresh = tag.clone().respond(&HeaderMap::new(), || "body");
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(fresh.headers()[header::ETAG], tag.as_str());
+
+        let cached = tag.clone().respond(&if_none_match(tag.as_str()), || "body");
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], tag.as_str());
+    }
+}
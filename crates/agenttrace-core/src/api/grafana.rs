@@ -0,0 +1,486 @@
+//! Grafana JSON datasource API
+//!
+//! Endpoints following the SimpleJSON datasource contract, which the JSON
+//! and Infinity datasources also speak, so Grafana dashboards can chart
+//! AgentTrace metrics without SQL against the span tables:
+//!
+//! - `GET /grafana` answers the datasource's connection test
+//! - `POST /grafana/search` lists the queryable targets
+//! - `POST /grafana/query` runs targets as aggregation queries
+//! - `POST /grafana/annotations` returns provider incidents and alerts
+//! - `POST /grafana/tag-keys` and `/grafana/tag-values` feed ad hoc filters
+//!
+//! A target is an aggregation metric, optionally grouped: `p95_latency`,
+//! `sum_cost by model`, `count by attributes.user.tier`. Its `payload` may
+//! add `filters` and `limit` as in `POST /api/v1/query`, and the
+//! dashboard's ad hoc filters apply to every target.
+
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::models::{
+    AggregateMetric, AggregateRequest, AggregateRow, SearchFilter, SpanQuery, AGGREGATE_BUCKETS, MAX_AGGREGATE_BUCKETS,
+};
+
+use super::error::ApiError;
+use super::handlers::AppState;
+
+/// Metrics a target may name, as in `POST /api/v1/query`
+const METRICS: &[&str] = &[
+    "count",
+    "error_rate",
+    "avg_latency",
+    "p50_latency",
+    "p95_latency",
+    "p99_latency",
+    "sum_cost",
+    "sum_tokens",
+];
+
+/// Groupings offered by search; `attributes.<key>` is accepted too
+const GROUPS: &[&str] = &["service", "model", "operation", "status"];
+
+/// Span fields offered for ad hoc filters
+const TAG_KEYS: &[&str] = &["service_name", "model_name", "operation_name", "status"];
+
+/// How far back tag values are looked up
+const TAG_LOOKBACK_DAYS: i64 = 7;
+
+/// Most alert events returned as annotations
+const MAX_ALERT_ANNOTATIONS: i64 = 500;
+
+/// Time range of a query, as Grafana sends it
+#[derive(Debug, Deserialize)]
+pub struct TimeRange {
+    /// Start of the range
+    pub from: DateTime<Utc>,
+    /// End of the range
+    pub to: DateTime<Utc>,
+}
+
+/// `POST /grafana/search` body
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchRequest {
+    /// Text typed so far in the target field
+    #[serde(default)]
+    pub target: String,
+}
+
+/// `POST /grafana/query` body
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    /// Time range of the panel
+    pub range: TimeRange,
+    /// Width Grafana would like between points
+    #[serde(default)]
+    pub interval_ms: Option<i64>,
+    /// Queries of the panel
+    pub targets: Vec<QueryTarget>,
+    /// Dashboard ad hoc filters, applied to every target
+    #[serde(default)]
+    pub adhoc_filters: Vec<AdhocFilter>,
+}
+
+/// One query of a panel
+#[derive(Debug, Deserialize)]
+pub struct QueryTarget {
+    /// Metric, optionally grouped, e.g. `sum_cost by model`
+    #[serde(default)]
+    pub target: String,
+    /// `timeserie` (default) or `table`
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
+    /// Extra query options: an object, or JSON text of one
+    #[serde(default, alias = "data")]
+    pub payload: Value,
+    /// Whether the query is disabled in the panel
+    #[serde(default)]
+    pub hide: bool,
+}
+
+/// Options a target's payload may set
+#[derive(Debug, Default, Deserialize)]
+struct TargetPayload {
+    #[serde(default)]
+    filters: Vec<SearchFilter>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
+
+/// A dashboard-wide filter
+#[derive(Debug, Deserialize)]
+pub struct AdhocFilter {
+    /// Span field, one of those from `/grafana/tag-keys`
+    pub key: String,
+    /// `=`, `!=`, `<`, `>` or `=~` (contains)
+    pub operator: String,
+    /// Value to compare with
+    pub value: String,
+}
+
+impl AdhocFilter {
+    fn to_search_filter(&self) -> Result<SearchFilter, ApiError> {
+        let operator = match self.operator.as_str() {
+            "=" => "eq",
+            "!=" => "ne",
+            "<" => "lt",
+            ">" => "gt",
+            "=~" => "contains",
+            other => {
+                return Err(ApiError::invalid(format!(
+                    "Unsupported ad hoc filter operator: {}",
+                    other
+                )))
+            }
+        };
+        Ok(SearchFilter {
+            field: self.key.clone(),
+            operator: operator.to_string(),
+            value: Value::String(self.value.clone()),
+        })
+    }
+}
+
+/// `POST /grafana/annotations` body
+#[derive(Debug, Deserialize)]
+pub struct AnnotationsRequest {
+    /// Time range of the dashboard
+    pub range: TimeRange,
+    /// The annotation query as configured in Grafana, echoed back on every
+    /// result; its `query` picks `incidents`, `alerts` or both when empty
+    #[serde(default)]
+    pub annotation: Value,
+}
+
+/// An annotation as Grafana draws it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrafanaAnnotation {
+    /// The request's annotation query
+    pub annotation: Value,
+    /// Milliseconds since the Unix epoch
+    pub time: i64,
+    /// End of a region annotation, when the incident or alert ended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_end: Option<i64>,
+    /// Incident title or alert message
+    pub title: String,
+    /// Incident link or the alert's value against its threshold
+    pub text: String,
+    /// Incident kind and source, or `alert`, severity and service
+    pub tags: Vec<String>,
+}
+
+/// `POST /grafana/tag-values` body
+#[derive(Debug, Deserialize)]
+pub struct TagValuesRequest {
+    /// Tag key to list values of
+    pub key: String,
+}
+
+/// `GET /grafana`
+pub async fn test_connection() -> &'static str {
+    "OK"
+}
+
+/// `POST /grafana/search`
+///
+/// Every metric alone and grouped by each dimension, narrowed to those
+/// containing the text typed so far.
+pub async fn search(request: Option<Json<SearchRequest>>) -> Json<Vec<String>> {
+    let typed = request.map(|Json(r)| r.target.to_lowercase()).unwrap_or_default();
+    let targets = METRICS
+        .iter()
+        .flat_map(|metric| {
+            std::iter::once((*metric).to_string())
+                .chain(GROUPS.iter().map(move |group| format!("{} by {}", metric, group)))
+        })
+        .filter(|target| target.contains(typed.trim()))
+        .collect();
+    Json(targets)
+}
+
+/// `POST /grafana/query`
+pub async fn query(
+    State(state): State<AppState>,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<Value>>, ApiError> {
+    let adhoc = request
+        .adhoc_filters
+        .iter()
+        .map(AdhocFilter::to_search_filter)
+        .collect::<Result<Vec<_>, _>>()?;
+    let bucket = bucket_for(
+        (request.range.to - request.range.from).num_seconds(),
+        request.interval_ms,
+    );
+
+    let mut results = Vec::new();
+    for target in request
+        .targets
+        .iter()
+        .filter(|t| !t.hide && !t.target.trim().is_empty())
+    {
+        let (metric, group_by) = parse_target(&target.target)?;
+        let payload = parse_payload(&target.payload)?;
+        let table = target.kind.as_deref() == Some("table");
+
+        let mut filters = payload.filters;
+        filters.extend(adhoc.iter().cloned());
+        let query = AggregateRequest {
+            metric,
+            group_by: group_by.clone(),
+            bucket: (!table).then(|| bucket.to_string()),
+            filters,
+            since: request.range.from,
+            until: Some(request.range.to),
+            limit: payload.limit,
+        }
+        .compile()
+        .map_err(ApiError::from)?;
+
+        let rows = state.span_repo.aggregate(&query).await.map_err(ApiError::from)?;
+        let name = target.target.split_whitespace().next().unwrap_or_default();
+        if table {
+            results.push(table_result(name, group_by.as_deref(), &rows));
+        } else {
+            results.extend(time_series(name, &rows));
+        }
+    }
+
+    Ok(Json(results))
+}
+
+/// `POST /grafana/annotations`
+///
+/// Incidents and alerts are stored in PostgreSQL, so other backends
+/// return no annotations.
+pub async fn annotations(
+    State(state): State<AppState>,
+    Json(request): Json<AnnotationsRequest>,
+) -> Result<Json<Vec<GrafanaAnnotation>>, ApiError> {
+    let TimeRange { from, to } = request.range;
+    let source = request.annotation["query"]
+        .as_str()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if !["", "incidents", "alerts"].contains(&source.as_str()) {
+        return Err(ApiError::invalid(format!(
+            "Unknown annotation query '{}': expected incidents, alerts or nothing for both",
+            source
+        )));
+    }
+
+    let mut results = Vec::new();
+    if let Some(repo) = state.annotations.as_ref().filter(|_| source != "alerts") {
+        for incident in repo.list(None, from, to).await.map_err(ApiError::from)? {
+            results.push(GrafanaAnnotation {
+                annotation: request.annotation.clone(),
+                time: incident.starts_at.timestamp_millis(),
+                time_end: incident.ends_at.map(|t| t.timestamp_millis()),
+                text: incident.url.unwrap_or_default(),
+                title: incident.title,
+                tags: vec![incident.kind.as_str().to_string(), incident.source],
+            });
+        }
+    }
+    if let Some(repo) = state.alert_repo.as_ref().filter(|_| source != "incidents") {
+        let events = repo
+            .list_recent_events(from, MAX_ALERT_ANNOTATIONS)
+            .await
+            .map_err(ApiError::from)?;
+        for event in events.into_iter().filter(|e| e.triggered_at <= to) {
+            let mut tags = vec!["alert".to_string(), format!("{:?}", event.severity).to_lowercase()];
+            tags.extend(event.service_name);
+            results.push(GrafanaAnnotation {
+                annotation: request.annotation.clone(),
+                time: event.triggered_at.timestamp_millis(),
+                time_end: event.resolved_at.map(|t| t.timestamp_millis()),
+                title: event.message,
+                text: format!("value {} (threshold {})", event.metric_value, event.threshold_value),
+                tags,
+            });
+        }
+    }
+
+    results.sort_by_key(|a| a.time);
+    Ok(Json(results))
+}
+
+/// `POST /grafana/tag-keys`
+pub async fn tag_keys() -> Json<Vec<Value>> {
+    Json(
+        TAG_KEYS
+            .iter()
+            .map(|key| json!({"type": "string", "text": key}))
+            .collect(),
+    )
+}
+
+/// `POST /grafana/tag-values`
+///
+/// The most common values over the last week.
+pub async fn tag_values(
+    State(state): State<AppState>,
+    Json(request): Json<TagValuesRequest>,
+) -> Result<Json<Vec<Value>>, ApiError> {
+    let until = Utc::now();
+    let since = until - chrono::Duration::days(TAG_LOOKBACK_DAYS);
+    let facets = state
+        .span_repo
+        .search_facets(&SpanQuery::default(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    let values = match request.key.as_str() {
+        "service_name" => facets.services,
+        "model_name" => facets.models,
+        "operation_name" => facets.operations,
+        "status" => facets.statuses,
+        _ => Vec::new(),
+    };
+    Ok(Json(values.into_iter().map(|f| json!({"text": f.value})).collect()))
+}
+
+/// Split a target into its metric and optional grouping
+fn parse_target(target: &str) -> Result<(AggregateMetric, Option<String>), ApiError> {
+    let mut words = target.split_whitespace();
+    let metric = words.next().unwrap_or_default();
+    let group_by = match (words.next(), words.next(), words.next()) {
+        (None, ..) => None,
+        (Some("by"), Some(group), None) => Some(group.to_string()),
+        _ => {
+            return Err(ApiError::invalid(format!(
+                "Invalid target '{}': expected '<metric>' or '<metric> by <group>'",
+                target
+            )))
+        }
+    };
+    let metric = serde_json::from_value(Value::String(metric.to_string())).map_err(|_| {
+        ApiError::invalid(format!(
+            "Unknown metric '{}': expected one of {}",
+            metric,
+            METRICS.join(", ")
+        ))
+    })?;
+    Ok((metric, group_by))
+}
+
+fn parse_payload(payload: &Value) -> Result<TargetPayload, ApiError> {
+    let payload = match payload {
+        Value::Null => return Ok(TargetPayload::default()),
+        Value::String(text) if text.trim().is_empty() => return Ok(TargetPayload::default()),
+        Value::String(text) => serde_json::from_str(text),
+        other => serde_json::from_value(other.clone()),
+    };
+    payload.map_err(|e| ApiError::invalid(format!("Invalid target payload: {}", e)))
+}
+
+/// The narrowest bucket at least as wide as Grafana's interval that keeps
+/// the range within the bucket limit
+fn bucket_for(range_secs: i64, interval_ms: Option<i64>) -> &'static str {
+    let wanted_secs = interval_ms.unwrap_or(0) / 1000;
+    AGGREGATE_BUCKETS
+        .iter()
+        .find(|(_, secs)| *secs >= wanted_secs && range_secs / secs < MAX_AGGREGATE_BUCKETS)
+        .or(AGGREGATE_BUCKETS.last())
+        .map_or("1d", |(name, _)| *name)
+}
+
+/// One series per group, with `[value, milliseconds]` points in time order
+fn time_series(metric: &str, rows: &[AggregateRow]) -> Vec<Value> {
+    let mut series: Vec<(String, Vec<(f64, i64)>)> = Vec::new();
+    for row in rows {
+        let Some(bucket) = row.bucket else { continue };
+        let name = match &row.group {
+            Some(group) => format!("{} {}", metric, group),
+            None => metric.to_string(),
+        };
+        let point = (row.value, bucket.timestamp_millis());
+        match series.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, points)) => points.push(point),
+            None => series.push((name, vec![point])),
+        }
+    }
+    series
+        .into_iter()
+        .map(|(target, datapoints)| json!({"target": target, "datapoints": datapoints}))
+        .collect()
+}
+
+/// One row per group, highest value first
+fn table_result(metric: &str, group_by: Option<&str>, rows: &[AggregateRow]) -> Value {
+    let mut columns = Vec::new();
+    if let Some(group_by) = group_by {
+        columns.push(json!({"text": group_by, "type": "string"}));
+    }
+    columns.push(json!({"text": metric, "type": "number"}));
+
+    let rows: Vec<Value> = rows
+        .iter()
+        .map(|row| match (group_by, &row.group) {
+            (Some(_), group) => json!([group, row.value]),
+            (None, _) => json!([row.value]),
+        })
+        .collect();
+    json!({"type": "table", "columns": columns, "rows": rows})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let (metric, group) = parse_target("p95_latency by model").unwrap();
+        assert_eq!(metric, AggregateMetric::P95Latency);
+        assert_eq!(group.as_deref(), Some("model"));
+        assert_eq!(parse_target("sum_cost").unwrap(), (AggregateMetric::SumCost, None));
+        assert!(parse_target("max_cost").is_err());
+        assert!(parse_target("count per model").is_err());
+    }
+
+    #[test]
+    fn test_bucket_for() {
+        // A day charted every 5 minutes
+        assert_eq!(bucket_for(86_400, Some(300_000)), "5m");
+        // Narrower than the smallest bucket
+        assert_eq!(bucket_for(3_600, Some(15_000)), "1m");
+        // Thirty days can't use 15m buckets without passing the bucket limit
+        assert_eq!(bucket_for(30 * 86_400, Some(60_000)), "1h");
+    }
+
+    #[test]
+    fn test_time_series_groups_rows() {
+        let at = |secs| DateTime::<Utc>::from_timestamp(secs, 0);
+        let rows = vec![
+            AggregateRow {
+                group: Some("gpt-4o".to_string()),
+                bucket: at(0),
+                value: 2.0,
+            },
+            AggregateRow {
+                group: Some("claude".to_string()),
+                bucket: at(0),
+                value: 1.0,
+            },
+            AggregateRow {
+                group: Some("gpt-4o".to_string()),
+                bucket: at(60),
+                value: 3.0,
+            },
+        ];
+
+        let series = time_series("count", &rows);
+        assert_eq!(series.len(), 2);
+        assert_eq!(
+            series[0],
+            json!({"target": "count gpt-4o", "datapoints": [[2.0, 0], [3.0, 60_000]]})
+        );
+        assert_eq!(series[1]["target"], "count claude");
+    }
+}
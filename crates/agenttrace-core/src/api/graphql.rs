@@ -0,0 +1,718 @@
+//! GraphQL query endpoint
+//!
+//! `/api/graphql` exposes traces, spans, metrics summaries and alerts over
+//! the same repositories as the REST API, so tooling can fetch a trace with
+//! its spans, summary and related alerts in one round trip:
+//!
+//! ```graphql
+//! {
+//!   trace(traceId: "abc123") {
+//!     rootOperation
+//!     durationMs
+//!     spans(filter: "status:error") { operationName durationMs statusMessage }
+//!     alertEvents { message severity rule { name } }
+//!   }
+//! }
+//! ```
+//!
+//! Relations are resolved only when selected, and the same relation across
+//! a list of parents is fetched in one batched query. The schema is
+//! read-only; writes go through the REST API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Error, Json, Object, Result, Schema, SchemaBuilder,
+    SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::alerting::AlertRepository;
+use crate::db::SpanStore;
+use crate::models::{self, SpanQuery};
+
+use super::handlers::AppState;
+
+/// Deepest selection accepted, to keep nested queries cheap
+const MAX_DEPTH: usize = 8;
+
+/// Highest estimated cost accepted; each field costs 1 and a list field
+/// costs the number of items it may return times its selection
+const MAX_COMPLEXITY: usize = 20_000;
+
+/// Estimated size of lists that take no limit, like a trace's spans
+const UNBOUNDED_LIST_COST: usize = 20;
+
+/// Default and maximum number of items a list field returns
+const DEFAULT_LIMIT: i32 = 50;
+const MAX_LIMIT: i32 = 500;
+
+/// Executable GraphQL schema
+pub type AgentTraceSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Build the schema over the API state
+pub fn build_schema(state: AppState) -> AgentTraceSchema {
+    let loader = GraphLoader {
+        span_repo: state.span_repo.clone(),
+        alert_repo: state.alert_repo.clone(),
+    };
+    schema_builder()
+        .data(DataLoader::new(loader, tokio::spawn))
+        .data(state)
+        .finish()
+}
+
+fn schema_builder() -> SchemaBuilder<QueryRoot, EmptyMutation, EmptySubscription> {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+}
+
+/// Serialized name of an enum value, e.g. `error` for `SpanStatus::Error`
+fn label<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn clamp_limit(limit: Option<i32>) -> i64 {
+    i64::from(limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT))
+}
+
+fn alert_repo<'a>(ctx: &Context<'a>) -> Result<&'a AlertRepository> {
+    ctx.data::<AppState>()?
+        .alert_repo
+        .as_ref()
+        .ok_or_else(|| Error::new("Alerting not configured"))
+}
+
+fn loader<'a>(ctx: &Context<'a>) -> Result<&'a DataLoader<GraphLoader>> {
+    ctx.data::<DataLoader<GraphLoader>>()
+}
+
+/// Batches the lookups of nested fields, so resolving a relation for a list
+/// of N parents costs one query instead of N
+pub struct GraphLoader {
+    span_repo: Arc<dyn SpanStore>,
+    alert_repo: Option<AlertRepository>,
+}
+
+impl GraphLoader {
+    fn alerts(&self) -> Result<&AlertRepository> {
+        self.alert_repo
+            .as_ref()
+            .ok_or_else(|| Error::new("Alerting not configured"))
+    }
+}
+
+/// A rule by ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RuleId(Uuid);
+
+/// A trace's summary by trace ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TraceId(String);
+
+/// All spans of a trace
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TraceSpans(String);
+
+/// The most recent events of a rule
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RuleEvents {
+    rule_id: Uuid,
+    limit: i64,
+}
+
+/// The most recent events that sampled a trace
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TraceEvents {
+    trace_id: String,
+    limit: i64,
+}
+
+/// Split keys by their per-parent limit, since each batch query takes one
+fn by_limit<K, T>(keys: &[K], split: impl Fn(&K) -> (i64, T)) -> BTreeMap<i64, Vec<T>> {
+    let mut groups: BTreeMap<i64, Vec<T>> = BTreeMap::new();
+    for key in keys {
+        let (limit, id) = split(key);
+        groups.entry(limit).or_default().push(id);
+    }
+    groups
+}
+
+impl Loader<RuleId> for GraphLoader {
+    type Value = models::AlertRule;
+    type Error = Error;
+
+    async fn load(&self, keys: &[RuleId]) -> Result<HashMap<RuleId, Self::Value>> {
+        let ids: Vec<Uuid> = keys.iter().map(|key| key.0).collect();
+        let rules = self.alerts()?.get_rules(&ids).await?;
+        Ok(rules.into_iter().map(|rule| (RuleId(rule.id), rule)).collect())
+    }
+}
+
+impl Loader<TraceId> for GraphLoader {
+    type Value = models::TraceSummary;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TraceId]) -> Result<HashMap<TraceId, Self::Value>> {
+        let ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let summaries = self.span_repo.get_trace_summaries(&ids).await?;
+        Ok(summaries
+            .into_iter()
+            .map(|summary| (TraceId(summary.trace_id.clone()), summary))
+            .collect())
+    }
+}
+
+impl Loader<TraceSpans> for GraphLoader {
+    type Value = Vec<models::Span>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TraceSpans]) -> Result<HashMap<TraceSpans, Self::Value>> {
+        let ids: Vec<String> = keys.iter().map(|key| key.0.clone()).collect();
+        let mut spans: HashMap<TraceSpans, Self::Value> = HashMap::new();
+        for span in self.span_repo.get_by_trace_ids(&ids).await? {
+            spans.entry(TraceSpans(span.trace_id.clone())).or_default().push(span);
+        }
+        Ok(spans)
+    }
+}
+
+impl Loader<RuleEvents> for GraphLoader {
+    type Value = Vec<models::AlertEvent>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[RuleEvents]) -> Result<HashMap<RuleEvents, Self::Value>> {
+        let mut events: HashMap<RuleEvents, Self::Value> = HashMap::new();
+        for (limit, rule_ids) in by_limit(keys, |key| (key.limit, key.rule_id)) {
+            for event in self.alerts()?.list_events_for_rules(&rule_ids, limit).await? {
+                events
+                    .entry(RuleEvents { rule_id: event.rule_id, limit })
+                    .or_default()
+                    .push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl Loader<TraceEvents> for GraphLoader {
+    type Value = Vec<models::AlertEvent>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TraceEvents]) -> Result<HashMap<TraceEvents, Self::Value>> {
+        let mut events: HashMap<TraceEvents, Self::Value> = HashMap::new();
+        for (limit, trace_ids) in by_limit(keys, |key| (key.limit, key.trace_id.clone())) {
+            for (trace_id, event) in self.alerts()?.list_events_for_traces(&trace_ids, limit).await? {
+                events.entry(TraceEvents { trace_id, limit }).or_default().push(event);
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Root query type
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Look up a trace by ID
+    async fn trace(&self, ctx: &Context<'_>, trace_id: String) -> Result<Option<Trace>> {
+        let state = ctx.data::<AppState>()?;
+        let summary = state.span_repo.get_trace_summary(&trace_id).await?;
+        Ok(summary.map(Trace))
+    }
+
+    /// Recent traces, newest first
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn traces(
+        &self,
+        ctx: &Context<'_>,
+        service: Option<String>,
+        status: Option<String>,
+        since: Option<DateTime<Utc>>,
+        limit: Option<i32>,
+    ) -> Result<Vec<Trace>> {
+        let state = ctx.data::<AppState>()?;
+        let (traces, _) = state
+            .span_repo
+            .list_traces(service.as_deref(), status.as_deref(), since, None, clamp_limit(limit), None)
+            .await?;
+        Ok(traces.into_iter().map(Trace).collect())
+    }
+
+    /// Look up a span by its ID
+    async fn span(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<Span>> {
+        let state = ctx.data::<AppState>()?;
+        let span = state.span_repo.get_by_id(&id).await?;
+        Ok(span.map(Span::from))
+    }
+
+    /// Aggregate metrics over a time range (default: the last hour)
+    async fn metrics_summary(
+        &self,
+        ctx: &Context<'_>,
+        service: Option<String>,
+        model: Option<String>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<MetricsSummary> {
+        let state = ctx.data::<AppState>()?;
+        let since = since.unwrap_or_else(|| Utc::now() - chrono::Duration::hours(1));
+        let until = until.unwrap_or_else(Utc::now);
+        state.config.database.query_limits.check_range(Some(since), Some(until))?;
+
+        let summary = state
+            .span_repo
+            .get_metrics_summary(service.as_deref(), model.as_deref(), since, until)
+            .await?;
+        Ok(summary.into())
+    }
+
+    /// All alert rules
+    #[graphql(complexity = "UNBOUNDED_LIST_COST * child_complexity")]
+    async fn alert_rules(&self, ctx: &Context<'_>) -> Result<Vec<AlertRule>> {
+        let rules = alert_repo(ctx)?.list_rules().await?;
+        Ok(rules.into_iter().map(AlertRule).collect())
+    }
+
+    /// Alert events triggered since a time (default: the last 24 hours)
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn alert_events(
+        &self,
+        ctx: &Context<'_>,
+        since: Option<DateTime<Utc>>,
+        limit: Option<i32>,
+    ) -> Result<Vec<AlertEvent>> {
+        let since = since.unwrap_or_else(|| Utc::now() - chrono::Duration::hours(24));
+        let events = alert_repo(ctx)?.list_recent_events(since, clamp_limit(limit)).await?;
+        Ok(events.into_iter().map(AlertEvent).collect())
+    }
+}
+
+/// A trace and its summary
+pub struct Trace(models::TraceSummary);
+
+#[Object]
+impl Trace {
+    async fn trace_id(&self) -> &str {
+        &self.0.trace_id
+    }
+
+    async fn root_operation(&self) -> &str {
+        &self.0.root_operation
+    }
+
+    async fn service_name(&self) -> &str {
+        &self.0.service_name
+    }
+
+    async fn started_at(&self) -> DateTime<Utc> {
+        self.0.started_at
+    }
+
+    async fn duration_ms(&self) -> Option<f64> {
+        self.0.duration_ms
+    }
+
+    async fn span_count(&self) -> i64 {
+        self.0.span_count
+    }
+
+    async fn error_count(&self) -> i64 {
+        self.0.error_count
+    }
+
+    async fn total_tokens(&self) -> i64 {
+        self.0.total_tokens
+    }
+
+    async fn total_cost_usd(&self) -> f64 {
+        self.0.total_cost_usd
+    }
+
+    /// Spans in start order, optionally narrowed by a compact search query
+    #[graphql(complexity = "UNBOUNDED_LIST_COST * child_complexity")]
+    async fn spans(&self, ctx: &Context<'_>, filter: Option<String>) -> Result<Vec<Span>> {
+        let spans = match filter.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            Some(filter) => {
+                let query = SpanQuery::parse(filter)?;
+                let state = ctx.data::<AppState>()?;
+                state.span_repo.search_in_trace(&self.0.trace_id, &query).await?
+            }
+            None => {
+                let mut spans = loader(ctx)?
+                    .load_one(TraceSpans(self.0.trace_id.clone()))
+                    .await?
+                    .unwrap_or_default();
+                spans.sort_by_key(|span| span.started_at);
+                spans
+            }
+        };
+        Ok(spans.into_iter().map(Span::from).collect())
+    }
+
+    /// Alert events that sampled this trace
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn alert_events(&self, ctx: &Context<'_>, limit: Option<i32>) -> Result<Vec<AlertEvent>> {
+        let key = TraceEvents {
+            trace_id: self.0.trace_id.clone(),
+            limit: clamp_limit(limit),
+        };
+        let events = loader(ctx)?.load_one(key).await?.unwrap_or_default();
+        Ok(events.into_iter().map(AlertEvent).collect())
+    }
+}
+
+/// A single operation within a trace
+#[derive(SimpleObject)]
+pub struct Span {
+    /// Unique identifier
+    pub id: Uuid,
+    /// Span ID
+    pub span_id: String,
+    /// ID of the trace the span belongs to
+    pub trace_id: String,
+    /// Parent span ID, unset for root spans
+    pub parent_span_id: Option<String>,
+    /// Conversation or session the span belongs to
+    pub session_id: Option<String>,
+    /// End user the span ran for
+    pub user_id: Option<String>,
+    /// Operation name
+    pub operation_name: String,
+    /// Service that emitted the span
+    pub service_name: String,
+    /// `internal`, `server`, `client`, `producer` or `consumer`
+    pub span_kind: String,
+    /// When the span started
+    pub started_at: DateTime<Utc>,
+    /// When the span ended, unset while it is running
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Duration in milliseconds
+    pub duration_ms: Option<f64>,
+    /// `ok`, `error`, `unset` or `in_progress`
+    pub status: String,
+    /// Error message or status detail
+    pub status_message: Option<String>,
+    /// LLM model name
+    pub model_name: Option<String>,
+    /// LLM provider
+    pub model_provider: Option<String>,
+    /// Input tokens
+    pub tokens_in: Option<i32>,
+    /// Input tokens read from the prompt cache, a subset of `tokensIn`
+    pub tokens_cached_in: Option<i32>,
+    /// Output tokens
+    pub tokens_out: Option<i32>,
+    /// Cost in USD
+    pub cost_usd: Option<f64>,
+    /// Name of the tool called
+    pub tool_name: Option<String>,
+    /// Start of the prompt
+    pub prompt_preview: Option<String>,
+    /// Start of the completion
+    pub completion_preview: Option<String>,
+    /// Custom attributes
+    pub attributes: Json<serde_json::Value>,
+}
+
+impl From<models::Span> for Span {
+    fn from(span: models::Span) -> Self {
+        Self {
+            id: span.id,
+            span_kind: label(&span.span_kind),
+            status: label(&span.status),
+            span_id: span.span_id,
+            trace_id: span.trace_id,
+            parent_span_id: span.parent_span_id,
+            session_id: span.session_id,
+            user_id: span.user_id,
+            operation_name: span.operation_name,
+            service_name: span.service_name,
+            started_at: span.started_at,
+            ended_at: span.ended_at,
+            duration_ms: span.duration_ms,
+            status_message: span.status_message,
+            model_name: span.model_name,
+            model_provider: span.model_provider,
+            tokens_in: span.tokens_in,
+            tokens_cached_in: span.tokens_cached_in,
+            tokens_out: span.tokens_out,
+            cost_usd: span.cost_usd,
+            tool_name: span.tool_name,
+            prompt_preview: span.prompt_preview,
+            completion_preview: span.completion_preview,
+            attributes: Json(span.attributes),
+        }
+    }
+}
+
+/// Aggregate metrics over a time range
+#[derive(SimpleObject)]
+pub struct MetricsSummary {
+    /// Spans in the range
+    pub total_spans: i64,
+    /// Traces in the range
+    pub total_traces: i64,
+    /// Input and output tokens
+    pub total_tokens: i64,
+    /// Cost in USD
+    pub total_cost_usd: f64,
+    /// Spans that ended in error
+    pub error_count: i64,
+    /// Percentage of spans that ended in error
+    pub error_rate: f64,
+    /// Mean span duration in milliseconds
+    pub avg_latency_ms: f64,
+    /// Median span duration in milliseconds
+    pub p50_latency_ms: f64,
+    /// 95th percentile span duration in milliseconds
+    pub p95_latency_ms: f64,
+    /// 99th percentile span duration in milliseconds
+    pub p99_latency_ms: f64,
+}
+
+impl From<models::MetricsSummaryResponse> for MetricsSummary {
+    fn from(summary: models::MetricsSummaryResponse) -> Self {
+        Self {
+            total_spans: summary.total_spans,
+            total_traces: summary.total_traces,
+            total_tokens: summary.total_tokens,
+            total_cost_usd: summary.total_cost_usd,
+            error_count: summary.error_count,
+            error_rate: summary.error_rate,
+            avg_latency_ms: summary.avg_latency_ms,
+            p50_latency_ms: summary.p50_latency_ms,
+            p95_latency_ms: summary.p95_latency_ms,
+            p99_latency_ms: summary.p99_latency_ms,
+        }
+    }
+}
+
+/// An alert rule
+pub struct AlertRule(models::AlertRule);
+
+#[Object]
+impl AlertRule {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn service_name(&self) -> Option<&str> {
+        self.0.service_name.as_deref()
+    }
+
+    async fn model_name(&self) -> Option<&str> {
+        self.0.model_name.as_deref()
+    }
+
+    async fn metric(&self) -> &str {
+        &self.0.metric
+    }
+
+    async fn operator(&self) -> String {
+        label(&self.0.operator)
+    }
+
+    async fn threshold(&self) -> Option<f64> {
+        self.0.threshold
+    }
+
+    async fn window_minutes(&self) -> i32 {
+        self.0.window_minutes
+    }
+
+    async fn severity(&self) -> String {
+        label(&self.0.severity)
+    }
+
+    async fn enabled(&self) -> bool {
+        self.0.enabled
+    }
+
+    async fn last_triggered_at(&self) -> Option<DateTime<Utc>> {
+        self.0.last_triggered_at
+    }
+
+    /// Most recent events of this rule
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn events(&self, ctx: &Context<'_>, limit: Option<i32>) -> Result<Vec<AlertEvent>> {
+        let key = RuleEvents {
+            rule_id: self.0.id,
+            limit: clamp_limit(limit),
+        };
+        let events = loader(ctx)?.load_one(key).await?.unwrap_or_default();
+        Ok(events.into_iter().map(AlertEvent).collect())
+    }
+}
+
+/// A triggered alert
+pub struct AlertEvent(models::AlertEvent);
+
+#[Object]
+impl AlertEvent {
+    async fn id(&self) -> Uuid {
+        self.0.id
+    }
+
+    async fn rule_id(&self) -> Uuid {
+        self.0.rule_id
+    }
+
+    async fn triggered_at(&self) -> DateTime<Utc> {
+        self.0.triggered_at
+    }
+
+    async fn resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.0.resolved_at
+    }
+
+    async fn status(&self) -> String {
+        label(&self.0.status)
+    }
+
+    async fn severity(&self) -> String {
+        label(&self.0.severity)
+    }
+
+    async fn message(&self) -> &str {
+        &self.0.message
+    }
+
+    async fn metric_value(&self) -> f64 {
+        self.0.metric_value
+    }
+
+    async fn threshold_value(&self) -> f64 {
+        self.0.threshold_value
+    }
+
+    async fn service_name(&self) -> Option<&str> {
+        self.0.service_name.as_deref()
+    }
+
+    async fn incident_id(&self) -> Option<Uuid> {
+        self.0.incident_id
+    }
+
+    async fn trace_ids(&self) -> Vec<String> {
+        self.0.trace_ids.clone()
+    }
+
+    async fn acknowledged_by(&self) -> Option<&str> {
+        self.0.acknowledgement.as_ref().map(|ack| ack.acknowledged_by.as_str())
+    }
+
+    async fn acknowledged_at(&self) -> Option<DateTime<Utc>> {
+        self.0.acknowledgement.as_ref().map(|ack| ack.acknowledged_at)
+    }
+
+    /// Comment left when the event was acknowledged
+    async fn ack_comment(&self) -> Option<&str> {
+        self.0.acknowledgement.as_ref().and_then(|ack| ack.comment.as_deref())
+    }
+
+    /// Who is handling the event
+    async fn assignee(&self) -> Option<&str> {
+        self.0.acknowledgement.as_ref().and_then(|ack| ack.assignee.as_deref())
+    }
+
+    /// The rule that triggered the event
+    async fn rule(&self, ctx: &Context<'_>) -> Result<Option<AlertRule>> {
+        let rule = loader(ctx)?.load_one(RuleId(self.0.rule_id)).await?;
+        Ok(rule.map(AlertRule))
+    }
+
+    /// Sample traces behind the event that are still stored
+    #[graphql(complexity = "UNBOUNDED_LIST_COST * child_complexity")]
+    async fn traces(&self, ctx: &Context<'_>) -> Result<Vec<Trace>> {
+        let keys = self.0.trace_ids.iter().cloned().map(TraceId);
+        let mut summaries = loader(ctx)?.load_many(keys).await?;
+        Ok(self
+            .0
+            .trace_ids
+            .iter()
+            .filter_map(|trace_id| summaries.remove(&TraceId(trace_id.clone())))
+            .map(Trace)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::tests::create_test_span;
+    use crate::db::SqliteStore;
+
+    #[tokio::test]
+    async fn test_deep_and_costly_queries_are_rejected() {
+        let schema = schema_builder().finish();
+        let errors = |query: &'static str| {
+            let schema = &schema;
+            async move {
+                let response = schema.execute(query).await;
+                response.errors.into_iter().map(|e| e.message).collect::<Vec<_>>()
+            }
+        };
+
+        let deep = "{ alertEvents(limit: 1) { rule { events(limit: 1) { rule { events(limit: 1) { rule { \
+                    events(limit: 1) { rule { name } } } } } } } } }";
+        assert_eq!(errors(deep).await, vec!["Query is nested too deep."]);
+
+        let costly = "{ traces(limit: 500) { traceId alertEvents(limit: 500) { message } } }";
+        assert_eq!(errors(costly).await, vec!["Query is too complex."]);
+
+        // Within the limits, execution gets as far as looking up the state
+        let cheap = errors("{ traces(limit: 10) { traceId spans { spanId } } }").await;
+        assert!(cheap.iter().all(|e| !e.contains("too complex") && !e.contains("too deep")));
+    }
+
+    #[tokio::test]
+    async fn test_loader_batches_trace_lookups() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        store
+            .insert_batch(&[
+                create_test_span("t1", "a", None, 100.0),
+                create_test_span("t1", "b", Some("a"), 40.0),
+                create_test_span("t2", "c", None, 300.0),
+            ])
+            .await
+            .unwrap();
+        let loader = DataLoader::new(
+            GraphLoader {
+                span_repo: Arc::new(store),
+                alert_repo: None,
+            },
+            tokio::spawn,
+        );
+
+        let keys = ["t1", "t2", "missing"].map(|id| TraceId(id.to_string()));
+        let summaries = loader.load_many(keys).await.unwrap();
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[&TraceId("t1".to_string())].span_count, 2);
+        assert_eq!(summaries[&TraceId("t2".to_string())].span_count, 1);
+
+        let spans = loader.load_one(TraceSpans("t1".to_string())).await.unwrap().unwrap();
+        assert_eq!(spans.len(), 2);
+        assert!(loader.load_one(TraceSpans("missing".to_string())).await.unwrap().is_none());
+
+        let rule = loader.load_one(RuleId(Uuid::new_v4())).await;
+        assert_eq!(rule.unwrap_err().message, "Alerting not configured");
+    }
+}
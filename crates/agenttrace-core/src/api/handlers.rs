@@ -2,35 +2,72 @@
 
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    Extension,
+    http::{header, HeaderMap, StatusCode},
     response::sse::{Event, Sse},
+    response::{IntoResponse, Response},
     Json,
 };
 use futures_util::stream::Stream;
 use serde::{Deserialize, Serialize};
-use std::{convert::Infallible, sync::Arc, time::Duration};
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt as _;
 use uuid::Uuid;
 
-use crate::collector::Pipeline;
-use crate::db::{RedisPool, SpanRepository};
+use crate::collector::{MediaUsage, Pipeline, PipelineStats, PricingEntry};
+use crate::config::Config;
+use crate::collector::SchemaRegistry;
+use crate::subscriptions::SubscriptionRegistry;
+use crate::db::{PubSub, SpanStore};
+use crate::error::Error;
+use crate::export::ExportFormat;
+use crate::jobs::QueryJobs;
+use crate::locale::NumberFormat;
 use crate::models::{
-    Span, SpanStatus, SpanKind,
-    CostMetric, ErrorMetric, LatencyMetric, MetricsSummaryResponse,
-    SearchFilter, SortConfig, TraceSummary,
+    AggregateRequest, AggregateResponse, Annotation, AuditAction, ANONYMOUS_ACTOR, AuditChange, AuditEntry, AuditQuery, AnnotationKind, Span, SpanEvent, SpanLink, SpanPayload, SpanPriority, SpanStatus, SpanKind,
+    CostMetric, ErrorMetric, LatencyMetric, LatencyTarget, LatencyTargetSummary,
+    OperationEfficiency, OperationTargetStatus, PageCursor, QueryJob, QueryJobRequest,
+    BulkExportRequest, JobStatus,
+    SearchFacets, SearchFilter, ServiceSummary,
+    SortConfig, SpanQuery, StorageReport, TargetBreach, TextSearchHit, TimeBreakdownMetric, TokenEfficiency,
+    TraceAnnotation, TraceAnnotationInput, TraceComparison, TraceDeletion, TraceSummary,
+    WebhookDelivery, WebhookSubscription, WebhookSubscriptionInput,
 };
 
-use crate::alerting::{AlertEvaluator, AlertRepository};
+use crate::alerting::{
+    validate_escalation, validate_template, AlertEvaluator, AlertRepository, NotificationSender,
+};
+use crate::annotations::AnnotationRepository;
+use crate::audit::AuditRepository;
+use crate::budgets::BudgetRepository;
+use crate::reports::{self, ReportFormat, ReportPeriod};
+use crate::slos::SloRepository;
+use crate::views::ViewRepository;
+
+use super::error::ApiError;
+use super::etag::ETagBuilder;
+use super::middleware::Actor;
 
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
     pub pipeline: Arc<Pipeline>,
-    pub span_repo: SpanRepository,
-    pub redis: Option<RedisPool>,
+    pub span_repo: Arc<dyn SpanStore>,
+    pub pubsub: Option<Arc<dyn PubSub>>,
     pub alert_repo: Option<AlertRepository>,
     pub alert_evaluator: Option<Arc<AlertEvaluator>>,
+    pub annotations: Option<AnnotationRepository>,
+    pub jobs: Arc<QueryJobs>,
+    pub views: Option<ViewRepository>,
+    /// Audit log of changes made through the API (PostgreSQL storage only)
+    pub audit: Option<AuditRepository>,
+    /// Spend budgets (PostgreSQL storage only)
+    pub budgets: Option<BudgetRepository>,
+    /// Service level objectives (PostgreSQL storage only)
+    pub slos: Option<SloRepository>,
+    /// Effective configuration, for the admin API
+    pub config: Arc<Config>,
 }
 
 /// Health check response
@@ -48,12 +85,25 @@ pub async fn health() -> Json<HealthResponse> {
     })
 }
 
+/// Span metrics in the OpenMetrics text format, for Prometheus to scrape
+pub async fn prometheus_metrics(State(state): State<AppState>) -> Result<Response, ApiError> {
+    if !state.config.prometheus.enabled {
+        return Err(ApiError::not_found("Prometheus metrics are disabled"));
+    }
+    let body = state.pipeline.span_metrics().openmetrics();
+    Ok(([(header::CONTENT_TYPE, crate::prometheus::OPENMETRICS_CONTENT_TYPE)], body).into_response())
+}
+
 /// Span ingestion request
 #[derive(Debug, Deserialize)]
 pub struct IngestSpanRequest {
     pub span_id: String,
     pub trace_id: String,
     pub parent_span_id: Option<String>,
+    /// Session (multi-turn conversation) the trace belongs to
+    pub session_id: Option<String>,
+    /// End user the session belongs to
+    pub user_id: Option<String>,
     pub operation_name: String,
     pub service_name: Option<String>,
     pub started_at: chrono::DateTime<chrono::Utc>,
@@ -63,14 +113,25 @@ pub struct IngestSpanRequest {
     pub model_name: Option<String>,
     pub model_provider: Option<String>,
     pub tokens_in: Option<i32>,
+    pub tokens_cached_in: Option<i32>,
     pub tokens_out: Option<i32>,
     pub tokens_reasoning: Option<i32>,
+    /// Images generated or read, for per-image pricing
+    pub images: Option<f64>,
+    /// Seconds of audio transcribed or synthesized, for per-second pricing
+    pub audio_seconds: Option<f64>,
+    /// Tokens embedded, for embedding pricing
+    pub embedding_tokens: Option<f64>,
     pub tool_name: Option<String>,
     pub tool_input: Option<serde_json::Value>,
     pub tool_output: Option<serde_json::Value>,
+    pub tool_duration_ms: Option<f64>,
     pub prompt_preview: Option<String>,
     pub completion_preview: Option<String>,
     pub attributes: Option<serde_json::Value>,
+    pub events: Option<Vec<SpanEvent>>,
+    pub links: Option<Vec<SpanLink>>,
+    pub priority: Option<SpanPriority>,
 }
 
 /// Span ingestion response
@@ -84,7 +145,7 @@ pub struct IngestSpanResponse {
 pub async fn ingest_span(
     State(state): State<AppState>,
     Json(req): Json<IngestSpanRequest>,
-) -> Result<Json<IngestSpanResponse>, (StatusCode, String)> {
+) -> Result<Json<IngestSpanResponse>, ApiError> {
     let span = convert_request_to_span(req);
     let span_id = span.span_id.clone();
 
@@ -92,7 +153,7 @@ pub async fn ingest_span(
         .pipeline
         .submit(span)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
 
     Ok(Json(IngestSpanResponse {
         success: true,
@@ -117,7 +178,7 @@ pub struct IngestBatchResponse {
 pub async fn ingest_batch(
     State(state): State<AppState>,
     Json(req): Json<IngestBatchRequest>,
-) -> Result<Json<IngestBatchResponse>, (StatusCode, String)> {
+) -> Result<Json<IngestBatchResponse>, ApiError> {
     let total = req.spans.len();
     let spans: Vec<Span> = req.spans.into_iter().map(convert_request_to_span).collect();
 
@@ -125,7 +186,33 @@ pub async fn ingest_batch(
         .pipeline
         .submit_batch(spans)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+
+    Ok(Json(IngestBatchResponse {
+        accepted,
+        rejected: total - accepted,
+    }))
+}
+
+/// Ingest LangSmith runs: a run tree, a list of runs or a `{post, patch}`
+/// batch, as LangChain's tracer sends them
+///
+/// Runs posted without an end time are recorded as in progress and
+/// finished by a later patch for the same run. Patches for runs that are
+/// neither in progress here nor stored are rejected.
+pub async fn ingest_langsmith_runs(
+    State(state): State<AppState>,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<Json<IngestBatchResponse>, ApiError> {
+    let spans = crate::import::spans_from_langsmith(&payload).map_err(ApiError::from)?;
+    let total = spans.len();
+    let spans = resolve_patched_runs(&state, spans).await?;
+
+    let accepted = state
+        .pipeline
+        .submit_batch(spans)
+        .await
+        .map_err(ApiError::from)?;
 
     Ok(Json(IngestBatchResponse {
         accepted,
@@ -133,6 +220,96 @@ pub async fn ingest_batch(
     }))
 }
 
+/// Fill in the trace of patches that don't name one from the run they
+/// finish, dropping patches for unknown runs
+async fn resolve_patched_runs(state: &AppState, mut spans: Vec<Span>) -> Result<Vec<Span>, ApiError> {
+    for span in spans.iter_mut().filter(|s| s.trace_id.is_empty()) {
+        if let Some(trace_id) = state.pipeline.in_progress_trace_id(&span.span_id) {
+            span.trace_id = trace_id;
+        }
+    }
+
+    let unresolved: Vec<String> = spans
+        .iter()
+        .filter(|s| s.trace_id.is_empty())
+        .map(|s| s.span_id.clone())
+        .collect();
+    if unresolved.is_empty() {
+        return Ok(spans);
+    }
+
+    let stored: HashMap<String, String> = state
+        .span_repo
+        .get_in_progress(&unresolved)
+        .await
+        .map_err(ApiError::from)?
+        .into_iter()
+        .map(|s| (s.span_id, s.trace_id))
+        .collect();
+    spans.retain_mut(|span| {
+        if !span.trace_id.is_empty() {
+            return true;
+        }
+        match stored.get(&span.span_id) {
+            Some(trace_id) => {
+                span.trace_id = trace_id.clone();
+                true
+            }
+            None => {
+                tracing::debug!("Rejecting LangSmith patch for unknown run {}", span.span_id);
+                false
+            }
+        }
+    });
+    Ok(spans)
+}
+
+/// Apply a LangSmith run update (end time, outputs or error) to a run
+/// posted earlier
+pub async fn update_langsmith_run(
+    State(state): State<AppState>,
+    Path(run_id): Path<String>,
+    Json(mut update): Json<serde_json::Value>,
+) -> Result<Json<IngestBatchResponse>, ApiError> {
+    if !update.is_object() {
+        return Err(ApiError::from(Error::validation("expected a run update object")));
+    }
+    update["id"] = serde_json::Value::String(run_id.clone());
+    let response = ingest_langsmith_runs(State(state), Json(serde_json::json!({ "patch": [update] }))).await?;
+    if response.accepted == 0 {
+        return Err(ApiError::not_found(format!("Run {} not found", run_id)));
+    }
+    Ok(response)
+}
+
+/// Ingest an OTLP/HTTP `ExportTraceServiceRequest`, in protobuf or JSON
+/// by its content type, so OpenTelemetry exporters can send spans directly
+pub async fn ingest_otlp_traces(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, ApiError> {
+    let protobuf = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-protobuf"));
+    let format = if protobuf {
+        crate::import::ImportFormat::OtlpProtobuf
+    } else {
+        crate::import::ImportFormat::OtlpJson
+    };
+
+    let spans = crate::import::spans_from_otlp(&body, format).map_err(ApiError::from)?;
+    state.pipeline.submit_batch(spans).await.map_err(ApiError::from)?;
+
+    // An empty ExportTraceServiceResponse means every span was accepted
+    Ok(if protobuf {
+        ([(header::CONTENT_TYPE, "application/x-protobuf")], Vec::new()).into_response()
+    } else {
+        Json(serde_json::json!({})).into_response()
+    })
+}
+
 /// Query parameters for listing spans
 #[derive(Debug, Deserialize)]
 pub struct ListSpansQuery {
@@ -152,7 +329,7 @@ pub struct ListSpansResponse {
 pub async fn list_spans(
     State(state): State<AppState>,
     Query(query): Query<ListSpansQuery>,
-) -> Result<Json<ListSpansResponse>, (StatusCode, String)> {
+) -> Result<Json<ListSpansResponse>, ApiError> {
     let limit = query.limit.unwrap_or(100);
 
     let spans = if let Some(trace_id) = query.trace_id {
@@ -160,13 +337,13 @@ pub async fn list_spans(
             .span_repo
             .get_by_trace_id(&trace_id)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(ApiError::from)?
     } else {
         state
             .span_repo
             .get_recent(limit)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .map_err(ApiError::from)?
     };
 
     let total = spans.len();
@@ -177,29 +354,68 @@ pub async fn list_spans(
 pub async fn get_span(
     State(state): State<AppState>,
     Path(span_id): Path<Uuid>,
-) -> Result<Json<Span>, (StatusCode, String)> {
+) -> Result<Json<Span>, ApiError> {
     let span = state
         .span_repo
         .get_by_id(&span_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Span not found".to_string()))?;
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Span not found".to_string()))?;
 
     Ok(Json(span))
 }
 
+/// Get the full prompt and completion stored for a span
+///
+/// Span previews are cut at 500 characters; this loads the redacted full
+/// text when payload storage is enabled.
+pub async fn get_span_payload(
+    State(state): State<AppState>,
+    Path(span_id): Path<Uuid>,
+) -> Result<Json<SpanPayload>, ApiError> {
+    let payloads = state
+        .pipeline
+        .payloads()
+        .ok_or(ApiError::unavailable("Payload storage not enabled".to_string()))?;
+    let span = state
+        .span_repo
+        .get_by_id(&span_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Span not found".to_string()))?;
+
+    let payload = payloads
+        .get(&span.trace_id, &span.span_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("No payload stored for span".to_string()))?;
+
+    Ok(Json(payload))
+}
+
 fn convert_request_to_span(req: IngestSpanRequest) -> Span {
     let status = match req.status.as_deref() {
         Some("ok") => SpanStatus::Ok,
         Some("error") => SpanStatus::Error,
+        Some("in_progress") => SpanStatus::InProgress,
         _ => SpanStatus::Unset,
     };
 
+    let mut attributes = req.attributes.unwrap_or_else(|| serde_json::json!({}));
+    MediaUsage {
+        images: req.images,
+        audio_seconds: req.audio_seconds,
+        embedding_tokens: req.embedding_tokens,
+    }
+    .record(&mut attributes);
+
     Span {
         id: Uuid::new_v4(),
         span_id: req.span_id,
         trace_id: req.trace_id,
         parent_span_id: req.parent_span_id,
+        session_id: req.session_id,
+        user_id: req.user_id,
         operation_name: req.operation_name,
         service_name: req.service_name.unwrap_or_else(|| "unknown".to_string()),
         span_kind: SpanKind::Internal,
@@ -211,18 +427,20 @@ fn convert_request_to_span(req: IngestSpanRequest) -> Span {
         model_name: req.model_name,
         model_provider: req.model_provider,
         tokens_in: req.tokens_in,
+        tokens_cached_in: req.tokens_cached_in,
         tokens_out: req.tokens_out,
         tokens_reasoning: req.tokens_reasoning,
         cost_usd: None,
         tool_name: req.tool_name,
         tool_input: req.tool_input,
         tool_output: req.tool_output,
-        tool_duration_ms: None,
+        tool_duration_ms: req.tool_duration_ms,
         prompt_preview: req.prompt_preview,
         completion_preview: req.completion_preview,
-        attributes: req.attributes.unwrap_or_else(|| serde_json::json!({})),
-        events: vec![],
-        links: vec![],
+        attributes,
+        events: req.events.unwrap_or_default(),
+        links: req.links.unwrap_or_default(),
+        priority: req.priority.unwrap_or_default(),
     }
 }
 
@@ -231,8 +449,34 @@ fn convert_request_to_span(req: IngestSpanRequest) -> Span {
 pub struct StreamQuery {
     /// Filter by trace_id (optional)
     pub trace_id: Option<String>,
-    /// Channel to subscribe to: "spans", "llm", or "trace:{id}"
+    /// Channel to subscribe to: "spans", "llm", "trace:{id}", or "debug:{session_id}"
     pub channel: Option<String>,
+    /// Only emit spans from this service
+    pub service: Option<String>,
+    /// Only emit spans for this model
+    pub model: Option<String>,
+    /// Only emit spans with this status ("ok", "error", "unset", "in_progress")
+    pub status: Option<String>,
+    /// Only emit spans costing at least this much (USD)
+    pub min_cost: Option<f64>,
+    /// Only emit spans lasting at least this long (ms)
+    pub min_duration: Option<f64>,
+}
+
+impl StreamQuery {
+    /// Server-side span filter, if any filter parameter is set
+    fn span_filter(&self) -> Option<SpanQuery> {
+        let filter = SpanQuery {
+            service: self.service.clone(),
+            model: self.model.clone(),
+            status: self.status.as_ref().map(|s| s.to_lowercase()),
+            min_cost: self.min_cost,
+            min_duration: self.min_duration,
+            ..Default::default()
+        };
+
+        (filter != SpanQuery::default()).then_some(filter)
+    }
 }
 
 // ============================================================================
@@ -250,6 +494,12 @@ pub struct SearchQuery {
     pub model: Option<String>,
     /// Status filter (ok, error)
     pub status: Option<String>,
+    /// Priority filter (high, normal, low)
+    pub priority: Option<SpanPriority>,
+    /// Attribute filters, e.g. `customer_id:acme,agent_version:2`
+    pub attr: Option<String>,
+    /// Only spans of traces that did, or did not, get a negative rating
+    pub has_negative_feedback: Option<bool>,
     /// Minimum duration in ms
     pub min_duration: Option<f64>,
     /// Maximum duration in ms
@@ -258,9 +508,9 @@ pub struct SearchQuery {
     pub min_cost: Option<f64>,
     /// Maximum cost in USD
     pub max_cost: Option<f64>,
-    /// Start time (ISO 8601)
+    /// Start time (ISO 8601), default 24 hours before `until`
     pub since: Option<chrono::DateTime<chrono::Utc>>,
-    /// End time (ISO 8601)
+    /// End time (ISO 8601), default now
     pub until: Option<chrono::DateTime<chrono::Utc>>,
     /// Sort by field
     pub sort_by: Option<String>,
@@ -270,25 +520,42 @@ pub struct SearchQuery {
     pub limit: Option<i64>,
     /// Offset for pagination
     pub offset: Option<i64>,
+    /// `next_cursor` from the previous page, instead of `offset`
+    pub cursor: Option<String>,
 }
 
 /// Search response
 #[derive(Serialize)]
 pub struct SearchResponse {
     pub spans: Vec<Span>,
+    /// Matching spans, counted up to the configured row ceiling
     pub total: i64,
     pub limit: i64,
     pub offset: i64,
+    /// Cursor for the next page when this one is full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 /// Search spans with filters
-/// Search spans with filters
+///
+/// Pages can be fetched by `offset` or, when sorting by start time, by the
+/// `next_cursor` of the previous page.
 pub async fn search_spans(
     State(state): State<AppState>,
     Query(query): Query<SearchQuery>,
-) -> Result<Json<SearchResponse>, (StatusCode, String)> {
+) -> Result<Json<SearchResponse>, ApiError> {
     let limit = query.limit.unwrap_or(50).min(1000);
     let offset = query.offset.unwrap_or(0);
+    let cursor = parse_cursor(query.cursor.as_deref(), offset).map_err(ApiError::from)?;
+    let sort_by = query.sort_by.as_deref().unwrap_or("started_at");
+    let attributes = query
+        .attr
+        .as_deref()
+        .map(parse_attribute_pairs)
+        .transpose()
+        .map_err(ApiError::from)?
+        .unwrap_or_default();
 
     let (spans, total) = state
         .span_repo
@@ -297,28 +564,95 @@ pub async fn search_spans(
             query.service.as_deref(),
             query.model.as_deref(),
             query.status.as_deref(),
+            query.priority,
+            &attributes,
+            query.has_negative_feedback,
             query.min_duration,
             query.max_duration,
             query.min_cost,
             query.max_cost,
             query.since,
             query.until,
-            query.sort_by.as_deref().unwrap_or("started_at"),
+            sort_by,
             query.sort_order.as_deref().unwrap_or("desc") == "desc",
             limit,
             offset,
+            cursor.as_ref(),
         )
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+
+    let next_cursor = spans
+        .last()
+        .filter(|_| sort_by == "started_at" && spans.len() as i64 == limit)
+        .map(|span| PageCursor::after(span).encode());
 
     Ok(Json(SearchResponse {
         spans,
         total,
         limit,
         offset,
+        next_cursor,
     }))
 }
 
+/// Decode a pagination cursor, which can't be combined with an offset
+fn parse_cursor(cursor: Option<&str>, offset: i64) -> crate::error::Result<Option<PageCursor>> {
+    match cursor {
+        Some(_) if offset > 0 => Err(Error::validation("Use either 'cursor' or 'offset', not both")),
+        Some(token) => PageCursor::decode(token).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Search facets query parameters
+#[derive(Debug, Deserialize)]
+pub struct FacetsQuery {
+    /// Search query, e.g. `service:planner status:error timeout`
+    pub filter: Option<String>,
+    /// Start time (ISO 8601), default 24 hours ago
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// End time (ISO 8601), default now
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Count matching spans per service, model, status and operation
+pub async fn search_facets(
+    State(state): State<AppState>,
+    Query(query): Query<FacetsQuery>,
+) -> Result<Json<SearchFacets>, ApiError> {
+    let filter = SpanQuery::parse(query.filter.as_deref().unwrap_or_default()).map_err(ApiError::from)?;
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let facets = state
+        .span_repo
+        .search_facets(&filter, since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(facets))
+}
+
+/// Parse comma-separated `key:value` attribute filters
+fn parse_attribute_pairs(input: &str) -> crate::error::Result<Vec<(String, String)>> {
+    input
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| match pair.split_once(':') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(Error::validation(format!(
+                "Attribute filters must be key:value pairs, got '{}'",
+                pair
+            ))),
+        })
+        .collect()
+}
+
 /// Advanced search request
 #[derive(Debug, Deserialize)]
 pub struct AdvancedSearchRequest {
@@ -335,7 +669,7 @@ pub struct AdvancedSearchRequest {
 pub async fn advanced_search(
     State(state): State<AppState>,
     Json(req): Json<AdvancedSearchRequest>,
-) -> Result<Json<SearchResponse>, (StatusCode, String)> {
+) -> Result<Json<SearchResponse>, ApiError> {
     let limit = req.limit.unwrap_or(50).min(1000);
     let offset = req.offset.unwrap_or(0);
 
@@ -343,13 +677,69 @@ pub async fn advanced_search(
         .span_repo
         .advanced_search(&req.filters, req.sort.as_ref(), limit, offset)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
 
     Ok(Json(SearchResponse {
         spans,
         total,
         limit,
         offset,
+        next_cursor: None,
+    }))
+}
+
+/// Full-text search query parameters
+#[derive(Debug, Deserialize)]
+pub struct TextSearchQuery {
+    /// Search terms; supports quoted phrases, `or` and `-term`
+    pub q: String,
+    /// Service name filter
+    pub service: Option<String>,
+    /// Start time (ISO 8601), default 24 hours before `until`
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// End time (ISO 8601), default now
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Maximum results
+    pub limit: Option<i64>,
+    /// Offset for pagination
+    pub offset: Option<i64>,
+}
+
+/// Full-text search response
+#[derive(Serialize)]
+pub struct TextSearchResponse {
+    pub hits: Vec<TextSearchHit>,
+    /// Matching spans, counted up to the configured row ceiling
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Full-text search over prompts, completions and operation names, ranked by
+/// relevance with matches highlighted in `<mark>` tags
+pub async fn text_search(
+    State(state): State<AppState>,
+    Query(query): Query<TextSearchQuery>,
+) -> Result<Json<TextSearchResponse>, ApiError> {
+    let q = query.q.trim();
+    if q.is_empty() {
+        return Err(ApiError::from(Error::validation("Search query 'q' must not be empty")));
+    }
+
+    let limit = query.limit.unwrap_or(50).min(1000);
+    let offset = query.offset.unwrap_or(0);
+
+    let (hits, total) = state
+        .span_repo
+        .text_search(q, query.service.as_deref(), query.since, query.until, limit, offset)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(TextSearchResponse {
+        hits,
+        total,
+        limit,
+        offset,
     }))
 }
 
@@ -363,37 +753,53 @@ pub struct ListTracesQuery {
     pub service: Option<String>,
     pub status: Option<String>,
     pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Trace summary field to sort by, default `started_at`
+    pub sort_by: Option<String>,
+    /// Sort order (asc, desc), default desc
+    pub sort_order: Option<String>,
     pub limit: Option<i64>,
-    pub offset: Option<i64>,
+    /// `next_cursor` from the previous page; only for newest first
+    pub cursor: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct ListTracesResponse {
     pub traces: Vec<TraceSummary>,
     pub total: i64,
+    /// Cursor for the next page when this one is full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
-/// List traces
+/// List traces, newest first unless `sort_by` says otherwise
 pub async fn list_traces(
     State(state): State<AppState>,
     Query(query): Query<ListTracesQuery>,
-) -> Result<Json<ListTracesResponse>, (StatusCode, String)> {
+) -> Result<Json<ListTracesResponse>, ApiError> {
     let limit = query.limit.unwrap_or(50);
+    let cursor = parse_cursor(query.cursor.as_deref(), 0).map_err(ApiError::from)?;
+    let sort = query.sort_by.map(|field| SortConfig {
+        field,
+        descending: query.sort_order.as_deref().unwrap_or("desc") == "desc",
+    });
 
-    let traces = state
+    let (traces, next) = state
         .span_repo
         .list_traces(
             query.service.as_deref(),
             query.status.as_deref(),
             query.since,
+            sort.as_ref(),
             limit,
+            cursor.as_ref(),
         )
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
 
     Ok(Json(ListTracesResponse {
         total: traces.len() as i64,
         traces,
+        next_cursor: next.map(|c| c.encode()),
     }))
 }
 
@@ -403,393 +809,2415 @@ pub struct TraceDetail {
     pub trace_id: String,
     pub spans: Vec<Span>,
     pub summary: TraceSummary,
+    /// Other traces that spans in this trace link to
+    pub linked_trace_ids: Vec<String>,
+    /// Other traces with spans linking into this trace
+    pub linked_from_trace_ids: Vec<String>,
+    /// Spans slower than their operation's registered latency target
+    pub target_breaches: Vec<TargetBreach>,
+    /// Notes left on the trace during review (PostgreSQL storage only)
+    pub annotations: Vec<TraceAnnotation>,
 }
 
+/// Get a trace with its spans, links, target breaches and annotations
+///
+/// Responses carry an ETag derived from the span count, latest span
+/// timestamp, incoming links, breaches and annotation edits; a matching
+/// `If-None-Match` gets `304 Not Modified` without the payload.
 pub async fn get_trace(
     State(state): State<AppState>,
     Path(trace_id): Path<String>,
-) -> Result<Json<TraceDetail>, (StatusCode, String)> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let spans = state
         .span_repo
         .get_by_trace_id(&trace_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
 
     if spans.is_empty() {
-        return Err((StatusCode::NOT_FOUND, "Trace not found".to_string()));
+        return Err(ApiError::not_found("Trace not found".to_string()));
     }
 
-    // Build summary
-    let root = spans.iter().find(|s| s.parent_span_id.is_none());
-    let error_count = spans.iter().filter(|s| s.status == SpanStatus::Error).count() as i64;
-    let total_tokens: i64 = spans
+    let summary = state
+        .span_repo
+        .get_trace_summary(&trace_id)
+        .await
+        .map_err(ApiError::from)?
+        .unwrap_or_else(|| TraceSummary::from_spans(&trace_id, &spans));
+
+    let mut linked_trace_ids: Vec<String> = spans
         .iter()
-        .filter_map(|s| s.tokens_in.map(|t| t as i64))
-        .sum::<i64>()
-        + spans
-            .iter()
-            .filter_map(|s| s.tokens_out.map(|t| t as i64))
-            .sum::<i64>();
-    let total_cost: f64 = spans.iter().filter_map(|s| s.cost_usd).sum();
-
-    let summary = TraceSummary {
-        trace_id: trace_id.clone(),
-        root_operation: root.map(|s| s.operation_name.clone()).unwrap_or_default(),
-        service_name: root.map(|s| s.service_name.clone()).unwrap_or_default(),
-        started_at: root.map(|s| s.started_at).unwrap_or_else(chrono::Utc::now),
-        duration_ms: root.and_then(|s| s.duration_ms),
-        span_count: spans.len() as i64,
-        error_count,
-        total_tokens,
-        total_cost_usd: total_cost,
+        .flat_map(|s| s.links.iter())
+        .filter(|l| l.trace_id != trace_id)
+        .map(|l| l.trace_id.clone())
+        .collect();
+    linked_trace_ids.sort();
+    linked_trace_ids.dedup();
+
+    let linked_from_trace_ids = state
+        .span_repo
+        .get_linking_trace_ids(&trace_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    let target_breaches = state
+        .pipeline
+        .schema_registry()
+        .map(|registry| registry.target_breaches(&spans))
+        .unwrap_or_default();
+
+    // Notes are extra context; the trace is still worth returning without them
+    let annotations = match &state.annotations {
+        Some(repo) => repo.list_for_trace(&trace_id).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to load annotations of trace {}: {}", trace_id, e);
+            Vec::new()
+        }),
+        None => Vec::new(),
     };
 
-    Ok(Json(TraceDetail {
-        trace_id,
-        spans,
-        summary,
+    let latest_span_at = spans
+        .iter()
+        .map(|s| s.ended_at.unwrap_or(s.started_at).max(s.started_at))
+        .max();
+    let mut etag = ETagBuilder::new()
+        .part(&trace_id)
+        .part(spans.len())
+        .part(latest_span_at.map(|t| t.to_rfc3339()).unwrap_or_default())
+        .part(linked_from_trace_ids.join(","))
+        .part(target_breaches.len());
+    for annotation in &annotations {
+        etag = etag.part(annotation.id).part(annotation.updated_at.to_rfc3339());
+    }
+
+    Ok(etag.finish().respond(&headers, || {
+        Json(TraceDetail {
+            trace_id,
+            spans,
+            summary,
+            linked_trace_ids,
+            linked_from_trace_ids,
+            target_breaches,
+            annotations,
+        })
     }))
 }
 
-/// Get spans for a trace
-pub async fn get_trace_spans(
+/// Trace export parameters
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// json, otlp (OTLP/JSON), otlp-proto or jaeger
+    pub format: Option<String>,
+}
+
+/// Export a trace as AgentTrace JSON, OTLP or Jaeger JSON
+pub async fn export_trace(
     State(state): State<AppState>,
     Path(trace_id): Path<String>,
-) -> Result<Json<Vec<Span>>, (StatusCode, String)> {
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let format = ExportFormat::parse(query.format.as_deref().unwrap_or("json")).map_err(ApiError::from)?;
+
     let spans = state
         .span_repo
         .get_by_trace_id(&trace_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+    if spans.is_empty() {
+        return Err(ApiError::not_found("Trace not found".to_string()));
+    }
 
-    Ok(Json(spans))
+    let body = crate::export::export_trace(&trace_id, &spans, format).map_err(ApiError::from)?;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response())
 }
 
-// ============================================================================
-// Metrics Handlers
-// ============================================================================
+/// Compare two traces span by span
+///
+/// Spans are aligned by their operation path from the root and their
+/// position among spans with the same path, so a regression shows up as a
+/// delta on the step that slowed down or as a step that was added or removed.
+pub async fn compare_traces(
+    State(state): State<AppState>,
+    Path((trace_a, trace_b)): Path<(String, String)>,
+) -> Result<Json<TraceComparison>, ApiError> {
+    let (a_spans, a) = load_trace(&state, &trace_a).await?;
+    let (b_spans, b) = load_trace(&state, &trace_b).await?;
 
-/// Metrics query parameters
-#[derive(Debug, Deserialize)]
-pub struct MetricsQuery {
-    pub service: Option<String>,
-    pub model: Option<String>,
-    pub since: Option<chrono::DateTime<chrono::Utc>>,
-    pub until: Option<chrono::DateTime<chrono::Utc>>,
-    pub group_by: Option<String>,
+    Ok(Json(TraceComparison::new(a, &a_spans, b, &b_spans)))
 }
 
-pub async fn get_metrics_summary(
-    State(state): State<AppState>,
-    Query(query): Query<MetricsQuery>,
-) -> Result<Json<MetricsSummaryResponse>, (StatusCode, String)> {
-    let since = query
-        .since
-        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(1));
-    let until = query.until.unwrap_or_else(chrono::Utc::now);
+/// A trace's spans and summary, or 404 naming the trace
+async fn load_trace(
+    state: &AppState,
+    trace_id: &str,
+) -> Result<(Vec<Span>, TraceSummary), ApiError> {
+    let spans = state
+        .span_repo
+        .get_by_trace_id(trace_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    if spans.is_empty() {
+        return Err(ApiError::not_found(format!("Trace {} not found", trace_id)));
+    }
 
     let summary = state
         .span_repo
-        .get_metrics_summary(query.service.as_deref(), query.model.as_deref(), since, until)
+        .get_trace_summary(trace_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?
+        .unwrap_or_else(|| TraceSummary::from_spans(trace_id, &spans));
 
-    Ok(Json(summary))
+    Ok((spans, summary))
 }
 
-#[derive(Serialize)]
-pub struct CostMetricsResponse {
-    pub costs: Vec<CostMetric>,
-    pub total_cost_usd: f64,
+/// Trace spans query
+#[derive(Debug, Deserialize)]
+pub struct TraceSpansQuery {
+    /// Span filter, e.g. `status:error min_duration:500 attr.user.id:42`
+    pub filter: Option<String>,
 }
 
-pub async fn get_cost_metrics(
+/// Get spans for a trace, optionally only those matching `filter`
+pub async fn get_trace_spans(
     State(state): State<AppState>,
-    Query(query): Query<MetricsQuery>,
-) -> Result<Json<CostMetricsResponse>, (StatusCode, String)> {
-    let since = query
-        .since
-        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
-    let until = query.until.unwrap_or_else(chrono::Utc::now);
-    let group_by = query.group_by.as_deref().unwrap_or("model");
+    Path(trace_id): Path<String>,
+    Query(query): Query<TraceSpansQuery>,
+) -> Result<Json<Vec<Span>>, ApiError> {
+    let filter = query.filter.as_deref().map(str::trim).filter(|f| !f.is_empty());
+
+    let spans = match filter {
+        Some(filter) => {
+            let span_query = SpanQuery::parse(filter).map_err(ApiError::from)?;
+            state
+                .span_repo
+                .search_in_trace(&trace_id, &span_query)
+                .await
+                .map_err(ApiError::from)?
+        }
+        None => state
+            .span_repo
+            .get_by_trace_id(&trace_id)
+            .await
+            .map_err(ApiError::from)?,
+    };
 
-    let costs = state
+    Ok(Json(spans))
+}
+
+/// Delete whole traces by ID or attribute, e.g. for a data erasure request
+///
+/// A dry run only counts what would go. Real deletions also remove the
+/// traces' stored payloads, and are recorded in the audit log with the IDs
+/// of the traces removed.
+pub async fn delete_traces(
+    State(state): State<AppState>,
+    Json(deletion): Json<TraceDeletion>,
+) -> Result<Response, ApiError> {
+    let result = state
         .span_repo
-        .get_cost_by_group(query.service.as_deref(), group_by, since, until)
+        .delete_traces(&deletion)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+
+    let mut response = Json(&result).into_response();
+    if !deletion.dry_run {
+        if let Some(payloads) = state.pipeline.payloads() {
+            for trace_id in &result.trace_ids {
+                if let Err(e) = payloads.delete_trace(trace_id).await {
+                    tracing::warn!("Failed to delete payloads of trace {}: {}", trace_id, e);
+                }
+            }
+        }
+        let before = serde_json::json!({
+            "trace_ids": result.trace_ids,
+            "attributes": deletion.attributes,
+            "older_than": deletion.older_than,
+            "spans": result.spans,
+        });
+        let change = AuditChange::deleted("traces", deletion.describe(), &before);
+        response.extensions_mut().insert(change);
+    }
+    Ok(response)
+}
 
-    let total: f64 = costs.iter().map(|c| c.total_cost_usd).sum();
+// ============================================================================
+// Session Handlers
+// ============================================================================
 
-    Ok(Json(CostMetricsResponse {
-        costs,
-        total_cost_usd: total,
-    }))
+use crate::models::{SessionDetail, SessionSummary};
+
+/// List sessions query
+#[derive(Debug, Deserialize)]
+pub struct ListSessionsQuery {
+    /// Only sessions of this end user
+    pub user_id: Option<String>,
+    /// Only sessions active since this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Most sessions to return (default: 50)
+    pub limit: Option<i64>,
 }
 
+/// List sessions response
 #[derive(Serialize)]
-pub struct LatencyMetricsResponse {
-    pub metrics: Vec<LatencyMetric>,
+pub struct ListSessionsResponse {
+    /// Sessions, most recently active first
+    pub sessions: Vec<SessionSummary>,
+    /// Sessions matching the query
+    pub total: i64,
 }
 
-pub async fn get_latency_metrics(
+/// List sessions with their totals, most recently active first
+pub async fn list_sessions(
     State(state): State<AppState>,
-    Query(query): Query<MetricsQuery>,
-) -> Result<Json<LatencyMetricsResponse>, (StatusCode, String)> {
-    let since = query
-        .since
-        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
-    let until = query.until.unwrap_or_else(chrono::Utc::now);
-
-    let metrics = state
+    Query(query): Query<ListSessionsQuery>,
+) -> Result<Json<ListSessionsResponse>, ApiError> {
+    let (sessions, total) = state
         .span_repo
-        .get_latency_over_time(query.service.as_deref(), query.model.as_deref(), since, until)
+        .list_sessions(query.user_id.as_deref(), query.since, query.limit.unwrap_or(50))
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(LatencyMetricsResponse { metrics }))
-}
+        .map_err(ApiError::from)?;
 
-#[derive(Serialize)]
-pub struct ErrorMetricsResponse {
-    pub metrics: Vec<ErrorMetric>,
-    pub overall_error_rate: f64,
+    Ok(Json(ListSessionsResponse { sessions, total }))
 }
 
-pub async fn get_error_metrics(
+/// Get a session's cumulative totals and its traces, oldest first
+pub async fn get_session(
     State(state): State<AppState>,
-    Query(query): Query<MetricsQuery>,
-) -> Result<Json<ErrorMetricsResponse>, (StatusCode, String)> {
-    let since = query
-        .since
-        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
-    let until = query.until.unwrap_or_else(chrono::Utc::now);
-
-    let metrics = state
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionDetail>, ApiError> {
+    state
         .span_repo
-        .get_errors_over_time(query.service.as_deref(), query.model.as_deref(), since, until)
+        .get_session(&session_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let total_errors: i64 = metrics.iter().map(|m| m.error_count).sum();
-    let total_count: i64 = metrics.iter().map(|m| m.total_count).sum();
-    let overall_rate = if total_count > 0 {
-        total_errors as f64 / total_count as f64 * 100.0
-    } else {
-        0.0
-    };
-
-    Ok(Json(ErrorMetricsResponse {
-        metrics,
-        overall_error_rate: overall_rate,
-    }))
+        .map_err(ApiError::from)?
+        .map(Json)
+        .ok_or(ApiError::not_found("Session not found".to_string()))
 }
 
 // ============================================================================
-// Alert Handlers
+// Feedback Handlers
 // ============================================================================
 
-use crate::models::alert::{AlertEvent, AlertRule, AlertRuleInput};
+use crate::models::{FeedbackInput, FeedbackMetrics, TraceFeedback};
 
-/// List alert rules
-pub async fn list_alert_rules(
+/// Record a thumbs up/down and/or comment from an end user on a trace
+///
+/// The trace doesn't have to be stored yet, as feedback can arrive before
+/// the collector has flushed its spans.
+pub async fn create_feedback(
     State(state): State<AppState>,
-) -> Result<Json<Vec<AlertRule>>, (StatusCode, String)> {
-    let rules = state
-        .alert_repo
+    Json(mut input): Json<FeedbackInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<TraceFeedback>), ApiError> {
+    input.validate().map_err(ApiError::from)?;
+    let feedback = input.into_feedback();
+
+    state
+        .span_repo
+        .add_feedback(&feedback)
+        .await
+        .map_err(ApiError::from)?;
+
+    let change = AuditChange::created("trace_feedback", feedback.id, &feedback);
+    Ok((StatusCode::CREATED, Extension(change), Json(feedback)))
+}
+
+/// List the feedback left on a trace, oldest first
+pub async fn list_trace_feedback(
+    State(state): State<AppState>,
+    Path(trace_id): Path<String>,
+) -> Result<Json<Vec<TraceFeedback>>, ApiError> {
+    let feedback = state
+        .span_repo
+        .list_feedback(&trace_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(feedback))
+}
+
+// ============================================================================
+// Service Handlers
+// ============================================================================
+
+/// Service catalog query
+#[derive(Debug, Deserialize)]
+pub struct ServicesQuery {
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Service catalog response
+#[derive(Serialize)]
+pub struct ServicesResponse {
+    pub services: Vec<ServiceSummary>,
+}
+
+/// List services seen in a window, 24 hours by default
+pub async fn list_services(
+    State(state): State<AppState>,
+    Query(query): Query<ServicesQuery>,
+) -> Result<Json<ServicesResponse>, ApiError> {
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+    let since = query.since.unwrap_or_else(|| until - chrono::Duration::hours(24));
+    if since >= until {
+        return Err(ApiError::from(Error::validation("'since' must be before 'until'")));
+    }
+
+    let services = state
+        .span_repo
+        .list_services(since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(ServicesResponse { services }))
+}
+
+// ============================================================================
+// Metrics Handlers
+// ============================================================================
+
+/// Metrics query parameters
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    pub service: Option<String>,
+    pub model: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    pub group_by: Option<String>,
+}
+
+/// Get the metrics summary for a window
+///
+/// Responses carry an ETag derived from the window's span count and latest
+/// span timestamp, the latency targets and upstream incidents; a matching
+/// `If-None-Match` gets `304 Not Modified` without running the aggregate
+/// queries.
+pub async fn get_metrics_summary(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(1));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+    let service = query.service.as_deref();
+    let model = query.model.as_deref();
+
+    let targets = state
+        .pipeline
+        .schema_registry()
+        .map(|registry| registry.latency_targets(service))
+        .unwrap_or_default();
+    let incidents = upstream_incidents(&state, since, until).await;
+
+    let (span_count, latest_span_at) = state
+        .span_repo
+        .span_watermark(service, model, since, until)
+        .await
+        .map_err(ApiError::from)?;
+    let etag = ETagBuilder::new()
+        .part(service.unwrap_or_default())
+        .part(model.unwrap_or_default())
+        .part(query.since.map(|t| t.to_rfc3339()).unwrap_or_default())
+        .part(query.until.map(|t| t.to_rfc3339()).unwrap_or_default())
+        .part(span_count)
+        .part(latest_span_at.map(|t| t.to_rfc3339()).unwrap_or_default())
+        .part(serde_json::json!(targets))
+        .part(serde_json::json!(incidents))
+        .finish();
+    if etag.matches(&headers) {
+        return Ok(etag.not_modified());
+    }
+
+    let mut summary = state
+        .span_repo
+        .get_metrics_summary(service, model, since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    if !targets.is_empty() {
+        summary.latency_targets = Some(
+            latency_target_summary(state.span_repo.as_ref(), targets, since, until)
+                .await
+                .map_err(ApiError::from)?,
+        );
+    }
+
+    summary.upstream_incidents = incidents;
+
+    Ok(etag.respond(&headers, || Json(summary)))
+}
+
+/// Provider incidents overlapping the range, so metrics shaped by an
+/// upstream outage are marked as such
+///
+/// The incidents only annotate the metrics, so when they can't be read the
+/// failure is logged and the metrics are returned without them.
+async fn upstream_incidents(
+    state: &AppState,
+    since: chrono::DateTime<chrono::Utc>,
+    until: chrono::DateTime<chrono::Utc>,
+) -> Vec<Annotation> {
+    let Some(annotations) = &state.annotations else {
+        return vec![];
+    };
+    annotations.provider_incidents(since, until).await.unwrap_or_else(|e| {
+        tracing::warn!("Failed to load upstream incidents: {}", e);
+        vec![]
+    })
+}
+
+/// Compare each operation's observed latency with its registered target
+async fn latency_target_summary(
+    span_repo: &dyn SpanStore,
+    targets: Vec<(String, LatencyTarget)>,
+    since: chrono::DateTime<chrono::Utc>,
+    until: chrono::DateTime<chrono::Utc>,
+) -> crate::error::Result<LatencyTargetSummary> {
+    let mut operations = Vec::with_capacity(targets.len());
+
+    for (service_name, target) in targets {
+        let (observed_ms, span_count) = span_repo
+            .get_operation_latency(&service_name, &target.operation, since, until, target.percentile / 100.0)
+            .await?;
+
+        operations.push(OperationTargetStatus {
+            meeting_target: observed_ms.map(|ms| ms <= target.target_ms),
+            service_name,
+            operation: target.operation,
+            percentile: target.percentile,
+            target_ms: target.target_ms,
+            observed_ms,
+            span_count,
+        });
+    }
+
+    Ok(LatencyTargetSummary::from_statuses(operations))
+}
+
+#[derive(Serialize)]
+pub struct CostMetricsResponse {
+    pub costs: Vec<CostMetric>,
+    pub total_cost_usd: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<Annotation>,
+}
+
+pub async fn get_cost_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<CostMetricsResponse>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+    let group_by = query.group_by.as_deref().unwrap_or("model");
+    check_cost_group(&state.config, group_by)?;
+
+    let costs = state
+        .span_repo
+        .get_cost_by_group(query.service.as_deref(), group_by, since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    let total: f64 = costs.iter().map(|c| c.total_cost_usd).sum();
+
+    Ok(Json(CostMetricsResponse {
+        costs,
+        total_cost_usd: total,
+        upstream_incidents: upstream_incidents(&state, since, until).await,
+    }))
+}
+
+/// Reject `attr:<key>` cost groupings for keys not listed in
+/// `costs.attribution_keys`
+fn check_cost_group(config: &Config, group_by: &str) -> Result<(), ApiError> {
+    match group_by.strip_prefix("attr:") {
+        Some(key) if !config.costs.is_attribution_key(key) => Err(ApiError::invalid(format!(
+            "Cannot group costs by attribute '{}': add it to costs.attribution_keys",
+            key
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Serialize)]
+pub struct EfficiencyMetricsResponse {
+    pub operations: Vec<OperationEfficiency>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<Annotation>,
+}
+
+/// Token ratio and cost per successful trace for each operation
+pub async fn get_efficiency_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<EfficiencyMetricsResponse>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let operations = state
+        .span_repo
+        .get_operation_efficiency(query.service.as_deref(), query.model.as_deref(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(EfficiencyMetricsResponse {
+        operations,
+        upstream_incidents: upstream_incidents(&state, since, until).await,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct TokenEfficiencyResponse {
+    #[serde(flatten)]
+    pub efficiency: TokenEfficiency,
+    /// The same metrics over the equally long window just before, to spot
+    /// prompt-bloat regressions
+    pub previous: TokenEfficiency,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<Annotation>,
+}
+
+/// Cost per successful trace, tokens per trace, cache hit rate, reasoning
+/// share and output/input ratio across all spans
+pub async fn get_token_efficiency(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<TokenEfficiencyResponse>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+    let (service, model) = (query.service.as_deref(), query.model.as_deref());
+
+    let efficiency = state
+        .span_repo
+        .get_token_efficiency(service, model, since, until)
+        .await
+        .map_err(ApiError::from)?;
+    let previous = state
+        .span_repo
+        .get_token_efficiency(service, model, since - (until - since), since)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(TokenEfficiencyResponse {
+        efficiency,
+        previous,
+        upstream_incidents: upstream_incidents(&state, since, until).await,
+    }))
+}
+
+/// Ratings and comments on traces started in a window, with the average
+/// cost, duration and tokens of the traces rated each way
+pub async fn get_feedback_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<FeedbackMetrics>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let metrics = state
+        .span_repo
+        .get_feedback_metrics(query.service.as_deref(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(metrics))
+}
+
+pub async fn get_time_breakdown(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<TimeBreakdownMetric>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let breakdown = state
+        .span_repo
+        .get_time_breakdown(query.service.as_deref(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(breakdown))
+}
+
+#[derive(Serialize)]
+pub struct LatencyMetricsResponse {
+    pub metrics: Vec<LatencyMetric>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<Annotation>,
+}
+
+pub async fn get_latency_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<LatencyMetricsResponse>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let metrics = state
+        .span_repo
+        .get_latency_over_time(query.service.as_deref(), query.model.as_deref(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(LatencyMetricsResponse {
+        metrics,
+        upstream_incidents: upstream_incidents(&state, since, until).await,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct ErrorMetricsResponse {
+    pub metrics: Vec<ErrorMetric>,
+    pub overall_error_rate: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<Annotation>,
+}
+
+pub async fn get_error_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<ErrorMetricsResponse>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let metrics = state
+        .span_repo
+        .get_errors_over_time(query.service.as_deref(), query.model.as_deref(), since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    let total_errors: i64 = metrics.iter().map(|m| m.error_count).sum();
+    let total_count: i64 = metrics.iter().map(|m| m.total_count).sum();
+    let overall_rate = if total_count > 0 {
+        total_errors as f64 / total_count as f64 * 100.0
+    } else {
+        0.0
+    };
+
+    Ok(Json(ErrorMetricsResponse {
+        metrics,
+        overall_error_rate: overall_rate,
+        upstream_incidents: upstream_incidents(&state, since, until).await,
+    }))
+}
+
+/// Run a custom aggregation query
+pub async fn run_aggregate_query(
+    State(state): State<AppState>,
+    Json(request): Json<AggregateRequest>,
+) -> Result<Json<AggregateResponse>, ApiError> {
+    let group_by = request.group_by.clone();
+    let bucket = request.bucket.clone();
+    let query = request.compile().map_err(ApiError::from)?;
+
+    let rows = state
+        .span_repo
+        .aggregate(&query)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(AggregateResponse {
+        metric: query.metric,
+        group_by,
+        bucket,
+        since: query.since,
+        until: query.until,
+        rows,
+    }))
+}
+
+// ============================================================================
+// Report Handlers
+// ============================================================================
+
+/// Report query parameters
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    /// `week` (default) or `month`
+    pub period: Option<String>,
+    /// `markdown` (default), `html` or `json`
+    pub format: Option<String>,
+    /// End of the reported period (default: now)
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Render a weekly or monthly cost and usage report
+///
+/// Amounts use the server's `[display]` locale and currency.
+pub async fn generate_report(
+    State(state): State<AppState>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    let period = ReportPeriod::parse(query.period.as_deref().unwrap_or("week")).map_err(ApiError::from)?;
+    let format = ReportFormat::parse(query.format.as_deref().unwrap_or("markdown")).map_err(ApiError::from)?;
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let report = reports::generate(state.span_repo.as_ref(), state.alert_repo.as_ref(), period, until)
+        .await
+        .map_err(ApiError::from)?;
+    let body = reports::render(&report, format, &NumberFormat::new(&state.config.display))
+        .map_err(ApiError::from)?;
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], body).into_response())
+}
+
+// ============================================================================
+// Annotation Handlers
+// ============================================================================
+
+/// Annotation query parameters
+#[derive(Debug, Deserialize)]
+pub struct AnnotationsQuery {
+    /// Only return annotations of this kind (e.g., "provider_incident")
+    pub kind: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// List annotations overlapping a time range
+pub async fn list_annotations(
+    State(state): State<AppState>,
+    Query(query): Query<AnnotationsQuery>,
+) -> Result<Json<Vec<Annotation>>, ApiError> {
+    let annotations = state.annotations.as_ref().ok_or(ApiError::unavailable(
+        "Annotations require PostgreSQL storage".to_string(),
+    ))?;
+
+    let kind = query
+        .kind
+        .as_deref()
+        .map(|k| {
+            AnnotationKind::parse(k)
+                .ok_or_else(|| ApiError::invalid(format!("Unknown annotation kind: {}", k)))
+        })
+        .transpose()?;
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let annotations = annotations
+        .list(kind, since, until)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(annotations))
+}
+
+/// Trace annotations are stored in PostgreSQL and unavailable on other backends
+fn trace_annotations(state: &AppState) -> Result<&AnnotationRepository, ApiError> {
+    state.annotations.as_ref().ok_or(ApiError::unavailable(
+        "Annotations require PostgreSQL storage".to_string(),
+    ))
+}
+
+/// List the notes on a trace
+pub async fn list_trace_annotations(
+    State(state): State<AppState>,
+    Path(trace_id): Path<String>,
+) -> Result<Json<Vec<TraceAnnotation>>, ApiError> {
+    let annotations = trace_annotations(&state)?
+        .list_for_trace(&trace_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(annotations))
+}
+
+/// Write a note on a trace
+pub async fn create_trace_annotation(
+    State(state): State<AppState>,
+    Path(trace_id): Path<String>,
+    Json(input): Json<TraceAnnotationInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<TraceAnnotation>), ApiError> {
+    let repo = trace_annotations(&state)?;
+    check_annotation_target(&state, &trace_id, &input).await?;
+
+    let annotation = repo
+        .create_for_trace(&trace_id, input)
+        .await
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("trace_annotation", annotation.id, &annotation);
+
+    Ok((StatusCode::CREATED, Extension(change), Json(annotation)))
+}
+
+/// Fail unless the trace exists and, for a note about one span, the span is
+/// in the trace
+async fn check_annotation_target(
+    state: &AppState,
+    trace_id: &str,
+    input: &TraceAnnotationInput,
+) -> Result<(), ApiError> {
+    let spans = state.span_repo.get_by_trace_id(trace_id).await.map_err(ApiError::from)?;
+    if spans.is_empty() {
+        return Err(ApiError::not_found("Trace not found".to_string()));
+    }
+    match &input.span_id {
+        Some(span_id) if !spans.iter().any(|s| &s.span_id == span_id) => Err(ApiError::from(
+            Error::validation(format!("Span {} is not in trace {}", span_id, trace_id)),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Edit a note on a trace
+pub async fn update_trace_annotation(
+    State(state): State<AppState>,
+    Path((trace_id, annotation_id)): Path<(String, Uuid)>,
+    Json(input): Json<TraceAnnotationInput>,
+) -> Result<(Extension<AuditChange>, Json<TraceAnnotation>), ApiError> {
+    let repo = trace_annotations(&state)?;
+    let before = repo
+        .get_for_trace(&trace_id, annotation_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Annotation not found".to_string()))?;
+    check_annotation_target(&state, &trace_id, &input).await?;
+
+    let annotation = repo
+        .update_for_trace(&trace_id, annotation_id, input)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Annotation not found".to_string()))?;
+    let change = AuditChange::updated("trace_annotation", annotation_id, &before, &annotation);
+
+    Ok((Extension(change), Json(annotation)))
+}
+
+/// Delete a note on a trace
+pub async fn delete_trace_annotation(
+    State(state): State<AppState>,
+    Path((trace_id, annotation_id)): Path<(String, Uuid)>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = trace_annotations(&state)?;
+    let before = repo
+        .get_for_trace(&trace_id, annotation_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Annotation not found".to_string()))?;
+
+    let deleted = repo
+        .delete_for_trace(&trace_id, annotation_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    if deleted {
+        let change = AuditChange::deleted("trace_annotation", annotation_id, &before);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found("Annotation not found".to_string()))
+    }
+}
+
+// ============================================================================
+// System Handlers
+// ============================================================================
+
+/// Storage report query
+#[derive(Debug, Deserialize)]
+pub struct StorageQuery {
+    /// Days of ingest to measure rates over (default 7)
+    pub days: Option<i64>,
+    /// How far ahead to project disk usage, in days (default 30)
+    pub projection_days: Option<i64>,
+}
+
+/// Report storage usage and projected disk growth
+pub async fn get_storage_report(
+    State(state): State<AppState>,
+    Query(query): Query<StorageQuery>,
+) -> Result<Json<StorageReport>, ApiError> {
+    let days = query.days.unwrap_or(7).clamp(1, 90);
+    let projection_days = query.projection_days.unwrap_or(30).clamp(1, 3650);
+
+    let report = state
+        .span_repo
+        .get_storage_report(days, projection_days)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(report))
+}
+
+// ============================================================================
+// Alert Handlers
+// ============================================================================
+
+use crate::models::alert::{
+    AcknowledgeRequest, Acknowledgement, AlertEvent, AlertIncident, AlertRule, AlertRuleInput, AlertRuleListing,
+    AlertRuleRevision, AlertRuleUpdate, AlertStatus, EscalationStep, NotificationChannel, NotificationRecord,
+    RevertRuleRequest, RuleLintReport, RuleSchedule,
+};
+
+/// List alert rules, flagging those currently outside their schedule
+pub async fn list_alert_rules(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AlertRuleListing>>, ApiError> {
+    let rules = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?
+        .list_rules()
+        .await
+        .map_err(ApiError::from)?;
+
+    let now = chrono::Utc::now();
+    let listings = rules
+        .into_iter()
+        .map(|rule| AlertRuleListing {
+            scheduled_off: rule.is_scheduled_off(now),
+            rule,
+        })
+        .collect();
+
+    Ok(Json(listings))
+}
+
+/// Reject rule input with an invalid schedule, escalation or message template
+fn validate_rule_input(input: &AlertRuleInput) -> Result<(), ApiError> {
+    validate_rule_parts(
+        input.schedule.as_ref(),
+        input.escalation.as_deref(),
+        input.message_template.as_deref(),
+    )
+}
+
+fn validate_rule_update(update: &AlertRuleUpdate) -> Result<(), ApiError> {
+    validate_rule_parts(
+        update.schedule.value(),
+        update.escalation.value().map(Vec::as_slice),
+        update.message_template.value().map(String::as_str),
+    )
+}
+
+fn validate_rule_parts(
+    schedule: Option<&RuleSchedule>,
+    escalation: Option<&[EscalationStep]>,
+    template: Option<&str>,
+) -> Result<(), ApiError> {
+    if let Some(schedule) = schedule {
+        schedule.validate().map_err(ApiError::from)?;
+    }
+    if let Some(escalation) = escalation {
+        validate_escalation(escalation).map_err(ApiError::from)?;
+    }
+    if let Some(template) = template {
+        validate_template(template).map_err(ApiError::from)?;
+    }
+    Ok(())
+}
+
+/// Create alert rule
+pub async fn create_alert_rule(
+    State(state): State<AppState>,
+    actor: Option<Extension<Actor>>,
+    Json(input): Json<AlertRuleInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<AlertRule>), ApiError> {
+    validate_rule_input(&input)?;
+
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+    let rule = repo.create_rule(input).await.map_err(ApiError::from)?;
+    record_rule_revision(repo, actor, rule.id, AuditAction::Create, None, Some(&rule), None).await;
+    let change = AuditChange::created("alert_rule", rule.id, &rule);
+
+    Ok((StatusCode::CREATED, Extension(change), Json(rule)))
+}
+
+/// Get alert rule by ID
+pub async fn get_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<Json<AlertRule>, ApiError> {
+    let rule = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?
+        .get_rule(rule_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+
+    Ok(Json(rule))
+}
+
+/// Update alert rule
+pub async fn update_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    actor: Option<Extension<Actor>>,
+    Json(update): Json<AlertRuleUpdate>,
+) -> Result<(Extension<AuditChange>, Json<AlertRule>), ApiError> {
+    validate_rule_update(&update)?;
+
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+    let before = repo
+        .get_rule(rule_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+
+    let rule = repo
+        .update_rule(rule_id, update)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+    record_rule_revision(repo, actor, rule_id, AuditAction::Update, Some(&before), Some(&rule), None).await;
+    let change = AuditChange::updated("alert_rule", rule_id, &before, &rule);
+
+    Ok((Extension(change), Json(rule)))
+}
+
+/// Delete alert rule
+pub async fn delete_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    actor: Option<Extension<Actor>>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+    let before = repo
+        .get_rule(rule_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+
+    let deleted = repo.delete_rule(rule_id).await.map_err(ApiError::from)?;
+
+    if deleted {
+        record_rule_revision(repo, actor, rule_id, AuditAction::Delete, Some(&before), None, None).await;
+        let change = AuditChange::deleted("alert_rule", rule_id, &before);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found("Rule not found".to_string()))
+    }
+}
+
+/// Record a rule change in the rule's revision history
+///
+/// Like the audit log, history is written once the change has been made,
+/// so failing to record it is logged rather than failing the request.
+async fn record_rule_revision(
+    repo: &AlertRepository,
+    actor: Option<Extension<Actor>>,
+    rule_id: Uuid,
+    action: AuditAction,
+    before: Option<&AlertRule>,
+    after: Option<&AlertRule>,
+    reverted_to: Option<i32>,
+) {
+    let actor = actor.map_or_else(|| ANONYMOUS_ACTOR.to_string(), |Extension(Actor(actor))| actor);
+    if let Err(e) = repo
+        .record_rule_revision(rule_id, action, &actor, before, after, reverted_to)
+        .await
+    {
+        tracing::warn!(rule_id = %rule_id, "Failed to record alert rule revision: {}", e);
+    }
+}
+
+/// List an alert rule's revisions, newest first
+///
+/// History is kept after a rule is deleted, so it can be restored.
+pub async fn get_alert_rule_history(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<Json<Vec<AlertRuleRevision>>, ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let revisions = repo.list_rule_revisions(rule_id).await.map_err(ApiError::from)?;
+    if revisions.is_empty() && repo.get_rule(rule_id).await.map_err(ApiError::from)?.is_none() {
+        return Err(ApiError::not_found("Rule not found".to_string()));
+    }
+
+    Ok(Json(revisions))
+}
+
+/// Revert an alert rule to how it was after an earlier revision
+///
+/// Restores the rule if it has since been deleted. The revert is recorded
+/// as a new revision, so it can itself be undone.
+pub async fn revert_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    actor: Option<Extension<Actor>>,
+    Json(request): Json<RevertRuleRequest>,
+) -> Result<(Extension<AuditChange>, Json<AlertRule>), ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let revision = repo
+        .get_rule_revision(rule_id, request.revision)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found(format!("Revision {} not found", request.revision)))?;
+    let target = revision.restored_rule().map_err(ApiError::from)?;
+
+    let before = repo.get_rule(rule_id).await.map_err(ApiError::from)?;
+    let rule = repo.restore_rule(&target).await.map_err(ApiError::from)?;
+
+    let (action, change) = match &before {
+        Some(before) => (AuditAction::Update, AuditChange::updated("alert_rule", rule_id, before, &rule)),
+        None => (AuditAction::Create, AuditChange::created("alert_rule", rule_id, &rule)),
+    };
+    record_rule_revision(repo, actor, rule_id, action, before.as_ref(), Some(&rule), Some(request.revision)).await;
+
+    Ok((Extension(change), Json(rule)))
+}
+
+/// Test alert rule
+#[derive(Serialize)]
+pub struct TestAlertResponse {
+    pub would_trigger: bool,
+    pub event: Option<AlertEvent>,
+    pub current_value: Option<f64>,
+}
+
+pub async fn test_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+) -> Result<Json<TestAlertResponse>, ApiError> {
+    let rule = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?
+        .get_rule(rule_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+
+    let evaluator = state
+        .alert_evaluator
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alert evaluator not configured".to_string()))?;
+
+    let event = evaluator
+        .test_rule(&rule)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(TestAlertResponse {
+        would_trigger: event.is_some(),
+        current_value: event.as_ref().map(|e| e.metric_value),
+        event,
+    }))
+}
+
+/// Outcome of a test notification
+#[derive(Debug, Serialize)]
+pub struct TestChannelResponse {
+    /// Channel type
+    pub channel_type: String,
+    /// Whether the channel accepted the notification
+    pub success: bool,
+    /// HTTP status the service answered with, if it answered
+    pub status: Option<u16>,
+    /// Why it failed; the service's response body is never included
+    pub error: Option<String>,
+    /// Whether the failure looks temporary, so a real alert would be retried
+    pub retryable: bool,
+    /// When it was sent
+    pub sent_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Send a test alert through a channel config, returning the delivery result
+///
+/// Admin only. The channel's URL must resolve to public addresses only, so
+/// the endpoint can't be used to probe the server's network. Nothing is
+/// stored; a failed delivery is reported in the response rather than as an
+/// error status.
+pub async fn test_notification_channel(
+    State(state): State<AppState>,
+    Json(channel): Json<NotificationChannel>,
+) -> Result<Json<TestChannelResponse>, ApiError> {
+    if matches!(channel, NotificationChannel::Email { .. }) {
+        return Err(ApiError::not_implemented("Email notifications are not supported yet"));
+    }
+    if let Some(url) = channel.target_url() {
+        crate::net::ensure_public_url(url).await.map_err(ApiError::from)?;
+    }
+
+    let result = NotificationSender::new()
+        .with_public_url(state.config.alerting.public_url.clone())
+        .send_test(&channel)
+        .await;
+
+    let error = match (result.success, result.status) {
+        (true, _) => None,
+        (false, Some(status)) => Some(format!("{} returned HTTP {}", result.channel_type, status)),
+        (false, None) => result.error,
+    };
+    Ok(Json(TestChannelResponse {
+        channel_type: result.channel_type,
+        success: result.success,
+        status: result.status,
+        error,
+        retryable: result.retryable,
+        sent_at: result.sent_at,
+    }))
+}
+
+/// Lint alert rule query
+#[derive(Debug, Deserialize)]
+pub struct LintAlertQuery {
+    /// How far back to inspect data, in hours (default 7 days)
+    pub lookback_hours: Option<i64>,
+}
+
+/// Lint an alert rule against other rules and recent data
+pub async fn lint_alert_rule(
+    State(state): State<AppState>,
+    Path(rule_id): Path<Uuid>,
+    Query(query): Query<LintAlertQuery>,
+) -> Result<Json<RuleLintReport>, ApiError> {
+    let repo = state
+        .alert_repo
         .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let rules = repo
         .list_rules()
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+
+    let rule = rules
+        .iter()
+        .find(|r| r.id == rule_id)
+        .ok_or(ApiError::not_found("Rule not found".to_string()))?;
+
+    let evaluator = state
+        .alert_evaluator
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alert evaluator not configured".to_string()))?;
+
+    let lookback_minutes = query.lookback_hours.unwrap_or(24 * 7).clamp(1, 24 * 90) * 60;
+
+    let report = evaluator
+        .lint_rule(rule, &rules, lookback_minutes)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(report))
+}
+
+/// List alert events query
+#[derive(Debug, Deserialize)]
+pub struct ListAlertEventsQuery {
+    pub rule_id: Option<Uuid>,
+    pub status: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// List alert events
+pub async fn list_alert_events(
+    State(state): State<AppState>,
+    Query(query): Query<ListAlertEventsQuery>,
+) -> Result<Json<Vec<AlertEvent>>, ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let events = if query.status.as_deref() == Some("active") {
+        repo.list_active_events().await
+    } else if let Some(rule_id) = query.rule_id {
+        repo.list_events_for_rule(rule_id, query.limit.unwrap_or(50)).await
+    } else {
+        let since = query
+            .since
+            .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
+        repo.list_recent_events(since, query.limit.unwrap_or(100)).await
+    }
+    .map_err(ApiError::from)?;
+
+    Ok(Json(events))
+}
+
+/// Get alert event by ID
+pub async fn get_alert_event(
+    State(state): State<AppState>,
+    Path(event_id): Path<Uuid>,
+) -> Result<Json<AlertEvent>, ApiError> {
+    let event = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?
+        .get_event(event_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Event not found".to_string()))?;
+
+    Ok(Json(event))
+}
+
+/// Acknowledgement by the request's actor, or anonymous without one
+fn acknowledgement(
+    actor: Option<Extension<Actor>>,
+    request: Option<Json<AcknowledgeRequest>>,
+) -> Result<Acknowledgement, ApiError> {
+    let actor = actor.map_or_else(|| ANONYMOUS_ACTOR.to_string(), |Extension(Actor(actor))| actor);
+    request
+        .map(|Json(request)| request)
+        .unwrap_or_default()
+        .acknowledgement(actor, chrono::Utc::now())
+        .map_err(ApiError::from)
+}
+
+/// Acknowledge an alert, with an optional comment and assignee
+///
+/// The alert's channels are told who acknowledged it in the background.
+pub async fn acknowledge_alert(
+    State(state): State<AppState>,
+    Path(event_id): Path<Uuid>,
+    actor: Option<Extension<Actor>>,
+    request: Option<Json<AcknowledgeRequest>>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<AlertEvent>), ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let ack = acknowledgement(actor, request)?;
+
+    let before = repo
+        .get_event(event_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Event not found".to_string()))?;
+
+    if !repo.acknowledge_event(event_id, &ack).await.map_err(ApiError::from)? {
+        return Err(ApiError::conflict("Resolved alerts can't be acknowledged"));
+    }
+    let event = AlertEvent {
+        status: AlertStatus::Acknowledged,
+        acknowledgement: Some(ack.clone()),
+        ..before.clone()
+    };
+    notify_acknowledged(&state, vec![event.clone()]);
+
+    let change = AuditChange::new(
+        AuditAction::Acknowledge,
+        "alert_event",
+        event_id,
+        before.acknowledgement.as_ref(),
+        Some(&ack),
+    );
+
+    Ok((StatusCode::OK, Extension(change), Json(event)))
+}
+
+/// Send acknowledgement notifications for events to their rules' channels,
+/// recording them on each event
+///
+/// Runs in the background so the request doesn't wait on slow channels,
+/// returning the task, or None when alerting isn't configured. Digested
+/// alerts are skipped, since they were sent under a shared key.
+fn notify_acknowledged(state: &AppState, events: Vec<AlertEvent>) -> Option<tokio::task::JoinHandle<()>> {
+    let repo = state.alert_repo.clone()?;
+    let digest = state.config.alerting.digest.clone();
+    let notifier = NotificationSender::new()
+        .with_public_url(state.config.alerting.public_url.clone());
+
+    Some(tokio::spawn(async move {
+        for event in events {
+            if digest.interval_for(event.severity).is_some() {
+                continue;
+            }
+            let rule = match repo.get_rule(event.rule_id).await {
+                Ok(Some(rule)) => rule,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(event_id = %event.id, "Failed to load rule for acknowledgement: {}", e);
+                    continue;
+                }
+            };
+
+            let Some(notification) = notifier.acknowledgement(&rule, &event) else {
+                continue;
+            };
+            let records: Vec<NotificationRecord> = notifier
+                .send_all(&rule.notification_channels, &notification)
+                .await
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            if records.is_empty() {
+                continue;
+            }
+            if let Err(e) = repo.append_event_notifications(event.id, &records).await {
+                tracing::warn!(event_id = %event.id, "Failed to record acknowledgement notifications: {}", e);
+            }
+        }
+    }))
+}
+
+/// List alert incidents query
+#[derive(Debug, Deserialize)]
+pub struct ListAlertIncidentsQuery {
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// List alert incidents, including any still open from before `since`
+pub async fn list_alert_incidents(
+    State(state): State<AppState>,
+    Query(query): Query<ListAlertIncidentsQuery>,
+) -> Result<Json<Vec<AlertIncident>>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
+
+    let incidents = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?
+        .list_incidents(since, query.limit.unwrap_or(100).clamp(1, 1000))
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(incidents))
+}
+
+/// An incident with the events grouped into it
+#[derive(Debug, Serialize)]
+pub struct AlertIncidentDetail {
+    pub incident: AlertIncident,
+    pub events: Vec<AlertEvent>,
+}
+
+/// Get an alert incident and its events
+pub async fn get_alert_incident(
+    State(state): State<AppState>,
+    Path(incident_id): Path<Uuid>,
+) -> Result<Json<AlertIncidentDetail>, ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let incident = repo
+        .get_incident(incident_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Incident not found".to_string()))?;
+
+    let events = repo
+        .list_incident_events(incident_id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(AlertIncidentDetail { incident, events }))
+}
+
+/// Acknowledge an incident and every active event in it, with an optional
+/// comment and assignee
+pub async fn acknowledge_alert_incident(
+    State(state): State<AppState>,
+    Path(incident_id): Path<Uuid>,
+    actor: Option<Extension<Actor>>,
+    request: Option<Json<AcknowledgeRequest>>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = state
+        .alert_repo
+        .as_ref()
+        .ok_or(ApiError::unavailable("Alerting not configured".to_string()))?;
+
+    let ack = acknowledgement(actor, request)?;
+
+    let acknowledged = repo
+        .acknowledge_incident(incident_id, &ack)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Incident not found".to_string()))?;
+
+    if !acknowledged.is_empty() {
+        let events = repo
+            .list_incident_events(incident_id)
+            .await
+            .map_err(ApiError::from)?
+            .into_iter()
+            .filter(|event| acknowledged.contains(&event.id))
+            .collect();
+        notify_acknowledged(&state, events);
+    }
+    let change = AuditChange::new(
+        AuditAction::Acknowledge,
+        "alert_incident",
+        incident_id,
+        None::<&()>,
+        Some(&ack),
+    );
+
+    Ok((StatusCode::OK, Extension(change)))
+}
+
+// ============================================================================
+// Saved View Handlers
+// ============================================================================
+
+use crate::models::{SavedView, SavedViewInput};
+
+/// Saved views are stored in PostgreSQL and unavailable on other backends
+fn view_repository(state: &AppState) -> Result<&ViewRepository, ApiError> {
+    state.views.as_ref().ok_or(ApiError::unavailable(
+        "Saved views require PostgreSQL storage".to_string(),
+    ))
+}
+
+/// List saved views query
+#[derive(Debug, Deserialize)]
+pub struct ListViewsQuery {
+    pub owner: Option<String>,
+}
+
+/// List saved views by name
+pub async fn list_views(
+    State(state): State<AppState>,
+    Query(query): Query<ListViewsQuery>,
+) -> Result<Json<Vec<SavedView>>, ApiError> {
+    let views = view_repository(&state)?
+        .list(query.owner.as_deref())
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(views))
+}
+
+/// Save a new view
+pub async fn create_view(
+    State(state): State<AppState>,
+    Json(input): Json<SavedViewInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<SavedView>), ApiError> {
+    let view = view_repository(&state)?
+        .create(input)
+        .await
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("view", view.id, &view);
+
+    Ok((StatusCode::CREATED, Extension(change), Json(view)))
+}
+
+/// Get a saved view by ID or name
+pub async fn get_view(
+    State(state): State<AppState>,
+    Path(view): Path<String>,
+) -> Result<Json<SavedView>, ApiError> {
+    view_repository(&state)?
+        .get(&view)
+        .await
+        .map_err(ApiError::from)?
+        .map(Json)
+        .ok_or(ApiError::not_found(format!("No view named '{}'", view)))
+}
+
+/// Replace a saved view
+pub async fn update_view(
+    State(state): State<AppState>,
+    Path(view): Path<String>,
+    Json(input): Json<SavedViewInput>,
+) -> Result<(Extension<AuditChange>, Json<SavedView>), ApiError> {
+    let repo = view_repository(&state)?;
+    let before = repo
+        .get(&view)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found(format!("No view named '{}'", view)))?;
+
+    let updated = repo
+        .update(&before.id.to_string(), input)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found(format!("No view named '{}'", view)))?;
+    let change = AuditChange::updated("view", updated.id, &before, &updated);
+
+    Ok((Extension(change), Json(updated)))
+}
+
+/// Delete a saved view
+pub async fn delete_view(
+    State(state): State<AppState>,
+    Path(view): Path<String>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = view_repository(&state)?;
+    let before = repo
+        .get(&view)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found(format!("No view named '{}'", view)))?;
+
+    let deleted = repo
+        .delete(&before.id.to_string())
+        .await
+        .map_err(ApiError::from)?;
+
+    if deleted {
+        let change = AuditChange::deleted("view", before.id, &before);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found(format!("No view named '{}'", view)))
+    }
+}
+
+// ============================================================================
+// Attribute Schema Handlers
+// ============================================================================
+
+use crate::models::{AttributeSchema, AttributeSchemaInput, SchemaComplianceReport};
+
+/// Attribute schemas are stored in PostgreSQL and unavailable on other backends
+fn schema_registry(state: &AppState) -> Result<&SchemaRegistry, ApiError> {
+    state.pipeline.schema_registry().ok_or(ApiError::unavailable(
+        "Attribute schemas require PostgreSQL storage".to_string(),
+    ))
+}
+
+/// List registered attribute schemas
+pub async fn list_schemas(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AttributeSchema>>, ApiError> {
+    Ok(Json(schema_registry(&state)?.list()))
+}
+
+/// Get the attribute schema for a service
+pub async fn get_schema(
+    State(state): State<AppState>,
+    Path(service): Path<String>,
+) -> Result<Json<AttributeSchema>, ApiError> {
+    schema_registry(&state)?
+        .get(&service)
+        .map(Json)
+        .ok_or(ApiError::not_found(format!("No schema registered for '{}'", service)))
+}
+
+/// Register or replace the attribute schema for a service
+pub async fn put_schema(
+    State(state): State<AppState>,
+    Path(service): Path<String>,
+    Json(input): Json<AttributeSchemaInput>,
+) -> Result<(Extension<AuditChange>, Json<AttributeSchema>), ApiError> {
+    let registry = schema_registry(&state)?;
+    let before = registry.get(&service);
+
+    let schema = registry
+        .register(&service, input)
+        .await
+        .map_err(ApiError::from)?;
+    let change = match &before {
+        Some(before) => AuditChange::updated("schema", &service, before, &schema),
+        None => AuditChange::created("schema", &service, &schema),
+    };
+
+    Ok((Extension(change), Json(schema)))
+}
+
+/// Remove the attribute schema for a service
+pub async fn delete_schema(
+    State(state): State<AppState>,
+    Path(service): Path<String>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let registry = schema_registry(&state)?;
+    let before = registry.get(&service);
+
+    let deleted = registry
+        .remove(&service)
+        .await
+        .map_err(ApiError::from)?;
+
+    if deleted {
+        let change = AuditChange::new(AuditAction::Delete, "schema", &service, before.as_ref(), None::<&()>);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found(format!("No schema registered for '{}'", service)))
+    }
+}
+
+/// Schema compliance query
+#[derive(Debug, Deserialize)]
+pub struct SchemaComplianceQuery {
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Get schema compliance for a service
+pub async fn get_schema_compliance(
+    State(state): State<AppState>,
+    Path(service): Path<String>,
+    Query(query): Query<SchemaComplianceQuery>,
+) -> Result<Json<SchemaComplianceReport>, ApiError> {
+    let since = query
+        .since
+        .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::hours(24));
+    let until = query.until.unwrap_or_else(chrono::Utc::now);
+
+    let report = schema_registry(&state)?
+        .compliance(&service, since, until)
+        .await
+        .map_err(ApiError::from)?;
 
-    Ok(Json(rules))
+    Ok(Json(report))
 }
 
-/// Create alert rule
-pub async fn create_alert_rule(
+// ============================================================================
+// Webhook Subscription Handlers
+// ============================================================================
+
+fn subscription_registry(state: &AppState) -> Result<&SubscriptionRegistry, ApiError> {
+    state.pipeline.subscriptions().ok_or(ApiError::unavailable(
+        "Webhook subscriptions require PostgreSQL storage".to_string(),
+    ))
+}
+
+/// List webhook subscriptions
+pub async fn list_subscriptions(
     State(state): State<AppState>,
-    Json(input): Json<AlertRuleInput>,
-) -> Result<(StatusCode, Json<AlertRule>), (StatusCode, String)> {
-    let rule = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .create_rule(input)
+) -> Result<Json<Vec<WebhookSubscription>>, ApiError> {
+    Ok(Json(subscription_registry(&state)?.list()))
+}
+
+/// Get a webhook subscription
+pub async fn get_subscription(
+    State(state): State<AppState>,
+    Path(subscription_id): Path<Uuid>,
+) -> Result<Json<WebhookSubscription>, ApiError> {
+    subscription_registry(&state)?
+        .get(subscription_id)
+        .map(Json)
+        .ok_or(ApiError::not_found("Subscription not found".to_string()))
+}
+
+/// Create a webhook subscription
+pub async fn create_subscription(
+    State(state): State<AppState>,
+    Json(input): Json<WebhookSubscriptionInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<WebhookSubscription>), ApiError> {
+    let subscription = subscription_registry(&state)?
+        .create(input)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("subscription", subscription.id, &subscription);
 
-    Ok((StatusCode::CREATED, Json(rule)))
+    Ok((StatusCode::CREATED, Extension(change), Json(subscription)))
 }
 
-/// Get alert rule by ID
-pub async fn get_alert_rule(
+/// Replace a webhook subscription
+pub async fn update_subscription(
     State(state): State<AppState>,
-    Path(rule_id): Path<Uuid>,
-) -> Result<Json<AlertRule>, (StatusCode, String)> {
-    let rule = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .get_rule(rule_id)
+    Path(subscription_id): Path<Uuid>,
+    Json(input): Json<WebhookSubscriptionInput>,
+) -> Result<(Extension<AuditChange>, Json<WebhookSubscription>), ApiError> {
+    let registry = subscription_registry(&state)?;
+    let before = registry
+        .get(subscription_id)
+        .ok_or(ApiError::not_found("Subscription not found".to_string()))?;
+
+    let subscription = registry
+        .update(subscription_id, input)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Rule not found".to_string()))?;
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Subscription not found".to_string()))?;
+    let change = AuditChange::updated("subscription", subscription_id, &before, &subscription);
 
-    Ok(Json(rule))
+    Ok((Extension(change), Json(subscription)))
 }
 
-/// Update alert rule
-pub async fn update_alert_rule(
+/// Delete a webhook subscription and its delivery history
+pub async fn delete_subscription(
     State(state): State<AppState>,
-    Path(rule_id): Path<Uuid>,
-    Json(input): Json<AlertRuleInput>,
-) -> Result<Json<AlertRule>, (StatusCode, String)> {
-    let rule = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .update_rule(rule_id, input)
+    Path(subscription_id): Path<Uuid>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let registry = subscription_registry(&state)?;
+    let before = registry.get(subscription_id);
+
+    let deleted = registry
+        .remove(subscription_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Rule not found".to_string()))?;
+        .map_err(ApiError::from)?;
 
-    Ok(Json(rule))
+    if deleted {
+        let change = AuditChange::new(
+            AuditAction::Delete,
+            "subscription",
+            subscription_id,
+            before.as_ref(),
+            None::<&()>,
+        );
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found("Subscription not found".to_string()))
+    }
 }
 
-/// Delete alert rule
-pub async fn delete_alert_rule(
+/// Delivery history query
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    pub limit: Option<i64>,
+}
+
+/// List a webhook subscription's most recent deliveries
+pub async fn list_subscription_deliveries(
     State(state): State<AppState>,
-    Path(rule_id): Path<Uuid>,
-) -> Result<StatusCode, (StatusCode, String)> {
-    let deleted = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .delete_rule(rule_id)
+    Path(subscription_id): Path<Uuid>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let registry = subscription_registry(&state)?;
+    if registry.get(subscription_id).is_none() {
+        return Err(ApiError::not_found("Subscription not found".to_string()));
+    }
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let deliveries = registry
+        .deliveries(subscription_id, limit)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(deliveries))
+}
+
+// ============================================================================
+// Budget Handlers
+// ============================================================================
+
+use crate::models::{Budget, BudgetInput, BudgetStatus};
+
+/// Budgets are stored in PostgreSQL and unavailable on other backends
+fn budget_repository(state: &AppState) -> Result<&BudgetRepository, ApiError> {
+    state.budgets.as_ref().ok_or(ApiError::unavailable(
+        "Budgets require PostgreSQL storage".to_string(),
+    ))
+}
+
+/// List budgets by name, with their spend this period
+pub async fn list_budgets(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BudgetStatus>>, ApiError> {
+    let repo = budget_repository(&state)?;
+    let now = chrono::Utc::now();
+
+    let budgets = repo.list().await.map_err(ApiError::from)?;
+    let statuses = repo.statuses(budgets, now).await.map_err(ApiError::from)?;
+
+    Ok(Json(statuses))
+}
+
+/// Get a budget with its spend this period
+pub async fn get_budget(
+    State(state): State<AppState>,
+    Path(budget_id): Path<Uuid>,
+) -> Result<Json<BudgetStatus>, ApiError> {
+    let repo = budget_repository(&state)?;
+    let budget = repo
+        .get(budget_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Budget not found".to_string()))?;
+
+    let status = repo
+        .status(budget, chrono::Utc::now())
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(status))
+}
+
+/// Create a budget
+pub async fn create_budget(
+    State(state): State<AppState>,
+    Json(input): Json<BudgetInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<Budget>), ApiError> {
+    let budget = budget_repository(&state)?
+        .create(input)
+        .await
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("budget", budget.id, &budget);
+
+    Ok((StatusCode::CREATED, Extension(change), Json(budget)))
+}
+
+/// Replace a budget
+pub async fn update_budget(
+    State(state): State<AppState>,
+    Path(budget_id): Path<Uuid>,
+    Json(input): Json<BudgetInput>,
+) -> Result<(Extension<AuditChange>, Json<Budget>), ApiError> {
+    let repo = budget_repository(&state)?;
+    let before = repo
+        .get(budget_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Budget not found".to_string()))?;
+
+    let budget = repo
+        .update(budget_id, input)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Budget not found".to_string()))?;
+    let change = AuditChange::updated("budget", budget_id, &before, &budget);
+
+    Ok((Extension(change), Json(budget)))
+}
+
+/// Delete a budget and its threshold history
+pub async fn delete_budget(
+    State(state): State<AppState>,
+    Path(budget_id): Path<Uuid>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = budget_repository(&state)?;
+    let before = repo
+        .get(budget_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("Budget not found".to_string()))?;
+
+    let deleted = repo.delete(budget_id).await.map_err(ApiError::from)?;
 
     if deleted {
-        Ok(StatusCode::NO_CONTENT)
+        let change = AuditChange::deleted("budget", budget_id, &before);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
     } else {
-        Err((StatusCode::NOT_FOUND, "Rule not found".to_string()))
+        Err(ApiError::not_found("Budget not found".to_string()))
     }
 }
 
-/// Test alert rule
-#[derive(Serialize)]
-pub struct TestAlertResponse {
-    pub would_trigger: bool,
-    pub event: Option<AlertEvent>,
-    pub current_value: Option<f64>,
+// ============================================================================
+// SLO Handlers
+// ============================================================================
+
+use crate::models::{BurnRate, Slo, SloInput, SloStatus};
+
+/// Longest window a burn-rate query may look back over
+const MAX_BURN_RATE_WINDOW_DAYS: u64 = 90;
+
+/// SLOs are stored in PostgreSQL and unavailable on other backends
+fn slo_repository(state: &AppState) -> Result<&SloRepository, ApiError> {
+    state.slos.as_ref().ok_or(ApiError::unavailable(
+        "SLOs require PostgreSQL storage".to_string(),
+    ))
 }
 
-pub async fn test_alert_rule(
+async fn find_slo(repo: &SloRepository, slo_id: Uuid) -> Result<Slo, ApiError> {
+    repo.get(slo_id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("SLO not found".to_string()))
+}
+
+/// List SLOs by name, with their performance over their windows
+pub async fn list_slos(
     State(state): State<AppState>,
-    Path(rule_id): Path<Uuid>,
-) -> Result<Json<TestAlertResponse>, (StatusCode, String)> {
-    let rule = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .get_rule(rule_id)
+) -> Result<Json<Vec<SloStatus>>, ApiError> {
+    let repo = slo_repository(&state)?;
+    let now = chrono::Utc::now();
+
+    let mut statuses = Vec::new();
+    for slo in repo.list().await.map_err(ApiError::from)? {
+        statuses.push(repo.status(slo, now).await.map_err(ApiError::from)?);
+    }
+
+    Ok(Json(statuses))
+}
+
+/// Get an SLO with its performance over its window
+pub async fn get_slo(
+    State(state): State<AppState>,
+    Path(slo_id): Path<Uuid>,
+) -> Result<Json<SloStatus>, ApiError> {
+    let repo = slo_repository(&state)?;
+    let slo = find_slo(repo, slo_id).await?;
+
+    let status = repo
+        .status(slo, chrono::Utc::now())
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Rule not found".to_string()))?;
+        .map_err(ApiError::from)?;
 
-    let evaluator = state
-        .alert_evaluator
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alert evaluator not configured".to_string()))?;
+    Ok(Json(status))
+}
 
-    let event = evaluator
-        .test_rule(&rule)
+/// Create an SLO
+pub async fn create_slo(
+    State(state): State<AppState>,
+    Json(input): Json<SloInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<Slo>), ApiError> {
+    let slo = slo_repository(&state)?
+        .create(input)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("slo", slo.id, &slo);
 
-    Ok(Json(TestAlertResponse {
-        would_trigger: event.is_some(),
-        current_value: event.as_ref().map(|e| e.metric_value),
-        event,
-    }))
+    Ok((StatusCode::CREATED, Extension(change), Json(slo)))
 }
 
-/// List alert events query
-#[derive(Debug, Deserialize)]
-pub struct ListAlertEventsQuery {
-    pub rule_id: Option<Uuid>,
-    pub status: Option<String>,
-    pub since: Option<chrono::DateTime<chrono::Utc>>,
-    pub limit: Option<i64>,
+/// Replace an SLO
+pub async fn update_slo(
+    State(state): State<AppState>,
+    Path(slo_id): Path<Uuid>,
+    Json(input): Json<SloInput>,
+) -> Result<(Extension<AuditChange>, Json<Slo>), ApiError> {
+    let repo = slo_repository(&state)?;
+    let before = find_slo(repo, slo_id).await?;
+
+    let slo = repo
+        .update(slo_id, input)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or(ApiError::not_found("SLO not found".to_string()))?;
+    let change = AuditChange::updated("slo", slo_id, &before, &slo);
+
+    Ok((Extension(change), Json(slo)))
 }
 
-/// List alert events
-pub async fn list_alert_events(
+/// Delete an SLO and its alert history
+pub async fn delete_slo(
     State(state): State<AppState>,
-    Query(query): Query<ListAlertEventsQuery>,
-) -> Result<Json<Vec<AlertEvent>>, (StatusCode, String)> {
-    let repo = state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?;
+    Path(slo_id): Path<Uuid>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    let repo = slo_repository(&state)?;
+    let before = find_slo(repo, slo_id).await?;
 
-    let events = if query.status.as_deref() == Some("active") {
-        repo.list_active_events().await
-    } else if let Some(rule_id) = query.rule_id {
-        repo.list_events_for_rule(rule_id, query.limit.unwrap_or(50)).await
+    let deleted = repo.delete(slo_id).await.map_err(ApiError::from)?;
+
+    if deleted {
+        let change = AuditChange::deleted("slo", slo_id, &before);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
     } else {
-        let since = query
-            .since
-            .unwrap_or_else(|| chrono::Utc::now() - chrono::Duration::days(7));
-        repo.list_recent_events(since, query.limit.unwrap_or(100)).await
+        Err(ApiError::not_found("SLO not found".to_string()))
     }
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+}
 
-    Ok(Json(events))
+/// Burn-rate query parameters
+#[derive(Debug, Deserialize)]
+pub struct BurnRateQuery {
+    /// Comma-separated windows ending now, e.g. `5m,1h,6h`
+    pub windows: Option<String>,
 }
 
-/// Get alert event by ID
-pub async fn get_alert_event(
+/// Get an SLO's burn rates over windows ending now
+///
+/// Without `windows`, reports the windows its burn-rate alerts use.
+pub async fn get_slo_burn_rates(
     State(state): State<AppState>,
-    Path(event_id): Path<Uuid>,
-) -> Result<Json<AlertEvent>, (StatusCode, String)> {
-    let event = state
-        .alert_repo
+    Path(slo_id): Path<Uuid>,
+    Query(query): Query<BurnRateQuery>,
+) -> Result<Json<Vec<BurnRate>>, ApiError> {
+    let repo = slo_repository(&state)?;
+    let slo = find_slo(repo, slo_id).await?;
+
+    let windows = match query.windows.as_deref() {
+        Some(windows) => parse_burn_rate_windows(windows)?,
+        None => crate::slos::alert_windows(&slo.burn_rate_alerts),
+    };
+
+    let rates = repo
+        .burn_rates(&slo, &windows, chrono::Utc::now())
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(rates))
+}
+
+/// Parse `5m,1h,6h` into minutes
+fn parse_burn_rate_windows(windows: &str) -> Result<Vec<u32>, ApiError> {
+    windows
+        .split(',')
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let minutes = humantime::parse_duration(w)
+                .map_err(|e| ApiError::invalid(format!("Invalid window '{}': {}", w, e)))?
+                .as_secs()
+                / 60;
+            if minutes == 0 || minutes > MAX_BURN_RATE_WINDOW_DAYS * 24 * 60 {
+                return Err(ApiError::invalid(format!(
+                    "Window '{}' must be 1 minute to {} days",
+                    w, MAX_BURN_RATE_WINDOW_DAYS
+                )));
+            }
+            Ok(u32::try_from(minutes).unwrap_or(u32::MAX))
+        })
+        .collect()
+}
+
+// ============================================================================
+// Admin Handlers
+// ============================================================================
+
+/// Live pipeline state, for working out where spans went
+#[derive(Serialize)]
+pub struct PipelineStatus {
+    #[serde(flatten)]
+    pub stats: PipelineStats,
+    /// Whether span costs are calculated at ingest
+    pub cost_calculation: bool,
+    /// Whether spans are checked against attribute schemas
+    pub schema_validation: bool,
+    /// Whether matching spans are posted to webhook subscriptions
+    pub webhooks: bool,
+    /// Whether spans are checked against per-trace guardrail rules
+    pub trace_guardrails: bool,
+    /// Whether spans are published for real-time streaming
+    pub streaming: bool,
+}
+
+/// Get live pipeline statistics: queue fill, ingest and write rates, the
+/// last batch write and spans dropped or shed along the way
+pub async fn get_pipeline_status(State(state): State<AppState>) -> Json<PipelineStatus> {
+    let pipeline = &state.pipeline;
+    let config = pipeline.config();
+
+    Json(PipelineStatus {
+        stats: pipeline.stats(),
+        cost_calculation: config.enable_cost_calculation,
+        schema_validation: config.enable_schema_validation && pipeline.schema_registry().is_some(),
+        webhooks: config.enable_webhooks && pipeline.subscriptions().is_some(),
+        trace_guardrails: config.enable_guardrails && pipeline.guardrails().is_some(),
+        streaming: config.enable_redis_streaming && state.pubsub.is_some(),
+    })
+}
+
+/// Get the effective configuration, with credentials redacted
+pub async fn get_admin_config(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(state.config.redacted())
+}
+
+/// List every priced model with its effective price and where it came from
+pub async fn list_pricing(State(state): State<AppState>) -> Json<Vec<PricingEntry>> {
+    Json(state.pipeline.cost_calculator().list())
+}
+
+/// Outcome of reloading model prices
+#[derive(Debug, Serialize)]
+pub struct PricingReloadResponse {
+    /// Pricing file or URL read, if one is configured
+    pub source: Option<String>,
+    /// Models it prices
+    pub models: usize,
+}
+
+/// Re-read the configured pricing file or URL now, rather than waiting for
+/// the next reload
+///
+/// The audit entry records the price list before and after.
+pub async fn reload_pricing(
+    State(state): State<AppState>,
+) -> Result<(Extension<AuditChange>, Json<PricingReloadResponse>), ApiError> {
+    let pricing = &state.pipeline.config().pricing;
+    let calculator = state.pipeline.cost_calculator();
+    let before = calculator.list();
+    let models = calculator.reload(pricing).await.map_err(ApiError::from)?;
+
+    let source = pricing.source.clone();
+    let change = AuditChange::updated(
+        "pricing",
+        source.as_deref().unwrap_or("built-in"),
+        &before,
+        &calculator.list(),
+    );
+    Ok((Extension(change), Json(PricingReloadResponse { source, models })))
+}
+
+// ============================================================================
+// Audit Log Handlers
+// ============================================================================
+
+/// List changes made through the API, newest first
+///
+/// Filter by `actor`, `action`, `resource_type`, `resource_id` and a
+/// `since`/`until` range; `limit` defaults to 100.
+pub async fn list_audit_entries(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    let entries = state
+        .audit
         .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .get_event(event_id)
+        .ok_or(ApiError::unavailable("The audit log requires PostgreSQL storage".to_string()))?
+        .list(&query)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))?;
+        .map_err(ApiError::from)?;
 
-    Ok(Json(event))
+    Ok(Json(entries))
 }
 
-/// Acknowledge an alert
-pub async fn acknowledge_alert(
+// ============================================================================
+// Debug Session Handlers
+// ============================================================================
+
+use crate::models::{DebugSession, DebugSessionInput};
+
+/// List active debug sessions
+pub async fn list_debug_sessions(State(state): State<AppState>) -> Json<Vec<DebugSession>> {
+    Json(state.pipeline.debug_sessions().list())
+}
+
+/// Start a debug session that streams full span payloads for a limited time
+pub async fn start_debug_session(
     State(state): State<AppState>,
-    Path(event_id): Path<Uuid>,
-) -> Result<StatusCode, (StatusCode, String)> {
+    Json(input): Json<DebugSessionInput>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<DebugSession>), ApiError> {
+    if state.pubsub.is_none() {
+        return Err(ApiError::unavailable("Streaming not configured".to_string()));
+    }
+
+    let session = state
+        .pipeline
+        .debug_sessions()
+        .start(input)
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("debug_session", session.id, &session);
+
+    Ok((StatusCode::CREATED, Extension(change), Json(session)))
+}
+
+/// End a debug session early
+pub async fn stop_debug_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<(StatusCode, Extension<AuditChange>), ApiError> {
+    if state.pipeline.debug_sessions().stop(session_id) {
+        let change = AuditChange::new(AuditAction::Delete, "debug_session", session_id, None::<&()>, None::<&()>);
+        Ok((StatusCode::NO_CONTENT, Extension(change)))
+    } else {
+        Err(ApiError::not_found("Debug session not found".to_string()))
+    }
+}
+
+/// Queue a long-running rollup or export
+pub async fn submit_query_job(
+    State(state): State<AppState>,
+    Json(request): Json<QueryJobRequest>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<QueryJob>), ApiError> {
+    if let QueryJobRequest::CostRollup { group_by, .. } = &request {
+        check_cost_group(&state.config, group_by)?;
+    }
+    let job = state.jobs.submit(request).map_err(ApiError::from)?;
+    let change = AuditChange::created("query_job", job.id, &job);
+    Ok((StatusCode::ACCEPTED, Extension(change), Json(job)))
+}
+
+/// Get a query job's progress, and its result once completed
+pub async fn get_query_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<QueryJob>, ApiError> {
     state
-        .alert_repo
-        .as_ref()
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Alerting not configured".to_string()))?
-        .acknowledge_event(event_id)
+        .jobs
+        .fetch(job_id)
+        .await
+        .map_err(ApiError::from)?
+        .map(Json)
+        .ok_or(ApiError::not_found("Job not found".to_string()))
+}
+
+/// Queue a bulk export of spans as CSV, JSONL or Parquet
+///
+/// Poll `GET /api/v1/exports/{id}` until the job completes, then fetch the
+/// file from its `download_url`, or find it at its S3 `location`.
+pub async fn submit_export(
+    State(state): State<AppState>,
+    Json(request): Json<BulkExportRequest>,
+) -> Result<(StatusCode, Extension<AuditChange>, Json<QueryJob>), ApiError> {
+    let job = state
+        .jobs
+        .submit(QueryJobRequest::BulkExport(request))
+        .map_err(ApiError::from)?;
+    let change = AuditChange::created("export", job.id, &job);
+    Ok((StatusCode::ACCEPTED, Extension(change), Json(job)))
+}
+
+/// Download the file of a completed bulk export
+pub async fn download_export(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Response, ApiError> {
+    let job = state
+        .jobs
+        .get(job_id)
+        .ok_or(ApiError::not_found("Job not found".to_string()))?;
+    if job.status != JobStatus::Completed {
+        return Err(ApiError::conflict("Export has not completed".to_string()));
+    }
+
+    let (format, path) = state
+        .jobs
+        .download(job_id)
+        .ok_or(ApiError::not_found("Export has no file to download".to_string()))?;
+    // The job may have been pruned since
+    let file = tokio::fs::File::open(&path)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|_| ApiError::not_found("Export has no file to download".to_string()))?;
+    let disposition = format!("attachment; filename=\"agenttrace-export-{}.{}\"", job_id, format.extension());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+    )
+        .into_response())
+}
 
-    Ok(StatusCode::OK)
+/// Cancel a query job
+pub async fn cancel_query_job(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+) -> Result<(Extension<AuditChange>, Json<QueryJob>), ApiError> {
+    let before = state
+        .jobs
+        .get(job_id)
+        .ok_or(ApiError::not_found("Job not found".to_string()))?;
+
+    let job = state
+        .jobs
+        .cancel(job_id)
+        .ok_or(ApiError::not_found("Job not found".to_string()))?;
+    let resource_type = match job.request {
+        QueryJobRequest::BulkExport(_) => "export",
+        _ => "query_job",
+    };
+    let change = AuditChange::new(AuditAction::Cancel, resource_type, job_id, Some(&before), Some(&job));
+
+    Ok((Extension(change), Json(job)))
 }
 
 /// SSE stream endpoint for real-time span updates
 pub async fn stream_spans(
     State(state): State<AppState>,
     Query(query): Query<StreamQuery>,
-) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
-    let redis = state
-        .redis
-        .ok_or((StatusCode::SERVICE_UNAVAILABLE, "Redis not configured".to_string()))?;
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let pubsub = state
+        .pubsub
+        .ok_or(ApiError::unavailable("Streaming not configured".to_string()))?;
+
+    let filter = query.span_filter();
 
     // Determine which channel to subscribe to
     let channel = if let Some(trace_id) = query.trace_id {
@@ -797,18 +3225,29 @@ pub async fn stream_spans(
     } else {
         match query.channel.as_deref() {
             Some("llm") => "agenttrace:llm".to_string(),
+            Some(c) if c.starts_with("debug:") => {
+                let session_id = c.trim_start_matches("debug:");
+                Uuid::parse_str(session_id)
+                    .map_err(|_| ApiError::invalid("Invalid debug session id".to_string()))?;
+                format!("agenttrace:{}", c)
+            }
             _ => "agenttrace:spans".to_string(),
         }
     };
 
-    // Subscribe to the Redis channel
-    let rx = redis
+    // Subscribe to the channel
+    let rx = pubsub
         .subscribe(&channel)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(ApiError::from)?;
 
-    // Convert the receiver into a stream of SSE events
+    // Convert the receiver into a stream of SSE events, dropping spans that
+    // don't match the filter before they go over the wire
     let stream = ReceiverStream::new(rx)
+        .filter(move |payload| match &filter {
+            Some(filter) => serde_json::from_str::<Span>(payload).is_ok_and(|span| filter.matches(&span)),
+            None => true,
+        })
         .map(|payload| {
             Ok(Event::default()
                 .event("span")
@@ -823,3 +3262,94 @@ pub async fn stream_spans(
             .text("keepalive"),
     ))
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::collector::PipelineConfig;
+    use crate::db::{LocalPubSub, SqliteStore, Storage};
+
+    pub(crate) async fn test_state() -> AppState {
+        let store: Arc<dyn SpanStore> = Arc::new(SqliteStore::open(":memory:").await.unwrap());
+        let storage = Storage {
+            spans: store.clone(),
+            pubsub: Arc::new(LocalPubSub::new()),
+            database: None,
+        };
+        let config = Config::default();
+
+        AppState {
+            pipeline: Arc::new(Pipeline::new(PipelineConfig::default(), &storage)),
+            span_repo: store.clone(),
+            pubsub: None,
+            alert_repo: None,
+            alert_evaluator: None,
+            annotations: None,
+            jobs: Arc::new(QueryJobs::new(store, &config.jobs)),
+            views: None,
+            audit: None,
+            budgets: None,
+            slos: None,
+            config: Arc::new(config),
+        }
+    }
+
+    #[test]
+    fn test_acknowledgement_takes_the_actor() {
+        let request = AcknowledgeRequest {
+            comment: Some("  rolling back  ".to_string()),
+            assignee: Some("".to_string()),
+        };
+        let ack = acknowledgement(Some(Extension(Actor("dana".to_string()))), Some(Json(request))).unwrap();
+        assert_eq!(ack.acknowledged_by, "dana");
+        assert_eq!(ack.comment.as_deref(), Some("rolling back"));
+        assert_eq!(ack.assignee, None);
+
+        // No header and no body is still an acknowledgement
+        let ack = acknowledgement(None, None).unwrap();
+        assert_eq!(ack.acknowledged_by, ANONYMOUS_ACTOR);
+        assert_eq!(ack.summary(), format!("acked by {}", ANONYMOUS_ACTOR));
+
+        let long = AcknowledgeRequest {
+            comment: Some("x".repeat(10_000)),
+            assignee: None,
+        };
+        let err = acknowledgement(None, Some(Json(long))).unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_acknowledging_needs_alerting() {
+        let state = test_state().await;
+
+        let err = acknowledge_alert(State(state.clone()), Path(Uuid::new_v4()), None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+        let err = acknowledge_alert_incident(State(state.clone()), Path(Uuid::new_v4()), None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::SERVICE_UNAVAILABLE);
+
+        // Nothing to load rules from, so nothing is sent
+        let rule = AlertRule::new("High error rate", "error_rate", crate::models::Operator::Gt, 5.0);
+        let event = AlertEvent {
+            id: Uuid::new_v4(),
+            rule_id: rule.id,
+            triggered_at: chrono::Utc::now(),
+            resolved_at: None,
+            status: AlertStatus::Acknowledged,
+            severity: rule.severity,
+            message: "error_rate exceeded threshold".to_string(),
+            metric_value: 7.0,
+            threshold_value: 5.0,
+            service_name: None,
+            trace_ids: vec![],
+            notifications_sent: vec![],
+            metadata: serde_json::json!({}),
+            incident_id: None,
+            acknowledgement: acknowledgement(None, None).ok(),
+        };
+        assert!(notify_acknowledged(&state, vec![event]).is_none());
+    }
+}
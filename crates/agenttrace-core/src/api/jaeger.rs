@@ -0,0 +1,280 @@
+//! Jaeger-compatible query API
+//!
+//! Read-only endpoints shaped like Jaeger's HTTP query API, so Grafana's
+//! Jaeger datasource can browse AgentTrace traces:
+//!
+//! - `GET /api/services`
+//! - `GET /api/services/{service}/operations`
+//! - `GET /api/traces?service=...&operation=...&tags=...&start=...&end=...`
+//! - `GET /api/traces/{trace_id}`
+//!
+//! Traces are translated with [`crate::export::jaeger`].
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::export::jaeger::{to_jaeger_trace, JaegerTrace};
+use crate::models::{Span, SpanQuery, TraceSearch};
+
+use super::handlers::AppState;
+
+/// How far back service and operation lists look
+const LOOKBACK_DAYS: i64 = 7;
+
+/// Default number of traces for a search, as in Jaeger's UI
+const DEFAULT_LIMIT: i64 = 20;
+
+/// Most traces a search may return
+const MAX_LIMIT: i64 = 1_500;
+
+/// Response envelope used by every Jaeger endpoint
+#[derive(Debug, Serialize)]
+pub struct JaegerResponse<T> {
+    /// The result
+    pub data: T,
+    /// Number of items in `data`
+    pub total: usize,
+    /// Always 0; Jaeger's UI doesn't page
+    pub limit: usize,
+    /// Always 0
+    pub offset: usize,
+    /// Set when the request failed
+    pub errors: Option<Vec<JaegerError>>,
+}
+
+impl<T> JaegerResponse<T> {
+    fn new(data: T, total: usize) -> Self {
+        Self { data, total, limit: 0, offset: 0, errors: None }
+    }
+}
+
+/// An error in Jaeger's envelope
+#[derive(Debug, Serialize)]
+pub struct JaegerError {
+    /// HTTP status code
+    pub code: u16,
+    /// What went wrong
+    pub msg: String,
+}
+
+/// Error response in Jaeger's envelope
+type Rejection = (StatusCode, Json<JaegerResponse<Option<()>>>);
+
+type JaegerResult<T> = Result<Json<JaegerResponse<T>>, Rejection>;
+
+fn jaeger_error(code: StatusCode, msg: impl Into<String>) -> Rejection {
+    let mut body = JaegerResponse::new(None, 0);
+    body.errors = Some(vec![JaegerError { code: code.as_u16(), msg: msg.into() }]);
+    (code, Json(body))
+}
+
+fn internal(e: crate::error::Error) -> Rejection {
+    match e {
+        crate::error::Error::Validation(msg) => jaeger_error(StatusCode::BAD_REQUEST, msg),
+        other => jaeger_error(StatusCode::INTERNAL_SERVER_ERROR, other.to_string()),
+    }
+}
+
+/// `GET /api/services`
+pub async fn list_services(State(state): State<AppState>) -> JaegerResult<Vec<String>> {
+    let until = Utc::now();
+    let since = until - chrono::Duration::days(LOOKBACK_DAYS);
+
+    let services: Vec<String> = state
+        .span_repo
+        .list_services(since, until)
+        .await
+        .map_err(internal)?
+        .into_iter()
+        .map(|s| s.service_name)
+        .collect();
+
+    let total = services.len();
+    Ok(Json(JaegerResponse::new(services, total)))
+}
+
+/// `GET /api/services/{service}/operations`
+///
+/// Lists the service's busiest operations.
+pub async fn list_operations(
+    State(state): State<AppState>,
+    Path(service): Path<String>,
+) -> JaegerResult<Vec<String>> {
+    let until = Utc::now();
+    let since = until - chrono::Duration::days(LOOKBACK_DAYS);
+    let query = SpanQuery { service: Some(service), ..Default::default() };
+
+    let operations: Vec<String> = state
+        .span_repo
+        .search_facets(&query, since, until)
+        .await
+        .map_err(internal)?
+        .operations
+        .into_iter()
+        .map(|f| f.value)
+        .collect();
+
+    let total = operations.len();
+    Ok(Json(JaegerResponse::new(operations, total)))
+}
+
+/// `GET /api/traces/{trace_id}`
+pub async fn get_trace(
+    State(state): State<AppState>,
+    Path(trace_id): Path<String>,
+) -> JaegerResult<Vec<JaegerTrace>> {
+    let spans = state.span_repo.get_by_trace_id(&trace_id).await.map_err(internal)?;
+    if spans.is_empty() {
+        return Err(jaeger_error(StatusCode::NOT_FOUND, "trace not found"));
+    }
+
+    Ok(Json(JaegerResponse::new(vec![to_jaeger_trace(&trace_id, &spans)], 1)))
+}
+
+/// Trace search parameters, named as in Jaeger's API
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JaegerTraceQuery {
+    /// Service a span must come from
+    pub service: Option<String>,
+    /// Operation name of that span
+    pub operation: Option<String>,
+    /// JSON object of tag filters, e.g. `{"error":"true"}`
+    pub tags: Option<String>,
+    /// Start of the range, in microseconds since the Unix epoch
+    pub start: Option<i64>,
+    /// End of the range, in microseconds since the Unix epoch
+    pub end: Option<i64>,
+    /// Minimum trace duration, e.g. `1.2s` or `100ms`
+    pub min_duration: Option<String>,
+    /// Maximum trace duration
+    pub max_duration: Option<String>,
+    /// Most traces to return (default: 20, at most 1500)
+    pub limit: Option<i64>,
+}
+
+/// Parse a Jaeger duration such as `1.2s`, `100ms` or `500us` into milliseconds
+fn parse_duration_ms(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic() || c == 'µ')?;
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number.parse().ok()?;
+    let factor = match unit {
+        "ns" => 1e-6,
+        "us" | "µs" => 1e-3,
+        "ms" => 1.0,
+        "s" => 1_000.0,
+        "m" => 60_000.0,
+        "h" => 3_600_000.0,
+        _ => return None,
+    };
+    Some(value * factor)
+}
+
+/// The span condition for a Jaeger tag filter
+///
+/// Tags AgentTrace derives from span fields are matched against those
+/// fields; any other tag is matched against the span's attributes.
+fn tag_query(key: &str, value: &str) -> SpanQuery {
+    let mut query = SpanQuery::default();
+    match key {
+        "error" if value == "true" => query.status = Some("error".to_string()),
+        "otel.status_code" => query.status = Some(value.to_lowercase()),
+        "gen_ai.request.model" => query.model = Some(value.to_string()),
+        _ => query.attributes.push((key.to_string(), value.to_string())),
+    }
+    query
+}
+
+/// `GET /api/traces`
+///
+/// Filters run in storage, and the matching traces' spans are then loaded
+/// in one query.
+pub async fn search_traces(
+    State(state): State<AppState>,
+    Query(query): Query<JaegerTraceQuery>,
+) -> JaegerResult<Vec<JaegerTrace>> {
+    let tags: HashMap<String, String> = match query.tags.as_deref().filter(|t| !t.is_empty()) {
+        Some(tags) => serde_json::from_str::<HashMap<String, Value>>(tags)
+            .map_err(|e| jaeger_error(StatusCode::BAD_REQUEST, format!("invalid tags: {}", e)))?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+                (key, value)
+            })
+            .collect(),
+        None => HashMap::new(),
+    };
+
+    let duration = |value: &Option<String>| -> Result<Option<f64>, Rejection> {
+        value
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                parse_duration_ms(v)
+                    .ok_or_else(|| jaeger_error(StatusCode::BAD_REQUEST, format!("invalid duration: {}", v)))
+            })
+            .transpose()
+    };
+
+    let search = TraceSearch {
+        service: query.service.filter(|s| !s.is_empty()),
+        operation: query.operation.filter(|o| !o.is_empty()),
+        spans: tags.iter().map(|(key, value)| tag_query(key, value)).collect(),
+        since: query.start.and_then(DateTime::<Utc>::from_timestamp_micros),
+        until: query.end.and_then(DateTime::<Utc>::from_timestamp_micros),
+        min_duration: duration(&query.min_duration)?,
+        max_duration: duration(&query.max_duration)?,
+        limit: query.limit.filter(|l| *l > 0).unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT),
+    };
+
+    let summaries = state.span_repo.find_traces(&search).await.map_err(internal)?;
+    let trace_ids: Vec<String> = summaries.into_iter().map(|s| s.trace_id).collect();
+
+    let mut spans: HashMap<String, Vec<Span>> = HashMap::new();
+    for span in state.span_repo.get_by_trace_ids(&trace_ids).await.map_err(internal)? {
+        spans.entry(span.trace_id.clone()).or_default().push(span);
+    }
+
+    let traces: Vec<JaegerTrace> = trace_ids
+        .iter()
+        .filter_map(|trace_id| spans.get(trace_id).map(|spans| to_jaeger_trace(trace_id, spans)))
+        .collect();
+
+    let total = traces.len();
+    Ok(Json(JaegerResponse::new(traces, total)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration_ms() {
+        assert_eq!(parse_duration_ms("1.5s"), Some(1_500.0));
+        assert_eq!(parse_duration_ms("100ms"), Some(100.0));
+        assert_eq!(parse_duration_ms("500us"), Some(0.5));
+        assert_eq!(parse_duration_ms("2m"), Some(120_000.0));
+        assert_eq!(parse_duration_ms("fast"), None);
+        assert_eq!(parse_duration_ms("10"), None);
+    }
+
+    #[test]
+    fn test_tag_query() {
+        assert_eq!(tag_query("error", "true").status.as_deref(), Some("error"));
+        assert_eq!(tag_query("otel.status_code", "OK").status.as_deref(), Some("ok"));
+        assert_eq!(tag_query("gen_ai.request.model", "gpt-4o").model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            tag_query("customer.tier", "gold").attributes,
+            vec![("customer.tier".to_string(), "gold".to_string())]
+        );
+    }
+}
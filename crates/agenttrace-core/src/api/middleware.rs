@@ -1 +1,187 @@
 //! API middleware
+
+use axum::body::to_bytes;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, warn, Instrument};
+use uuid::Uuid;
+
+use super::error::{ApiError, ErrorCode};
+use super::handlers::AppState;
+use crate::models::{AuditChange, ANONYMOUS_ACTOR};
+
+/// Header carrying the request ID, in requests and responses
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header naming who made a request, recorded in the audit log
+pub const ACTOR_HEADER: &str = "x-agenttrace-actor";
+
+/// Longest client-supplied request ID that is kept; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Longest actor name that is kept; longer ones are recorded as anonymous
+const MAX_ACTOR_LEN: usize = 128;
+
+/// Largest plain-text error body read back to wrap as an [`ApiError`]
+const MAX_REJECTION_BODY: usize = 16 * 1024;
+
+/// ID of the request being handled, in the request's extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Who made the request being handled, in the request's extensions
+///
+/// Set by the [audit middleware](audit) for handlers that keep their own
+/// history, like alert rule revisions.
+#[derive(Debug, Clone)]
+pub struct Actor(pub String);
+
+/// Assign every request an ID and report it in the response and logs
+///
+/// A well-formed `X-Request-Id` from the client (e.g. a proxy's) is kept,
+/// otherwise a UUID is generated. The ID is echoed in the `X-Request-Id`
+/// response header, recorded on the request's tracing span and added to
+/// [`ApiError`] bodies. Plain-text error responses produced before a handler
+/// runs, such as a malformed JSON body or path parameter, are rewritten as
+/// [`ApiError`]s so every error has the same shape.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map_or_else(|| Uuid::new_v4().to_string(), str::to_string);
+    request.extensions_mut().insert(RequestId(id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    let mut response = next.run(request).instrument(span.clone()).await;
+
+    let api_error = match response.extensions_mut().remove::<ApiError>() {
+        Some(api_error) => Some(api_error),
+        None => wrap_rejection(&mut response).await,
+    };
+    if let Some(mut api_error) = api_error {
+        span.in_scope(|| {
+            if api_error.status.is_server_error() {
+                error!(status = %api_error.status, code = ?api_error.code, "{}", api_error.message);
+            } else {
+                debug!(status = %api_error.status, code = ?api_error.code, "{}", api_error.message);
+            }
+        });
+
+        api_error.request_id = Some(id.clone());
+        response = api_error.to_response();
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Record the changes made by mutating requests in the audit log
+///
+/// Handlers describe what they changed with an [`AuditChange`] in their
+/// response's extensions; this adds the actor from the `X-AgentTrace-Actor`
+/// header and the request ID. Requests that fail change nothing and aren't
+/// recorded. Without PostgreSQL storage there is no audit log and changes
+/// are only logged.
+pub async fn audit(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let actor = request
+        .headers()
+        .get(ACTOR_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && v.len() <= MAX_ACTOR_LEN && !v.chars().any(char::is_control))
+        .unwrap_or(ANONYMOUS_ACTOR)
+        .to_string();
+    let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone());
+    request.extensions_mut().insert(Actor(actor.clone()));
+
+    let mut response = next.run(request).await;
+
+    let Some(change) = response.extensions_mut().remove::<AuditChange>() else {
+        return response;
+    };
+    debug!(
+        actor = %actor,
+        action = change.action.as_str(),
+        resource_type = change.resource_type,
+        resource_id = %change.resource_id,
+        "Audited change"
+    );
+
+    if let Some(repo) = &state.audit {
+        if let Err(e) = repo.record(&actor, &change, request_id.as_deref()).await {
+            warn!(
+                "Failed to record {} of {} {} in the audit log: {}",
+                change.action.as_str(),
+                change.resource_type,
+                change.resource_id,
+                e
+            );
+        }
+    }
+
+    response
+}
+
+/// Only let requests carrying `Authorization: Bearer <server.admin_token>`
+/// through
+///
+/// Guards endpoints that act on caller-supplied targets, such as posting a
+/// test notification to a URL. Without a configured token they are
+/// unavailable.
+pub async fn require_admin(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(expected) = state.config.server.admin_token.as_deref() else {
+        return ApiError::unavailable("Admin endpoints are disabled; set server.admin_token to enable them")
+            .into_response();
+    };
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    // Compare digests so the comparison time doesn't reveal the token
+    let authorized = provided.is_some_and(|token| Sha256::digest(token) == Sha256::digest(expected));
+    if !authorized {
+        return ApiError::unauthorized("Missing or wrong admin token").into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Read a plain-text error response back as an [`ApiError`], keeping its
+/// status
+///
+/// Other responses, including the Jaeger and GraphQL endpoints' own JSON
+/// errors, are left alone.
+async fn wrap_rejection(response: &mut Response) -> Option<ApiError> {
+    let status = response.status();
+    let plain_text = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/plain"));
+    if !(status.is_client_error() || status.is_server_error()) || !plain_text {
+        return None;
+    }
+
+    let body = std::mem::take(response.body_mut());
+    let message = to_bytes(body, MAX_REJECTION_BODY)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+
+    let mut api_error = ApiError::new(ErrorCode::from_status(status), message);
+    api_error.status = status;
+    Some(api_error)
+}
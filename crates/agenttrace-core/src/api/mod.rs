@@ -2,22 +2,39 @@
 //!
 //! This module provides the HTTP API for AgentTrace.
 
+pub mod error;
+pub mod etag;
+pub mod grafana;
+pub mod graphql;
 pub mod handlers;
+pub mod jaeger;
 pub mod middleware;
 pub mod routes;
+pub mod websocket;
 
+pub use error::{ApiError, ErrorCode};
 pub use handlers::AppState;
 pub use routes::create_router;
 
 use std::sync::Arc;
+
+use axum::http::{header, HeaderName};
 use tokio::net::TcpListener;
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 
 use crate::alerting::{AlertEvaluator, AlertRepository};
+use crate::annotations::AnnotationRepository;
+use crate::audit::AuditRepository;
+use crate::budgets::BudgetRepository;
 use crate::collector::Pipeline;
-use crate::db::{RedisPool, SpanRepository};
+use crate::config::Config;
+use crate::db::{PubSub, SpanStore};
 use crate::error::Result;
+use crate::jobs::QueryJobs;
+use crate::slos::SloRepository;
+use crate::views::ViewRepository;
 
 /// HTTP API server
 pub struct HttpServer {
@@ -26,20 +43,35 @@ pub struct HttpServer {
 
 impl HttpServer {
     /// Create a new HTTP server
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         pipeline: Arc<Pipeline>,
-        span_repo: SpanRepository,
-        redis: Option<RedisPool>,
+        span_repo: Arc<dyn SpanStore>,
+        pubsub: Option<Arc<dyn PubSub>>,
         alert_repo: Option<AlertRepository>,
         alert_evaluator: Option<Arc<AlertEvaluator>>,
+        annotations: Option<AnnotationRepository>,
+        jobs: Arc<QueryJobs>,
+        views: Option<ViewRepository>,
+        audit: Option<AuditRepository>,
+        budgets: Option<BudgetRepository>,
+        slos: Option<SloRepository>,
+        config: Arc<Config>,
     ) -> Self {
         Self {
             state: AppState {
                 pipeline,
                 span_repo,
-                redis,
+                pubsub,
                 alert_repo,
                 alert_evaluator,
+                annotations,
+                jobs,
+                views,
+                audit,
+                budgets,
+                slos,
+                config,
             },
         }
     }
@@ -49,9 +81,12 @@ impl HttpServer {
         let cors = CorsLayer::new()
             .allow_origin(Any)
             .allow_methods(Any)
-            .allow_headers(Any);
+            .allow_headers(Any)
+            .expose_headers([HeaderName::from_static(middleware::REQUEST_ID_HEADER), header::ETAG]);
 
-        let app = create_router(self.state).layer(cors);
+        // gzip or brotli, as the client accepts; trace details for long agent
+        // runs are several megabytes of repetitive JSON
+        let app = create_router(self.state).layer(CompressionLayer::new()).layer(cors);
 
         let listener = TcpListener::bind(addr)
             .await
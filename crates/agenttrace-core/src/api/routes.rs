@@ -1,40 +1,116 @@
 //! API routes
 
 use axum::{
-    routing::{delete, get, post, put},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
+use async_graphql_axum::GraphQL;
+
+use super::grafana;
+use super::graphql;
 use super::handlers::{self, AppState};
+use super::jaeger;
+use super::middleware;
+use super::websocket;
 
 /// Create the API router
+///
+/// Every response carries an `X-Request-Id`; see [`middleware::request_id`].
+/// Changes made through the API are recorded by [`middleware::audit`].
 pub fn create_router(state: AppState) -> Router {
     Router::new()
-        // Health
+        // Health and Prometheus metrics
         .route("/health", get(handlers::health))
+        .route("/metrics", get(handlers::prometheus_metrics))
 
         // Span ingestion
         .route("/api/v1/spans", post(handlers::ingest_span))
         .route("/api/v1/spans/batch", post(handlers::ingest_batch))
 
+        // LangSmith-compatible run ingestion, for LangChain's tracer
+        .route("/api/v1/langsmith/runs", post(handlers::ingest_langsmith_runs))
+        .route("/api/v1/langsmith/runs/batch", post(handlers::ingest_langsmith_runs))
+        .route("/api/v1/langsmith/runs/:run_id", patch(handlers::update_langsmith_run))
+
+        // OTLP/HTTP trace ingestion, for OpenTelemetry exporters
+        .route("/v1/traces", post(handlers::ingest_otlp_traces))
+
         // Span queries
         .route("/api/v1/spans", get(handlers::list_spans))
         .route("/api/v1/spans/:span_id", get(handlers::get_span))
+        .route("/api/v1/spans/:span_id/payload", get(handlers::get_span_payload))
 
         // Search
         .route("/api/v1/search", get(handlers::search_spans))
         .route("/api/v1/search/advanced", post(handlers::advanced_search))
+        .route("/api/v1/search/text", get(handlers::text_search))
+        .route("/api/v1/search/facets", get(handlers::search_facets))
 
         // Traces
         .route("/api/v1/traces", get(handlers::list_traces))
         .route("/api/v1/traces/:trace_id", get(handlers::get_trace))
         .route("/api/v1/traces/:trace_id/spans", get(handlers::get_trace_spans))
+        .route("/api/v1/traces/:trace_id/compare/:other_id", get(handlers::compare_traces))
+        .route("/api/v1/traces/:trace_id/export", get(handlers::export_trace))
+        .route("/api/v1/traces/:trace_id/annotations", get(handlers::list_trace_annotations))
+        .route("/api/v1/traces/:trace_id/annotations", post(handlers::create_trace_annotation))
+        .route(
+            "/api/v1/traces/:trace_id/annotations/:annotation_id",
+            put(handlers::update_trace_annotation),
+        )
+        .route(
+            "/api/v1/traces/:trace_id/annotations/:annotation_id",
+            delete(handlers::delete_trace_annotation),
+        )
+
+        // Sessions
+        .route("/api/v1/sessions", get(handlers::list_sessions))
+        .route("/api/v1/sessions/:session_id", get(handlers::get_session))
+
+        // End-user feedback
+        .route("/api/v1/feedback", post(handlers::create_feedback))
+        .route("/api/v1/traces/:trace_id/feedback", get(handlers::list_trace_feedback))
+
+        // Saved views
+        .route("/api/v1/views", get(handlers::list_views))
+        .route("/api/v1/views", post(handlers::create_view))
+        .route("/api/v1/views/:view", get(handlers::get_view))
+        .route("/api/v1/views/:view", put(handlers::update_view))
+        .route("/api/v1/views/:view", delete(handlers::delete_view))
+
+        // Services
+        .route("/api/v1/services", get(handlers::list_services))
 
         // Metrics
         .route("/api/v1/metrics/summary", get(handlers::get_metrics_summary))
         .route("/api/v1/metrics/costs", get(handlers::get_cost_metrics))
         .route("/api/v1/metrics/latency", get(handlers::get_latency_metrics))
         .route("/api/v1/metrics/errors", get(handlers::get_error_metrics))
+        .route("/api/v1/metrics/time-breakdown", get(handlers::get_time_breakdown))
+        .route("/api/v1/metrics/efficiency", get(handlers::get_efficiency_metrics))
+        .route("/api/v1/metrics/tokens", get(handlers::get_token_efficiency))
+        .route("/api/v1/metrics/feedback", get(handlers::get_feedback_metrics))
+        .route("/api/v1/query", post(handlers::run_aggregate_query))
+
+        // Reports
+        .route("/api/v1/reports/generate", get(handlers::generate_report))
+
+        // Jobs
+        .route("/api/v1/jobs/query", post(handlers::submit_query_job))
+        .route("/api/v1/jobs/:job_id", get(handlers::get_query_job))
+        .route("/api/v1/jobs/:job_id", delete(handlers::cancel_query_job))
+        .route("/api/v1/exports", post(handlers::submit_export))
+        .route("/api/v1/exports/:job_id", get(handlers::get_query_job))
+        .route("/api/v1/exports/:job_id", delete(handlers::cancel_query_job))
+        .route("/api/v1/exports/:job_id/download", get(handlers::download_export))
+
+        // Annotations
+        .route("/api/v1/annotations", get(handlers::list_annotations))
+
+        // System
+        .route("/api/v1/system/storage", get(handlers::get_storage_report))
 
         // Alerts
         .route("/api/v1/alerts/rules", get(handlers::list_alert_rules))
@@ -43,12 +119,207 @@ pub fn create_router(state: AppState) -> Router {
         .route("/api/v1/alerts/rules/:rule_id", put(handlers::update_alert_rule))
         .route("/api/v1/alerts/rules/:rule_id", delete(handlers::delete_alert_rule))
         .route("/api/v1/alerts/rules/:rule_id/test", post(handlers::test_alert_rule))
+        .route("/api/v1/alerts/rules/:rule_id/lint", get(handlers::lint_alert_rule))
+        .route("/api/v1/alerts/rules/:rule_id/history", get(handlers::get_alert_rule_history))
+        .route("/api/v1/alerts/rules/:rule_id/revert", post(handlers::revert_alert_rule))
         .route("/api/v1/alerts/events", get(handlers::list_alert_events))
         .route("/api/v1/alerts/events/:event_id", get(handlers::get_alert_event))
         .route("/api/v1/alerts/events/:event_id/acknowledge", post(handlers::acknowledge_alert))
+        .route("/api/v1/alerts/incidents", get(handlers::list_alert_incidents))
+        .route("/api/v1/alerts/incidents/:incident_id", get(handlers::get_alert_incident))
+        .route(
+            "/api/v1/alerts/incidents/:incident_id/acknowledge",
+            post(handlers::acknowledge_alert_incident),
+        )
+
+        // Attribute schemas
+        .route("/api/v1/schemas", get(handlers::list_schemas))
+        .route("/api/v1/schemas/:service", get(handlers::get_schema))
+        .route("/api/v1/schemas/:service", put(handlers::put_schema))
+        .route("/api/v1/schemas/:service", delete(handlers::delete_schema))
+        .route("/api/v1/schemas/:service/compliance", get(handlers::get_schema_compliance))
+
+        // Webhook subscriptions
+        .route("/api/v1/subscriptions", get(handlers::list_subscriptions))
+        .route("/api/v1/subscriptions", post(handlers::create_subscription))
+        .route("/api/v1/subscriptions/:subscription_id", get(handlers::get_subscription))
+        .route("/api/v1/subscriptions/:subscription_id", put(handlers::update_subscription))
+        .route("/api/v1/subscriptions/:subscription_id", delete(handlers::delete_subscription))
+        .route(
+            "/api/v1/subscriptions/:subscription_id/deliveries",
+            get(handlers::list_subscription_deliveries),
+        )
+
+        // Spend budgets
+        .route("/api/v1/budgets", get(handlers::list_budgets))
+        .route("/api/v1/budgets", post(handlers::create_budget))
+        .route("/api/v1/budgets/:budget_id", get(handlers::get_budget))
+        .route("/api/v1/budgets/:budget_id", put(handlers::update_budget))
+        .route("/api/v1/budgets/:budget_id", delete(handlers::delete_budget))
+
+        // Service level objectives
+        .route("/api/v1/slos", get(handlers::list_slos))
+        .route("/api/v1/slos", post(handlers::create_slo))
+        .route("/api/v1/slos/:slo_id", get(handlers::get_slo))
+        .route("/api/v1/slos/:slo_id", put(handlers::update_slo))
+        .route("/api/v1/slos/:slo_id", delete(handlers::delete_slo))
+        .route("/api/v1/slos/:slo_id/burn-rate", get(handlers::get_slo_burn_rates))
+
+        // Admin
+        .merge(admin_routes(&state))
+
+        // Audit log
+        .route("/api/v1/audit", get(handlers::list_audit_entries))
 
         // Real-time streaming
         .route("/api/v1/stream", get(handlers::stream_spans))
+        .route("/api/v1/stream/ws", get(websocket::stream_ws))
+
+        // Jaeger-compatible query API, for Grafana's Jaeger datasource
+        .route("/api/services", get(jaeger::list_services))
+        .route("/api/services/:service/operations", get(jaeger::list_operations))
+        .route("/api/traces", get(jaeger::search_traces))
+        .route("/api/traces/:trace_id", get(jaeger::get_trace))
+
+        // Grafana JSON datasource API, for dashboards over aggregation queries
+        .route("/grafana", get(grafana::test_connection))
+        .route("/grafana/search", post(grafana::search))
+        .route("/grafana/query", post(grafana::query))
+        .route("/grafana/annotations", post(grafana::annotations))
+        .route("/grafana/tag-keys", post(grafana::tag_keys))
+        .route("/grafana/tag-values", post(grafana::tag_values))
+
+        // GraphQL queries over traces, spans, metrics and alerts
+        .route_service("/api/graphql", GraphQL::new(graphql::build_schema(state.clone())))
 
+        .layer(from_fn_with_state(state.clone(), middleware::audit))
+        .layer(from_fn(middleware::request_id))
         .with_state(state)
 }
+
+/// Routes that need `Authorization: Bearer <server.admin_token>`; see
+/// [`middleware::require_admin`]
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/v1/deletions", post(handlers::delete_traces))
+        .route("/api/v1/alerts/channels/test", post(handlers::test_notification_channel))
+        .route("/api/v1/admin/pipeline", get(handlers::get_pipeline_status))
+        .route("/api/v1/admin/config", get(handlers::get_admin_config))
+        .route("/api/v1/admin/pricing", get(handlers::list_pricing))
+        .route("/api/v1/admin/pricing/reload", post(handlers::reload_pricing))
+        // Debug sessions capture full span payloads
+        .route("/api/v1/debug/sessions", get(handlers::list_debug_sessions))
+        .route("/api/v1/debug/sessions", post(handlers::start_debug_session))
+        .route("/api/v1/debug/sessions/:session_id", delete(handlers::stop_debug_session))
+        .route_layer(from_fn_with_state(state.clone(), middleware::require_admin))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::{header, Method, Request, StatusCode};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::api::handlers::tests::test_state;
+    use crate::config::Config;
+    use crate::db::sqlite::tests::create_test_span;
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    async fn state(admin_token: Option<&str>) -> AppState {
+        let mut config = Config::default();
+        config.server.admin_token = admin_token.map(str::to_string);
+        AppState {
+            config: Arc::new(config),
+            ..test_state().await
+        }
+    }
+
+    async fn router(admin_token: Option<&str>) -> Router {
+        create_router(state(admin_token).await)
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> StatusCode {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        router.clone().oneshot(request.unwrap()).await.unwrap().status()
+    }
+
+    async fn status(router: &Router, method: Method, uri: &str, token: Option<&str>) -> StatusCode {
+        send(router, method, uri, token, None).await
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_the_token() {
+        let router = router(Some(TOKEN)).await;
+        for (method, uri) in [
+            (Method::GET, "/api/v1/admin/pipeline"),
+            (Method::GET, "/api/v1/admin/config"),
+            (Method::GET, "/api/v1/admin/pricing"),
+            (Method::POST, "/api/v1/admin/pricing/reload"),
+            (Method::GET, "/api/v1/debug/sessions"),
+        ] {
+            assert_eq!(status(&router, method.clone(), uri, None).await, StatusCode::UNAUTHORIZED, "{}", uri);
+            assert_eq!(
+                status(&router, method.clone(), uri, Some("wrong-token-wrong")).await,
+                StatusCode::UNAUTHORIZED,
+                "{}",
+                uri
+            );
+            assert_eq!(status(&router, method, uri, Some(TOKEN)).await, StatusCode::OK, "{}", uri);
+        }
+
+        let stop = format!("/api/v1/debug/sessions/{}", uuid::Uuid::new_v4());
+        assert_eq!(status(&router, Method::DELETE, &stop, None).await, StatusCode::UNAUTHORIZED);
+
+        // Other routes don't need it
+        assert_eq!(status(&router, Method::GET, "/health", None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_deletions_need_the_token() {
+        let state = state(Some(TOKEN)).await;
+        let store = state.span_repo.clone();
+        let router = create_router(state);
+        store.insert_batch(&[create_test_span("t1", "a", None, 10.0)]).await.unwrap();
+        let deletion = serde_json::json!({ "trace_ids": ["t1"] });
+
+        let rejected = send(&router, Method::POST, "/api/v1/deletions", None, Some(deletion.clone())).await;
+        assert_eq!(rejected, StatusCode::UNAUTHORIZED);
+        assert_eq!(store.get_by_trace_id("t1").await.unwrap().len(), 1);
+
+        let accepted = send(&router, Method::POST, "/api/v1/deletions", Some(TOKEN), Some(deletion)).await;
+        assert_eq!(accepted, StatusCode::OK);
+        assert!(store.get_by_trace_id("t1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_are_disabled_without_a_token() {
+        let router = router(None).await;
+        assert_eq!(
+            status(&router, Method::GET, "/api/v1/admin/config", Some(TOKEN)).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            status(&router, Method::POST, "/api/v1/admin/pricing/reload", None).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
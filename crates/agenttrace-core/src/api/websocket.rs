@@ -0,0 +1,402 @@
+//! WebSocket span streaming
+//!
+//! `/api/v1/stream/ws` carries the same pub/sub channels as the SSE stream,
+//! but a client can subscribe, unsubscribe and change filters over a single
+//! connection. Messages are JSON objects tagged by `type`:
+//!
+//! ```text
+//! → {"type": "subscribe", "channel": "spans", "filter": "service:planner status:error"}
+//! ← {"type": "subscribed", "channel": "spans"}
+//! ← {"type": "span", "channel": "spans", "span": {...}}
+//! → {"type": "filter", "channel": "spans", "filter": "min_duration:500"}
+//! ← {"type": "filter_updated", "channel": "spans"}
+//! → {"type": "unsubscribe", "channel": "spans"}
+//! ← {"type": "unsubscribed", "channel": "spans"}
+//! ```
+//!
+//! Channels are `spans`, `llm`, `trace:{id}` and `debug:{session_id}`.
+//! Filters use the compact search syntax ([`SpanQuery`]) and are evaluated
+//! per connection, so each client only receives the spans it asked for.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::response::Response;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::db::PubSub;
+use crate::error::{Error, Result};
+use crate::models::{Span, SpanQuery};
+
+use super::error::ApiError;
+use super::handlers::AppState;
+
+/// Channels a single connection may subscribe to at once
+const MAX_SUBSCRIPTIONS: usize = 32;
+
+/// Messages buffered per connection before spans for a slow client are dropped
+const OUTBOX_CAPACITY: usize = 256;
+
+/// Message sent by the client
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Start receiving a channel, or replace its filter if already subscribed
+    Subscribe {
+        channel: String,
+        #[serde(default)]
+        filter: Option<String>,
+    },
+    /// Stop receiving a channel
+    Unsubscribe { channel: String },
+    /// Change the filter of a subscribed channel; an empty filter clears it
+    Filter {
+        channel: String,
+        #[serde(default)]
+        filter: Option<String>,
+    },
+    /// Application-level liveness check
+    Ping,
+}
+
+/// Message sent by the server
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed { channel: String },
+    Unsubscribed { channel: String },
+    FilterUpdated { channel: String },
+    Span { channel: String, span: Box<Span> },
+    Error { message: String },
+    Pong,
+}
+
+/// Pub/sub channel behind a stream channel name
+pub fn stream_channel(name: &str) -> Result<String> {
+    match name {
+        "spans" => return Ok("agenttrace:spans".to_string()),
+        "llm" => return Ok("agenttrace:llm".to_string()),
+        _ => {}
+    }
+
+    if let Some(trace_id) = name.strip_prefix("trace:").filter(|id| !id.is_empty()) {
+        return Ok(format!("agenttrace:trace:{}", trace_id));
+    }
+
+    if let Some(session_id) = name.strip_prefix("debug:") {
+        Uuid::parse_str(session_id).map_err(|_| Error::validation("Invalid debug session id"))?;
+        return Ok(format!("agenttrace:{}", name));
+    }
+
+    Err(Error::validation(format!("Unknown channel: {}", name)))
+}
+
+/// Parse an optional filter, treating a blank one as no filter
+fn parse_filter(filter: Option<&str>) -> Result<Option<SpanQuery>> {
+    match filter.map(str::trim).filter(|f| !f.is_empty()) {
+        Some(filter) => {
+            let query = SpanQuery::parse(filter)?;
+            query.check_live()?;
+            Ok(Some(query))
+        }
+        None => Ok(None),
+    }
+}
+
+/// WebSocket endpoint for real-time span updates
+pub async fn stream_ws(
+    State(state): State<AppState>,
+    ws: WebSocketUpgrade,
+) -> std::result::Result<Response, ApiError> {
+    let pubsub = state
+        .pubsub
+        .ok_or(ApiError::unavailable("Streaming not configured".to_string()))?;
+
+    Ok(ws.on_upgrade(move |socket| serve_connection(socket, pubsub)))
+}
+
+struct Subscription {
+    filter: watch::Sender<Option<SpanQuery>>,
+    task: JoinHandle<()>,
+}
+
+/// Subscriptions held by one WebSocket connection
+struct Connection {
+    pubsub: Arc<dyn PubSub>,
+    outbox: mpsc::Sender<ServerMessage>,
+    subscriptions: HashMap<String, Subscription>,
+}
+
+impl Connection {
+    /// Apply a client message and build the reply
+    async fn handle(&mut self, text: &str) -> ServerMessage {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return ServerMessage::Error { message: format!("Invalid message: {}", e) },
+        };
+
+        let reply = match message {
+            ClientMessage::Subscribe { channel, filter } => {
+                self.subscribe(channel, filter.as_deref()).await
+            }
+            ClientMessage::Unsubscribe { channel } => self.unsubscribe(channel),
+            ClientMessage::Filter { channel, filter } => self.set_filter(channel, filter.as_deref()),
+            ClientMessage::Ping => Ok(ServerMessage::Pong),
+        };
+
+        reply.unwrap_or_else(|e| ServerMessage::Error { message: e.to_string() })
+    }
+
+    async fn subscribe(&mut self, channel: String, filter: Option<&str>) -> Result<ServerMessage> {
+        let filter = parse_filter(filter)?;
+
+        if let Some(existing) = self.subscriptions.get(&channel) {
+            existing.filter.send_replace(filter);
+            return Ok(ServerMessage::Subscribed { channel });
+        }
+
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+            return Err(Error::validation(format!(
+                "At most {} subscriptions per connection",
+                MAX_SUBSCRIPTIONS
+            )));
+        }
+
+        let rx = self.pubsub.subscribe(&stream_channel(&channel)?).await?;
+        let (filter_tx, filter_rx) = watch::channel(filter);
+        let task = tokio::spawn(forward(channel.clone(), rx, filter_rx, self.outbox.clone()));
+
+        self.subscriptions.insert(channel.clone(), Subscription { filter: filter_tx, task });
+        Ok(ServerMessage::Subscribed { channel })
+    }
+
+    fn unsubscribe(&mut self, channel: String) -> Result<ServerMessage> {
+        let subscription = self
+            .subscriptions
+            .remove(&channel)
+            .ok_or_else(|| Error::validation(format!("Not subscribed to {}", channel)))?;
+        subscription.task.abort();
+
+        Ok(ServerMessage::Unsubscribed { channel })
+    }
+
+    fn set_filter(&mut self, channel: String, filter: Option<&str>) -> Result<ServerMessage> {
+        let subscription = self
+            .subscriptions
+            .get(&channel)
+            .ok_or_else(|| Error::validation(format!("Not subscribed to {}", channel)))?;
+        subscription.filter.send_replace(parse_filter(filter)?);
+
+        Ok(ServerMessage::FilterUpdated { channel })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for subscription in self.subscriptions.values() {
+            subscription.task.abort();
+        }
+    }
+}
+
+/// Relay matching spans from a pub/sub channel to the connection
+async fn forward(
+    channel: String,
+    mut rx: mpsc::Receiver<String>,
+    filter: watch::Receiver<Option<SpanQuery>>,
+    outbox: mpsc::Sender<ServerMessage>,
+) {
+    while let Some(payload) = rx.recv().await {
+        let Ok(span) = serde_json::from_str::<Span>(&payload) else {
+            continue;
+        };
+
+        let wanted = match &*filter.borrow() {
+            Some(query) => query.matches(&span),
+            None => true,
+        };
+        if !wanted {
+            continue;
+        }
+
+        // Drop spans for a client that can't keep up rather than stall the subscription
+        let message = ServerMessage::Span { channel: channel.clone(), span: Box::new(span) };
+        if let Err(mpsc::error::TrySendError::Closed(_)) = outbox.try_send(message) {
+            break;
+        }
+    }
+}
+
+async fn serve_connection(socket: WebSocket, pubsub: Arc<dyn PubSub>) {
+    let (mut sink, mut stream) = socket.split();
+    let (outbox, mut outgoing) = mpsc::channel::<ServerMessage>(OUTBOX_CAPACITY);
+
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            let Ok(text) = serde_json::to_string(&message) else {
+                continue;
+            };
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut connection = Connection {
+        pubsub,
+        outbox: outbox.clone(),
+        subscriptions: HashMap::new(),
+    };
+
+    while let Some(Ok(message)) = stream.next().await {
+        let reply = match message {
+            Message::Text(text) => connection.handle(&text).await,
+            Message::Close(_) => break,
+            // Protocol pings are answered by the WebSocket layer
+            _ => continue,
+        };
+
+        if outbox.send(reply).await.is_err() {
+            break;
+        }
+    }
+
+    drop(connection);
+    writer.abort();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::db::sqlite::tests::create_test_span;
+    use crate::db::LocalPubSub;
+
+    fn connection(pubsub: &LocalPubSub) -> (Connection, mpsc::Receiver<ServerMessage>) {
+        let (outbox, outgoing) = mpsc::channel(OUTBOX_CAPACITY);
+        let connection = Connection {
+            pubsub: Arc::new(pubsub.clone()),
+            outbox,
+            subscriptions: HashMap::new(),
+        };
+        (connection, outgoing)
+    }
+
+    async fn publish(pubsub: &LocalPubSub, span_id: &str, duration_ms: f64) {
+        let span = create_test_span("trace-1", span_id, None, duration_ms);
+        pubsub
+            .publish("agenttrace:spans", &serde_json::to_string(&span).unwrap())
+            .await
+            .unwrap();
+    }
+
+    async fn next_span_id(outgoing: &mut mpsc::Receiver<ServerMessage>) -> String {
+        match tokio::time::timeout(Duration::from_secs(1), outgoing.recv()).await {
+            Ok(Some(ServerMessage::Span { channel, span })) => {
+                assert_eq!(channel, "spans");
+                span.span_id
+            }
+            other => panic!("expected a span, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stream_channels() {
+        assert_eq!(stream_channel("spans").unwrap(), "agenttrace:spans");
+        assert_eq!(stream_channel("llm").unwrap(), "agenttrace:llm");
+        assert_eq!(stream_channel("trace:abc").unwrap(), "agenttrace:trace:abc");
+
+        let session = Uuid::new_v4();
+        assert_eq!(
+            stream_channel(&format!("debug:{}", session)).unwrap(),
+            format!("agenttrace:debug:{}", session)
+        );
+
+        assert!(stream_channel("trace:").is_err());
+        assert!(stream_channel("debug:not-a-uuid").is_err());
+        assert!(stream_channel("agenttrace:spans").is_err());
+    }
+
+    #[test]
+    fn test_blank_filters_are_cleared() {
+        assert!(parse_filter(None).unwrap().is_none());
+        assert!(parse_filter(Some("  ")).unwrap().is_none());
+        assert!(parse_filter(Some("min_duration:500")).unwrap().is_some());
+        // Feedback filters need the store
+        assert!(parse_filter(Some("has_negative_feedback:true")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_filter_per_connection() {
+        let pubsub = LocalPubSub::new();
+        let (mut connection, mut outgoing) = connection(&pubsub);
+
+        let reply = connection
+            .handle(r#"{"type": "subscribe", "channel": "spans", "filter": "min_duration:500"}"#)
+            .await;
+        assert!(matches!(reply, ServerMessage::Subscribed { ref channel } if channel == "spans"));
+
+        publish(&pubsub, "fast", 10.0).await;
+        publish(&pubsub, "slow", 900.0).await;
+        assert_eq!(next_span_id(&mut outgoing).await, "slow");
+
+        // Clearing the filter lets every span through without resubscribing
+        let reply = connection.handle(r#"{"type": "filter", "channel": "spans", "filter": ""}"#).await;
+        assert!(matches!(reply, ServerMessage::FilterUpdated { .. }));
+        publish(&pubsub, "fast", 10.0).await;
+        assert_eq!(next_span_id(&mut outgoing).await, "fast");
+
+        let reply = connection.handle(r#"{"type": "unsubscribe", "channel": "spans"}"#).await;
+        assert!(matches!(reply, ServerMessage::Unsubscribed { .. }));
+        assert!(connection.subscriptions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bad_messages_get_errors() {
+        let pubsub = LocalPubSub::new();
+        let (mut connection, _outgoing) = connection(&pubsub);
+
+        for message in [
+            "not json",
+            r#"{"type": "subscribe"}"#,
+            r#"{"type": "subscribe", "channel": "nope"}"#,
+            r#"{"type": "subscribe", "channel": "spans", "filter": "min_duration:abc"}"#,
+            r#"{"type": "unsubscribe", "channel": "spans"}"#,
+            r#"{"type": "filter", "channel": "spans", "filter": "service:planner"}"#,
+        ] {
+            let reply = connection.handle(message).await;
+            assert!(matches!(reply, ServerMessage::Error { .. }), "{} should fail", message);
+        }
+        assert!(connection.subscriptions.is_empty());
+
+        assert!(matches!(connection.handle(r#"{"type": "ping"}"#).await, ServerMessage::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_limit() {
+        let pubsub = LocalPubSub::new();
+        let (mut connection, _outgoing) = connection(&pubsub);
+
+        for i in 0..MAX_SUBSCRIPTIONS {
+            let reply = connection
+                .handle(&format!(r#"{{"type": "subscribe", "channel": "trace:{}"}}"#, i))
+                .await;
+            assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+        }
+        let reply = connection.handle(r#"{"type": "subscribe", "channel": "spans"}"#).await;
+        assert!(matches!(reply, ServerMessage::Error { .. }));
+
+        // Resubscribing replaces the filter rather than counting again
+        let reply = connection
+            .handle(r#"{"type": "subscribe", "channel": "trace:0", "filter": "status:error"}"#)
+            .await;
+        assert!(matches!(reply, ServerMessage::Subscribed { .. }));
+    }
+}
@@ -0,0 +1,184 @@
+//! Parquet encoding for archived spans
+//!
+//! Commonly queried fields are stored as typed columns so archives can be
+//! scanned directly by tools such as DuckDB or Athena. The full span is also
+//! kept as JSON in the `span` column, which is what restores read back.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::error::{Error, Result};
+use crate::models::Span;
+
+/// Column holding the full span as JSON
+const SPAN_COLUMN: &str = "span";
+
+/// Arrow schema of an archive file
+fn schema() -> Schema {
+    Schema::new(vec![
+        Field::new("span_id", DataType::Utf8, false),
+        Field::new("trace_id", DataType::Utf8, false),
+        Field::new("parent_span_id", DataType::Utf8, true),
+        Field::new("operation_name", DataType::Utf8, false),
+        Field::new("service_name", DataType::Utf8, false),
+        Field::new("status", DataType::Utf8, false),
+        Field::new("priority", DataType::Utf8, false),
+        Field::new(
+            "started_at",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("duration_ms", DataType::Float64, true),
+        Field::new("model_name", DataType::Utf8, true),
+        Field::new("tokens_in", DataType::Int32, true),
+        Field::new("tokens_out", DataType::Int32, true),
+        Field::new("cost_usd", DataType::Float64, true),
+        Field::new(SPAN_COLUMN, DataType::Utf8, false),
+    ])
+}
+
+/// Encode spans as a zstd-compressed Parquet file
+pub fn encode(spans: &[Span]) -> Result<Vec<u8>> {
+    let span_json = spans
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(spans.iter().map(|s| s.span_id.as_str()))),
+        Arc::new(StringArray::from_iter_values(spans.iter().map(|s| s.trace_id.as_str()))),
+        Arc::new(StringArray::from(
+            spans.iter().map(|s| s.parent_span_id.as_deref()).collect::<Vec<_>>(),
+        )),
+        Arc::new(StringArray::from_iter_values(spans.iter().map(|s| s.operation_name.as_str()))),
+        Arc::new(StringArray::from_iter_values(spans.iter().map(|s| s.service_name.as_str()))),
+        Arc::new(StringArray::from_iter_values(
+            spans.iter().map(|s| s.status.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(spans.iter().map(|s| s.priority.as_str()))),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(spans.iter().map(|s| s.started_at.timestamp_micros()))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(Float64Array::from(spans.iter().map(|s| s.duration_ms).collect::<Vec<_>>())),
+        Arc::new(StringArray::from(
+            spans.iter().map(|s| s.model_name.as_deref()).collect::<Vec<_>>(),
+        )),
+        Arc::new(Int32Array::from(spans.iter().map(|s| s.tokens_in).collect::<Vec<_>>())),
+        Arc::new(Int32Array::from(spans.iter().map(|s| s.tokens_out).collect::<Vec<_>>())),
+        Arc::new(Float64Array::from(spans.iter().map(|s| s.cost_usd).collect::<Vec<_>>())),
+        Arc::new(StringArray::from_iter_values(span_json)),
+    ];
+
+    let batch = RecordBatch::try_new(Arc::new(schema()), columns)
+        .map_err(|e| Error::Archive(e.to_string()))?;
+
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+
+    let mut buf = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buf, batch.schema(), Some(props))
+        .map_err(|e| Error::Archive(e.to_string()))?;
+    writer.write(&batch).map_err(|e| Error::Archive(e.to_string()))?;
+    writer.close().map_err(|e| Error::Archive(e.to_string()))?;
+
+    Ok(buf)
+}
+
+/// Decode the spans in a Parquet archive file
+pub fn decode(data: Bytes) -> Result<Vec<Span>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(data)
+        .and_then(ParquetRecordBatchReaderBuilder::build)
+        .map_err(|e| Error::Archive(e.to_string()))?;
+
+    let mut spans = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| Error::Archive(e.to_string()))?;
+        let column = batch
+            .column_by_name(SPAN_COLUMN)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .ok_or_else(|| Error::Archive(format!("Archive file has no '{}' column", SPAN_COLUMN)))?;
+
+        for i in 0..column.len() {
+            spans.push(serde_json::from_str(column.value(i))?);
+        }
+    }
+
+    Ok(spans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SpanKind, SpanPriority, SpanStatus};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    fn create_test_span(span_id: &str, parent: Option<&str>) -> Span {
+        let started_at = Utc::now() - Duration::days(30);
+        Span {
+            id: Uuid::new_v4(),
+            span_id: span_id.to_string(),
+            trace_id: "t1".to_string(),
+            parent_span_id: parent.map(str::to_string),
+            session_id: None,
+            user_id: None,
+            operation_name: format!("op-{}", span_id),
+            service_name: "agent".to_string(),
+            span_kind: SpanKind::Internal,
+            started_at,
+            ended_at: Some(started_at + Duration::milliseconds(250)),
+            duration_ms: Some(250.0),
+            status: SpanStatus::Ok,
+            status_message: None,
+            model_name: None,
+            model_provider: None,
+            tokens_in: None,
+            tokens_cached_in: None,
+            tokens_out: None,
+            tokens_reasoning: None,
+            cost_usd: None,
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: None,
+            completion_preview: None,
+            attributes: serde_json::json!({"user": "u-1"}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::Normal,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut llm = create_test_span("a", None);
+        llm.model_name = Some("gpt-4o".to_string());
+        llm.tokens_in = Some(120);
+        llm.cost_usd = Some(0.004);
+        llm.priority = SpanPriority::High;
+        let tool = create_test_span("b", Some("a"));
+
+        let data = encode(&[llm, tool.clone()]).unwrap();
+        let spans = decode(Bytes::from(data)).unwrap();
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].span_id, "a");
+        assert_eq!(spans[0].model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(spans[0].priority, SpanPriority::High);
+        assert_eq!(spans[1].parent_span_id.as_deref(), Some("a"));
+        assert_eq!(spans[1].started_at, tool.started_at);
+        assert_eq!(spans[1].attributes, tool.attributes);
+    }
+}
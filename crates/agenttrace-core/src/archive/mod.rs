@@ -0,0 +1,192 @@
+//! Cold-storage archival of spans
+//!
+//! Spans older than `[archive] after_days` are exported hour by hour to
+//! Parquet files on S3, GCS or the local filesystem, partitioned as
+//! `date=YYYY-MM-DD/hour=HH/spans.parquet` under the archive URL. Each file is
+//! recorded in the `span_archives` manifest so a time range can later be
+//! restored into the spans table for investigation.
+
+mod format;
+mod repository;
+
+pub use format::{decode, encode};
+pub use repository::ArchiveRepository;
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use tracing::info;
+use url::Url;
+use uuid::Uuid;
+
+use crate::config::ArchiveConfig;
+use crate::db::{PostgresPool, SpanRepository};
+use crate::error::{Error, Result};
+use crate::models::{ArchiveManifest, RestoreSummary, Span};
+
+/// Spans inserted per batch when restoring
+const RESTORE_BATCH_SIZE: usize = 1000;
+
+/// Exports old spans to cold storage and restores them on demand
+pub struct Archiver {
+    config: ArchiveConfig,
+    spans: SpanRepository,
+    manifests: ArchiveRepository,
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl Archiver {
+    /// Create an archiver for the configured archive URL
+    ///
+    /// S3 and GCS credentials are read from the standard `AWS_*` and
+    /// `GOOGLE_*` environment variables.
+    pub fn new(config: &ArchiveConfig, postgres: &PostgresPool) -> Result<Self> {
+        let (store, prefix) = open_store(&config.url)?;
+
+        Ok(Self {
+            config: config.clone(),
+            spans: SpanRepository::new(postgres),
+            manifests: ArchiveRepository::new(postgres.pool().clone()),
+            store,
+            prefix,
+        })
+    }
+
+    /// Archive every complete hour older than `after_days` that has not been
+    /// archived yet, returning the partitions written
+    ///
+    /// Archival moves forward from the last archived hour, so spans arriving
+    /// after their hour was archived are not picked up.
+    pub async fn archive_pending(&self) -> Result<Vec<ArchiveManifest>> {
+        let cutoff = hour_start(Utc::now() - Duration::days(i64::from(self.config.after_days)))?;
+        let mut since = self.manifests.watermark().await?;
+        let mut written = Vec::new();
+
+        while let Some(first) = self.manifests.earliest_span(since, cutoff).await? {
+            let start = hour_start(first)?;
+            let end = start + Duration::hours(1);
+
+            let spans = self.spans.get_in_range(start, end).await?;
+            written.push(self.write_partition(start, end, &spans).await?);
+            since = Some(end);
+        }
+
+        Ok(written)
+    }
+
+    /// Insert archived spans started within `[since, until)` back into the
+    /// spans table
+    ///
+    /// Spans that are still stored are left untouched.
+    pub async fn restore(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<RestoreSummary> {
+        if since >= until {
+            return Err(Error::validation("Restore range must end after it starts"));
+        }
+
+        let mut summary = RestoreSummary::default();
+
+        for manifest in self.manifests.list(since, until).await? {
+            let data = self
+                .store
+                .get(&self.object_path(&manifest.location))
+                .await
+                .map_err(|e| Error::Archive(e.to_string()))?
+                .bytes()
+                .await
+                .map_err(|e| Error::Archive(e.to_string()))?;
+
+            let spans: Vec<_> = decode(data)?
+                .into_iter()
+                .filter(|s| s.started_at >= since && s.started_at < until)
+                .collect();
+
+            for chunk in spans.chunks(RESTORE_BATCH_SIZE) {
+                summary.spans_restored += self.spans.insert_batch(chunk).await?;
+            }
+            summary.partitions += 1;
+        }
+
+        Ok(summary)
+    }
+
+    async fn write_partition(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        spans: &[Span],
+    ) -> Result<ArchiveManifest> {
+        let location = start.format("date=%Y-%m-%d/hour=%H/spans.parquet").to_string();
+        let data = encode(spans)?;
+        let size_bytes = data.len();
+
+        self.store
+            .put(&self.object_path(&location), Bytes::from(data))
+            .await
+            .map_err(|e| Error::Archive(e.to_string()))?;
+
+        let manifest = ArchiveManifest {
+            id: Uuid::new_v4(),
+            partition_start: start,
+            partition_end: end,
+            location,
+            span_count: spans.len() as i64,
+            size_bytes: size_bytes as i64,
+            created_at: Utc::now(),
+        };
+        self.manifests.record(&manifest).await?;
+
+        info!("Archived {} spans to {}", manifest.span_count, manifest.location);
+        Ok(manifest)
+    }
+
+    fn object_path(&self, location: &str) -> Path {
+        Path::from(format!("{}/{}", self.prefix, location))
+    }
+}
+
+/// Open the object store behind an archive URL, returning it with the key
+/// prefix archive files are written under
+pub(crate) fn open_store(url: &str) -> Result<(Arc<dyn ObjectStore>, Path)> {
+    let parsed = Url::parse(url).map_err(|e| Error::config(format!("Invalid archive URL '{}': {}", url, e)))?;
+
+    let store: Arc<dyn ObjectStore> = match parsed.scheme() {
+        "s3" => Arc::new(
+            AmazonS3Builder::from_env()
+                .with_url(url)
+                .build()
+                .map_err(|e| Error::config(e.to_string()))?,
+        ),
+        "gs" => Arc::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url)
+                .build()
+                .map_err(|e| Error::config(e.to_string()))?,
+        ),
+        "file" => {
+            std::fs::create_dir_all(parsed.path())?;
+            let store = LocalFileSystem::new_with_prefix(parsed.path())
+                .map_err(|e| Error::config(e.to_string()))?;
+            return Ok((Arc::new(store), Path::default()));
+        }
+        other => {
+            return Err(Error::config(format!(
+                "Unsupported archive URL scheme '{}' (expected s3, gs or file)",
+                other
+            )))
+        }
+    };
+
+    Ok((store, Path::from(parsed.path())))
+}
+
+fn hour_start(t: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    t.duration_trunc(Duration::hours(1))
+        .map_err(|e| Error::internal(e.to_string()))
+}
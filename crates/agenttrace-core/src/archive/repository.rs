@@ -0,0 +1,109 @@
+//! Archive manifest repository
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+use crate::error::Result;
+use crate::models::ArchiveManifest;
+
+/// Repository for archive manifests
+#[derive(Clone)]
+pub struct ArchiveRepository {
+    pool: PgPool,
+}
+
+impl ArchiveRepository {
+    /// Create a new archive repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record an archived partition
+    pub async fn record(&self, manifest: &ArchiveManifest) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO span_archives (
+                id, partition_start, partition_end, location, span_count, size_bytes, created_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (partition_start) DO UPDATE SET
+                partition_end = EXCLUDED.partition_end,
+                location = EXCLUDED.location,
+                span_count = EXCLUDED.span_count,
+                size_bytes = EXCLUDED.size_bytes,
+                created_at = EXCLUDED.created_at
+            "#,
+        )
+        .bind(manifest.id)
+        .bind(manifest.partition_start)
+        .bind(manifest.partition_end)
+        .bind(&manifest.location)
+        .bind(manifest.span_count)
+        .bind(manifest.size_bytes)
+        .bind(manifest.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List archived partitions overlapping `[since, until)`
+    pub async fn list(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<ArchiveManifest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, partition_start, partition_end, location, span_count, size_bytes, created_at
+            FROM span_archives
+            WHERE partition_start < $2 AND partition_end > $1
+            ORDER BY partition_start
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_manifest).collect()
+    }
+
+    /// End of the most recently archived partition
+    pub async fn watermark(&self) -> Result<Option<DateTime<Utc>>> {
+        let watermark = sqlx::query_scalar("SELECT MAX(partition_end) FROM span_archives")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(watermark)
+    }
+
+    /// Start time of the earliest span in `[since, until)`
+    pub async fn earliest_span(
+        &self,
+        since: Option<DateTime<Utc>>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let earliest = sqlx::query_scalar(
+            r#"
+            SELECT MIN(started_at) FROM spans
+            WHERE ($1::timestamptz IS NULL OR started_at >= $1) AND started_at < $2
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(earliest)
+    }
+}
+
+fn row_to_manifest(row: &PgRow) -> Result<ArchiveManifest> {
+    Ok(ArchiveManifest {
+        id: row.try_get("id")?,
+        partition_start: row.try_get("partition_start")?,
+        partition_end: row.try_get("partition_end")?,
+        location: row.try_get("location")?,
+        span_count: row.try_get("span_count")?,
+        size_bytes: row.try_get("size_bytes")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
@@ -0,0 +1,114 @@
+//! Audit log
+//!
+//! Mutating API handlers describe what they changed with an
+//! [`AuditChange`](crate::models::AuditChange) attached to their response.
+//! The [audit middleware](crate::api::middleware::audit) adds who made the
+//! request and its request ID, and records the entry here, so security can
+//! see who created or deleted alert rules, exports and subscriptions via
+//! `GET /api/v1/audit`.
+
+use chrono::Utc;
+use sqlx::postgres::{PgRow, Postgres};
+use sqlx::{PgPool, QueryBuilder, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{AuditAction, AuditChange, AuditEntry, AuditQuery};
+
+/// Most entries returned by one audit log query
+pub const MAX_AUDIT_LIMIT: i64 = 1000;
+
+const AUDIT_COLUMNS: &str = "id, occurred_at, actor, action, resource_type, resource_id, diff, request_id";
+
+/// Repository for the audit log
+#[derive(Clone)]
+pub struct AuditRepository {
+    pool: PgPool,
+}
+
+impl AuditRepository {
+    /// Create a new audit repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a change made by `actor`
+    pub async fn record(&self, actor: &str, change: &AuditChange, request_id: Option<&str>) -> Result<AuditEntry> {
+        let entry = AuditEntry {
+            id: Uuid::new_v4(),
+            occurred_at: Utc::now(),
+            actor: actor.to_string(),
+            action: change.action,
+            resource_type: change.resource_type.to_string(),
+            resource_id: change.resource_id.clone(),
+            diff: change.diff(),
+            request_id: request_id.map(str::to_string),
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (id, occurred_at, actor, action, resource_type, resource_id, diff, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(entry.id)
+        .bind(entry.occurred_at)
+        .bind(&entry.actor)
+        .bind(entry.action.as_str())
+        .bind(&entry.resource_type)
+        .bind(&entry.resource_id)
+        .bind(&entry.diff)
+        .bind(&entry.request_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// List entries matching the query, newest first
+    pub async fn list(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let mut builder = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM audit_log WHERE 1 = 1", AUDIT_COLUMNS));
+
+        if let Some(actor) = &query.actor {
+            builder.push(" AND actor = ").push_bind(actor.clone());
+        }
+        if let Some(action) = query.action {
+            builder.push(" AND action = ").push_bind(action.as_str());
+        }
+        if let Some(resource_type) = &query.resource_type {
+            builder.push(" AND resource_type = ").push_bind(resource_type.clone());
+        }
+        if let Some(resource_id) = &query.resource_id {
+            builder.push(" AND resource_id = ").push_bind(resource_id.clone());
+        }
+        if let Some(since) = query.since {
+            builder.push(" AND occurred_at >= ").push_bind(since);
+        }
+        if let Some(until) = query.until {
+            builder.push(" AND occurred_at < ").push_bind(until);
+        }
+
+        builder
+            .push(" ORDER BY occurred_at DESC, id LIMIT ")
+            .push_bind(query.limit.unwrap_or(100).clamp(1, MAX_AUDIT_LIMIT));
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter().map(row_to_entry).collect()
+    }
+}
+
+fn row_to_entry(row: &PgRow) -> Result<AuditEntry> {
+    let action: String = row.try_get("action")?;
+
+    Ok(AuditEntry {
+        id: row.try_get("id")?,
+        occurred_at: row.try_get("occurred_at")?,
+        actor: row.try_get("actor")?,
+        action: AuditAction::parse(&action)
+            .ok_or_else(|| Error::Database(format!("Unknown audit action: {}", action)))?,
+        resource_type: row.try_get("resource_type")?,
+        resource_id: row.try_get("resource_id")?,
+        diff: row.try_get("diff")?,
+        request_id: row.try_get("request_id")?,
+    })
+}
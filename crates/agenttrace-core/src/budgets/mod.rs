@@ -0,0 +1,131 @@
+//! Spend budgets
+//!
+//! A budget caps spend over a calendar period, e.g. $2,000 a month for the
+//! `support-bot` service, rather than over the sliding window an alert rule
+//! watches. Budgets can be scoped to a service, a model, a project (the
+//! `agenttrace.project` span attribute) or any combination; an unscoped
+//! budget covers all spend.
+//!
+//! Spend is summed from the hourly cost aggregate since the start of the
+//! current week (Monday, UTC) or month. Each threshold, 50%, 80% and 100% by default, is
+//! notified once per period through the same channels alert rules use.
+
+mod repository;
+
+pub use repository::BudgetRepository;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
+
+use crate::alerting::{AlertRepository, Notification, NotificationSender, RetryPolicy};
+use crate::config::BudgetsConfig;
+use crate::error::Result;
+use crate::models::{BudgetAlert, BudgetStatus, NotificationRecord, RetryMessage};
+
+/// Checks budgets' spend and notifies thresholds as they are crossed
+pub struct BudgetEvaluator {
+    repo: BudgetRepository,
+    notifier: NotificationSender,
+    retries: Option<(AlertRepository, RetryPolicy)>,
+    interval: Duration,
+}
+
+impl BudgetEvaluator {
+    /// Create an evaluator checking budgets at the configured interval
+    pub fn new(repo: BudgetRepository, config: &BudgetsConfig) -> Self {
+        Self {
+            repo,
+            notifier: NotificationSender::new(),
+            retries: None,
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+        }
+    }
+
+    /// Send notifications with this sender
+    pub fn with_notifier(mut self, notifier: NotificationSender) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Queue failed notifications in this repository's retries, with this
+    /// policy
+    pub fn with_retries(mut self, repo: AlertRepository, policy: RetryPolicy) -> Self {
+        self.retries = Some((repo, policy));
+        self
+    }
+
+    /// Check budgets until the task is dropped
+    pub async fn start(&self) {
+        info!(interval_secs = self.interval.as_secs(), "Starting budget evaluator");
+
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.evaluate_all().await {
+                warn!("Failed to evaluate budgets: {}", e);
+            }
+        }
+    }
+
+    /// Check every enabled budget once, returning how many notified
+    pub async fn evaluate_all(&self) -> Result<usize> {
+        let now = Utc::now();
+        let budgets = self.repo.list().await?.into_iter().filter(|b| b.enabled).collect();
+        let mut notified = 0;
+
+        for status in self.repo.statuses(budgets, now).await? {
+            match self.evaluate(status, now).await {
+                Ok(true) => notified += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to evaluate budget: {}", e),
+            }
+        }
+
+        Ok(notified)
+    }
+
+    /// Check one budget, notifying if it crossed a threshold since the last
+    /// check
+    ///
+    /// When several thresholds are crossed at once, e.g. by a large batch
+    /// of spans or a budget created mid-period, only the highest is sent.
+    async fn evaluate(&self, status: BudgetStatus, now: DateTime<Utc>) -> Result<bool> {
+        let mut newest = None;
+        for threshold in &status.thresholds_crossed {
+            let alert = BudgetAlert::new(&status, *threshold, now);
+            if self.repo.claim_threshold(&alert).await? {
+                newest = Some(alert);
+            }
+        }
+
+        let Some(alert) = newest else {
+            debug!(budget = status.budget.name, spent_usd = status.spent_usd, "Budget within thresholds");
+            return Ok(false);
+        };
+
+        info!(
+            budget = alert.budget_name,
+            threshold = alert.threshold_percent,
+            spent_usd = alert.spent_usd,
+            "Budget threshold crossed"
+        );
+
+        let results = self
+            .notifier
+            .send_all(&status.budget.notification_channels, &Notification::budget_alert(&alert))
+            .await;
+        if let Some((repo, policy)) = &self.retries {
+            // The first attempt is recorded either way, so failing to queue
+            // is only logged
+            if let Err(e) = policy.queue_failures(repo, &RetryMessage::BudgetAlert(alert.clone()), &results).await {
+                warn!(error = %e, "Failed to queue budget notification retries");
+            }
+        }
+        let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+        self.repo.record_notifications(&alert, &records).await?;
+
+        Ok(true)
+    }
+}
@@ -0,0 +1,307 @@
+//! Budget repository
+
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{
+    Budget, BudgetAlert, BudgetInput, BudgetPeriod, BudgetStatus, NotificationChannel,
+    NotificationRecord,
+};
+
+const BUDGET_COLUMNS: &str = "id, name, description, service_name, model_name, project, period, \
+     amount_usd, thresholds, notification_channels, enabled, created_at, updated_at";
+
+/// Repository for budgets and the thresholds they have crossed
+#[derive(Clone)]
+pub struct BudgetRepository {
+    pool: PgPool,
+}
+
+impl BudgetRepository {
+    /// Create a new budget repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// List budgets by name
+    pub async fn list(&self) -> Result<Vec<Budget>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM budgets ORDER BY name", BUDGET_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(row_to_budget).collect()
+    }
+
+    /// Get a budget by ID
+    pub async fn get(&self, id: Uuid) -> Result<Option<Budget>> {
+        let row = sqlx::query(&format!("SELECT {} FROM budgets WHERE id = $1", BUDGET_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(row_to_budget).transpose()
+    }
+
+    /// Create a budget
+    pub async fn create(&self, input: BudgetInput) -> Result<Budget> {
+        input.validate()?;
+
+        let now = Utc::now();
+        let budget = Budget {
+            id: Uuid::new_v4(),
+            name: input.name.trim().to_string(),
+            description: input.description,
+            service_name: input.service_name,
+            model_name: input.model_name,
+            project: input.project,
+            period: input.period,
+            amount_usd: input.amount_usd,
+            thresholds: input.thresholds,
+            notification_channels: input.notification_channels,
+            enabled: input.enabled,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO budgets (
+                id, name, description, service_name, model_name, project, period,
+                amount_usd, thresholds, notification_channels, enabled, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            "#,
+        )
+        .bind(budget.id)
+        .bind(&budget.name)
+        .bind(&budget.description)
+        .bind(&budget.service_name)
+        .bind(&budget.model_name)
+        .bind(&budget.project)
+        .bind(budget.period.as_str())
+        .bind(budget.amount_usd)
+        .bind(thresholds_to_db(&budget.thresholds))
+        .bind(serde_json::to_value(&budget.notification_channels)?)
+        .bind(budget.enabled)
+        .bind(budget.created_at)
+        .bind(budget.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &budget.name))?;
+
+        Ok(budget)
+    }
+
+    /// Replace a budget's settings
+    ///
+    /// Returns None if the budget doesn't exist. Thresholds already crossed
+    /// this period stay crossed.
+    pub async fn update(&self, id: Uuid, input: BudgetInput) -> Result<Option<Budget>> {
+        input.validate()?;
+        let name = input.name.trim().to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE budgets SET
+                name = $2,
+                description = $3,
+                service_name = $4,
+                model_name = $5,
+                project = $6,
+                period = $7,
+                amount_usd = $8,
+                thresholds = $9,
+                notification_channels = $10,
+                enabled = $11,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&name)
+        .bind(&input.description)
+        .bind(&input.service_name)
+        .bind(&input.model_name)
+        .bind(&input.project)
+        .bind(input.period.as_str())
+        .bind(input.amount_usd)
+        .bind(thresholds_to_db(&input.thresholds))
+        .bind(serde_json::to_value(&input.notification_channels)?)
+        .bind(input.enabled)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &name))?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    /// Delete a budget and its threshold history
+    pub async fn delete(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM budgets WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Budgets' spend in the period containing `now`, in their order
+    ///
+    /// Spend is summed from the `cost_hourly` aggregate in one query for
+    /// all the budgets. Periods start at midnight, so they cover whole
+    /// buckets.
+    pub async fn statuses(&self, budgets: Vec<Budget>, now: DateTime<Utc>) -> Result<Vec<BudgetStatus>> {
+        if budgets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ids: Vec<Uuid> = budgets.iter().map(|b| b.id).collect();
+        let starts: Vec<DateTime<Utc>> = budgets.iter().map(|b| b.period.bounds(now).0).collect();
+        let services: Vec<Option<String>> = budgets.iter().map(|b| b.service_name.clone()).collect();
+        let models: Vec<Option<String>> = budgets.iter().map(|b| b.model_name.clone()).collect();
+        let projects: Vec<Option<String>> = budgets.iter().map(|b| b.project.clone()).collect();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT b.id, COALESCE(SUM(c.cost_usd), 0)::DOUBLE PRECISION AS spent
+            FROM UNNEST($1::uuid[], $2::timestamptz[], $3::text[], $4::text[], $5::text[])
+                AS b(id, since, service_name, model_name, project)
+            LEFT JOIN cost_hourly c
+              ON c.bucket >= b.since AND c.bucket <= $6
+             AND (b.service_name IS NULL OR c.service_name = b.service_name)
+             AND (b.model_name IS NULL OR c.model_name = b.model_name)
+             AND (b.project IS NULL OR c.project = b.project)
+            GROUP BY b.id
+            "#,
+        )
+        .bind(&ids)
+        .bind(&starts)
+        .bind(&services)
+        .bind(&models)
+        .bind(&projects)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut spent = std::collections::HashMap::with_capacity(rows.len());
+        for row in &rows {
+            spent.insert(row.try_get::<Uuid, _>("id")?, row.try_get::<f64, _>("spent")?);
+        }
+
+        Ok(budgets
+            .into_iter()
+            .map(|budget| {
+                let spent = spent.get(&budget.id).copied().unwrap_or(0.0);
+                BudgetStatus::new(budget, spent, now)
+            })
+            .collect())
+    }
+
+    /// A budget's spend in the period containing `now`
+    pub async fn status(&self, budget: Budget, now: DateTime<Utc>) -> Result<BudgetStatus> {
+        let mut statuses = self.statuses(vec![budget], now).await?;
+        statuses.pop().ok_or_else(|| Error::internal("No status for budget"))
+    }
+
+    /// Record that a budget crossed a threshold this period
+    ///
+    /// Returns false if it was already recorded, so each threshold is
+    /// notified once per period even with several collectors running.
+    pub async fn claim_threshold(&self, alert: &BudgetAlert) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO budget_alerts (budget_id, period_start, threshold_percent, spent_usd, triggered_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(alert.budget_id)
+        .bind(alert.period_start)
+        .bind(i32::try_from(alert.threshold_percent).unwrap_or(i32::MAX))
+        .bind(alert.spent_usd)
+        .bind(alert.triggered_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the notifications sent for a crossed threshold
+    pub async fn record_notifications(&self, alert: &BudgetAlert, records: &[NotificationRecord]) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE budget_alerts SET notifications_sent = $4
+            WHERE budget_id = $1 AND period_start = $2 AND threshold_percent = $3
+            "#,
+        )
+        .bind(alert.budget_id)
+        .bind(alert.period_start)
+        .bind(i32::try_from(alert.threshold_percent).unwrap_or(i32::MAX))
+        .bind(serde_json::to_value(records)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Add notifications sent for a threshold after the first, like retries
+    pub async fn append_notifications(&self, alert: &BudgetAlert, records: &[NotificationRecord]) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE budget_alerts SET notifications_sent = notifications_sent || $4
+            WHERE budget_id = $1 AND period_start = $2 AND threshold_percent = $3
+            "#,
+        )
+        .bind(alert.budget_id)
+        .bind(alert.period_start)
+        .bind(i32::try_from(alert.threshold_percent).unwrap_or(i32::MAX))
+        .bind(serde_json::to_value(records)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Report a duplicate name as a validation error
+fn name_conflict(e: sqlx::Error, name: &str) -> Error {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            Error::validation(format!("A budget named '{}' already exists", name))
+        }
+        _ => e.into(),
+    }
+}
+
+fn thresholds_to_db(thresholds: &[u32]) -> Vec<i32> {
+    thresholds.iter().map(|t| i32::try_from(*t).unwrap_or(i32::MAX)).collect()
+}
+
+fn row_to_budget(row: &PgRow) -> Result<Budget> {
+    let period: String = row.try_get("period")?;
+    let thresholds: Vec<i32> = row.try_get("thresholds")?;
+    let channels: serde_json::Value = row.try_get("notification_channels")?;
+    let notification_channels: Vec<NotificationChannel> = serde_json::from_value(channels).unwrap_or_default();
+
+    Ok(Budget {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        service_name: row.try_get("service_name")?,
+        model_name: row.try_get("model_name")?,
+        project: row.try_get("project")?,
+        period: BudgetPeriod::parse(&period)?,
+        amount_usd: row.try_get("amount_usd")?,
+        thresholds: thresholds.into_iter().filter_map(|t| u32::try_from(t).ok()).collect(),
+        notification_channels,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
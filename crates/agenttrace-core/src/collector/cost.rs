@@ -1,25 +1,295 @@
 //! Cost calculator for LLM calls
 //!
 //! Calculates the cost of LLM API calls based on token usage and model pricing.
+//! Prices come from a built-in table, overridden by a pricing file or URL
+//! (`pricing.source`) that is re-read while the collector runs, so provider
+//! price changes don't need a release. A model can have several dated
+//! versions of its prices; each span is costed at the version in effect when
+//! it started, so older spans keep the prices they were charged.
+//!
+//! Usage that isn't billed by the token (images, audio and embeddings) is
+//! read from span attributes (see [`MediaUsage`]) and priced by the
+//! matching optional rates on [`ModelPricing`].
+//!
+//! Hosted models can be priced per provider (Azure OpenAI, AWS Bedrock,
+//! Vertex AI) and per region within a provider. A span is priced by the most
+//! specific table that knows its model: its provider and `cloud.region`
+//! attribute, then its provider, then the model alone.
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::NaiveDate;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::config::PricingConfig;
+use crate::error::{Error, Result};
 use crate::models::Span;
 
+/// How long to wait for a pricing URL
+const PRICING_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Source named for prices from the built-in table
+pub const BUILTIN_PRICING_SOURCE: &str = "built-in";
+
+/// Attribute key under which the pricing version used to cost a span is
+/// recorded, e.g. `gpt-4o@2024-10-02`
+pub const PRICING_VERSION_ATTRIBUTE: &str = "agenttrace.pricing_version";
+
+/// Attribute key for the cloud region that served a span, used to pick
+/// regional prices
+pub const REGION_ATTRIBUTE: &str = "cloud.region";
+
+/// Attribute key for the number of images generated or read
+pub const IMAGES_ATTRIBUTE: &str = "agenttrace.usage.images";
+
+/// Attribute key for seconds of audio transcribed or synthesized
+pub const AUDIO_SECONDS_ATTRIBUTE: &str = "agenttrace.usage.audio_seconds";
+
+/// Attribute key for tokens embedded
+pub const EMBEDDING_TOKENS_ATTRIBUTE: &str = "agenttrace.usage.embedding_tokens";
+
 /// Pricing information for a model (per million tokens)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ModelPricing {
     /// Cost per million input tokens
     pub input_per_million: f64,
     /// Cost per million output tokens
     pub output_per_million: f64,
     /// Cost per million cached input tokens (if applicable)
+    #[serde(default)]
     pub cached_input_per_million: Option<f64>,
+    /// Cost per image generated or read (if applicable)
+    #[serde(default)]
+    pub per_image: Option<f64>,
+    /// Cost per second of audio (if applicable)
+    #[serde(default)]
+    pub per_audio_second: Option<f64>,
+    /// Cost per thousand embedded tokens (if applicable)
+    #[serde(default)]
+    pub embedding_per_thousand: Option<f64>,
+    /// First day (UTC) these prices apply; unset means from the start
+    #[serde(default)]
+    pub effective_from: Option<NaiveDate>,
+    /// Day (UTC) these prices stop applying; unset means still current
+    #[serde(default)]
+    pub effective_until: Option<NaiveDate>,
+}
+
+impl ModelPricing {
+    /// Whether these prices apply on `date`
+    pub fn is_effective_on(&self, date: NaiveDate) -> bool {
+        self.effective_from.is_none_or(|from| from <= date)
+            && self.effective_until.is_none_or(|until| date < until)
+    }
+
+    /// Name of this version of `model`'s prices, e.g. `gpt-4o@2024-10-02`,
+    /// or just the model for prices that have always applied
+    pub fn version(&self, model: &str) -> String {
+        match self.effective_from {
+            Some(from) => format!("{}@{}", model, from),
+            None => model.to_string(),
+        }
+    }
+}
+
+/// Non-token usage recorded on a span, stored in its attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MediaUsage {
+    /// Images generated or read
+    pub images: Option<f64>,
+    /// Seconds of audio transcribed or synthesized
+    pub audio_seconds: Option<f64>,
+    /// Tokens embedded
+    pub embedding_tokens: Option<f64>,
+}
+
+impl MediaUsage {
+    /// Read usage from span attributes, ignoring non-numeric values
+    pub fn from_attributes(attributes: &serde_json::Value) -> Self {
+        let number = |key: &str| attributes.get(key).and_then(serde_json::Value::as_f64);
+        Self {
+            images: number(IMAGES_ATTRIBUTE),
+            audio_seconds: number(AUDIO_SECONDS_ATTRIBUTE),
+            embedding_tokens: number(EMBEDDING_TOKENS_ATTRIBUTE),
+        }
+    }
+
+    /// Write the usage that is set into span attributes, overwriting any
+    /// values already there
+    ///
+    /// Attributes that aren't a JSON object become one.
+    pub fn record(&self, attributes: &mut serde_json::Value) {
+        let fields = [
+            (IMAGES_ATTRIBUTE, self.images),
+            (AUDIO_SECONDS_ATTRIBUTE, self.audio_seconds),
+            (EMBEDDING_TOKENS_ATTRIBUTE, self.embedding_tokens),
+        ];
+        if fields.iter().all(|(_, value)| value.is_none()) {
+            return;
+        }
+        if !attributes.is_object() {
+            *attributes = serde_json::json!({});
+        }
+        if let Some(map) = attributes.as_object_mut() {
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    map.insert(key.to_string(), serde_json::json!(value));
+                }
+            }
+        }
+    }
+
+    /// Cost of this usage at `pricing`; usage without a matching rate costs nothing
+    fn cost(&self, pricing: &ModelPricing) -> f64 {
+        let priced = [
+            (self.images, pricing.per_image),
+            (self.audio_seconds, pricing.per_audio_second),
+            (self.embedding_tokens.map(|tokens| tokens / 1_000.0), pricing.embedding_per_thousand),
+        ];
+        priced
+            .into_iter()
+            .filter_map(|(amount, rate)| Some(amount?.max(0.0) * rate?))
+            .sum()
+    }
+}
+
+/// Which spans a set of prices applies to: a model, optionally only when
+/// served by a provider, and optionally only in one of its regions
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PricingKey {
+    provider: Option<String>,
+    region: Option<String>,
+    model: String,
+}
+
+impl PricingKey {
+    /// Prices for `model` from any provider
+    fn model(model: impl Into<String>) -> Self {
+        Self {
+            provider: None,
+            region: None,
+            model: model.into(),
+        }
+    }
+
+    /// Prices for `model` when served by `provider`
+    fn hosted(provider: &str, model: impl Into<String>) -> Self {
+        Self {
+            provider: Some(canonical_provider(provider)),
+            region: None,
+            model: model.into(),
+        }
+    }
+
+    /// Name used in pricing versions, e.g. `bedrock/eu-west-1/anthropic.claude-3-haiku`
+    fn label(&self) -> String {
+        [self.provider.as_deref(), self.region.as_deref(), Some(self.model.as_str())]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Where these prices live in a pricing file, for errors
+    fn path(&self) -> String {
+        match (&self.provider, &self.region) {
+            (Some(provider), Some(region)) => {
+                format!("providers.{}.regions.{}.models.{}", provider, region, self.model)
+            }
+            (Some(provider), None) => format!("providers.{}.models.{}", provider, self.model),
+            _ => format!("models.{}", self.model),
+        }
+    }
+}
+
+/// Canonical name for a model provider, so the names SDKs and OpenTelemetry
+/// use for the same cloud (`azure_openai`, `az.ai.openai`, ...) share prices
+pub fn canonical_provider(provider: &str) -> String {
+    let provider = provider.trim().to_lowercase();
+    match provider.as_str() {
+        "azure" | "azure_openai" | "azure-openai" | "azureopenai" | "az.ai.openai" => "azure".to_string(),
+        "bedrock" | "aws_bedrock" | "aws-bedrock" | "aws.bedrock" => "bedrock".to_string(),
+        "vertex" | "vertex_ai" | "vertex-ai" | "vertexai" | "gcp.vertex_ai" => "vertex".to_string(),
+        _ => provider,
+    }
+}
+
+/// One version of a model's price, for listing
+#[derive(Debug, Clone, Serialize)]
+pub struct PricingEntry {
+    /// Model name, or the prefix of the names it prices
+    pub model: String,
+    /// Provider these prices are limited to; unset for any provider
+    pub provider: Option<String>,
+    /// Region of `provider` these prices are limited to; unset for every region
+    pub region: Option<String>,
+    /// Name of this version, as recorded on the spans it costs
+    pub version: String,
+    #[serde(flatten)]
+    pub pricing: ModelPricing,
+    /// Where the price came from: the pricing file or URL, or `built-in`
+    pub source: String,
+}
+
+/// Contents of a pricing file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PricingFile {
+    #[serde(default)]
+    models: HashMap<String, PricingVersions>,
+    /// `[providers.<provider>.models."<name>"]` tables
+    #[serde(default)]
+    providers: HashMap<String, ProviderPricingFile>,
+}
+
+/// A provider's prices in a pricing file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProviderPricingFile {
+    #[serde(default)]
+    models: HashMap<String, PricingVersions>,
+    /// `[providers.<provider>.regions.<region>.models."<name>"]` tables
+    #[serde(default)]
+    regions: HashMap<String, RegionPricingFile>,
+}
+
+/// A provider region's prices in a pricing file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RegionPricingFile {
+    #[serde(default)]
+    models: HashMap<String, PricingVersions>,
+}
+
+/// A model's prices in a pricing file: a `[models."<name>"]` table, or
+/// `[[models."<name>"]]` tables for prices that changed over time
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum PricingVersions {
+    One(ModelPricing),
+    Many(Vec<ModelPricing>),
+}
+
+/// Prices loaded from `pricing.source`
+#[derive(Debug, Default)]
+struct PricingOverrides {
+    source: String,
+    pricing: HashMap<PricingKey, Vec<ModelPricing>>,
 }
 
 /// Cost calculator with model pricing database
+///
+/// Clones share the loaded overrides, so a reload through one is seen by all.
+#[derive(Clone)]
 pub struct CostCalculator {
-    pricing: HashMap<String, ModelPricing>,
+    /// Built-in prices, plus any set with [`set_pricing`](Self::set_pricing),
+    /// each model's versions ordered oldest first
+    pricing: HashMap<PricingKey, Vec<ModelPricing>>,
+    overrides: Arc<RwLock<PricingOverrides>>,
 }
 
 impl Default for CostCalculator {
@@ -41,6 +311,7 @@ impl CostCalculator {
                 input_per_million: 15.0,
                 output_per_million: 75.0,
                 cached_input_per_million: Some(1.5),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -49,6 +320,7 @@ impl CostCalculator {
                 input_per_million: 3.0,
                 output_per_million: 15.0,
                 cached_input_per_million: Some(0.3),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -57,6 +329,7 @@ impl CostCalculator {
                 input_per_million: 0.25,
                 output_per_million: 1.25,
                 cached_input_per_million: Some(0.03),
+                ..Default::default()
             },
         );
         // Claude 3.5 family
@@ -66,6 +339,7 @@ impl CostCalculator {
                 input_per_million: 3.0,
                 output_per_million: 15.0,
                 cached_input_per_million: Some(0.3),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -74,6 +348,7 @@ impl CostCalculator {
                 input_per_million: 3.0,
                 output_per_million: 15.0,
                 cached_input_per_million: Some(0.3),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -82,6 +357,7 @@ impl CostCalculator {
                 input_per_million: 0.80,
                 output_per_million: 4.0,
                 cached_input_per_million: Some(0.08),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -90,6 +366,7 @@ impl CostCalculator {
                 input_per_million: 0.80,
                 output_per_million: 4.0,
                 cached_input_per_million: Some(0.08),
+                ..Default::default()
             },
         );
         // Claude 4 family
@@ -99,6 +376,7 @@ impl CostCalculator {
                 input_per_million: 3.0,
                 output_per_million: 15.0,
                 cached_input_per_million: Some(0.3),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -107,6 +385,7 @@ impl CostCalculator {
                 input_per_million: 15.0,
                 output_per_million: 75.0,
                 cached_input_per_million: Some(1.5),
+                ..Default::default()
             },
         );
 
@@ -117,6 +396,7 @@ impl CostCalculator {
                 input_per_million: 30.0,
                 output_per_million: 60.0,
                 cached_input_per_million: None,
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -125,6 +405,7 @@ impl CostCalculator {
                 input_per_million: 10.0,
                 output_per_million: 30.0,
                 cached_input_per_million: None,
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -133,6 +414,8 @@ impl CostCalculator {
                 input_per_million: 2.50,
                 output_per_million: 10.0,
                 cached_input_per_million: Some(1.25),
+                effective_from: NaiveDate::from_ymd_opt(2024, 10, 2),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -141,6 +424,7 @@ impl CostCalculator {
                 input_per_million: 0.15,
                 output_per_million: 0.60,
                 cached_input_per_million: Some(0.075),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -149,6 +433,7 @@ impl CostCalculator {
                 input_per_million: 15.0,
                 output_per_million: 60.0,
                 cached_input_per_million: Some(7.5),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -157,6 +442,7 @@ impl CostCalculator {
                 input_per_million: 3.0,
                 output_per_million: 12.0,
                 cached_input_per_million: Some(1.5),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -165,6 +451,7 @@ impl CostCalculator {
                 input_per_million: 150.0,
                 output_per_million: 600.0,
                 cached_input_per_million: None,
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -173,6 +460,37 @@ impl CostCalculator {
                 input_per_million: 0.50,
                 output_per_million: 1.50,
                 cached_input_per_million: None,
+                ..Default::default()
+            },
+        );
+
+        // OpenAI image, audio and embedding models, billed by usage attributes
+        pricing.insert(
+            "dall-e-3".to_string(),
+            ModelPricing {
+                per_image: Some(0.04),
+                ..Default::default()
+            },
+        );
+        pricing.insert(
+            "whisper-1".to_string(),
+            ModelPricing {
+                per_audio_second: Some(0.0001),
+                ..Default::default()
+            },
+        );
+        pricing.insert(
+            "text-embedding-3-small".to_string(),
+            ModelPricing {
+                embedding_per_thousand: Some(0.00002),
+                ..Default::default()
+            },
+        );
+        pricing.insert(
+            "text-embedding-3-large".to_string(),
+            ModelPricing {
+                embedding_per_thousand: Some(0.00013),
+                ..Default::default()
             },
         );
 
@@ -183,6 +501,7 @@ impl CostCalculator {
                 input_per_million: 1.25,
                 output_per_million: 5.0,
                 cached_input_per_million: Some(0.3125),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -191,6 +510,7 @@ impl CostCalculator {
                 input_per_million: 0.075,
                 output_per_million: 0.30,
                 cached_input_per_million: Some(0.01875),
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -199,6 +519,7 @@ impl CostCalculator {
                 input_per_million: 0.10,
                 output_per_million: 0.40,
                 cached_input_per_million: Some(0.025),
+                ..Default::default()
             },
         );
 
@@ -209,6 +530,7 @@ impl CostCalculator {
                 input_per_million: 2.0,
                 output_per_million: 6.0,
                 cached_input_per_million: None,
+                ..Default::default()
             },
         );
         pricing.insert(
@@ -217,13 +539,167 @@ impl CostCalculator {
                 input_per_million: 0.2,
                 output_per_million: 0.6,
                 cached_input_per_million: None,
+                ..Default::default()
             },
         );
 
-        Self { pricing }
+        let mut pricing: HashMap<PricingKey, Vec<ModelPricing>> = pricing
+            .into_iter()
+            .map(|(model, pricing)| (PricingKey::model(model), vec![pricing]))
+            .collect();
+
+        // Hosted models, where the cloud serving them sets the price:
+        // (provider, model, input, output, cached input) per million tokens
+        let hosted: &[(&str, &str, f64, f64, Option<f64>)] = &[
+            // Azure OpenAI (global standard deployments)
+            ("azure", "gpt-4o", 2.50, 10.0, Some(1.25)),
+            ("azure", "gpt-4o-mini", 0.165, 0.66, Some(0.083)),
+            ("azure", "gpt-4-turbo", 10.0, 30.0, None),
+            ("azure", "gpt-4", 30.0, 60.0, None),
+            ("azure", "gpt-35-turbo", 0.50, 1.50, None),
+            ("azure", "o1", 15.0, 60.0, Some(7.5)),
+            ("azure", "o1-mini", 3.30, 13.20, Some(1.65)),
+            // AWS Bedrock (on-demand, us-east-1)
+            ("bedrock", "anthropic.claude-3-opus", 15.0, 75.0, None),
+            ("bedrock", "anthropic.claude-3-sonnet", 3.0, 15.0, None),
+            ("bedrock", "anthropic.claude-3-haiku", 0.25, 1.25, None),
+            ("bedrock", "anthropic.claude-3-5-sonnet", 3.0, 15.0, Some(0.3)),
+            ("bedrock", "anthropic.claude-3-5-haiku", 0.80, 4.0, Some(0.08)),
+            ("bedrock", "meta.llama3-1-70b-instruct", 0.72, 0.72, None),
+            ("bedrock", "meta.llama3-1-8b-instruct", 0.22, 0.22, None),
+            ("bedrock", "amazon.nova-pro", 0.80, 3.20, Some(0.20)),
+            ("bedrock", "amazon.nova-lite", 0.06, 0.24, Some(0.015)),
+            ("bedrock", "amazon.nova-micro", 0.035, 0.14, Some(0.00875)),
+            ("bedrock", "mistral.mistral-large", 4.0, 12.0, None),
+            // Vertex AI
+            ("vertex", "gemini-1.5-pro", 1.25, 5.0, Some(0.3125)),
+            ("vertex", "gemini-1.5-flash", 0.075, 0.30, Some(0.01875)),
+            ("vertex", "gemini-2.0-flash", 0.15, 0.60, Some(0.0375)),
+            ("vertex", "claude-3-5-sonnet", 3.0, 15.0, Some(0.3)),
+            ("vertex", "claude-3-5-haiku", 0.80, 4.0, Some(0.08)),
+            ("vertex", "claude-3-opus", 15.0, 75.0, Some(1.5)),
+        ];
+        for &(provider, model, input, output, cached) in hosted {
+            pricing.insert(
+                PricingKey::hosted(provider, model),
+                vec![ModelPricing {
+                    input_per_million: input,
+                    output_per_million: output,
+                    cached_input_per_million: cached,
+                    ..Default::default()
+                }],
+            );
+        }
+        pricing.insert(
+            PricingKey::hosted("bedrock", "amazon.titan-embed-text-v2"),
+            vec![ModelPricing {
+                embedding_per_thousand: Some(0.00002),
+                ..Default::default()
+            }],
+        );
+
+        // Earlier prices, for spans from before a price change.
+        // gpt-4o pointed at the pricier 2024-05-13 snapshot until 2024-10-02
+        if let Some(versions) = pricing.get_mut(&PricingKey::model("gpt-4o")) {
+            versions.insert(
+                0,
+                ModelPricing {
+                    input_per_million: 5.0,
+                    output_per_million: 15.0,
+                    cached_input_per_million: None,
+                    effective_until: NaiveDate::from_ymd_opt(2024, 10, 2),
+                    ..Default::default()
+                },
+            );
+        }
+
+        Self {
+            pricing,
+            overrides: Arc::new(RwLock::new(PricingOverrides::default())),
+        }
+    }
+
+    /// Re-read the pricing file or URL in `config`, replacing the prices
+    /// loaded from it before; returns how many models it prices
+    ///
+    /// A model in the source replaces every built-in version of that
+    /// model's prices.
+    ///
+    /// A source that can't be read or parsed leaves the current prices in
+    /// place. Without a source, any loaded prices are dropped.
+    pub async fn reload(&self, config: &PricingConfig) -> Result<usize> {
+        let Some(source) = config.source.as_deref() else {
+            *self.overrides.write() = PricingOverrides::default();
+            return Ok(0);
+        };
+
+        let text = if source.starts_with("http://") || source.starts_with("https://") {
+            let response = reqwest::Client::new()
+                .get(source)
+                .timeout(PRICING_FETCH_TIMEOUT)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| Error::config(format!("Failed to fetch pricing from {}: {}", source, e)))?;
+            response
+                .text()
+                .await
+                .map_err(|e| Error::config(format!("Failed to fetch pricing from {}: {}", source, e)))?
+        } else {
+            tokio::fs::read_to_string(source)
+                .await
+                .map_err(|e| Error::config(format!("Failed to read pricing file {}: {}", source, e)))?
+        };
+
+        let pricing = parse_pricing(&text)
+            .map_err(|e| Error::config(format!("Invalid pricing in {}: {}", source, e)))?;
+        let count = pricing.len();
+        *self.overrides.write() = PricingOverrides {
+            source: source.to_string(),
+            pricing,
+        };
+
+        Ok(count)
+    }
+
+    /// Every version of every priced model, ordered by name then date
+    pub fn list(&self) -> Vec<PricingEntry> {
+        let overrides = self.overrides.read();
+        let entries = |pricing: &HashMap<PricingKey, Vec<ModelPricing>>, source: &str| {
+            pricing
+                .iter()
+                .flat_map(|(key, versions)| {
+                    versions.iter().map(move |pricing| {
+                        let entry = PricingEntry {
+                            model: key.model.clone(),
+                            provider: key.provider.clone(),
+                            region: key.region.clone(),
+                            version: pricing.version(&key.label()),
+                            pricing: pricing.clone(),
+                            source: source.to_string(),
+                        };
+                        (key.clone(), entry)
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut listed = entries(&overrides.pricing, &overrides.source);
+        listed.extend(
+            entries(&self.pricing, BUILTIN_PRICING_SOURCE)
+                .into_iter()
+                .filter(|(key, _)| !overrides.pricing.contains_key(key)),
+        );
+        listed.sort_by(|(a_key, a), (b_key, b)| {
+            (&a_key.model, &a_key.provider, &a_key.region)
+                .cmp(&(&b_key.model, &b_key.provider, &b_key.region))
+                .then(a.pricing.effective_from.cmp(&b.pricing.effective_from))
+        });
+        listed.into_iter().map(|(_, entry)| entry).collect()
     }
 
-    /// Calculate cost for a span
+    /// Calculate cost for a span at the prices in effect when it started,
+    /// recording the version used under [`PRICING_VERSION_ATTRIBUTE`]
     pub fn calculate(&self, span: &mut Span) {
         // Only calculate for LLM calls with token usage
         if !span.is_llm_call() {
@@ -236,8 +712,14 @@ impl CostCalculator {
         };
 
         // Find matching pricing
-        let pricing = self.find_pricing(model_name);
-        let pricing = match pricing {
+        let region = span.attributes.get(REGION_ATTRIBUTE).and_then(|v| v.as_str());
+        let found = self.find_pricing(
+            span.model_provider.as_deref(),
+            region,
+            model_name,
+            span.started_at.date_naive(),
+        );
+        let (version, pricing) = match found {
             Some(p) => p,
             None => {
                 // Unknown model, can't calculate cost
@@ -246,58 +728,207 @@ impl CostCalculator {
             }
         };
 
-        let tokens_in = span.tokens_in.unwrap_or(0) as f64;
+        let tokens_in = span.tokens_in.unwrap_or(0).max(0);
+        let tokens_cached = span.tokens_cached_in.unwrap_or(0).clamp(0, tokens_in) as f64;
+        let tokens_uncached = tokens_in as f64 - tokens_cached;
         let tokens_out = span.tokens_out.unwrap_or(0) as f64;
         let tokens_reasoning = span.tokens_reasoning.unwrap_or(0) as f64;
 
-        // Calculate input cost
-        let input_cost = (tokens_in / 1_000_000.0) * pricing.input_per_million;
+        // Calculate input cost (cache reads are part of tokens_in, billed at the
+        // cached rate when the model has one)
+        let cached_rate = pricing.cached_input_per_million.unwrap_or(pricing.input_per_million);
+        let input_cost = (tokens_uncached / 1_000_000.0) * pricing.input_per_million
+            + (tokens_cached / 1_000_000.0) * cached_rate;
 
         // Calculate output cost (reasoning tokens count as output)
         let output_cost = ((tokens_out + tokens_reasoning) / 1_000_000.0) * pricing.output_per_million;
 
-        span.cost_usd = Some(input_cost + output_cost);
-    }
+        let media_cost = MediaUsage::from_attributes(&span.attributes).cost(&pricing);
 
-    /// Find pricing for a model by matching model name prefix
-    fn find_pricing(&self, model_name: &str) -> Option<&ModelPricing> {
-        // Try exact match first
-        if let Some(pricing) = self.pricing.get(model_name) {
-            return Some(pricing);
+        span.cost_usd = Some(input_cost + output_cost + media_cost);
+        if let Some(map) = span.attributes.as_object_mut() {
+            map.insert(PRICING_VERSION_ATTRIBUTE.to_string(), serde_json::Value::String(version));
         }
+    }
 
-        // Try prefix match (e.g., "claude-3-5-sonnet-20241022" matches "claude-3-5-sonnet")
-        for (key, pricing) in &self.pricing {
-            if model_name.starts_with(key) {
-                return Some(pricing);
-            }
-        }
+    /// Find pricing for a model on `date` by matching model name prefix,
+    /// with the version's name
+    ///
+    /// The provider's prices for `region` are tried first, then the
+    /// provider's prices, then prices for the model from any provider. Within
+    /// each, loaded prices win over built-in ones, and the longest matching
+    /// name wins among prefix matches, so `gpt-4o-mini-2024` is priced as
+    /// `gpt-4o-mini` rather than `gpt-4o` or `gpt-4`.
+    fn find_pricing(
+        &self,
+        provider: Option<&str>,
+        region: Option<&str>,
+        model_name: &str,
+        date: NaiveDate,
+    ) -> Option<(String, ModelPricing)> {
+        let overrides = self.overrides.read();
+        let provider = provider.map(canonical_provider);
+        let region = region.map(|r| r.trim().to_lowercase());
 
-        // Try contains match for versioned models
-        for (key, pricing) in &self.pricing {
-            if model_name.contains(key) {
-                return Some(pricing);
+        let mut scopes = Vec::with_capacity(3);
+        if let Some(provider) = &provider {
+            if region.is_some() {
+                scopes.push((Some(provider.as_str()), region.as_deref()));
             }
+            scopes.push((Some(provider.as_str()), None));
         }
+        scopes.push((None, None));
+
+        scopes.into_iter().find_map(|(provider, region)| {
+            let candidates = || {
+                overrides
+                    .pricing
+                    .iter()
+                    .chain(&self.pricing)
+                    .filter(|(key, _)| key.provider.as_deref() == provider && key.region.as_deref() == region)
+            };
 
-        None
+            // Try exact match first, then prefix match (e.g.,
+            // "claude-3-5-sonnet-20241022" matches "claude-3-5-sonnet"), then
+            // contains match for versioned models
+            let (key, versions) = candidates()
+                .find(|(key, _)| key.model == model_name)
+                .or_else(|| longest_match(candidates(), |model| model_name.starts_with(model)))
+                .or_else(|| longest_match(candidates(), |model| model_name.contains(model)))?;
+
+            version_on(versions, date).map(|pricing| (pricing.version(&key.label()), pricing.clone()))
+        })
     }
 
-    /// Add or update pricing for a model
+    /// Add or update pricing for a model, replacing any earlier versions
     pub fn set_pricing(&mut self, model: String, pricing: ModelPricing) {
-        self.pricing.insert(model, pricing);
+        self.pricing.insert(PricingKey::model(model), vec![pricing]);
+    }
+
+    /// Get the current pricing for a model
+    pub fn get_pricing(&self, model: &str) -> Option<ModelPricing> {
+        self.get_pricing_on(model, chrono::Utc::now().date_naive())
     }
 
-    /// Get pricing for a model
-    pub fn get_pricing(&self, model: &str) -> Option<&ModelPricing> {
-        self.find_pricing(model)
+    /// Get the pricing for a model in effect on `date`
+    pub fn get_pricing_on(&self, model: &str, date: NaiveDate) -> Option<ModelPricing> {
+        self.find_pricing(None, None, model, date).map(|(_, pricing)| pricing)
+    }
+
+    /// Get the current pricing for a model served by `provider`, in
+    /// `region` if given, falling back to the model's general prices
+    pub fn get_hosted_pricing(&self, provider: &str, region: Option<&str>, model: &str) -> Option<ModelPricing> {
+        self.find_pricing(Some(provider), region, model, chrono::Utc::now().date_naive())
+            .map(|(_, pricing)| pricing)
     }
 }
 
+/// The longest model name `matches` accepts, with its prices; earlier
+/// candidates win ties
+fn longest_match<'a>(
+    candidates: impl Iterator<Item = (&'a PricingKey, &'a Vec<ModelPricing>)>,
+    matches: impl Fn(&str) -> bool,
+) -> Option<(&'a PricingKey, &'a Vec<ModelPricing>)> {
+    let mut best: Option<(&PricingKey, &Vec<ModelPricing>)> = None;
+    for (key, versions) in candidates {
+        if matches(&key.model) && best.map_or(true, |(best_key, _)| key.model.len() > best_key.model.len()) {
+            best = Some((key, versions));
+        }
+    }
+    best
+}
+
+/// The version in effect on `date`; dates outside every version's range are
+/// priced at the latest version
+fn version_on(versions: &[ModelPricing], date: NaiveDate) -> Option<&ModelPricing> {
+    versions
+        .iter()
+        .find(|pricing| pricing.is_effective_on(date))
+        .or_else(|| versions.last())
+}
+
+/// Parse a TOML pricing table, rejecting negative or non-finite prices and
+/// versions whose date ranges overlap
+fn parse_pricing(text: &str) -> std::result::Result<HashMap<PricingKey, Vec<ModelPricing>>, String> {
+    let file: PricingFile = toml::from_str(text).map_err(|e| e.to_string())?;
+
+    let mut tables: Vec<(PricingKey, PricingVersions)> = file
+        .models
+        .into_iter()
+        .map(|(model, versions)| (PricingKey::model(model), versions))
+        .collect();
+    for (provider, provider_file) in file.providers {
+        for (model, versions) in provider_file.models {
+            tables.push((PricingKey::hosted(&provider, model), versions));
+        }
+        for (region, region_file) in provider_file.regions {
+            for (model, versions) in region_file.models {
+                let key = PricingKey {
+                    region: Some(region.trim().to_lowercase()),
+                    ..PricingKey::hosted(&provider, model)
+                };
+                tables.push((key, versions));
+            }
+        }
+    }
+
+    let mut models = HashMap::with_capacity(tables.len());
+    for (key, versions) in tables {
+        let path = key.path();
+        let mut versions = match versions {
+            PricingVersions::One(pricing) => vec![pricing],
+            PricingVersions::Many(versions) => versions,
+        };
+        if versions.is_empty() {
+            return Err(format!("{}: must have at least one price", path));
+        }
+
+        for pricing in &versions {
+            let prices = [
+                Some(pricing.input_per_million),
+                Some(pricing.output_per_million),
+                pricing.cached_input_per_million,
+                pricing.per_image,
+                pricing.per_audio_second,
+                pricing.embedding_per_thousand,
+            ];
+            if prices.into_iter().flatten().any(|price| !price.is_finite() || price < 0.0) {
+                return Err(format!("{}: prices must be non-negative numbers", path));
+            }
+            if let (Some(from), Some(until)) = (pricing.effective_from, pricing.effective_until) {
+                if from >= until {
+                    return Err(format!("{}: effective_from must be before effective_until", path));
+                }
+            }
+        }
+
+        // Unset effective_from sorts first, as the earliest version
+        versions.sort_by_key(|pricing| pricing.effective_from);
+        for pair in versions.windows(2) {
+            let overlaps = match (pair[0].effective_until, pair[1].effective_from) {
+                (Some(until), Some(from)) => until > from,
+                _ => true,
+            };
+            if overlaps {
+                return Err(format!(
+                    "{}: versions overlap; each needs effective_until at or before the next one's effective_from",
+                    path
+                ));
+            }
+        }
+
+        if models.insert(key, versions).is_some() {
+            return Err(format!("{}: priced more than once under different provider names", path));
+        }
+    }
+
+    Ok(models)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Utc;
+    use chrono::{TimeZone, Utc};
     use uuid::Uuid;
 
     fn create_test_span(model: &str, tokens_in: i32, tokens_out: i32) -> Span {
@@ -306,6 +937,8 @@ mod tests {
             span_id: "test-span".to_string(),
             trace_id: "test-trace".to_string(),
             parent_span_id: None,
+            session_id: None,
+            user_id: None,
             operation_name: "llm_call".to_string(),
             service_name: "test".to_string(),
             span_kind: crate::models::SpanKind::Internal,
@@ -317,6 +950,7 @@ mod tests {
             model_name: Some(model.to_string()),
             model_provider: Some("anthropic".to_string()),
             tokens_in: Some(tokens_in),
+            tokens_cached_in: None,
             tokens_out: Some(tokens_out),
             tokens_reasoning: None,
             cost_usd: None,
@@ -329,6 +963,7 @@ mod tests {
             attributes: serde_json::json!({}),
             events: vec![],
             links: vec![],
+            priority: crate::models::SpanPriority::Normal,
         }
     }
 
@@ -362,6 +997,110 @@ mod tests {
         assert!((cost - 7.50).abs() < 0.01);
     }
 
+    #[test]
+    fn test_cached_tokens_billed_at_cached_rate() {
+        let calculator = CostCalculator::new();
+        let mut span = create_test_span("claude-sonnet-4-20250514", 1_000_000, 0);
+        span.tokens_cached_in = Some(800_000);
+
+        calculator.calculate(&mut span);
+
+        // 200K uncached at $3/M + 800K cached at $0.30/M = $0.60 + $0.24
+        assert!((span.cost_usd.unwrap() - 0.84).abs() < 0.0001);
+
+        // Models without a cached rate bill cache reads as normal input, and
+        // a cached count above tokens_in is capped
+        let mut span = create_test_span("gpt-4", 1_000_000, 0);
+        span.tokens_cached_in = Some(2_000_000);
+        calculator.calculate(&mut span);
+        let pricing = calculator.get_pricing("gpt-4").unwrap();
+        assert!(pricing.cached_input_per_million.is_none());
+        assert!((span.cost_usd.unwrap() - pricing.input_per_million).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_media_usage_priced_from_attributes() {
+        let calculator = CostCalculator::new();
+
+        let mut span = create_test_span("dall-e-3", 0, 0);
+        MediaUsage {
+            images: Some(3.0),
+            ..MediaUsage::default()
+        }
+        .record(&mut span.attributes);
+        calculator.calculate(&mut span);
+        assert!((span.cost_usd.unwrap() - 0.12).abs() < 1e-9);
+
+        let mut span = create_test_span("text-embedding-3-small", 0, 0);
+        span.attributes = serde_json::json!({ EMBEDDING_TOKENS_ATTRIBUTE: 500_000, AUDIO_SECONDS_ATTRIBUTE: 60 });
+        calculator.calculate(&mut span);
+        // 500K tokens at $0.00002/1K; audio has no rate for this model
+        assert!((span.cost_usd.unwrap() - 0.01).abs() < 1e-9);
+
+        // Non-numeric usage is ignored
+        let mut span = create_test_span("whisper-1", 0, 0);
+        span.attributes = serde_json::json!({ AUDIO_SECONDS_ATTRIBUTE: "60" });
+        calculator.calculate(&mut span);
+        assert_eq!(span.cost_usd, Some(0.0));
+
+        let mut span = create_test_span("whisper-1", 0, 0);
+        span.attributes = serde_json::json!({ AUDIO_SECONDS_ATTRIBUTE: 90.5 });
+        calculator.calculate(&mut span);
+        assert!((span.cost_usd.unwrap() - 0.00905).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_provider_and_region_pricing() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            b"[providers.aws_bedrock.regions.eu-west-1.models.\"anthropic.claude-3-haiku\"]\n\
+              input_per_million = 0.30\noutput_per_million = 1.50\n",
+        )
+        .unwrap();
+        let calculator = CostCalculator::new();
+        calculator
+            .reload(&PricingConfig {
+                source: Some(file.path().to_str().unwrap().to_string()),
+                ..PricingConfig::default()
+            })
+            .await
+            .unwrap();
+
+        let mut span = create_test_span("eu.anthropic.claude-3-haiku-20240307-v1:0", 1_000_000, 0);
+        span.model_provider = Some("aws.bedrock".to_string());
+        span.attributes = serde_json::json!({ REGION_ATTRIBUTE: "EU-WEST-1" });
+        calculator.calculate(&mut span);
+        assert!((span.cost_usd.unwrap() - 0.30).abs() < 1e-9);
+        assert_eq!(
+            span.attributes[PRICING_VERSION_ATTRIBUTE],
+            "bedrock/eu-west-1/anthropic.claude-3-haiku"
+        );
+
+        // Other regions get the provider's prices, other providers the model's
+        let regional = |region| calculator.get_hosted_pricing("bedrock", Some(region), "anthropic.claude-3-haiku");
+        assert_eq!(regional("us-east-1").unwrap().input_per_million, 0.25);
+        assert_eq!(
+            calculator.get_hosted_pricing("azure_openai", None, "gpt-4o-mini").unwrap().input_per_million,
+            0.165
+        );
+        assert_eq!(
+            calculator.get_hosted_pricing("azure", None, "claude-3-opus").unwrap().input_per_million,
+            15.0
+        );
+        assert_eq!(calculator.get_pricing("gpt-4o-mini").unwrap().input_per_million, 0.15);
+    }
+
+    #[test]
+    fn test_parse_pricing_rejects_duplicate_provider_aliases() {
+        let err = parse_pricing(
+            "[providers.azure.models.gpt-4o]\ninput_per_million = 1.0\noutput_per_million = 1.0\n\
+             [providers.azure_openai.models.gpt-4o]\ninput_per_million = 2.0\noutput_per_million = 2.0\n",
+        )
+        .unwrap_err();
+        assert!(err.contains("providers.azure.models.gpt-4o"));
+    }
+
     #[test]
     fn test_unknown_model() {
         let calculator = CostCalculator::new();
@@ -372,4 +1111,86 @@ mod tests {
         // Should not set cost for unknown model
         assert!(span.cost_usd.is_none());
     }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let calculator = CostCalculator::new();
+        let pricing = calculator.get_pricing("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(pricing.input_per_million, 0.15);
+    }
+
+    #[tokio::test]
+    async fn test_reload_merges_over_builtin() {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            b"[models.gpt-4o]\ninput_per_million = 2.0\noutput_per_million = 8.0\n\n\
+              [models.new-model]\ninput_per_million = 1.0\noutput_per_million = 2.0\n",
+        )
+        .unwrap();
+        let config = PricingConfig {
+            source: Some(file.path().to_str().unwrap().to_string()),
+            ..PricingConfig::default()
+        };
+
+        let calculator = CostCalculator::new();
+        let shared = calculator.clone();
+        assert_eq!(calculator.reload(&config).await.unwrap(), 2);
+
+        let mut span = create_test_span("gpt-4o-2024-08-06", 1_000_000, 1_000_000);
+        shared.calculate(&mut span);
+        assert!((span.cost_usd.unwrap() - 10.0).abs() < 0.0001);
+        assert!(shared.get_pricing("new-model").is_some());
+
+        let entries = shared.list();
+        let gpt4o = entries.iter().find(|e| e.model == "gpt-4o").unwrap();
+        assert_eq!(gpt4o.source, config.source.clone().unwrap());
+        let opus = entries.iter().find(|e| e.model == "claude-3-opus").unwrap();
+        assert_eq!(opus.source, BUILTIN_PRICING_SOURCE);
+
+        // Dropping the source goes back to the built-in prices
+        calculator.reload(&PricingConfig::default()).await.unwrap();
+        assert!(shared.get_pricing("new-model").is_none());
+    }
+
+    #[test]
+    fn test_parse_pricing_rejects_negative_prices() {
+        let err = parse_pricing("[models.x]\ninput_per_million = -1.0\noutput_per_million = 1.0\n").unwrap_err();
+        assert!(err.contains("models.x"));
+        assert!(parse_pricing("[models.x]\ninput_per_million = 1.0\n").is_err());
+    }
+
+    #[test]
+    fn test_price_in_effect_when_span_started() {
+        let calculator = CostCalculator::new();
+
+        let mut before = create_test_span("gpt-4o", 1_000_000, 0);
+        before.started_at = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        calculator.calculate(&mut before);
+        assert!((before.cost_usd.unwrap() - 5.0).abs() < 0.0001);
+        assert_eq!(before.attributes[PRICING_VERSION_ATTRIBUTE], "gpt-4o");
+
+        let mut after = create_test_span("gpt-4o", 1_000_000, 0);
+        after.started_at = Utc.with_ymd_and_hms(2024, 10, 2, 0, 0, 0).unwrap();
+        calculator.calculate(&mut after);
+        assert!((after.cost_usd.unwrap() - 2.5).abs() < 0.0001);
+        assert_eq!(after.attributes[PRICING_VERSION_ATTRIBUTE], "gpt-4o@2024-10-02");
+    }
+
+    #[test]
+    fn test_parse_pricing_versions() {
+        let models = parse_pricing(
+            "[[models.m]]\neffective_from = \"2025-03-01\"\ninput_per_million = 1.0\noutput_per_million = 2.0\n\n\
+             [[models.m]]\neffective_until = \"2025-03-01\"\ninput_per_million = 3.0\noutput_per_million = 4.0\n",
+        )
+        .unwrap();
+        let versions = &models[&PricingKey::model("m")];
+        assert_eq!(versions[0].input_per_million, 3.0);
+        assert_eq!(versions[1].version("m"), "m@2025-03-01");
+
+        // The second version starts before the first ends
+        let overlapping = "[[models.m]]\neffective_until = \"2025-03-01\"\ninput_per_million = 1.0\noutput_per_million = 2.0\n\n\
+                           [[models.m]]\neffective_from = \"2025-02-01\"\ninput_per_million = 1.0\noutput_per_million = 2.0\n";
+        assert!(parse_pricing(overlapping).unwrap_err().contains("overlap"));
+    }
 }
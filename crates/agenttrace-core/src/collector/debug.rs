@@ -0,0 +1,151 @@
+//! Temporary debug streaming
+//!
+//! A debug session selects spans by service and/or trace ID pattern for a
+//! limited time. While it is active, the pipeline publishes matching spans
+//! with their full, untruncated payloads to the session's Redis channel.
+//! Sessions expire on their own, after which capture returns to normal.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use parking_lot::RwLock;
+use regex::Regex;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{DebugSession, DebugSessionInput, Span};
+
+/// Default session length in minutes
+const DEFAULT_DURATION_MINUTES: i64 = 10;
+
+/// Longest allowed session in minutes
+const MAX_DURATION_MINUTES: i64 = 60;
+
+/// Maximum number of concurrently active sessions
+const MAX_SESSIONS: usize = 20;
+
+struct ActiveSession {
+    session: DebugSession,
+    trace_id_regex: Option<Regex>,
+}
+
+impl ActiveSession {
+    fn matches(&self, span: &Span) -> bool {
+        if let Some(service) = &self.session.service_name {
+            if &span.service_name != service {
+                return false;
+            }
+        }
+
+        if let Some(re) = &self.trace_id_regex {
+            if !re.is_match(&span.trace_id) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Registry of active debug sessions
+#[derive(Clone, Default)]
+pub struct DebugSessions {
+    sessions: Arc<RwLock<Vec<ActiveSession>>>,
+}
+
+impl DebugSessions {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a debug session
+    pub fn start(&self, input: DebugSessionInput) -> Result<DebugSession> {
+        if input.service_name.is_none() && input.trace_id_pattern.is_none() {
+            return Err(Error::validation(
+                "A debug session needs a service_name or trace_id_pattern",
+            ));
+        }
+
+        let minutes = input.duration_minutes.unwrap_or(DEFAULT_DURATION_MINUTES);
+        if !(1..=MAX_DURATION_MINUTES).contains(&minutes) {
+            return Err(Error::validation(format!(
+                "duration_minutes must be between 1 and {}",
+                MAX_DURATION_MINUTES
+            )));
+        }
+
+        let trace_id_regex = input
+            .trace_id_pattern
+            .as_deref()
+            .map(glob_to_regex)
+            .transpose()?;
+
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let session = DebugSession {
+            id,
+            service_name: input.service_name,
+            trace_id_pattern: input.trace_id_pattern,
+            channel: format!("agenttrace:debug:{}", id),
+            created_at: now,
+            expires_at: now + Duration::minutes(minutes),
+        };
+
+        let mut sessions = self.sessions.write();
+        sessions.retain(|s| s.session.expires_at > now);
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(Error::validation(format!(
+                "Too many active debug sessions (max {})",
+                MAX_SESSIONS
+            )));
+        }
+        sessions.push(ActiveSession {
+            session: session.clone(),
+            trace_id_regex,
+        });
+
+        Ok(session)
+    }
+
+    /// End a session early, returning whether it was active
+    pub fn stop(&self, id: Uuid) -> bool {
+        let mut sessions = self.sessions.write();
+        let before = sessions.len();
+        sessions.retain(|s| s.session.id != id);
+        sessions.len() != before
+    }
+
+    /// List active sessions
+    pub fn list(&self) -> Vec<DebugSession> {
+        let now = Utc::now();
+        self.sessions
+            .read()
+            .iter()
+            .filter(|s| s.session.expires_at > now)
+            .map(|s| s.session.clone())
+            .collect()
+    }
+
+    /// Channels of the active sessions that match a span
+    pub fn matching_channels(&self, span: &Span) -> Vec<String> {
+        let sessions = self.sessions.read();
+        if sessions.is_empty() {
+            return Vec::new();
+        }
+
+        let now = Utc::now();
+        sessions
+            .iter()
+            .filter(|s| s.session.expires_at > now && s.matches(span))
+            .map(|s| s.session.channel.clone())
+            .collect()
+    }
+}
+
+/// Convert a `*` wildcard pattern into an anchored regex
+fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let escaped = regex::escape(pattern).replace(r"\*", ".*");
+    Regex::new(&format!("^{}$", escaped))
+        .map_err(|e| Error::validation(format!("Invalid trace_id_pattern: {}", e)))
+}
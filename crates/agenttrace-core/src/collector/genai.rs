@@ -0,0 +1,385 @@
+//! OpenTelemetry GenAI and Vercel AI SDK attribute conventions
+//!
+//! OpenTelemetry instrumentation describes LLM and tool calls in span
+//! attributes rather than AgentTrace's span fields. Recognized attributes
+//! fill in the fields a span left empty; fields set directly always win and
+//! the attributes themselves are kept.
+//!
+//! | Span field | OpenTelemetry GenAI | Vercel AI SDK |
+//! |------------|---------------------|---------------|
+//! | `model_name` | `gen_ai.response.model`, `gen_ai.request.model` | `ai.response.model`, `ai.model.id` |
+//! | `model_provider` | `gen_ai.provider.name`, `gen_ai.system` | `ai.model.provider` |
+//! | `tokens_in` | `gen_ai.usage.input_tokens`, `gen_ai.usage.prompt_tokens` | `ai.usage.inputTokens`, `ai.usage.promptTokens` |
+//! | `tokens_out` | `gen_ai.usage.output_tokens`, `gen_ai.usage.completion_tokens` | `ai.usage.outputTokens`, `ai.usage.completionTokens` |
+//! | `tokens_cached_in` | `gen_ai.usage.cache_read.input_tokens` | `ai.usage.cachedInputTokens` |
+//! | `tokens_reasoning` | `gen_ai.usage.reasoning_tokens` | `ai.usage.reasoningTokens` |
+//! | `prompt_preview` | `gen_ai.input.messages`, `gen_ai.prompt`, `gen_ai.prompt.<n>.*` | `ai.prompt.messages`, `ai.prompt` |
+//! | `completion_preview` | `gen_ai.output.messages`, `gen_ai.completion`, `gen_ai.completion.<n>.*` | `ai.response.text`, `ai.response.object`, `ai.response.toolCalls` |
+//! | `tool_name` | `gen_ai.tool.name` | `ai.toolCall.name` |
+//! | `tool_input` | `gen_ai.tool.call.arguments` | `ai.toolCall.args`, `ai.toolCall.input` |
+//! | `tool_output` | `gen_ai.tool.call.result` | `ai.toolCall.result`, `ai.toolCall.output` |
+//! | `session_id` | `gen_ai.conversation.id` | `ai.telemetry.metadata.sessionId` |
+//! | `user_id` | | `ai.telemetry.metadata.userId` |
+//!
+//! The Vercel AI SDK wraps each provider call (`ai.generateText.doGenerate`)
+//! in a span for the whole operation (`ai.generateText`) that repeats the
+//! model and the total usage of its steps. Model and tokens are only taken
+//! from the provider call spans, so calls and tokens aren't counted twice.
+
+use serde_json::{Map, Value};
+
+use crate::models::{Span, SpanKind};
+
+/// Vercel AI SDK operation suffixes of spans around a single provider call
+const VERCEL_PROVIDER_CALLS: &[&str] = &[".doGenerate", ".doStream", ".doEmbed"];
+
+/// Fill empty span fields from GenAI attribute conventions
+pub fn apply_genai_conventions(span: &mut Span) {
+    let Value::Object(attributes) = &span.attributes else {
+        return;
+    };
+    let attrs = Attributes(attributes);
+
+    let filled = Filled {
+        model_name: attrs.string(&[
+            "gen_ai.response.model",
+            "gen_ai.request.model",
+            "ai.response.model",
+            "ai.model.id",
+        ]),
+        model_provider: attrs
+            .string(&["gen_ai.provider.name", "gen_ai.system"])
+            // e.g. `openai.chat`, `anthropic.messages`
+            .or_else(|| {
+                attrs
+                    .string(&["ai.model.provider"])
+                    .map(|p| p.split('.').next().unwrap_or_default().to_string())
+            }),
+        tokens_in: attrs.count(&[
+            "gen_ai.usage.input_tokens",
+            "gen_ai.usage.prompt_tokens",
+            "ai.usage.inputTokens",
+            "ai.usage.promptTokens",
+        ]),
+        tokens_out: attrs.count(&[
+            "gen_ai.usage.output_tokens",
+            "gen_ai.usage.completion_tokens",
+            "ai.usage.outputTokens",
+            "ai.usage.completionTokens",
+        ]),
+        tokens_cached_in: attrs.count(&["gen_ai.usage.cache_read.input_tokens", "ai.usage.cachedInputTokens"]),
+        tokens_reasoning: attrs.count(&["gen_ai.usage.reasoning_tokens", "ai.usage.reasoningTokens"]),
+        prompt: prompt_text(&attrs),
+        completion: completion_text(&attrs),
+        tool_name: attrs.string(&["gen_ai.tool.name", "ai.toolCall.name"]),
+        tool_input: attrs.json(&["gen_ai.tool.call.arguments", "ai.toolCall.args", "ai.toolCall.input"]),
+        tool_output: attrs.json(&["gen_ai.tool.call.result", "ai.toolCall.result", "ai.toolCall.output"]),
+        session_id: attrs.string(&[
+            "gen_ai.conversation.id",
+            "ai.telemetry.metadata.sessionId",
+            "ai.telemetry.metadata.session_id",
+        ]),
+        user_id: attrs.string(&["ai.telemetry.metadata.userId", "ai.telemetry.metadata.user_id"]),
+        wrapper: attrs.string(&["ai.operationId"]).is_some_and(|operation| {
+            operation != "ai.toolCall" && !VERCEL_PROVIDER_CALLS.iter().any(|suffix| operation.ends_with(suffix))
+        }),
+    };
+    filled.apply(span);
+}
+
+/// Field values read from a span's attributes
+struct Filled {
+    model_name: Option<String>,
+    model_provider: Option<String>,
+    tokens_in: Option<i32>,
+    tokens_out: Option<i32>,
+    tokens_cached_in: Option<i32>,
+    tokens_reasoning: Option<i32>,
+    prompt: Option<String>,
+    completion: Option<String>,
+    tool_name: Option<String>,
+    tool_input: Option<Value>,
+    tool_output: Option<Value>,
+    session_id: Option<String>,
+    user_id: Option<String>,
+    /// A Vercel AI SDK span around one or more provider calls
+    wrapper: bool,
+}
+
+impl Filled {
+    fn apply(self, span: &mut Span) {
+        fn fill<T>(field: &mut Option<T>, value: Option<T>) {
+            if field.is_none() {
+                *field = value;
+            }
+        }
+
+        if !self.wrapper {
+            let was_llm_call = span.model_name.is_some();
+            fill(&mut span.model_name, self.model_name);
+            fill(&mut span.model_provider, self.model_provider);
+            fill(&mut span.tokens_in, self.tokens_in);
+            fill(&mut span.tokens_out, self.tokens_out);
+            fill(&mut span.tokens_cached_in, self.tokens_cached_in);
+            fill(&mut span.tokens_reasoning, self.tokens_reasoning);
+            if !was_llm_call && span.model_name.is_some() && span.span_kind == SpanKind::Internal {
+                span.span_kind = SpanKind::Client;
+            }
+        }
+        fill(&mut span.prompt_preview, self.prompt);
+        fill(&mut span.completion_preview, self.completion);
+        fill(&mut span.tool_name, self.tool_name);
+        fill(&mut span.tool_input, self.tool_input);
+        fill(&mut span.tool_output, self.tool_output);
+        fill(&mut span.session_id, self.session_id);
+        fill(&mut span.user_id, self.user_id);
+    }
+}
+
+/// Lookups in a span's flat, dotted attribute keys
+struct Attributes<'a>(&'a Map<String, Value>);
+
+impl Attributes<'_> {
+    /// The first of `keys` that is set to a non-empty value
+    fn get(&self, keys: &[&str]) -> Option<&Value> {
+        keys.iter()
+            .filter_map(|key| self.0.get(*key))
+            .find(|value| match value {
+                Value::Null => false,
+                Value::String(s) => !s.is_empty(),
+                _ => true,
+            })
+    }
+
+    fn string(&self, keys: &[&str]) -> Option<String> {
+        self.get(keys).map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+    }
+
+    /// Counts may arrive as numbers or as numeric strings
+    fn count(&self, keys: &[&str]) -> Option<i32> {
+        keys.iter().filter_map(|key| self.0.get(*key)).find_map(|value| {
+            let n = match value {
+                Value::Number(n) => n.as_i64()?,
+                Value::String(s) => s.trim().parse().ok()?,
+                _ => return None,
+            };
+            i32::try_from(n).ok()
+        })
+    }
+
+    /// Structured values, which exporters often send as JSON strings
+    fn json(&self, keys: &[&str]) -> Option<Value> {
+        self.get(keys).map(|value| match value {
+            Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| value.clone()),
+            other => other.clone(),
+        })
+    }
+
+    /// Indexed messages such as `gen_ai.prompt.0.role` and
+    /// `gen_ai.prompt.0.content`, one per line
+    fn indexed_messages(&self, prefix: &str) -> Option<String> {
+        let lines: Vec<String> = (0..)
+            .map_while(|i| {
+                let content = self.string(&[format!("{}.{}.content", prefix, i).as_str()]);
+                let role = self.string(&[format!("{}.{}.role", prefix, i).as_str()]);
+                (content.is_some() || role.is_some())
+                    .then(|| format!("{}: {}", role.as_deref().unwrap_or("user"), content.unwrap_or_default()))
+            })
+            .collect();
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+fn prompt_text(attrs: &Attributes<'_>) -> Option<String> {
+    if let Some(messages) = attrs.json(&["gen_ai.input.messages", "ai.prompt.messages"]) {
+        return messages_text(&messages);
+    }
+    if let Some(prompt) = attrs.json(&["gen_ai.prompt"]) {
+        return messages_text(&prompt);
+    }
+    if let Some(prompt) = attrs.indexed_messages("gen_ai.prompt") {
+        return Some(prompt);
+    }
+    // `{"system": ..., "prompt": ...}` or `{"system": ..., "messages": [...]}`
+    let prompt = attrs.json(&["ai.prompt"])?;
+    let mut lines = Vec::new();
+    if let Some(system) = prompt["system"].as_str() {
+        lines.push(format!("system: {}", system));
+    }
+    match (&prompt["messages"], &prompt["prompt"]) {
+        (messages @ Value::Array(_), _) => lines.extend(messages_text(messages)),
+        (_, Value::String(text)) => lines.push(format!("user: {}", text)),
+        _ if lines.is_empty() => return messages_text(&prompt),
+        _ => {}
+    }
+    Some(lines.join("\n"))
+}
+
+fn completion_text(attrs: &Attributes<'_>) -> Option<String> {
+    if let Some(messages) = attrs.json(&["gen_ai.output.messages"]) {
+        return messages_text(&messages);
+    }
+    if let Some(completion) = attrs.json(&["gen_ai.completion"]) {
+        return messages_text(&completion);
+    }
+    if let Some(completion) = attrs.indexed_messages("gen_ai.completion") {
+        return Some(completion);
+    }
+    attrs.string(&["ai.response.text", "ai.response.object", "ai.response.toolCalls"])
+}
+
+/// Messages rendered one per line as `role: text`; text that isn't a list
+/// of messages is used as it is
+fn messages_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::Array(messages) => messages
+            .iter()
+            .map(|message| match message {
+                Value::String(text) => text.clone(),
+                _ => format!(
+                    "{}: {}",
+                    message["role"].as_str().unwrap_or("user"),
+                    content_text(message)
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::String(text) => text.clone(),
+        Value::Null => return None,
+        other => other.to_string(),
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Text of a message's content: a string, content parts (`text` or
+/// `content` per part), or the GenAI `parts` list
+fn content_text(message: &Value) -> String {
+    let parts = match (&message["content"], &message["parts"]) {
+        (Value::String(text), _) => return text.clone(),
+        (Value::Array(parts), _) | (_, Value::Array(parts)) => parts,
+        _ => return String::new(),
+    };
+    parts
+        .iter()
+        .filter_map(|part| part["text"].as_str().or_else(|| part["content"].as_str()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn span_with(attributes: Value) -> Span {
+        serde_json::from_value(json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "span_id": "a1",
+            "trace_id": "t1",
+            "parent_span_id": null,
+            "operation_name": "chat gpt-4o",
+            "service_name": "agent",
+            "span_kind": "internal",
+            "started_at": "2026-01-01T00:00:00Z",
+            "ended_at": null,
+            "duration_ms": null,
+            "status": "ok",
+            "status_message": null,
+            "model_name": null,
+            "model_provider": null,
+            "tokens_in": null,
+            "tokens_cached_in": null,
+            "tokens_out": null,
+            "tokens_reasoning": null,
+            "cost_usd": null,
+            "tool_name": null,
+            "tool_input": null,
+            "tool_output": null,
+            "tool_duration_ms": null,
+            "prompt_preview": null,
+            "completion_preview": null,
+            "attributes": attributes,
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_otel_genai_attributes() {
+        let mut span = span_with(json!({
+            "gen_ai.system": "openai",
+            "gen_ai.request.model": "gpt-4o",
+            "gen_ai.response.model": "gpt-4o-2024-08-06",
+            "gen_ai.usage.input_tokens": 120,
+            "gen_ai.usage.output_tokens": "30",
+            "gen_ai.prompt.0.role": "system",
+            "gen_ai.prompt.0.content": "Be brief.",
+            "gen_ai.prompt.1.role": "user",
+            "gen_ai.prompt.1.content": "Hi",
+            "gen_ai.output.messages": "[{\"role\":\"assistant\",\"parts\":[{\"type\":\"text\",\"content\":\"Hello!\"}]}]"
+        }));
+        span.tokens_in = Some(125);
+
+        apply_genai_conventions(&mut span);
+        assert_eq!(span.model_name.as_deref(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(span.model_provider.as_deref(), Some("openai"));
+        // Fields set directly win
+        assert_eq!(span.tokens_in, Some(125));
+        assert_eq!(span.tokens_out, Some(30));
+        assert_eq!(span.span_kind, SpanKind::Client);
+        assert_eq!(span.prompt_preview.as_deref(), Some("system: Be brief.\nuser: Hi"));
+        assert_eq!(span.completion_preview.as_deref(), Some("assistant: Hello!"));
+        assert!(span.attributes.get("gen_ai.request.model").is_some());
+    }
+
+    #[test]
+    fn test_vercel_ai_sdk_attributes() {
+        let mut call = span_with(json!({
+            "ai.operationId": "ai.generateText.doGenerate",
+            "ai.model.id": "claude-3-5-sonnet-20241022",
+            "ai.model.provider": "anthropic.messages",
+            "ai.usage.promptTokens": 50,
+            "ai.usage.completionTokens": 8,
+            "ai.prompt.messages": "[{\"role\":\"user\",\"content\":[{\"type\":\"text\",\"text\":\"Weather?\"}]}]",
+            "ai.response.text": "Sunny.",
+            "ai.telemetry.metadata.sessionId": "s-9"
+        }));
+        apply_genai_conventions(&mut call);
+        assert_eq!(call.model_name.as_deref(), Some("claude-3-5-sonnet-20241022"));
+        assert_eq!(call.model_provider.as_deref(), Some("anthropic"));
+        assert_eq!((call.tokens_in, call.tokens_out), (Some(50), Some(8)));
+        assert_eq!(call.prompt_preview.as_deref(), Some("user: Weather?"));
+        assert_eq!(call.completion_preview.as_deref(), Some("Sunny."));
+        assert_eq!(call.session_id.as_deref(), Some("s-9"));
+
+        // The operation span repeats the step totals
+        let mut wrapper = span_with(json!({
+            "ai.operationId": "ai.generateText",
+            "ai.model.id": "claude-3-5-sonnet-20241022",
+            "ai.usage.promptTokens": 50,
+            "ai.prompt": "{\"system\":\"Be brief.\",\"prompt\":\"Weather?\"}"
+        }));
+        apply_genai_conventions(&mut wrapper);
+        assert_eq!(wrapper.model_name, None);
+        assert_eq!(wrapper.tokens_in, None);
+        assert_eq!(
+            wrapper.prompt_preview.as_deref(),
+            Some("system: Be brief.\nuser: Weather?")
+        );
+
+        let mut tool = span_with(json!({
+            "ai.operationId": "ai.toolCall",
+            "ai.toolCall.name": "weather",
+            "ai.toolCall.args": "{\"city\":\"Oslo\"}",
+            "ai.toolCall.result": "\"sunny\""
+        }));
+        apply_genai_conventions(&mut tool);
+        assert_eq!(tool.tool_name.as_deref(), Some("weather"));
+        assert_eq!(tool.tool_input, Some(json!({"city": "Oslo"})));
+        assert_eq!(tool.tool_output, Some(json!("sunny")));
+    }
+}
@@ -10,9 +10,9 @@ use tracing::{debug, info};
 use uuid::Uuid;
 
 use crate::error::Result;
-use crate::models::{Span, SpanEvent, SpanKind, SpanStatus};
+use crate::models::{Span, SpanEvent, SpanKind, SpanPriority, SpanStatus};
 
-use super::Pipeline;
+use super::{MediaUsage, Pipeline};
 
 /// gRPC server for the collector
 pub struct GrpcServer {
@@ -57,24 +57,34 @@ pub struct SendSpanRequest {
     pub span_id: String,
     pub trace_id: String,
     pub parent_span_id: Option<String>,
+    pub session_id: Option<String>,
+    pub user_id: Option<String>,
     pub operation_name: String,
     pub service_name: String,
     pub start_time_unix_nano: i64,
     pub end_time_unix_nano: Option<i64>,
+    /// 0 = unset, 1 = ok, 2 = error, 3 = in progress (a later request for
+    /// the same span completes it)
     pub status: i32,
     pub status_message: Option<String>,
     pub model_name: Option<String>,
     pub model_provider: Option<String>,
     pub tokens_in: Option<i32>,
+    pub tokens_cached_in: Option<i32>,
     pub tokens_out: Option<i32>,
     pub tokens_reasoning: Option<i32>,
+    pub images: Option<f64>,
+    pub audio_seconds: Option<f64>,
+    pub embedding_tokens: Option<f64>,
     pub tool_name: Option<String>,
     pub tool_input: Option<String>,
     pub tool_output: Option<String>,
+    pub tool_duration_ms: Option<f64>,
     pub prompt_preview: Option<String>,
     pub completion_preview: Option<String>,
     pub attributes: Option<String>, // JSON string
     pub events: Vec<SpanEventProto>,
+    pub priority: Option<String>, // "high", "normal" or "low"
 }
 
 #[derive(Debug, Clone)]
@@ -125,14 +135,21 @@ impl CollectorServiceImpl {
             0 => SpanStatus::Unset,
             1 => SpanStatus::Ok,
             2 => SpanStatus::Error,
+            3 => SpanStatus::InProgress,
             _ => SpanStatus::Unset,
         };
 
-        let attributes: serde_json::Value = req
+        let mut attributes: serde_json::Value = req
             .attributes
             .as_ref()
             .and_then(|s| serde_json::from_str(s).ok())
             .unwrap_or_else(|| serde_json::json!({}));
+        MediaUsage {
+            images: req.images,
+            audio_seconds: req.audio_seconds,
+            embedding_tokens: req.embedding_tokens,
+        }
+        .record(&mut attributes);
 
         let tool_input: Option<serde_json::Value> = req
             .tool_input
@@ -162,6 +179,8 @@ impl CollectorServiceImpl {
             span_id: req.span_id,
             trace_id: req.trace_id,
             parent_span_id: req.parent_span_id,
+            session_id: req.session_id,
+            user_id: req.user_id,
             operation_name: req.operation_name,
             service_name: req.service_name,
             span_kind: SpanKind::Internal,
@@ -173,18 +192,24 @@ impl CollectorServiceImpl {
             model_name: req.model_name,
             model_provider: req.model_provider,
             tokens_in: req.tokens_in,
+            tokens_cached_in: req.tokens_cached_in,
             tokens_out: req.tokens_out,
             tokens_reasoning: req.tokens_reasoning,
             cost_usd: None, // Will be calculated by pipeline
             tool_name: req.tool_name,
             tool_input,
             tool_output,
-            tool_duration_ms: None,
+            tool_duration_ms: req.tool_duration_ms,
             prompt_preview: req.prompt_preview,
             completion_preview: req.completion_preview,
             attributes,
             events,
             links: vec![],
+            priority: req
+                .priority
+                .as_deref()
+                .and_then(SpanPriority::parse)
+                .unwrap_or_default(),
         }
     }
 }
@@ -228,6 +253,9 @@ impl Collector for CollectorServiceImpl {
                 success: true,
                 span_id,
             })),
+            Err(crate::error::Error::RateLimit) => {
+                Err(Status::resource_exhausted("Collector overloaded; low-priority span dropped"))
+            }
             Err(e) => {
                 tracing::error!("Failed to submit span: {}", e);
                 Err(Status::internal(format!("Failed to submit span: {}", e)))
@@ -1,30 +1,60 @@
 //! Collector module - High-performance telemetry ingestion
 //!
 //! The collector receives spans via gRPC, HTTP, and UDP, processes them through
-//! a pipeline, and stores them in TimescaleDB while streaming to Redis.
+//! a pipeline, and stores them in TimescaleDB while streaming to Redis (or in
+//! SQLite with in-process streaming in local/dev mode).
 
 mod cost;
+mod debug;
+mod genai;
 mod grpc;
 mod pipeline;
+mod schema;
 
-pub use cost::CostCalculator;
+pub use cost::{
+    canonical_provider, CostCalculator, MediaUsage, ModelPricing, PricingEntry, AUDIO_SECONDS_ATTRIBUTE,
+    BUILTIN_PRICING_SOURCE, EMBEDDING_TOKENS_ATTRIBUTE, IMAGES_ATTRIBUTE, PRICING_VERSION_ATTRIBUTE, REGION_ATTRIBUTE,
+};
+pub use debug::DebugSessions;
+pub use genai::apply_genai_conventions;
 pub use grpc::GrpcServer;
-pub use pipeline::{Pipeline, PipelineConfig};
+pub use pipeline::{FlushRecord, Pipeline, PipelineConfig, PipelineStats};
+pub use schema::{validate_attributes, SchemaRegistry};
 
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{info, error, warn};
 
+use crate::alerting::{AlertEvaluator, AlertRepository, NotificationRetrier, NotificationSender, RetryPolicy};
+use crate::annotations::{AnnotationRepository, ProviderStatusPoller};
 use crate::api::HttpServer;
+use crate::archive::Archiver;
+use crate::audit::AuditRepository;
+use crate::budgets::{BudgetEvaluator, BudgetRepository};
 use crate::config::Config;
-use crate::db::{Database, SpanRepository};
+use crate::db::{Database, Storage};
 use crate::error::Result;
+use crate::jobs::QueryJobs;
 use crate::models::Span;
+use crate::prometheus::RemoteWriter;
+use crate::slos::{SloEvaluator, SloRepository};
+use crate::subscriptions::SubscriptionRepository;
+use crate::views::ViewRepository;
+
+/// How often spans past their priority tier's retention, and summaries of
+/// traces with no spans left, are pruned
+const PRIORITY_PRUNE_INTERVAL_SECS: u64 = 3600;
+
+/// How often full span payloads past `payloads.retention_days` are deleted
+const PAYLOAD_PRUNE_INTERVAL_SECS: u64 = 3600;
+
+/// How often streaming spans left `in_progress` past their TTL are closed
+const IN_PROGRESS_SWEEP_INTERVAL_SECS: u64 = 600;
 
 /// The main collector service
 pub struct Collector {
     config: Config,
-    db: Database,
+    storage: Storage,
     pipeline: Arc<Pipeline>,
     shutdown_tx: Option<mpsc::Sender<()>>,
 }
@@ -32,20 +62,28 @@ pub struct Collector {
 impl Collector {
     /// Create a new collector instance
     pub async fn new(config: Config) -> Result<Self> {
-        let db = Database::new(&config).await?;
+        let storage = Storage::new(&config).await?;
 
         let pipeline_config = PipelineConfig {
             batch_size: config.collector.batch_size,
             batch_timeout_ms: config.collector.batch_timeout_ms,
             enable_cost_calculation: true,
             enable_redis_streaming: true,
+            guardrail_trace_ttl_secs: config.alerting.guardrail_trace_ttl_seconds,
+            alert_public_url: config.alerting.public_url.clone(),
+            alert_retry_policy: RetryPolicy::new(&config.alerting.delivery),
+            pricing: config.pricing.clone(),
+            payloads: config.payloads.clone(),
+            prometheus: config.prometheus.clone(),
+            forwarding: config.forwarding.clone(),
+            ..PipelineConfig::default()
         };
 
-        let pipeline = Arc::new(Pipeline::new(pipeline_config, db.clone()));
+        let pipeline = Arc::new(Pipeline::new(pipeline_config, &storage));
 
         Ok(Self {
             config,
-            db,
+            storage,
             pipeline,
             shutdown_tx: None,
         })
@@ -56,9 +94,246 @@ impl Collector {
         info!("Starting AgentTrace collector...");
 
         // Health check databases
-        self.db.health_check().await?;
+        self.storage.health_check().await?;
         info!("Database connections healthy");
 
+        if let Some(db) = &self.storage.database {
+            // Apply configured data retention
+            if self.config.retention.apply_on_startup {
+                match db.postgres.apply_retention(&self.config.retention).await {
+                    Ok(0) => {}
+                    Ok(changed) => info!("Updated {} retention policies", changed),
+                    Err(e) => warn!("Failed to apply retention policies: {}", e),
+                }
+            }
+
+            // Apply configured chunk compression
+            if self.config.compression.apply_on_startup {
+                match db.apply_compression_policy(&self.config.compression).await {
+                    Ok(0) => {}
+                    Ok(changed) => info!("Updated {} compression policies", changed),
+                    Err(e) => warn!("Failed to apply compression policies: {}", e),
+                }
+            }
+        }
+
+        // Prune spans in shorter priority tiers than their chunks, and the
+        // summaries, sessions and feedback of traces whose spans are gone, and
+        // old webhook deliveries
+        let prune_handle = match &self.storage.database {
+            Some(db) => {
+                let postgres = db.postgres.clone();
+                let subscriptions = SubscriptionRepository::new(db.postgres.pool().clone());
+                let retention = self.config.retention.clone();
+                Some(tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                        PRIORITY_PRUNE_INTERVAL_SECS,
+                    ));
+                    loop {
+                        interval.tick().await;
+                        match postgres.prune_spans_by_priority(&retention).await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("Pruned {} spans past their priority retention", deleted),
+                            Err(e) => warn!("Failed to prune spans by priority: {}", e),
+                        }
+                        match postgres.prune_trace_summaries(&retention).await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("Pruned {} stale trace summaries", deleted),
+                            Err(e) => warn!("Failed to prune trace summaries: {}", e),
+                        }
+                        match postgres.prune_feedback(&retention).await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("Pruned {} stale trace feedback entries", deleted),
+                            Err(e) => warn!("Failed to prune trace feedback: {}", e),
+                        }
+                        match postgres.prune_sessions().await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("Pruned {} stale sessions", deleted),
+                            Err(e) => warn!("Failed to prune sessions: {}", e),
+                        }
+                        match subscriptions.prune_deliveries().await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("Pruned {} old webhook deliveries", deleted),
+                            Err(e) => warn!("Failed to prune webhook deliveries: {}", e),
+                        }
+                    }
+                }))
+            }
+            _ => None,
+        };
+
+        // Expire full span payloads on their own retention
+        let payload_handle = self.pipeline.payloads().cloned().map(|payloads| {
+            tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(std::time::Duration::from_secs(PAYLOAD_PRUNE_INTERVAL_SECS));
+                loop {
+                    interval.tick().await;
+                    match payloads.prune().await {
+                        Ok(0) => {}
+                        Ok(deleted) => info!("Pruned {} expired span payloads", deleted),
+                        Err(e) => warn!("Failed to prune span payloads: {}", e),
+                    }
+                }
+            })
+        });
+
+        // Close streaming spans whose final update never came, so they stop
+        // waiting and are counted
+        let in_progress_handle = {
+            let spans = self.storage.spans.clone();
+            tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(std::time::Duration::from_secs(IN_PROGRESS_SWEEP_INTERVAL_SECS));
+                loop {
+                    interval.tick().await;
+                    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(pipeline::IN_PROGRESS_TTL_SECS);
+                    match spans.close_stale_in_progress(cutoff).await {
+                        Ok(0) => {}
+                        Ok(closed) => info!("Closed {} stale in-progress spans", closed),
+                        Err(e) => warn!("Failed to close stale in-progress spans: {}", e),
+                    }
+                }
+            })
+        };
+
+        // Export old spans to cold storage
+        let archive_handle = match &self.storage.database {
+            Some(db) if self.config.archive.enabled => {
+                match Archiver::new(&self.config.archive, &db.postgres) {
+                    Ok(archiver) => {
+                        let period = std::time::Duration::from_secs(self.config.archive.interval_secs.max(60));
+                        Some(tokio::spawn(async move {
+                            let mut interval = tokio::time::interval(period);
+                            loop {
+                                interval.tick().await;
+                                match archiver.archive_pending().await {
+                                    Ok(written) if written.is_empty() => {}
+                                    Ok(written) => info!("Archived {} span partitions", written.len()),
+                                    Err(e) => warn!("Failed to archive spans: {}", e),
+                                }
+                            }
+                        }))
+                    }
+                    Err(e) => {
+                        warn!("Span archival disabled: {}", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // Push span metrics to a Prometheus remote-write endpoint
+        let remote_write_handle =
+            RemoteWriter::new(&self.config.prometheus, self.pipeline.span_metrics().clone()).map(|writer| {
+                tokio::spawn(async move {
+                    writer.start().await;
+                })
+            });
+
+        // Record upstream provider incidents as annotations
+        let status_handle = match &self.storage.database {
+            Some(db) if self.config.provider_status.enabled => {
+                let poller = ProviderStatusPoller::new(
+                    &self.config.provider_status,
+                    AnnotationRepository::new(db.postgres.pool().clone()),
+                );
+                let period = std::time::Duration::from_secs(self.config.provider_status.interval_secs.max(30));
+                Some(tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(period);
+                    loop {
+                        interval.tick().await;
+                        if let Err(e) = poller.poll().await {
+                            warn!("Failed to record provider incidents: {}", e);
+                        }
+                    }
+                }))
+            }
+            _ => None,
+        };
+
+        // Evaluate alert rules and send notifications
+        let alert_repo = self
+            .storage
+            .database
+            .as_ref()
+            .map(|db| AlertRepository::new(db.postgres.pool().clone()));
+        let alert_evaluator = match (&alert_repo, &self.storage.database) {
+            (Some(repo), Some(db)) => Some(Arc::new(
+                AlertEvaluator::with_config(repo.clone(), self.storage.spans.clone(), &self.config.alerting)
+                    .with_annotations(AnnotationRepository::new(db.postgres.pool().clone())),
+            )),
+            _ => None,
+        };
+        let alert_handle = alert_evaluator.clone().map(|evaluator| {
+            tokio::spawn(async move {
+                evaluator.start().await;
+            })
+        });
+
+        // SLOs are counted from spans, so their windows can't outlast them
+        let slo_repo = self
+            .storage
+            .database
+            .as_ref()
+            .map(|db| {
+                SloRepository::new(db.postgres.pool().clone())
+                    .with_span_retention(self.config.retention.spans_days)
+            });
+
+        let budget_repo = self
+            .storage
+            .database
+            .as_ref()
+            .map(|db| BudgetRepository::new(db.postgres.pool().clone()));
+
+        // Retry failed alert, SLO and budget notifications and report channels that keep failing
+        let retry_handle = alert_repo.clone().map(|repo| {
+            let mut retrier = NotificationRetrier::new(repo, &self.config.alerting.delivery).with_notifier(
+                NotificationSender::new().with_public_url(self.config.alerting.public_url.clone()),
+            );
+            if let Some(slos) = &slo_repo {
+                retrier = retrier.with_slos(slos.clone());
+            }
+            if let Some(budgets) = &budget_repo {
+                retrier = retrier.with_budgets(budgets.clone());
+            }
+            tokio::spawn(async move {
+                retrier.start().await;
+            })
+        });
+
+        // Check spend budgets and notify crossed thresholds
+        let budget_handle = match (&budget_repo, &alert_repo) {
+            (Some(repo), Some(alert_repo)) if self.config.budgets.enabled => {
+                let evaluator = BudgetEvaluator::new(repo.clone(), &self.config.budgets)
+                    .with_notifier(
+                        NotificationSender::new().with_public_url(self.config.alerting.public_url.clone()),
+                    )
+                    .with_retries(alert_repo.clone(), RetryPolicy::new(&self.config.alerting.delivery));
+                Some(tokio::spawn(async move {
+                    evaluator.start().await;
+                }))
+            }
+            _ => None,
+        };
+
+        // Check SLO burn rates and notify alerts as they start firing
+        let slo_handle = match (&slo_repo, &alert_repo) {
+            (Some(repo), Some(alert_repo)) if self.config.slos.enabled => {
+                let evaluator = SloEvaluator::new(repo.clone(), &self.config.slos)
+                    .with_notifier(
+                        NotificationSender::new().with_public_url(self.config.alerting.public_url.clone()),
+                    )
+                    .with_retries(alert_repo.clone(), RetryPolicy::new(&self.config.alerting.delivery));
+                Some(tokio::spawn(async move {
+                    evaluator.start().await;
+                }))
+            }
+            _ => None,
+        };
+
         // Create shutdown channel
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
         self.shutdown_tx = Some(shutdown_tx);
@@ -69,11 +344,35 @@ impl Collector {
             pipeline.start().await;
         });
 
+        // Start the query job workers
+        let jobs = Arc::new(QueryJobs::new(self.storage.spans.clone(), &self.config.jobs));
+        let job_handles = jobs.start();
+
         // Start HTTP server
         let http_addr = format!("{}:{}", self.config.server.host, self.config.server.http_port);
-        let span_repo = SpanRepository::new(&self.db.postgres);
-        let redis_pool = Some(self.db.redis.clone());
-        let http_server = HttpServer::new(self.pipeline.clone(), span_repo, redis_pool, None, None);
+        let http_server = HttpServer::new(
+            self.pipeline.clone(),
+            self.storage.spans.clone(),
+            Some(self.storage.pubsub.clone()),
+            alert_repo,
+            alert_evaluator,
+            self.storage
+                .database
+                .as_ref()
+                .map(|db| AnnotationRepository::new(db.postgres.pool().clone())),
+            jobs,
+            self.storage
+                .database
+                .as_ref()
+                .map(|db| ViewRepository::new(db.postgres.pool().clone())),
+            self.storage
+                .database
+                .as_ref()
+                .map(|db| AuditRepository::new(db.postgres.pool().clone())),
+            budget_repo,
+            slo_repo,
+            Arc::new(self.config.clone()),
+        );
 
         info!("Starting HTTP server on {}", http_addr);
 
@@ -105,10 +404,44 @@ impl Collector {
             }
         }
 
-        // Cleanup
-        pipeline_handle.abort();
+        // Cleanup, flushing spans already queued in the pipeline
         http_handle.abort();
         grpc_handle.abort();
+        self.pipeline.shutdown();
+        if let Err(e) = pipeline_handle.await {
+            error!("Pipeline task failed: {}", e);
+        }
+        if let Some(handle) = prune_handle {
+            handle.abort();
+        }
+        if let Some(handle) = payload_handle {
+            handle.abort();
+        }
+        in_progress_handle.abort();
+        if let Some(handle) = archive_handle {
+            handle.abort();
+        }
+        if let Some(handle) = status_handle {
+            handle.abort();
+        }
+        if let Some(handle) = remote_write_handle {
+            handle.abort();
+        }
+        if let Some(handle) = alert_handle {
+            handle.abort();
+        }
+        if let Some(handle) = retry_handle {
+            handle.abort();
+        }
+        if let Some(handle) = budget_handle {
+            handle.abort();
+        }
+        if let Some(handle) = slo_handle {
+            handle.abort();
+        }
+        for handle in job_handles {
+            handle.abort();
+        }
 
         info!("Collector stopped");
         Ok(())
@@ -122,9 +455,14 @@ impl Collector {
         Ok(())
     }
 
-    /// Get a reference to the database
-    pub fn database(&self) -> &Database {
-        &self.db
+    /// Get the TimescaleDB/Redis connections, if that backend is in use
+    pub fn database(&self) -> Option<&Database> {
+        self.storage.database.as_ref()
+    }
+
+    /// Get the span storage
+    pub fn storage(&self) -> &Storage {
+        &self.storage
     }
 
     /// Get the pipeline for direct span submission
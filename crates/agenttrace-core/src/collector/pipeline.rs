@@ -1,21 +1,50 @@
 //! Processing pipeline for spans
 //!
 //! The pipeline receives spans, enriches them with computed fields,
-//! calculates costs, batches them for efficiency, and stores them.
+//! calculates costs, batches them for efficiency, and stores them. Processed
+//! spans are also checked against webhook subscriptions, added to their
+//! trace's totals for per-trace guardrail rules and forwarded to any
+//! configured Datadog or New Relic sinks.
+//!
+//! Streaming calls may send a span twice: first with status `in_progress`,
+//! then a final update with token counts. The pipeline remembers in-progress
+//! spans and merges the update into them, so cost is calculated on the full
+//! span, and only final spans reach webhooks and guardrails. Updates whose
+//! start event it doesn't remember are merged into the stored start event
+//! when their batch is flushed.
+//!
+//! With payload storage enabled, prompts and completions are redacted and
+//! their full text is stored alongside each batch before previews are cut.
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use parking_lot::Mutex;
-use tokio::sync::mpsc;
+use serde::Serialize;
+use tokio::sync::{mpsc, Notify};
 use tokio::time::interval;
 use tracing::{debug, error, info, warn};
 
-use crate::db::{Database, SpanRepository, RedisStreamer};
-use crate::error::Result;
-use crate::models::Span;
+use crate::alerting::{AlertRepository, GuardrailRegistry, NotificationSender, RetryPolicy};
+use crate::config::{ForwardingConfig, PayloadsConfig, PricingConfig, PrometheusConfig};
+use crate::db::{SchemaRepository, SpanPublisher, SpanStore, Storage};
+use crate::error::{Error, Result};
+use crate::forwarding::SpanForwarder;
+use crate::models::{Span, SpanPayload, SpanPriority};
+use crate::payloads::PayloadStore;
+use crate::prometheus::SpanMetrics;
+use crate::subscriptions::{SubscriptionRegistry, SubscriptionRepository};
 
 use super::cost::CostCalculator;
+use super::debug::DebugSessions;
+use super::genai::apply_genai_conventions;
+use super::schema::SchemaRegistry;
+
+/// Longest shutdown waits for queued spans to be forwarded to APM vendors
+const FORWARD_DRAIN_TIMEOUT_SECS: u64 = 30;
 
 /// Pipeline configuration
 #[derive(Debug, Clone)]
@@ -26,8 +55,38 @@ pub struct PipelineConfig {
     pub batch_timeout_ms: u64,
     /// Whether to calculate costs for LLM spans
     pub enable_cost_calculation: bool,
-    /// Whether to stream spans to Redis for real-time updates
+    /// Whether to stream spans for real-time updates
     pub enable_redis_streaming: bool,
+    /// Whether to fill span fields from OpenTelemetry GenAI and Vercel AI SDK attributes
+    pub enable_genai_conventions: bool,
+    /// Whether to check span attributes against registered schemas
+    pub enable_schema_validation: bool,
+    /// How often to reload attribute schemas from the database (seconds)
+    pub schema_refresh_secs: u64,
+    /// Whether to deliver matching spans to webhook subscriptions
+    pub enable_webhooks: bool,
+    /// How often to reload webhook subscriptions from the database (seconds)
+    pub subscription_refresh_secs: u64,
+    /// Whether to check per-trace guardrail rules as spans arrive
+    pub enable_guardrails: bool,
+    /// How often to reload guardrail rules from the database (seconds)
+    pub guardrail_refresh_secs: u64,
+    /// How long a trace's running totals are kept after its last span (seconds)
+    pub guardrail_trace_ttl_secs: u64,
+    /// Server base URL that guardrail alerts link their trace to
+    pub alert_public_url: Option<String>,
+    /// How failed guardrail notifications are retried
+    pub alert_retry_policy: RetryPolicy,
+    /// Queue fill ratio above which low-priority spans are dropped
+    pub low_priority_shed_ratio: f64,
+    /// Where to load model prices from, over the built-in table
+    pub pricing: PricingConfig,
+    /// Full prompt/completion payload storage
+    pub payloads: PayloadsConfig,
+    /// Span metrics for Prometheus
+    pub prometheus: PrometheusConfig,
+    /// Datadog and New Relic sinks spans are forwarded to
+    pub forwarding: ForwardingConfig,
 }
 
 impl Default for PipelineConfig {
@@ -37,6 +96,21 @@ impl Default for PipelineConfig {
             batch_timeout_ms: 1000,
             enable_cost_calculation: true,
             enable_redis_streaming: true,
+            enable_genai_conventions: true,
+            enable_schema_validation: true,
+            schema_refresh_secs: 60,
+            enable_webhooks: true,
+            subscription_refresh_secs: 30,
+            enable_guardrails: true,
+            guardrail_refresh_secs: 30,
+            guardrail_trace_ttl_secs: 3600,
+            alert_public_url: None,
+            alert_retry_policy: RetryPolicy::default(),
+            low_priority_shed_ratio: 0.8,
+            pricing: PricingConfig::default(),
+            payloads: PayloadsConfig::default(),
+            prometheus: PrometheusConfig::default(),
+            forwarding: ForwardingConfig::default(),
         }
     }
 }
@@ -47,34 +121,101 @@ pub struct Pipeline {
     span_tx: mpsc::Sender<Span>,
     span_rx: Arc<Mutex<Option<mpsc::Receiver<Span>>>>,
     cost_calculator: CostCalculator,
-    span_repository: SpanRepository,
-    redis_streamer: RedisStreamer,
+    span_store: Arc<dyn SpanStore>,
+    publisher: SpanPublisher,
+    schema_registry: Option<SchemaRegistry>,
+    subscriptions: Option<SubscriptionRegistry>,
+    guardrails: Option<GuardrailRegistry>,
+    debug_sessions: DebugSessions,
+    payloads: Option<PayloadStore>,
+    span_metrics: SpanMetrics,
+    forwarder: Option<SpanForwarder>,
+    in_progress: Mutex<InProgressSpans>,
+    counters: PipelineCounters,
+    shutdown: Notify,
 }
 
 impl Pipeline {
     /// Create a new pipeline
-    pub fn new(config: PipelineConfig, db: Database) -> Self {
+    ///
+    /// Attribute schemas, webhook subscriptions and guardrail rules are
+    /// stored in PostgreSQL, so schema validation, webhooks and guardrails
+    /// are unavailable on other backends.
+    pub fn new(config: PipelineConfig, storage: &Storage) -> Self {
         let (span_tx, span_rx) = mpsc::channel(config.batch_size * 10);
+        let guardrails = storage.database.as_ref().map(|db| {
+            GuardrailRegistry::new(AlertRepository::new(db.postgres.pool().clone()), &db.redis)
+                .with_trace_ttl(config.guardrail_trace_ttl_secs)
+                .with_retry_policy(config.alert_retry_policy)
+                .with_notifier(NotificationSender::new().with_public_url(config.alert_public_url.clone()))
+        });
+        let payloads = if config.payloads.enabled {
+            match PayloadStore::new(&config.payloads, storage.spans.clone()) {
+                Ok(payloads) => Some(payloads),
+                Err(e) => {
+                    warn!("Payload storage disabled: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let span_metrics = SpanMetrics::new(&config.prometheus.latency_buckets_secs);
+        let forwarder = SpanForwarder::new(&config.forwarding);
 
         Self {
             config,
             span_tx,
             span_rx: Arc::new(Mutex::new(Some(span_rx))),
             cost_calculator: CostCalculator::new(),
-            span_repository: SpanRepository::new(&db.postgres),
-            redis_streamer: RedisStreamer::new(&db.redis),
+            span_store: storage.spans.clone(),
+            publisher: SpanPublisher::new(storage.pubsub.clone()),
+            schema_registry: storage
+                .database
+                .as_ref()
+                .map(|db| SchemaRegistry::new(SchemaRepository::new(&db.postgres))),
+            subscriptions: storage
+                .database
+                .as_ref()
+                .map(|db| SubscriptionRegistry::new(SubscriptionRepository::new(db.postgres.pool().clone()))),
+            guardrails,
+            debug_sessions: DebugSessions::new(),
+            payloads,
+            span_metrics,
+            forwarder,
+            in_progress: Mutex::new(InProgressSpans::default()),
+            counters: PipelineCounters::new(),
+            shutdown: Notify::new(),
         }
     }
 
     /// Submit a span for processing
+    ///
+    /// Under load, low-priority spans are rejected with [`Error::RateLimit`]
+    /// once the queue passes `low_priority_shed_ratio`; other spans wait for
+    /// room in the queue.
     pub async fn submit(&self, span: Span) -> Result<()> {
+        if span.priority == SpanPriority::Low && self.queue_fill() > self.config.low_priority_shed_ratio {
+            self.counters.shed.fetch_add(1, Ordering::Relaxed);
+            return Err(Error::RateLimit);
+        }
+
         self.span_tx
             .send(span)
             .await
-            .map_err(|e| crate::error::Error::Channel(e.to_string()))?;
+            .map_err(|e| Error::Channel(e.to_string()))?;
+        self.counters.received.record();
         Ok(())
     }
 
+    /// Fraction of the queue currently in use
+    #[allow(clippy::cast_precision_loss)]
+    fn queue_fill(&self) -> f64 {
+        let max = self.config.batch_size * 10;
+        max.saturating_sub(self.span_tx.capacity()) as f64 / max.max(1) as f64
+    }
+
     /// Submit a batch of spans for processing
     pub async fn submit_batch(&self, spans: Vec<Span>) -> Result<usize> {
         let mut count = 0;
@@ -86,6 +227,17 @@ impl Pipeline {
         Ok(count)
     }
 
+    /// Trace ID of an in-progress span waiting here for its final update
+    pub fn in_progress_trace_id(&self, span_id: &str) -> Option<String> {
+        self.in_progress.lock().spans.get(span_id).map(|span| span.trace_id.clone())
+    }
+
+    /// Stop accepting spans and make [`Pipeline::start`] return once the
+    /// queued spans have been processed and flushed
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
     /// Start the pipeline processing loop
     pub async fn start(&self) {
         // Take ownership of the receiver
@@ -104,10 +256,25 @@ impl Pipeline {
         let batch_timeout = Duration::from_millis(self.config.batch_timeout_ms);
         let enable_cost = self.config.enable_cost_calculation;
         let enable_redis = self.config.enable_redis_streaming;
-
-        let cost_calculator = CostCalculator::new();
-        let span_repository = self.span_repository.clone();
-        let redis_streamer = self.redis_streamer.clone();
+        let enable_genai = self.config.enable_genai_conventions;
+        let schema_registry = self
+            .schema_registry
+            .clone()
+            .filter(|_| self.config.enable_schema_validation);
+        let subscriptions = self.subscriptions.clone().filter(|_| self.config.enable_webhooks);
+        let delivery_handle = subscriptions.as_ref().and_then(SubscriptionRegistry::start);
+        let guardrails = self.guardrails.clone().filter(|_| self.config.enable_guardrails);
+        let guardrail_handle = guardrails.as_ref().and_then(GuardrailRegistry::start);
+        let forwarder = self.forwarder.clone();
+        let mut forward_handle = forwarder.as_ref().and_then(SpanForwarder::start);
+
+        let cost_calculator = self.cost_calculator.clone();
+        let pricing = self.config.pricing.clone();
+        let span_store = self.span_store.clone();
+        let publisher = self.publisher.clone();
+        let debug_sessions = self.debug_sessions.clone();
+        let payloads = self.payloads.clone();
+        let span_metrics = self.span_metrics.clone();
 
         info!(
             "Pipeline started (batch_size={}, timeout={}ms)",
@@ -115,60 +282,264 @@ impl Pipeline {
         );
 
         let mut batch: Vec<Span> = Vec::with_capacity(batch_size);
+        let mut batch_payloads: Vec<SpanPayload> = Vec::new();
         let mut flush_interval = interval(batch_timeout);
+        // The first tick completes immediately, which performs the initial load
+        let mut schema_refresh = interval(Duration::from_secs(self.config.schema_refresh_secs.max(1)));
+        let mut subscription_refresh = interval(Duration::from_secs(self.config.subscription_refresh_secs.max(1)));
+        let mut guardrail_refresh = interval(Duration::from_secs(self.config.guardrail_refresh_secs.max(1)));
+        let mut pricing_refresh = interval(Duration::from_secs(pricing.reload_interval_secs.max(1)));
+
+        let mut closing = false;
 
         loop {
             tokio::select! {
                 // Receive a span
-                Some(mut span) = span_rx.recv() => {
+                received = span_rx.recv() => {
+                    // Channel closed and drained after shutdown
+                    let Some(span) = received else {
+                        if !batch.is_empty() {
+                            flush_batch(
+                                span_store.as_ref(),
+                                &mut batch,
+                                enable_cost.then_some(&cost_calculator),
+                                payloads.as_ref(),
+                                &mut batch_payloads,
+                                &self.counters,
+                            )
+                            .await;
+                        }
+                        if let Some(handle) = &delivery_handle {
+                            handle.abort();
+                        }
+                        if let Some(handle) = &guardrail_handle {
+                            handle.abort();
+                        }
+                        // Let queued spans reach the vendors, but don't hang
+                        // shutdown on one that is down
+                        if let (Some(forwarder), Some(mut handle)) = (&forwarder, forward_handle.take()) {
+                            forwarder.close();
+                            let drain = Duration::from_secs(FORWARD_DRAIN_TIMEOUT_SECS);
+                            if tokio::time::timeout(drain, &mut handle).await.is_err() {
+                                warn!(
+                                    "Dropping spans still queued for forwarding after {}s",
+                                    FORWARD_DRAIN_TIMEOUT_SECS
+                                );
+                                handle.abort();
+                            }
+                        }
+                        info!("Pipeline stopped");
+                        break;
+                    };
+
+                    // Fold an update for a streaming span into its start event
+                    let mut span = self.in_progress.lock().merge(span);
+
+                    // Fill model, token, preview and tool fields from GenAI attributes
+                    if enable_genai {
+                        apply_genai_conventions(&mut span);
+                    }
+
+                    // Redact and keep the full payload before previews are truncated
+                    if let Some(payload) = payloads.as_ref().and_then(|p| p.capture(&mut span)) {
+                        batch_payloads.push(payload);
+                    }
+
+                    // Keep the full payload for active debug sessions before previews are truncated
+                    let debug_channels = debug_sessions.matching_channels(&span);
+                    let full_previews = (!debug_channels.is_empty())
+                        .then(|| (span.prompt_preview.clone(), span.completion_preview.clone()));
+
                     // Enrich the span
                     enrich_span(&mut span);
 
+                    // Check attributes against the service's schema
+                    if let Some(schema_registry) = &schema_registry {
+                        let violations = schema_registry.apply(&mut span);
+                        if violations > 0 {
+                            debug!(
+                                "Span {} from {} has {} schema violation(s)",
+                                span.span_id, span.service_name, violations
+                            );
+                        }
+                    }
+
                     // Calculate cost if enabled
                     if enable_cost {
                         cost_calculator.calculate(&mut span);
                     }
 
-                    // Stream to Redis if enabled
+                    // Stream full payloads to debug sessions
+                    if let Some((prompt, completion)) = full_previews {
+                        let mut full_span = span.clone();
+                        full_span.prompt_preview = prompt;
+                        full_span.completion_preview = completion;
+                        if let Err(e) = publisher.publish_debug(&full_span, &debug_channels).await {
+                            warn!("Failed to publish debug span: {}", e);
+                        }
+                    }
+
+                    // Stream to subscribers if enabled
                     if enable_redis {
-                        if let Err(e) = redis_streamer.publish_span(&span).await {
-                            warn!("Failed to publish span to Redis: {}", e);
+                        if let Err(e) = publisher.publish_span(&span).await {
+                            warn!("Failed to publish span: {}", e);
                         }
                     }
 
+                    // Post newly matching traces to webhook subscriptions
+                    if let Some(subscriptions) = subscriptions.as_ref().filter(|_| !span.is_in_progress()) {
+                        subscriptions.dispatch(&span);
+                    }
+
+                    // Add to the trace's running totals for guardrail rules
+                    if let Some(guardrails) = guardrails.as_ref().filter(|_| !span.is_in_progress()) {
+                        guardrails.observe(&span);
+                    }
+
+                    // Send to Datadog and New Relic sinks
+                    if let Some(forwarder) = forwarder.as_ref().filter(|_| !span.is_in_progress()) {
+                        forwarder.forward(&span);
+                    }
+
+                    // Add to the Prometheus span metrics
+                    span_metrics.record(&span);
+
                     batch.push(span);
 
                     // Flush if batch is full
                     if batch.len() >= batch_size {
-                        flush_batch(&span_repository, &mut batch).await;
+                        flush_batch(
+                            span_store.as_ref(),
+                            &mut batch,
+                            enable_cost.then_some(&cost_calculator),
+                            payloads.as_ref(),
+                            &mut batch_payloads,
+                            &self.counters,
+                        )
+                        .await;
                     }
                 }
 
                 // Periodic flush
                 _ = flush_interval.tick() => {
                     if !batch.is_empty() {
-                        flush_batch(&span_repository, &mut batch).await;
+                        flush_batch(
+                            span_store.as_ref(),
+                            &mut batch,
+                            enable_cost.then_some(&cost_calculator),
+                            payloads.as_ref(),
+                            &mut batch_payloads,
+                            &self.counters,
+                        )
+                        .await;
                     }
                 }
 
-                // Channel closed
-                else => {
-                    // Final flush
-                    if !batch.is_empty() {
-                        flush_batch(&span_repository, &mut batch).await;
+                // Pick up schema changes made by other collector instances
+                _ = schema_refresh.tick(), if schema_registry.is_some() => {
+                    if let Some(schema_registry) = &schema_registry {
+                        if let Err(e) = schema_registry.reload().await {
+                            warn!("Failed to reload attribute schemas: {}", e);
+                        }
                     }
-                    info!("Pipeline stopped");
-                    break;
+                }
+
+                // Pick up subscriptions changed through other collector instances
+                _ = subscription_refresh.tick(), if subscriptions.is_some() => {
+                    if let Some(subscriptions) = &subscriptions {
+                        if let Err(e) = subscriptions.reload().await {
+                            warn!("Failed to reload webhook subscriptions: {}", e);
+                        }
+                    }
+                }
+
+                // Pick up guardrail rules changed through the API or other instances
+                _ = guardrail_refresh.tick(), if guardrails.is_some() => {
+                    if let Some(guardrails) = &guardrails {
+                        if let Err(e) = guardrails.reload().await {
+                            warn!("Failed to reload trace guardrail rules: {}", e);
+                        }
+                    }
+                }
+
+                // Pick up price changes in the pricing file or URL
+                _ = pricing_refresh.tick(), if pricing.source.is_some() => {
+                    match cost_calculator.reload(&pricing).await {
+                        Ok(count) => debug!("Loaded pricing for {} models", count),
+                        Err(e) => warn!("Failed to reload model pricing: {}", e),
+                    }
+                }
+
+                // Stop taking new spans; the queue drains through the receive arm
+                _ = self.shutdown.notified(), if !closing => {
+                    closing = true;
+                    span_rx.close();
                 }
             }
         }
     }
 
+    /// Get the pipeline's configuration
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// Get the attribute schema registry, if the backend supports schemas
+    pub fn schema_registry(&self) -> Option<&SchemaRegistry> {
+        self.schema_registry.as_ref()
+    }
+
+    /// Get the per-trace guardrail rules, if the backend supports them
+    pub fn guardrails(&self) -> Option<&GuardrailRegistry> {
+        self.guardrails.as_ref()
+    }
+
+    /// Get the webhook subscriptions, if the backend supports them
+    pub fn subscriptions(&self) -> Option<&SubscriptionRegistry> {
+        self.subscriptions.as_ref()
+    }
+
+    /// Get the cost calculator, with any prices loaded from `pricing.source`
+    pub fn cost_calculator(&self) -> &CostCalculator {
+        &self.cost_calculator
+    }
+
+    /// Get the active debug sessions
+    pub fn debug_sessions(&self) -> &DebugSessions {
+        &self.debug_sessions
+    }
+
+    /// Get the full payload store, if payload storage is enabled
+    pub fn payloads(&self) -> Option<&PayloadStore> {
+        self.payloads.as_ref()
+    }
+
+    /// Get the span metrics served to Prometheus
+    pub fn span_metrics(&self) -> &SpanMetrics {
+        &self.span_metrics
+    }
+
     /// Get pipeline statistics
     pub fn stats(&self) -> PipelineStats {
+        let counters = &self.counters;
+        let last_flush = counters.last_flush.lock().clone();
+        let last_failed_flush = counters.last_failed_flush.lock().clone();
+
         PipelineStats {
+            started_at: counters.started_at,
             queue_capacity: self.span_tx.capacity(),
             queue_max_capacity: self.config.batch_size * 10,
+            queue_fill: self.queue_fill(),
+            received_spans: counters.received.total(),
+            stored_spans: counters.stored.total(),
+            dropped_spans: counters.dropped.load(Ordering::Relaxed),
+            received_per_sec: counters.received.per_second(),
+            stored_per_sec: counters.stored.per_second(),
+            shed_low_priority: counters.shed.load(Ordering::Relaxed),
+            low_priority_shed_ratio: self.config.low_priority_shed_ratio,
+            last_flush,
+            last_failed_flush,
+            debug_sessions: self.debug_sessions.list().len(),
         }
     }
 }
@@ -183,24 +554,127 @@ fn enrich_span(span: &mut Span) {
         span.service_name = "unknown".to_string();
     }
 
+    // Sessions may be set through OpenTelemetry attributes instead
+    span.fill_session_from_attributes();
+
     // Truncate previews if too long
     if let Some(ref mut preview) = span.prompt_preview {
-        if preview.len() > 500 {
-            preview.truncate(500);
-            preview.push_str("...");
-        }
+        truncate_preview(preview);
     }
 
     if let Some(ref mut preview) = span.completion_preview {
-        if preview.len() > 500 {
-            preview.truncate(500);
-            preview.push_str("...");
+        truncate_preview(preview);
+    }
+}
+
+/// Cut a preview to 500 bytes, backing off to a character boundary
+fn truncate_preview(preview: &mut String) {
+    if preview.len() > 500 {
+        let mut end = 500;
+        while !preview.is_char_boundary(end) {
+            end -= 1;
+        }
+        preview.truncate(end);
+        preview.push_str("...");
+    }
+}
+
+/// Most in-progress spans remembered at once
+const MAX_IN_PROGRESS_SPANS: usize = 10_000;
+
+/// How long an in-progress span is kept waiting for its final update, in
+/// memory and in storage
+pub(crate) const IN_PROGRESS_TTL_SECS: i64 = 3600;
+
+/// Start events of streaming spans, by span ID, waiting for their update
+#[derive(Default)]
+struct InProgressSpans {
+    spans: HashMap<String, Span>,
+}
+
+impl InProgressSpans {
+    /// Merge a span into its remembered start event, if any
+    ///
+    /// The merged span keeps the start event's ID and start time so it
+    /// replaces the stored row. It is remembered again while it is still in
+    /// progress and forgotten once final.
+    fn merge(&mut self, span: Span) -> Span {
+        let span = match self.spans.remove(&span.span_id) {
+            Some(mut started) => {
+                started.apply_update(span);
+                started
+            }
+            None => span,
+        };
+
+        if span.is_in_progress() {
+            if self.spans.len() >= MAX_IN_PROGRESS_SPANS {
+                // Spans that never finished would otherwise hold their slots forever
+                let cutoff = Utc::now() - chrono::Duration::seconds(IN_PROGRESS_TTL_SECS);
+                self.spans.retain(|_, s| s.started_at > cutoff);
+            }
+            if self.spans.len() < MAX_IN_PROGRESS_SPANS {
+                self.spans.insert(span.span_id.clone(), span.clone());
+            } else {
+                debug!("Too many in-progress spans, not tracking {}", span.span_id);
+            }
+        }
+        span
+    }
+}
+
+/// Merge final spans into the `in_progress` start events already stored
+/// for them, recalculating cost on the merged span
+///
+/// Covers start events this collector no longer remembers: stored by an
+/// earlier run, by another collector, or forgotten after the TTL. The merged
+/// span keeps the stored start time, so it replaces the stored row even when
+/// the update was sent with a different one.
+async fn merge_stored_in_progress(store: &dyn SpanStore, batch: &mut [Span], costs: Option<&CostCalculator>) {
+    let span_ids: Vec<String> = batch
+        .iter()
+        .filter(|s| !s.is_in_progress())
+        .map(|s| s.span_id.clone())
+        .collect();
+    if span_ids.is_empty() {
+        return;
+    }
+
+    let mut stored: HashMap<(String, String), Span> = match store.get_in_progress(&span_ids).await {
+        Ok(stored) => stored
+            .into_iter()
+            .map(|s| ((s.trace_id.clone(), s.span_id.clone()), s))
+            .collect(),
+        Err(e) => {
+            warn!("Failed to look up stored in-progress spans: {}", e);
+            return;
+        }
+    };
+
+    for span in batch.iter_mut().filter(|s| !s.is_in_progress()) {
+        let Some(mut started) = stored.remove(&(span.trace_id.clone(), span.span_id.clone())) else {
+            continue;
+        };
+        started.apply_update(span.clone());
+        if let Some(costs) = costs {
+            costs.calculate(&mut started);
         }
+        *span = started;
     }
 }
 
-/// Flush a batch of spans to the database
-async fn flush_batch(repo: &SpanRepository, batch: &mut Vec<Span>) {
+/// Flush a batch of spans to the database, then the payloads captured from
+/// them
+///
+/// Payloads of a batch that failed to insert are dropped with it.
+async fn flush_batch(
+    store: &dyn SpanStore,
+    batch: &mut Vec<Span>,
+    costs: Option<&CostCalculator>,
+    payload_store: Option<&PayloadStore>,
+    payloads: &mut Vec<SpanPayload>,
+    counters: &PipelineCounters,
+) {
     if batch.is_empty() {
         return;
     }
@@ -208,24 +682,314 @@ async fn flush_batch(repo: &SpanRepository, batch: &mut Vec<Span>) {
     let batch_size = batch.len();
     debug!("Flushing batch of {} spans", batch_size);
 
-    match repo.insert_batch(batch).await {
+    merge_stored_in_progress(store, batch, costs).await;
+
+    match store.insert_batch(batch).await {
         Ok(inserted) => {
             debug!("Inserted {} of {} spans", inserted, batch_size);
+            counters.stored.record_many(batch_size as u64);
+            *counters.last_flush.lock() = Some(FlushRecord {
+                at: Utc::now(),
+                spans: batch_size,
+                inserted: Some(inserted),
+                error: None,
+            });
+
+            if let Some(payload_store) = payload_store.filter(|_| !payloads.is_empty()) {
+                if let Err(e) = payload_store.put_batch(payloads).await {
+                    warn!("Failed to store {} span payloads: {}", payloads.len(), e);
+                }
+            }
         }
         Err(e) => {
             error!("Failed to insert batch: {}", e);
             // TODO: implement retry logic or dead letter queue
+            counters.dropped.fetch_add(batch_size as u64, Ordering::Relaxed);
+            let record = FlushRecord {
+                at: Utc::now(),
+                spans: batch_size,
+                inserted: None,
+                error: Some(e.to_string()),
+            };
+            *counters.last_flush.lock() = Some(record.clone());
+            *counters.last_failed_flush.lock() = Some(record);
         }
     }
 
     batch.clear();
+    payloads.clear();
+}
+
+/// Seconds of history behind the per-second rates
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// Counts events in one-second buckets over the last [`RATE_WINDOW_SECS`]
+///
+/// Lock-free so it can sit on the ingest path; concurrent updates racing a
+/// bucket rollover may lose a few counts, which is fine for rates.
+struct RateCounter {
+    total: AtomicU64,
+    buckets: [(AtomicU64, AtomicU64); RATE_WINDOW_SECS as usize],
+}
+
+impl RateCounter {
+    fn new() -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            buckets: std::array::from_fn(|_| (AtomicU64::new(0), AtomicU64::new(0))),
+        }
+    }
+
+    fn record(&self) {
+        self.record_many(1);
+    }
+
+    fn record_many(&self, count: u64) {
+        self.record_at(now_secs(), count);
+    }
+
+    fn record_at(&self, second: u64, count: u64) {
+        self.total.fetch_add(count, Ordering::Relaxed);
+
+        let (stamp, bucket) = &self.buckets[(second % RATE_WINDOW_SECS) as usize];
+        if stamp.swap(second, Ordering::Relaxed) != second {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        bucket.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    fn per_second(&self) -> f64 {
+        self.per_second_at(now_secs())
+    }
+
+    /// Average over the window's complete seconds, up to `second`
+    #[allow(clippy::cast_precision_loss)]
+    fn per_second_at(&self, second: u64) -> f64 {
+        let window = second.saturating_sub(RATE_WINDOW_SECS)..second;
+        let count: u64 = self
+            .buckets
+            .iter()
+            .filter(|(stamp, _)| window.contains(&stamp.load(Ordering::Relaxed)))
+            .map(|(_, bucket)| bucket.load(Ordering::Relaxed))
+            .sum();
+        count as f64 / RATE_WINDOW_SECS as f64
+    }
+}
+
+fn now_secs() -> u64 {
+    u64::try_from(Utc::now().timestamp()).unwrap_or_default()
+}
+
+/// Counters behind [`PipelineStats`]
+struct PipelineCounters {
+    started_at: DateTime<Utc>,
+    received: RateCounter,
+    stored: RateCounter,
+    dropped: AtomicU64,
+    shed: AtomicU64,
+    last_flush: Mutex<Option<FlushRecord>>,
+    last_failed_flush: Mutex<Option<FlushRecord>>,
+}
+
+impl PipelineCounters {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            received: RateCounter::new(),
+            stored: RateCounter::new(),
+            dropped: AtomicU64::new(0),
+            shed: AtomicU64::new(0),
+            last_flush: Mutex::new(None),
+            last_failed_flush: Mutex::new(None),
+        }
+    }
+}
+
+/// Outcome of writing a batch to storage
+#[derive(Debug, Clone, Serialize)]
+pub struct FlushRecord {
+    /// When the batch was written
+    pub at: DateTime<Utc>,
+    /// Spans in the batch
+    pub spans: usize,
+    /// Spans written; fewer than `spans` when some were already stored
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted: Option<usize>,
+    /// Why the write failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Pipeline statistics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PipelineStats {
+    /// When the pipeline was created
+    pub started_at: DateTime<Utc>,
     /// Current queue capacity (available slots)
     pub queue_capacity: usize,
     /// Maximum queue capacity
     pub queue_max_capacity: usize,
+    /// Fraction of the queue in use
+    pub queue_fill: f64,
+    /// Spans accepted into the queue since startup
+    pub received_spans: u64,
+    /// Spans in batches written to storage since startup
+    pub stored_spans: u64,
+    /// Spans lost because their batch failed to write; there is no
+    /// dead-letter queue, so these aren't retried
+    pub dropped_spans: u64,
+    /// Spans accepted per second over the last minute
+    pub received_per_sec: f64,
+    /// Spans written per second over the last minute
+    pub stored_per_sec: f64,
+    /// Low-priority spans dropped because the queue was nearly full
+    pub shed_low_priority: u64,
+    /// Queue fill above which low-priority spans are dropped
+    pub low_priority_shed_ratio: f64,
+    /// Most recent batch write
+    pub last_flush: Option<FlushRecord>,
+    /// Most recent batch write that failed
+    pub last_failed_flush: Option<FlushRecord>,
+    /// Active debug sessions
+    pub debug_sessions: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SpanStatus;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_rate_counter() {
+        let counter = RateCounter::new();
+        counter.record_at(1_000, 30);
+        counter.record_at(1_001, 30);
+        // The current second is still filling and isn't counted
+        counter.record_at(1_002, 500);
+
+        assert_eq!(counter.total(), 560);
+        assert!((counter.per_second_at(1_002) - 1.0).abs() < f64::EPSILON);
+
+        // A bucket is reset when its slot comes round again
+        counter.record_at(1_000 + RATE_WINDOW_SECS, 6);
+        assert!((counter.per_second_at(1_000 + RATE_WINDOW_SECS + 1) - 536.0 / 60.0).abs() < 1e-9);
+
+        // Old buckets age out of the window
+        assert!(counter.per_second_at(5_000).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_truncate_preview_keeps_characters_whole() {
+        let mut preview = format!("{}é", "a".repeat(499));
+        truncate_preview(&mut preview);
+        assert_eq!(preview, format!("{}...", "a".repeat(499)));
+
+        let mut short = "hello".to_string();
+        truncate_preview(&mut short);
+        assert_eq!(short, "hello");
+    }
+
+    /// Start event of a streaming call
+    fn started_span() -> Span {
+        serde_json::from_value(serde_json::json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "span_id": "a1",
+            "trace_id": "t1",
+            "parent_span_id": null,
+            "operation_name": "chat",
+            "service_name": "agent",
+            "span_kind": "client",
+            "started_at": "2026-01-01T00:00:00Z",
+            "ended_at": null,
+            "duration_ms": null,
+            "status": "in_progress",
+            "status_message": null,
+            "model_name": "gpt-4o",
+            "model_provider": "openai",
+            "tokens_in": null,
+            "tokens_cached_in": null,
+            "tokens_out": null,
+            "tokens_reasoning": null,
+            "cost_usd": null,
+            "tool_name": null,
+            "tool_input": null,
+            "tool_output": null,
+            "tool_duration_ms": null,
+            "prompt_preview": "hello",
+            "completion_preview": null,
+            "attributes": {"stream": true},
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_in_progress_merge() {
+        let started = started_span();
+
+        let mut update = started.clone();
+        update.id = Uuid::new_v4();
+        update.status = SpanStatus::Ok;
+        update.started_at += chrono::Duration::milliseconds(5);
+        update.ended_at = Some(started.started_at + chrono::Duration::seconds(2));
+        update.model_name = None;
+        update.prompt_preview = None;
+        update.tokens_in = Some(100);
+        update.tokens_out = Some(40);
+        update.attributes = serde_json::json!({"finish_reason": "stop"});
+
+        let mut in_progress = InProgressSpans::default();
+        let first = in_progress.merge(started.clone());
+        assert!(first.is_in_progress());
+        assert_eq!(in_progress.spans.len(), 1);
+
+        let merged = in_progress.merge(update);
+        assert!(in_progress.spans.is_empty());
+        assert_eq!(merged.id, started.id);
+        assert_eq!(merged.started_at, started.started_at);
+        assert_eq!(merged.status, SpanStatus::Ok);
+        assert_eq!(merged.model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(merged.prompt_preview.as_deref(), Some("hello"));
+        assert_eq!(merged.tokens_in, Some(100));
+        assert_eq!(merged.attributes["stream"], true);
+        assert_eq!(merged.attributes["finish_reason"], "stop");
+    }
+
+    #[tokio::test]
+    async fn test_update_merges_into_stored_start_event() {
+        let store = crate::db::SqliteStore::open(":memory:").await.unwrap();
+        let started = started_span();
+        store.insert_batch(std::slice::from_ref(&started)).await.unwrap();
+
+        // Sent to a collector that never saw the start event, with its own start time
+        let mut update = started.clone();
+        update.id = Uuid::new_v4();
+        update.status = SpanStatus::Ok;
+        update.started_at += chrono::Duration::milliseconds(5);
+        update.ended_at = Some(started.started_at + chrono::Duration::seconds(2));
+        update.model_name = None;
+        update.tokens_in = Some(1_000_000);
+        update.tokens_out = Some(0);
+        update.attributes = serde_json::json!({});
+
+        let costs = CostCalculator::new();
+        let mut batch = vec![update];
+        merge_stored_in_progress(&store, &mut batch, Some(&costs)).await;
+        assert_eq!(batch[0].id, started.id);
+        assert_eq!(batch[0].started_at, started.started_at);
+        assert_eq!(batch[0].model_name.as_deref(), Some("gpt-4o"));
+        assert!(batch[0].cost_usd.unwrap() > 0.0);
+
+        store.insert_batch(&batch).await.unwrap();
+        let spans = store.get_by_trace_id("t1").await.unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].status, SpanStatus::Ok);
+        assert_eq!(spans[0].cost_usd, batch[0].cost_usd);
+    }
 }
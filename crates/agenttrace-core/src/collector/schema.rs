@@ -0,0 +1,428 @@
+//! Attribute schema enforcement
+//!
+//! Services can register the attributes their spans are expected to carry.
+//! Incoming spans are checked against the registered schema: simple type
+//! mismatches (e.g., `"42"` for an integer) are coerced in place, and anything
+//! that cannot be fixed is recorded under `agenttrace.schema_violations` so
+//! compliance can be queried later.
+//!
+//! The registry also holds each service's per-operation latency targets, used
+//! to report target attainment and flag slow spans in trace detail.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde_json::Value;
+
+use crate::db::SchemaRepository;
+use crate::error::{Error, Result};
+use crate::models::{
+    AttributeSchema, AttributeSchemaInput, AttributeType, LatencyTarget, SchemaComplianceReport,
+    SchemaViolation, Span, TargetBreach, ViolationKind, SCHEMA_VIOLATIONS_ATTRIBUTE,
+};
+
+/// In-memory cache of registered schemas, backed by the database
+#[derive(Clone)]
+pub struct SchemaRegistry {
+    repo: SchemaRepository,
+    schemas: Arc<RwLock<HashMap<String, AttributeSchema>>>,
+}
+
+impl SchemaRegistry {
+    /// Create an empty registry; call [`reload`](Self::reload) to load schemas
+    pub fn new(repo: SchemaRepository) -> Self {
+        Self {
+            repo,
+            schemas: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Reload all schemas from the database, returning how many were loaded
+    pub async fn reload(&self) -> Result<usize> {
+        let schemas = self.repo.list().await?;
+        let count = schemas.len();
+
+        *self.schemas.write() = schemas
+            .into_iter()
+            .map(|s| (s.service_name.clone(), s))
+            .collect();
+
+        Ok(count)
+    }
+
+    /// List registered schemas, ordered by service name
+    pub fn list(&self) -> Vec<AttributeSchema> {
+        let mut schemas: Vec<AttributeSchema> = self.schemas.read().values().cloned().collect();
+        schemas.sort_by(|a, b| a.service_name.cmp(&b.service_name));
+        schemas
+    }
+
+    /// Get the schema registered for a service
+    pub fn get(&self, service_name: &str) -> Option<AttributeSchema> {
+        self.schemas.read().get(service_name).cloned()
+    }
+
+    /// Register or replace the schema for a service
+    pub async fn register(&self, service_name: &str, input: AttributeSchemaInput) -> Result<AttributeSchema> {
+        validate_input(service_name, &input)?;
+
+        let schema = self.repo.upsert(service_name, &input).await?;
+        self.schemas
+            .write()
+            .insert(schema.service_name.clone(), schema.clone());
+
+        Ok(schema)
+    }
+
+    /// Remove the schema for a service
+    pub async fn remove(&self, service_name: &str) -> Result<bool> {
+        let deleted = self.repo.delete(service_name).await?;
+        self.schemas.write().remove(service_name);
+        Ok(deleted)
+    }
+
+    /// Schema compliance for a service over a time range
+    pub async fn compliance(
+        &self,
+        service_name: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SchemaComplianceReport> {
+        self.repo.compliance(service_name, since, until).await
+    }
+
+    /// Latency targets with their service, optionally for a single service
+    pub fn latency_targets(&self, service_name: Option<&str>) -> Vec<(String, LatencyTarget)> {
+        self.list()
+            .into_iter()
+            .filter(|s| service_name.map_or(true, |name| s.service_name == name))
+            .flat_map(|s| {
+                let service = s.service_name;
+                s.latency_targets.into_iter().map(move |t| (service.clone(), t))
+            })
+            .collect()
+    }
+
+    /// Spans that took longer than their operation's latency target
+    pub fn target_breaches(&self, spans: &[Span]) -> Vec<TargetBreach> {
+        let schemas = self.schemas.read();
+        spans
+            .iter()
+            .filter_map(|span| target_breach(schemas.get(&span.service_name)?, span))
+            .collect()
+    }
+
+    /// Check a span against its service's schema, coercing and tagging in place
+    ///
+    /// Returns the number of violations recorded on the span.
+    pub fn apply(&self, span: &mut Span) -> usize {
+        // Never trust a violation tag sent by the client
+        if let Some(map) = span.attributes.as_object_mut() {
+            map.remove(SCHEMA_VIOLATIONS_ATTRIBUTE);
+        }
+
+        let schemas = self.schemas.read();
+        let Some(schema) = schemas.get(&span.service_name) else {
+            return 0;
+        };
+
+        let violations = validate_attributes(schema, &mut span.attributes);
+        if violations.is_empty() {
+            return 0;
+        }
+
+        let count = violations.len();
+        if let Some(map) = span.attributes.as_object_mut() {
+            map.insert(
+                SCHEMA_VIOLATIONS_ATTRIBUTE.to_string(),
+                serde_json::to_value(violations).unwrap_or_default(),
+            );
+        }
+        count
+    }
+}
+
+/// Check a span's duration against its operation's latency target
+pub fn target_breach(schema: &AttributeSchema, span: &Span) -> Option<TargetBreach> {
+    let target = schema.latency_target(&span.operation_name)?;
+    let duration_ms = span.duration_ms?;
+
+    (duration_ms > target.target_ms).then(|| TargetBreach {
+        span_id: span.span_id.clone(),
+        operation_name: span.operation_name.clone(),
+        duration_ms,
+        target_ms: target.target_ms,
+    })
+}
+
+/// Validate span attributes against a schema, coercing simple mismatches
+pub fn validate_attributes(schema: &AttributeSchema, attributes: &mut Value) -> Vec<SchemaViolation> {
+    if attributes.is_null() {
+        *attributes = Value::Object(serde_json::Map::new());
+    }
+
+    let Some(map) = attributes.as_object_mut() else {
+        return Vec::new();
+    };
+
+    let mut violations = Vec::new();
+
+    for spec in &schema.attributes {
+        let value = match map.get_mut(&spec.key) {
+            Some(v) if !v.is_null() => v,
+            _ => {
+                if spec.required {
+                    violations.push(SchemaViolation {
+                        key: spec.key.clone(),
+                        kind: ViolationKind::Missing,
+                        expected: spec.attr_type,
+                        actual: None,
+                    });
+                }
+                continue;
+            }
+        };
+
+        match coerce(value, spec.attr_type) {
+            Coercion::Valid => {}
+            Coercion::Coerced(coerced) => *value = coerced,
+            Coercion::Mismatch => violations.push(SchemaViolation {
+                key: spec.key.clone(),
+                kind: ViolationKind::TypeMismatch,
+                expected: spec.attr_type,
+                actual: Some(json_type_name(value).to_string()),
+            }),
+        }
+    }
+
+    violations
+}
+
+/// Outcome of checking a value against an expected type
+enum Coercion {
+    Valid,
+    Coerced(Value),
+    Mismatch,
+}
+
+fn coerce(value: &Value, expected: AttributeType) -> Coercion {
+    match (expected, value) {
+        (AttributeType::String, Value::String(_))
+        | (AttributeType::Boolean, Value::Bool(_))
+        | (AttributeType::Float, Value::Number(_))
+        | (AttributeType::Object, Value::Object(_))
+        | (AttributeType::Array, Value::Array(_)) => Coercion::Valid,
+
+        (AttributeType::String, Value::Number(n)) => Coercion::Coerced(Value::String(n.to_string())),
+        (AttributeType::String, Value::Bool(b)) => Coercion::Coerced(Value::String(b.to_string())),
+
+        (AttributeType::Integer, Value::Number(n)) => {
+            if n.is_i64() || n.is_u64() {
+                Coercion::Valid
+            } else {
+                match n.as_f64() {
+                    Some(f) if f.fract() == 0.0 && f.abs() < 9.0e15 => {
+                        Coercion::Coerced(Value::from(f as i64))
+                    }
+                    _ => Coercion::Mismatch,
+                }
+            }
+        }
+        (AttributeType::Integer, Value::String(s)) => match s.trim().parse::<i64>() {
+            Ok(i) => Coercion::Coerced(Value::from(i)),
+            Err(_) => Coercion::Mismatch,
+        },
+
+        (AttributeType::Float, Value::String(s)) => match s.trim().parse::<f64>() {
+            Ok(f) if f.is_finite() => Coercion::Coerced(Value::from(f)),
+            _ => Coercion::Mismatch,
+        },
+
+        (AttributeType::Boolean, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Coercion::Coerced(Value::Bool(true)),
+            "false" => Coercion::Coerced(Value::Bool(false)),
+            _ => Coercion::Mismatch,
+        },
+        (AttributeType::Boolean, Value::Number(n)) => match n.as_i64() {
+            Some(0) => Coercion::Coerced(Value::Bool(false)),
+            Some(1) => Coercion::Coerced(Value::Bool(true)),
+            _ => Coercion::Mismatch,
+        },
+
+        (AttributeType::Object, Value::String(s)) => match serde_json::from_str::<Value>(s) {
+            Ok(v @ Value::Object(_)) => Coercion::Coerced(v),
+            _ => Coercion::Mismatch,
+        },
+        (AttributeType::Array, Value::String(s)) => match serde_json::from_str::<Value>(s) {
+            Ok(v @ Value::Array(_)) => Coercion::Coerced(v),
+            _ => Coercion::Mismatch,
+        },
+
+        _ => Coercion::Mismatch,
+    }
+}
+
+fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn validate_input(service_name: &str, input: &AttributeSchemaInput) -> Result<()> {
+    if service_name.trim().is_empty() {
+        return Err(Error::validation("Service name must not be empty"));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for spec in &input.attributes {
+        if spec.key.trim().is_empty() {
+            return Err(Error::validation("Attribute keys must not be empty"));
+        }
+        if spec.key == SCHEMA_VIOLATIONS_ATTRIBUTE {
+            return Err(Error::validation(format!(
+                "'{}' is reserved",
+                SCHEMA_VIOLATIONS_ATTRIBUTE
+            )));
+        }
+        if !seen.insert(spec.key.as_str()) {
+            return Err(Error::validation(format!("Duplicate attribute key '{}'", spec.key)));
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for target in &input.latency_targets {
+        if target.operation.trim().is_empty() {
+            return Err(Error::validation("Latency target operations must not be empty"));
+        }
+        if target.percentile.is_nan() || target.percentile <= 0.0 || target.percentile > 100.0 {
+            return Err(Error::validation(format!(
+                "Latency target percentile for '{}' must be in (0, 100]",
+                target.operation
+            )));
+        }
+        if target.target_ms.is_nan() || target.target_ms <= 0.0 {
+            return Err(Error::validation(format!(
+                "Latency target for '{}' must be positive",
+                target.operation
+            )));
+        }
+        if !seen.insert(target.operation.as_str()) {
+            return Err(Error::validation(format!(
+                "Duplicate latency target for '{}'",
+                target.operation
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AttributeSpec;
+    use serde_json::json;
+
+    fn create_test_schema() -> AttributeSchema {
+        let spec = |key: &str, attr_type, required| AttributeSpec {
+            key: key.to_string(),
+            attr_type,
+            required,
+            description: None,
+        };
+
+        AttributeSchema {
+            service_name: "agent".to_string(),
+            attributes: vec![
+                spec("user.id", AttributeType::String, true),
+                spec("agent.step", AttributeType::Integer, true),
+                spec("retrieval.score", AttributeType::Float, false),
+                spec("cache.hit", AttributeType::Boolean, false),
+            ],
+            latency_targets: vec![LatencyTarget {
+                operation: "llm.chat".to_string(),
+                percentile: 95.0,
+                target_ms: 2000.0,
+            }],
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn create_test_span(operation: &str, duration_ms: f64) -> Span {
+        serde_json::from_value(json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "span_id": "s1",
+            "trace_id": "t1",
+            "operation_name": operation,
+            "service_name": "agent",
+            "span_kind": "internal",
+            "started_at": "2026-01-01T00:00:00Z",
+            "duration_ms": duration_ms,
+            "status": "ok",
+            "attributes": {},
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_target_breach() {
+        let schema = create_test_schema();
+
+        let breach = target_breach(&schema, &create_test_span("llm.chat", 2500.0)).unwrap();
+        assert_eq!(breach.target_ms, 2000.0);
+        assert_eq!(breach.duration_ms, 2500.0);
+
+        assert!(target_breach(&schema, &create_test_span("llm.chat", 1500.0)).is_none());
+        assert!(target_breach(&schema, &create_test_span("tool.search", 9000.0)).is_none());
+    }
+
+    #[test]
+    fn test_valid_attributes() {
+        let mut attrs = json!({"user.id": "u1", "agent.step": 3, "retrieval.score": 0.8});
+        let violations = validate_attributes(&create_test_schema(), &mut attrs);
+        assert!(violations.is_empty());
+    }
+
+    #[test]
+    fn test_coerces_simple_mismatches() {
+        let mut attrs = json!({"user.id": 42, "agent.step": "7", "retrieval.score": "0.5", "cache.hit": "TRUE"});
+        let violations = validate_attributes(&create_test_schema(), &mut attrs);
+
+        assert!(violations.is_empty());
+        assert_eq!(attrs, json!({"user.id": "42", "agent.step": 7, "retrieval.score": 0.5, "cache.hit": true}));
+    }
+
+    #[test]
+    fn test_reports_missing_and_mismatched() {
+        let mut attrs = json!({"agent.step": "seven", "cache.hit": [1]});
+        let violations = validate_attributes(&create_test_schema(), &mut attrs);
+
+        assert_eq!(violations.len(), 3);
+        assert_eq!(violations[0].key, "user.id");
+        assert_eq!(violations[0].kind, ViolationKind::Missing);
+        assert_eq!(violations[1].key, "agent.step");
+        assert_eq!(violations[1].kind, ViolationKind::TypeMismatch);
+        assert_eq!(violations[1].actual.as_deref(), Some("string"));
+        assert_eq!(violations[2].key, "cache.hit");
+        assert_eq!(violations[2].actual.as_deref(), Some("array"));
+    }
+
+    #[test]
+    fn test_null_attributes_treated_as_empty() {
+        let mut attrs = Value::Null;
+        let violations = validate_attributes(&create_test_schema(), &mut attrs);
+        assert_eq!(violations.len(), 2);
+        assert!(attrs.is_object());
+    }
+}
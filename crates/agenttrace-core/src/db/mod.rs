@@ -1,14 +1,27 @@
 //! Database layer for AgentTrace
 //!
-//! Provides connections to TimescaleDB and Redis.
+//! Provides connections to TimescaleDB and Redis, or an embedded SQLite
+//! database with in-process streaming for local/dev mode.
 
 mod postgres;
+mod pubsub;
 mod redis;
+mod seed;
+pub(crate) mod sqlite;
+mod store;
 
-pub use postgres::{PostgresPool, SpanRepository};
+pub use postgres::{
+    PostgresPool, SchemaRepository, SpanRepository, COMPRESSION_TABLES, RETENTION_TABLES,
+};
+pub use pubsub::{LocalPubSub, PubSub, SpanPublisher};
 pub use redis::{RedisPool, RedisStreamer};
+pub use seed::{sample_traces, SpanGenerator, SpanProfile};
+pub use sqlite::SqliteStore;
+pub use store::SpanStore;
 
-use crate::config::Config;
+use std::sync::Arc;
+
+use crate::config::{CompressionConfig, Config, StorageKind};
 use crate::error::Result;
 
 /// Database connections bundle
@@ -34,6 +47,11 @@ impl Database {
         self.postgres.migrate().await
     }
 
+    /// Apply the configured chunk compression policies, returning how many changed
+    pub async fn apply_compression_policy(&self, config: &CompressionConfig) -> Result<usize> {
+        self.postgres.apply_compression_policy(config).await
+    }
+
     /// Check database health
     pub async fn health_check(&self) -> Result<()> {
         self.postgres.health_check().await?;
@@ -41,3 +59,48 @@ impl Database {
         Ok(())
     }
 }
+
+/// Span storage and streaming for the configured backend
+#[derive(Clone)]
+pub struct Storage {
+    /// Span store
+    pub spans: Arc<dyn SpanStore>,
+    /// Real-time streaming transport
+    pub pubsub: Arc<dyn PubSub>,
+    /// TimescaleDB/Redis connections, absent in SQLite mode
+    pub database: Option<Database>,
+}
+
+impl Storage {
+    /// Connect to the backend selected by `config.storage`
+    pub async fn new(config: &Config) -> Result<Self> {
+        match config.storage {
+            StorageKind::Postgres => Ok(Self::from_database(Database::new(config).await?)),
+            StorageKind::Sqlite => {
+                let store = SqliteStore::open(&config.sqlite.path).await?;
+                Ok(Self {
+                    spans: Arc::new(store),
+                    pubsub: Arc::new(LocalPubSub::new()),
+                    database: None,
+                })
+            }
+        }
+    }
+
+    /// Use existing TimescaleDB and Redis connections
+    pub fn from_database(db: Database) -> Self {
+        Self {
+            spans: Arc::new(SpanRepository::new(&db.postgres)),
+            pubsub: Arc::new(db.redis.clone()),
+            database: Some(db),
+        }
+    }
+
+    /// Check backend health
+    pub async fn health_check(&self) -> Result<()> {
+        match &self.database {
+            Some(db) => db.health_check().await,
+            None => self.spans.get_recent(1).await.map(|_| ()),
+        }
+    }
+}
@@ -1,46 +1,217 @@
 //! PostgreSQL/TimescaleDB connection and queries
 
-use chrono::{DateTime, Utc};
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use sqlx::Row;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::{PgPool, PgPoolOptions, Postgres};
+use sqlx::{QueryBuilder, Row, Transaction};
 use uuid::Uuid;
 
-use crate::config::DatabaseConfig;
+use crate::config::{CompressionConfig, DatabaseConfig, QueryLimitsConfig, RetentionConfig};
 use crate::error::{Error, Result};
 use crate::models::{
-    Span, SpanStatus, SpanKind,
-    CostMetric, ErrorMetric, ErrorStats, LatencyMetric, MetricsSummaryResponse,
-    SearchFilter, SortConfig, TraceSummary,
+    AggregateGroup, AggregateMetric, AggregateQuery, AggregateRow,
+    Span, SpanPayload, SpanPriority, SpanStatus, SpanKind, STALE_IN_PROGRESS_MESSAGE,
+    CostMetric, ErrorMetric, ErrorStats, LatencyMetric, MetricsSummaryResponse, OperationEfficiency, TokenEfficiency,
+    attribute_match_values, FacetCount, PageCursor, SearchFacets, SearchFilter, SortConfig, SpanQuery,
+    ServiceSummary, TextHighlight, TextSearchHit, TimeBreakdown, TimeBreakdownMetric, TraceDeletion,
+    TraceDeletionResult, TraceSearch, TraceSummary, SessionDetail, SessionSummary,
+    FeedbackGroup, FeedbackMetrics, FeedbackRating, TraceFeedback, WindowStats,
+    ColumnStorage, CompressionStats, DailyIngest, DailySpanCount, DatabaseStats, RetentionPolicy,
+    StorageReport, TableSize,
+    AttributeSchema, AttributeSchemaInput, AttributeViolationCount, SchemaComplianceReport,
+    SCHEMA_VIOLATIONS_ATTRIBUTE,
 };
 
 /// PostgreSQL connection pool
 #[derive(Clone)]
 pub struct PostgresPool {
     pool: PgPool,
+    replica: Option<PgPool>,
+    limits: QueryLimitsConfig,
 }
 
 impl PostgresPool {
-    /// Create a new PostgreSQL connection pool
+    /// Create a new PostgreSQL connection pool, plus a pool for the read
+    /// replica if one is configured
     pub async fn new(config: &DatabaseConfig) -> Result<Self> {
-        let pool = PgPoolOptions::new()
-            .max_connections(config.max_connections)
-            .min_connections(config.min_connections)
-            .connect(&config.url)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+        let pool = connect(config, &config.url).await?;
+        let replica = match &config.replica_url {
+            Some(url) => Some(connect(config, url).await?),
+            None => None,
+        };
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            replica,
+            limits: config.query_limits.clone(),
+        })
     }
 
     /// Run migrations
     pub async fn migrate(&self) -> Result<()> {
-        sqlx::migrate!("../../migrations")
+        MIGRATOR
             .run(&self.pool)
             .await
             .map_err(|e| Error::Database(format!("Migration failed: {}", e)))?;
         Ok(())
     }
 
+    /// Apply pending migrations up to and including `target` (all of them if
+    /// `None`), returning the versions applied
+    pub async fn migrate_to(&self, target: Option<i64>) -> Result<Vec<i64>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.lock().await.map_err(migrate_error)?;
+
+        let result = async {
+            conn.ensure_migrations_table().await.map_err(migrate_error)?;
+            if let Some(version) = conn.dirty_version().await.map_err(migrate_error)? {
+                return Err(Error::Database(format!(
+                    "Migration {} previously failed partway and must be fixed by hand",
+                    version
+                )));
+            }
+
+            let applied: HashSet<i64> = conn
+                .list_applied_migrations()
+                .await
+                .map_err(migrate_error)?
+                .into_iter()
+                .map(|m| m.version)
+                .collect();
+
+            let mut versions = Vec::new();
+            for migration in MIGRATOR.iter() {
+                if migration.migration_type.is_down_migration()
+                    || applied.contains(&migration.version)
+                    || target.is_some_and(|t| migration.version > t)
+                {
+                    continue;
+                }
+
+                conn.apply(migration).await.map_err(migrate_error)?;
+                versions.push(migration.version);
+            }
+
+            Ok(versions)
+        }
+        .await;
+
+        conn.unlock().await.map_err(migrate_error)?;
+        result
+    }
+
+    /// Revert the last `steps` applied migrations using the scripts in
+    /// `migrations/rollback`, returning the versions reverted
+    pub async fn rollback(&self, steps: usize) -> Result<Vec<i64>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await.map_err(migrate_error)?;
+
+        let mut applied = conn.list_applied_migrations().await.map_err(migrate_error)?;
+        applied.sort_by_key(|m| std::cmp::Reverse(m.version));
+
+        let mut versions = Vec::new();
+        for migration in applied.iter().take(steps) {
+            let sql = ROLLBACKS
+                .iter()
+                .find(|(version, _)| *version == migration.version)
+                .map(|(_, sql)| *sql)
+                .ok_or_else(|| {
+                    Error::validation(format!("Migration {} has no rollback script", migration.version))
+                })?;
+
+            let mut tx = self.pool.begin().await?;
+            sqlx::Executor::execute(&mut *tx, sql).await?;
+            sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            versions.push(migration.version);
+        }
+
+        Ok(versions)
+    }
+
+    /// Table sizes, span volume and schema version
+    pub async fn database_stats(&self, days: i64) -> Result<DatabaseStats> {
+        let tables = sqlx::query(
+            r#"
+            SELECT
+                c.relname::TEXT as table_name,
+                CASE WHEN h.hypertable_name IS NOT NULL
+                    THEN hypertable_size(c.oid::regclass)
+                    ELSE pg_total_relation_size(c.oid)
+                END::BIGINT as total_bytes,
+                h.num_chunks::BIGINT as chunk_count
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            LEFT JOIN timescaledb_information.hypertables h
+                ON h.hypertable_schema = n.nspname AND h.hypertable_name = c.relname
+            WHERE n.nspname = 'public' AND c.relkind IN ('r', 'p')
+            ORDER BY total_bytes DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| TableSize {
+            table: row.try_get("table_name").unwrap_or_default(),
+            total_bytes: row.try_get("total_bytes").unwrap_or(0),
+            chunk_count: row.try_get("chunk_count").ok().flatten(),
+        })
+        .collect();
+
+        let span_row = sqlx::query(
+            r#"
+            SELECT
+                approximate_row_count('spans') as span_count,
+                MIN(started_at) as oldest_span,
+                MAX(started_at) as newest_span
+            FROM spans
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let daily_spans = sqlx::query(
+            r#"
+            SELECT time_bucket('1 day', started_at) as day, COUNT(*) as span_count
+            FROM spans
+            WHERE started_at >= time_bucket('1 day', NOW()) - make_interval(days => $1::INT - 1)
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(days)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| DailySpanCount {
+            day: row.try_get("day").unwrap_or_else(|_| Utc::now()),
+            span_count: row.try_get("span_count").unwrap_or(0),
+        })
+        .collect();
+
+        let schema_version = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&self.pool)
+            .await
+            .ok()
+            .flatten();
+
+        Ok(DatabaseStats {
+            schema_version,
+            latest_migration: MIGRATOR.iter().map(|m| m.version).max(),
+            tables,
+            approximate_span_count: span_row.try_get("span_count").unwrap_or(0),
+            oldest_span: span_row.try_get("oldest_span").ok().flatten(),
+            newest_span: span_row.try_get("newest_span").ok().flatten(),
+            daily_spans,
+        })
+    }
+
     /// Health check
     pub async fn health_check(&self) -> Result<()> {
         sqlx::query("SELECT 1")
@@ -50,16 +221,361 @@ impl PostgresPool {
         Ok(())
     }
 
+    /// List retention policies on hypertables and continuous aggregates
+    pub async fn retention_policies(&self) -> Result<Vec<RetentionPolicy>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                j.hypertable_name as table_name,
+                EXTRACT(EPOCH FROM (j.config->>'drop_after')::INTERVAL)::DOUBLE PRECISION / 86400 as drop_after_days,
+                j.schedule_interval::TEXT as schedule_interval,
+                s.last_run_started_at as last_run_at,
+                s.last_run_status as last_run_status,
+                s.next_start as next_run_at
+            FROM timescaledb_information.jobs j
+            LEFT JOIN timescaledb_information.job_stats s ON s.job_id = j.job_id
+            WHERE j.proc_name = 'policy_retention'
+            ORDER BY j.hypertable_name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .map(|r| RetentionPolicy {
+                table: r.try_get("table_name").unwrap_or_default(),
+                drop_after_days: r.try_get("drop_after_days").ok(),
+                schedule_interval: r.try_get("schedule_interval").ok(),
+                last_run_at: r.try_get("last_run_at").ok(),
+                last_run_status: r.try_get("last_run_status").ok(),
+                next_run_at: r.try_get("next_run_at").ok(),
+            })
+            .collect())
+    }
+
+    /// Replace the retention policy on a table
+    pub async fn set_retention(&self, table: &str, days: u32) -> Result<()> {
+        if !RETENTION_TABLES.contains(&table) {
+            return Err(Error::validation(format!(
+                "Unknown retention table '{}' (expected one of: {})",
+                table,
+                RETENTION_TABLES.join(", ")
+            )));
+        }
+        if days == 0 {
+            return Err(Error::validation("Retention must be at least 1 day"));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        sqlx::query("SELECT remove_retention_policy($1::regclass, if_exists => TRUE)")
+            .bind(table)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        sqlx::query("SELECT add_retention_policy($1::regclass, make_interval(days => $2))")
+            .bind(table)
+            .bind(days as i32)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Apply configured retention, skipping tables whose policy already matches
+    pub async fn apply_retention(&self, config: &RetentionConfig) -> Result<usize> {
+        let current = self.retention_policies().await?;
+        let mut changed = 0;
+
+        for (table, days) in config.policies() {
+            let unchanged = current
+                .iter()
+                .any(|p| p.table == table && p.drop_after_days == Some(f64::from(days)));
+            if unchanged {
+                continue;
+            }
+
+            self.set_retention(table, days).await?;
+            changed += 1;
+        }
+
+        Ok(changed)
+    }
+
+    /// Delete spans that have outlived their priority tier's retention,
+    /// with their events
+    ///
+    /// Span chunks are only dropped after the longest tier, so shorter tiers
+    /// are pruned here, one chunk's time range per statement so each stays
+    /// within a single, possibly compressed, chunk.
+    pub async fn prune_spans_by_priority(&self, config: &RetentionConfig) -> Result<u64> {
+        let mut deleted = 0;
+
+        for (priority, days) in config.priority_pruning() {
+            let cutoff = Utc::now() - Duration::days(i64::from(days));
+            let ranges: Vec<(DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+                r#"
+                SELECT range_start, LEAST(range_end, $1)
+                FROM timescaledb_information.chunks
+                WHERE hypertable_name = 'spans' AND range_start < $1
+                ORDER BY range_start
+                "#,
+            )
+            .bind(cutoff)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+            for (start, end) in ranges {
+                let (spans,): (i64,) = sqlx::query_as(
+                    r#"
+                    WITH pruned AS (
+                        DELETE FROM spans
+                        WHERE priority = $1 AND started_at >= $2 AND started_at < $3
+                        RETURNING trace_id, span_id, started_at
+                    ),
+                    pruned_events AS (
+                        DELETE FROM span_events e
+                        USING pruned p
+                        WHERE e.trace_id = p.trace_id AND e.span_id = p.span_id
+                          AND e.timestamp >= p.started_at
+                    )
+                    SELECT COUNT(*) FROM pruned
+                    "#,
+                )
+                .bind(priority.as_str())
+                .bind(start)
+                .bind(end)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+
+                deleted += u64::try_from(spans).unwrap_or_default();
+            }
+        }
+
+        Ok(deleted)
+    }
+
+    /// Delete summaries of traces whose span chunks have been dropped
+    ///
+    /// Summaries go by the trace's start, a day after the longest span
+    /// retention to cover long-running traces, so the delete is an index
+    /// range scan rather than a check of every summary for spans. Spans
+    /// pruned individually by priority leave their trace's counts and sums
+    /// as they were. Does nothing when spans are kept forever.
+    pub async fn prune_trace_summaries(&self, config: &RetentionConfig) -> Result<u64> {
+        let Some(days) = config.span_chunk_days() else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - Duration::days(i64::from(days) + 1);
+
+        let result = sqlx::query("DELETE FROM trace_summaries WHERE started_at < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Delete feedback left on traces past the longest span retention
+    ///
+    /// Feedback goes by when it was left, on the same schedule as
+    /// [`Self::prune_trace_summaries`]. Does nothing when spans are kept
+    /// forever.
+    pub async fn prune_feedback(&self, config: &RetentionConfig) -> Result<u64> {
+        let Some(days) = config.span_chunk_days() else {
+            return Ok(0);
+        };
+        let cutoff = Utc::now() - Duration::days(i64::from(days) + 1);
+
+        let result = sqlx::query("DELETE FROM trace_feedback WHERE created_at < $1")
+            .bind(cutoff)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Delete sessions none of whose traces are left
+    ///
+    /// Run after [`Self::prune_trace_summaries`]: traces are dropped from
+    /// their session once their summary is gone, and a session goes with its
+    /// last trace. Sessions that keep some traces have their totals summed
+    /// again from the traces left.
+    pub async fn prune_sessions(&self) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+        let touched: Vec<String> = sqlx::query_scalar(
+            r#"
+            DELETE FROM session_traces st
+            WHERE NOT EXISTS (SELECT 1 FROM trace_summaries t WHERE t.trace_id = st.trace_id)
+            RETURNING st.session_id
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+        reconcile_sessions(&mut tx, &touched).await?;
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        let result = sqlx::query(
+            r#"
+            DELETE FROM sessions s
+            WHERE s.updated_at < NOW() - INTERVAL '1 day'
+              AND NOT EXISTS (SELECT 1 FROM session_traces st WHERE st.session_id = s.session_id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Compression policy and savings for each compressible table
+    pub async fn compression_stats(&self) -> Result<Vec<CompressionStats>> {
+        let mut stats = Vec::new();
+
+        for table in COMPRESSION_TABLES {
+            let row = sqlx::query(
+                r#"
+                SELECT
+                    COALESCE(s.total_chunks, 0)::BIGINT as total_chunks,
+                    COALESCE(s.number_compressed_chunks, 0)::BIGINT as compressed_chunks,
+                    s.before_compression_total_bytes::BIGINT as before_bytes,
+                    s.after_compression_total_bytes::BIGINT as after_bytes,
+                    (
+                        SELECT EXTRACT(EPOCH FROM (j.config->>'compress_after')::INTERVAL)::DOUBLE PRECISION / 86400
+                        FROM timescaledb_information.jobs j
+                        WHERE j.proc_name = 'policy_compression' AND j.hypertable_name = $1
+                        LIMIT 1
+                    ) as compress_after_days
+                FROM hypertable_compression_stats($1::regclass) s
+                "#,
+            )
+            .bind(table)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+            let Some(row) = row else { continue };
+
+            let before_bytes: Option<i64> = row.try_get("before_bytes").ok().flatten();
+            let after_bytes: Option<i64> = row.try_get("after_bytes").ok().flatten();
+            let compression_ratio = match (before_bytes, after_bytes) {
+                (Some(before), Some(after)) if after > 0 => Some(before as f64 / after as f64),
+                _ => None,
+            };
+
+            stats.push(CompressionStats {
+                table: table.to_string(),
+                compress_after_days: row.try_get("compress_after_days").ok().flatten(),
+                total_chunks: row.try_get("total_chunks").unwrap_or(0),
+                compressed_chunks: row.try_get("compressed_chunks").unwrap_or(0),
+                before_bytes,
+                after_bytes,
+                compression_ratio,
+            });
+        }
+
+        Ok(stats)
+    }
+
+    /// Replace the compression policy on a table, or remove it with `None`
+    pub async fn set_compression(&self, table: &str, after_days: Option<u32>) -> Result<()> {
+        if !COMPRESSION_TABLES.contains(&table) {
+            return Err(Error::validation(format!(
+                "Unknown compression table '{}' (expected one of: {})",
+                table,
+                COMPRESSION_TABLES.join(", ")
+            )));
+        }
+        if after_days == Some(0) {
+            return Err(Error::validation("Compression age must be at least 1 day"));
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        sqlx::query("SELECT remove_compression_policy($1::regclass, if_exists => TRUE)")
+            .bind(table)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        if let Some(days) = after_days {
+            sqlx::query("SELECT add_compression_policy($1::regclass, make_interval(days => $2))")
+                .bind(table)
+                .bind(days as i32)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+        }
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Apply configured compression, skipping tables whose policy already matches
+    pub async fn apply_compression_policy(&self, config: &CompressionConfig) -> Result<usize> {
+        let current = self.compression_stats().await?;
+        let mut changed = 0;
+
+        for (table, days) in config.policies() {
+            let unchanged = current
+                .iter()
+                .any(|s| s.table == table && s.compress_after_days == days.map(f64::from));
+            if unchanged {
+                continue;
+            }
+
+            self.set_compression(table, days).await?;
+            changed += 1;
+        }
+
+        Ok(changed)
+    }
+
     /// Get the underlying pool
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
+
+    /// Get the pool for read-only queries: the replica if configured,
+    /// otherwise the primary
+    pub fn read_pool(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+}
+
+async fn connect(config: &DatabaseConfig, url: &str) -> Result<PgPool> {
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .connect(url)
+        .await
+        .map_err(Error::from)
 }
 
 /// Repository for span operations
+///
+/// Writes go to the primary. Searches, metrics and trace reads go to the
+/// read replica when one is configured, so dashboard queries don't contend
+/// with ingest; they may lag the primary by the replication delay.
+///
+/// Searches are held to the configured [`QueryLimitsConfig`] and fail with
+/// [`Error::QueryLimit`] rather than scanning without bound.
 #[derive(Clone)]
 pub struct SpanRepository {
     pool: PgPool,
+    read_pool: PgPool,
+    limits: QueryLimitsConfig,
 }
 
 impl SpanRepository {
@@ -67,11 +583,18 @@ impl SpanRepository {
     pub fn new(pool: &PostgresPool) -> Self {
         Self {
             pool: pool.pool.clone(),
+            read_pool: pool.read_pool().clone(),
+            limits: pool.limits.clone(),
         }
     }
 
     /// Insert a single span
+    ///
+    /// An existing span with the same ID and start time is updated, and its
+    /// rows in `span_events` are replaced.
     pub async fn insert(&self, span: &Span) -> Result<()> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
         sqlx::query(
             r#"
             INSERT INTO spans (
@@ -79,10 +602,12 @@ impl SpanRepository {
                 span_kind, started_at, ended_at, duration_ms, status, status_message,
                 model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
                 cost_usd, tool_name, tool_input, tool_output, tool_duration_ms,
-                prompt_preview, completion_preview, attributes, events
+                prompt_preview, completion_preview, attributes, events, links, priority,
+                tokens_cached_in, session_id, user_id
             ) VALUES (
                 $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
-                $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26
+                $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29,
+                $30, $31
             )
             ON CONFLICT (span_id, started_at) DO UPDATE SET
                 ended_at = EXCLUDED.ended_at,
@@ -91,10 +616,12 @@ impl SpanRepository {
                 status_message = EXCLUDED.status_message,
                 tokens_in = EXCLUDED.tokens_in,
                 tokens_out = EXCLUDED.tokens_out,
+                tokens_cached_in = EXCLUDED.tokens_cached_in,
                 cost_usd = EXCLUDED.cost_usd,
                 tool_output = EXCLUDED.tool_output,
                 completion_preview = EXCLUDED.completion_preview,
-                events = EXCLUDED.events
+                events = EXCLUDED.events,
+                links = EXCLUDED.links
             "#,
         )
         .bind(&span.id)
@@ -123,93 +650,396 @@ impl SpanRepository {
         .bind(&span.completion_preview)
         .bind(&span.attributes)
         .bind(serde_json::to_value(&span.events).unwrap_or_default())
-        .execute(&self.pool)
+        .bind(serde_json::to_value(&span.links).unwrap_or_default())
+        .bind(span.priority.as_str())
+        .bind(&span.tokens_cached_in)
+        .bind(&span.session_id)
+        .bind(&span.user_id)
+        .execute(&mut *tx)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
 
+        sqlx::query("DELETE FROM span_events WHERE span_id = $1 AND trace_id = $2")
+            .bind(&span.span_id)
+            .bind(&span.trace_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        insert_events(&mut tx, &[span]).await?;
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
         Ok(())
     }
 
     /// Insert multiple spans in a batch
+    ///
+    /// All spans are sent as column arrays and inserted with a single
+    /// `INSERT ... SELECT FROM UNNEST(...)` statement. If that statement fails
+    /// (e.g., one span violates a constraint), the batch is retried row by row
+    /// so valid spans are still stored. Returns the number of spans inserted
+    /// or updated; spans that already exist are skipped unless the stored
+    /// span is still `in_progress`, in which case it is updated.
     pub async fn insert_batch(&self, spans: &[Span]) -> Result<usize> {
         if spans.is_empty() {
             return Ok(0);
         }
 
+        match self.insert_batch_unnest(spans).await {
+            Ok(inserted) => Ok(inserted),
+            Err(e) => {
+                tracing::warn!("Batch insert failed, retrying row by row: {}", e);
+                self.insert_batch_rowwise(spans).await
+            }
+        }
+    }
+
+    /// Insert a batch with a single multi-row statement
+    async fn insert_batch_unnest(&self, spans: &[Span]) -> Result<usize> {
+        // A statement can't update the same row twice, so only the latest
+        // version of each span is written
+        let mut latest: HashMap<(&str, DateTime<Utc>), usize> = HashMap::with_capacity(spans.len());
+        for (i, span) in spans.iter().enumerate() {
+            latest.insert((span.span_id.as_str(), span.started_at), i);
+        }
+        let spans: Vec<&Span> = spans
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| latest.get(&(s.span_id.as_str(), s.started_at)) == Some(i))
+            .map(|(_, s)| s)
+            .collect();
+
+        let n = spans.len();
+        let mut ids = Vec::with_capacity(n);
+        let mut span_ids = Vec::with_capacity(n);
+        let mut trace_ids = Vec::with_capacity(n);
+        let mut parent_span_ids = Vec::with_capacity(n);
+        let mut operation_names = Vec::with_capacity(n);
+        let mut service_names = Vec::with_capacity(n);
+        let mut span_kinds = Vec::with_capacity(n);
+        let mut started_ats = Vec::with_capacity(n);
+        let mut ended_ats = Vec::with_capacity(n);
+        let mut durations = Vec::with_capacity(n);
+        let mut statuses = Vec::with_capacity(n);
+        let mut status_messages = Vec::with_capacity(n);
+        let mut model_names = Vec::with_capacity(n);
+        let mut model_providers = Vec::with_capacity(n);
+        let mut tokens_in = Vec::with_capacity(n);
+        let mut tokens_out = Vec::with_capacity(n);
+        let mut tokens_reasoning = Vec::with_capacity(n);
+        let mut costs = Vec::with_capacity(n);
+        let mut tool_names = Vec::with_capacity(n);
+        let mut tool_inputs = Vec::with_capacity(n);
+        let mut tool_outputs = Vec::with_capacity(n);
+        let mut tool_durations = Vec::with_capacity(n);
+        let mut prompt_previews = Vec::with_capacity(n);
+        let mut completion_previews = Vec::with_capacity(n);
+        let mut attributes = Vec::with_capacity(n);
+        let mut events = Vec::with_capacity(n);
+        let mut links = Vec::with_capacity(n);
+        let mut priorities = Vec::with_capacity(n);
+        let mut tokens_cached_in = Vec::with_capacity(n);
+        let mut session_ids = Vec::with_capacity(n);
+        let mut user_ids = Vec::with_capacity(n);
+
+        for span in &spans {
+            ids.push(span.id);
+            span_ids.push(span.span_id.clone());
+            trace_ids.push(span.trace_id.clone());
+            parent_span_ids.push(span.parent_span_id.clone());
+            operation_names.push(span.operation_name.clone());
+            service_names.push(span.service_name.clone());
+            span_kinds.push(span_kind_to_str(&span.span_kind));
+            started_ats.push(span.started_at);
+            ended_ats.push(span.ended_at);
+            durations.push(span.duration_ms);
+            statuses.push(span_status_to_str(&span.status));
+            status_messages.push(span.status_message.clone());
+            model_names.push(span.model_name.clone());
+            model_providers.push(span.model_provider.clone());
+            tokens_in.push(span.tokens_in);
+            tokens_out.push(span.tokens_out);
+            tokens_reasoning.push(span.tokens_reasoning);
+            costs.push(span.cost_usd);
+            tool_names.push(span.tool_name.clone());
+            tool_inputs.push(span.tool_input.clone());
+            tool_outputs.push(span.tool_output.clone());
+            tool_durations.push(span.tool_duration_ms);
+            prompt_previews.push(span.prompt_preview.clone());
+            completion_previews.push(span.completion_preview.clone());
+            attributes.push(span.attributes.clone());
+            events.push(serde_json::to_value(&span.events).unwrap_or_default());
+            links.push(serde_json::to_value(&span.links).unwrap_or_default());
+            priorities.push(span.priority.as_str());
+            tokens_cached_in.push(span.tokens_cached_in);
+            session_ids.push(span.session_id.clone());
+            user_ids.push(span.user_id.clone());
+        }
+
         let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        let sql = format!(
+            r#"
+            INSERT INTO spans (
+                id, span_id, trace_id, parent_span_id, operation_name, service_name,
+                span_kind, started_at, ended_at, duration_ms, status, status_message,
+                model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
+                cost_usd, tool_name, tool_input, tool_output, tool_duration_ms,
+                prompt_preview, completion_preview, attributes, events, links, priority,
+                tokens_cached_in, session_id, user_id
+            )
+            SELECT * FROM UNNEST(
+                $1::uuid[], $2::varchar[], $3::varchar[], $4::varchar[], $5::varchar[],
+                $6::varchar[], $7::varchar[], $8::timestamptz[], $9::timestamptz[],
+                $10::float8[], $11::varchar[], $12::text[], $13::varchar[], $14::varchar[],
+                $15::int4[], $16::int4[], $17::int4[], $18::float8[], $19::varchar[],
+                $20::jsonb[], $21::jsonb[], $22::float8[], $23::text[], $24::text[],
+                $25::jsonb[], $26::jsonb[], $27::jsonb[], $28::varchar[], $29::int4[],
+                $30::varchar[], $31::varchar[]
+            )
+            {}
+            "#,
+            SPAN_UPSERT
+        );
+        let rows = sqlx::query(&sql)
+            .bind(ids)
+            .bind(span_ids)
+            .bind(trace_ids)
+            .bind(parent_span_ids)
+            .bind(operation_names)
+            .bind(service_names)
+            .bind(span_kinds)
+            .bind(started_ats)
+            .bind(ended_ats)
+            .bind(durations)
+            .bind(statuses)
+            .bind(status_messages)
+            .bind(model_names)
+            .bind(model_providers)
+            .bind(tokens_in)
+            .bind(tokens_out)
+            .bind(tokens_reasoning)
+            .bind(costs)
+            .bind(tool_names)
+            .bind(tool_inputs)
+            .bind(tool_outputs)
+            .bind(tool_durations)
+            .bind(prompt_previews)
+            .bind(completion_previews)
+            .bind(attributes)
+            .bind(events)
+            .bind(links)
+            .bind(priorities)
+            .bind(tokens_cached_in)
+            .bind(session_ids)
+            .bind(user_ids)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        // Only copy events for spans that were actually written, so redelivered
+        // spans don't duplicate their events
+        let written: HashMap<String, bool> = rows
+            .iter()
+            .filter_map(|r| Some((r.try_get("span_id").ok()?, r.try_get("inserted").ok()?)))
+            .collect();
+        let written_spans: Vec<&Span> = spans
+            .iter()
+            .copied()
+            .filter(|s| written.contains_key(&s.span_id))
+            .collect();
+        let (new_spans, updated_spans): (Vec<&Span>, Vec<&Span>) =
+            written_spans.iter().copied().partition(|s| written[&s.span_id]);
+        insert_events(&mut tx, &new_spans).await?;
+        replace_events(&mut tx, &updated_spans).await?;
+
+        // In-progress spans are left out of the totals until their final
+        // update is written, so a streaming call is counted once
+        let final_spans: Vec<&Span> = written_spans.into_iter().filter(|s| !s.is_in_progress()).collect();
+        upsert_trace_summaries(&mut tx, &final_spans).await?;
+        upsert_service_activity(&mut tx, &final_spans).await?;
+        upsert_sessions(&mut tx, &final_spans).await?;
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(rows.len())
+    }
+
+    /// Insert a batch one statement per span, skipping spans that fail
+    async fn insert_batch_rowwise(&self, spans: &[Span]) -> Result<usize> {
         let mut count = 0;
 
         for span in spans {
-            let result = sqlx::query(
+            let sql = format!(
                 r#"
                 INSERT INTO spans (
                     id, span_id, trace_id, parent_span_id, operation_name, service_name,
                     span_kind, started_at, ended_at, duration_ms, status, status_message,
                     model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
                     cost_usd, tool_name, tool_input, tool_output, tool_duration_ms,
-                    prompt_preview, completion_preview, attributes, events
+                    prompt_preview, completion_preview, attributes, events, links, priority,
+                    tokens_cached_in, session_id, user_id
                 ) VALUES (
                     $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
-                    $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26
+                    $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29,
+                    $30, $31
                 )
-                ON CONFLICT (span_id, started_at) DO NOTHING
+                {}
                 "#,
-            )
-            .bind(&span.id)
-            .bind(&span.span_id)
-            .bind(&span.trace_id)
-            .bind(&span.parent_span_id)
-            .bind(&span.operation_name)
-            .bind(&span.service_name)
-            .bind(span_kind_to_str(&span.span_kind))
-            .bind(&span.started_at)
-            .bind(&span.ended_at)
-            .bind(&span.duration_ms)
-            .bind(span_status_to_str(&span.status))
-            .bind(&span.status_message)
-            .bind(&span.model_name)
-            .bind(&span.model_provider)
-            .bind(&span.tokens_in)
-            .bind(&span.tokens_out)
-            .bind(&span.tokens_reasoning)
-            .bind(&span.cost_usd)
-            .bind(&span.tool_name)
-            .bind(&span.tool_input)
-            .bind(&span.tool_output)
-            .bind(&span.tool_duration_ms)
-            .bind(&span.prompt_preview)
-            .bind(&span.completion_preview)
-            .bind(&span.attributes)
-            .bind(serde_json::to_value(&span.events).unwrap_or_default())
-            .execute(&mut *tx)
-            .await;
-
-            if result.is_ok() {
-                count += 1;
+                SPAN_UPSERT
+            );
+            let result = sqlx::query(&sql)
+                .bind(span.id)
+                .bind(&span.span_id)
+                .bind(&span.trace_id)
+                .bind(&span.parent_span_id)
+                .bind(&span.operation_name)
+                .bind(&span.service_name)
+                .bind(span_kind_to_str(&span.span_kind))
+                .bind(span.started_at)
+                .bind(span.ended_at)
+                .bind(span.duration_ms)
+                .bind(span_status_to_str(&span.status))
+                .bind(&span.status_message)
+                .bind(&span.model_name)
+                .bind(&span.model_provider)
+                .bind(span.tokens_in)
+                .bind(span.tokens_out)
+                .bind(span.tokens_reasoning)
+                .bind(span.cost_usd)
+                .bind(&span.tool_name)
+                .bind(&span.tool_input)
+                .bind(&span.tool_output)
+                .bind(span.tool_duration_ms)
+                .bind(&span.prompt_preview)
+                .bind(&span.completion_preview)
+                .bind(&span.attributes)
+                .bind(serde_json::to_value(&span.events).unwrap_or_default())
+                .bind(serde_json::to_value(&span.links).unwrap_or_default())
+                .bind(span.priority.as_str())
+                .bind(span.tokens_cached_in)
+                .bind(&span.session_id)
+                .bind(&span.user_id)
+                .fetch_optional(&self.pool)
+                .await;
+
+            match result {
+                Ok(Some(row)) => {
+                    count += 1;
+                    let inserted: bool = row.try_get("inserted").unwrap_or(true);
+                    let mut conn = self.pool.acquire().await.map_err(|e| Error::Database(e.to_string()))?;
+                    let events = if inserted {
+                        insert_events(&mut conn, &[span]).await
+                    } else {
+                        replace_events(&mut conn, &[span]).await
+                    };
+                    if let Err(e) = events {
+                        tracing::warn!("Failed to insert events for span {}: {}", span.span_id, e);
+                    }
+                    if span.is_in_progress() {
+                        continue;
+                    }
+                    if let Err(e) = upsert_trace_summaries(&mut conn, &[span]).await {
+                        tracing::warn!("Failed to update trace summary for span {}: {}", span.span_id, e);
+                    }
+                    if let Err(e) = upsert_service_activity(&mut conn, &[span]).await {
+                        tracing::warn!("Failed to update service activity for span {}: {}", span.span_id, e);
+                    }
+                    if let Err(e) = upsert_sessions(&mut conn, &[span]).await {
+                        tracing::warn!("Failed to update session for span {}: {}", span.span_id, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to insert span {}: {}", span.span_id, e),
             }
         }
 
-        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
         Ok(count)
     }
 
-    /// Get a span by ID
-    pub async fn get_by_id(&self, id: &Uuid) -> Result<Option<Span>> {
-        let row = sqlx::query(
+    /// Get the stored `in_progress` spans with any of these span IDs
+    ///
+    /// Reads from the primary, since the start event may have been written
+    /// moments ago.
+    pub async fn get_in_progress(&self, span_ids: &[String]) -> Result<Vec<Span>> {
+        if span_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            "SELECT {} FROM spans WHERE span_id = ANY($1) AND status = 'in_progress'",
+            SPAN_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(span_ids)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    /// Close `in_progress` spans started before a time, returning how many
+    /// were closed
+    ///
+    /// A start event whose span was also stored final under another start
+    /// time is a duplicate and is deleted. The rest are marked as errors and
+    /// added to their trace, service and session totals, which left them out
+    /// while in progress.
+    pub async fn close_stale_in_progress(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        let deleted = sqlx::query(
             r#"
-            SELECT id, span_id, trace_id, parent_span_id, operation_name, service_name,
-                   span_kind, started_at, ended_at, duration_ms, status, status_message,
-                   model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
-                   CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
-                   tool_name, tool_input, tool_output, tool_duration_ms,
-                   prompt_preview, completion_preview, attributes, events
-            FROM spans WHERE id = $1
+            DELETE FROM spans s
+            WHERE s.status = 'in_progress' AND s.started_at < $1
+              AND EXISTS (
+                  SELECT 1 FROM spans f
+                  WHERE f.span_id = s.span_id AND f.trace_id = s.trace_id AND f.status <> 'in_progress'
+              )
             "#,
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .bind(before)
+        .execute(&mut *tx)
         .await
-        .map_err(|e| Error::Database(e.to_string()))?;
+        .map_err(|e| Error::Database(e.to_string()))?
+        .rows_affected();
+
+        let sql = format!(
+            r#"
+            UPDATE spans
+            SET status = 'error', status_message = COALESCE(status_message, $2)
+            WHERE status = 'in_progress' AND started_at < $1
+            RETURNING {}
+            "#,
+            SPAN_COLUMNS
+        );
+        let closed = sqlx::query(&sql)
+            .bind(before)
+            .bind(STALE_IN_PROGRESS_MESSAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?
+            .iter()
+            .map(row_to_span)
+            .collect::<Result<Vec<_>>>()?;
+
+        let closed: Vec<&Span> = closed.iter().collect();
+        upsert_trace_summaries(&mut tx, &closed).await?;
+        upsert_service_activity(&mut tx, &closed).await?;
+        upsert_sessions(&mut tx, &closed).await?;
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(deleted + closed.len() as u64)
+    }
+
+    /// Get a span by ID
+    pub async fn get_by_id(&self, id: &Uuid) -> Result<Option<Span>> {
+        let sql = format!("SELECT {} FROM spans WHERE id = $1", SPAN_COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(id)
+            .fetch_optional(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
 
         match row {
             Some(row) => Ok(Some(row_to_span(&row)?)),
@@ -219,43 +1049,165 @@ impl SpanRepository {
 
     /// Get spans by trace ID
     pub async fn get_by_trace_id(&self, trace_id: &str) -> Result<Vec<Span>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, span_id, trace_id, parent_span_id, operation_name, service_name,
-                   span_kind, started_at, ended_at, duration_ms, status, status_message,
-                   model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
-                   CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
-                   tool_name, tool_input, tool_output, tool_duration_ms,
-                   prompt_preview, completion_preview, attributes, events
-            FROM spans WHERE trace_id = $1 ORDER BY started_at ASC
-            "#,
-        )
-        .bind(trace_id)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::Database(e.to_string()))?;
+        let sql = format!(
+            "SELECT {} FROM spans WHERE trace_id = $1 ORDER BY started_at ASC",
+            SPAN_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(trace_id)
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
 
         rows.iter().map(row_to_span).collect()
     }
 
-    /// Get recent spans
-    pub async fn get_recent(&self, limit: i64) -> Result<Vec<Span>> {
+    /// Get all spans of several traces in start order
+    pub async fn get_by_trace_ids(&self, trace_ids: &[String]) -> Result<Vec<Span>> {
+        let sql = format!(
+            "SELECT {} FROM spans WHERE trace_id = ANY($1) ORDER BY started_at ASC",
+            SPAN_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(trace_ids)
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    /// Get all spans started within `[since, until)`, in start order
+    ///
+    /// Reads from the primary so archival never misses spans the replica
+    /// hasn't caught up on.
+    pub async fn get_in_range(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<Span>> {
+        let sql = format!(
+            "SELECT {} FROM spans WHERE started_at >= $1 AND started_at < $2 ORDER BY started_at ASC",
+            SPAN_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(since)
+            .bind(until)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    /// Get the spans of a trace that match a query, in start order
+    pub async fn search_in_trace(&self, trace_id: &str, query: &SpanQuery) -> Result<Vec<Span>> {
+        let mut filter = SpanFilter::from_query(query)?;
+        filter.bind("trace_id = $?", trace_id);
+
+        let mut sql = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM spans", SPAN_COLUMNS));
+        filter.push_where(&mut sql);
+        sql.push(" ORDER BY started_at ASC");
+
+        let rows = sql
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    /// Count spans matching a query per service, model, status and
+    /// operation in one grouped pass
+    ///
+    /// Spans without a model are left out of the model facet.
+    pub async fn search_facets(
+        &self,
+        query: &SpanQuery,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SearchFacets> {
+        self.check_range(Some(since), Some(until))?;
+
+        let mut filter = SpanFilter::from_query(query)?;
+        filter.bind("started_at >= $?", since);
+        filter.bind("started_at <= $?", until);
+
+        let mut sql = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT
+                service_name, model_name, status, operation_name,
+                GROUPING(service_name, model_name, status, operation_name) as facet,
+                COUNT(*) as count
+            FROM spans"#,
+        );
+        filter.push_where(&mut sql);
+        sql.push(
+            r#"
+            GROUP BY GROUPING SETS ((service_name), (model_name), (status), (operation_name), ())"#,
+        );
+
+        let mut tx = self.read_tx().await?;
+        let rows = sql
+            .build()
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| self.timeout_error(e))?;
+        tx.commit().await?;
+
+        // GROUPING() sets a bit for each column left out of the row's set,
+        // first column highest
+        let mut facets = SearchFacets::default();
+        for row in &rows {
+            let count: i64 = row.try_get("count")?;
+            let (target, column) = match row.try_get::<i32, _>("facet")? {
+                0b0111 => (&mut facets.services, "service_name"),
+                0b1011 => (&mut facets.models, "model_name"),
+                0b1101 => (&mut facets.statuses, "status"),
+                0b1110 => (&mut facets.operations, "operation_name"),
+                _ => {
+                    facets.total = count;
+                    continue;
+                }
+            };
+
+            if let Some(value) = row.try_get::<Option<String>, _>(column)? {
+                target.push(FacetCount { value, count });
+            }
+        }
+
+        facets.truncate();
+        Ok(facets)
+    }
+
+    /// Get IDs of other traces containing spans that link into a trace
+    pub async fn get_linking_trace_ids(&self, trace_id: &str) -> Result<Vec<String>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, span_id, trace_id, parent_span_id, operation_name, service_name,
-                   span_kind, started_at, ended_at, duration_ms, status, status_message,
-                   model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
-                   CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
-                   tool_name, tool_input, tool_output, tool_duration_ms,
-                   prompt_preview, completion_preview, attributes, events
-            FROM spans ORDER BY started_at DESC LIMIT $1
+            SELECT DISTINCT trace_id FROM spans
+            WHERE links @> jsonb_build_array(jsonb_build_object('trace_id', $1::text))
+              AND trace_id <> $1
+            ORDER BY trace_id
+            LIMIT 100
             "#,
         )
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .bind(trace_id)
+        .fetch_all(&self.read_pool)
         .await
         .map_err(|e| Error::Database(e.to_string()))?;
 
+        Ok(rows.iter().filter_map(|r| r.try_get("trace_id").ok()).collect())
+    }
+
+    /// Get recent spans
+    pub async fn get_recent(&self, limit: i64) -> Result<Vec<Span>> {
+        let sql = format!(
+            "SELECT {} FROM spans ORDER BY started_at DESC LIMIT $1",
+            SPAN_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(limit)
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
         rows.iter().map(row_to_span).collect()
     }
 
@@ -264,6 +1216,9 @@ impl SpanRepository {
     // =========================================================================
 
     /// Search spans with filters
+    ///
+    /// With a `cursor`, the page starts after that span instead of at
+    /// `offset`; cursors require sorting by `started_at`.
     #[allow(clippy::too_many_arguments)]
     pub async fn search(
         &self,
@@ -271,6 +1226,9 @@ impl SpanRepository {
         service: Option<&str>,
         model: Option<&str>,
         status: Option<&str>,
+        priority: Option<SpanPriority>,
+        attributes: &[(String, String)],
+        has_negative_feedback: Option<bool>,
         min_duration: Option<f64>,
         max_duration: Option<f64>,
         min_cost: Option<f64>,
@@ -281,86 +1239,81 @@ impl SpanRepository {
         sort_desc: bool,
         limit: i64,
         offset: i64,
+        cursor: Option<&PageCursor>,
     ) -> Result<(Vec<Span>, i64)> {
-        let mut conditions = vec!["1=1".to_string()];
+        let since = Some(self.check_range(since, until)?);
+        self.check_page(limit, offset)?;
+
+        let sort_column = sortable_column(sort_by)?;
+        if cursor.is_some() && sort_column != "started_at" {
+            return Err(Error::validation("Cursors can only be used when sorting by started_at"));
+        }
+        let mut filter = SpanFilter::default();
 
         if let Some(q) = query {
-            conditions.push(format!(
-                "(operation_name ILIKE '%{}%' OR prompt_preview ILIKE '%{}%' OR completion_preview ILIKE '%{}%')",
-                q.replace('\'', "''"), q.replace('\'', "''"), q.replace('\'', "''")
-            ));
+            let pattern = format!("%{}%", escape_like(q));
+            filter.push(
+                "(operation_name ILIKE $? OR prompt_preview ILIKE $? OR completion_preview ILIKE $?)",
+                vec![pattern.clone().into(), pattern.clone().into(), pattern.into()],
+            );
         }
 
         if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+            filter.bind("service_name = $?", svc);
         }
 
         if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+            filter.bind("model_name = $?", m);
         }
 
         if let Some(s) = status {
-            conditions.push(format!("status = '{}'", s.replace('\'', "''")));
+            filter.bind("status = $?", s);
+        }
+
+        if let Some(p) = priority {
+            filter.bind("priority = $?", p.as_str());
+        }
+
+        for (key, value) in attributes {
+            filter.attribute(key, "eq", &serde_json::Value::String(value.clone()))?;
+        }
+
+        if let Some(has) = has_negative_feedback {
+            filter.negative_feedback(has);
         }
 
         if let Some(min) = min_duration {
-            conditions.push(format!("duration_ms >= {}", min));
+            filter.bind("duration_ms >= $?", min);
         }
 
         if let Some(max) = max_duration {
-            conditions.push(format!("duration_ms <= {}", max));
+            filter.bind("duration_ms <= $?", max);
         }
 
         if let Some(min) = min_cost {
-            conditions.push(format!("cost_usd >= {}", min));
+            filter.bind("cost_usd >= $?", min);
         }
 
         if let Some(max) = max_cost {
-            conditions.push(format!("cost_usd <= {}", max));
+            filter.bind("cost_usd <= $?", max);
         }
 
         if let Some(start) = since {
-            conditions.push(format!("started_at >= '{}'", start.format("%Y-%m-%d %H:%M:%S")));
+            filter.bind("started_at >= $?", start);
         }
 
         if let Some(end) = until {
-            conditions.push(format!("started_at <= '{}'", end.format("%Y-%m-%d %H:%M:%S")));
+            filter.bind("started_at <= $?", end);
         }
 
-        let where_clause = conditions.join(" AND ");
         let order = if sort_desc { "DESC" } else { "ASC" };
-
-        let count_sql = format!("SELECT COUNT(*) as cnt FROM spans WHERE {}", where_clause);
-        let count_row = sqlx::query(&count_sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
-        let total: i64 = count_row.try_get("cnt").unwrap_or(0);
-
-        let sql = format!(
-            r#"
-            SELECT id, span_id, trace_id, parent_span_id, operation_name, service_name,
-                   span_kind, started_at, ended_at, duration_ms, status, status_message,
-                   model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
-                   CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
-                   tool_name, tool_input, tool_output, tool_duration_ms,
-                   prompt_preview, completion_preview, attributes, events
-            FROM spans WHERE {} ORDER BY {} {} LIMIT {} OFFSET {}
-            "#,
-            where_clause, sort_by, order, limit, offset
-        );
-
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
-
-        let spans: Vec<Span> = rows.iter().filter_map(|r| row_to_span(r).ok()).collect();
-
-        Ok((spans, total))
+        self.fetch_filtered(&filter, sort_column, order, limit, offset, cursor).await
     }
 
     /// Advanced search with complex filters
+    ///
+    /// Time bounds are ordinary filters here, so only the page and the
+    /// statement timeout are checked.
     pub async fn advanced_search(
         &self,
         filters: &[SearchFilter],
@@ -368,645 +1321,2997 @@ impl SpanRepository {
         limit: i64,
         offset: i64,
     ) -> Result<(Vec<Span>, i64)> {
-        let mut conditions = vec!["1=1".to_string()];
-
-        for filter in filters {
-            let op = match filter.operator.as_str() {
-                "eq" => "=",
-                "ne" => "!=",
-                "gt" => ">",
-                "gte" => ">=",
-                "lt" => "<",
-                "lte" => "<=",
-                "contains" => "ILIKE",
-                _ => "=",
-            };
-
-            let value_str = match &filter.value {
-                serde_json::Value::String(s) => {
-                    if filter.operator == "contains" {
-                        format!("'%{}%'", s.replace('\'', "''"))
-                    } else {
-                        format!("'{}'", s.replace('\'', "''"))
-                    }
-                }
-                serde_json::Value::Number(n) => n.to_string(),
-                serde_json::Value::Bool(b) => b.to_string(),
-                _ => continue,
-            };
+        self.check_page(limit, offset)?;
 
-            conditions.push(format!("{} {} {}", filter.field, op, value_str));
-        }
+        let filter = SpanFilter::from_search_filters(filters)?;
 
-        let where_clause = conditions.join(" AND ");
         let (sort_field, sort_desc) = sort
             .map(|s| (s.field.as_str(), s.descending))
             .unwrap_or(("started_at", true));
+        let sort_column = sortable_column(sort_field)?;
         let order = if sort_desc { "DESC" } else { "ASC" };
 
-        let count_sql = format!("SELECT COUNT(*) as cnt FROM spans WHERE {}", where_clause);
-        let count_row = sqlx::query(&count_sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
-        let total: i64 = count_row.try_get("cnt").unwrap_or(0);
+        self.fetch_filtered(&filter, sort_column, order, limit, offset, None).await
+    }
 
-        let sql = format!(
-            r#"
-            SELECT id, span_id, trace_id, parent_span_id, operation_name, service_name,
-                   span_kind, started_at, ended_at, duration_ms, status, status_message,
-                   model_name, model_provider, tokens_in, tokens_out, tokens_reasoning,
-                   CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
-                   tool_name, tool_input, tool_output, tool_duration_ms,
-                   prompt_preview, completion_preview, attributes, events
-            FROM spans WHERE {} ORDER BY {} {} LIMIT {} OFFSET {}
-            "#,
-            where_clause, sort_field, order, limit, offset
+    /// Full-text search over operation names, prompts and completions,
+    /// ordered by relevance
+    ///
+    /// `query` uses web search syntax: quoted phrases, `or` and `-term`.
+    pub async fn text_search(
+        &self,
+        query: &str,
+        service: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<TextSearchHit>, i64)> {
+        let since = Some(self.check_range(since, until)?);
+        self.check_page(limit, offset)?;
+
+        let mut filter = SpanFilter::default();
+        filter.bind(
+            &format!("{} @@ websearch_to_tsquery('english', $?)", TEXT_SEARCH_VECTOR),
+            query,
         );
 
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+        if let Some(svc) = service {
+            filter.bind("service_name = $?", svc);
+        }
+
+        if let Some(start) = since {
+            filter.bind("started_at >= $?", start);
+        }
+
+        if let Some(end) = until {
+            filter.bind("started_at <= $?", end);
+        }
+
+        let mut tx = self.read_tx().await?;
+        let total = self.count_capped(&mut tx, &filter).await?;
+
+        let headlines: Vec<String> = TEXT_SEARCH_FIELDS
+            .iter()
+            .map(|f| format!("ts_headline('english', {f}, q, '{TEXT_HEADLINE_OPTIONS}') AS {f}_headline"))
+            .collect();
+
+        let mut sql = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {}, ts_rank({}, q)::DOUBLE PRECISION AS rank, {} FROM spans, websearch_to_tsquery('english', ",
+            SPAN_COLUMNS,
+            TEXT_SEARCH_VECTOR,
+            headlines.join(", "),
+        ));
+        sql.push_bind(query.to_string());
+        sql.push(") AS q");
+        filter.push_where(&mut sql);
+        sql.push(" ORDER BY rank DESC, started_at DESC");
+        sql.push(" LIMIT ").push_bind(limit);
+        sql.push(" OFFSET ").push_bind(offset);
+
+        let rows = sql
+            .build()
+            .fetch_all(&mut *tx)
             .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+            .map_err(|e| self.timeout_error(e))?;
+        tx.commit().await?;
+
+        let mut hits = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let highlights = TEXT_SEARCH_FIELDS
+                .iter()
+                .filter_map(|field| {
+                    let fragment: Option<String> = row.try_get(format!("{}_headline", field).as_str()).ok()?;
+                    fragment
+                        .filter(|f| f.contains("<mark>"))
+                        .map(|fragment| TextHighlight { field: (*field).to_string(), fragment })
+                })
+                .collect();
+
+            hits.push(TextSearchHit {
+                span: row_to_span(row)?,
+                rank: row.try_get("rank").unwrap_or_default(),
+                highlights,
+            });
+        }
+
+        Ok((hits, total))
+    }
+
+    /// Count and fetch a page of spans matching a filter
+    ///
+    /// The total counts every match, not just those after `after`. Rows are
+    /// ordered by `id` within equal sort values so pages never overlap.
+    async fn fetch_filtered(
+        &self,
+        filter: &SpanFilter,
+        sort_column: &str,
+        order: &str,
+        limit: i64,
+        offset: i64,
+        after: Option<&PageCursor>,
+    ) -> Result<(Vec<Span>, i64)> {
+        let mut tx = self.read_tx().await?;
+        let total = self.count_capped(&mut tx, filter).await?;
+
+        let mut page = filter.clone();
+        if let Some(cursor) = after {
+            let op = if order == "DESC" { "<" } else { ">" };
+            page.push(
+                &format!("(started_at, id) {} ($?, $?)", op),
+                vec![cursor.started_at.into(), cursor.id.into()],
+            );
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM spans", SPAN_COLUMNS));
+        page.push_where(&mut query);
+        query.push(format!(" ORDER BY {} {}, id {}", sort_column, order, order));
+        query.push(" LIMIT ").push_bind(limit);
+        query.push(" OFFSET ").push_bind(offset);
+
+        let rows = query
+            .build()
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| self.timeout_error(e))?;
+        tx.commit().await?;
 
         let spans: Vec<Span> = rows.iter().filter_map(|r| row_to_span(r).ok()).collect();
 
         Ok((spans, total))
     }
 
-    /// List traces with summaries
+    /// Count spans matching a filter, stopping at the row ceiling
+    async fn count_capped(
+        &self,
+        tx: &mut Transaction<'static, Postgres>,
+        filter: &SpanFilter,
+    ) -> Result<i64> {
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) as cnt FROM (SELECT 1 FROM spans");
+        filter.push_where(&mut query);
+        query.push(" LIMIT ").push_bind(self.limits.max_rows);
+        query.push(") AS matched");
+
+        let row = query
+            .build()
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| self.timeout_error(e))?;
+
+        Ok(row.try_get("cnt").unwrap_or(0))
+    }
+
+    /// Begin a read-only transaction on the read pool with the search
+    /// statement timeout applied
+    async fn read_tx(&self) -> Result<Transaction<'static, Postgres>> {
+        let mut tx = self.read_pool.begin().await?;
+        // SET doesn't take bind parameters; the value is a plain integer
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {}",
+            self.limits.statement_timeout_ms
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        Ok(tx)
+    }
+
+    /// Reject searches over a range wider than allowed, returning the start
+    /// time to search from (by default the last day before `until`)
+    fn check_range(&self, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Result<DateTime<Utc>> {
+        self.limits.check_range(since, until)
+    }
+
+    /// Reject pages that reach past the row ceiling
+    fn check_page(&self, limit: i64, offset: i64) -> Result<()> {
+        let max_rows = self.limits.max_rows;
+        if offset.saturating_add(limit) > max_rows {
+            return Err(Error::query_limit(
+                format!("Search pages past the first {} results", max_rows),
+                "Page with `next_cursor` instead of `offset`, or add filters",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Turn a cancelled statement into a query limit error
+    fn timeout_error(&self, e: sqlx::Error) -> Error {
+        match &e {
+            // query_canceled, raised when statement_timeout fires
+            sqlx::Error::Database(db) if db.code().as_deref() == Some("57014") => Error::query_limit(
+                format!(
+                    "Search took longer than {} ms and was cancelled",
+                    self.limits.statement_timeout_ms
+                ),
+                "Narrow the time range or filter by service, model or status",
+            ),
+            _ => Error::Database(e.to_string()),
+        }
+    }
+
+    /// List traces with summaries, newest first
+    ///
+    /// Returns a cursor for the next page, positioned after the last trace's
+    /// root span, when the page is full.
     pub async fn list_traces(
         &self,
         service: Option<&str>,
         status: Option<&str>,
         since: Option<DateTime<Utc>>,
+        sort: Option<&SortConfig>,
         limit: i64,
-    ) -> Result<Vec<TraceSummary>> {
-        let mut conditions = vec!["parent_span_id IS NULL".to_string()];
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<TraceSummary>, Option<PageCursor>)> {
+        let order = SortConfig::trace_order(sort, cursor)?;
+        let mut filter = SpanFilter::default();
+        filter.raw("root_id IS NOT NULL");
 
         if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+            filter.bind("service_name = $?", svc);
         }
 
         if let Some(s) = status {
-            conditions.push(format!("status = '{}'", s.replace('\'', "''")));
+            filter.bind("status = $?", s);
         }
 
         if let Some(start) = since {
-            conditions.push(format!("started_at >= '{}'", start.format("%Y-%m-%d %H:%M:%S")));
+            filter.bind("started_at >= $?", start);
         }
 
-        let where_clause = conditions.join(" AND ");
+        if let Some(c) = cursor {
+            filter.push(
+                "(started_at, root_id) < ($?, $?)",
+                vec![c.started_at.into(), c.id.into()],
+            );
+        }
 
-        let sql = format!(
-            r#"
-            SELECT
-                s.trace_id,
-                s.operation_name as root_operation,
-                s.service_name,
-                s.started_at,
-                s.duration_ms,
-                COALESCE(stats.span_count, 1) as span_count,
-                COALESCE(stats.error_count, 0) as error_count,
-                COALESCE(stats.total_tokens, 0) as total_tokens,
-                COALESCE(stats.total_cost, 0) as total_cost_usd
-            FROM spans s
-            LEFT JOIN (
-                SELECT
-                    trace_id,
-                    COUNT(*) as span_count,
-                    SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
-                    SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
-                    SUM(COALESCE(cost_usd, 0)) as total_cost
-                FROM spans
-                GROUP BY trace_id
-            ) stats ON s.trace_id = stats.trace_id
-            WHERE {}
-            ORDER BY s.started_at DESC
-            LIMIT {}
-            "#,
-            where_clause, limit
-        );
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM trace_summaries",
+            TRACE_SUMMARY_COLUMNS
+        ));
+        filter.push_where(&mut query);
+        match order {
+            // The column comes from TRACE_SORT_FIELDS, never the request
+            Some((column, descending)) => query.push(format!(
+                " ORDER BY {} {} NULLS LAST, root_id DESC LIMIT ",
+                column,
+                if descending { "DESC" } else { "ASC" }
+            )),
+            None => query.push(" ORDER BY started_at DESC, root_id DESC LIMIT "),
+        };
+        query.push_bind(limit);
 
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
             .await
             .map_err(|e| Error::Database(e.to_string()))?;
 
         let mut traces = Vec::new();
+        let mut last = None;
         for row in rows {
-            traces.push(TraceSummary {
-                trace_id: row.try_get("trace_id").unwrap_or_default(),
-                root_operation: row.try_get("root_operation").unwrap_or_default(),
-                service_name: row.try_get("service_name").unwrap_or_default(),
-                started_at: row.try_get("started_at").unwrap_or_else(|_| Utc::now()),
-                duration_ms: row.try_get("duration_ms").ok(),
-                span_count: row.try_get("span_count").unwrap_or(0),
-                error_count: row.try_get("error_count").unwrap_or(0),
-                total_tokens: row.try_get("total_tokens").unwrap_or(0),
-                total_cost_usd: row.try_get::<f64, _>("total_cost_usd").unwrap_or(0.0),
+            last = Some(PageCursor {
+                started_at: row.try_get("started_at")?,
+                id: row.try_get("root_id")?,
             });
+            traces.push(row_to_trace_summary(&row));
         }
 
-        Ok(traces)
+        let next = last.filter(|_| order.is_none() && traces.len() as i64 == limit);
+        Ok((traces, next))
     }
 
-    // =========================================================================
-    // Metrics Methods
-    // =========================================================================
+    /// Find traces by what their spans contain, newest first
+    ///
+    /// Span conditions are `trace_id IN` subqueries bounded below by the
+    /// search start, since no span of a trace starts before its root.
+    pub async fn find_traces(&self, search: &TraceSearch) -> Result<Vec<TraceSummary>> {
+        let mut filter = SpanFilter::default();
+        filter.raw("root_id IS NOT NULL");
 
-    /// Get metrics summary
-    pub async fn get_metrics_summary(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-    ) -> Result<MetricsSummaryResponse> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+        if let Some(start) = search.since {
+            filter.bind("started_at >= $?", start);
+        }
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+        if let Some(end) = search.until {
+            filter.bind("started_at <= $?", end);
         }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+        if let Some(min) = search.min_duration {
+            filter.bind("duration_ms >= $?", min);
+        }
+
+        if let Some(max) = search.max_duration {
+            filter.bind("duration_ms <= $?", max);
         }
 
-        let where_clause = conditions.join(" AND ");
+        let mut named = SpanFilter::default();
+        if let Some(svc) = &search.service {
+            named.bind("service_name = $?", svc.as_str());
+        }
+        if let Some(op) = &search.operation {
+            named.bind("operation_name = $?", op.as_str());
+        }
+
+        let mut span_filters = vec![named];
+        for query in &search.spans {
+            span_filters.push(SpanFilter::from_query(query)?);
+        }
+        for mut spans in span_filters.into_iter().filter(|f| !f.conditions.is_empty()) {
+            if let Some(start) = search.since {
+                spans.bind("started_at >= $?", start);
+            }
+            filter.traces_with(spans);
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM trace_summaries",
+            TRACE_SUMMARY_COLUMNS
+        ));
+        filter.push_where(&mut query);
+        query.push(" ORDER BY started_at DESC, root_id DESC LIMIT ").push_bind(search.limit);
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows.iter().map(row_to_trace_summary).collect())
+    }
 
+    /// Get a trace's summary, once any of its spans have been stored
+    pub async fn get_trace_summary(&self, trace_id: &str) -> Result<Option<TraceSummary>> {
         let sql = format!(
-            r#"
-            SELECT
-                COUNT(*) as total_spans,
-                COUNT(DISTINCT trace_id) as total_traces,
-                SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
-                SUM(COALESCE(cost_usd, 0)) as total_cost_usd,
-                SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
-                AVG(duration_ms) as avg_latency_ms,
-                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) as p50_latency_ms,
-                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms) as p95_latency_ms,
-                PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms) as p99_latency_ms
-            FROM spans
-            WHERE {}
-            "#,
-            where_clause
+            "SELECT {} FROM trace_summaries WHERE trace_id = $1",
+            TRACE_SUMMARY_COLUMNS
         );
-
         let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
+            .bind(trace_id)
+            .fetch_optional(&self.read_pool)
             .await
             .map_err(|e| Error::Database(e.to_string()))?;
 
-        let total_spans: i64 = row.try_get("total_spans").unwrap_or(0);
-        let error_count: i64 = row.try_get("error_count").unwrap_or(0);
-
-        Ok(MetricsSummaryResponse {
-            total_spans,
-            total_traces: row.try_get("total_traces").unwrap_or(0),
-            total_tokens: row.try_get("total_tokens").unwrap_or(0),
-            total_cost_usd: row.try_get::<f64, _>("total_cost_usd").unwrap_or(0.0),
-            error_count,
-            error_rate: if total_spans > 0 {
-                error_count as f64 / total_spans as f64 * 100.0
-            } else {
-                0.0
-            },
-            avg_latency_ms: row.try_get::<f64, _>("avg_latency_ms").unwrap_or(0.0),
-            p50_latency_ms: row.try_get::<f64, _>("p50_latency_ms").unwrap_or(0.0),
-            p95_latency_ms: row.try_get::<f64, _>("p95_latency_ms").unwrap_or(0.0),
-            p99_latency_ms: row.try_get::<f64, _>("p99_latency_ms").unwrap_or(0.0),
-        })
+        Ok(row.as_ref().map(row_to_trace_summary))
     }
 
-    /// Get cost metrics grouped by field
-    pub async fn get_cost_by_group(
-        &self,
+    /// Get the summaries of several traces, skipping those with no spans
+    /// stored
+    pub async fn get_trace_summaries(&self, trace_ids: &[String]) -> Result<Vec<TraceSummary>> {
+        let sql = format!(
+            "SELECT {} FROM trace_summaries WHERE trace_id = ANY($1)",
+            TRACE_SUMMARY_COLUMNS
+        );
+        let rows = sqlx::query(&sql)
+            .bind(trace_ids)
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows.iter().map(row_to_trace_summary).collect())
+    }
+
+    /// List the most expensive traces started in a window, costliest first
+    pub async fn top_traces_by_cost(
+        &self,
         service: Option<&str>,
-        group_by: &str,
         since: DateTime<Utc>,
         until: DateTime<Utc>,
-    ) -> Result<Vec<CostMetric>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+        limit: i64,
+    ) -> Result<Vec<TraceSummary>> {
+        let mut filter = SpanFilter::default();
+        filter.raw("root_id IS NOT NULL");
+        filter.bind("started_at >= $?", since);
+        filter.bind("started_at < $?", until);
 
         if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+            filter.bind("service_name = $?", svc);
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM trace_summaries",
+            TRACE_SUMMARY_COLUMNS
+        ));
+        filter.push_where(&mut query);
+        query.push(" ORDER BY total_cost_usd DESC, started_at DESC LIMIT ").push_bind(limit);
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows.iter().map(row_to_trace_summary).collect())
+    }
+
+    /// Delete the traces a deletion selects, with their events, summaries,
+    /// annotations, feedback and stored payloads, or only count them on a
+    /// dry run
+    ///
+    /// Spans already copied to the archive are left there. Payloads offloaded
+    /// to object storage are deleted by the caller, see
+    /// [`PayloadStore::delete_trace`](crate::payloads::PayloadStore::delete_trace).
+    pub async fn delete_traces(&self, deletion: &TraceDeletion) -> Result<TraceDeletionResult> {
+        deletion.validate()?;
+
+        let mut filter = SpanFilter::default();
+        if !deletion.trace_ids.is_empty() {
+            let placeholders = vec!["$?"; deletion.trace_ids.len()].join(", ");
+            filter.push(
+                &format!("trace_id IN ({})", placeholders),
+                deletion.trace_ids.iter().map(|id| id.as_str().into()).collect(),
+            );
+        }
+        for (key, value) in &deletion.attributes {
+            filter.attribute(key, "eq", &serde_json::Value::String(value.clone()))?;
+        }
+        if let Some(before) = deletion.older_than {
+            filter.bind("started_at < $?", before);
+        }
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT DISTINCT trace_id FROM spans");
+        filter.push_where(&mut query);
+        query.push(" ORDER BY trace_id");
+
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+        let trace_ids = query
+            .build()
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?
+            .iter()
+            .map(|row| row.try_get::<String, _>("trace_id"))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let spans: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM spans WHERE trace_id = ANY($1)")
+            .bind(&trace_ids)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        if !deletion.dry_run && !trace_ids.is_empty() {
+            for table in [
+                "spans",
+                "span_events",
+                "trace_summaries",
+                "trace_annotations",
+                "trace_feedback",
+                "span_payloads",
+            ] {
+                sqlx::query(&format!("DELETE FROM {} WHERE trace_id = ANY($1)", table))
+                    .bind(&trace_ids)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| Error::Database(e.to_string()))?;
+            }
+
+            let sessions: Vec<String> =
+                sqlx::query_scalar("DELETE FROM session_traces WHERE trace_id = ANY($1) RETURNING session_id")
+                    .bind(&trace_ids)
+                    .fetch_all(&mut *tx)
+                    .await
+                    .map_err(|e| Error::Database(e.to_string()))?;
+            reconcile_sessions(&mut tx, &sessions).await?;
+            sqlx::query(
+                r#"
+                DELETE FROM sessions s
+                WHERE s.session_id = ANY($1)
+                  AND NOT EXISTS (SELECT 1 FROM session_traces st WHERE st.session_id = s.session_id)
+                "#,
+            )
+            .bind(&sessions)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+            tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
         }
 
-        let where_clause = conditions.join(" AND ");
-        let group_field = match group_by {
-            "model" => "model_name",
-            "service" => "service_name",
-            "operation" => "operation_name",
-            _ => "model_name",
+        Ok(TraceDeletionResult {
+            dry_run: deletion.dry_run,
+            traces: trace_ids.len() as i64,
+            spans,
+            trace_ids,
+        })
+    }
+
+    /// List sessions active since a time, most recently active first, with
+    /// the number of sessions that match
+    pub async fn list_sessions(
+        &self,
+        user_id: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<(Vec<SessionSummary>, i64)> {
+        let filter = |select: &str| {
+            let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM sessions WHERE 1 = 1", select));
+            if let Some(user_id) = user_id {
+                query.push(" AND user_id = ").push_bind(user_id.to_string());
+            }
+            if let Some(since) = since {
+                query.push(" AND last_seen >= ").push_bind(since);
+            }
+            query
+        };
+
+        let mut query = filter(SESSION_COLUMNS);
+        query.push(" ORDER BY last_seen DESC LIMIT ").push_bind(limit);
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let total: i64 = filter("COUNT(*)")
+            .build_query_scalar()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok((rows.iter().map(row_to_session_summary).collect(), total))
+    }
+
+    /// Get a session with the summaries of its traces, oldest first
+    pub async fn get_session(&self, session_id: &str) -> Result<Option<SessionDetail>> {
+        let sql = format!("SELECT {} FROM sessions WHERE session_id = $1", SESSION_COLUMNS);
+        let Some(row) = sqlx::query(&sql)
+            .bind(session_id)
+            .fetch_optional(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?
+        else {
+            return Ok(None);
         };
 
         let sql = format!(
             r#"
-            SELECT
-                COALESCE({}, 'unknown') as group_name,
-                SUM(COALESCE(cost_usd, 0)) as total_cost_usd,
-                SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
-                COUNT(*) as call_count
-            FROM spans
-            WHERE {}
-            GROUP BY {}
-            ORDER BY total_cost_usd DESC
+            SELECT {} FROM trace_summaries
+            WHERE trace_id IN (SELECT trace_id FROM session_traces WHERE session_id = $1)
+            ORDER BY started_at ASC NULLS LAST
             "#,
-            group_field, where_clause, group_field
+            TRACE_SUMMARY_COLUMNS
         );
-
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+        let traces = sqlx::query(&sql)
+            .bind(session_id)
+            .fetch_all(&self.read_pool)
             .await
             .map_err(|e| Error::Database(e.to_string()))?;
 
-        let mut costs = Vec::new();
-        for row in rows {
-            costs.push(CostMetric {
-                group: row.try_get("group_name").unwrap_or_default(),
-                total_cost_usd: row.try_get::<f64, _>("total_cost_usd").unwrap_or(0.0),
-                total_tokens: row.try_get("total_tokens").unwrap_or(0),
-                call_count: row.try_get("call_count").unwrap_or(0),
-            });
-        }
+        Ok(Some(SessionDetail {
+            session: row_to_session_summary(&row),
+            traces: traces.iter().map(row_to_trace_summary).collect(),
+        }))
+    }
 
-        Ok(costs)
+    /// Record end-user feedback on a trace
+    pub async fn add_feedback(&self, feedback: &TraceFeedback) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO trace_feedback (id, trace_id, rating, comment, user_id, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(feedback.id)
+        .bind(&feedback.trace_id)
+        .bind(feedback.rating.map(FeedbackRating::as_str))
+        .bind(&feedback.comment)
+        .bind(&feedback.user_id)
+        .bind(feedback.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(())
     }
 
-    /// Get latency metrics over time
-    pub async fn get_latency_over_time(
+    /// List the feedback left on a trace, oldest first
+    pub async fn list_feedback(&self, trace_id: &str) -> Result<Vec<TraceFeedback>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, trace_id, rating, comment, user_id, created_at
+            FROM trace_feedback
+            WHERE trace_id = $1
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(trace_id)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_feedback).collect()
+    }
+
+    /// Get feedback counts with the average cost, duration and tokens of
+    /// the rated traces whose root started in a window
+    ///
+    /// Traces are averaged once per rating however many users rated them.
+    pub async fn get_feedback_metrics(
         &self,
         service: Option<&str>,
-        model: Option<&str>,
         since: DateTime<Utc>,
         until: DateTime<Utc>,
-    ) -> Result<Vec<LatencyMetric>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+    ) -> Result<FeedbackMetrics> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            WITH rated AS (
+                SELECT f.trace_id, f.rating, COUNT(*) as feedback_count
+                FROM trace_feedback f
+                JOIN trace_summaries t ON t.trace_id = f.trace_id
+                WHERE f.rating IS NOT NULL"#,
+        );
+        push_trace_window(&mut query, service, since, until);
+        query.push(
+            r#"
+                GROUP BY f.trace_id, f.rating
+            )
+            SELECT
+                r.rating,
+                SUM(r.feedback_count)::BIGINT as feedback_count,
+                COUNT(*) as trace_count,
+                AVG(t.total_cost_usd) as avg_cost_usd,
+                AVG(t.duration_ms) as avg_duration_ms,
+                AVG(t.total_tokens)::DOUBLE PRECISION as avg_tokens
+            FROM rated r
+            JOIN trace_summaries t ON t.trace_id = r.trace_id
+            GROUP BY r.rating"#,
+        );
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+        let mut metrics = FeedbackMetrics::default();
+        for row in &rows {
+            let rating: String = row.try_get("rating").map_err(|e| Error::Database(e.to_string()))?;
+            let group = FeedbackGroup {
+                feedback_count: row.try_get("feedback_count").unwrap_or(0),
+                trace_count: row.try_get("trace_count").unwrap_or(0),
+                avg_cost_usd: row.try_get("avg_cost_usd").ok(),
+                avg_duration_ms: row.try_get("avg_duration_ms").ok(),
+                avg_tokens: row.try_get("avg_tokens").ok(),
+            };
+            match FeedbackRating::parse(&rating) {
+                Some(FeedbackRating::Positive) => metrics.positive = group,
+                Some(FeedbackRating::Negative) => metrics.negative = group,
+                None => {}
+            }
         }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT COUNT(*) FROM trace_feedback f
+            JOIN trace_summaries t ON t.trace_id = f.trace_id
+            WHERE f.comment IS NOT NULL"#,
+        );
+        push_trace_window(&mut query, service, since, until);
+        metrics.comment_count = query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        metrics.compute_ratios();
+        Ok(metrics)
+    }
+
+    /// Store full span payloads, replacing any already stored for the same
+    /// span, returning how many were written
+    pub async fn put_payloads(&self, payloads: &[SpanPayload]) -> Result<usize> {
+        if payloads.is_empty() {
+            return Ok(0);
         }
 
-        let where_clause = conditions.join(" AND ");
+        // A statement can't update the same row twice, so only the latest
+        // payload of each span is written
+        let mut latest: HashMap<(&str, &str), &SpanPayload> = HashMap::with_capacity(payloads.len());
+        for payload in payloads {
+            latest.insert((payload.trace_id.as_str(), payload.span_id.as_str()), payload);
+        }
 
-        let sql = format!(
+        let n = latest.len();
+        let mut trace_ids = Vec::with_capacity(n);
+        let mut span_ids = Vec::with_capacity(n);
+        let mut prompts = Vec::with_capacity(n);
+        let mut completions = Vec::with_capacity(n);
+        let mut truncated = Vec::with_capacity(n);
+        let mut sizes = Vec::with_capacity(n);
+        let mut created_ats = Vec::with_capacity(n);
+        for payload in latest.into_values() {
+            trace_ids.push(payload.trace_id.clone());
+            span_ids.push(payload.span_id.clone());
+            prompts.push(payload.prompt.clone());
+            completions.push(payload.completion.clone());
+            truncated.push(payload.truncated);
+            sizes.push(payload.size_bytes);
+            created_ats.push(payload.created_at);
+        }
+
+        let result = sqlx::query(
             r#"
-            SELECT
-                time_bucket('1 hour', started_at) as bucket,
-                AVG(duration_ms) as avg_ms,
-                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) as p50_ms,
-                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms) as p95_ms,
-                PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms) as p99_ms,
-                COUNT(*) as count
-            FROM spans
-            WHERE {}
-            GROUP BY bucket
-            ORDER BY bucket
+            INSERT INTO span_payloads (trace_id, span_id, prompt, completion, truncated, size_bytes, created_at)
+            SELECT * FROM UNNEST(
+                $1::VARCHAR[], $2::VARCHAR[], $3::TEXT[], $4::TEXT[], $5::BOOLEAN[], $6::BIGINT[], $7::TIMESTAMPTZ[]
+            )
+            ON CONFLICT (trace_id, span_id) DO UPDATE SET
+                prompt = EXCLUDED.prompt,
+                completion = EXCLUDED.completion,
+                truncated = EXCLUDED.truncated,
+                size_bytes = EXCLUDED.size_bytes,
+                created_at = EXCLUDED.created_at
             "#,
-            where_clause
-        );
+        )
+        .bind(&trace_ids)
+        .bind(&span_ids)
+        .bind(&prompts)
+        .bind(&completions)
+        .bind(&truncated)
+        .bind(&sizes)
+        .bind(&created_ats)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
 
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+        Ok(result.rows_affected() as usize)
+    }
+
+    /// Get the full payload stored for a span
+    pub async fn get_payload(&self, trace_id: &str, span_id: &str) -> Result<Option<SpanPayload>> {
+        let row = sqlx::query(
+            r#"
+            SELECT trace_id, span_id, prompt, completion, truncated, size_bytes, created_at
+            FROM span_payloads
+            WHERE trace_id = $1 AND span_id = $2
+            "#,
+        )
+        .bind(trace_id)
+        .bind(span_id)
+        .fetch_optional(&self.read_pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        row.as_ref().map(row_to_payload).transpose()
+    }
+
+    /// Delete payloads captured before a time, returning how many were deleted
+    pub async fn prune_payloads(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM span_payloads WHERE created_at < $1")
+            .bind(before)
+            .execute(&self.pool)
             .await
             .map_err(|e| Error::Database(e.to_string()))?;
 
-        let mut metrics = Vec::new();
-        for row in rows {
-            metrics.push(LatencyMetric {
-                timestamp: row.try_get("bucket").unwrap_or_else(|_| Utc::now()),
-                avg_ms: row.try_get::<f64, _>("avg_ms").unwrap_or(0.0),
-                p50_ms: row.try_get::<f64, _>("p50_ms").unwrap_or(0.0),
-                p95_ms: row.try_get::<f64, _>("p95_ms").unwrap_or(0.0),
-                p99_ms: row.try_get::<f64, _>("p99_ms").unwrap_or(0.0),
-                count: row.try_get("count").unwrap_or(0),
-            });
-        }
+        Ok(result.rows_affected())
+    }
 
-        Ok(metrics)
+    /// List services with spans in a window, by name
+    ///
+    /// Activity is kept in hourly buckets, so the window is widened to whole
+    /// hours.
+    pub async fn list_services(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<ServiceSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                service_name,
+                MAX(last_seen) as last_seen,
+                SUM(span_count)::BIGINT as span_count,
+                SUM(error_count)::BIGINT as error_count,
+                SUM(cost_usd) as total_cost_usd
+            FROM service_activity
+            WHERE bucket >= date_trunc('hour', $1::timestamptz) AND bucket < $2
+            GROUP BY service_name
+            ORDER BY service_name
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(ServiceSummary::new(
+                    row.try_get("service_name")?,
+                    row.try_get("last_seen")?,
+                    row.try_get("span_count")?,
+                    row.try_get("error_count")?,
+                    row.try_get("total_cost_usd")?,
+                    until - since,
+                ))
+            })
+            .collect()
     }
 
-    /// Get error metrics over time
-    pub async fn get_errors_over_time(
+    // =========================================================================
+    // Metrics Methods
+    // =========================================================================
+
+    /// Get metrics summary
+    pub async fn get_metrics_summary(
         &self,
         service: Option<&str>,
         model: Option<&str>,
         since: DateTime<Utc>,
         until: DateTime<Utc>,
-    ) -> Result<Vec<ErrorMetric>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
-
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
-        }
-
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
-        }
-
-        let where_clause = conditions.join(" AND ");
+    ) -> Result<MetricsSummaryResponse> {
+        let filter = SpanFilter::scoped(service, model, since, until);
 
-        let sql = format!(
+        let mut query = QueryBuilder::<Postgres>::new(
             r#"
             SELECT
-                time_bucket('1 hour', started_at) as bucket,
+                COUNT(*) as total_spans,
+                COUNT(DISTINCT trace_id) as total_traces,
+                SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
+                SUM(COALESCE(cost_usd, 0)) as total_cost_usd,
                 SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
-                COUNT(*) as total_count
+                AVG(duration_ms) as avg_latency_ms,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) as p50_latency_ms,
+                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms) as p95_latency_ms,
+                PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms) as p99_latency_ms
             FROM spans
-            WHERE {}
-            GROUP BY bucket
-            ORDER BY bucket
             "#,
-            where_clause
         );
+        filter.push_where(&mut query);
 
-        let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
             .await
             .map_err(|e| Error::Database(e.to_string()))?;
 
-        let mut metrics = Vec::new();
-        for row in rows {
-            let error_count: i64 = row.try_get("error_count").unwrap_or(0);
-            let total_count: i64 = row.try_get("total_count").unwrap_or(0);
-            let error_rate = if total_count > 0 {
-                error_count as f64 / total_count as f64 * 100.0
+        let total_spans: i64 = row.try_get("total_spans").unwrap_or(0);
+        let error_count: i64 = row.try_get("error_count").unwrap_or(0);
+
+        Ok(MetricsSummaryResponse {
+            total_spans,
+            total_traces: row.try_get("total_traces").unwrap_or(0),
+            total_tokens: row.try_get("total_tokens").unwrap_or(0),
+            total_cost_usd: row.try_get::<f64, _>("total_cost_usd").unwrap_or(0.0),
+            error_count,
+            error_rate: if total_spans > 0 {
+                error_count as f64 / total_spans as f64 * 100.0
             } else {
                 0.0
-            };
+            },
+            avg_latency_ms: row.try_get::<f64, _>("avg_latency_ms").unwrap_or(0.0),
+            p50_latency_ms: row.try_get::<f64, _>("p50_latency_ms").unwrap_or(0.0),
+            p95_latency_ms: row.try_get::<f64, _>("p95_latency_ms").unwrap_or(0.0),
+            p99_latency_ms: row.try_get::<f64, _>("p99_latency_ms").unwrap_or(0.0),
+            latency_targets: None,
+            upstream_incidents: vec![],
+        })
+    }
 
-            metrics.push(ErrorMetric {
-                timestamp: row.try_get("bucket").unwrap_or_else(|_| Utc::now()),
-                error_count,
-                total_count,
-                error_rate,
-            });
+    /// Count the spans in a window and get the latest start time
+    pub async fn span_watermark(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<(i64, Option<DateTime<Utc>>)> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) as span_count, MAX(started_at) as latest FROM spans");
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok((
+            row.try_get("span_count").unwrap_or(0),
+            row.try_get("latest").unwrap_or(None),
+        ))
+    }
+
+    /// Compute one metric per group and time bucket for a custom query
+    ///
+    /// Grouped queries keep the `limit` groups with the highest overall
+    /// value. Runs under the search statement timeout and range limit.
+    pub async fn aggregate(&self, query: &AggregateQuery) -> Result<Vec<AggregateRow>> {
+        self.check_range(Some(query.since), Some(query.until))?;
+
+        let mut filter = SpanFilter::from_search_filters(&query.filters)?;
+        filter.bind("started_at >= $?", query.since);
+        filter.bind("started_at <= $?", query.until);
+        filter.raw("status <> 'in_progress'");
+
+        let metric = aggregate_metric_sql(query.metric);
+        let mut columns = Vec::new();
+        let mut sql = QueryBuilder::<Postgres>::new("SELECT ");
+
+        if let Some(group) = &query.group {
+            push_group_expr(&mut sql, group);
+            sql.push(" AS grp, ");
+            columns.push("grp");
+        }
+        if let Some(secs) = query.bucket_secs {
+            sql.push("time_bucket(make_interval(secs => ")
+                .push_bind(secs as f64)
+                .push("), started_at) AS bucket, ");
+            columns.push("bucket");
+        }
+        sql.push(format!("{} AS value FROM spans", metric));
+        filter.push_where(&mut sql);
+
+        // Restrict bucketed series to the top groups over the whole range
+        if let (Some(group), Some(_)) = (&query.group, query.bucket_secs) {
+            sql.push(" AND ");
+            push_group_expr(&mut sql, group);
+            sql.push(" IN (SELECT grp FROM (SELECT ");
+            push_group_expr(&mut sql, group);
+            sql.push(format!(" AS grp, {} AS value FROM spans", metric));
+            filter.push_where(&mut sql);
+            sql.push(" GROUP BY grp ORDER BY value DESC LIMIT ")
+                .push_bind(query.limit)
+                .push(") AS top)");
         }
 
-        Ok(metrics)
+        if !columns.is_empty() {
+            sql.push(format!(" GROUP BY {}", columns.join(", ")));
+        }
+        match (&query.group, query.bucket_secs) {
+            (_, Some(_)) => {
+                sql.push(" ORDER BY bucket, value DESC");
+            }
+            (Some(_), None) => {
+                sql.push(" ORDER BY value DESC LIMIT ").push_bind(query.limit);
+            }
+            (None, None) => {}
+        }
+
+        let mut tx = self.read_tx().await?;
+        let rows = sql
+            .build()
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| self.timeout_error(e))?;
+        tx.commit().await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| AggregateRow {
+                group: row.try_get::<Option<String>, _>("grp").ok().flatten(),
+                bucket: row.try_get::<Option<DateTime<Utc>>, _>("bucket").ok().flatten(),
+                value: row.try_get::<f64, _>("value").unwrap_or(0.0),
+            })
+            .collect())
+    }
+
+    /// Get cost metrics grouped by field, or by the value of attribute
+    /// `<key>` for `attr:<key>`
+    pub async fn get_cost_by_group(
+        &self,
+        service: Option<&str>,
+        group_by: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CostMetric>> {
+        let filter = SpanFilter::scoped(service, None, since, until);
+
+        // Time groupings are labelled by bucket start (UTC) and returned in
+        // chronological order; the others are ordered by cost
+        let (group_expr, order_by) = match group_by {
+            "day" => (
+                "to_char(time_bucket('1 day', started_at) AT TIME ZONE 'UTC', 'YYYY-MM-DD')",
+                "group_name ASC",
+            ),
+            "hour" => (
+                "to_char(time_bucket('1 hour', started_at) AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:00')",
+                "group_name ASC",
+            ),
+            "service" => ("COALESCE(service_name, 'unknown')", "total_cost_usd DESC"),
+            "operation" => ("COALESCE(operation_name, 'unknown')", "total_cost_usd DESC"),
+            _ => ("COALESCE(model_name, 'unknown')", "total_cost_usd DESC"),
+        };
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT ");
+        match group_by.strip_prefix("attr:") {
+            Some(key) => {
+                query.push("COALESCE(attributes ->> ").push_bind(key.to_string()).push(", 'unknown')");
+            }
+            None => {
+                query.push(group_expr);
+            }
+        }
+        query.push(
+            r#" as group_name,
+                SUM(COALESCE(cost_usd, 0)) as total_cost_usd,
+                SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
+                COUNT(*) as call_count
+            FROM spans
+            "#,
+        );
+        filter.push_where(&mut query);
+        query.push(format!(" GROUP BY group_name ORDER BY {}", order_by));
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut costs = Vec::new();
+        for row in rows {
+            costs.push(CostMetric {
+                group: row.try_get("group_name").unwrap_or_default(),
+                total_cost_usd: row.try_get::<f64, _>("total_cost_usd").unwrap_or(0.0),
+                total_tokens: row.try_get("total_tokens").unwrap_or(0),
+                call_count: row.try_get("call_count").unwrap_or(0),
+            });
+        }
+
+        Ok(costs)
+    }
+
+    /// Get token and cost efficiency per operation, most expensive first
+    ///
+    /// Only operations that used tokens or cost money are included. A trace
+    /// counts as successful when none of its spans in the range errored.
+    pub async fn get_operation_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<OperationEfficiency>> {
+        let failed = SpanFilter::scoped(None, None, since, until);
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new("WITH failed AS (SELECT DISTINCT trace_id FROM spans");
+        failed.push_where(&mut query);
+        query.push(
+            r#" AND status = 'error')
+            SELECT
+                service_name,
+                operation_name,
+                COUNT(*) as span_count,
+                COALESCE(SUM(tokens_in), 0)::BIGINT as tokens_in,
+                COALESCE(SUM(tokens_out), 0)::BIGINT as tokens_out,
+                COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION as total_cost_usd,
+                COALESCE(SUM(cost_usd) FILTER (WHERE status = 'error'), 0)::DOUBLE PRECISION as error_cost_usd,
+                COUNT(DISTINCT trace_id) as trace_count,
+                COUNT(DISTINCT trace_id) FILTER (
+                    WHERE trace_id NOT IN (SELECT trace_id FROM failed)
+                ) as successful_traces
+            FROM spans"#,
+        );
+        filter.push_where(&mut query);
+        query.push(
+            r#"
+            GROUP BY service_name, operation_name
+            HAVING SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) > 0
+                OR SUM(COALESCE(cost_usd, 0)) > 0
+            ORDER BY total_cost_usd DESC, service_name, operation_name"#,
+        );
+
+        let rows = query.build().fetch_all(&self.read_pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let mut efficiency = OperationEfficiency {
+                    service_name: row.try_get("service_name").unwrap_or_default(),
+                    operation_name: row.try_get("operation_name").unwrap_or_default(),
+                    span_count: row.try_get("span_count").unwrap_or(0),
+                    tokens_in: row.try_get("tokens_in").unwrap_or(0),
+                    tokens_out: row.try_get("tokens_out").unwrap_or(0),
+                    output_input_ratio: None,
+                    total_cost_usd: row.try_get("total_cost_usd").unwrap_or(0.0),
+                    error_cost_usd: row.try_get("error_cost_usd").unwrap_or(0.0),
+                    trace_count: row.try_get("trace_count").unwrap_or(0),
+                    successful_traces: row.try_get("successful_traces").unwrap_or(0),
+                    cost_per_successful_trace: None,
+                };
+                efficiency.compute_ratios();
+                efficiency
+            })
+            .collect())
+    }
+
+    /// Get token usage efficiency across the spans in a window
+    ///
+    /// A trace counts as successful when none of its spans in the range
+    /// errored.
+    pub async fn get_token_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TokenEfficiency> {
+        let failed = SpanFilter::scoped(None, None, since, until);
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new("WITH failed AS (SELECT DISTINCT trace_id FROM spans");
+        failed.push_where(&mut query);
+        query.push(
+            r#" AND status = 'error')
+            SELECT
+                COUNT(*) as span_count,
+                COUNT(DISTINCT trace_id) as trace_count,
+                COUNT(DISTINCT trace_id) FILTER (
+                    WHERE trace_id NOT IN (SELECT trace_id FROM failed)
+                ) as successful_traces,
+                COALESCE(SUM(tokens_in), 0)::BIGINT as tokens_in,
+                COALESCE(SUM(tokens_cached_in), 0)::BIGINT as tokens_cached_in,
+                COALESCE(SUM(tokens_out), 0)::BIGINT as tokens_out,
+                COALESCE(SUM(tokens_reasoning), 0)::BIGINT as tokens_reasoning,
+                COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION as total_cost_usd
+            FROM spans"#,
+        );
+        filter.push_where(&mut query);
+
+        let row = query.build().fetch_one(&self.read_pool).await?;
+
+        let mut efficiency = TokenEfficiency {
+            span_count: row.try_get("span_count").unwrap_or(0),
+            trace_count: row.try_get("trace_count").unwrap_or(0),
+            successful_traces: row.try_get("successful_traces").unwrap_or(0),
+            tokens_in: row.try_get("tokens_in").unwrap_or(0),
+            tokens_cached_in: row.try_get("tokens_cached_in").unwrap_or(0),
+            tokens_out: row.try_get("tokens_out").unwrap_or(0),
+            tokens_reasoning: row.try_get("tokens_reasoning").unwrap_or(0),
+            total_cost_usd: row.try_get("total_cost_usd").unwrap_or(0.0),
+            ..TokenEfficiency::default()
+        };
+        efficiency.compute_ratios();
+        Ok(efficiency)
+    }
+
+    /// Get the LLM / tool / overhead time split across traces
+    ///
+    /// Only traces whose root span started in the range and has a duration
+    /// are included.
+    pub async fn get_time_breakdown(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TimeBreakdownMetric> {
+        let filter = SpanFilter::scoped(service, None, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            WITH per_trace AS (
+                SELECT
+                    trace_id,
+                    MAX(duration_ms) FILTER (WHERE parent_span_id IS NULL) as total_ms,
+                    COALESCE(SUM(duration_ms) FILTER (WHERE model_name IS NOT NULL), 0) as llm_ms,
+                    COALESCE(SUM(COALESCE(tool_duration_ms, duration_ms))
+                        FILTER (WHERE tool_name IS NOT NULL AND model_name IS NULL), 0) as tool_ms
+                FROM spans
+                WHERE trace_id IN (
+                    SELECT trace_id FROM spans"#,
+        );
+        filter.push_where(&mut query);
+        query.push(
+            r#" AND parent_span_id IS NULL
+                )
+                GROUP BY trace_id
+            )
+            SELECT
+                COUNT(*) as trace_count,
+                COALESCE(SUM(total_ms), 0) as total_ms,
+                COALESCE(SUM(llm_ms), 0) as llm_ms,
+                COALESCE(SUM(tool_ms), 0) as tool_ms,
+                COALESCE(SUM(GREATEST(total_ms - llm_ms - tool_ms, 0)), 0) as overhead_ms
+            FROM per_trace
+            WHERE total_ms IS NOT NULL
+            "#,
+        );
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let total = TimeBreakdown {
+            total_ms: row.try_get("total_ms").unwrap_or(0.0),
+            llm_ms: row.try_get("llm_ms").unwrap_or(0.0),
+            tool_ms: row.try_get("tool_ms").unwrap_or(0.0),
+            overhead_ms: row.try_get("overhead_ms").unwrap_or(0.0),
+        };
+
+        Ok(TimeBreakdownMetric::from_totals(
+            row.try_get("trace_count").unwrap_or(0),
+            total,
+        ))
+    }
+
+    /// Get latency metrics over time
+    pub async fn get_latency_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<LatencyMetric>> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT
+                time_bucket('1 hour', started_at) as bucket,
+                AVG(duration_ms) as avg_ms,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) as p50_ms,
+                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms) as p95_ms,
+                PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms) as p99_ms,
+                COUNT(*) as count
+            FROM spans
+            "#,
+        );
+        filter.push_where(&mut query);
+        query.push(" GROUP BY bucket ORDER BY bucket");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut metrics = Vec::new();
+        for row in rows {
+            metrics.push(LatencyMetric {
+                timestamp: row.try_get("bucket").unwrap_or_else(|_| Utc::now()),
+                avg_ms: row.try_get::<f64, _>("avg_ms").unwrap_or(0.0),
+                p50_ms: row.try_get::<f64, _>("p50_ms").unwrap_or(0.0),
+                p95_ms: row.try_get::<f64, _>("p95_ms").unwrap_or(0.0),
+                p99_ms: row.try_get::<f64, _>("p99_ms").unwrap_or(0.0),
+                count: row.try_get("count").unwrap_or(0),
+            });
+        }
+
+        Ok(metrics)
+    }
+
+    /// Get error metrics over time
+    pub async fn get_errors_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ErrorMetric>> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT
+                time_bucket('1 hour', started_at) as bucket,
+                SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
+                COUNT(*) as total_count
+            FROM spans
+            "#,
+        );
+        filter.push_where(&mut query);
+        query.push(" GROUP BY bucket ORDER BY bucket");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let mut metrics = Vec::new();
+        for row in rows {
+            let error_count: i64 = row.try_get("error_count").unwrap_or(0);
+            let total_count: i64 = row.try_get("total_count").unwrap_or(0);
+            let error_rate = if total_count > 0 {
+                error_count as f64 / total_count as f64 * 100.0
+            } else {
+                0.0
+            };
+
+            metrics.push(ErrorMetric {
+                timestamp: row.try_get("bucket").unwrap_or_else(|_| Utc::now()),
+                error_count,
+                total_count,
+                error_rate,
+            });
+        }
+
+        Ok(metrics)
+    }
+
+    // =========================================================================
+    // Alerting Metric Methods
+    // =========================================================================
+
+    /// Get error statistics for alerting
+    pub async fn get_error_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<ErrorStats> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            r#"
+            SELECT
+                SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
+                COUNT(*) as total,
+                ARRAY_AGG(DISTINCT trace_id) FILTER (WHERE status = 'error') as sample_trace_ids
+            FROM spans
+            "#,
+        );
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(ErrorStats {
+            error_count: row.try_get("error_count").unwrap_or(0),
+            total: row.try_get("total").unwrap_or(0),
+            sample_trace_ids: row.try_get::<Vec<String>, _>("sample_trace_ids").unwrap_or_default(),
+        })
+    }
+
+    /// Get latency percentile for alerting
+    pub async fn get_latency_percentile(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<Option<f64>> {
+        let mut filter = SpanFilter::scoped(service, model, since, until);
+        filter.raw("duration_ms IS NOT NULL");
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT PERCENTILE_CONT(");
+        query
+            .push_bind(percentile)
+            .push(") WITHIN GROUP (ORDER BY duration_ms) as p_val FROM spans");
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get::<f64, _>("p_val").ok())
+    }
+
+    /// Get a latency percentile and span count for one operation of a service
+    pub async fn get_operation_latency(
+        &self,
+        service: &str,
+        operation: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<(Option<f64>, i64)> {
+        let mut filter = SpanFilter::scoped(Some(service), None, since, until);
+        filter.bind("operation_name = $?", operation);
+        filter.raw("duration_ms IS NOT NULL");
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT PERCENTILE_CONT(");
+        query
+            .push_bind(percentile)
+            .push(") WITHIN GROUP (ORDER BY duration_ms) as p_val, COUNT(*) as span_count FROM spans");
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok((
+            row.try_get::<f64, _>("p_val").ok(),
+            row.try_get("span_count").unwrap_or(0),
+        ))
+    }
+
+    /// Get average latency for alerting
+    pub async fn get_latency_avg(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        let mut filter = SpanFilter::scoped(service, model, since, until);
+        filter.raw("duration_ms IS NOT NULL");
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT AVG(duration_ms) as avg_val FROM spans");
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get::<f64, _>("avg_val").ok())
+    }
+
+    /// Get total cost for alerting
+    pub async fn get_cost_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT SUM(COALESCE(cost_usd, 0)) as total_cost FROM spans",
+        );
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get::<f64, _>("total_cost").ok())
+    }
+
+    /// Get total token count for alerting
+    pub async fn get_token_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<i64>> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens FROM spans",
+        );
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get::<i64, _>("total_tokens").ok())
+    }
+
+    /// Get span count for alerting
+    pub async fn get_span_count(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<i64> {
+        let filter = SpanFilter::scoped(service, model, since, until);
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT COUNT(*) as cnt FROM spans");
+        filter.push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get("cnt").unwrap_or(0))
+    }
+
+    /// Get alerting metrics for each `window`-long window from `since` to
+    /// `until`, in one query bucketed from `since`
+    pub async fn get_window_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<Vec<WindowStats>> {
+        let mut filter = SpanFilter::scoped(service, model, since, until);
+        // A span at exactly `until` would start a window of its own
+        filter.bind("started_at < $?", until);
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT time_bucket(make_interval(secs => ");
+        query
+            .push_bind(window.num_seconds() as f64)
+            .push("), started_at, ")
+            .push_bind(since)
+            .push(
+                r#") AS bucket,
+                COUNT(*) AS total,
+                SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) AS error_count,
+                AVG(duration_ms) AS latency_avg,
+                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY duration_ms) AS latency_p50,
+                PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY duration_ms) AS latency_p95,
+                PERCENTILE_CONT(0.99) WITHIN GROUP (ORDER BY duration_ms) AS latency_p99,
+                SUM(COALESCE(cost_usd, 0)) AS cost_sum,
+                SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) AS token_sum
+            FROM spans"#,
+            );
+        filter.push_where(&mut query);
+        query.push(" GROUP BY bucket ORDER BY bucket");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(WindowStats {
+                    window_start: row.try_get("bucket")?,
+                    total: row.try_get("total")?,
+                    error_count: row.try_get("error_count").unwrap_or(0),
+                    latency_avg: row.try_get("latency_avg").ok(),
+                    latency_p50: row.try_get("latency_p50").ok(),
+                    latency_p95: row.try_get("latency_p95").ok(),
+                    latency_p99: row.try_get("latency_p99").ok(),
+                    cost_sum: row.try_get("cost_sum").unwrap_or(0.0),
+                    token_sum: row.try_get("token_sum").unwrap_or(0),
+                })
+            })
+            .collect()
+    }
+
+    // =========================================================================
+    // Storage Methods
+    // =========================================================================
+
+    /// Report storage usage of the spans table and project its growth
+    ///
+    /// Ingest rates are measured over the last `lookback_days`. Raw row sizes
+    /// are scaled to on-disk size (indexes, compression) using the ratio of
+    /// the hypertable size to its approximate row count.
+    pub async fn get_storage_report(&self, lookback_days: i64, projection_days: i64) -> Result<StorageReport> {
+        let since = Utc::now() - chrono::Duration::days(lookback_days);
+
+        let row = sqlx::query(
+            r#"
+            SELECT
+                hypertable_size('spans')::BIGINT as table_bytes,
+                approximate_row_count('spans')::BIGINT as span_count,
+                (
+                    SELECT EXTRACT(EPOCH FROM (config->>'drop_after')::INTERVAL)::DOUBLE PRECISION / 86400
+                    FROM timescaledb_information.jobs
+                    WHERE proc_name = 'policy_retention' AND hypertable_name = 'spans'
+                    LIMIT 1
+                ) as retention_days
+            "#,
+        )
+        .fetch_one(&self.read_pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        let table_bytes: i64 = row.try_get("table_bytes").unwrap_or(0);
+        let approximate_span_count: i64 = row.try_get("span_count").unwrap_or(0);
+        let retention_days: Option<f64> = row.try_get("retention_days").ok().flatten();
+
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                time_bucket('1 day', started_at) as day,
+                COUNT(*) as span_count,
+                SUM(pg_column_size(s.*))::BIGINT as bytes
+            FROM spans s
+            WHERE started_at >= $1
+            GROUP BY day
+            ORDER BY day
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.read_pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        let daily_ingest: Vec<DailyIngest> = rows
+            .iter()
+            .map(|r| DailyIngest {
+                day: r.try_get("day").unwrap_or_else(|_| Utc::now()),
+                span_count: r.try_get("span_count").unwrap_or(0),
+                bytes: r.try_get("bytes").unwrap_or(0),
+            })
+            .collect();
+
+        let mut query = QueryBuilder::<Postgres>::new("SELECT SUM(pg_column_size(s.*))::BIGINT as total_bytes");
+        for column in STORAGE_COLUMNS {
+            query.push(format!(
+                ", SUM(COALESCE(pg_column_size({col}), 0))::BIGINT as {col}",
+                col = column
+            ));
+        }
+        query.push(" FROM spans s WHERE started_at >= ").push_bind(since);
+
+        let row = query
+            .build()
+            .fetch_one(&self.read_pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let total_bytes: i64 = row.try_get("total_bytes").unwrap_or(0);
+        let share = |bytes: i64| {
+            if total_bytes > 0 {
+                bytes as f64 / total_bytes as f64 * 100.0
+            } else {
+                0.0
+            }
+        };
+
+        let mut columns: Vec<ColumnStorage> = STORAGE_COLUMNS
+            .iter()
+            .map(|column| {
+                let bytes: i64 = row.try_get(*column).unwrap_or(0);
+                ColumnStorage {
+                    column: column.to_string(),
+                    bytes,
+                    share: share(bytes),
+                }
+            })
+            .collect();
+        columns.sort_by_key(|c| std::cmp::Reverse(c.bytes));
+
+        let attribute_share = columns
+            .iter()
+            .find(|c| c.column == "attributes")
+            .map(|c| c.share)
+            .unwrap_or(0.0);
+
+        let ingested_spans: i64 = daily_ingest.iter().map(|d| d.span_count).sum();
+        let avg_span_bytes = if ingested_spans > 0 {
+            total_bytes as f64 / ingested_spans as f64
+        } else {
+            0.0
+        };
+
+        // On-disk bytes per span, falling back to raw size for an empty table
+        let disk_bytes_per_span = if approximate_span_count > 0 {
+            table_bytes as f64 / approximate_span_count as f64
+        } else {
+            avg_span_bytes
+        };
+        let avg_daily_spans = ingested_spans as f64 / lookback_days.max(1) as f64;
+        let avg_daily_growth_bytes = avg_daily_spans * disk_bytes_per_span;
+
+        let steady_state_bytes = retention_days.map(|days| (avg_daily_growth_bytes * days) as i64);
+        let unbounded = table_bytes + (avg_daily_growth_bytes * projection_days as f64) as i64;
+        let projected_table_bytes = match (retention_days, steady_state_bytes) {
+            (Some(days), Some(steady)) => {
+                if table_bytes <= steady {
+                    unbounded.min(steady)
+                } else if projection_days as f64 >= days {
+                    // Volume dropped; old chunks age out down to the steady state
+                    steady
+                } else {
+                    table_bytes
+                }
+            }
+            _ => unbounded,
+        };
+
+        Ok(StorageReport {
+            lookback_days,
+            table_bytes,
+            approximate_span_count,
+            retention_days,
+            daily_ingest,
+            avg_span_bytes,
+            attribute_share,
+            columns,
+            avg_daily_growth_bytes,
+            projection_days,
+            projected_table_bytes,
+            steady_state_bytes,
+        })
+    }
+}
+
+/// Span columns broken out in storage reports
+const STORAGE_COLUMNS: &[&str] = &[
+    "attributes",
+    "events",
+    "links",
+    "tool_input",
+    "tool_output",
+    "prompt_preview",
+    "completion_preview",
+    "operation_name",
+    "service_name",
+];
+
+/// Repository for per-service attribute schemas
+#[derive(Clone)]
+pub struct SchemaRepository {
+    pool: PgPool,
+}
+
+impl SchemaRepository {
+    /// Create a new schema repository
+    pub fn new(pool: &PostgresPool) -> Self {
+        Self {
+            pool: pool.pool.clone(),
+        }
+    }
+
+    /// List all registered schemas
+    pub async fn list(&self) -> Result<Vec<AttributeSchema>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT service_name, attributes, latency_targets, created_at, updated_at
+            FROM attribute_schemas
+            ORDER BY service_name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_schema).collect()
+    }
+
+    /// Create or replace the schema for a service
+    pub async fn upsert(&self, service_name: &str, input: &AttributeSchemaInput) -> Result<AttributeSchema> {
+        let attributes_json = serde_json::to_value(&input.attributes)?;
+        let targets_json = serde_json::to_value(&input.latency_targets)?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO attribute_schemas (service_name, attributes, latency_targets)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (service_name) DO UPDATE SET
+                attributes = EXCLUDED.attributes,
+                latency_targets = EXCLUDED.latency_targets
+            RETURNING service_name, attributes, latency_targets, created_at, updated_at
+            "#,
+        )
+        .bind(service_name)
+        .bind(attributes_json)
+        .bind(targets_json)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        row_to_schema(&row)
+    }
+
+    /// Delete the schema for a service
+    pub async fn delete(&self, service_name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM attribute_schemas WHERE service_name = $1")
+            .bind(service_name)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Summarize schema violations recorded on a service's spans
+    pub async fn compliance(
+        &self,
+        service_name: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SchemaComplianceReport> {
+        // The key is inlined (it is a constant) so the partial index can be used
+        let count_sql = format!(
+            r#"
+            SELECT
+                COUNT(*) as total_spans,
+                COUNT(*) FILTER (WHERE attributes ? '{key}') as violating_spans
+            FROM spans
+            WHERE service_name = $1 AND started_at >= $2 AND started_at <= $3
+            "#,
+            key = SCHEMA_VIOLATIONS_ATTRIBUTE
+        );
+        let row = sqlx::query(&count_sql)
+            .bind(service_name)
+            .bind(since)
+            .bind(until)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let total_spans: i64 = row.try_get("total_spans").unwrap_or(0);
+        let violating_spans: i64 = row.try_get("violating_spans").unwrap_or(0);
+
+        let breakdown_sql = format!(
+            r#"
+            SELECT v->>'key' as key, v->>'kind' as kind, COUNT(*) as cnt
+            FROM spans, jsonb_array_elements(attributes->'{key}') v
+            WHERE service_name = $1 AND started_at >= $2 AND started_at <= $3
+              AND attributes ? '{key}'
+            GROUP BY 1, 2
+            ORDER BY cnt DESC
+            "#,
+            key = SCHEMA_VIOLATIONS_ATTRIBUTE
+        );
+        let rows = sqlx::query(&breakdown_sql)
+            .bind(service_name)
+            .bind(since)
+            .bind(until)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let violations = rows
+            .iter()
+            .filter_map(|r| {
+                let kind: String = r.try_get("kind").ok()?;
+                Some(AttributeViolationCount {
+                    key: r.try_get("key").ok()?,
+                    kind: serde_json::from_value(serde_json::Value::String(kind)).ok()?,
+                    count: r.try_get("cnt").unwrap_or(0),
+                })
+            })
+            .collect();
+
+        let compliant_spans = total_spans - violating_spans;
+
+        Ok(SchemaComplianceReport {
+            service_name: service_name.to_string(),
+            since,
+            until,
+            total_spans,
+            compliant_spans,
+            compliance_rate: if total_spans > 0 {
+                compliant_spans as f64 / total_spans as f64 * 100.0
+            } else {
+                100.0
+            },
+            violations,
+        })
+    }
+}
+
+fn row_to_schema(row: &sqlx::postgres::PgRow) -> Result<AttributeSchema> {
+    let attributes: serde_json::Value = row.try_get("attributes").unwrap_or_default();
+    let latency_targets: serde_json::Value = row.try_get("latency_targets").unwrap_or_default();
+
+    Ok(AttributeSchema {
+        service_name: row.try_get("service_name").map_err(|e| Error::Database(e.to_string()))?,
+        attributes: serde_json::from_value(attributes).unwrap_or_default(),
+        latency_targets: serde_json::from_value(latency_targets).unwrap_or_default(),
+        created_at: row.try_get("created_at").unwrap_or_else(|_| Utc::now()),
+        updated_at: row.try_get("updated_at").unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+/// Schema migrations embedded from `migrations/`
+static MIGRATOR: Migrator = sqlx::migrate!("../../migrations");
+
+/// Scripts reverting each migration, by version
+///
+/// Every migration needs an entry here for `agenttrace db rollback` to get
+/// past it.
+const ROLLBACKS: &[(i64, &str)] = &[
+    (1, include_str!("../../../../migrations/rollback/001_initial_schema.sql")),
+    (2, include_str!("../../../../migrations/rollback/002_alerting.sql")),
+    (3, include_str!("../../../../migrations/rollback/003_attribute_schemas.sql")),
+    (4, include_str!("../../../../migrations/rollback/004_span_links.sql")),
+    (5, include_str!("../../../../migrations/rollback/005_compression.sql")),
+    (6, include_str!("../../../../migrations/rollback/006_span_priority.sql")),
+    (7, include_str!("../../../../migrations/rollback/007_span_archives.sql")),
+    (8, include_str!("../../../../migrations/rollback/008_alert_rule_schedules.sql")),
+    (9, include_str!("../../../../migrations/rollback/009_latency_targets.sql")),
+    (10, include_str!("../../../../migrations/rollback/010_full_text_search.sql")),
+    (11, include_str!("../../../../migrations/rollback/011_annotations.sql")),
+    (12, include_str!("../../../../migrations/rollback/012_trace_summaries.sql")),
+    (13, include_str!("../../../../migrations/rollback/013_service_activity.sql")),
+    (14, include_str!("../../../../migrations/rollback/014_alert_incidents.sql")),
+    (15, include_str!("../../../../migrations/rollback/015_saved_views.sql")),
+    (16, include_str!("../../../../migrations/rollback/016_trace_annotations.sql")),
+    (17, include_str!("../../../../migrations/rollback/017_webhook_subscriptions.sql")),
+    (18, include_str!("../../../../migrations/rollback/018_audit_log.sql")),
+    (19, include_str!("../../../../migrations/rollback/019_alert_rule_state.sql")),
+    (20, include_str!("../../../../migrations/rollback/020_alert_message_templates.sql")),
+    (21, include_str!("../../../../migrations/rollback/021_alert_escalation.sql")),
+    (22, include_str!("../../../../migrations/rollback/022_budgets.sql")),
+    (23, include_str!("../../../../migrations/rollback/023_trace_guardrails.sql")),
+    (24, include_str!("../../../../migrations/rollback/024_notification_retries.sql")),
+    (25, include_str!("../../../../migrations/rollback/025_alert_rule_revisions.sql")),
+    (26, include_str!("../../../../migrations/rollback/026_slos.sql")),
+    (27, include_str!("../../../../migrations/rollback/027_alert_acknowledgements.sql")),
+    (28, include_str!("../../../../migrations/rollback/028_span_cached_tokens.sql")),
+    (29, include_str!("../../../../migrations/rollback/029_span_in_progress.sql")),
+    (30, include_str!("../../../../migrations/rollback/030_sessions.sql")),
+    (31, include_str!("../../../../migrations/rollback/031_trace_feedback.sql")),
+    (32, include_str!("../../../../migrations/rollback/032_span_payloads.sql")),
+    (33, include_str!("../../../../migrations/rollback/033_in_progress_aggregates.sql")),
+    (34, include_str!("../../../../migrations/rollback/034_cost_hourly.sql")),
+    (35, include_str!("../../../../migrations/rollback/035_notification_retry_messages.sql")),
+    (36, include_str!("../../../../migrations/rollback/036_span_priority_check.sql")),
+    (37, include_str!("../../../../migrations/rollback/037_alert_event_trace_index.sql")),
+    (38, include_str!("../../../../migrations/rollback/038_trace_feedback_created_index.sql")),
+];
+
+fn migrate_error(e: MigrateError) -> Error {
+    Error::Database(format!("Migration failed: {}", e))
+}
+
+/// Hypertables with native compression enabled
+pub const COMPRESSION_TABLES: &[&str] = &["spans", "span_events"];
+
+/// Tables whose retention can be configured
+pub const RETENTION_TABLES: &[&str] = &[
+    "spans",
+    "span_events",
+    "metrics_hourly",
+    "metrics_5min",
+    "cost_hourly",
+    "service_activity",
+    "alert_events",
+];
+
+/// Columns selected when loading full spans
+const SPAN_COLUMNS: &str = r#"
+    id, span_id, trace_id, parent_span_id, operation_name, service_name,
+    span_kind, started_at, ended_at, duration_ms, status, status_message,
+    model_name, model_provider, tokens_in, tokens_out, tokens_reasoning, tokens_cached_in,
+    CAST(cost_usd AS DOUBLE PRECISION) as cost_usd,
+    tool_name, tool_input, tool_output, tool_duration_ms,
+    prompt_preview, completion_preview, attributes, events, links, priority,
+    session_id, user_id
+"#;
+
+/// Conflict clause for batch inserts
+///
+/// Redelivered spans are skipped, except that a stored `in_progress` span is
+/// replaced by its later update. Fields the update leaves empty keep their
+/// stored values; `cost_usd` is always taken from the update, since the
+/// pipeline merges the stored start event into it and recalculates the cost
+/// on the merged span. `inserted` tells new rows apart from updated ones.
+const SPAN_UPSERT: &str = r#"
+    ON CONFLICT (span_id, started_at) DO UPDATE SET
+        parent_span_id = COALESCE(EXCLUDED.parent_span_id, spans.parent_span_id),
+        session_id = COALESCE(EXCLUDED.session_id, spans.session_id),
+        user_id = COALESCE(EXCLUDED.user_id, spans.user_id),
+        operation_name = EXCLUDED.operation_name,
+        ended_at = COALESCE(EXCLUDED.ended_at, spans.ended_at),
+        duration_ms = COALESCE(EXCLUDED.duration_ms, spans.duration_ms),
+        status = EXCLUDED.status,
+        status_message = COALESCE(EXCLUDED.status_message, spans.status_message),
+        model_name = COALESCE(EXCLUDED.model_name, spans.model_name),
+        model_provider = COALESCE(EXCLUDED.model_provider, spans.model_provider),
+        tokens_in = COALESCE(EXCLUDED.tokens_in, spans.tokens_in),
+        tokens_out = COALESCE(EXCLUDED.tokens_out, spans.tokens_out),
+        tokens_reasoning = COALESCE(EXCLUDED.tokens_reasoning, spans.tokens_reasoning),
+        tokens_cached_in = COALESCE(EXCLUDED.tokens_cached_in, spans.tokens_cached_in),
+        cost_usd = EXCLUDED.cost_usd,
+        tool_name = COALESCE(EXCLUDED.tool_name, spans.tool_name),
+        tool_input = COALESCE(EXCLUDED.tool_input, spans.tool_input),
+        tool_output = COALESCE(EXCLUDED.tool_output, spans.tool_output),
+        tool_duration_ms = COALESCE(EXCLUDED.tool_duration_ms, spans.tool_duration_ms),
+        prompt_preview = COALESCE(EXCLUDED.prompt_preview, spans.prompt_preview),
+        completion_preview = COALESCE(EXCLUDED.completion_preview, spans.completion_preview),
+        attributes = COALESCE(spans.attributes, '{}'::jsonb) || COALESCE(EXCLUDED.attributes, '{}'::jsonb),
+        events = CASE WHEN jsonb_array_length(EXCLUDED.events) > 0
+            THEN EXCLUDED.events ELSE spans.events END,
+        links = CASE WHEN jsonb_array_length(EXCLUDED.links) > 0
+            THEN EXCLUDED.links ELSE spans.links END,
+        priority = EXCLUDED.priority
+    WHERE spans.status = 'in_progress'
+    RETURNING span_id, (xmax = 0) AS inserted
+"#;
+
+/// Columns selected when loading sessions
+const SESSION_COLUMNS: &str = r#"
+    session_id, user_id, started_at, last_seen, trace_count, span_count,
+    error_count, total_tokens, total_cost_usd
+"#;
+
+/// Columns selected when loading trace summaries
+const TRACE_SUMMARY_COLUMNS: &str = r#"
+    trace_id, root_id, root_operation, service_name, started_at, duration_ms,
+    span_count, error_count, total_tokens, total_cost_usd, llm_ms, tool_ms
+"#;
+
+/// Weighted document searched by full-text search
+///
+/// Must match the expression indexed by `idx_spans_text_search` exactly, or
+/// the planner will not use the index.
+const TEXT_SEARCH_VECTOR: &str = "(setweight(to_tsvector('english', coalesce(operation_name, '')), 'A') \
+    || setweight(to_tsvector('english', coalesce(prompt_preview, '')), 'B') \
+    || setweight(to_tsvector('english', coalesce(completion_preview, '')), 'B'))";
+
+/// Span fields highlighted in full-text search results
+const TEXT_SEARCH_FIELDS: &[&str] = &["operation_name", "prompt_preview", "completion_preview"];
+
+/// `ts_headline` options for search highlights
+const TEXT_HEADLINE_OPTIONS: &str = "StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10";
+
+/// Span columns that may appear in ORDER BY
+const SORTABLE_COLUMNS: &[&str] = &[
+    "started_at",
+    "ended_at",
+    "duration_ms",
+    "cost_usd",
+    "tokens_in",
+    "tokens_out",
+    "operation_name",
+    "service_name",
+    "model_name",
+    "status",
+];
+
+/// Span columns that may be used in advanced search filters
+const FILTERABLE_COLUMNS: &[&str] = &[
+    "span_id",
+    "trace_id",
+    "parent_span_id",
+    "session_id",
+    "user_id",
+    "operation_name",
+    "service_name",
+    "span_kind",
+    "started_at",
+    "ended_at",
+    "duration_ms",
+    "status",
+    "status_message",
+    "model_name",
+    "model_provider",
+    "tokens_in",
+    "tokens_out",
+    "tokens_reasoning",
+    "tokens_cached_in",
+    "cost_usd",
+    "tool_name",
+    "tool_duration_ms",
+    "prompt_preview",
+    "completion_preview",
+    "priority",
+];
+
+/// Resolve a user-supplied sort field to a known column
+pub(super) fn sortable_column(field: &str) -> Result<&'static str> {
+    SORTABLE_COLUMNS
+        .iter()
+        .find(|c| **c == field)
+        .copied()
+        .ok_or_else(|| Error::validation(format!("Cannot sort by '{}'", field)))
+}
+
+/// Resolve a user-supplied filter field to a known column
+pub(super) fn filterable_column(field: &str) -> Result<&'static str> {
+    FILTERABLE_COLUMNS
+        .iter()
+        .find(|c| **c == field)
+        .copied()
+        .ok_or_else(|| Error::validation(format!("Cannot filter by '{}'", field)))
+}
+
+/// SQL for an aggregation metric; every variant maps to a fixed expression
+fn aggregate_metric_sql(metric: AggregateMetric) -> &'static str {
+    match metric {
+        AggregateMetric::Count => "COUNT(*)::DOUBLE PRECISION",
+        AggregateMetric::SumCost => "COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION",
+        AggregateMetric::SumTokens => {
+            "COALESCE(SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)), 0)::DOUBLE PRECISION"
+        }
+        AggregateMetric::AvgLatency => "COALESCE(AVG(duration_ms), 0)::DOUBLE PRECISION",
+        AggregateMetric::P50Latency => {
+            "COALESCE(percentile_cont(0.5) WITHIN GROUP (ORDER BY duration_ms), 0)::DOUBLE PRECISION"
+        }
+        AggregateMetric::P95Latency => {
+            "COALESCE(percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms), 0)::DOUBLE PRECISION"
+        }
+        AggregateMetric::P99Latency => {
+            "COALESCE(percentile_cont(0.99) WITHIN GROUP (ORDER BY duration_ms), 0)::DOUBLE PRECISION"
+        }
+        AggregateMetric::ErrorRate => {
+            "COALESCE(COUNT(*) FILTER (WHERE status = 'error') * 100.0 / NULLIF(COUNT(*), 0), 0)::DOUBLE PRECISION"
+        }
+    }
+}
+
+/// Append the expression an aggregation groups by, binding attribute keys
+fn push_group_expr(query: &mut QueryBuilder<'_, Postgres>, group: &AggregateGroup) {
+    match group {
+        AggregateGroup::Service => {
+            query.push("service_name");
+        }
+        AggregateGroup::Model => {
+            query.push("COALESCE(model_name, 'unknown')");
+        }
+        AggregateGroup::Operation => {
+            query.push("operation_name");
+        }
+        AggregateGroup::Status => {
+            query.push("status");
+        }
+        AggregateGroup::Attribute(key) => {
+            query.push("COALESCE(attributes ->> ").push_bind(key.clone()).push(", 'unknown')");
+        }
+    }
+}
+
+/// Escape LIKE wildcards so user input matches literally
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// A value bound into a dynamically built query
+#[derive(Debug, Clone)]
+enum BindValue {
+    Text(String),
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+    Time(DateTime<Utc>),
+    Uuid(Uuid),
+}
+
+impl BindValue {
+    fn push_bind(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Text(v) => {
+                query.push_bind(v.clone());
+            }
+            Self::Float(v) => {
+                query.push_bind(*v);
+            }
+            Self::Int(v) => {
+                query.push_bind(*v);
+            }
+            Self::Bool(v) => {
+                query.push_bind(*v);
+            }
+            Self::Time(v) => {
+                query.push_bind(*v);
+            }
+            Self::Uuid(v) => {
+                query.push_bind(*v);
+            }
+        }
+    }
+}
+
+impl From<&str> for BindValue {
+    fn from(v: &str) -> Self {
+        Self::Text(v.to_string())
+    }
+}
+
+impl From<String> for BindValue {
+    fn from(v: String) -> Self {
+        Self::Text(v)
+    }
+}
+
+impl From<f64> for BindValue {
+    fn from(v: f64) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<i64> for BindValue {
+    fn from(v: i64) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<bool> for BindValue {
+    fn from(v: bool) -> Self {
+        Self::Bool(v)
+    }
+}
+
+impl From<DateTime<Utc>> for BindValue {
+    fn from(v: DateTime<Utc>) -> Self {
+        Self::Time(v)
+    }
+}
+
+impl From<Uuid> for BindValue {
+    fn from(v: Uuid) -> Self {
+        Self::Uuid(v)
     }
+}
 
-    // =========================================================================
-    // Alerting Metric Methods
-    // =========================================================================
+/// WHERE clause whose values are sent as bind parameters, never as SQL text
+#[derive(Debug, Clone, Default)]
+struct SpanFilter {
+    conditions: Vec<(String, Vec<BindValue>)>,
+}
 
-    /// Get error statistics for alerting
-    pub async fn get_error_stats(
-        &self,
+impl SpanFilter {
+    /// Time range and service/model scope shared by the metrics queries
+    ///
+    /// Streaming spans still in progress are left out until they finish, so
+    /// metrics and alerts count each call once, with its final status.
+    fn scoped(
         service: Option<&str>,
         model: Option<&str>,
         since: DateTime<Utc>,
         until: DateTime<Utc>,
-    ) -> Result<ErrorStats> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+    ) -> Self {
+        let mut filter = Self::default();
+        filter.bind("started_at >= $?", since);
+        filter.bind("started_at <= $?", until);
+        filter.raw("status <> 'in_progress'");
 
         if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+            filter.bind("service_name = $?", svc);
         }
 
         if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+            filter.bind("model_name = $?", m);
         }
 
-        let where_clause = conditions.join(" AND ");
+        filter
+    }
 
-        let sql = format!(
-            r#"
-            SELECT
-                SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
-                COUNT(*) as total,
-                ARRAY_AGG(DISTINCT trace_id) FILTER (WHERE status = 'error') as sample_trace_ids
-            FROM spans
-            WHERE {}
-            "#,
-            where_clause
-        );
+    /// Conditions for advanced search filters
+    fn from_search_filters(filters: &[SearchFilter]) -> Result<Self> {
+        let mut filter = Self::default();
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+        for f in filters {
+            if let Some(key) = f.attribute_key() {
+                let value = &f.value;
+                if value.is_string() || value.is_number() || value.is_boolean() {
+                    filter.attribute(key, &f.operator, value)?;
+                }
+                continue;
+            }
 
-        Ok(ErrorStats {
-            error_count: row.try_get("error_count").unwrap_or(0),
-            total: row.try_get("total").unwrap_or(0),
-            sample_trace_ids: row.try_get::<Vec<String>, _>("sample_trace_ids").unwrap_or_default(),
-        })
+            let column = filterable_column(&f.field)?;
+            let op = match f.operator.as_str() {
+                "eq" => "=",
+                "ne" => "!=",
+                "gt" => ">",
+                "gte" => ">=",
+                "lt" => "<",
+                "lte" => "<=",
+                "contains" => "ILIKE",
+                other => {
+                    return Err(Error::validation(format!("Unsupported filter operator: {}", other)))
+                }
+            };
+
+            let value = match &f.value {
+                serde_json::Value::String(s) if f.operator == "contains" => {
+                    BindValue::Text(format!("%{}%", escape_like(s)))
+                }
+                serde_json::Value::String(s) => BindValue::Text(s.clone()),
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => BindValue::Int(i),
+                    None => BindValue::Float(n.as_f64().unwrap_or_default()),
+                },
+                serde_json::Value::Bool(b) => BindValue::Bool(*b),
+                _ => continue,
+            };
+
+            filter.bind(&format!("{} {} $?", column, op), value);
+        }
+
+        Ok(filter)
     }
 
-    /// Get latency percentile for alerting
-    pub async fn get_latency_percentile(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-        percentile: f64,
-    ) -> Result<Option<f64>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-            "duration_ms IS NOT NULL".to_string(),
-        ];
+    /// Conditions for a parsed search query
+    fn from_query(query: &SpanQuery) -> Result<Self> {
+        let mut filter = Self::default();
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+        if let Some(q) = &query.text {
+            let pattern = format!("%{}%", escape_like(q));
+            filter.push(
+                "(operation_name ILIKE $? OR prompt_preview ILIKE $? OR completion_preview ILIKE $?)",
+                vec![pattern.clone().into(), pattern.clone().into(), pattern.into()],
+            );
         }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+        if let Some(op) = &query.operation {
+            filter.bind("operation_name ILIKE $?", format!("%{}%", escape_like(op)));
         }
 
-        let where_clause = conditions.join(" AND ");
+        if let Some(svc) = &query.service {
+            filter.bind("service_name = $?", svc.as_str());
+        }
 
-        let sql = format!(
-            r#"
-            SELECT PERCENTILE_CONT({}) WITHIN GROUP (ORDER BY duration_ms) as p_val
-            FROM spans
-            WHERE {}
-            "#,
-            percentile, where_clause
-        );
+        if let Some(m) = &query.model {
+            filter.bind("model_name = $?", m.as_str());
+        }
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+        if let Some(s) = &query.status {
+            filter.bind("status = $?", s.as_str());
+        }
 
-        Ok(row.try_get::<f64, _>("p_val").ok())
-    }
+        if let Some(p) = query.priority {
+            filter.bind("priority = $?", p.as_str());
+        }
 
-    /// Get average latency for alerting
-    pub async fn get_latency_avg(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-    ) -> Result<Option<f64>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-            "duration_ms IS NOT NULL".to_string(),
-        ];
+        if let Some(min) = query.min_duration {
+            filter.bind("duration_ms >= $?", min);
+        }
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+        if let Some(max) = query.max_duration {
+            filter.bind("duration_ms <= $?", max);
         }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+        if let Some(min) = query.min_cost {
+            filter.bind("cost_usd >= $?", min);
         }
 
-        let where_clause = conditions.join(" AND ");
+        if let Some(max) = query.max_cost {
+            filter.bind("cost_usd <= $?", max);
+        }
 
-        let sql = format!(
-            "SELECT AVG(duration_ms) as avg_val FROM spans WHERE {}",
-            where_clause
-        );
+        for (key, value) in &query.attributes {
+            filter.attribute(key, "eq", &serde_json::Value::String(value.clone()))?;
+        }
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+        if let Some(has) = query.has_negative_feedback {
+            filter.negative_feedback(has);
+        }
 
-        Ok(row.try_get::<f64, _>("avg_val").ok())
+        Ok(filter)
     }
 
-    /// Get total cost for alerting
-    pub async fn get_cost_sum(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-    ) -> Result<Option<f64>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+    /// Keep spans of traces that did, or did not, get a negative rating
+    fn negative_feedback(&mut self, has: bool) -> &mut Self {
+        self.raw(&format!(
+            "trace_id {}IN (SELECT trace_id FROM trace_feedback WHERE rating = 'negative')",
+            if has { "" } else { "NOT " }
+        ))
+    }
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+    /// Keep traces with a span that matches every condition of another filter
+    fn traces_with(&mut self, spans: SpanFilter) -> &mut Self {
+        let (sql, values): (Vec<String>, Vec<Vec<BindValue>>) = spans.conditions.into_iter().unzip();
+        self.push(
+            &format!("trace_id IN (SELECT trace_id FROM spans WHERE {})", sql.join(" AND ")),
+            values.into_iter().flatten().collect(),
+        )
+    }
+
+    /// Add a condition with no bound values
+    fn raw(&mut self, sql: &str) -> &mut Self {
+        self.conditions.push((sql.to_string(), Vec::new()));
+        self
+    }
+
+    /// Add a condition with a single bound value in place of `$?`
+    fn bind(&mut self, sql: &str, value: impl Into<BindValue>) -> &mut Self {
+        self.push(sql, vec![value.into()])
+    }
+
+    /// Add a condition; each `$?` in `sql` takes the next value from `values`
+    fn push(&mut self, sql: &str, values: Vec<BindValue>) -> &mut Self {
+        debug_assert_eq!(sql.matches("$?").count(), values.len());
+        self.conditions.push((sql.to_string(), values));
+        self
+    }
+
+    /// Add a condition on an attribute, keeping equality as JSONB containment
+    /// so it can use the GIN index on `attributes`
+    ///
+    /// As with columns, comparisons against a missing attribute never match,
+    /// and ordering comparisons only match values of the same JSON type.
+    fn attribute(&mut self, key: &str, operator: &str, value: &serde_json::Value) -> Result<&mut Self> {
+        let contains = || {
+            let candidates = attribute_match_values(value);
+            let sql = vec!["attributes @> jsonb_build_object($?::text, $?::jsonb)"; candidates.len()];
+            let values = candidates
+                .into_iter()
+                .flat_map(|v| [BindValue::from(key), BindValue::Text(v.to_string())])
+                .collect::<Vec<_>>();
+            (format!("({})", sql.join(" OR ")), values)
+        };
+
+        match operator {
+            "eq" => {
+                let (sql, values) = contains();
+                Ok(self.push(&sql, values))
+            }
+            "ne" => {
+                let (sql, mut values) = contains();
+                values.insert(0, key.into());
+                Ok(self.push(&format!("(attributes ? $? AND NOT {})", sql), values))
+            }
+            "contains" => {
+                let pattern = format!("%{}%", escape_like(value.as_str().unwrap_or_default()));
+                Ok(self.push("attributes ->> $? ILIKE $?", vec![key.into(), pattern.into()]))
+            }
+            "gt" | "gte" | "lt" | "lte" => {
+                let op = match operator {
+                    "gt" => ">",
+                    "gte" => ">=",
+                    "lt" => "<",
+                    _ => "<=",
+                };
+                let json = value.to_string();
+                Ok(self.push(
+                    &format!(
+                        "(jsonb_typeof(attributes -> $?) = jsonb_typeof($?::jsonb) AND attributes -> $? {} $?::jsonb)",
+                        op
+                    ),
+                    vec![key.into(), json.clone().into(), key.into(), json.into()],
+                ))
+            }
+            other => Err(Error::validation(format!("Unsupported filter operator: {}", other))),
         }
+    }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+    /// Append ` WHERE ...` (if there are any conditions) to a query
+    fn push_where(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        for (i, (sql, values)) in self.conditions.iter().enumerate() {
+            query.push(if i == 0 { " WHERE " } else { " AND " });
+
+            let mut values = values.iter();
+            let mut parts = sql.split("$?").peekable();
+            while let Some(part) = parts.next() {
+                query.push(part);
+                if parts.peek().is_some() {
+                    if let Some(value) = values.next() {
+                        value.push_bind(query);
+                    }
+                }
+            }
         }
+    }
+}
 
-        let where_clause = conditions.join(" AND ");
+/// Copy span events into `span_events` so they can be queried by name and time
+async fn insert_events(conn: &mut sqlx::PgConnection, spans: &[&Span]) -> Result<()> {
+    let mut span_ids = Vec::new();
+    let mut trace_ids = Vec::new();
+    let mut names = Vec::new();
+    let mut timestamps = Vec::new();
+    let mut attributes = Vec::new();
+
+    for span in spans {
+        for event in &span.events {
+            span_ids.push(span.span_id.clone());
+            trace_ids.push(span.trace_id.clone());
+            names.push(event.name.chars().take(255).collect::<String>());
+            timestamps.push(event.timestamp);
+            attributes.push(event.attributes.clone());
+        }
+    }
 
-        let sql = format!(
-            "SELECT SUM(COALESCE(cost_usd, 0)) as total_cost FROM spans WHERE {}",
-            where_clause
-        );
+    if span_ids.is_empty() {
+        return Ok(());
+    }
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+    sqlx::query(
+        r#"
+        INSERT INTO span_events (span_id, trace_id, name, timestamp, attributes)
+        SELECT * FROM UNNEST($1::varchar[], $2::varchar[], $3::varchar[], $4::timestamptz[], $5::jsonb[])
+        "#,
+    )
+    .bind(span_ids)
+    .bind(trace_ids)
+    .bind(names)
+    .bind(timestamps)
+    .bind(attributes)
+    .execute(conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(())
+}
 
-        Ok(row.try_get::<f64, _>("total_cost").ok())
+/// Replace the `span_events` rows of updated spans that carry events
+///
+/// Updates without events keep the events stored with the start event.
+async fn replace_events(conn: &mut sqlx::PgConnection, spans: &[&Span]) -> Result<()> {
+    let spans: Vec<&Span> = spans.iter().copied().filter(|s| !s.events.is_empty()).collect();
+    if spans.is_empty() {
+        return Ok(());
     }
 
-    /// Get total token count for alerting
-    pub async fn get_token_sum(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-    ) -> Result<Option<i64>> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+    let span_ids: Vec<String> = spans.iter().map(|s| s.span_id.clone()).collect();
+    let trace_ids: Vec<String> = spans.iter().map(|s| s.trace_id.clone()).collect();
+    sqlx::query(
+        r#"
+        DELETE FROM span_events e
+        USING UNNEST($1::varchar[], $2::varchar[]) AS t(span_id, trace_id)
+        WHERE e.span_id = t.span_id AND e.trace_id = t.trace_id
+        "#,
+    )
+    .bind(span_ids)
+    .bind(trace_ids)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    insert_events(conn, &spans).await
+}
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
+/// Running totals for one trace within an inserted batch
+#[derive(Default)]
+struct TraceTotals<'a> {
+    root: Option<&'a Span>,
+    span_count: i64,
+    error_count: i64,
+    total_tokens: i64,
+    total_cost_usd: f64,
+    llm_ms: f64,
+    tool_ms: f64,
+}
+
+/// Fold newly inserted spans into `trace_summaries`
+///
+/// Only pass spans that were actually inserted, or redelivered spans are
+/// counted twice.
+async fn upsert_trace_summaries(conn: &mut sqlx::PgConnection, spans: &[&Span]) -> Result<()> {
+    // Sorted by trace so concurrent batches lock summary rows in the same order
+    let mut traces: BTreeMap<&str, TraceTotals> = BTreeMap::new();
+    for span in spans {
+        let totals = traces.entry(span.trace_id.as_str()).or_default();
+        if span.parent_span_id.is_none()
+            && totals.root.map_or(true, |r| (span.started_at, span.id) < (r.started_at, r.id))
+        {
+            totals.root = Some(span);
         }
+        totals.span_count += 1;
+        if span.status == SpanStatus::Error {
+            totals.error_count += 1;
+        }
+        totals.total_tokens += i64::from(span.tokens_in.unwrap_or(0)) + i64::from(span.tokens_out.unwrap_or(0));
+        totals.total_cost_usd += span.cost_usd.unwrap_or(0.0);
+        if span.is_llm_call() {
+            totals.llm_ms += span.duration_ms.unwrap_or(0.0);
+        } else if span.is_tool_call() {
+            totals.tool_ms += span.tool_duration_ms.or(span.duration_ms).unwrap_or(0.0);
+        }
+    }
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+    if traces.is_empty() {
+        return Ok(());
+    }
+
+    let n = traces.len();
+    let mut trace_ids = Vec::with_capacity(n);
+    let mut root_ids = Vec::with_capacity(n);
+    let mut root_operations = Vec::with_capacity(n);
+    let mut service_names = Vec::with_capacity(n);
+    let mut statuses = Vec::with_capacity(n);
+    let mut started_ats = Vec::with_capacity(n);
+    let mut durations = Vec::with_capacity(n);
+    let mut span_counts = Vec::with_capacity(n);
+    let mut error_counts = Vec::with_capacity(n);
+    let mut total_tokens = Vec::with_capacity(n);
+    let mut costs = Vec::with_capacity(n);
+    let mut llm_ms = Vec::with_capacity(n);
+    let mut tool_ms = Vec::with_capacity(n);
+
+    for (trace_id, totals) in traces {
+        trace_ids.push(trace_id.to_string());
+        root_ids.push(totals.root.map(|r| r.id));
+        root_operations.push(totals.root.map(|r| r.operation_name.clone()));
+        service_names.push(totals.root.map(|r| r.service_name.clone()));
+        statuses.push(totals.root.map(|r| span_status_to_str(&r.status)));
+        started_ats.push(totals.root.map(|r| r.started_at));
+        durations.push(totals.root.and_then(|r| r.duration_ms));
+        span_counts.push(totals.span_count);
+        error_counts.push(totals.error_count);
+        total_tokens.push(totals.total_tokens);
+        costs.push(totals.total_cost_usd);
+        llm_ms.push(totals.llm_ms);
+        tool_ms.push(totals.tool_ms);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO trace_summaries (
+            trace_id, root_id, root_operation, service_name, status, started_at, duration_ms,
+            span_count, error_count, total_tokens, total_cost_usd, llm_ms, tool_ms
+        )
+        SELECT * FROM UNNEST(
+            $1::varchar[], $2::uuid[], $3::varchar[], $4::varchar[], $5::varchar[],
+            $6::timestamptz[], $7::float8[], $8::int8[], $9::int8[], $10::int8[],
+            $11::float8[], $12::float8[], $13::float8[]
+        )
+        ON CONFLICT (trace_id) DO UPDATE SET
+            root_id = COALESCE(trace_summaries.root_id, EXCLUDED.root_id),
+            root_operation = CASE WHEN trace_summaries.root_id IS NULL
+                THEN EXCLUDED.root_operation ELSE trace_summaries.root_operation END,
+            service_name = CASE WHEN trace_summaries.root_id IS NULL
+                THEN EXCLUDED.service_name ELSE trace_summaries.service_name END,
+            status = CASE WHEN trace_summaries.root_id IS NULL
+                THEN EXCLUDED.status ELSE trace_summaries.status END,
+            started_at = CASE WHEN trace_summaries.root_id IS NULL
+                THEN EXCLUDED.started_at ELSE trace_summaries.started_at END,
+            duration_ms = CASE WHEN trace_summaries.root_id IS NULL
+                THEN EXCLUDED.duration_ms ELSE trace_summaries.duration_ms END,
+            span_count = trace_summaries.span_count + EXCLUDED.span_count,
+            error_count = trace_summaries.error_count + EXCLUDED.error_count,
+            total_tokens = trace_summaries.total_tokens + EXCLUDED.total_tokens,
+            total_cost_usd = trace_summaries.total_cost_usd + EXCLUDED.total_cost_usd,
+            llm_ms = trace_summaries.llm_ms + EXCLUDED.llm_ms,
+            tool_ms = trace_summaries.tool_ms + EXCLUDED.tool_ms,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(trace_ids)
+    .bind(root_ids)
+    .bind(root_operations)
+    .bind(service_names)
+    .bind(statuses)
+    .bind(started_ats)
+    .bind(durations)
+    .bind(span_counts)
+    .bind(error_counts)
+    .bind(total_tokens)
+    .bind(costs)
+    .bind(llm_ms)
+    .bind(tool_ms)
+    .execute(conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Running totals for one service and hour within an inserted batch
+struct ActivityTotals {
+    span_count: i64,
+    error_count: i64,
+    cost_usd: f64,
+    last_seen: DateTime<Utc>,
+}
+
+/// Fold newly inserted spans into the hourly `service_activity` buckets
+async fn upsert_service_activity(conn: &mut sqlx::PgConnection, spans: &[&Span]) -> Result<()> {
+    // Sorted by service and hour so concurrent batches lock rows in the same order
+    let mut buckets: BTreeMap<(&str, DateTime<Utc>), ActivityTotals> = BTreeMap::new();
+    for span in spans {
+        let hour = span.started_at.duration_trunc(Duration::hours(1)).unwrap_or(span.started_at);
+        let entry = buckets
+            .entry((span.service_name.as_str(), hour))
+            .or_insert(ActivityTotals {
+                span_count: 0,
+                error_count: 0,
+                cost_usd: 0.0,
+                last_seen: span.started_at,
+            });
+        entry.span_count += 1;
+        if span.status == SpanStatus::Error {
+            entry.error_count += 1;
         }
+        entry.cost_usd += span.cost_usd.unwrap_or(0.0);
+        entry.last_seen = entry.last_seen.max(span.started_at);
+    }
 
-        let where_clause = conditions.join(" AND ");
+    if buckets.is_empty() {
+        return Ok(());
+    }
 
-        let sql = format!(
-            "SELECT SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens FROM spans WHERE {}",
-            where_clause
-        );
+    let mut hours = Vec::with_capacity(buckets.len());
+    let mut service_names = Vec::with_capacity(buckets.len());
+    let mut span_counts = Vec::with_capacity(buckets.len());
+    let mut error_counts = Vec::with_capacity(buckets.len());
+    let mut costs = Vec::with_capacity(buckets.len());
+    let mut last_seen = Vec::with_capacity(buckets.len());
+    for ((service, hour), totals) in buckets {
+        hours.push(hour);
+        service_names.push(service.to_string());
+        span_counts.push(totals.span_count);
+        error_counts.push(totals.error_count);
+        costs.push(totals.cost_usd);
+        last_seen.push(totals.last_seen);
+    }
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+    sqlx::query(
+        r#"
+        INSERT INTO service_activity (bucket, service_name, span_count, error_count, cost_usd, last_seen)
+        SELECT * FROM UNNEST(
+            $1::timestamptz[], $2::varchar[], $3::int8[], $4::int8[], $5::float8[], $6::timestamptz[]
+        )
+        ON CONFLICT (service_name, bucket) DO UPDATE SET
+            span_count = service_activity.span_count + EXCLUDED.span_count,
+            error_count = service_activity.error_count + EXCLUDED.error_count,
+            cost_usd = service_activity.cost_usd + EXCLUDED.cost_usd,
+            last_seen = GREATEST(service_activity.last_seen, EXCLUDED.last_seen)
+        "#,
+    )
+    .bind(hours)
+    .bind(service_names)
+    .bind(span_counts)
+    .bind(error_counts)
+    .bind(costs)
+    .bind(last_seen)
+    .execute(conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(())
+}
 
-        Ok(row.try_get::<i64, _>("total_tokens").ok())
+/// Running totals for one session within an inserted batch
+struct SessionTotals<'a> {
+    user_id: Option<&'a str>,
+    started_at: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    span_count: i64,
+    error_count: i64,
+    total_tokens: i64,
+    total_cost_usd: f64,
+}
+
+/// Sum the totals of sessions that lost traces again from the traces left
+///
+/// Sessions with no traces left keep their totals; they are deleted by the
+/// caller.
+async fn reconcile_sessions(conn: &mut sqlx::PgConnection, session_ids: &[String]) -> Result<()> {
+    if session_ids.is_empty() {
+        return Ok(());
     }
 
-    /// Get span count for alerting
-    pub async fn get_span_count(
-        &self,
-        service: Option<&str>,
-        model: Option<&str>,
-        since: DateTime<Utc>,
-        until: DateTime<Utc>,
-    ) -> Result<i64> {
-        let mut conditions = vec![
-            format!("started_at >= '{}'", since.format("%Y-%m-%d %H:%M:%S")),
-            format!("started_at <= '{}'", until.format("%Y-%m-%d %H:%M:%S")),
-        ];
+    sqlx::query(
+        r#"
+        UPDATE sessions s SET
+            started_at = t.started_at,
+            last_seen = GREATEST(t.last_seen, t.started_at),
+            trace_count = t.trace_count,
+            span_count = t.span_count,
+            error_count = t.error_count,
+            total_tokens = t.total_tokens,
+            total_cost_usd = t.total_cost_usd,
+            updated_at = NOW()
+        FROM (
+            SELECT
+                st.session_id,
+                MIN(st.started_at) AS started_at,
+                MAX(ts.started_at + COALESCE(ts.duration_ms, 0) * INTERVAL '1 millisecond') AS last_seen,
+                COUNT(*) AS trace_count,
+                SUM(ts.span_count)::BIGINT AS span_count,
+                SUM(ts.error_count)::BIGINT AS error_count,
+                SUM(ts.total_tokens)::BIGINT AS total_tokens,
+                SUM(ts.total_cost_usd) AS total_cost_usd
+            FROM session_traces st
+            JOIN trace_summaries ts ON ts.trace_id = st.trace_id
+            WHERE st.session_id = ANY($1)
+            GROUP BY st.session_id
+        ) t
+        WHERE s.session_id = t.session_id
+        "#,
+    )
+    .bind(session_ids)
+    .execute(conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(())
+}
 
-        if let Some(svc) = service {
-            conditions.push(format!("service_name = '{}'", svc.replace('\'', "''")));
-        }
+/// The sessions a batch of spans touches, sorted so concurrent batches lock
+/// rows in the same order
+#[derive(Default)]
+struct SessionBatch<'a> {
+    /// Totals per session
+    sessions: BTreeMap<&'a str, SessionTotals<'a>>,
+    /// Start of each trace, by session and trace ID
+    traces: BTreeMap<(&'a str, &'a str), DateTime<Utc>>,
+}
 
-        if let Some(m) = model {
-            conditions.push(format!("model_name = '{}'", m.replace('\'', "''")));
+/// Sum a batch of spans per session, skipping spans outside any session
+fn fold_sessions<'a>(spans: &[&'a Span]) -> SessionBatch<'a> {
+    let SessionBatch { mut sessions, mut traces } = SessionBatch::default();
+    for span in spans {
+        let Some(session_id) = span.session_id.as_deref() else {
+            continue;
+        };
+        let last_seen = span.ended_at.unwrap_or(span.started_at);
+        let totals = sessions.entry(session_id).or_insert(SessionTotals {
+            user_id: None,
+            started_at: span.started_at,
+            last_seen,
+            span_count: 0,
+            error_count: 0,
+            total_tokens: 0,
+            total_cost_usd: 0.0,
+        });
+        totals.user_id = totals.user_id.or(span.user_id.as_deref());
+        totals.started_at = totals.started_at.min(span.started_at);
+        totals.last_seen = totals.last_seen.max(last_seen);
+        totals.span_count += 1;
+        if span.status == SpanStatus::Error {
+            totals.error_count += 1;
         }
+        totals.total_tokens += i64::from(span.tokens_in.unwrap_or(0)) + i64::from(span.tokens_out.unwrap_or(0));
+        totals.total_cost_usd += span.cost_usd.unwrap_or(0.0);
 
-        let where_clause = conditions.join(" AND ");
+        let started = traces.entry((session_id, span.trace_id.as_str())).or_insert(span.started_at);
+        *started = (*started).min(span.started_at);
+    }
 
-        let sql = format!("SELECT COUNT(*) as cnt FROM spans WHERE {}", where_clause);
+    SessionBatch { sessions, traces }
+}
 
-        let row = sqlx::query(&sql)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| Error::Database(e.to_string()))?;
+/// Fold newly inserted spans into `sessions`
+///
+/// Traces are recorded in `session_traces`, and only those new to their
+/// session add to its trace count. As with trace summaries, only pass spans
+/// that were actually inserted.
+async fn upsert_sessions(conn: &mut sqlx::PgConnection, spans: &[&Span]) -> Result<()> {
+    let SessionBatch { sessions, traces } = fold_sessions(spans);
+    if sessions.is_empty() {
+        return Ok(());
+    }
 
-        Ok(row.try_get("cnt").unwrap_or(0))
+    let mut session_ids = Vec::with_capacity(traces.len());
+    let mut trace_ids = Vec::with_capacity(traces.len());
+    let mut started_ats = Vec::with_capacity(traces.len());
+    for ((session_id, trace_id), started_at) in traces {
+        session_ids.push(session_id.to_string());
+        trace_ids.push(trace_id.to_string());
+        started_ats.push(started_at);
+    }
+
+    let new_traces: Vec<String> = sqlx::query_scalar(
+        r#"
+        INSERT INTO session_traces (session_id, trace_id, started_at)
+        SELECT * FROM UNNEST($1::varchar[], $2::varchar[], $3::timestamptz[])
+        ON CONFLICT (session_id, trace_id) DO NOTHING
+        RETURNING session_id
+        "#,
+    )
+    .bind(session_ids)
+    .bind(trace_ids)
+    .bind(started_ats)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    let n = sessions.len();
+    let mut session_ids = Vec::with_capacity(n);
+    let mut user_ids = Vec::with_capacity(n);
+    let mut started_ats = Vec::with_capacity(n);
+    let mut last_seen = Vec::with_capacity(n);
+    let mut trace_counts = Vec::with_capacity(n);
+    let mut span_counts = Vec::with_capacity(n);
+    let mut error_counts = Vec::with_capacity(n);
+    let mut total_tokens = Vec::with_capacity(n);
+    let mut costs = Vec::with_capacity(n);
+    for (session_id, totals) in sessions {
+        trace_counts.push(new_traces.iter().filter(|s| s.as_str() == session_id).count() as i64);
+        session_ids.push(session_id.to_string());
+        user_ids.push(totals.user_id.map(str::to_string));
+        started_ats.push(totals.started_at);
+        last_seen.push(totals.last_seen);
+        span_counts.push(totals.span_count);
+        error_counts.push(totals.error_count);
+        total_tokens.push(totals.total_tokens);
+        costs.push(totals.total_cost_usd);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO sessions (
+            session_id, user_id, started_at, last_seen, trace_count, span_count,
+            error_count, total_tokens, total_cost_usd
+        )
+        SELECT * FROM UNNEST(
+            $1::varchar[], $2::varchar[], $3::timestamptz[], $4::timestamptz[], $5::int8[],
+            $6::int8[], $7::int8[], $8::int8[], $9::float8[]
+        )
+        ON CONFLICT (session_id) DO UPDATE SET
+            user_id = COALESCE(sessions.user_id, EXCLUDED.user_id),
+            started_at = LEAST(sessions.started_at, EXCLUDED.started_at),
+            last_seen = GREATEST(sessions.last_seen, EXCLUDED.last_seen),
+            trace_count = sessions.trace_count + EXCLUDED.trace_count,
+            span_count = sessions.span_count + EXCLUDED.span_count,
+            error_count = sessions.error_count + EXCLUDED.error_count,
+            total_tokens = sessions.total_tokens + EXCLUDED.total_tokens,
+            total_cost_usd = sessions.total_cost_usd + EXCLUDED.total_cost_usd,
+            updated_at = NOW()
+        "#,
+    )
+    .bind(session_ids)
+    .bind(user_ids)
+    .bind(started_ats)
+    .bind(last_seen)
+    .bind(trace_counts)
+    .bind(span_counts)
+    .bind(error_counts)
+    .bind(total_tokens)
+    .bind(costs)
+    .execute(conn)
+    .await
+    .map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Narrow a query over `trace_summaries t` to traces of a service started
+/// in a window
+fn push_trace_window(
+    query: &mut QueryBuilder<'_, Postgres>,
+    service: Option<&str>,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) {
+    query.push(" AND t.started_at >= ").push_bind(since);
+    query.push(" AND t.started_at < ").push_bind(until);
+    if let Some(service) = service {
+        query.push(" AND t.service_name = ").push_bind(service.to_string());
+    }
+}
+
+fn row_to_feedback(row: &sqlx::postgres::PgRow) -> Result<TraceFeedback> {
+    let rating: Option<String> = row.try_get("rating").map_err(|e| Error::Database(e.to_string()))?;
+    Ok(TraceFeedback {
+        id: row.try_get("id").map_err(|e| Error::Database(e.to_string()))?,
+        trace_id: row.try_get("trace_id").map_err(|e| Error::Database(e.to_string()))?,
+        rating: rating.as_deref().and_then(FeedbackRating::parse),
+        comment: row.try_get("comment").map_err(|e| Error::Database(e.to_string()))?,
+        user_id: row.try_get("user_id").map_err(|e| Error::Database(e.to_string()))?,
+        created_at: row.try_get("created_at").map_err(|e| Error::Database(e.to_string()))?,
+    })
+}
+
+fn row_to_payload(row: &sqlx::postgres::PgRow) -> Result<SpanPayload> {
+    Ok(SpanPayload {
+        trace_id: row.try_get("trace_id").map_err(|e| Error::Database(e.to_string()))?,
+        span_id: row.try_get("span_id").map_err(|e| Error::Database(e.to_string()))?,
+        prompt: row.try_get("prompt").map_err(|e| Error::Database(e.to_string()))?,
+        completion: row.try_get("completion").map_err(|e| Error::Database(e.to_string()))?,
+        truncated: row.try_get("truncated").map_err(|e| Error::Database(e.to_string()))?,
+        size_bytes: row.try_get("size_bytes").map_err(|e| Error::Database(e.to_string()))?,
+        created_at: row.try_get("created_at").map_err(|e| Error::Database(e.to_string()))?,
+    })
+}
+
+fn row_to_session_summary(row: &sqlx::postgres::PgRow) -> SessionSummary {
+    SessionSummary {
+        session_id: row.try_get("session_id").unwrap_or_default(),
+        user_id: row.try_get("user_id").ok().flatten(),
+        started_at: row.try_get("started_at").unwrap_or_else(|_| Utc::now()),
+        last_seen: row.try_get("last_seen").unwrap_or_else(|_| Utc::now()),
+        trace_count: row.try_get("trace_count").unwrap_or(0),
+        span_count: row.try_get("span_count").unwrap_or(0),
+        error_count: row.try_get("error_count").unwrap_or(0),
+        total_tokens: row.try_get("total_tokens").unwrap_or(0),
+        total_cost_usd: row.try_get("total_cost_usd").unwrap_or(0.0),
     }
 }
 
-fn span_status_to_str(status: &SpanStatus) -> &'static str {
-    match status {
-        SpanStatus::Ok => "ok",
-        SpanStatus::Error => "error",
-        SpanStatus::Unset => "unset",
+fn row_to_trace_summary(row: &sqlx::postgres::PgRow) -> TraceSummary {
+    let duration_ms: Option<f64> = row.try_get("duration_ms").ok().flatten();
+    let time_breakdown = duration_ms.map(|d| {
+        TimeBreakdown::new(
+            d,
+            row.try_get("llm_ms").unwrap_or(0.0),
+            row.try_get("tool_ms").unwrap_or(0.0),
+        )
+    });
+
+    TraceSummary {
+        trace_id: row.try_get("trace_id").unwrap_or_default(),
+        root_operation: row.try_get::<Option<String>, _>("root_operation").ok().flatten().unwrap_or_default(),
+        service_name: row.try_get::<Option<String>, _>("service_name").ok().flatten().unwrap_or_default(),
+        started_at: row
+            .try_get::<Option<DateTime<Utc>>, _>("started_at")
+            .ok()
+            .flatten()
+            .unwrap_or_else(Utc::now),
+        duration_ms,
+        span_count: row.try_get("span_count").unwrap_or(0),
+        error_count: row.try_get("error_count").unwrap_or(0),
+        total_tokens: row.try_get("total_tokens").unwrap_or(0),
+        total_cost_usd: row.try_get("total_cost_usd").unwrap_or(0.0),
+        time_breakdown,
     }
 }
 
+pub(super) fn span_status_to_str(status: &SpanStatus) -> &'static str {
+    status.as_str()
+}
+
 fn span_kind_to_str(kind: &SpanKind) -> &'static str {
     match kind {
         SpanKind::Internal => "internal",
@@ -1017,25 +4322,55 @@ fn span_kind_to_str(kind: &SpanKind) -> &'static str {
     }
 }
 
+fn span_status_from_str(status: &str) -> SpanStatus {
+    match status.to_ascii_lowercase().as_str() {
+        "ok" => SpanStatus::Ok,
+        "error" => SpanStatus::Error,
+        "in_progress" => SpanStatus::InProgress,
+        _ => SpanStatus::Unset,
+    }
+}
+
+fn span_kind_from_str(kind: &str) -> SpanKind {
+    match kind.to_ascii_lowercase().as_str() {
+        "client" => SpanKind::Client,
+        "server" => SpanKind::Server,
+        "producer" => SpanKind::Producer,
+        "consumer" => SpanKind::Consumer,
+        _ => SpanKind::Internal,
+    }
+}
+
 fn row_to_span(row: &sqlx::postgres::PgRow) -> Result<Span> {
     Ok(Span {
         id: row.try_get("id").map_err(|e| Error::Database(e.to_string()))?,
         span_id: row.try_get("span_id").map_err(|e| Error::Database(e.to_string()))?,
         trace_id: row.try_get("trace_id").map_err(|e| Error::Database(e.to_string()))?,
         parent_span_id: row.try_get("parent_span_id").ok(),
+        session_id: row.try_get("session_id").ok(),
+        user_id: row.try_get("user_id").ok(),
         operation_name: row.try_get("operation_name").map_err(|e| Error::Database(e.to_string()))?,
         service_name: row.try_get("service_name").unwrap_or_default(),
-        span_kind: SpanKind::Internal, // TODO: parse from DB
+        span_kind: row
+            .try_get::<Option<String>, _>("span_kind")
+            .ok()
+            .flatten()
+            .map_or(SpanKind::Internal, |k| span_kind_from_str(&k)),
         started_at: row.try_get("started_at").map_err(|e| Error::Database(e.to_string()))?,
         ended_at: row.try_get("ended_at").ok(),
         duration_ms: row.try_get("duration_ms").ok(),
-        status: SpanStatus::Ok, // TODO: parse from DB
+        status: row
+            .try_get::<Option<String>, _>("status")
+            .ok()
+            .flatten()
+            .map_or(SpanStatus::Unset, |s| span_status_from_str(&s)),
         status_message: row.try_get("status_message").ok(),
         model_name: row.try_get("model_name").ok(),
         model_provider: row.try_get("model_provider").ok(),
         tokens_in: row.try_get("tokens_in").ok(),
         tokens_out: row.try_get("tokens_out").ok(),
         tokens_reasoning: row.try_get("tokens_reasoning").ok(),
+        tokens_cached_in: row.try_get("tokens_cached_in").ok(),
         cost_usd: row.try_get("cost_usd").ok(),
         tool_name: row.try_get("tool_name").ok(),
         tool_input: row.try_get("tool_input").ok(),
@@ -1044,7 +4379,93 @@ fn row_to_span(row: &sqlx::postgres::PgRow) -> Result<Span> {
         prompt_preview: row.try_get("prompt_preview").ok(),
         completion_preview: row.try_get("completion_preview").ok(),
         attributes: row.try_get("attributes").unwrap_or_default(),
-        events: vec![],
-        links: vec![],
+        events: row
+            .try_get::<serde_json::Value, _>("events")
+            .ok()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default(),
+        links: row
+            .try_get::<serde_json::Value, _>("links")
+            .ok()
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default(),
+        priority: row
+            .try_get::<Option<String>, _>("priority")
+            .ok()
+            .flatten()
+            .and_then(|p| SpanPriority::parse(&p))
+            .unwrap_or_default(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_status_round_trip() {
+        for status in [SpanStatus::Ok, SpanStatus::Error, SpanStatus::Unset, SpanStatus::InProgress] {
+            assert_eq!(span_status_from_str(span_status_to_str(&status)), status);
+        }
+    }
+
+    #[test]
+    fn test_span_kind_round_trip() {
+        for kind in [
+            SpanKind::Internal,
+            SpanKind::Client,
+            SpanKind::Server,
+            SpanKind::Producer,
+            SpanKind::Consumer,
+        ] {
+            assert_eq!(span_kind_from_str(span_kind_to_str(&kind)), kind);
+        }
+    }
+
+    #[test]
+    fn test_fold_sessions() {
+        use crate::db::sqlite::tests::create_test_span;
+
+        let mut spans = [
+            create_test_span("t1", "a", None, 100.0),
+            create_test_span("t1", "b", Some("a"), 40.0),
+            create_test_span("t2", "c", None, 300.0),
+            create_test_span("t3", "d", None, 10.0),
+        ];
+        for span in &mut spans[..3] {
+            span.session_id = Some("s1".to_string());
+            span.tokens_in = Some(10);
+            span.tokens_out = Some(5);
+            span.cost_usd = Some(0.25);
+        }
+        spans[1].status = SpanStatus::Error;
+        spans[1].user_id = Some("u1".to_string());
+        spans[2].started_at -= Duration::minutes(1);
+
+        let batch = fold_sessions(&spans.iter().collect::<Vec<_>>());
+        // Spans outside a session are skipped
+        assert_eq!(batch.sessions.len(), 1);
+        let s1 = &batch.sessions["s1"];
+        assert_eq!((s1.span_count, s1.error_count, s1.total_tokens), (3, 1, 45));
+        assert!((s1.total_cost_usd - 0.75).abs() < 1e-9);
+        assert_eq!(s1.user_id, Some("u1"));
+        assert_eq!(s1.started_at, spans[2].started_at);
+        assert_eq!(s1.last_seen, spans[2].ended_at.unwrap());
+
+        let traces: Vec<_> = batch.traces.keys().copied().collect();
+        assert_eq!(traces, [("s1", "t1"), ("s1", "t2")]);
+        assert_eq!(batch.traces[&("s1", "t1")], spans[0].started_at);
+    }
+
+    #[test]
+    fn test_parse_is_case_insensitive() {
+        assert_eq!(span_status_from_str("ERROR"), SpanStatus::Error);
+        assert_eq!(span_kind_from_str("Client"), SpanKind::Client);
+    }
+
+    #[test]
+    fn test_unknown_values_fall_back() {
+        assert_eq!(span_status_from_str("bogus"), SpanStatus::Unset);
+        assert_eq!(span_kind_from_str("bogus"), SpanKind::Internal);
+    }
+}
@@ -0,0 +1,187 @@
+//! Pub/sub for real-time span streaming
+//!
+//! Redis carries span updates between collector instances in production. In
+//! local/dev mode [`LocalPubSub`] delivers them in-process instead, so the
+//! live stream works without any external services.
+
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use redis::AsyncCommands;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::error::{Error, Result};
+use crate::models::Span;
+
+use super::redis::RedisPool;
+
+/// Messages buffered per channel before slow subscribers start missing them
+const LOCAL_CHANNEL_CAPACITY: usize = 256;
+
+/// Publish/subscribe transport
+#[async_trait::async_trait]
+pub trait PubSub: Send + Sync {
+    /// Publish a message to a channel
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()>;
+
+    /// Subscribe to a channel and return a receiver for messages
+    async fn subscribe(&self, channel: &str) -> Result<mpsc::Receiver<String>>;
+}
+
+#[async_trait::async_trait]
+impl PubSub for RedisPool {
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()> {
+        let mut conn = self.pool().get().await.map_err(|e| Error::Redis(e.to_string()))?;
+        let _: () = conn
+            .publish(channel, payload)
+            .await
+            .map_err(|e| Error::Redis(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<mpsc::Receiver<String>> {
+        RedisPool::subscribe(self, channel).await
+    }
+}
+
+/// In-process pub/sub
+#[derive(Clone, Default)]
+pub struct LocalPubSub {
+    channels: Arc<DashMap<String, broadcast::Sender<String>>>,
+}
+
+impl LocalPubSub {
+    /// Create a pub/sub with no channels
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl PubSub for LocalPubSub {
+    async fn publish(&self, channel: &str, payload: &str) -> Result<()> {
+        let delivered = match self.channels.get(channel) {
+            Some(tx) => tx.send(payload.to_string()).is_ok(),
+            None => return Ok(()),
+        };
+
+        // Every subscriber has gone away
+        if !delivered {
+            self.channels.remove_if(channel, |_, tx| tx.receiver_count() == 0);
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe(&self, channel: &str) -> Result<mpsc::Receiver<String>> {
+        let mut messages = self
+            .channels
+            .entry(channel.to_string())
+            .or_insert_with(|| broadcast::channel(LOCAL_CHANNEL_CAPACITY).0)
+            .subscribe();
+
+        let (tx, rx) = mpsc::channel::<String>(100);
+        let channel = channel.to_string();
+
+        tokio::spawn(async move {
+            loop {
+                match messages.recv().await {
+                    Ok(payload) => {
+                        if tx.send(payload).await.is_err() {
+                            tracing::debug!("SSE client disconnected");
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Subscriber to {} skipped {} messages", channel, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(rx)
+    }
+}
+
+/// Publishes processed spans for real-time updates
+#[derive(Clone)]
+pub struct SpanPublisher {
+    pubsub: Arc<dyn PubSub>,
+}
+
+impl SpanPublisher {
+    /// Create a publisher on top of a pub/sub transport
+    pub fn new(pubsub: Arc<dyn PubSub>) -> Self {
+        Self { pubsub }
+    }
+
+    /// Publish a span to the real-time stream
+    pub async fn publish_span(&self, span: &Span) -> Result<()> {
+        let span_json = serde_json::to_string(span)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+
+        // Publish to the spans channel
+        self.pubsub.publish("agenttrace:spans", &span_json).await?;
+
+        // Also publish to trace-specific channel for filtered subscriptions
+        let trace_channel = format!("agenttrace:trace:{}", span.trace_id);
+        self.pubsub.publish(&trace_channel, &span_json).await?;
+
+        // If it's an LLM call, publish to the llm channel
+        if span.is_llm_call() {
+            self.pubsub.publish("agenttrace:llm", &span_json).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Publish a full span payload to debug session channels
+    pub async fn publish_debug(&self, span: &Span, channels: &[String]) -> Result<()> {
+        let span_json = serde_json::to_string(span)
+            .map_err(|e| Error::Serialization(e.to_string()))?;
+
+        for channel in channels {
+            self.pubsub.publish(channel, &span_json).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Publish multiple spans
+    pub async fn publish_batch(&self, spans: &[Span]) -> Result<usize> {
+        let mut count = 0;
+        for span in spans {
+            if self.publish_span(span).await.is_ok() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_pubsub_delivers_to_subscribers() {
+        let pubsub = LocalPubSub::new();
+        let mut a = pubsub.subscribe("agenttrace:spans").await.unwrap();
+        let mut b = pubsub.subscribe("agenttrace:spans").await.unwrap();
+        let mut other = pubsub.subscribe("agenttrace:llm").await.unwrap();
+
+        pubsub.publish("agenttrace:spans", "hello").await.unwrap();
+
+        assert_eq!(a.recv().await.as_deref(), Some("hello"));
+        assert_eq!(b.recv().await.as_deref(), Some("hello"));
+        assert!(other.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_pubsub_without_subscribers() {
+        let pubsub = LocalPubSub::new();
+        pubsub.publish("agenttrace:spans", "dropped").await.unwrap();
+        assert!(pubsub.channels.is_empty());
+    }
+}
@@ -8,7 +8,6 @@ use tokio::sync::mpsc;
 
 use crate::config::RedisConfig as AppRedisConfig;
 use crate::error::{Error, Result};
-use crate::models::Span;
 
 /// Redis connection pool
 #[derive(Clone)]
@@ -100,7 +99,7 @@ impl RedisPool {
     }
 }
 
-/// Redis streamer for real-time span updates
+/// Redis key/value helpers for metrics snapshots and counters
 #[derive(Clone)]
 pub struct RedisStreamer {
     pool: Pool,
@@ -114,48 +113,6 @@ impl RedisStreamer {
         }
     }
 
-    /// Publish a span to the real-time stream
-    pub async fn publish_span(&self, span: &Span) -> Result<()> {
-        let mut conn = self.pool.get().await.map_err(|e| Error::Redis(e.to_string()))?;
-
-        let span_json = serde_json::to_string(span)
-            .map_err(|e| Error::Serialization(e.to_string()))?;
-
-        // Publish to the spans channel
-        let _: () = conn
-            .publish("agenttrace:spans", &span_json)
-            .await
-            .map_err(|e| Error::Redis(e.to_string()))?;
-
-        // Also publish to trace-specific channel for filtered subscriptions
-        let trace_channel = format!("agenttrace:trace:{}", span.trace_id);
-        let _: () = conn
-            .publish(&trace_channel, &span_json)
-            .await
-            .map_err(|e| Error::Redis(e.to_string()))?;
-
-        // If it's an LLM call, publish to the llm channel
-        if span.is_llm_call() {
-            let _: () = conn
-                .publish("agenttrace:llm", &span_json)
-                .await
-                .map_err(|e| Error::Redis(e.to_string()))?;
-        }
-
-        Ok(())
-    }
-
-    /// Publish multiple spans
-    pub async fn publish_batch(&self, spans: &[Span]) -> Result<usize> {
-        let mut count = 0;
-        for span in spans {
-            if self.publish_span(span).await.is_ok() {
-                count += 1;
-            }
-        }
-        Ok(count)
-    }
-
     /// Store the latest metrics snapshot
     pub async fn set_metrics_snapshot(&self, key: &str, data: &str, ttl_seconds: u64) -> Result<()> {
         let mut conn = self.pool.get().await.map_err(|e| Error::Redis(e.to_string()))?;
@@ -0,0 +1,391 @@
+//! Sample data for `agenttrace db seed` and `agenttrace demo`
+//!
+//! Traces are shaped like real agent runs: a root span for the task with a
+//! loop of LLM calls and tool calls beneath it. Costs and durations are left
+//! for the pipeline to fill in, as they would be for SDK spans.
+
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use uuid::Uuid;
+
+use crate::models::{Span, SpanKind, SpanPriority, SpanStatus};
+
+/// An agent and the kind of task it runs
+struct Scenario {
+    service: &'static str,
+    operation: &'static str,
+    tasks: &'static [&'static str],
+    tools: &'static [&'static str],
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        service: "support-agent",
+        operation: "handle_ticket",
+        tasks: &[
+            "Customer was charged twice for order #{n}",
+            "Customer cannot reset their password",
+            "Customer asks for a refund on a late delivery",
+        ],
+        tools: &["lookup_order", "search_kb", "issue_refund", "send_email"],
+    },
+    Scenario {
+        service: "research-agent",
+        operation: "answer_question",
+        tasks: &[
+            "Summarize recent changes to data retention regulations",
+            "Compare vector databases for a {n}M document corpus",
+            "Find benchmarks for small open-weight models",
+        ],
+        tools: &["web_search", "fetch_page", "extract_citations"],
+    },
+    Scenario {
+        service: "code-agent",
+        operation: "fix_issue",
+        tasks: &[
+            "Fix failing test in the payments module (issue #{n})",
+            "Add pagination to the orders endpoint",
+            "Upgrade the HTTP client and fix breaking changes",
+        ],
+        tools: &["read_file", "search_code", "apply_patch", "run_tests"],
+    },
+];
+
+/// `(provider, model)` pairs with known pricing
+const MODELS: &[(&str, &str)] = &[
+    ("anthropic", "claude-sonnet-4"),
+    ("anthropic", "claude-3-5-haiku"),
+    ("openai", "gpt-4o"),
+    ("openai", "gpt-4o-mini"),
+];
+
+/// Chance that a tool call fails and ends the run
+const TOOL_ERROR_RATE: f64 = 0.06;
+
+/// Shape of the traces a [`SpanGenerator`] produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpanProfile {
+    /// Agent runs: a loop of LLM and tool calls under a task span
+    #[default]
+    Agentic,
+    /// Single LLM calls under a request span, like a chat backend
+    Chat,
+}
+
+impl SpanProfile {
+    /// Parse a profile name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "agentic" => Some(Self::Agentic),
+            "chat" => Some(Self::Chat),
+            _ => None,
+        }
+    }
+}
+
+/// Generate `count` traces started at random times within the last `hours`
+pub fn sample_traces(count: usize, hours: i64) -> Vec<Vec<Span>> {
+    traces_within(&mut rand::thread_rng(), count, hours)
+}
+
+/// Generates a stream of traces that have just finished, for demo mode
+///
+/// With a seed, the same traces (IDs included) are generated on every run,
+/// so a demo session can be replayed when reporting a bug.
+pub struct SpanGenerator {
+    rng: StdRng,
+    profile: SpanProfile,
+}
+
+impl SpanGenerator {
+    /// Create a generator, seeded for reproducible output
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            rng,
+            profile: SpanProfile::default(),
+        }
+    }
+
+    /// Generate traces of another shape
+    pub fn with_profile(mut self, profile: SpanProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// A trace that ends at the current time
+    pub fn next_trace(&mut self) -> Vec<Span> {
+        let now = Utc::now();
+        let mut trace = match self.profile {
+            SpanProfile::Agentic => sample_trace(&mut self.rng, now),
+            SpanProfile::Chat => chat_trace(&mut self.rng, now),
+        };
+
+        let ended_at = trace[0].ended_at.unwrap_or(now);
+        let shift = ended_at - now;
+        for span in &mut trace {
+            span.started_at -= shift;
+            span.ended_at = span.ended_at.map(|t| t - shift);
+        }
+        trace
+    }
+
+    /// `count` traces started at random times within the last `hours`, to
+    /// give the demo some history
+    pub fn history(&mut self, count: usize, hours: i64) -> Vec<Vec<Span>> {
+        traces_within(&mut self.rng, count, hours)
+    }
+}
+
+fn traces_within(rng: &mut impl Rng, count: usize, hours: i64) -> Vec<Vec<Span>> {
+    let now = Utc::now();
+
+    (0..count)
+        .map(|_| {
+            let started_at = now - Duration::seconds(rng.gen_range(60..hours.max(1) * 3600));
+            sample_trace(rng, started_at)
+        })
+        .collect()
+}
+
+fn sample_trace(rng: &mut impl Rng, started_at: DateTime<Utc>) -> Vec<Span> {
+    let scenario = SCENARIOS.choose(rng).expect("scenarios are not empty");
+    let (provider, model) = *MODELS.choose(rng).expect("models are not empty");
+    let task = scenario
+        .tasks
+        .choose(rng)
+        .expect("tasks are not empty")
+        .replace("{n}", &rng.gen_range(100..10_000).to_string());
+    let trace_id = Uuid::from_u128(rng.gen()).simple().to_string();
+
+    let mut root = new_span(
+        rng,
+        &trace_id,
+        None,
+        scenario.operation,
+        scenario.service,
+        started_at,
+    );
+    root.span_kind = SpanKind::Server;
+    root.prompt_preview = Some(task.clone());
+    root.attributes = serde_json::json!({
+        "customer_id": format!("cust-{:04}", rng.gen_range(1..250)),
+        "agent_version": format!("1.{}", rng.gen_range(2..5)),
+    });
+
+    let mut spans = Vec::new();
+    let mut cursor = started_at + Duration::milliseconds(rng.gen_range(5..40));
+    let mut failure = None;
+
+    for step in 1..=rng.gen_range(1..=4) {
+        let tool_name = *scenario.tools.choose(rng).expect("tools are not empty");
+
+        let mut llm = llm_span(rng, &root, provider, model, cursor);
+        llm.prompt_preview = Some(format!("Step {}: {}", step, task));
+        llm.completion_preview = Some(format!("I'll call {} to gather what I need.", tool_name));
+        cursor = llm.ended_at.unwrap_or(cursor);
+        spans.push(llm);
+
+        let mut tool = new_span(
+            rng,
+            &trace_id,
+            Some(&root.span_id),
+            &format!("tool.{}", tool_name),
+            scenario.service,
+            cursor,
+        );
+        tool.tool_name = Some(tool_name.to_string());
+        tool.tool_input = Some(serde_json::json!({ "query": task }));
+        tool.ended_at = Some(cursor + Duration::milliseconds(rng.gen_range(30..2500)));
+
+        if rng.gen_bool(TOOL_ERROR_RATE) {
+            let message = format!("{} timed out", tool_name);
+            tool.status = SpanStatus::Error;
+            tool.status_message = Some(message.clone());
+            failure = Some(message);
+        } else {
+            tool.status = SpanStatus::Ok;
+            tool.tool_output =
+                Some(serde_json::json!({ "ok": true, "results": rng.gen_range(1..8) }));
+        }
+
+        cursor = tool.ended_at.unwrap_or(cursor);
+        spans.push(tool);
+
+        if failure.is_some() {
+            break;
+        }
+    }
+
+    if failure.is_none() {
+        let mut answer = llm_span(rng, &root, provider, model, cursor);
+        answer.prompt_preview = Some(format!("Write the final answer for: {}", task));
+        answer.completion_preview =
+            Some("Here is what I found and the actions I took.".to_string());
+        cursor = answer.ended_at.unwrap_or(cursor);
+        spans.push(answer);
+    }
+
+    root.ended_at = Some(cursor + Duration::milliseconds(rng.gen_range(2..20)));
+    match failure {
+        Some(message) => {
+            root.status = SpanStatus::Error;
+            root.status_message = Some(message);
+        }
+        None => root.status = SpanStatus::Ok,
+    }
+
+    spans.insert(0, root);
+    spans
+}
+
+/// A request span with a single LLM call beneath it
+fn chat_trace(rng: &mut impl Rng, started_at: DateTime<Utc>) -> Vec<Span> {
+    let scenario = SCENARIOS.choose(rng).expect("scenarios are not empty");
+    let (provider, model) = *MODELS.choose(rng).expect("models are not empty");
+    let trace_id = Uuid::from_u128(rng.gen()).simple().to_string();
+
+    let mut root = new_span(rng, &trace_id, None, "chat.request", scenario.service, started_at);
+    root.span_kind = SpanKind::Server;
+
+    let task = scenario
+        .tasks
+        .choose(rng)
+        .expect("tasks are not empty")
+        .replace("{n}", &rng.gen_range(100..10_000).to_string());
+    let called_at = started_at + Duration::milliseconds(rng.gen_range(2..15));
+    let mut llm = llm_span(rng, &root, provider, model, called_at);
+    llm.prompt_preview = Some(task);
+    let ended_at = llm.ended_at.unwrap_or(started_at);
+
+    root.ended_at = Some(ended_at + Duration::milliseconds(rng.gen_range(2..20)));
+    root.status = SpanStatus::Ok;
+    vec![root, llm]
+}
+
+/// An LLM call under `root`, timed roughly by the tokens it generates
+fn llm_span(
+    rng: &mut impl Rng,
+    root: &Span,
+    provider: &str,
+    model: &str,
+    started_at: DateTime<Utc>,
+) -> Span {
+    let tokens_in = rng.gen_range(400..6000);
+    let tokens_out = rng.gen_range(40..900);
+
+    let mut span = new_span(
+        rng,
+        &root.trace_id,
+        Some(&root.span_id),
+        "llm.chat",
+        &root.service_name,
+        started_at,
+    );
+    span.span_kind = SpanKind::Client;
+    span.model_provider = Some(provider.to_string());
+    span.model_name = Some(model.to_string());
+    span.tokens_in = Some(tokens_in);
+    span.tokens_out = Some(tokens_out);
+    span.status = SpanStatus::Ok;
+    span.ended_at = Some(
+        started_at + Duration::milliseconds(rng.gen_range(250..600) + i64::from(tokens_out) * 15),
+    );
+    span
+}
+
+fn new_span(
+    rng: &mut impl Rng,
+    trace_id: &str,
+    parent_span_id: Option<&str>,
+    operation: &str,
+    service: &str,
+    started_at: DateTime<Utc>,
+) -> Span {
+    Span {
+        id: Uuid::from_u128(rng.gen()),
+        span_id: format!("{:016x}", rng.gen::<u64>()),
+        trace_id: trace_id.to_string(),
+        parent_span_id: parent_span_id.map(str::to_string),
+        session_id: None,
+        user_id: None,
+        operation_name: operation.to_string(),
+        service_name: service.to_string(),
+        span_kind: SpanKind::Internal,
+        started_at,
+        ended_at: None,
+        duration_ms: None,
+        status: SpanStatus::Unset,
+        status_message: None,
+        model_name: None,
+        model_provider: None,
+        tokens_in: None,
+        tokens_cached_in: None,
+        tokens_out: None,
+        tokens_reasoning: None,
+        cost_usd: None,
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        tool_duration_ms: None,
+        prompt_preview: None,
+        completion_preview: None,
+        attributes: serde_json::json!({}),
+        events: vec![],
+        links: vec![],
+        priority: SpanPriority::Normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_traces_are_well_formed() {
+        for trace in sample_traces(20, 24) {
+            let root = &trace[0];
+            assert!(root.parent_span_id.is_none());
+            assert!(trace.len() >= 3);
+
+            for span in &trace[1..] {
+                assert_eq!(span.trace_id, root.trace_id);
+                assert_eq!(span.parent_span_id.as_deref(), Some(root.span_id.as_str()));
+                assert!(span.started_at >= root.started_at);
+                assert!(span.ended_at <= root.ended_at);
+            }
+
+            let failed = trace
+                .iter()
+                .any(|s| s.tool_name.is_some() && s.status == SpanStatus::Error);
+            assert_eq!(root.status == SpanStatus::Error, failed);
+        }
+    }
+
+    #[test]
+    fn test_chat_profile() {
+        let mut generator = SpanGenerator::new(Some(3)).with_profile(SpanProfile::Chat);
+        let trace = generator.next_trace();
+        assert_eq!(trace.len(), 2);
+        assert!(trace[1].model_name.is_some());
+        assert_eq!(trace[1].parent_span_id.as_deref(), Some(trace[0].span_id.as_str()));
+        assert_eq!(SpanProfile::parse("chat"), Some(SpanProfile::Chat));
+    }
+
+    #[test]
+    fn test_seeded_generator_is_reproducible() {
+        let a = SpanGenerator::new(Some(7)).next_trace();
+        let b = SpanGenerator::new(Some(7)).next_trace();
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a[0].trace_id, b[0].trace_id);
+        assert_eq!(a[1].tokens_out, b[1].tokens_out);
+
+        let root = &a[0];
+        assert!(root.ended_at.unwrap() <= Utc::now());
+    }
+}
@@ -0,0 +1,2684 @@
+//! SQLite span storage for local/dev mode
+//!
+//! Spans are stored as JSON documents alongside the handful of columns used
+//! to narrow queries (trace, service, model, start time). Filtering beyond
+//! that, sorting and metric aggregation happen in Rust, which is plenty for
+//! the data volumes of a single developer machine.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, DurationRound, SecondsFormat, Utc};
+use serde_json::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{
+    attribute_match_values, AggregateGroup, AggregateMetric, AggregateQuery, AggregateRow, CostMetric, ErrorMetric, ErrorStats, FacetCount, FeedbackGroup, FeedbackMetrics, FeedbackRating, LatencyMetric,
+    MetricsSummaryResponse, OperationEfficiency, TokenEfficiency, PageCursor, SearchFacets, SearchFilter, ServiceSummary, SessionDetail, SessionSummary, SortConfig, Span,
+    SpanPayload, SpanPriority, SpanQuery, SpanStatus, StorageReport, TextHighlight, TextSearchHit, TimeBreakdown,
+    TimeBreakdownMetric, TraceDeletion, TraceDeletionResult, TraceFeedback, TraceSearch, TraceSummary, WindowStats,
+};
+
+use super::postgres::{filterable_column, sortable_column, span_status_to_str};
+use super::store::SpanStore;
+
+/// Schema created when the database is opened
+const SCHEMA: &[&str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS spans (
+        span_id TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        id TEXT NOT NULL,
+        trace_id TEXT NOT NULL,
+        parent_span_id TEXT,
+        service_name TEXT NOT NULL,
+        model_name TEXT,
+        data TEXT NOT NULL,
+        PRIMARY KEY (span_id, started_at)
+    )
+    "#,
+    "CREATE INDEX IF NOT EXISTS idx_spans_id ON spans (id)",
+    "CREATE INDEX IF NOT EXISTS idx_spans_trace ON spans (trace_id, started_at)",
+    "CREATE INDEX IF NOT EXISTS idx_spans_started ON spans (started_at)",
+    "CREATE INDEX IF NOT EXISTS idx_spans_service ON spans (service_name, started_at)",
+    r#"
+    CREATE TABLE IF NOT EXISTS trace_feedback (
+        id TEXT PRIMARY KEY,
+        trace_id TEXT NOT NULL,
+        rating TEXT,
+        comment TEXT,
+        user_id TEXT,
+        created_at TEXT NOT NULL
+    )
+    "#,
+    "CREATE INDEX IF NOT EXISTS idx_trace_feedback_trace ON trace_feedback (trace_id, created_at)",
+    r#"
+    CREATE TABLE IF NOT EXISTS span_payloads (
+        trace_id TEXT NOT NULL,
+        span_id TEXT NOT NULL,
+        prompt TEXT,
+        completion TEXT,
+        truncated INTEGER NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        PRIMARY KEY (trace_id, span_id)
+    )
+    "#,
+    "CREATE INDEX IF NOT EXISTS idx_span_payloads_created ON span_payloads (created_at)",
+];
+
+/// SQLite-backed span store
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) a database file, or `:memory:`
+    pub async fn open(path: &str) -> Result<Self> {
+        let connected = if path == ":memory:" {
+            let options = SqliteConnectOptions::from_str("sqlite::memory:")
+                .map_err(|e| Error::Database(e.to_string()))?;
+
+            // Each connection to :memory: gets its own database, so keep
+            // exactly one open for the life of the pool
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await
+        } else {
+            let options = SqliteConnectOptions::new()
+                .filename(path)
+                .create_if_missing(true)
+                .journal_mode(SqliteJournalMode::Wal);
+
+            SqlitePoolOptions::new()
+                .max_connections(5)
+                .connect_with(options)
+                .await
+        };
+        let pool = connected.map_err(|e| Error::Database(e.to_string()))?;
+
+        for statement in SCHEMA {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    /// Load the spans in scope, in start order
+    async fn load(&self, scope: &Scope<'_>) -> Result<Vec<Span>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT data FROM spans");
+        scope.push_where(&mut query);
+        query.push(" ORDER BY started_at ASC");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+}
+
+/// Column filters applied in SQL before any in-memory filtering
+#[derive(Debug, Default)]
+struct Scope<'a> {
+    trace_id: Option<&'a str>,
+    service: Option<&'a str>,
+    model: Option<&'a str>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    roots_only: bool,
+    /// Only spans of this session
+    session_id: Option<&'a str>,
+    /// Only spans that belong to some session
+    in_session: bool,
+    /// Only spans of traces that did, or did not, get a negative rating
+    negative_feedback: Option<bool>,
+    /// Leave out streaming spans still in progress
+    finished_only: bool,
+}
+
+impl<'a> Scope<'a> {
+    /// Time range and service/model scope shared by the metrics queries,
+    /// which count each streaming span once it has finished
+    fn window(
+        service: Option<&'a str>,
+        model: Option<&'a str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            service,
+            model,
+            since: Some(since),
+            until: Some(until),
+            finished_only: true,
+            ..Self::default()
+        }
+    }
+
+    /// All spans of one trace
+    fn trace(trace_id: &'a str) -> Self {
+        Self {
+            trace_id: Some(trace_id),
+            ..Self::default()
+        }
+    }
+
+    fn push_where(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        query.push(" WHERE 1 = 1");
+
+        if let Some(trace_id) = self.trace_id {
+            query.push(" AND trace_id = ").push_bind(trace_id.to_string());
+        }
+        if let Some(service) = self.service {
+            query.push(" AND service_name = ").push_bind(service.to_string());
+        }
+        if let Some(model) = self.model {
+            query.push(" AND model_name = ").push_bind(model.to_string());
+        }
+        if let Some(since) = self.since {
+            query.push(" AND started_at >= ").push_bind(timestamp(since));
+        }
+        if let Some(until) = self.until {
+            query.push(" AND started_at <= ").push_bind(timestamp(until));
+        }
+        if self.roots_only {
+            query.push(" AND parent_span_id IS NULL");
+        }
+        if let Some(session_id) = self.session_id {
+            query
+                .push(" AND json_extract(data, '$.session_id') = ")
+                .push_bind(session_id.to_string());
+        }
+        if self.in_session {
+            query.push(" AND json_extract(data, '$.session_id') IS NOT NULL");
+        }
+        if let Some(has) = self.negative_feedback {
+            query.push(if has { " AND trace_id IN" } else { " AND trace_id NOT IN" });
+            query.push(" (SELECT trace_id FROM trace_feedback WHERE rating = 'negative')");
+        }
+        if self.finished_only {
+            query.push(" AND json_extract(data, '$.status') <> 'in_progress'");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SpanStore for SqliteStore {
+    async fn insert_batch(&self, spans: &[Span]) -> Result<usize> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+        let mut inserted = 0;
+
+        for span in spans {
+            let stored = sqlx::query("SELECT data FROM spans WHERE span_id = ? AND started_at = ?")
+                .bind(&span.span_id)
+                .bind(timestamp(span.started_at))
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+
+            // Redelivered spans are skipped; an in-progress span takes its update
+            if let Some(row) = stored {
+                let mut stored = row_to_span(&row)?;
+                if !stored.is_in_progress() {
+                    continue;
+                }
+                // The pipeline recalculates cost on the merged span
+                stored.apply_update(span.clone());
+                stored.cost_usd = span.cost_usd;
+                sqlx::query(
+                    r#"
+                    UPDATE spans SET parent_span_id = ?, service_name = ?, model_name = ?, data = ?
+                    WHERE span_id = ? AND started_at = ?
+                    "#,
+                )
+                .bind(&stored.parent_span_id)
+                .bind(&stored.service_name)
+                .bind(&stored.model_name)
+                .bind(serde_json::to_string(&stored)?)
+                .bind(&span.span_id)
+                .bind(timestamp(span.started_at))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+
+                inserted += 1;
+                continue;
+            }
+
+            let data = serde_json::to_string(span)?;
+            let result = sqlx::query(
+                r#"
+                INSERT INTO spans (
+                    span_id, started_at, id, trace_id, parent_span_id,
+                    service_name, model_name, data
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (span_id, started_at) DO NOTHING
+                "#,
+            )
+            .bind(&span.span_id)
+            .bind(timestamp(span.started_at))
+            .bind(span.id.to_string())
+            .bind(&span.trace_id)
+            .bind(&span.parent_span_id)
+            .bind(&span.service_name)
+            .bind(&span.model_name)
+            .bind(data)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+            inserted += result.rows_affected() as usize;
+        }
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(inserted)
+    }
+
+    async fn get_in_progress(&self, span_ids: &[String]) -> Result<Vec<Span>> {
+        if span_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT data FROM spans WHERE span_id IN (");
+        let mut ids = query.separated(", ");
+        for span_id in span_ids {
+            ids.push_bind(span_id);
+        }
+        query.push(") AND json_extract(data, '$.status') = 'in_progress'");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    async fn close_stale_in_progress(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        let deleted = sqlx::query(
+            r#"
+            DELETE FROM spans
+            WHERE json_extract(data, '$.status') = 'in_progress' AND started_at < ?
+              AND EXISTS (
+                  SELECT 1 FROM spans f
+                  WHERE f.span_id = spans.span_id AND f.trace_id = spans.trace_id
+                    AND json_extract(f.data, '$.status') <> 'in_progress'
+              )
+            "#,
+        )
+        .bind(timestamp(before))
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?
+        .rows_affected();
+
+        let rows = sqlx::query(
+            "SELECT data FROM spans WHERE json_extract(data, '$.status') = 'in_progress' AND started_at < ?",
+        )
+        .bind(timestamp(before))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        for row in &rows {
+            let mut span = row_to_span(row)?;
+            span.close_stale();
+            sqlx::query("UPDATE spans SET data = ? WHERE span_id = ? AND started_at = ?")
+                .bind(serde_json::to_string(&span)?)
+                .bind(&span.span_id)
+                .bind(timestamp(span.started_at))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::Database(e.to_string()))?;
+        }
+
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+        Ok(deleted + rows.len() as u64)
+    }
+
+    async fn get_by_id(&self, id: &Uuid) -> Result<Option<Span>> {
+        let row = sqlx::query("SELECT data FROM spans WHERE id = ?")
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        row.as_ref().map(row_to_span).transpose()
+    }
+
+    async fn get_by_trace_id(&self, trace_id: &str) -> Result<Vec<Span>> {
+        self.load(&Scope::trace(trace_id)).await
+    }
+
+    async fn get_by_trace_ids(&self, trace_ids: &[String]) -> Result<Vec<Span>> {
+        if trace_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT data FROM spans WHERE trace_id IN (");
+        let mut ids = query.separated(", ");
+        for trace_id in trace_ids {
+            ids.push_bind(trace_id);
+        }
+        query.push(") ORDER BY started_at ASC");
+
+        let rows = query
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    async fn search_in_trace(&self, trace_id: &str, query: &SpanQuery) -> Result<Vec<Span>> {
+        let scope = Scope {
+            negative_feedback: query.has_negative_feedback,
+            ..Scope::trace(trace_id)
+        };
+        let spans = self.load(&scope).await?;
+        Ok(spans.into_iter().filter(|s| query.matches(s)).collect())
+    }
+
+    async fn search_facets(
+        &self,
+        query: &SpanQuery,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SearchFacets> {
+        let scope = Scope {
+            negative_feedback: query.has_negative_feedback,
+            ..Scope::window(None, None, since, until)
+        };
+        let spans = self.load(&scope).await?;
+
+        let mut counts: [BTreeMap<&str, i64>; 4] = Default::default();
+        let mut total = 0;
+        for span in spans.iter().filter(|s| query.matches(s)) {
+            total += 1;
+            *counts[0].entry(span.service_name.as_str()).or_default() += 1;
+            if let Some(model) = &span.model_name {
+                *counts[1].entry(model.as_str()).or_default() += 1;
+            }
+            *counts[2].entry(span_status_to_str(&span.status)).or_default() += 1;
+            *counts[3].entry(span.operation_name.as_str()).or_default() += 1;
+        }
+
+        let [services, models, statuses, operations] = counts.map(|facet| {
+            facet
+                .into_iter()
+                .map(|(value, count)| FacetCount {
+                    value: value.to_string(),
+                    count,
+                })
+                .collect()
+        });
+
+        let mut facets = SearchFacets {
+            total,
+            services,
+            models,
+            statuses,
+            operations,
+        };
+        facets.truncate();
+        Ok(facets)
+    }
+
+    async fn get_linking_trace_ids(&self, trace_id: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT DISTINCT s.trace_id FROM spans s, json_each(s.data, '$.links') l
+            WHERE json_extract(l.value, '$.trace_id') = ?1
+              AND s.trace_id <> ?1
+            ORDER BY s.trace_id
+            LIMIT 100
+            "#,
+        )
+        .bind(trace_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(rows.iter().filter_map(|r| r.try_get("trace_id").ok()).collect())
+    }
+
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Span>> {
+        let rows = sqlx::query("SELECT data FROM spans ORDER BY started_at DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_span).collect()
+    }
+
+    async fn search(
+        &self,
+        query: Option<&str>,
+        service: Option<&str>,
+        model: Option<&str>,
+        status: Option<&str>,
+        priority: Option<SpanPriority>,
+        attributes: &[(String, String)],
+        has_negative_feedback: Option<bool>,
+        min_duration: Option<f64>,
+        max_duration: Option<f64>,
+        min_cost: Option<f64>,
+        max_cost: Option<f64>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        sort_by: &str,
+        sort_desc: bool,
+        limit: i64,
+        offset: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<Span>, i64)> {
+        let sort_column = sortable_column(sort_by)?;
+        if cursor.is_some() && sort_column != "started_at" {
+            return Err(Error::validation("Cursors can only be used when sorting by started_at"));
+        }
+
+        let span_query = SpanQuery {
+            text: query.map(str::to_string),
+            status: status.map(str::to_string),
+            priority,
+            attributes: attributes.to_vec(),
+            min_duration,
+            max_duration,
+            min_cost,
+            max_cost,
+            ..SpanQuery::default()
+        };
+
+        let scope = Scope {
+            service,
+            model,
+            since,
+            until,
+            negative_feedback: has_negative_feedback,
+            ..Scope::default()
+        };
+        let spans = self
+            .load(&scope)
+            .await?
+            .into_iter()
+            .filter(|s| span_query.matches(s))
+            .collect();
+
+        Ok(page(spans, sort_column, sort_desc, limit, offset, cursor))
+    }
+
+    async fn advanced_search(
+        &self,
+        filters: &[SearchFilter],
+        sort: Option<&SortConfig>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Span>, i64)> {
+        let conditions = FieldCondition::from_search_filters(filters)?;
+
+        let (sort_field, sort_desc) = sort
+            .map(|s| (s.field.as_str(), s.descending))
+            .unwrap_or(("started_at", true));
+        let sort_column = sortable_column(sort_field)?;
+
+        let spans = self
+            .load(&Scope::default())
+            .await?
+            .into_iter()
+            .filter(|span| {
+                let doc = serde_json::to_value(span).unwrap_or_default();
+                conditions.iter().all(|c| c.matches(&doc))
+            })
+            .collect();
+
+        Ok(page(spans, sort_column, sort_desc, limit, offset, None))
+    }
+
+    async fn text_search(
+        &self,
+        query: &str,
+        service: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<TextSearchHit>, i64)> {
+        let terms = TextTerms::parse(query);
+        let scope = Scope {
+            service,
+            since,
+            until,
+            ..Scope::default()
+        };
+
+        let mut hits: Vec<TextSearchHit> = self
+            .load(&scope)
+            .await?
+            .into_iter()
+            .filter_map(|span| terms.hit(span))
+            .collect();
+        let total = hits.len() as i64;
+
+        hits.sort_by(|a, b| {
+            b.rank
+                .total_cmp(&a.rank)
+                .then_with(|| b.span.started_at.cmp(&a.span.started_at))
+        });
+
+        let hits = hits
+            .into_iter()
+            .skip(usize::try_from(offset).unwrap_or(0))
+            .take(usize::try_from(limit).unwrap_or(0))
+            .collect();
+
+        Ok((hits, total))
+    }
+
+    async fn list_traces(
+        &self,
+        service: Option<&str>,
+        status: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        sort: Option<&SortConfig>,
+        limit: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<TraceSummary>, Option<PageCursor>)> {
+        let order = SortConfig::trace_order(sort, cursor)?;
+        let scope = Scope {
+            service,
+            since,
+            roots_only: true,
+            ..Scope::default()
+        };
+        let mut roots = self.load(&scope).await?;
+        roots.sort_by(|a, b| (b.started_at, b.id).cmp(&(a.started_at, a.id)));
+
+        // Other orders need every summary before the page can be picked
+        if let Some((field, descending)) = order {
+            let mut traces = Vec::new();
+            for root in roots.iter().filter(|r| status.map_or(true, |s| span_status_to_str(&r.status) == s)) {
+                let spans = self.load(&Scope::trace(&root.trace_id)).await?;
+                traces.push(TraceSummary::from_spans(&root.trace_id, &spans));
+            }
+            // Stable, so ties stay newest first; missing values go last
+            traces.sort_by(|a, b| match (a.sort_value(field), b.sort_value(field)) {
+                (Some(x), Some(y)) if descending => y.total_cmp(&x),
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (a, b) => b.is_some().cmp(&a.is_some()),
+            });
+            traces.truncate(usize::try_from(limit).unwrap_or(0));
+            return Ok((traces, None));
+        }
+
+        let mut traces = Vec::new();
+        let mut last = None;
+        for root in roots
+            .iter()
+            .filter(|r| cursor.map_or(true, |c| (r.started_at, r.id) < (c.started_at, c.id)))
+            .filter(|r| status.map_or(true, |s| span_status_to_str(&r.status) == s))
+            .take(usize::try_from(limit).unwrap_or(0))
+        {
+            let spans = self.load(&Scope::trace(&root.trace_id)).await?;
+            traces.push(TraceSummary::from_spans(&root.trace_id, &spans));
+            last = Some(PageCursor::after(root));
+        }
+
+        let next = last.filter(|_| traces.len() as i64 == limit);
+        Ok((traces, next))
+    }
+
+    async fn find_traces(&self, search: &TraceSearch) -> Result<Vec<TraceSummary>> {
+        let scope = Scope {
+            since: search.since,
+            until: search.until,
+            roots_only: true,
+            ..Scope::default()
+        };
+        let mut roots = self.load(&scope).await?;
+        roots.sort_by(|a, b| (b.started_at, b.id).cmp(&(a.started_at, a.id)));
+
+        let mut traces = Vec::new();
+        for root in &roots {
+            if traces.len() as i64 >= search.limit {
+                break;
+            }
+            let spans = self.load(&Scope::trace(&root.trace_id)).await?;
+            let summary = TraceSummary::from_spans(&root.trace_id, &spans);
+            if search.matches(&summary, &spans) {
+                traces.push(summary);
+            }
+        }
+
+        Ok(traces)
+    }
+
+    async fn get_trace_summary(&self, trace_id: &str) -> Result<Option<TraceSummary>> {
+        let spans = self.load(&Scope::trace(trace_id)).await?;
+        Ok((!spans.is_empty()).then(|| TraceSummary::from_spans(trace_id, &spans)))
+    }
+
+    async fn get_trace_summaries(&self, trace_ids: &[String]) -> Result<Vec<TraceSummary>> {
+        let mut by_trace: HashMap<String, Vec<Span>> = HashMap::new();
+        for span in self.get_by_trace_ids(trace_ids).await? {
+            by_trace.entry(span.trace_id.clone()).or_default().push(span);
+        }
+
+        Ok(by_trace
+            .iter()
+            .map(|(trace_id, spans)| TraceSummary::from_spans(trace_id, spans))
+            .collect())
+    }
+
+    async fn top_traces_by_cost(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<TraceSummary>> {
+        let scope = Scope {
+            service,
+            since: Some(since),
+            until: Some(until),
+            roots_only: true,
+            ..Scope::default()
+        };
+        let roots = self.load(&scope).await?;
+
+        let mut traces = Vec::new();
+        for root in &roots {
+            let spans = self.load(&Scope::trace(&root.trace_id)).await?;
+            traces.push(TraceSummary::from_spans(&root.trace_id, &spans));
+        }
+        traces.sort_by(|a, b| {
+            b.total_cost_usd
+                .total_cmp(&a.total_cost_usd)
+                .then_with(|| b.started_at.cmp(&a.started_at))
+        });
+        traces.truncate(usize::try_from(limit).unwrap_or(0));
+        Ok(traces)
+    }
+
+    async fn delete_traces(&self, deletion: &TraceDeletion) -> Result<TraceDeletionResult> {
+        deletion.validate()?;
+
+        let span_query = SpanQuery {
+            attributes: deletion.attributes.clone().into_iter().collect(),
+            ..SpanQuery::default()
+        };
+        // Named traces are loaded one by one rather than scanning every span
+        let scopes: Vec<Scope> = if deletion.trace_ids.is_empty() {
+            vec![Scope::default()]
+        } else {
+            deletion.trace_ids.iter().map(|id| Scope::trace(id)).collect()
+        };
+        let mut trace_ids = BTreeSet::new();
+        for scope in scopes {
+            let scope = Scope { until: deletion.older_than, ..scope };
+            for span in self.load(&scope).await? {
+                if span_query.matches(&span) {
+                    trace_ids.insert(span.trace_id);
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+        let mut spans = 0;
+        for trace_id in &trace_ids {
+            if deletion.dry_run {
+                let row = sqlx::query("SELECT COUNT(*) as cnt FROM spans WHERE trace_id = ?")
+                    .bind(trace_id)
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(|e| Error::Database(e.to_string()))?;
+                spans += row.try_get::<i64, _>("cnt").unwrap_or(0);
+            } else {
+                let result = sqlx::query("DELETE FROM spans WHERE trace_id = ?")
+                    .bind(trace_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| Error::Database(e.to_string()))?;
+                spans += result.rows_affected() as i64;
+                for table in ["trace_feedback", "span_payloads"] {
+                    sqlx::query(&format!("DELETE FROM {} WHERE trace_id = ?", table))
+                        .bind(trace_id)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| Error::Database(e.to_string()))?;
+                }
+            }
+        }
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(TraceDeletionResult {
+            dry_run: deletion.dry_run,
+            traces: trace_ids.len() as i64,
+            spans,
+            trace_ids: trace_ids.into_iter().collect(),
+        })
+    }
+
+    async fn list_sessions(
+        &self,
+        user_id: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<(Vec<SessionSummary>, i64)> {
+        let spans = self
+            .load(&Scope {
+                in_session: true,
+                ..Scope::default()
+            })
+            .await?;
+        let mut by_session: BTreeMap<&str, Vec<&Span>> = BTreeMap::new();
+        for span in &spans {
+            if let Some(session_id) = span.session_id.as_deref() {
+                by_session.entry(session_id).or_default().push(span);
+            }
+        }
+
+        let mut sessions: Vec<SessionSummary> = by_session
+            .into_iter()
+            .filter_map(|(session_id, spans)| SessionSummary::from_spans(session_id, &spans))
+            .filter(|s| user_id.is_none_or(|u| s.user_id.as_deref() == Some(u)))
+            .filter(|s| since.is_none_or(|since| s.last_seen >= since))
+            .collect();
+        sessions.sort_by_key(|s| Reverse(s.last_seen));
+        let total = sessions.len() as i64;
+        sessions.truncate(usize::try_from(limit).unwrap_or(0));
+        Ok((sessions, total))
+    }
+
+    async fn get_session(&self, session_id: &str) -> Result<Option<SessionDetail>> {
+        let spans = self
+            .load(&Scope {
+                session_id: Some(session_id),
+                ..Scope::default()
+            })
+            .await?;
+        let session_spans: Vec<&Span> = spans.iter().collect();
+        let Some(session) = SessionSummary::from_spans(session_id, &session_spans) else {
+            return Ok(None);
+        };
+
+        // Spans are loaded oldest first, so traces come out in start order
+        let mut trace_ids: Vec<&str> = Vec::new();
+        for span in &spans {
+            if !trace_ids.contains(&span.trace_id.as_str()) {
+                trace_ids.push(&span.trace_id);
+            }
+        }
+        let mut traces = Vec::with_capacity(trace_ids.len());
+        for trace_id in trace_ids {
+            let spans = self.load(&Scope::trace(trace_id)).await?;
+            traces.push(TraceSummary::from_spans(trace_id, &spans));
+        }
+
+        Ok(Some(SessionDetail { session, traces }))
+    }
+
+    async fn add_feedback(&self, feedback: &TraceFeedback) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO trace_feedback (id, trace_id, rating, comment, user_id, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(feedback.id.to_string())
+        .bind(&feedback.trace_id)
+        .bind(feedback.rating.map(FeedbackRating::as_str))
+        .bind(&feedback.comment)
+        .bind(&feedback.user_id)
+        .bind(timestamp(feedback.created_at))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn list_feedback(&self, trace_id: &str) -> Result<Vec<TraceFeedback>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, trace_id, rating, comment, user_id, created_at
+            FROM trace_feedback
+            WHERE trace_id = ?
+            ORDER BY created_at, id
+            "#,
+        )
+        .bind(trace_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        rows.iter().map(row_to_feedback).collect()
+    }
+
+    async fn get_feedback_metrics(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<FeedbackMetrics> {
+        // Only feedback on traces that started in the window
+        let rows = sqlx::query(
+            r#"
+            SELECT id, trace_id, rating, comment, user_id, created_at FROM trace_feedback
+            WHERE trace_id IN (
+                SELECT trace_id FROM spans
+                GROUP BY trace_id
+                HAVING MIN(started_at) >= ? AND MIN(started_at) < ?
+            )
+            "#,
+        )
+        .bind(timestamp(since))
+        .bind(timestamp(until))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+        let feedback = rows.iter().map(row_to_feedback).collect::<Result<Vec<_>>>()?;
+
+        let mut by_trace: BTreeMap<&str, Vec<&TraceFeedback>> = BTreeMap::new();
+        for f in &feedback {
+            by_trace.entry(f.trace_id.as_str()).or_default().push(f);
+        }
+
+        // (ratings, traces, cost, duration, tokens) per rating
+        let mut totals: HashMap<FeedbackRating, (i64, i64, f64, f64, i64)> = HashMap::new();
+        let mut metrics = FeedbackMetrics::default();
+        for (trace_id, feedback) in by_trace {
+            let spans = self.load(&Scope::trace(trace_id)).await?;
+            if spans.is_empty() {
+                continue;
+            }
+            let trace = TraceSummary::from_spans(trace_id, &spans);
+            if trace.started_at < since
+                || trace.started_at >= until
+                || service.is_some_and(|svc| trace.service_name != svc)
+            {
+                continue;
+            }
+
+            metrics.comment_count += feedback.iter().filter(|f| f.comment.is_some()).count() as i64;
+            for rating in [FeedbackRating::Positive, FeedbackRating::Negative] {
+                let count = feedback.iter().filter(|f| f.rating == Some(rating)).count() as i64;
+                if count == 0 {
+                    continue;
+                }
+                let entry = totals.entry(rating).or_default();
+                entry.0 += count;
+                entry.1 += 1;
+                entry.2 += trace.total_cost_usd;
+                entry.3 += trace.duration_ms.unwrap_or(0.0);
+                entry.4 += trace.total_tokens;
+            }
+        }
+
+        for (rating, (ratings, traces, cost, duration, tokens)) in totals {
+            let group = FeedbackGroup {
+                feedback_count: ratings,
+                trace_count: traces,
+                avg_cost_usd: Some(cost / traces as f64),
+                avg_duration_ms: Some(duration / traces as f64),
+                avg_tokens: Some(tokens as f64 / traces as f64),
+            };
+            match rating {
+                FeedbackRating::Positive => metrics.positive = group,
+                FeedbackRating::Negative => metrics.negative = group,
+            }
+        }
+
+        metrics.compute_ratios();
+        Ok(metrics)
+    }
+
+    async fn put_payloads(&self, payloads: &[SpanPayload]) -> Result<usize> {
+        let mut tx = self.pool.begin().await.map_err(|e| Error::Database(e.to_string()))?;
+        for payload in payloads {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO span_payloads
+                    (trace_id, span_id, prompt, completion, truncated, size_bytes, created_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&payload.trace_id)
+            .bind(&payload.span_id)
+            .bind(&payload.prompt)
+            .bind(&payload.completion)
+            .bind(payload.truncated)
+            .bind(payload.size_bytes)
+            .bind(timestamp(payload.created_at))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+        }
+        tx.commit().await.map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(payloads.len())
+    }
+
+    async fn get_payload(&self, trace_id: &str, span_id: &str) -> Result<Option<SpanPayload>> {
+        let row = sqlx::query(
+            r#"
+            SELECT trace_id, span_id, prompt, completion, truncated, size_bytes, created_at
+            FROM span_payloads
+            WHERE trace_id = ? AND span_id = ?
+            "#,
+        )
+        .bind(trace_id)
+        .bind(span_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::Database(e.to_string()))?;
+
+        row.as_ref().map(row_to_payload).transpose()
+    }
+
+    async fn prune_payloads(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM span_payloads WHERE created_at < ?")
+            .bind(timestamp(before))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn list_services(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<ServiceSummary>> {
+        let spans = self.load(&Scope::window(None, None, since, until)).await?;
+
+        // (last seen, spans, errors, cost) per service
+        let mut services: BTreeMap<&str, (DateTime<Utc>, i64, i64, f64)> = BTreeMap::new();
+        for span in &spans {
+            let entry = services
+                .entry(span.service_name.as_str())
+                .or_insert((span.started_at, 0, 0, 0.0));
+            entry.0 = entry.0.max(span.started_at);
+            entry.1 += 1;
+            if span.status == SpanStatus::Error {
+                entry.2 += 1;
+            }
+            entry.3 += span.cost_usd.unwrap_or(0.0);
+        }
+
+        Ok(services
+            .into_iter()
+            .map(|(name, (last_seen, count, errors, cost))| {
+                ServiceSummary::new(name.to_string(), last_seen, count, errors, cost, until - since)
+            })
+            .collect())
+    }
+
+    async fn get_metrics_summary(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<MetricsSummaryResponse> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        let durations = sorted_durations(spans.iter());
+
+        let total_spans = spans.len() as i64;
+        let error_count = spans.iter().filter(|s| s.status == SpanStatus::Error).count() as i64;
+        let total_traces = spans.iter().map(|s| s.trace_id.as_str()).collect::<HashSet<_>>().len();
+
+        Ok(MetricsSummaryResponse {
+            total_spans,
+            total_traces: total_traces as i64,
+            total_tokens: spans.iter().map(span_tokens).sum(),
+            total_cost_usd: spans.iter().filter_map(|s| s.cost_usd).sum(),
+            error_count,
+            error_rate: if total_spans > 0 {
+                error_count as f64 / total_spans as f64 * 100.0
+            } else {
+                0.0
+            },
+            avg_latency_ms: mean(&durations).unwrap_or(0.0),
+            p50_latency_ms: percentile_cont(&durations, 0.5).unwrap_or(0.0),
+            p95_latency_ms: percentile_cont(&durations, 0.95).unwrap_or(0.0),
+            p99_latency_ms: percentile_cont(&durations, 0.99).unwrap_or(0.0),
+            latency_targets: None,
+            upstream_incidents: vec![],
+        })
+    }
+
+    async fn span_watermark(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<(i64, Option<DateTime<Utc>>)> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) as span_count, MAX(started_at) as latest FROM spans");
+        Scope::window(service, model, since, until).push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        let latest: Option<String> = row.try_get("latest").map_err(|e| Error::Database(e.to_string()))?;
+        let latest = latest
+            .map(|t| DateTime::parse_from_rfc3339(&t).map(|t| t.with_timezone(&Utc)))
+            .transpose()
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok((row.try_get("span_count").map_err(|e| Error::Database(e.to_string()))?, latest))
+    }
+
+    async fn aggregate(&self, query: &AggregateQuery) -> Result<Vec<AggregateRow>> {
+        let conditions = FieldCondition::from_search_filters(&query.filters)?;
+        let spans: Vec<Span> = self
+            .load(&Scope::window(None, None, query.since, query.until))
+            .await?
+            .into_iter()
+            .filter(|span| {
+                let doc = serde_json::to_value(span).unwrap_or_default();
+                conditions.iter().all(|c| c.matches(&doc))
+            })
+            .collect();
+
+        // Keep the top groups by their value over the whole range
+        let top_groups: Option<HashSet<String>> = query.group.as_ref().map(|group| {
+            let mut groups: HashMap<String, Vec<&Span>> = HashMap::new();
+            for span in &spans {
+                groups.entry(aggregate_group(group, span)).or_default().push(span);
+            }
+
+            let mut totals: Vec<(String, f64)> = groups
+                .into_iter()
+                .map(|(name, members)| (name, aggregate_value(query.metric, &members)))
+                .collect();
+            totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+            totals.into_iter().take(query.limit as usize).map(|(name, _)| name).collect()
+        });
+
+        let mut cells: BTreeMap<(Option<DateTime<Utc>>, Option<String>), Vec<&Span>> = BTreeMap::new();
+        for span in &spans {
+            let group = query.group.as_ref().map(|g| aggregate_group(g, span));
+            if let (Some(group), Some(top)) = (&group, &top_groups) {
+                if !top.contains(group) {
+                    continue;
+                }
+            }
+
+            let bucket = query.bucket_secs.map(|secs| {
+                let ts = span.started_at.timestamp();
+                DateTime::from_timestamp(ts - ts.rem_euclid(secs), 0).unwrap_or(span.started_at)
+            });
+            cells.entry((bucket, group)).or_default().push(span);
+        }
+
+        // An ungrouped, unbucketed query always has one row, as in SQL
+        if query.group.is_none() && query.bucket_secs.is_none() && cells.is_empty() {
+            cells.insert((None, None), Vec::new());
+        }
+
+        let mut rows: Vec<AggregateRow> = cells
+            .into_iter()
+            .map(|((bucket, group), members)| AggregateRow {
+                group,
+                bucket,
+                value: aggregate_value(query.metric, &members),
+            })
+            .collect();
+        rows.sort_by(|a, b| a.bucket.cmp(&b.bucket).then(b.value.total_cmp(&a.value)));
+
+        Ok(rows)
+    }
+
+    async fn get_cost_by_group(
+        &self,
+        service: Option<&str>,
+        group_by: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CostMetric>> {
+        let spans = self.load(&Scope::window(service, None, since, until)).await?;
+
+        let attribute = group_by
+            .strip_prefix("attr:")
+            .map(|key| AggregateGroup::Attribute(key.to_string()));
+
+        let mut groups: HashMap<String, CostMetric> = HashMap::new();
+        for span in &spans {
+            let group = match (&attribute, group_by) {
+                (Some(attribute), _) => aggregate_group(attribute, span),
+                (None, "day") => span.started_at.format("%Y-%m-%d").to_string(),
+                (None, "hour") => span.started_at.format("%Y-%m-%d %H:00").to_string(),
+                (None, "service") => span.service_name.clone(),
+                (None, "operation") => span.operation_name.clone(),
+                (None, _) => span.model_name.clone().unwrap_or_else(|| "unknown".to_string()),
+            };
+
+            let metric = groups.entry(group.clone()).or_insert_with(|| CostMetric {
+                group,
+                total_cost_usd: 0.0,
+                total_tokens: 0,
+                call_count: 0,
+            });
+            metric.total_cost_usd += span.cost_usd.unwrap_or(0.0);
+            metric.total_tokens += span_tokens(span);
+            metric.call_count += 1;
+        }
+
+        // Time groupings are returned in chronological order, the others by cost
+        let mut costs: Vec<CostMetric> = groups.into_values().collect();
+        match group_by {
+            "day" | "hour" => costs.sort_by(|a, b| a.group.cmp(&b.group)),
+            _ => costs.sort_by(|a, b| b.total_cost_usd.total_cmp(&a.total_cost_usd)),
+        }
+
+        Ok(costs)
+    }
+
+    async fn get_operation_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<OperationEfficiency>> {
+        let spans = self.load(&Scope::window(None, None, since, until)).await?;
+
+        let failed: HashSet<&str> = spans
+            .iter()
+            .filter(|s| s.status == SpanStatus::Error)
+            .map(|s| s.trace_id.as_str())
+            .collect();
+
+        let mut groups: BTreeMap<(&str, &str), (OperationEfficiency, HashSet<&str>)> = BTreeMap::new();
+        for span in &spans {
+            if service.is_some_and(|svc| span.service_name != svc)
+                || model.is_some_and(|m| span.model_name.as_deref() != Some(m))
+            {
+                continue;
+            }
+
+            let (efficiency, traces) = groups
+                .entry((span.service_name.as_str(), span.operation_name.as_str()))
+                .or_insert_with(|| {
+                    let efficiency = OperationEfficiency {
+                        service_name: span.service_name.clone(),
+                        operation_name: span.operation_name.clone(),
+                        span_count: 0,
+                        tokens_in: 0,
+                        tokens_out: 0,
+                        output_input_ratio: None,
+                        total_cost_usd: 0.0,
+                        error_cost_usd: 0.0,
+                        trace_count: 0,
+                        successful_traces: 0,
+                        cost_per_successful_trace: None,
+                    };
+                    (efficiency, HashSet::new())
+                });
+
+            let cost = span.cost_usd.unwrap_or(0.0);
+            efficiency.span_count += 1;
+            efficiency.tokens_in += i64::from(span.tokens_in.unwrap_or(0));
+            efficiency.tokens_out += i64::from(span.tokens_out.unwrap_or(0));
+            efficiency.total_cost_usd += cost;
+            if span.status == SpanStatus::Error {
+                efficiency.error_cost_usd += cost;
+            }
+            if traces.insert(span.trace_id.as_str()) {
+                efficiency.trace_count += 1;
+                if !failed.contains(span.trace_id.as_str()) {
+                    efficiency.successful_traces += 1;
+                }
+            }
+        }
+
+        let mut operations: Vec<OperationEfficiency> = groups
+            .into_values()
+            .map(|(mut efficiency, _)| {
+                efficiency.compute_ratios();
+                efficiency
+            })
+            .filter(|e| e.tokens_in + e.tokens_out > 0 || e.total_cost_usd > 0.0)
+            .collect();
+        operations.sort_by(|a, b| b.total_cost_usd.total_cmp(&a.total_cost_usd));
+
+        Ok(operations)
+    }
+
+    async fn get_token_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TokenEfficiency> {
+        let spans = self.load(&Scope::window(None, None, since, until)).await?;
+
+        let failed: HashSet<&str> = spans
+            .iter()
+            .filter(|s| s.status == SpanStatus::Error)
+            .map(|s| s.trace_id.as_str())
+            .collect();
+
+        let mut efficiency = TokenEfficiency::default();
+        let mut traces: HashSet<&str> = HashSet::new();
+        for span in &spans {
+            if service.is_some_and(|svc| span.service_name != svc)
+                || model.is_some_and(|m| span.model_name.as_deref() != Some(m))
+            {
+                continue;
+            }
+
+            efficiency.span_count += 1;
+            efficiency.tokens_in += i64::from(span.tokens_in.unwrap_or(0));
+            efficiency.tokens_cached_in += i64::from(span.tokens_cached_in.unwrap_or(0));
+            efficiency.tokens_out += i64::from(span.tokens_out.unwrap_or(0));
+            efficiency.tokens_reasoning += i64::from(span.tokens_reasoning.unwrap_or(0));
+            efficiency.total_cost_usd += span.cost_usd.unwrap_or(0.0);
+            if traces.insert(span.trace_id.as_str()) {
+                efficiency.trace_count += 1;
+                if !failed.contains(span.trace_id.as_str()) {
+                    efficiency.successful_traces += 1;
+                }
+            }
+        }
+
+        efficiency.compute_ratios();
+        Ok(efficiency)
+    }
+
+    async fn get_time_breakdown(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TimeBreakdownMetric> {
+        let scope = Scope {
+            roots_only: true,
+            ..Scope::window(service, None, since, until)
+        };
+        let roots = self.load(&scope).await?;
+
+        let mut seen = HashSet::new();
+        let mut trace_count = 0;
+        let mut total = TimeBreakdown::default();
+
+        for root in &roots {
+            let Some(duration_ms) = root.duration_ms else {
+                continue;
+            };
+            if !seen.insert(root.trace_id.as_str()) {
+                continue;
+            }
+
+            let spans = self.load(&Scope::trace(&root.trace_id)).await?;
+            let breakdown = TimeBreakdown::from_spans(duration_ms, &spans);
+
+            trace_count += 1;
+            total.total_ms += breakdown.total_ms;
+            total.llm_ms += breakdown.llm_ms;
+            total.tool_ms += breakdown.tool_ms;
+            total.overhead_ms += breakdown.overhead_ms;
+        }
+
+        Ok(TimeBreakdownMetric::from_totals(trace_count, total))
+    }
+
+    async fn get_latency_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<LatencyMetric>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+
+        Ok(hourly(&spans)
+            .into_iter()
+            .map(|(timestamp, bucket)| {
+                let durations = sorted_durations(bucket.iter().copied());
+                LatencyMetric {
+                    timestamp,
+                    avg_ms: mean(&durations).unwrap_or(0.0),
+                    p50_ms: percentile_cont(&durations, 0.5).unwrap_or(0.0),
+                    p95_ms: percentile_cont(&durations, 0.95).unwrap_or(0.0),
+                    p99_ms: percentile_cont(&durations, 0.99).unwrap_or(0.0),
+                    count: bucket.len() as i64,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_errors_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ErrorMetric>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+
+        Ok(hourly(&spans)
+            .into_iter()
+            .map(|(timestamp, bucket)| {
+                let error_count = bucket.iter().filter(|s| s.status == SpanStatus::Error).count() as i64;
+                let total_count = bucket.len() as i64;
+                ErrorMetric {
+                    timestamp,
+                    error_count,
+                    total_count,
+                    error_rate: error_count as f64 / total_count as f64 * 100.0,
+                }
+            })
+            .collect())
+    }
+
+    async fn get_error_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<ErrorStats> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        let errors: Vec<&Span> = spans.iter().filter(|s| s.status == SpanStatus::Error).collect();
+
+        Ok(ErrorStats {
+            error_count: errors.len() as i64,
+            total: spans.len() as i64,
+            sample_trace_ids: errors
+                .iter()
+                .map(|s| s.trace_id.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    async fn get_latency_percentile(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<Option<f64>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        Ok(percentile_cont(&sorted_durations(spans.iter()), percentile))
+    }
+
+    async fn get_operation_latency(
+        &self,
+        service: &str,
+        operation: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<(Option<f64>, i64)> {
+        let spans = self.load(&Scope::window(Some(service), None, since, until)).await?;
+        let durations = sorted_durations(spans.iter().filter(|s| s.operation_name == operation));
+        Ok((percentile_cont(&durations, percentile), durations.len() as i64))
+    }
+
+    async fn get_latency_avg(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        Ok(mean(&sorted_durations(spans.iter())))
+    }
+
+    async fn get_cost_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        Ok((!spans.is_empty()).then(|| spans.iter().filter_map(|s| s.cost_usd).sum()))
+    }
+
+    async fn get_token_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<i64>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        Ok((!spans.is_empty()).then(|| spans.iter().map(span_tokens).sum()))
+    }
+
+    async fn get_span_count(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<i64> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) as cnt FROM spans");
+        Scope::window(service, model, since, until).push_where(&mut query);
+
+        let row = query
+            .build()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Error::Database(e.to_string()))?;
+
+        Ok(row.try_get("cnt").unwrap_or(0))
+    }
+
+    async fn get_window_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<Vec<WindowStats>> {
+        let spans = self.load(&Scope::window(service, model, since, until)).await?;
+        let window_ms = window.num_milliseconds().max(1);
+
+        let mut windows: BTreeMap<i64, Vec<&Span>> = BTreeMap::new();
+        for span in spans.iter().filter(|s| s.started_at < until) {
+            let index = (span.started_at - since).num_milliseconds() / window_ms;
+            windows.entry(index).or_default().push(span);
+        }
+
+        Ok(windows
+            .into_iter()
+            .map(|(index, spans)| {
+                let durations = sorted_durations(spans.iter().copied());
+                WindowStats {
+                    window_start: since + Duration::milliseconds(index * window_ms),
+                    total: spans.len() as i64,
+                    error_count: spans.iter().filter(|s| s.status == SpanStatus::Error).count() as i64,
+                    latency_avg: mean(&durations),
+                    latency_p50: percentile_cont(&durations, 0.5),
+                    latency_p95: percentile_cont(&durations, 0.95),
+                    latency_p99: percentile_cont(&durations, 0.99),
+                    cost_sum: spans.iter().filter_map(|s| s.cost_usd).sum(),
+                    token_sum: spans.iter().map(|s| span_tokens(s)).sum(),
+                }
+            })
+            .collect())
+    }
+
+    async fn get_storage_report(&self, _lookback_days: i64, _projection_days: i64) -> Result<StorageReport> {
+        Err(Error::config("Storage reports require PostgreSQL storage"))
+    }
+}
+
+/// An advanced search condition evaluated against a span's JSON form
+struct FieldCondition {
+    column: &'static str,
+    /// Key within `attributes` for `attributes.<key>` filters
+    attribute: Option<String>,
+    operator: &'static str,
+    value: Value,
+}
+
+impl FieldCondition {
+    /// Conditions for advanced search filters
+    fn from_search_filters(filters: &[SearchFilter]) -> Result<Vec<Self>> {
+        let mut conditions = Vec::new();
+
+        for f in filters {
+            let (column, attribute) = match f.attribute_key() {
+                Some(key) => ("attributes", Some(key.to_string())),
+                None => (filterable_column(&f.field)?, None),
+            };
+            let operator = match f.operator.as_str() {
+                "eq" => "eq",
+                "ne" => "ne",
+                "gt" => "gt",
+                "gte" => "gte",
+                "lt" => "lt",
+                "lte" => "lte",
+                "contains" => "contains",
+                other => {
+                    return Err(Error::validation(format!("Unsupported filter operator: {}", other)))
+                }
+            };
+
+            if matches!(f.value, Value::String(_) | Value::Number(_) | Value::Bool(_)) {
+                conditions.push(Self {
+                    column,
+                    attribute,
+                    operator,
+                    value: f.value.clone(),
+                });
+            }
+        }
+
+        Ok(conditions)
+    }
+
+    fn matches(&self, doc: &Value) -> bool {
+        let field = match &self.attribute {
+            Some(key) => doc.get(self.column).and_then(|attrs| attrs.get(key)),
+            None => doc.get(self.column),
+        };
+
+        // As in SQL, comparisons against a missing value never match
+        let field = match field {
+            Some(Value::Null) | None => return false,
+            Some(field) => field,
+        };
+
+        if self.attribute.is_some() && matches!(self.operator, "eq" | "ne") {
+            let equal = attribute_match_values(&self.value)
+                .iter()
+                .any(|v| compare_values(field, v) == Some(Ordering::Equal));
+            return equal == (self.operator == "eq");
+        }
+
+        if self.operator == "contains" {
+            return match (field.as_str(), self.value.as_str()) {
+                (Some(f), Some(v)) => f.to_lowercase().contains(&v.to_lowercase()),
+                _ => false,
+            };
+        }
+
+        let Some(ordering) = compare_values(field, &self.value) else {
+            return false;
+        };
+
+        match self.operator {
+            "eq" => ordering == Ordering::Equal,
+            "ne" => ordering != Ordering::Equal,
+            "gt" => ordering == Ordering::Greater,
+            "gte" => ordering != Ordering::Less,
+            "lt" => ordering == Ordering::Less,
+            "lte" => ordering != Ordering::Greater,
+            _ => false,
+        }
+    }
+}
+
+/// Terms of a full-text query, approximating PostgreSQL's
+/// `websearch_to_tsquery` with case-insensitive substring matching
+///
+/// Every term must appear in the operation name or a preview, and no
+/// `-excluded` term may appear. Quotes and `or` are ignored.
+struct TextTerms {
+    required: Vec<String>,
+    excluded: Vec<String>,
+}
+
+impl TextTerms {
+    fn parse(query: &str) -> Self {
+        let mut terms = Self {
+            required: Vec::new(),
+            excluded: Vec::new(),
+        };
+
+        for word in query.split_whitespace() {
+            let (list, word) = match word.strip_prefix('-') {
+                Some(rest) => (&mut terms.excluded, rest),
+                None => (&mut terms.required, word),
+            };
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric()).to_ascii_lowercase();
+            if !word.is_empty() && word != "or" {
+                list.push(word);
+            }
+        }
+
+        terms
+    }
+
+    /// Score a span, weighting operation name matches above preview matches
+    /// as the PostgreSQL index does
+    fn hit(&self, span: Span) -> Option<TextSearchHit> {
+        let fields = [
+            ("operation_name", Some(span.operation_name.as_str()), 1.0),
+            ("prompt_preview", span.prompt_preview.as_deref(), 0.4),
+            ("completion_preview", span.completion_preview.as_deref(), 0.4),
+        ];
+        let lowered: Vec<String> = fields
+            .iter()
+            .map(|(_, text, _)| text.unwrap_or_default().to_ascii_lowercase())
+            .collect();
+
+        let contains = |term: &String| lowered.iter().any(|text| text.contains(term.as_str()));
+        if self.required.is_empty() || !self.required.iter().all(contains) || self.excluded.iter().any(contains) {
+            return None;
+        }
+
+        let mut score = 0.0;
+        let mut highlights = Vec::new();
+        for ((field, text, weight), lower) in fields.iter().zip(&lowered) {
+            let matches = self.matches(lower);
+            if matches.is_empty() {
+                continue;
+            }
+
+            score += weight * matches.len() as f64;
+            highlights.push(TextHighlight {
+                field: (*field).to_string(),
+                fragment: mark(text.unwrap_or_default(), &matches),
+            });
+        }
+
+        Some(TextSearchHit {
+            span,
+            rank: score / (1.0 + score),
+            highlights,
+        })
+    }
+
+    /// Non-overlapping byte ranges of term occurrences in a lowercased text
+    fn matches(&self, lower: &str) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = self
+            .required
+            .iter()
+            .flat_map(|term| lower.match_indices(term.as_str()).map(|(i, m)| (i, i + m.len())))
+            .collect();
+        ranges.sort_unstable();
+
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+}
+
+/// Wrap byte ranges of `text` in `<mark>` tags
+///
+/// Ranges come from the ASCII-lowercased text, which has the same byte
+/// offsets as the original.
+fn mark(text: &str, ranges: &[(usize, usize)]) -> String {
+    let mut out = String::with_capacity(text.len() + ranges.len() * 13);
+    let mut pos = 0;
+    for &(start, end) in ranges {
+        out.push_str(&text[pos..start]);
+        out.push_str("<mark>");
+        out.push_str(&text[start..end]);
+        out.push_str("</mark>");
+        pos = end;
+    }
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// Compare two JSON scalars, treating RFC 3339 strings as timestamps
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => {
+            match (a.parse::<DateTime<Utc>>(), b.parse::<DateTime<Utc>>()) {
+                (Ok(a), Ok(b)) => Some(a.cmp(&b)),
+                _ => Some(a.cmp(b)),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Sort spans by a column and cut out one page, returning it with the total
+///
+/// Missing values sort last ascending and first descending, as in PostgreSQL,
+/// and ties are broken by ID. With `after`, the page starts past that span
+/// rather than at `offset`; the total still counts every span.
+fn page(
+    spans: Vec<Span>,
+    column: &str,
+    descending: bool,
+    limit: i64,
+    offset: i64,
+    after: Option<&PageCursor>,
+) -> (Vec<Span>, i64) {
+    let total = spans.len() as i64;
+
+    let mut keyed: Vec<(Value, Span)> = spans
+        .into_iter()
+        .map(|span| {
+            let key = serde_json::to_value(&span)
+                .ok()
+                .and_then(|doc| doc.get(column).cloned())
+                .unwrap_or(Value::Null);
+            (key, span)
+        })
+        .collect();
+
+    keyed.sort_by(|(a, a_span), (b, b_span)| {
+        let ordering = match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => compare_values(a, b).unwrap_or(Ordering::Equal),
+        }
+        .then_with(|| a_span.id.cmp(&b_span.id));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+
+    let spans = keyed
+        .into_iter()
+        .map(|(_, span)| span)
+        .filter(|span| {
+            after.map_or(true, |c| {
+                let position = (span.started_at, span.id).cmp(&(c.started_at, c.id));
+                position == if descending { Ordering::Less } else { Ordering::Greater }
+            })
+        })
+        .skip(usize::try_from(offset).unwrap_or(0))
+        .take(usize::try_from(limit).unwrap_or(0))
+        .collect();
+
+    (spans, total)
+}
+
+/// Group spans into hourly buckets in time order
+fn hourly(spans: &[Span]) -> BTreeMap<DateTime<Utc>, Vec<&Span>> {
+    let mut buckets: BTreeMap<DateTime<Utc>, Vec<&Span>> = BTreeMap::new();
+    for span in spans {
+        let bucket = span
+            .started_at
+            .duration_trunc(chrono::Duration::hours(1))
+            .unwrap_or(span.started_at);
+        buckets.entry(bucket).or_default().push(span);
+    }
+    buckets
+}
+
+fn sorted_durations<'a>(spans: impl Iterator<Item = &'a Span>) -> Vec<f64> {
+    let mut durations: Vec<f64> = spans.filter_map(|s| s.duration_ms).collect();
+    durations.sort_by(f64::total_cmp);
+    durations
+}
+
+/// Group value of a span for an aggregation, `unknown` when missing
+fn aggregate_group(group: &AggregateGroup, span: &Span) -> String {
+    match group {
+        AggregateGroup::Service => span.service_name.clone(),
+        AggregateGroup::Model => span.model_name.clone().unwrap_or_else(|| "unknown".to_string()),
+        AggregateGroup::Operation => span.operation_name.clone(),
+        AggregateGroup::Status => span_status_to_str(&span.status).to_string(),
+        AggregateGroup::Attribute(key) => match span.attributes.get(key) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => "unknown".to_string(),
+            Some(other) => other.to_string(),
+        },
+    }
+}
+
+/// Aggregation metric over a set of spans, matching the SQL expressions
+fn aggregate_value(metric: AggregateMetric, spans: &[&Span]) -> f64 {
+    match metric {
+        AggregateMetric::Count => spans.len() as f64,
+        AggregateMetric::SumCost => spans.iter().filter_map(|s| s.cost_usd).sum(),
+        AggregateMetric::SumTokens => spans.iter().map(|s| span_tokens(s)).sum::<i64>() as f64,
+        AggregateMetric::AvgLatency => mean(&sorted_durations(spans.iter().copied())).unwrap_or(0.0),
+        AggregateMetric::ErrorRate if spans.is_empty() => 0.0,
+        AggregateMetric::ErrorRate => {
+            let errors = spans.iter().filter(|s| s.status == SpanStatus::Error).count();
+            errors as f64 * 100.0 / spans.len() as f64
+        }
+        percentile => {
+            let durations = sorted_durations(spans.iter().copied());
+            percentile_cont(&durations, percentile.percentile().unwrap_or(0.5)).unwrap_or(0.0)
+        }
+    }
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Continuous percentile over sorted values, matching `PERCENTILE_CONT`
+fn percentile_cont(sorted: &[f64], percentile: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = percentile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+fn span_tokens(span: &Span) -> i64 {
+    i64::from(span.tokens_in.unwrap_or(0)) + i64::from(span.tokens_out.unwrap_or(0))
+}
+
+/// Timestamps are stored with a fixed width so they sort as text
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn row_to_span(row: &SqliteRow) -> Result<Span> {
+    let data: String = row.try_get("data").map_err(|e| Error::Database(e.to_string()))?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+fn row_to_payload(row: &SqliteRow) -> Result<SpanPayload> {
+    let created_at: String = row.try_get("created_at").map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(SpanPayload {
+        trace_id: row.try_get("trace_id").map_err(|e| Error::Database(e.to_string()))?,
+        span_id: row.try_get("span_id").map_err(|e| Error::Database(e.to_string()))?,
+        prompt: row.try_get("prompt").map_err(|e| Error::Database(e.to_string()))?,
+        completion: row.try_get("completion").map_err(|e| Error::Database(e.to_string()))?,
+        truncated: row.try_get("truncated").map_err(|e| Error::Database(e.to_string()))?,
+        size_bytes: row.try_get("size_bytes").map_err(|e| Error::Database(e.to_string()))?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| Error::Database(e.to_string()))?
+            .with_timezone(&Utc),
+    })
+}
+
+fn row_to_feedback(row: &SqliteRow) -> Result<TraceFeedback> {
+    let id: String = row.try_get("id").map_err(|e| Error::Database(e.to_string()))?;
+    let rating: Option<String> = row.try_get("rating").map_err(|e| Error::Database(e.to_string()))?;
+    let created_at: String = row.try_get("created_at").map_err(|e| Error::Database(e.to_string()))?;
+
+    Ok(TraceFeedback {
+        id: Uuid::parse_str(&id).map_err(|e| Error::Database(e.to_string()))?,
+        trace_id: row.try_get("trace_id").map_err(|e| Error::Database(e.to_string()))?,
+        rating: rating.as_deref().and_then(FeedbackRating::parse),
+        comment: row.try_get("comment").map_err(|e| Error::Database(e.to_string()))?,
+        user_id: row.try_get("user_id").map_err(|e| Error::Database(e.to_string()))?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map_err(|e| Error::Database(e.to_string()))?
+            .with_timezone(&Utc),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::models::{FeedbackInput, SpanKind, SpanLink, STALE_IN_PROGRESS_MESSAGE};
+    use chrono::Duration;
+
+    pub(crate) fn create_test_span(trace_id: &str, span_id: &str, parent: Option<&str>, duration_ms: f64) -> Span {
+        let started_at = Utc::now() - Duration::minutes(5);
+        Span {
+            id: Uuid::new_v4(),
+            span_id: span_id.to_string(),
+            trace_id: trace_id.to_string(),
+            parent_span_id: parent.map(str::to_string),
+            session_id: None,
+            user_id: None,
+            operation_name: format!("op-{}", span_id),
+            service_name: "agent".to_string(),
+            span_kind: SpanKind::Internal,
+            started_at,
+            ended_at: Some(started_at + Duration::milliseconds(duration_ms as i64)),
+            duration_ms: Some(duration_ms),
+            status: SpanStatus::Ok,
+            status_message: None,
+            model_name: None,
+            model_provider: None,
+            tokens_in: None,
+            tokens_cached_in: None,
+            tokens_out: None,
+            tokens_reasoning: None,
+            cost_usd: None,
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: None,
+            completion_preview: None,
+            attributes: serde_json::json!({}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::Normal,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_insert_is_idempotent() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let root = create_test_span("t1", "a", None, 100.0);
+        let child = create_test_span("t1", "b", Some("a"), 40.0);
+
+        assert_eq!(store.insert_batch(&[root.clone(), child]).await.unwrap(), 2);
+        assert_eq!(store.insert_batch(&[root.clone()]).await.unwrap(), 0);
+
+        let spans = store.get_by_trace_id("t1").await.unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(store.get_by_id(&root.id).await.unwrap().unwrap().span_id, "a");
+    }
+
+    #[tokio::test]
+    async fn test_find_traces() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut failed = create_test_span("t2", "d", Some("c"), 20.0);
+        failed.status = SpanStatus::Error;
+        failed.attributes = serde_json::json!({"customer.tier": "gold"});
+        store
+            .insert_batch(&[
+                create_test_span("t1", "a", None, 100.0),
+                create_test_span("t1", "b", Some("a"), 40.0),
+                create_test_span("t2", "c", None, 300.0),
+                failed,
+            ])
+            .await
+            .unwrap();
+
+        let search = |search: TraceSearch| {
+            let store = &store;
+            async move {
+                let traces = store.find_traces(&TraceSearch { limit: 10, ..search }).await.unwrap();
+                let mut ids: Vec<_> = traces.into_iter().map(|t| t.trace_id).collect();
+                ids.sort();
+                ids
+            }
+        };
+        assert_eq!(search(TraceSearch::default()).await, vec!["t1", "t2"]);
+        assert_eq!(search(TraceSearch { min_duration: Some(200.0), ..Default::default() }).await, vec!["t2"]);
+        assert_eq!(
+            search(TraceSearch { operation: Some("op-b".to_string()), ..Default::default() }).await,
+            vec!["t1"]
+        );
+        let tagged = SpanQuery {
+            status: Some("error".to_string()),
+            attributes: vec![("customer.tier".to_string(), "gold".to_string())],
+            ..Default::default()
+        };
+        assert_eq!(search(TraceSearch { spans: vec![tagged], ..Default::default() }).await, vec!["t2"]);
+
+        let spans = store.get_by_trace_ids(&["t1".to_string(), "t2".to_string()]).await.unwrap();
+        assert_eq!(spans.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_in_progress_span_is_updated() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut started = create_test_span("t1", "a", None, 0.0);
+        started.status = SpanStatus::InProgress;
+        started.ended_at = None;
+        started.duration_ms = None;
+        started.model_name = Some("gpt-4o".to_string());
+        started.prompt_preview = Some("hello".to_string());
+        assert_eq!(store.insert_batch(&[started.clone()]).await.unwrap(), 1);
+
+        let mut update = create_test_span("t1", "a", None, 1500.0);
+        update.started_at = started.started_at;
+        update.ended_at = Some(started.started_at + Duration::milliseconds(1500));
+        update.tokens_in = Some(120);
+        update.tokens_out = Some(30);
+        update.cost_usd = Some(0.0006);
+        assert_eq!(store.insert_batch(&[update.clone()]).await.unwrap(), 1);
+
+        let stored = store.get_by_id(&started.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, SpanStatus::Ok);
+        assert_eq!(stored.model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(stored.prompt_preview.as_deref(), Some("hello"));
+        assert_eq!(stored.tokens_out, Some(30));
+        assert_eq!(stored.cost_usd, Some(0.0006));
+        assert_eq!(store.get_by_trace_id("t1").await.unwrap().len(), 1);
+
+        // Once final, later deliveries are skipped
+        assert_eq!(store.insert_batch(&[update]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_in_progress_spans_are_closed() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let hours_ago = Utc::now() - Duration::hours(2);
+        let mut spans = Vec::new();
+        for span_id in ["a", "b", "c"] {
+            let mut span = create_test_span("t1", span_id, None, 0.0);
+            span.status = SpanStatus::InProgress;
+            span.started_at = hours_ago;
+            spans.push(span);
+        }
+        // "b" was also stored final under another start time, "c" is recent
+        let mut finished = create_test_span("t1", "b", None, 100.0);
+        finished.started_at = hours_ago + Duration::milliseconds(5);
+        spans.push(finished);
+        spans[2].started_at = Utc::now() - Duration::minutes(1);
+        store.insert_batch(&spans).await.unwrap();
+
+        let in_progress = store
+            .get_in_progress(&["a".to_string(), "b".to_string(), "z".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(in_progress.len(), 2);
+
+        // Metrics only count spans that finished
+        let since = Utc::now() - Duration::hours(3);
+        let summary = store.get_metrics_summary(None, None, since, Utc::now()).await.unwrap();
+        assert_eq!(summary.total_spans, 1);
+
+        let closed = store.close_stale_in_progress(Utc::now() - Duration::hours(1)).await.unwrap();
+        assert_eq!(closed, 2);
+
+        let spans = store.get_by_trace_id("t1").await.unwrap();
+        let status = |id: &str| {
+            spans
+                .iter()
+                .filter(|s| s.span_id == id)
+                .map(|s| (s.status, s.status_message.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            status("a"),
+            vec![(SpanStatus::Error, Some(STALE_IN_PROGRESS_MESSAGE.to_string()))]
+        );
+        assert_eq!(status("b"), vec![(SpanStatus::Ok, None)]);
+        assert_eq!(status("c"), vec![(SpanStatus::InProgress, None)]);
+        assert_eq!(store.close_stale_in_progress(Utc::now() - Duration::hours(1)).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans = vec![
+            create_test_span("t1", "a", None, 100.0),
+            create_test_span("t1", "b", Some("a"), 40.0),
+            create_test_span("t2", "c", None, 80.0),
+            create_test_span("t3", "d", None, 50.0),
+            create_test_span("t4", "e", None, 10.0),
+        ];
+        for (span, session) in spans.iter_mut().zip(["s1", "s1", "s1", "s2"]) {
+            span.session_id = Some(session.to_string());
+            span.user_id = Some("u1".to_string());
+            span.cost_usd = Some(0.5);
+        }
+        spans[3].user_id = Some("u2".to_string());
+        spans[2].started_at += Duration::seconds(10);
+        store.insert_batch(&spans).await.unwrap();
+
+        let (sessions, total) = store.list_sessions(None, None, 10).await.unwrap();
+        assert_eq!((sessions.len(), total), (2, 2));
+        let s1 = sessions.iter().find(|s| s.session_id == "s1").unwrap();
+        assert_eq!((s1.trace_count, s1.span_count), (2, 3));
+        assert!((s1.total_cost_usd - 1.5).abs() < 1e-9);
+
+        let (by_user, _) = store.list_sessions(Some("u2"), None, 10).await.unwrap();
+        assert_eq!(by_user.len(), 1);
+
+        // The total counts every match, not just the page
+        let (page, total) = store.list_sessions(None, None, 1).await.unwrap();
+        assert_eq!((page.len(), total), (1, 2));
+        assert_eq!(by_user[0].session_id, "s2");
+
+        let detail = store.get_session("s1").await.unwrap().unwrap();
+        let traces: Vec<&str> = detail.traces.iter().map(|t| t.trace_id.as_str()).collect();
+        assert_eq!(traces, ["t1", "t2"]);
+        assert!(store.get_session("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_feedback() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans = vec![
+            create_test_span("t1", "a", None, 100.0),
+            create_test_span("t2", "b", None, 300.0),
+            create_test_span("t3", "c", None, 50.0),
+        ];
+        for (span, cost) in spans.iter_mut().zip([0.1, 0.5, 0.2]) {
+            span.cost_usd = Some(cost);
+        }
+        store.insert_batch(&spans).await.unwrap();
+
+        let feedback = |trace_id: &str, rating, comment: Option<&str>| FeedbackInput {
+            trace_id: trace_id.to_string(),
+            rating,
+            comment: comment.map(str::to_string),
+            user_id: None,
+        }
+        .into_feedback();
+        for f in [
+            feedback("t1", Some(FeedbackRating::Positive), None),
+            feedback("t2", Some(FeedbackRating::Negative), Some("wrong file")),
+            feedback("t2", Some(FeedbackRating::Negative), None),
+            feedback("t3", None, Some("slow")),
+        ] {
+            store.add_feedback(&f).await.unwrap();
+        }
+        assert_eq!(store.list_feedback("t2").await.unwrap().len(), 2);
+
+        let (negative, total) = store
+            .search(
+                None, None, None, None, None, &[], Some(true), None, None, None, None, None, None,
+                "started_at", true, 10, 0, None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(negative[0].trace_id, "t2");
+
+        let query = SpanQuery::parse("has_negative_feedback:false").unwrap();
+        let facets = store
+            .search_facets(&query, Utc::now() - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(facets.total, 2);
+
+        let metrics = store
+            .get_feedback_metrics(None, Utc::now() - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!((metrics.negative.feedback_count, metrics.negative.trace_count), (2, 1));
+        assert_eq!(metrics.negative.avg_duration_ms, Some(300.0));
+        assert_eq!(metrics.positive.trace_count, 1);
+        assert_eq!(metrics.comment_count, 2);
+        assert!((metrics.satisfaction_rate.unwrap() - 100.0 / 3.0).abs() < 1e-9);
+
+        // Traces that started outside the window are left out
+        let earlier = store
+            .get_feedback_metrics(None, Utc::now() - Duration::hours(2), Utc::now() - Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!((earlier.negative.feedback_count, earlier.comment_count), (0, 0));
+
+        store
+            .delete_traces(&TraceDeletion {
+                trace_ids: vec!["t2".to_string()],
+                ..TraceDeletion::default()
+            })
+            .await
+            .unwrap();
+        assert!(store.list_feedback("t2").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_payloads() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let payload = |completion: &str, age_days| SpanPayload {
+            trace_id: "t1".to_string(),
+            span_id: "a".to_string(),
+            prompt: Some("full prompt".to_string()),
+            completion: Some(completion.to_string()),
+            truncated: false,
+            size_bytes: 11 + completion.len() as i64,
+            created_at: Utc::now() - Duration::days(age_days),
+        };
+
+        // A streaming span's final payload replaces its in-progress one
+        store
+            .put_payloads(&[payload("partial", 10), payload("final answer", 10)])
+            .await
+            .unwrap();
+        let stored = store.get_payload("t1", "a").await.unwrap().unwrap();
+        assert_eq!(stored.completion.as_deref(), Some("final answer"));
+        assert!(store.get_payload("t1", "b").await.unwrap().is_none());
+
+        assert_eq!(store.prune_payloads(Utc::now() - Duration::days(7)).await.unwrap(), 1);
+        assert!(store.get_payload("t1", "a").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_sorts_and_pages() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let spans: Vec<Span> = [30.0, 10.0, 20.0]
+            .iter()
+            .enumerate()
+            .map(|(i, d)| create_test_span(&format!("t{}", i), &format!("root{}", i), None, *d))
+            .collect();
+        store.insert_batch(&spans).await.unwrap();
+
+        let (page, total) = store
+            .search(
+                None, None, None, None, None, &[], None, Some(15.0), None, None, None, None, None,
+                "duration_ms", false, 1, 0, None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(page[0].duration_ms, Some(20.0));
+
+        assert!(store
+            .search(None, None, None, None, None, &[], None, None, None, None, None, None, None, "data", false, 10, 0, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pagination() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (0..5)
+            .map(|i| create_test_span(&format!("t{}", i), &format!("root{}", i), None, 10.0))
+            .collect();
+        // Two spans share a start time so the ID tiebreak is exercised
+        spans[3].started_at = spans[2].started_at;
+        for (i, span) in spans.iter_mut().enumerate() {
+            if i != 3 {
+                span.started_at += Duration::seconds(i as i64);
+            }
+        }
+        store.insert_batch(&spans).await.unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, total) = store
+                .search(
+                    None, None, None, None, None, &[], None, None, None, None, None, None, None,
+                    "started_at", true, 2, 0, cursor.as_ref(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(total, 5);
+            if page.is_empty() {
+                break;
+            }
+            cursor = page.last().map(PageCursor::after);
+            seen.extend(page.into_iter().map(|s| s.id));
+        }
+        assert_eq!(seen.len(), 5);
+        assert_eq!(seen.iter().collect::<HashSet<_>>().len(), 5);
+
+        assert!(store
+            .search(
+                None, None, None, None, None, &[], None, None, None, None, None, None, None,
+                "duration_ms", true, 2, 0, cursor.as_ref(),
+            )
+            .await
+            .is_err());
+
+        let (first, next) = store.list_traces(None, None, None, None, 3, None).await.unwrap();
+        assert_eq!(first.len(), 3);
+        let (rest, last) = store.list_traces(None, None, None, None, 3, next.as_ref()).await.unwrap();
+        assert_eq!(rest.len(), 2);
+        assert!(last.is_none());
+        assert!(rest.iter().all(|t| first.iter().all(|f| f.trace_id != t.trace_id)));
+    }
+
+    #[tokio::test]
+    async fn test_list_traces_sorted() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let spans: Vec<Span> = [("t1", 300.0), ("t2", 100.0), ("t3", 500.0), ("t4", 200.0)]
+            .into_iter()
+            .map(|(trace_id, duration)| create_test_span(trace_id, "root", None, duration))
+            .collect();
+        store.insert_batch(&spans).await.unwrap();
+
+        // The slowest traces overall, not just the newest page re-sorted
+        let slowest = SortConfig { field: "duration_ms".to_string(), descending: true };
+        let (traces, next) = store.list_traces(None, None, None, Some(&slowest), 2, None).await.unwrap();
+        let ids: Vec<&str> = traces.iter().map(|t| t.trace_id.as_str()).collect();
+        assert_eq!(ids, vec!["t3", "t1"]);
+        assert!(next.is_none());
+
+        let fastest = SortConfig { field: "duration_ms".to_string(), descending: false };
+        let (traces, _) = store.list_traces(None, None, None, Some(&fastest), 1, None).await.unwrap();
+        assert_eq!(traces[0].trace_id, "t2");
+
+        let cursor = PageCursor::after(&spans[0]);
+        assert!(store.list_traces(None, None, None, Some(&slowest), 2, Some(&cursor)).await.is_err());
+        let unknown = SortConfig { field: "trace_id".to_string(), descending: false };
+        assert!(store.list_traces(None, None, None, Some(&unknown), 2, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delete_traces_by_attribute() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut root = create_test_span("t1", "a", None, 100.0);
+        root.attributes = serde_json::json!({"customer_id": 123});
+        let child = create_test_span("t1", "b", Some("a"), 40.0);
+        let other = create_test_span("t2", "c", None, 50.0);
+        store.insert_batch(&[root, child, other]).await.unwrap();
+
+        let mut deletion = TraceDeletion {
+            attributes: [("customer_id".to_string(), "123".to_string())].into(),
+            dry_run: true,
+            ..TraceDeletion::default()
+        };
+        let counted = store.delete_traces(&deletion).await.unwrap();
+        assert_eq!((counted.traces, counted.spans), (1, 2));
+        assert_eq!(store.get_by_trace_id("t1").await.unwrap().len(), 2);
+
+        deletion.dry_run = false;
+        let deleted = store.delete_traces(&deletion).await.unwrap();
+        assert_eq!(deleted.trace_ids, vec!["t1".to_string()]);
+        assert_eq!(deleted.spans, 2);
+        assert!(store.get_by_trace_id("t1").await.unwrap().is_empty());
+        assert_eq!(store.get_by_trace_id("t2").await.unwrap().len(), 1);
+
+        // Nothing named, nothing deleted
+        assert!(store.delete_traces(&TraceDeletion::default()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_attribute_filters() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (0..3)
+            .map(|i| create_test_span(&format!("t{}", i), &format!("s{}", i), None, 10.0))
+            .collect();
+        spans[0].attributes = serde_json::json!({"customer_id": "acme", "agent_version": 2});
+        spans[1].attributes = serde_json::json!({"customer_id": "globex", "agent_version": 3});
+        store.insert_batch(&spans).await.unwrap();
+
+        let filter = |field: &str, operator: &str, value: Value| SearchFilter {
+            field: field.to_string(),
+            operator: operator.to_string(),
+            value,
+        };
+
+        let (found, _) = store
+            .advanced_search(&[filter("attributes.agent_version", "eq", "2".into())], None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].span_id, "s0");
+
+        let (found, _) = store
+            .advanced_search(&[filter("attributes.agent_version", "gte", 3.into())], None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].span_id, "s1");
+
+        // Spans without the attribute match neither eq nor ne
+        let (found, total) = store
+            .advanced_search(&[filter("attributes.customer_id", "ne", "acme".into())], None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(found[0].span_id, "s1");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_and_links() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (1..=4)
+            .map(|i| create_test_span("t1", &format!("s{}", i), None, f64::from(i) * 10.0))
+            .collect();
+        spans[3].status = SpanStatus::Error;
+
+        let mut linking = create_test_span("t2", "x", None, 5.0);
+        linking.links.push(SpanLink {
+            trace_id: "t1".to_string(),
+            span_id: "s1".to_string(),
+            attributes: serde_json::json!({}),
+        });
+        spans.push(linking);
+        store.insert_batch(&spans).await.unwrap();
+
+        let since = Utc::now() - Duration::hours(1);
+        let summary = store
+            .get_metrics_summary(None, None, since, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(summary.total_spans, 5);
+        assert_eq!(summary.total_traces, 2);
+        assert_eq!(summary.error_count, 1);
+        assert_eq!(summary.p50_latency_ms, 20.0);
+
+        assert_eq!(store.get_linking_trace_ids("t1").await.unwrap(), vec!["t2".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_window_stats() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let since = Utc::now() - Duration::minutes(30);
+        let mut spans: Vec<Span> = (0..4)
+            .map(|i| {
+                let mut span = create_test_span("t1", &format!("s{}", i), None, f64::from(i + 1) * 10.0);
+                // Two spans in each of the first and third windows
+                span.started_at = since + Duration::minutes(if i < 2 { 1 } else { 21 });
+                span.tokens_in = Some(100);
+                span
+            })
+            .collect();
+        spans[3].status = SpanStatus::Error;
+        store.insert_batch(&spans).await.unwrap();
+
+        let windows = store
+            .get_window_stats(None, None, since, since + Duration::minutes(30), Duration::minutes(10))
+            .await
+            .unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].window_start, since);
+        assert_eq!(windows[0].total, 2);
+        assert_eq!(windows[0].latency_avg, Some(15.0));
+        assert_eq!(windows[1].window_start, since + Duration::minutes(20));
+        assert_eq!(windows[1].error_count, 1);
+        assert_eq!(windows[1].latency_p50, Some(35.0));
+        assert_eq!(windows[1].token_sum, 200);
+    }
+
+    #[tokio::test]
+    async fn test_cost_by_attribute() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (1..=4)
+            .map(|i| create_test_span("t1", &format!("s{}", i), None, 10.0))
+            .collect();
+        for (span, (customer, cost)) in spans
+            .iter_mut()
+            .zip([(Some("acme"), 1.0), (Some("acme"), 2.0), (Some("globex"), 4.0), (None, 0.5)])
+        {
+            span.cost_usd = Some(cost);
+            if let Some(customer) = customer {
+                span.attributes = serde_json::json!({ "customer_id": customer });
+            }
+        }
+        store.insert_batch(&spans).await.unwrap();
+
+        let costs = store
+            .get_cost_by_group(None, "attr:customer_id", Utc::now() - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        let groups: Vec<(&str, f64, i64)> = costs
+            .iter()
+            .map(|c| (c.group.as_str(), c.total_cost_usd, c.call_count))
+            .collect();
+        assert_eq!(groups, vec![("globex", 4.0, 1), ("acme", 3.0, 2), ("unknown", 0.5, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_groups_and_limits() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (1..=5)
+            .map(|i| create_test_span("t1", &format!("s{}", i), None, f64::from(i) * 10.0))
+            .collect();
+        for (span, tier) in spans.iter_mut().zip(["free", "free", "free", "pro", "team"]) {
+            span.attributes = serde_json::json!({ "tier": tier });
+        }
+        spans[4].status = SpanStatus::Error;
+        store.insert_batch(&spans).await.unwrap();
+
+        let since = Utc::now() - Duration::hours(1);
+        let query = |metric, group: Option<AggregateGroup>, filters: Vec<SearchFilter>| AggregateQuery {
+            metric,
+            group,
+            bucket_secs: None,
+            filters,
+            since,
+            until: Utc::now(),
+            limit: 2,
+        };
+
+        let rows = store
+            .aggregate(&query(AggregateMetric::Count, Some(AggregateGroup::Attribute("tier".into())), vec![]))
+            .await
+            .unwrap();
+        let groups: Vec<(Option<&str>, f64)> = rows.iter().map(|r| (r.group.as_deref(), r.value)).collect();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0], (Some("free"), 3.0));
+
+        let error_rate = store
+            .aggregate(&query(AggregateMetric::ErrorRate, None, vec![]))
+            .await
+            .unwrap();
+        assert_eq!(error_rate.len(), 1);
+        assert_eq!(error_rate[0].value, 20.0);
+
+        let filtered = store
+            .aggregate(&query(
+                AggregateMetric::P50Latency,
+                None,
+                vec![SearchFilter {
+                    field: "attributes.tier".to_string(),
+                    operator: "eq".to_string(),
+                    value: serde_json::json!("free"),
+                }],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(filtered[0].value, 20.0);
+    }
+
+    #[tokio::test]
+    async fn test_search_facets() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut spans: Vec<Span> = (1..=4)
+            .map(|i| create_test_span("t1", &format!("s{}", i), None, 10.0))
+            .collect();
+        spans[0].model_name = Some("gpt-4o".to_string());
+        spans[1].model_name = Some("gpt-4o".to_string());
+        spans[1].status = SpanStatus::Error;
+        spans[2].operation_name = "op-s1".to_string();
+        spans[3].service_name = "other".to_string();
+        store.insert_batch(&spans).await.unwrap();
+
+        let since = Utc::now() - Duration::hours(1);
+        let facets = store
+            .search_facets(&SpanQuery::parse("service:agent").unwrap(), since, Utc::now())
+            .await
+            .unwrap();
+
+        let facet = |value: &str, count| FacetCount {
+            value: value.to_string(),
+            count,
+        };
+        assert_eq!(facets.total, 3);
+        assert_eq!(facets.services, vec![facet("agent", 3)]);
+        assert_eq!(facets.models, vec![facet("gpt-4o", 2)]);
+        assert_eq!(facets.statuses, vec![facet("ok", 2), facet("error", 1)]);
+        assert_eq!(facets.operations, vec![facet("op-s1", 2), facet("op-s2", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_operation_efficiency() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let llm_call = |trace_id: &str, span_id: &str, cost: f64| {
+            let mut span = create_test_span(trace_id, span_id, None, 10.0);
+            span.operation_name = "plan".to_string();
+            span.tokens_in = Some(1000);
+            span.tokens_out = Some(50);
+            span.cost_usd = Some(cost);
+            span
+        };
+
+        let mut failed_tool = create_test_span("t2", "tool", None, 5.0);
+        failed_tool.status = SpanStatus::Error;
+        let mut failed_call = llm_call("t3", "c", 0.5);
+        failed_call.status = SpanStatus::Error;
+
+        store
+            .insert_batch(&[llm_call("t1", "a", 0.1), llm_call("t2", "b", 0.1), failed_tool, failed_call])
+            .await
+            .unwrap();
+
+        let since = Utc::now() - Duration::hours(1);
+        let operations = store
+            .get_operation_efficiency(None, None, since, Utc::now())
+            .await
+            .unwrap();
+
+        // The tool span used no tokens and is left out
+        assert_eq!(operations.len(), 1);
+        let plan = &operations[0];
+        assert_eq!(plan.span_count, 3);
+        assert_eq!(plan.output_input_ratio, Some(0.05));
+        assert_eq!(plan.trace_count, 3);
+        assert_eq!(plan.successful_traces, 1);
+        assert!((plan.error_cost_usd - 0.5).abs() < 1e-9);
+        assert!((plan.cost_per_successful_trace.unwrap() - 0.7).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_token_efficiency() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let llm_call = |trace_id: &str, span_id: &str| {
+            let mut span = create_test_span(trace_id, span_id, None, 10.0);
+            span.tokens_in = Some(1000);
+            span.tokens_cached_in = Some(250);
+            span.tokens_out = Some(150);
+            span.tokens_reasoning = Some(50);
+            span.cost_usd = Some(0.2);
+            span
+        };
+        let mut failed = llm_call("t2", "b");
+        failed.status = SpanStatus::Error;
+        store.insert_batch(&[llm_call("t1", "a"), llm_call("t1", "c"), failed]).await.unwrap();
+
+        let efficiency = store
+            .get_token_efficiency(None, None, Utc::now() - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!((efficiency.span_count, efficiency.trace_count, efficiency.successful_traces), (3, 2, 1));
+        assert!((efficiency.cost_per_successful_trace.unwrap() - 0.6).abs() < 1e-9);
+        assert_eq!(efficiency.tokens_per_trace, Some(1800.0));
+        assert_eq!(efficiency.cached_hit_rate, Some(25.0));
+        assert_eq!(efficiency.reasoning_share, Some(25.0));
+        assert_eq!(efficiency.output_input_ratio, Some(0.15));
+
+        let empty = store
+            .get_token_efficiency(Some("other"), None, Utc::now() - Duration::hours(1), Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(empty.tokens_per_trace, None);
+        assert_eq!(empty.cached_hit_rate, None);
+    }
+
+    #[tokio::test]
+    async fn test_text_search_ranks_and_highlights() {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut by_name = create_test_span("t1", "a", None, 10.0);
+        by_name.operation_name = "refund_lookup".to_string();
+        let mut by_prompt = create_test_span("t1", "b", Some("a"), 10.0);
+        by_prompt.prompt_preview = Some("Customer asks about a Refund".to_string());
+        let mut excluded = create_test_span("t2", "c", None, 10.0);
+        excluded.prompt_preview = Some("refund denied".to_string());
+        store.insert_batch(&[by_name, by_prompt, excluded]).await.unwrap();
+
+        let (hits, total) = store
+            .text_search("refund -denied", None, None, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(hits[0].span.span_id, "a");
+        assert!(hits[0].rank > hits[1].rank);
+        assert_eq!(hits[1].highlights[0].field, "prompt_preview");
+        assert_eq!(hits[1].highlights[0].fragment, "Customer asks about a <mark>Refund</mark>");
+    }
+
+    #[test]
+    fn test_percentile_cont_interpolates() {
+        let values = [10.0, 20.0, 30.0, 40.0];
+        assert_eq!(percentile_cont(&values, 0.5), Some(25.0));
+        assert_eq!(percentile_cont(&values, 1.0), Some(40.0));
+        assert_eq!(percentile_cont(&[], 0.5), None);
+    }
+}
@@ -0,0 +1,698 @@
+//! Storage-agnostic span queries
+//!
+//! The pipeline, API and alert evaluator talk to span storage through
+//! [`SpanStore`], so the same code runs against TimescaleDB in production and
+//! SQLite in local/dev mode.
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::models::{
+    AggregateQuery, AggregateRow, CostMetric, ErrorMetric, ErrorStats, FeedbackMetrics, LatencyMetric, MetricsSummaryResponse,
+    OperationEfficiency, PageCursor, SearchFacets, SearchFilter, ServiceSummary, SessionDetail, SessionSummary,
+    SortConfig, Span, SpanPayload, SpanPriority, SpanQuery, StorageReport, TextSearchHit, TimeBreakdownMetric, TokenEfficiency, TraceDeletion, TraceDeletionResult,
+    TraceFeedback, TraceSearch, TraceSummary, WindowStats,
+};
+
+use super::postgres::SpanRepository;
+
+/// Span storage backend
+#[async_trait::async_trait]
+pub trait SpanStore: Send + Sync {
+    /// Insert a batch of spans, returning how many were written
+    ///
+    /// Spans already stored are skipped, except an `in_progress` span, which
+    /// is updated in place by a later version of itself.
+    async fn insert_batch(&self, spans: &[Span]) -> Result<usize>;
+
+    /// Get the stored `in_progress` spans with any of these span IDs, so a
+    /// final update can be merged into its start event
+    async fn get_in_progress(&self, span_ids: &[String]) -> Result<Vec<Span>>;
+
+    /// Close `in_progress` spans started before a time, whose final update
+    /// is not coming, returning how many were closed
+    ///
+    /// Start events that a final span with a different start time already
+    /// completed are deleted; the rest are marked as errors.
+    async fn close_stale_in_progress(&self, before: DateTime<Utc>) -> Result<u64>;
+
+    /// Get a span by its ID
+    async fn get_by_id(&self, id: &Uuid) -> Result<Option<Span>>;
+
+    /// Get all spans of a trace in start order
+    async fn get_by_trace_id(&self, trace_id: &str) -> Result<Vec<Span>>;
+
+    /// Get all spans of several traces in start order
+    async fn get_by_trace_ids(&self, trace_ids: &[String]) -> Result<Vec<Span>>;
+
+    /// Get the spans of a trace that match a query, in start order
+    async fn search_in_trace(&self, trace_id: &str, query: &SpanQuery) -> Result<Vec<Span>>;
+
+    /// Count spans matching a query per service, model, status and operation
+    async fn search_facets(
+        &self,
+        query: &SpanQuery,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SearchFacets>;
+
+    /// Get IDs of other traces containing spans that link into a trace
+    async fn get_linking_trace_ids(&self, trace_id: &str) -> Result<Vec<String>>;
+
+    /// Get the most recent spans
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Span>>;
+
+    /// Search spans with filters
+    ///
+    /// `has_negative_feedback` keeps only spans of traces that did, or did
+    /// not, get a negative rating. With a `cursor`, the page starts after
+    /// that span instead of at `offset`; cursors require sorting by
+    /// `started_at`.
+    #[allow(clippy::too_many_arguments)]
+    async fn search(
+        &self,
+        query: Option<&str>,
+        service: Option<&str>,
+        model: Option<&str>,
+        status: Option<&str>,
+        priority: Option<SpanPriority>,
+        attributes: &[(String, String)],
+        has_negative_feedback: Option<bool>,
+        min_duration: Option<f64>,
+        max_duration: Option<f64>,
+        min_cost: Option<f64>,
+        max_cost: Option<f64>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        sort_by: &str,
+        sort_desc: bool,
+        limit: i64,
+        offset: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<Span>, i64)>;
+
+    /// Advanced search with complex filters
+    async fn advanced_search(
+        &self,
+        filters: &[SearchFilter],
+        sort: Option<&SortConfig>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Span>, i64)>;
+
+    /// Full-text search over operation names, prompts and completions,
+    /// ordered by relevance
+    async fn text_search(
+        &self,
+        query: &str,
+        service: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<TextSearchHit>, i64)>;
+
+    /// List traces with summaries, newest first unless `sort` says
+    /// otherwise, with a cursor for the next page when this one is full and
+    /// in the default order
+    async fn list_traces(
+        &self,
+        service: Option<&str>,
+        status: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        sort: Option<&SortConfig>,
+        limit: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<TraceSummary>, Option<PageCursor>)>;
+
+    /// Find traces by what their spans contain, newest first
+    async fn find_traces(&self, search: &TraceSearch) -> Result<Vec<TraceSummary>>;
+
+    /// Get a trace's summary, or None if none of its spans are stored
+    async fn get_trace_summary(&self, trace_id: &str) -> Result<Option<TraceSummary>>;
+
+    /// Get the summaries of several traces, skipping those with no spans
+    /// stored
+    async fn get_trace_summaries(&self, trace_ids: &[String]) -> Result<Vec<TraceSummary>>;
+
+    /// List the most expensive traces started in a window, costliest first
+    async fn top_traces_by_cost(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<TraceSummary>>;
+
+    /// Delete the traces a deletion selects, or only count them on a dry run
+    async fn delete_traces(&self, deletion: &TraceDeletion) -> Result<TraceDeletionResult>;
+
+    /// List sessions active since a time, most recently active first, with
+    /// the number of sessions that match
+    async fn list_sessions(
+        &self,
+        user_id: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<(Vec<SessionSummary>, i64)>;
+
+    /// Get a session with its traces, or None if no span named it
+    async fn get_session(&self, session_id: &str) -> Result<Option<SessionDetail>>;
+
+    /// Record end-user feedback on a trace
+    async fn add_feedback(&self, feedback: &TraceFeedback) -> Result<()>;
+
+    /// List the feedback left on a trace, oldest first
+    async fn list_feedback(&self, trace_id: &str) -> Result<Vec<TraceFeedback>>;
+
+    /// Get feedback counts with the cost and latency of the rated traces
+    /// started in a window
+    async fn get_feedback_metrics(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<FeedbackMetrics>;
+
+    /// Store full span payloads, replacing any already stored for the same
+    /// span, returning how many were written
+    async fn put_payloads(&self, payloads: &[SpanPayload]) -> Result<usize>;
+
+    /// Get the full payload stored for a span
+    async fn get_payload(&self, trace_id: &str, span_id: &str) -> Result<Option<SpanPayload>>;
+
+    /// Delete payloads captured before a time, returning how many were deleted
+    async fn prune_payloads(&self, before: DateTime<Utc>) -> Result<u64>;
+
+    /// List services with spans in a window, by name
+    async fn list_services(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<ServiceSummary>>;
+
+    /// Get metrics summary
+    async fn get_metrics_summary(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<MetricsSummaryResponse>;
+
+    /// Count the spans in a window and get the latest start time, a cheap
+    /// stand-in for the window's contents when validating cached responses
+    async fn span_watermark(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<(i64, Option<DateTime<Utc>>)>;
+
+    /// Compute one metric per group and time bucket for a custom query
+    async fn aggregate(&self, query: &AggregateQuery) -> Result<Vec<AggregateRow>>;
+
+    /// Get cost metrics grouped by model, service, operation, day or hour,
+    /// or by the value of span attribute `<key>` for `attr:<key>`
+    async fn get_cost_by_group(
+        &self,
+        service: Option<&str>,
+        group_by: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CostMetric>>;
+
+    /// Get token and cost efficiency per operation, most expensive first
+    async fn get_operation_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<OperationEfficiency>>;
+
+    /// Get token usage efficiency across the spans in a window
+    async fn get_token_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TokenEfficiency>;
+
+    /// Get the LLM / tool / overhead time split across traces
+    async fn get_time_breakdown(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TimeBreakdownMetric>;
+
+    /// Get hourly latency metrics
+    async fn get_latency_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<LatencyMetric>>;
+
+    /// Get hourly error metrics
+    async fn get_errors_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ErrorMetric>>;
+
+    /// Get error statistics for alerting
+    async fn get_error_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<ErrorStats>;
+
+    /// Get latency percentile for alerting
+    async fn get_latency_percentile(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<Option<f64>>;
+
+    /// Get a latency percentile and span count for one operation of a service
+    async fn get_operation_latency(
+        &self,
+        service: &str,
+        operation: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<(Option<f64>, i64)>;
+
+    /// Get average latency for alerting
+    async fn get_latency_avg(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>>;
+
+    /// Get total cost for alerting
+    async fn get_cost_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>>;
+
+    /// Get total token count for alerting
+    async fn get_token_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<i64>>;
+
+    /// Get span count for alerting
+    async fn get_span_count(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<i64>;
+
+    /// Get alerting metrics for each `window`-long window from `since` to
+    /// `until` in one pass, oldest first; windows without spans are left out
+    async fn get_window_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<Vec<WindowStats>>;
+
+    /// Storage usage and growth projection
+    async fn get_storage_report(&self, lookback_days: i64, projection_days: i64) -> Result<StorageReport>;
+}
+
+#[async_trait::async_trait]
+impl SpanStore for SpanRepository {
+    async fn insert_batch(&self, spans: &[Span]) -> Result<usize> {
+        SpanRepository::insert_batch(self, spans).await
+    }
+
+    async fn get_in_progress(&self, span_ids: &[String]) -> Result<Vec<Span>> {
+        SpanRepository::get_in_progress(self, span_ids).await
+    }
+
+    async fn close_stale_in_progress(&self, before: DateTime<Utc>) -> Result<u64> {
+        SpanRepository::close_stale_in_progress(self, before).await
+    }
+
+    async fn get_by_id(&self, id: &Uuid) -> Result<Option<Span>> {
+        SpanRepository::get_by_id(self, id).await
+    }
+
+    async fn get_by_trace_id(&self, trace_id: &str) -> Result<Vec<Span>> {
+        SpanRepository::get_by_trace_id(self, trace_id).await
+    }
+
+    async fn get_by_trace_ids(&self, trace_ids: &[String]) -> Result<Vec<Span>> {
+        SpanRepository::get_by_trace_ids(self, trace_ids).await
+    }
+
+    async fn search_in_trace(&self, trace_id: &str, query: &SpanQuery) -> Result<Vec<Span>> {
+        SpanRepository::search_in_trace(self, trace_id, query).await
+    }
+
+    async fn search_facets(
+        &self,
+        query: &SpanQuery,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<SearchFacets> {
+        SpanRepository::search_facets(self, query, since, until).await
+    }
+
+    async fn get_linking_trace_ids(&self, trace_id: &str) -> Result<Vec<String>> {
+        SpanRepository::get_linking_trace_ids(self, trace_id).await
+    }
+
+    async fn get_recent(&self, limit: i64) -> Result<Vec<Span>> {
+        SpanRepository::get_recent(self, limit).await
+    }
+
+    async fn search(
+        &self,
+        query: Option<&str>,
+        service: Option<&str>,
+        model: Option<&str>,
+        status: Option<&str>,
+        priority: Option<SpanPriority>,
+        attributes: &[(String, String)],
+        has_negative_feedback: Option<bool>,
+        min_duration: Option<f64>,
+        max_duration: Option<f64>,
+        min_cost: Option<f64>,
+        max_cost: Option<f64>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        sort_by: &str,
+        sort_desc: bool,
+        limit: i64,
+        offset: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<Span>, i64)> {
+        SpanRepository::search(
+            self, query, service, model, status, priority, attributes, has_negative_feedback,
+            min_duration, max_duration, min_cost, max_cost, since, until, sort_by, sort_desc, limit,
+            offset, cursor,
+        )
+        .await
+    }
+
+    async fn advanced_search(
+        &self,
+        filters: &[SearchFilter],
+        sort: Option<&SortConfig>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<Span>, i64)> {
+        SpanRepository::advanced_search(self, filters, sort, limit, offset).await
+    }
+
+    async fn text_search(
+        &self,
+        query: &str,
+        service: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<TextSearchHit>, i64)> {
+        SpanRepository::text_search(self, query, service, since, until, limit, offset).await
+    }
+
+    async fn list_traces(
+        &self,
+        service: Option<&str>,
+        status: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        sort: Option<&SortConfig>,
+        limit: i64,
+        cursor: Option<&PageCursor>,
+    ) -> Result<(Vec<TraceSummary>, Option<PageCursor>)> {
+        SpanRepository::list_traces(self, service, status, since, sort, limit, cursor).await
+    }
+
+    async fn find_traces(&self, search: &TraceSearch) -> Result<Vec<TraceSummary>> {
+        SpanRepository::find_traces(self, search).await
+    }
+
+    async fn get_trace_summary(&self, trace_id: &str) -> Result<Option<TraceSummary>> {
+        SpanRepository::get_trace_summary(self, trace_id).await
+    }
+
+    async fn get_trace_summaries(&self, trace_ids: &[String]) -> Result<Vec<TraceSummary>> {
+        SpanRepository::get_trace_summaries(self, trace_ids).await
+    }
+
+    async fn top_traces_by_cost(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<TraceSummary>> {
+        SpanRepository::top_traces_by_cost(self, service, since, until, limit).await
+    }
+
+    async fn delete_traces(&self, deletion: &TraceDeletion) -> Result<TraceDeletionResult> {
+        SpanRepository::delete_traces(self, deletion).await
+    }
+
+    async fn list_sessions(
+        &self,
+        user_id: Option<&str>,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<(Vec<SessionSummary>, i64)> {
+        SpanRepository::list_sessions(self, user_id, since, limit).await
+    }
+
+    async fn get_session(&self, session_id: &str) -> Result<Option<SessionDetail>> {
+        SpanRepository::get_session(self, session_id).await
+    }
+
+    async fn add_feedback(&self, feedback: &TraceFeedback) -> Result<()> {
+        SpanRepository::add_feedback(self, feedback).await
+    }
+
+    async fn list_feedback(&self, trace_id: &str) -> Result<Vec<TraceFeedback>> {
+        SpanRepository::list_feedback(self, trace_id).await
+    }
+
+    async fn get_feedback_metrics(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<FeedbackMetrics> {
+        SpanRepository::get_feedback_metrics(self, service, since, until).await
+    }
+
+    async fn put_payloads(&self, payloads: &[SpanPayload]) -> Result<usize> {
+        SpanRepository::put_payloads(self, payloads).await
+    }
+
+    async fn get_payload(&self, trace_id: &str, span_id: &str) -> Result<Option<SpanPayload>> {
+        SpanRepository::get_payload(self, trace_id, span_id).await
+    }
+
+    async fn prune_payloads(&self, before: DateTime<Utc>) -> Result<u64> {
+        SpanRepository::prune_payloads(self, before).await
+    }
+
+    async fn list_services(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<ServiceSummary>> {
+        SpanRepository::list_services(self, since, until).await
+    }
+
+    async fn get_metrics_summary(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<MetricsSummaryResponse> {
+        SpanRepository::get_metrics_summary(self, service, model, since, until).await
+    }
+
+    async fn span_watermark(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<(i64, Option<DateTime<Utc>>)> {
+        SpanRepository::span_watermark(self, service, model, since, until).await
+    }
+
+    async fn aggregate(&self, query: &AggregateQuery) -> Result<Vec<AggregateRow>> {
+        SpanRepository::aggregate(self, query).await
+    }
+
+    async fn get_cost_by_group(
+        &self,
+        service: Option<&str>,
+        group_by: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<CostMetric>> {
+        SpanRepository::get_cost_by_group(self, service, group_by, since, until).await
+    }
+
+    async fn get_operation_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<OperationEfficiency>> {
+        SpanRepository::get_operation_efficiency(self, service, model, since, until).await
+    }
+
+    async fn get_token_efficiency(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TokenEfficiency> {
+        SpanRepository::get_token_efficiency(self, service, model, since, until).await
+    }
+
+    async fn get_time_breakdown(
+        &self,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<TimeBreakdownMetric> {
+        SpanRepository::get_time_breakdown(self, service, since, until).await
+    }
+
+    async fn get_latency_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<LatencyMetric>> {
+        SpanRepository::get_latency_over_time(self, service, model, since, until).await
+    }
+
+    async fn get_errors_over_time(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<ErrorMetric>> {
+        SpanRepository::get_errors_over_time(self, service, model, since, until).await
+    }
+
+    async fn get_error_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<ErrorStats> {
+        SpanRepository::get_error_stats(self, service, model, since, until).await
+    }
+
+    async fn get_latency_percentile(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<Option<f64>> {
+        SpanRepository::get_latency_percentile(self, service, model, since, until, percentile).await
+    }
+
+    async fn get_operation_latency(
+        &self,
+        service: &str,
+        operation: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        percentile: f64,
+    ) -> Result<(Option<f64>, i64)> {
+        SpanRepository::get_operation_latency(self, service, operation, since, until, percentile).await
+    }
+
+    async fn get_latency_avg(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        SpanRepository::get_latency_avg(self, service, model, since, until).await
+    }
+
+    async fn get_cost_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<f64>> {
+        SpanRepository::get_cost_sum(self, service, model, since, until).await
+    }
+
+    async fn get_token_sum(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<Option<i64>> {
+        SpanRepository::get_token_sum(self, service, model, since, until).await
+    }
+
+    async fn get_span_count(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Result<i64> {
+        SpanRepository::get_span_count(self, service, model, since, until).await
+    }
+
+    async fn get_window_stats(
+        &self,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        window: Duration,
+    ) -> Result<Vec<WindowStats>> {
+        SpanRepository::get_window_stats(self, service, model, since, until, window).await
+    }
+
+    async fn get_storage_report(&self, lookback_days: i64, projection_days: i64) -> Result<StorageReport> {
+        SpanRepository::get_storage_report(self, lookback_days, projection_days).await
+    }
+}
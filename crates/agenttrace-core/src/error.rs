@@ -40,6 +40,10 @@ pub enum Error {
     #[error("Authentication failed: {0}")]
     Auth(String),
 
+    /// A query was refused or cancelled for exceeding the configured limits
+    #[error("{message}")]
+    QueryLimit { message: String, hint: String },
+
     /// Rate limit exceeded
     #[error("Rate limit exceeded")]
     RateLimit,
@@ -75,6 +79,10 @@ pub enum Error {
     /// HTTP client error
     #[error("HTTP error: {0}")]
     Http(String),
+
+    /// Archive storage or encoding error
+    #[error("Archive error: {0}")]
+    Archive(String),
 }
 
 impl Error {
@@ -96,6 +104,14 @@ impl Error {
         Self::Config(msg.into())
     }
 
+    /// Create a query limit error with a hint on how to narrow the query
+    pub fn query_limit(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::QueryLimit {
+            message: message.into(),
+            hint: hint.into(),
+        }
+    }
+
     /// Create an internal error
     pub fn internal(msg: impl Into<String>) -> Self {
         Self::Internal(msg.into())
@@ -0,0 +1,157 @@
+//! File encodings for bulk span exports
+//!
+//! CSV carries the fields finance and spreadsheet users ask for, one row per
+//! span. JSONL keeps every span field. Parquet uses the archive's layout, so
+//! exported files can be queried alongside archived partitions.
+
+use crate::archive;
+use crate::error::{Error, Result};
+use crate::models::{ExportFileFormat, Span};
+
+/// CSV columns, in order
+const CSV_COLUMNS: &[&str] = &[
+    "trace_id",
+    "span_id",
+    "parent_span_id",
+    "service_name",
+    "operation_name",
+    "status",
+    "started_at",
+    "duration_ms",
+    "model_provider",
+    "model_name",
+    "tokens_in",
+    "tokens_out",
+    "cost_usd",
+];
+
+/// Encode spans in an export file format
+pub fn encode_spans(spans: &[Span], format: ExportFileFormat) -> Result<Vec<u8>> {
+    match format {
+        ExportFileFormat::Csv => Ok(to_csv(spans).into_bytes()),
+        ExportFileFormat::Jsonl => to_jsonl(spans),
+        ExportFileFormat::Parquet => archive::encode(spans),
+    }
+}
+
+fn to_csv(spans: &[Span]) -> String {
+    let mut out = CSV_COLUMNS.join(",");
+    out.push('\n');
+
+    for span in spans {
+        let status = span.status.as_str().to_string();
+        let fields = [
+            span.trace_id.clone(),
+            span.span_id.clone(),
+            span.parent_span_id.clone().unwrap_or_default(),
+            span.service_name.clone(),
+            span.operation_name.clone(),
+            status,
+            span.started_at.to_rfc3339(),
+            optional(span.duration_ms),
+            span.model_provider.clone().unwrap_or_default(),
+            span.model_name.clone().unwrap_or_default(),
+            optional(span.tokens_in),
+            optional(span.tokens_out),
+            optional(span.cost_usd),
+        ];
+
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+fn to_jsonl(spans: &[Span]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for span in spans {
+        serde_json::to_writer(&mut out, span).map_err(|e| Error::Serialization(e.to_string()))?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Quote a field if it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SpanKind, SpanPriority, SpanStatus};
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn create_test_span(operation: &str) -> Span {
+        Span {
+            id: Uuid::new_v4(),
+            span_id: "s1".to_string(),
+            trace_id: "t1".to_string(),
+            parent_span_id: None,
+            session_id: None,
+            user_id: None,
+            operation_name: operation.to_string(),
+            service_name: "planner".to_string(),
+            span_kind: SpanKind::Internal,
+            started_at: Utc::now(),
+            ended_at: None,
+            duration_ms: Some(250.0),
+            status: SpanStatus::Ok,
+            status_message: None,
+            model_name: Some("gpt-4o".to_string()),
+            model_provider: Some("openai".to_string()),
+            tokens_in: Some(100),
+            tokens_cached_in: None,
+            tokens_out: Some(20),
+            tokens_reasoning: None,
+            cost_usd: Some(0.25),
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: None,
+            completion_preview: None,
+            attributes: serde_json::json!({}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::Normal,
+        }
+    }
+
+    #[test]
+    fn test_csv_quotes_fields() {
+        assert_eq!(csv_field("planner"), "planner");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_encodings() {
+        let spans = vec![create_test_span("call, llm")];
+
+        let csv = String::from_utf8(encode_spans(&spans, ExportFileFormat::Csv).unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_COLUMNS.join(","));
+        assert!(lines[1].contains("\"call, llm\""));
+        assert!(lines[1].ends_with(",0.25"));
+
+        let jsonl = encode_spans(&spans, ExportFileFormat::Jsonl).unwrap();
+        let parsed: Span = serde_json::from_slice(jsonl.strip_suffix(b"\n").unwrap()).unwrap();
+        assert_eq!(parsed.operation_name, "call, llm");
+
+        let parquet = encode_spans(&spans, ExportFileFormat::Parquet).unwrap();
+        assert_eq!(archive::decode(parquet.into()).unwrap().len(), 1);
+    }
+}
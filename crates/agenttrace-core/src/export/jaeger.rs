@@ -0,0 +1,276 @@
+//! Jaeger JSON trace format
+//!
+//! Spans keep their IDs; LLM fields (model, tokens, cost, previews) become
+//! span tags alongside the span's own attributes, span events become logs
+//! and span links become `FOLLOWS_FROM` references.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::models::{Span, SpanKind, SpanStatus, SESSION_ID_ATTRIBUTE, USER_ID_ATTRIBUTE};
+
+/// A trace as returned by the Jaeger query API
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JaegerTrace {
+    /// Trace ID
+    #[serde(rename = "traceID")]
+    pub trace_id: String,
+    /// Spans in the trace
+    pub spans: Vec<JaegerSpan>,
+    /// Processes referenced by the spans, keyed by process ID
+    pub processes: HashMap<String, JaegerProcess>,
+    /// Jaeger warnings; never set
+    pub warnings: Option<Vec<String>>,
+}
+
+/// A span in a Jaeger trace
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JaegerSpan {
+    /// Trace ID
+    #[serde(rename = "traceID")]
+    pub trace_id: String,
+    /// Span ID
+    #[serde(rename = "spanID")]
+    pub span_id: String,
+    /// Span name
+    pub operation_name: String,
+    /// Parent (`CHILD_OF`) and link (`FOLLOWS_FROM`) references
+    pub references: Vec<JaegerReference>,
+    /// Jaeger span flags; always 1 (sampled)
+    pub flags: u32,
+    /// Microseconds since the Unix epoch
+    pub start_time: i64,
+    /// Microseconds
+    pub duration: i64,
+    /// Span attributes and LLM fields
+    pub tags: Vec<JaegerTag>,
+    /// Span events
+    pub logs: Vec<JaegerLog>,
+    /// Key into the trace's `processes`
+    #[serde(rename = "processID")]
+    pub process_id: String,
+    /// Jaeger warnings; never set
+    pub warnings: Option<Vec<String>>,
+}
+
+/// A reference from one span to another
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JaegerReference {
+    /// `CHILD_OF` or `FOLLOWS_FROM`
+    pub ref_type: &'static str,
+    /// Trace ID of the referenced span
+    #[serde(rename = "traceID")]
+    pub trace_id: String,
+    /// ID of the referenced span
+    #[serde(rename = "spanID")]
+    pub span_id: String,
+}
+
+/// A typed key/value pair
+#[derive(Debug, Serialize)]
+pub struct JaegerTag {
+    /// Tag name
+    pub key: String,
+    /// `string`, `bool`, `int64` or `float64`
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// Tag value
+    pub value: Value,
+}
+
+impl JaegerTag {
+    fn new(key: impl Into<String>, value: impl Into<Value>) -> Self {
+        let value = value.into();
+        let kind = match &value {
+            Value::Bool(_) => "bool",
+            Value::Number(n) if n.is_i64() || n.is_u64() => "int64",
+            Value::Number(_) => "float64",
+            _ => "string",
+        };
+        let value = match value {
+            Value::Bool(_) | Value::Number(_) | Value::String(_) => value,
+            other => Value::String(other.to_string()),
+        };
+        Self { key: key.into(), kind, value }
+    }
+}
+
+/// A timestamped log entry, converted from a span event
+#[derive(Debug, Serialize)]
+pub struct JaegerLog {
+    /// Microseconds since the Unix epoch
+    pub timestamp: i64,
+    /// Event name and attributes
+    pub fields: Vec<JaegerTag>,
+}
+
+/// The service that emitted a set of spans
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JaegerProcess {
+    /// Service name
+    pub service_name: String,
+    /// Process-level tags
+    pub tags: Vec<JaegerTag>,
+}
+
+fn micros(at: DateTime<Utc>) -> i64 {
+    at.timestamp_micros()
+}
+
+/// Flatten JSON attributes into tags, joining nested keys with dots
+fn push_attribute_tags(tags: &mut Vec<JaegerTag>, prefix: &str, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                push_attribute_tags(tags, &key, value);
+            }
+        }
+        Value::Null => {}
+        other if !prefix.is_empty() => tags.push(JaegerTag::new(prefix, other.clone())),
+        _ => {}
+    }
+}
+
+fn span_tags(span: &Span) -> Vec<JaegerTag> {
+    let kind = match span.span_kind {
+        SpanKind::Internal => "internal",
+        SpanKind::Client => "client",
+        SpanKind::Server => "server",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+    };
+
+    let mut tags = vec![JaegerTag::new("span.kind", kind)];
+    match span.status {
+        SpanStatus::Error => {
+            tags.push(JaegerTag::new("error", true));
+            tags.push(JaegerTag::new("otel.status_code", "ERROR"));
+        }
+        SpanStatus::Ok => tags.push(JaegerTag::new("otel.status_code", "OK")),
+        SpanStatus::Unset | SpanStatus::InProgress => {}
+    }
+    if let Some(message) = &span.status_message {
+        tags.push(JaegerTag::new("otel.status_description", message.as_str()));
+    }
+
+    let optional: [(&str, Option<Value>); 11] = [
+        (SESSION_ID_ATTRIBUTE, span.session_id.clone().map(Value::from)),
+        (USER_ID_ATTRIBUTE, span.user_id.clone().map(Value::from)),
+        ("gen_ai.request.model", span.model_name.clone().map(Value::from)),
+        ("gen_ai.system", span.model_provider.clone().map(Value::from)),
+        ("gen_ai.usage.input_tokens", span.tokens_in.map(Value::from)),
+        ("gen_ai.usage.cache_read.input_tokens", span.tokens_cached_in.map(Value::from)),
+        ("gen_ai.usage.output_tokens", span.tokens_out.map(Value::from)),
+        ("agenttrace.cost_usd", span.cost_usd.map(Value::from)),
+        ("agenttrace.tool_name", span.tool_name.clone().map(Value::from)),
+        ("agenttrace.prompt_preview", span.prompt_preview.clone().map(Value::from)),
+        ("agenttrace.completion_preview", span.completion_preview.clone().map(Value::from)),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            tags.push(JaegerTag::new(key, value));
+        }
+    }
+
+    push_attribute_tags(&mut tags, "", &span.attributes);
+    tags
+}
+
+/// Translate a trace's spans into a Jaeger trace, one process per service
+pub fn to_jaeger_trace(trace_id: &str, spans: &[Span]) -> JaegerTrace {
+    let mut processes: HashMap<String, JaegerProcess> = HashMap::new();
+    let mut process_ids: HashMap<&str, String> = HashMap::new();
+
+    let spans = spans
+        .iter()
+        .map(|span| {
+            let next_id = format!("p{}", process_ids.len() + 1);
+            let process_id = process_ids
+                .entry(span.service_name.as_str())
+                .or_insert_with(|| {
+                    processes.insert(
+                        next_id.clone(),
+                        JaegerProcess { service_name: span.service_name.clone(), tags: Vec::new() },
+                    );
+                    next_id
+                })
+                .clone();
+
+            let mut references: Vec<JaegerReference> = span
+                .parent_span_id
+                .iter()
+                .map(|parent| JaegerReference {
+                    ref_type: "CHILD_OF",
+                    trace_id: span.trace_id.clone(),
+                    span_id: parent.clone(),
+                })
+                .collect();
+            references.extend(span.links.iter().map(|link| JaegerReference {
+                ref_type: "FOLLOWS_FROM",
+                trace_id: link.trace_id.clone(),
+                span_id: link.span_id.clone(),
+            }));
+
+            let logs = span
+                .events
+                .iter()
+                .map(|event| {
+                    let mut fields = vec![JaegerTag::new("event", event.name.as_str())];
+                    push_attribute_tags(&mut fields, "", &event.attributes);
+                    JaegerLog { timestamp: micros(event.timestamp), fields }
+                })
+                .collect();
+
+            JaegerSpan {
+                trace_id: span.trace_id.clone(),
+                span_id: span.span_id.clone(),
+                operation_name: span.operation_name.clone(),
+                references,
+                flags: 1,
+                start_time: micros(span.started_at),
+                duration: span.duration_ms.map(|ms| (ms * 1000.0) as i64).unwrap_or(0),
+                tags: span_tags(span),
+                logs,
+                process_id,
+                warnings: None,
+            }
+        })
+        .collect();
+
+    JaegerTrace {
+        trace_id: trace_id.to_string(),
+        spans,
+        processes,
+        warnings: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_tags_are_flattened_and_typed() {
+        let mut tags = Vec::new();
+        push_attribute_tags(
+            &mut tags,
+            "",
+            &serde_json::json!({"user": {"id": 42, "tier": "pro"}, "cached": true, "score": 0.5, "skip": null}),
+        );
+
+        let find = |key: &str| tags.iter().find(|t| t.key == key).map(|t| (t.kind, t.value.clone()));
+        assert_eq!(find("user.id"), Some(("int64", serde_json::json!(42))));
+        assert_eq!(find("user.tier"), Some(("string", serde_json::json!("pro"))));
+        assert_eq!(find("cached"), Some(("bool", serde_json::json!(true))));
+        assert_eq!(find("score"), Some(("float64", serde_json::json!(0.5))));
+        assert_eq!(find("skip"), None);
+    }
+}
@@ -0,0 +1,67 @@
+//! Trace export formats
+//!
+//! Converts a trace's spans into formats other tools can import: the native
+//! JSON form, OTLP (JSON or protobuf) and Jaeger JSON. Used by both
+//! `agenttrace traces export` and `GET /api/v1/traces/{id}/export`.
+//! [`bulk`] encodes the CSV, JSONL and Parquet files of bulk export jobs.
+
+pub mod bulk;
+pub mod jaeger;
+pub mod otlp;
+
+use crate::error::{Error, Result};
+use crate::models::Span;
+
+/// Supported export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// AgentTrace's own span JSON
+    Json,
+    /// OTLP/JSON `ExportTraceServiceRequest`
+    OtlpJson,
+    /// OTLP protobuf `ExportTraceServiceRequest`
+    OtlpProtobuf,
+    /// Jaeger JSON, as returned by Jaeger's query API
+    Jaeger,
+}
+
+impl ExportFormat {
+    /// Parse a format name; `otlp` means OTLP/JSON
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "otlp" | "otlp-json" => Ok(Self::OtlpJson),
+            "otlp-proto" | "otlp-protobuf" => Ok(Self::OtlpProtobuf),
+            "jaeger" => Ok(Self::Jaeger),
+            other => Err(Error::validation(format!(
+                "Unknown export format '{}': expected json, otlp, otlp-proto or jaeger",
+                other
+            ))),
+        }
+    }
+
+    /// MIME type of the exported document
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::OtlpProtobuf => "application/x-protobuf",
+            _ => "application/json",
+        }
+    }
+
+    /// Whether the exported document is binary
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Self::OtlpProtobuf)
+    }
+}
+
+/// Export a trace's spans
+pub fn export_trace(trace_id: &str, spans: &[Span], format: ExportFormat) -> Result<Vec<u8>> {
+    let document = match format {
+        ExportFormat::OtlpProtobuf => return Ok(otlp::to_otlp_protobuf(spans)),
+        ExportFormat::Json => serde_json::json!({ "trace_id": trace_id, "spans": spans }),
+        ExportFormat::OtlpJson => otlp::to_otlp_json(spans),
+        ExportFormat::Jaeger => serde_json::json!({ "data": [jaeger::to_jaeger_trace(trace_id, spans)] }),
+    };
+
+    serde_json::to_vec_pretty(&document).map_err(|e| Error::Serialization(e.to_string()))
+}
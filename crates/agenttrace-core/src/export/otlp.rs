@@ -0,0 +1,443 @@
+//! OTLP trace format
+//!
+//! Traces are encoded as an OTLP `ExportTraceServiceRequest`, either as
+//! protobuf or in the OTLP/JSON mapping (hex IDs, camelCase fields, 64-bit
+//! integers as strings). Spans are grouped into one resource per service.
+//!
+//! OTLP needs 16-byte trace IDs and 8-byte span IDs. IDs that are already
+//! hex of that length are used as-is; others are derived from a SHA-256 of
+//! the original ID, which is kept in the `agenttrace.trace_id` and
+//! `agenttrace.span_id` attributes.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::models::{Span, SpanKind, SpanStatus, SESSION_ID_ATTRIBUTE, USER_ID_ATTRIBUTE};
+
+/// Instrumentation scope recorded on exported spans
+const SCOPE_NAME: &str = "agenttrace";
+
+/// Messages from `opentelemetry/proto/trace/v1/trace.proto` and
+/// `opentelemetry/proto/common/v1/common.proto`, defined by hand as the
+/// collector's gRPC types are
+pub(crate) mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ExportTraceServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub resource_spans: Vec<ResourceSpans>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResourceSpans {
+        #[prost(message, optional, tag = "1")]
+        pub resource: Option<Resource>,
+        #[prost(message, repeated, tag = "2")]
+        pub scope_spans: Vec<ScopeSpans>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Resource {
+        #[prost(message, repeated, tag = "1")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ScopeSpans {
+        #[prost(message, optional, tag = "1")]
+        pub scope: Option<InstrumentationScope>,
+        #[prost(message, repeated, tag = "2")]
+        pub spans: Vec<Span>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InstrumentationScope {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub version: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Span {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub span_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "4")]
+        pub parent_span_id: Vec<u8>,
+        #[prost(string, tag = "5")]
+        pub name: String,
+        #[prost(int32, tag = "6")]
+        pub kind: i32,
+        #[prost(fixed64, tag = "7")]
+        pub start_time_unix_nano: u64,
+        #[prost(fixed64, tag = "8")]
+        pub end_time_unix_nano: u64,
+        #[prost(message, repeated, tag = "9")]
+        pub attributes: Vec<KeyValue>,
+        #[prost(message, repeated, tag = "11")]
+        pub events: Vec<Event>,
+        #[prost(message, repeated, tag = "13")]
+        pub links: Vec<Link>,
+        #[prost(message, optional, tag = "15")]
+        pub status: Option<Status>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Event {
+        #[prost(fixed64, tag = "1")]
+        pub time_unix_nano: u64,
+        #[prost(string, tag = "2")]
+        pub name: String,
+        #[prost(message, repeated, tag = "3")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Link {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub span_id: Vec<u8>,
+        #[prost(message, repeated, tag = "4")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Status {
+        #[prost(string, tag = "2")]
+        pub message: String,
+        #[prost(int32, tag = "3")]
+        pub code: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyValue {
+        #[prost(string, tag = "1")]
+        pub key: String,
+        #[prost(message, optional, tag = "2")]
+        pub value: Option<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnyValue {
+        #[prost(oneof = "any_value::Value", tags = "1, 2, 3, 4, 5, 6")]
+        pub value: Option<any_value::Value>,
+    }
+
+    pub mod any_value {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        pub enum Value {
+            #[prost(string, tag = "1")]
+            StringValue(String),
+            #[prost(bool, tag = "2")]
+            BoolValue(bool),
+            #[prost(int64, tag = "3")]
+            IntValue(i64),
+            #[prost(double, tag = "4")]
+            DoubleValue(f64),
+            #[prost(message, tag = "5")]
+            ArrayValue(super::ArrayValue),
+            #[prost(message, tag = "6")]
+            KvlistValue(super::KeyValueList),
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ArrayValue {
+        #[prost(message, repeated, tag = "1")]
+        pub values: Vec<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyValueList {
+        #[prost(message, repeated, tag = "1")]
+        pub values: Vec<KeyValue>,
+    }
+}
+
+use proto::any_value::Value as AnyValueKind;
+
+/// `SPAN_KIND_*` values
+fn span_kind(kind: SpanKind) -> i32 {
+    match kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+        SpanKind::Producer => 4,
+        SpanKind::Consumer => 5,
+    }
+}
+
+/// `STATUS_CODE_*` values
+fn status_code(status: SpanStatus) -> i32 {
+    match status {
+        SpanStatus::Unset | SpanStatus::InProgress => 0,
+        SpanStatus::Ok => 1,
+        SpanStatus::Error => 2,
+    }
+}
+
+/// Binary ID of `len` bytes: the ID itself if it is hex of that length,
+/// otherwise a hash of it. Returns whether the ID had to be derived.
+fn otlp_id(id: &str, len: usize) -> (Vec<u8>, bool) {
+    match hex::decode(id) {
+        Ok(bytes) if bytes.len() == len => (bytes, false),
+        _ => (Sha256::digest(id.as_bytes())[..len].to_vec(), true),
+    }
+}
+
+fn nanos(at: DateTime<Utc>) -> u64 {
+    at.timestamp_nanos_opt().unwrap_or_default().max(0) as u64
+}
+
+fn any_value(value: &Value) -> Option<proto::AnyValue> {
+    let value = match value {
+        Value::Null => return None,
+        Value::Bool(b) => AnyValueKind::BoolValue(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => AnyValueKind::IntValue(i),
+            None => AnyValueKind::DoubleValue(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => AnyValueKind::StringValue(s.clone()),
+        Value::Array(items) => AnyValueKind::ArrayValue(proto::ArrayValue {
+            values: items.iter().filter_map(any_value).collect(),
+        }),
+        Value::Object(map) => AnyValueKind::KvlistValue(proto::KeyValueList {
+            values: map.iter().filter_map(|(k, v)| key_value(k, v)).collect(),
+        }),
+    };
+    Some(proto::AnyValue { value: Some(value) })
+}
+
+fn key_value(key: &str, value: &Value) -> Option<proto::KeyValue> {
+    any_value(value).map(|value| proto::KeyValue { key: key.to_string(), value: Some(value) })
+}
+
+/// Top-level attributes of a JSON object as key/values
+fn attributes(value: &Value) -> Vec<proto::KeyValue> {
+    value
+        .as_object()
+        .map(|map| map.iter().filter_map(|(k, v)| key_value(k, v)).collect())
+        .unwrap_or_default()
+}
+
+fn to_otlp_span(span: &Span) -> proto::Span {
+    let (trace_id, derived_trace) = otlp_id(&span.trace_id, 16);
+    let (span_id, derived_span) = otlp_id(&span.span_id, 8);
+    let parent_span_id = span
+        .parent_span_id
+        .as_deref()
+        .map(|parent| otlp_id(parent, 8).0)
+        .unwrap_or_default();
+
+    let mut attrs = attributes(&span.attributes);
+    let llm_fields: [(&str, Option<Value>); 13] = [
+        (SESSION_ID_ATTRIBUTE, span.session_id.clone().map(Value::from)),
+        (USER_ID_ATTRIBUTE, span.user_id.clone().map(Value::from)),
+        ("gen_ai.request.model", span.model_name.clone().map(Value::from)),
+        ("gen_ai.system", span.model_provider.clone().map(Value::from)),
+        ("gen_ai.usage.input_tokens", span.tokens_in.map(Value::from)),
+        ("gen_ai.usage.cache_read.input_tokens", span.tokens_cached_in.map(Value::from)),
+        ("gen_ai.usage.output_tokens", span.tokens_out.map(Value::from)),
+        ("agenttrace.cost_usd", span.cost_usd.map(Value::from)),
+        ("agenttrace.tool_name", span.tool_name.clone().map(Value::from)),
+        ("agenttrace.prompt_preview", span.prompt_preview.clone().map(Value::from)),
+        ("agenttrace.completion_preview", span.completion_preview.clone().map(Value::from)),
+        ("agenttrace.trace_id", derived_trace.then(|| Value::from(span.trace_id.clone()))),
+        ("agenttrace.span_id", derived_span.then(|| Value::from(span.span_id.clone()))),
+    ];
+    attrs.extend(llm_fields.iter().filter_map(|(key, value)| key_value(key, value.as_ref()?)));
+
+    let end = span
+        .ended_at
+        .or_else(|| {
+            span.duration_ms
+                .map(|ms| span.started_at + chrono::Duration::microseconds((ms * 1000.0) as i64))
+        })
+        .unwrap_or(span.started_at);
+
+    proto::Span {
+        trace_id,
+        span_id,
+        parent_span_id,
+        name: span.operation_name.clone(),
+        kind: span_kind(span.span_kind),
+        start_time_unix_nano: nanos(span.started_at),
+        end_time_unix_nano: nanos(end),
+        attributes: attrs,
+        events: span
+            .events
+            .iter()
+            .map(|event| proto::Event {
+                time_unix_nano: nanos(event.timestamp),
+                name: event.name.clone(),
+                attributes: attributes(&event.attributes),
+            })
+            .collect(),
+        links: span
+            .links
+            .iter()
+            .map(|link| proto::Link {
+                trace_id: otlp_id(&link.trace_id, 16).0,
+                span_id: otlp_id(&link.span_id, 8).0,
+                attributes: attributes(&link.attributes),
+            })
+            .collect(),
+        status: Some(proto::Status {
+            message: span.status_message.clone().unwrap_or_default(),
+            code: status_code(span.status),
+        }),
+    }
+}
+
+/// Build an OTLP export request for a trace's spans, one resource per service
+pub(crate) fn to_otlp_request(spans: &[Span]) -> proto::ExportTraceServiceRequest {
+    let mut by_service: BTreeMap<&str, Vec<proto::Span>> = BTreeMap::new();
+    for span in spans {
+        by_service.entry(span.service_name.as_str()).or_default().push(to_otlp_span(span));
+    }
+
+    let resource_spans = by_service
+        .into_iter()
+        .map(|(service, spans)| proto::ResourceSpans {
+            resource: Some(proto::Resource {
+                attributes: key_value("service.name", &Value::from(service)).into_iter().collect(),
+            }),
+            scope_spans: vec![proto::ScopeSpans {
+                scope: Some(proto::InstrumentationScope {
+                    name: SCOPE_NAME.to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                }),
+                spans,
+            }],
+        })
+        .collect();
+
+    proto::ExportTraceServiceRequest { resource_spans }
+}
+
+/// Encode a trace's spans as an OTLP protobuf message
+pub fn to_otlp_protobuf(spans: &[Span]) -> Vec<u8> {
+    prost::Message::encode_to_vec(&to_otlp_request(spans))
+}
+
+fn any_value_json(value: &proto::AnyValue) -> Value {
+    match &value.value {
+        Some(AnyValueKind::StringValue(s)) => json!({ "stringValue": s }),
+        Some(AnyValueKind::BoolValue(b)) => json!({ "boolValue": b }),
+        Some(AnyValueKind::IntValue(i)) => json!({ "intValue": i.to_string() }),
+        Some(AnyValueKind::DoubleValue(d)) => json!({ "doubleValue": d }),
+        Some(AnyValueKind::ArrayValue(array)) => {
+            json!({ "arrayValue": { "values": array.values.iter().map(any_value_json).collect::<Vec<_>>() } })
+        }
+        Some(AnyValueKind::KvlistValue(list)) => {
+            json!({ "kvlistValue": { "values": key_values_json(&list.values) } })
+        }
+        None => json!({}),
+    }
+}
+
+fn key_values_json(values: &[proto::KeyValue]) -> Vec<Value> {
+    values
+        .iter()
+        .map(|kv| {
+            json!({
+                "key": kv.key,
+                "value": kv.value.as_ref().map(any_value_json).unwrap_or_else(|| json!({})),
+            })
+        })
+        .collect()
+}
+
+fn span_json(span: &proto::Span) -> Value {
+    let mut value = json!({
+        "traceId": hex::encode(&span.trace_id),
+        "spanId": hex::encode(&span.span_id),
+        "name": span.name,
+        "kind": span.kind,
+        "startTimeUnixNano": span.start_time_unix_nano.to_string(),
+        "endTimeUnixNano": span.end_time_unix_nano.to_string(),
+        "attributes": key_values_json(&span.attributes),
+        "events": span.events.iter().map(|event| json!({
+            "timeUnixNano": event.time_unix_nano.to_string(),
+            "name": event.name,
+            "attributes": key_values_json(&event.attributes),
+        })).collect::<Vec<_>>(),
+        "links": span.links.iter().map(|link| json!({
+            "traceId": hex::encode(&link.trace_id),
+            "spanId": hex::encode(&link.span_id),
+            "attributes": key_values_json(&link.attributes),
+        })).collect::<Vec<_>>(),
+        "status": span.status.as_ref().map(|status| json!({
+            "message": status.message,
+            "code": status.code,
+        })),
+    });
+
+    if !span.parent_span_id.is_empty() {
+        value["parentSpanId"] = json!(hex::encode(&span.parent_span_id));
+    }
+    value
+}
+
+/// Encode a trace's spans in the OTLP/JSON mapping
+pub fn to_otlp_json(spans: &[Span]) -> Value {
+    request_json(&to_otlp_request(spans))
+}
+
+/// An export request in the OTLP/JSON mapping
+pub(crate) fn request_json(request: &proto::ExportTraceServiceRequest) -> Value {
+    let resource_spans: Vec<Value> = request
+        .resource_spans
+        .iter()
+        .map(|rs| {
+            json!({
+                "resource": {
+                    "attributes": key_values_json(rs.resource.as_ref().map(|r| r.attributes.as_slice()).unwrap_or_default()),
+                },
+                "scopeSpans": rs.scope_spans.iter().map(|ss| json!({
+                    "scope": ss.scope.as_ref().map(|scope| json!({
+                        "name": scope.name,
+                        "version": scope.version,
+                    })),
+                    "spans": ss.spans.iter().map(span_json).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({ "resourceSpans": resource_spans })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_kept_or_derived() {
+        let (id, derived) = otlp_id("4bf92f3577b34da6a3ce929d0e0e4736", 16);
+        assert_eq!(hex::encode(id), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(!derived);
+
+        let (id, derived) = otlp_id("trace-1", 16);
+        assert_eq!(id.len(), 16);
+        assert!(derived);
+        assert_eq!(id, otlp_id("trace-1", 16).0);
+    }
+
+    #[test]
+    fn test_attribute_values_map_to_any_value() {
+        let attrs = attributes(&json!({"n": 3, "f": 1.5, "ok": true, "tags": ["a"], "skip": null}));
+        let find = |key: &str| attrs.iter().find(|kv| kv.key == key).and_then(|kv| kv.value.clone());
+
+        assert_eq!(find("n").and_then(|v| v.value), Some(AnyValueKind::IntValue(3)));
+        assert_eq!(find("f").and_then(|v| v.value), Some(AnyValueKind::DoubleValue(1.5)));
+        assert_eq!(find("ok").and_then(|v| v.value), Some(AnyValueKind::BoolValue(true)));
+        assert!(matches!(find("tags").and_then(|v| v.value), Some(AnyValueKind::ArrayValue(_))));
+        assert!(find("skip").is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! Datadog Agent trace format
+//!
+//! The Agent's `/v0.3/traces` endpoint takes an array of traces, each an
+//! array of spans with 64-bit integer IDs. String tags go in `meta` and
+//! numeric ones in `metrics`; the high half of a 128-bit trace ID is kept
+//! in the `_dd.p.tid` tag so Datadog shows the same trace ID.
+
+use std::collections::BTreeMap;
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::span_tags;
+use crate::models::{Span, SpanStatus};
+
+/// Build the request for a batch of traces from [`to_traces`]
+pub(super) fn request(client: &Client, url: &str, api_key: Option<&str>, traces: &Value) -> RequestBuilder {
+    // The Agent counts traces, not spans, in its stats
+    let trace_count = traces.as_array().map_or(0, Vec::len);
+    let mut builder = client
+        .put(url)
+        .header("X-Datadog-Trace-Count", trace_count.to_string())
+        .header("Datadog-Meta-Lang", "agenttrace");
+    if let Some(api_key) = api_key {
+        builder = builder.header("DD-API-KEY", api_key);
+    }
+    builder
+}
+
+/// Group spans into Datadog traces
+pub(super) fn to_traces(spans: &[&Span]) -> Value {
+    let mut traces: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for span in spans {
+        traces.entry(span.trace_id.as_str()).or_default().push(to_span(span));
+    }
+    Value::Array(traces.into_values().map(Value::Array).collect())
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_span(span: &Span) -> Value {
+    let mut meta = Map::new();
+    let mut metrics = Map::new();
+    for (key, value) in span_tags(span) {
+        match value {
+            Value::Number(n) => {
+                metrics.insert(key, n.into());
+            }
+            Value::String(s) => {
+                meta.insert(key, s.into());
+            }
+            other => {
+                meta.insert(key, other.to_string().into());
+            }
+        }
+    }
+    if let Some(message) = span
+        .status_message
+        .as_ref()
+        .filter(|_| span.status == SpanStatus::Error)
+    {
+        meta.insert("error.message".to_string(), message.clone().into());
+    }
+    if let Some(high) = trace_id_high(&span.trace_id) {
+        meta.insert("_dd.p.tid".to_string(), high.into());
+    }
+
+    let duration_ms = span
+        .duration_ms
+        .or_else(|| {
+            span.ended_at
+                .map(|end| (end - span.started_at).num_milliseconds() as f64)
+        })
+        .unwrap_or(0.0)
+        .max(0.0);
+
+    json!({
+        "trace_id": id64(&span.trace_id),
+        "span_id": id64(&span.span_id),
+        "parent_id": span.parent_span_id.as_deref().map_or(0, id64),
+        "name": span.operation_name,
+        "resource": span.operation_name,
+        "service": span.service_name,
+        "type": if span.model_name.is_some() { "llm" } else { "custom" },
+        "start": span.started_at.timestamp_nanos_opt().unwrap_or_default(),
+        "duration": (duration_ms * 1_000_000.0) as i64,
+        "error": i32::from(span.status == SpanStatus::Error),
+        "meta": meta,
+        "metrics": metrics,
+    })
+}
+
+/// A hex span or trace ID as the 64-bit integer Datadog expects:
+/// its low 64 bits, or for IDs that aren't hex, a stable hash of them
+fn id64(id: &str) -> u64 {
+    let bytes = match hex::decode(id) {
+        Ok(bytes) if !bytes.is_empty() => bytes,
+        _ => Sha256::digest(id.as_bytes()).to_vec(),
+    };
+    let low = &bytes[bytes.len().saturating_sub(8)..];
+    low.iter().fold(0, |id, byte| (id << 8) | u64::from(*byte))
+}
+
+/// High 64 bits of a 128-bit hex trace ID, as 16 hex characters
+fn trace_id_high(trace_id: &str) -> Option<&str> {
+    if trace_id.len() == 32 && trace_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Some(&trace_id[..16])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forwarding::tests::test_span;
+
+    #[test]
+    fn test_to_traces() {
+        let root = test_span("checkout", "00f067aa0ba902b7", None);
+        let child = test_span("checkout", "00000000000000a1", Some("00f067aa0ba902b7"));
+        let traces = to_traces(&[&root, &child]);
+
+        assert_eq!(traces.as_array().unwrap().len(), 1);
+        let span = &traces[0][1];
+        assert_eq!(span["trace_id"], 0x8448_eb21_1c80_319c_u64);
+        assert_eq!(span["span_id"], 0xa1);
+        assert_eq!(span["parent_id"], 0x00f0_67aa_0ba9_02b7_u64);
+        assert_eq!(traces[0][0]["parent_id"], 0);
+        assert_eq!(span["service"], "checkout");
+        assert_eq!(span["type"], "llm");
+        assert_eq!(span["duration"], 1_500_000_000_i64);
+        assert_eq!(span["error"], 1);
+        assert_eq!(span["meta"]["gen_ai.request.model"], "gpt-4o");
+        assert_eq!(span["meta"]["error.message"], "rate limited");
+        assert_eq!(span["meta"]["_dd.p.tid"], "0af7651916cd43dd");
+        assert_eq!(span["meta"]["retry"], "true");
+        assert_eq!(span["metrics"]["gen_ai.usage.input_tokens"], 100);
+        assert_eq!(span["metrics"]["http.status_code"], 429);
+
+        assert_eq!(id64("run-1"), id64("run-1"));
+        assert_eq!(trace_id_high("00f067aa0ba902b7"), None);
+    }
+
+    #[test]
+    fn test_request_counts_traces() {
+        let root = test_span("checkout", "00f067aa0ba902b7", None);
+        let child = test_span("checkout", "00000000000000a1", Some("00f067aa0ba902b7"));
+        let mut other = test_span("checkout", "00000000000000b2", None);
+        other.trace_id = "4bf92f3577b34da6a3ce929d0e0e4736".to_string();
+        let traces = to_traces(&[&root, &child, &other]);
+
+        let request = request(&Client::new(), "http://localhost:8126/v0.3/traces", Some("k3y"), &traces)
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["X-Datadog-Trace-Count"], "2");
+        assert_eq!(request.headers()["DD-API-KEY"], "k3y");
+    }
+}
@@ -0,0 +1,345 @@
+//! Span forwarding to Datadog APM and New Relic
+//!
+//! Teams that must keep traces in an existing APM vendor can forward spans
+//! there alongside local storage. Each configured sink gets the finished
+//! spans of the services its include/exclude rules accept, converted to the
+//! vendor's trace format:
+//!
+//! | Vendor | Endpoint | Format |
+//! |--------|----------|--------|
+//! | `datadog` | Datadog Agent, `/v0.3/traces` | traces as arrays of spans, LLM fields in `meta`/`metrics` |
+//! | `newrelic` | Trace API, `/trace/v1` | New Relic format, LLM fields as span attributes |
+//!
+//! Spans are queued and sent in batches by a background worker, so a slow
+//! or unreachable vendor never holds up ingestion; when the queue is full
+//! new spans are dropped and logged. Failed requests are retried with
+//! backoff. On shutdown the worker sends what is queued before it stops.
+
+mod datadog;
+mod newrelic;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::config::{ForwardingConfig, ForwardingSink, ForwardingVendor};
+use crate::models::{Span, SESSION_ID_ATTRIBUTE, USER_ID_ATTRIBUTE};
+
+/// Spans waiting to be forwarded before new ones are dropped
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// Requests made per batch and sink before giving up
+const MAX_ATTEMPTS: i32 = 3;
+
+/// Timeout for a single request
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Queue of finished spans and the worker that forwards them to each sink
+#[derive(Clone)]
+pub struct SpanForwarder {
+    sinks: Arc<[ForwardingSink]>,
+    batch_size: usize,
+    flush_interval: Duration,
+    queue: mpsc::Sender<Span>,
+    receiver: Arc<Mutex<Option<mpsc::Receiver<Span>>>>,
+    closed: Arc<Notify>,
+    client: Client,
+}
+
+impl SpanForwarder {
+    /// Create a forwarder for the configured sinks, or None if there are none
+    pub fn new(config: &ForwardingConfig) -> Option<Self> {
+        if config.sinks.is_empty() {
+            return None;
+        }
+
+        let (queue, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Some(Self {
+            sinks: config.sinks.clone().into(),
+            batch_size: config.batch_size.max(1),
+            flush_interval: Duration::from_secs(config.flush_interval_secs.max(1)),
+            queue,
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+            closed: Arc::new(Notify::new()),
+            client,
+        })
+    }
+
+    /// Queue a span if any sink accepts its service
+    ///
+    /// Never waits: when the queue is full the span is dropped and logged.
+    pub fn forward(&self, span: &Span) {
+        if !self.sinks.iter().any(|sink| sink.accepts(&span.service_name)) {
+            return;
+        }
+        if let Err(e) = self.queue.try_send(span.clone()) {
+            warn!("Dropping forwarded span, queue is full or closed: {}", e);
+        }
+    }
+
+    /// Spawn the worker, which sends a batch once it is full or the flush
+    /// interval passes
+    ///
+    /// Returns None if the worker was already started.
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        let mut receiver = self.receiver.lock().take()?;
+        let forwarder = self.clone();
+
+        Some(tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(forwarder.batch_size);
+            let mut flush = tokio::time::interval(forwarder.flush_interval);
+            loop {
+                tokio::select! {
+                    span = receiver.recv() => match span {
+                        Some(span) => {
+                            batch.push(span);
+                            if batch.len() >= forwarder.batch_size {
+                                forwarder.send(&std::mem::take(&mut batch)).await;
+                            }
+                        }
+                        None => break,
+                    },
+                    _ = flush.tick() => {
+                        if !batch.is_empty() {
+                            forwarder.send(&std::mem::take(&mut batch)).await;
+                        }
+                    }
+                    () = forwarder.closed.notified() => {
+                        // Take no new spans, but send the ones queued
+                        receiver.close();
+                        while let Some(span) = receiver.recv().await {
+                            batch.push(span);
+                            if batch.len() >= forwarder.batch_size {
+                                forwarder.send(&std::mem::take(&mut batch)).await;
+                            }
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if !batch.is_empty() {
+                forwarder.send(&batch).await;
+            }
+        }))
+    }
+
+    /// Stop taking spans; the worker sends those already queued, then
+    /// exits
+    pub fn close(&self) {
+        self.closed.notify_one();
+    }
+
+    /// Send each sink the spans of a batch it accepts
+    async fn send(&self, spans: &[Span]) {
+        for sink in self.sinks.iter() {
+            let accepted: Vec<&Span> = spans.iter().filter(|s| sink.accepts(&s.service_name)).collect();
+            if accepted.is_empty() {
+                continue;
+            }
+
+            let url = sink.endpoint();
+            let body = match sink.vendor {
+                ForwardingVendor::Datadog => datadog::to_traces(&accepted),
+                ForwardingVendor::NewRelic => newrelic::to_payload(&accepted),
+            };
+            match self.post(sink, url, &body).await {
+                Ok(()) => debug!("Forwarded {} spans to {}", accepted.len(), sink.vendor.as_str()),
+                Err(e) => warn!(
+                    "Failed to forward {} spans to {} at {}: {}",
+                    accepted.len(),
+                    sink.vendor.as_str(),
+                    url,
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Send a body, retrying server errors, rate limiting and failed
+    /// requests with backoff
+    async fn post(&self, sink: &ForwardingSink, url: &str, body: &Value) -> Result<(), String> {
+        let mut attempts = 0;
+        let mut error = String::new();
+
+        while attempts < MAX_ATTEMPTS {
+            if attempts > 0 {
+                tokio::time::sleep(Duration::from_secs(1 << (attempts - 1))).await;
+            }
+            attempts += 1;
+
+            let request = match sink.vendor {
+                ForwardingVendor::Datadog => datadog::request(&self.client, url, sink.api_key.as_deref(), body),
+                ForwardingVendor::NewRelic => newrelic::request(&self.client, url, sink.api_key.as_deref()),
+            };
+            match request.json(body).send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp) => {
+                    let status = resp.status();
+                    error = format!("returned {}", status);
+                    // Other client errors won't succeed on retry
+                    if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                        break;
+                    }
+                }
+                Err(e) => error = e.to_string(),
+            }
+        }
+
+        Err(format!("{} after {} attempts", error, attempts))
+    }
+}
+
+/// LLM fields and flattened attributes of a span, with the same keys as
+/// the Jaeger and OTLP exports
+fn span_tags(span: &Span) -> Vec<(String, Value)> {
+    let optional: [(&str, Option<Value>); 9] = [
+        (SESSION_ID_ATTRIBUTE, span.session_id.clone().map(Value::from)),
+        (USER_ID_ATTRIBUTE, span.user_id.clone().map(Value::from)),
+        ("gen_ai.request.model", span.model_name.clone().map(Value::from)),
+        ("gen_ai.system", span.model_provider.clone().map(Value::from)),
+        ("gen_ai.usage.input_tokens", span.tokens_in.map(Value::from)),
+        (
+            "gen_ai.usage.cache_read.input_tokens",
+            span.tokens_cached_in.map(Value::from),
+        ),
+        ("gen_ai.usage.output_tokens", span.tokens_out.map(Value::from)),
+        ("agenttrace.cost_usd", span.cost_usd.map(Value::from)),
+        ("agenttrace.tool_name", span.tool_name.clone().map(Value::from)),
+    ];
+
+    let mut tags: Vec<(String, Value)> = optional
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect();
+    push_attributes(&mut tags, "", &span.attributes);
+    tags
+}
+
+/// Flatten JSON attributes, joining nested keys with dots; arrays are kept
+/// as their JSON text
+fn push_attributes(tags: &mut Vec<(String, Value)>, prefix: &str, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                push_attributes(tags, &key, value);
+            }
+        }
+        Value::Null => {}
+        Value::Array(_) if !prefix.is_empty() => tags.push((prefix.to_string(), Value::String(value.to_string()))),
+        other if !prefix.is_empty() => tags.push((prefix.to_string(), other.clone())),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    pub(super) fn test_span(service: &str, span_id: &str, parent_span_id: Option<&str>) -> Span {
+        serde_json::from_value(json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "span_id": span_id,
+            "trace_id": "0af7651916cd43dd8448eb211c80319c",
+            "parent_span_id": parent_span_id,
+            "operation_name": "chat gpt-4o",
+            "service_name": service,
+            "span_kind": "client",
+            "started_at": "2026-01-01T00:00:00Z",
+            "ended_at": "2026-01-01T00:00:01.5Z",
+            "duration_ms": 1500.0,
+            "status": "error",
+            "status_message": "rate limited",
+            "model_name": "gpt-4o",
+            "model_provider": "openai",
+            "tokens_in": 100,
+            "tokens_cached_in": null,
+            "tokens_out": 20,
+            "tokens_reasoning": null,
+            "cost_usd": 0.5,
+            "tool_name": null,
+            "tool_input": null,
+            "tool_output": null,
+            "tool_duration_ms": null,
+            "prompt_preview": null,
+            "completion_preview": null,
+            "attributes": {"http": {"status_code": 429}, "retry": true, "tags": ["a"]},
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_service_rules_and_tags() {
+        let sink: ForwardingSink = serde_json::from_value(json!({
+            "vendor": "newrelic",
+            "api_key": "key",
+            "include_services": ["checkout", "search"],
+            "exclude_services": ["search"]
+        }))
+        .unwrap();
+        assert!(sink.accepts("checkout"));
+        assert!(!sink.accepts("search"));
+        assert!(!sink.accepts("billing"));
+        assert_eq!(sink.endpoint(), "https://trace-api.newrelic.com/trace/v1");
+
+        let tags = span_tags(&test_span("checkout", "00f067aa0ba902b7", None));
+        assert!(tags.contains(&("http.status_code".to_string(), json!(429))));
+        assert!(tags.contains(&("tags".to_string(), json!("[\"a\"]"))));
+        assert!(tags.contains(&("gen_ai.request.model".to_string(), json!("gpt-4o"))));
+    }
+
+    #[tokio::test]
+    async fn test_close_sends_queued_spans() {
+        let received = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = received.clone();
+        let app = axum::Router::new().route(
+            "/trace/v1",
+            axum::routing::post(move |axum::Json(body): axum::Json<Value>| async move {
+                let spans = body[0]["spans"].as_array().map_or(0, Vec::len);
+                counter.fetch_add(spans, std::sync::atomic::Ordering::SeqCst);
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let forwarder = SpanForwarder::new(&ForwardingConfig {
+            sinks: vec![serde_json::from_value(json!({
+                "vendor": "newrelic",
+                "url": format!("http://{}/trace/v1", addr),
+            }))
+            .unwrap()],
+            batch_size: 500,
+            flush_interval_secs: 3600,
+        })
+        .unwrap();
+        let handle = forwarder.start().unwrap();
+
+        for span_id in ["00000000000000a1", "00000000000000a2", "00000000000000a3"] {
+            forwarder.forward(&test_span("checkout", span_id, None));
+        }
+        forwarder.close();
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap();
+
+        assert_eq!(received.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+}
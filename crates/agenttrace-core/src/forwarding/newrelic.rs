@@ -0,0 +1,81 @@
+//! New Relic Trace API format
+//!
+//! Spans are sent in the `newrelic` data format: one payload with common
+//! attributes and a list of spans, each with its trace ID, span ID,
+//! millisecond timestamp and an attribute map holding the span's name,
+//! service, duration, parent and LLM fields.
+
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Map, Value};
+
+use super::span_tags;
+use crate::models::{Span, SpanStatus};
+
+/// Build the request for a batch
+pub(super) fn request(client: &Client, url: &str, api_key: Option<&str>) -> RequestBuilder {
+    client
+        .post(url)
+        .header("Api-Key", api_key.unwrap_or_default())
+        .header("Data-Format", "newrelic")
+        .header("Data-Format-Version", "1")
+}
+
+/// Build the Trace API payload for a batch of spans
+pub(super) fn to_payload(spans: &[&Span]) -> Value {
+    json!([{
+        "common": {"attributes": {"instrumentation.provider": "agenttrace"}},
+        "spans": spans.iter().map(|span| to_span(span)).collect::<Vec<_>>(),
+    }])
+}
+
+fn to_span(span: &Span) -> Value {
+    let mut attributes: Map<String, Value> = span_tags(span).into_iter().collect();
+    attributes.insert("name".to_string(), span.operation_name.clone().into());
+    attributes.insert("service.name".to_string(), span.service_name.clone().into());
+    if let Some(duration_ms) = span.duration_ms {
+        attributes.insert("duration.ms".to_string(), duration_ms.into());
+    }
+    if let Some(parent) = &span.parent_span_id {
+        attributes.insert("parent.id".to_string(), parent.clone().into());
+    }
+    if span.status == SpanStatus::Error {
+        attributes.insert("error".to_string(), true.into());
+        if let Some(message) = &span.status_message {
+            attributes.insert("error.message".to_string(), message.clone().into());
+        }
+    }
+
+    json!({
+        "trace.id": span.trace_id,
+        "id": span.span_id,
+        "timestamp": span.started_at.timestamp_millis(),
+        "attributes": attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forwarding::tests::test_span;
+
+    #[test]
+    fn test_to_payload() {
+        let span = test_span("checkout", "00000000000000a1", Some("00f067aa0ba902b7"));
+        let payload = to_payload(&[&span]);
+
+        assert_eq!(
+            payload[0]["common"]["attributes"]["instrumentation.provider"],
+            "agenttrace"
+        );
+        let span = &payload[0]["spans"][0];
+        assert_eq!(span["trace.id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(span["id"], "00000000000000a1");
+        assert_eq!(span["timestamp"], 1_767_225_600_000_i64);
+        assert_eq!(span["attributes"]["name"], "chat gpt-4o");
+        assert_eq!(span["attributes"]["service.name"], "checkout");
+        assert_eq!(span["attributes"]["duration.ms"], 1500.0);
+        assert_eq!(span["attributes"]["parent.id"], "00f067aa0ba902b7");
+        assert_eq!(span["attributes"]["error"], true);
+        assert_eq!(span["attributes"]["gen_ai.usage.output_tokens"], 20);
+    }
+}
@@ -0,0 +1,567 @@
+//! LangSmith runs
+//!
+//! Converts runs as LangChain's tracer sends them to LangSmith: a run tree
+//! with `child_runs`, a flat list linked by `parent_run_id`, or a
+//! `{"post": [...], "patch": [...]}` batch where patches finish runs posted
+//! earlier. Run IDs are UUIDs and become hex span and trace IDs. A patch
+//! that names no trace, and finishes no run in the same payload, gets an
+//! empty trace ID for the caller to resolve against the run it finishes.
+//!
+//! Only `llm` runs carry token counts. LangSmith rolls its children's usage
+//! up into chain runs; those totals are left out so a trace's tokens and
+//! cost aren't counted twice.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use super::parse_timestamp;
+use crate::error::{Error, Result};
+use crate::models::{Span, SpanEvent, SpanKind, SpanPriority, SpanStatus};
+
+/// Service name for runs whose project isn't known
+const DEFAULT_SERVICE: &str = "langchain";
+
+/// Metadata keys LangSmith groups runs into threads by
+const THREAD_KEYS: &[&str] = &["session_id", "thread_id", "conversation_id"];
+
+/// Run events that aren't kept: one per streamed token
+const SKIPPED_EVENTS: &[&str] = &["new_token"];
+
+/// Parse the runs in a LangSmith payload
+pub fn spans_from_langsmith(payload: &Value) -> Result<Vec<Span>> {
+    let mut runs = Vec::new();
+    match payload {
+        Value::Array(list) => flatten(list, false, None, &mut runs),
+        Value::Object(object) if object.contains_key("post") || object.contains_key("patch") => {
+            let list = |key: &str| payload[key].as_array().map(Vec::as_slice).unwrap_or_default();
+            flatten(list("post"), false, None, &mut runs);
+            flatten(list("patch"), true, None, &mut runs);
+        }
+        Value::Object(_) => flatten(std::slice::from_ref(payload), false, None, &mut runs),
+        _ => {
+            return Err(Error::validation(
+                "expected a run, a list of runs or a {post, patch} batch",
+            ))
+        }
+    }
+
+    // Runs without a trace ID or dotted order take their parent's trace
+    let mut traces: HashMap<String, String> = HashMap::new();
+    let mut spans = Vec::with_capacity(runs.len());
+    for (i, flat) in runs.iter().enumerate() {
+        let span = to_span(flat, &traces).map_err(|e| Error::validation(format!("run {}: {}", i + 1, e)))?;
+        traces.insert(span.span_id.clone(), span.trace_id.clone());
+        spans.push(span);
+    }
+    Ok(spans)
+}
+
+/// A run with what its place in a tree says about it
+struct FlatRun<'a> {
+    run: &'a Value,
+    /// Update to a run sent before
+    patch: bool,
+    /// Span and trace ID of the run it is nested in
+    parent: Option<(String, String)>,
+}
+
+fn flatten<'a>(runs: &'a [Value], patch: bool, parent: Option<&(String, String)>, out: &mut Vec<FlatRun<'a>>) {
+    for run in runs {
+        out.push(FlatRun {
+            run,
+            patch,
+            parent: parent.cloned(),
+        });
+        if let Some(children) = run["child_runs"].as_array() {
+            let id = hex_id(&run["id"]);
+            let trace_id = string(&run["trace_id"])
+                .map(|t| hex_id(&Value::String(t)))
+                .or_else(|| parent.map(|(_, trace)| trace.clone()))
+                .or_else(|| dotted_order_root(&run["dotted_order"]))
+                .unwrap_or_else(|| id.clone());
+            flatten(children, patch, Some(&(id, trace_id)), out);
+        }
+    }
+}
+
+fn to_span(flat: &FlatRun<'_>, traces: &HashMap<String, String>) -> std::result::Result<Span, String> {
+    let run = flat.run;
+    let span_id = hex_id(&run["id"]);
+    if span_id.is_empty() {
+        return Err("missing id".to_string());
+    }
+    let parent_span_id = flat
+        .parent
+        .as_ref()
+        .map(|(parent, _)| parent.clone())
+        .or_else(|| string(&run["parent_run_id"]).map(|id| hex_id(&Value::String(id))));
+    let trace_id = string(&run["trace_id"])
+        .map(|id| hex_id(&Value::String(id)))
+        .or_else(|| flat.parent.as_ref().map(|(_, trace)| trace.clone()))
+        .or_else(|| dotted_order_root(&run["dotted_order"]))
+        .or_else(|| parent_span_id.as_ref().and_then(|parent| traces.get(parent).cloned()))
+        .or_else(|| flat.patch.then(|| traces.get(&span_id).cloned()).flatten())
+        .unwrap_or_else(|| if flat.patch { String::new() } else { span_id.clone() });
+
+    let ended_at = parse_timestamp(&run["end_time"]);
+    let started_at = match (parse_timestamp(&run["start_time"]), flat.patch) {
+        (Some(started_at), _) => started_at,
+        // A patch is merged into the run it finishes, which keeps its own start
+        (None, true) => ended_at.unwrap_or_else(Utc::now),
+        (None, false) => return Err("missing start_time".to_string()),
+    };
+
+    let error = string(&run["error"]).filter(|e| !e.is_empty());
+    let status = if error.is_some() {
+        SpanStatus::Error
+    } else if ended_at.is_some() {
+        SpanStatus::Ok
+    } else {
+        SpanStatus::InProgress
+    };
+
+    let extra = &run["extra"];
+    let metadata = &extra["metadata"];
+    let params = &extra["invocation_params"];
+    let inputs = &run["inputs"];
+    let outputs = &run["outputs"];
+    // Patches usually leave out the run type; only LLM runs have generations
+    let run_type = match run["run_type"].as_str() {
+        Some(run_type) => run_type,
+        None if outputs.get("generations").is_some() || outputs.get("llm_output").is_some() => "llm",
+        None => "",
+    };
+
+    let mut span = Span {
+        id: Uuid::new_v4(),
+        span_id,
+        trace_id,
+        parent_span_id,
+        session_id: THREAD_KEYS.iter().find_map(|key| string(&metadata[*key])),
+        user_id: string(&metadata["user_id"]),
+        operation_name: run["name"].as_str().unwrap_or_default().to_string(),
+        service_name: string(&metadata["service_name"])
+            .or_else(|| string(&run["session_name"]))
+            .unwrap_or_else(|| {
+                if flat.patch {
+                    String::new()
+                } else {
+                    DEFAULT_SERVICE.to_string()
+                }
+            }),
+        span_kind: if run_type == "llm" {
+            SpanKind::Client
+        } else {
+            SpanKind::Internal
+        },
+        started_at,
+        ended_at: ended_at.filter(|end| *end >= started_at),
+        duration_ms: None,
+        status,
+        status_message: error,
+        model_name: None,
+        model_provider: None,
+        tokens_in: None,
+        tokens_cached_in: None,
+        tokens_out: None,
+        tokens_reasoning: None,
+        cost_usd: None,
+        tool_name: None,
+        tool_input: None,
+        tool_output: None,
+        tool_duration_ms: None,
+        prompt_preview: None,
+        completion_preview: None,
+        attributes: attributes(run),
+        events: run["events"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|event| !SKIPPED_EVENTS.contains(&event["name"].as_str().unwrap_or_default()))
+            .map(|event| SpanEvent {
+                name: event["name"].as_str().unwrap_or_default().to_string(),
+                timestamp: parse_timestamp(&event["time"]).unwrap_or(started_at),
+                attributes: event.get("kwargs").cloned().unwrap_or_else(|| json!({})),
+            })
+            .collect(),
+        links: vec![],
+        priority: SpanPriority::default(),
+    };
+
+    match run_type {
+        "llm" => {
+            span.model_name = ["model", "model_name"]
+                .iter()
+                .find_map(|key| string(&params[*key]))
+                .or_else(|| string(&metadata["ls_model_name"]));
+            span.model_provider = string(&metadata["ls_provider"]).or_else(|| {
+                // e.g. `openai-chat`, `anthropic-chat`
+                params["_type"]
+                    .as_str()
+                    .and_then(|t| t.split('-').next())
+                    .map(str::to_string)
+            });
+            let usage = Usage::from_llm_run(run);
+            span.tokens_in = usage.input;
+            span.tokens_cached_in = usage.cached;
+            span.tokens_out = usage.output;
+            span.tokens_reasoning = usage.reasoning;
+            span.prompt_preview = prompt_text(inputs);
+            span.completion_preview = generation_text(outputs);
+        }
+        "tool" => {
+            span.tool_name = Some(span.operation_name.clone()).filter(|name| !name.is_empty());
+            span.tool_input = Some(inputs.clone()).filter(|v| !v.is_null());
+            span.tool_output = Some(outputs.clone()).filter(|v| !v.is_null());
+        }
+        _ => {
+            span.prompt_preview = value_text(inputs);
+            span.completion_preview = value_text(outputs);
+        }
+    }
+
+    Ok(span)
+}
+
+/// Token counts of an LLM run
+#[derive(Debug, Default)]
+struct Usage {
+    input: Option<i32>,
+    cached: Option<i32>,
+    output: Option<i32>,
+    reasoning: Option<i32>,
+}
+
+impl Usage {
+    /// Read usage from wherever the model integration reported it: chat
+    /// messages' `usage_metadata`, the provider's own `llm_output`, or the
+    /// counts LangSmith computed for the run
+    fn from_llm_run(run: &Value) -> Self {
+        let outputs = &run["outputs"];
+
+        let messages: Vec<&Value> = outputs["generations"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .flat_map(|g| g.as_array().map_or_else(|| vec![g], |inner| inner.iter().collect()))
+            .map(|generation| &generation["message"]["kwargs"]["usage_metadata"])
+            .filter(|usage| usage.is_object())
+            .collect();
+        if !messages.is_empty() {
+            let sum = |path: &[&str]| {
+                messages
+                    .iter()
+                    .filter_map(|usage| path.iter().fold(*usage, |v, key| &v[*key]).as_i64())
+                    .reduce(|a, b| a + b)
+                    .and_then(|n| i32::try_from(n).ok())
+            };
+            return Self {
+                input: sum(&["input_tokens"]),
+                cached: sum(&["input_token_details", "cache_read"]),
+                output: sum(&["output_tokens"]),
+                reasoning: sum(&["output_token_details", "reasoning"]),
+            };
+        }
+
+        let usage = &outputs["usage_metadata"];
+        if usage.is_object() {
+            return Self {
+                input: count(&usage["input_tokens"]),
+                cached: count(&usage["input_token_details"]["cache_read"]),
+                output: count(&usage["output_tokens"]),
+                reasoning: count(&usage["output_token_details"]["reasoning"]),
+            };
+        }
+
+        let llm_output = &outputs["llm_output"];
+        let usage = &llm_output["token_usage"];
+        if usage.is_object() {
+            return Self {
+                input: count(&usage["prompt_tokens"]),
+                cached: count(&usage["prompt_tokens_details"]["cached_tokens"]),
+                output: count(&usage["completion_tokens"]),
+                reasoning: count(&usage["completion_tokens_details"]["reasoning_tokens"]),
+            };
+        }
+        let usage = &llm_output["usage"];
+        if usage.is_object() {
+            return Self {
+                input: count(&usage["input_tokens"]),
+                cached: count(&usage["cache_read_input_tokens"]),
+                output: count(&usage["output_tokens"]),
+                reasoning: None,
+            };
+        }
+
+        Self {
+            input: count(&run["prompt_tokens"]),
+            output: count(&run["completion_tokens"]),
+            ..Self::default()
+        }
+    }
+}
+
+/// Run fields without a span field of their own, prefixed `langsmith.`
+fn attributes(run: &Value) -> Value {
+    let mut attributes = Map::new();
+    if let Some(run_type) = string(&run["run_type"]) {
+        attributes.insert("langsmith.run_type".to_string(), Value::String(run_type));
+    }
+    if let Some(id) = string(&run["id"]) {
+        attributes.insert("langsmith.run_id".to_string(), Value::String(id));
+    }
+    if let Some(tags) = run["tags"].as_array().filter(|tags| !tags.is_empty()) {
+        attributes.insert("langsmith.tags".to_string(), Value::Array(tags.clone()));
+    }
+    if let Some(metadata) = run["extra"]["metadata"].as_object() {
+        for (key, value) in metadata {
+            attributes.insert(format!("langsmith.metadata.{}", key), value.clone());
+        }
+    }
+    Value::Object(attributes)
+}
+
+/// Prompts or chat messages of an LLM run, one message per line
+fn prompt_text(inputs: &Value) -> Option<String> {
+    if let Some(prompts) = inputs["prompts"].as_array() {
+        let prompts: Vec<&str> = prompts.iter().filter_map(Value::as_str).collect();
+        return Some(prompts.join("\n")).filter(|p| !p.is_empty());
+    }
+    // A list of message lists, one per prompt in the batch
+    let lines: Vec<String> = inputs["messages"]
+        .as_array()?
+        .iter()
+        .flat_map(|m| m.as_array().map_or_else(|| vec![m], |inner| inner.iter().collect()))
+        .map(|message| format!("{}: {}", message_role(message), content_text(message_content(message))))
+        .collect();
+    Some(lines.join("\n")).filter(|p| !p.is_empty())
+}
+
+/// Text of an LLM run's first generation
+fn generation_text(outputs: &Value) -> Option<String> {
+    let generation = match &outputs["generations"][0] {
+        Value::Array(inner) => inner.first()?,
+        generation => generation,
+    };
+    string(&generation["text"])
+        .filter(|t| !t.is_empty())
+        .or_else(|| Some(content_text(message_content(&generation["message"]))).filter(|t| !t.is_empty()))
+}
+
+/// Inputs or outputs of a chain: a lone string as it is, anything else as JSON
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Object(map) if map.is_empty() => None,
+        Value::Object(map) if map.len() == 1 => match map.values().next() {
+            Some(Value::String(text)) => Some(text.clone()),
+            _ => Some(value.to_string()),
+        },
+        Value::String(text) => Some(text.clone()),
+        _ => Some(value.to_string()),
+    }
+}
+
+/// Role of a message, serialized by LangChain or as a plain dict
+fn message_role(message: &Value) -> String {
+    if let Some(role) = message["role"].as_str() {
+        return role.to_string();
+    }
+    let class = message["id"]
+        .as_array()
+        .and_then(|id| id.last())
+        .and_then(Value::as_str);
+    let kind = message["type"].as_str().filter(|t| *t != "constructor");
+    match class.or(kind).unwrap_or("user") {
+        "HumanMessage" | "HumanMessageChunk" | "human" => "user",
+        "AIMessage" | "AIMessageChunk" | "ai" => "assistant",
+        "SystemMessage" | "system" => "system",
+        "ToolMessage" | "tool" => "tool",
+        other => other,
+    }
+    .to_string()
+}
+
+fn message_content(message: &Value) -> &Value {
+    match message.get("kwargs") {
+        Some(kwargs) => &kwargs["content"],
+        None => &message["content"],
+    }
+}
+
+/// Text of message content, whether a string or a list of parts
+fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part.as_str().or_else(|| part["text"].as_str()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+/// Root run ID from a dotted order such as
+/// `20240501T120000000000Z<root>.20240501T120001000000Z<child>`
+fn dotted_order_root(value: &Value) -> Option<String> {
+    let first = value.as_str()?.split('.').next()?;
+    let (_, id) = first.split_once('Z')?;
+    Some(hex_id(&Value::String(id.to_string()))).filter(|id| !id.is_empty())
+}
+
+/// UUIDs as 32-char hex; other IDs are kept as they are
+fn hex_id(value: &Value) -> String {
+    let id = value.as_str().unwrap_or_default();
+    Uuid::parse_str(id).map_or_else(|_| id.to_string(), |uuid| uuid.simple().to_string())
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+fn count(value: &Value) -> Option<i32> {
+    value.as_i64().and_then(|n| i32::try_from(n).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_tree() {
+        let tree = json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "name": "AgentExecutor",
+            "run_type": "chain",
+            "start_time": "2024-05-01T12:00:00.000000",
+            "end_time": "2024-05-01T12:00:03.000000",
+            "session_name": "support-bot",
+            "extra": {"metadata": {"thread_id": "t-1", "user_id": "u-1"}},
+            "inputs": {"input": "Where is my order?"},
+            "outputs": {"output": "It ships today."},
+            "total_tokens": 160,
+            "child_runs": [
+                {
+                    "id": "0b8f7c52-3f14-4a7e-8f3c-1f2a3b4c5d6e",
+                    "name": "ChatOpenAI",
+                    "run_type": "llm",
+                    "start_time": "2024-05-01T12:00:00.100000",
+                    "end_time": "2024-05-01T12:00:01.100000",
+                    "extra": {
+                        "invocation_params": {"model_name": "gpt-4o", "_type": "openai-chat"},
+                        "metadata": {"thread_id": "t-1"}
+                    },
+                    "inputs": {"messages": [[
+                        {"lc": 1, "type": "constructor", "id": ["langchain", "schema", "messages", "SystemMessage"], "kwargs": {"content": "Be brief."}},
+                        {"lc": 1, "type": "constructor", "id": ["langchain", "schema", "messages", "HumanMessage"], "kwargs": {"content": "Where is my order?"}}
+                    ]]},
+                    "outputs": {
+                        "generations": [[{"text": "", "message": {"kwargs": {"content": "Let me check.", "usage_metadata": {"input_tokens": 100, "output_tokens": 20, "input_token_details": {"cache_read": 64}}}}}]],
+                        "llm_output": {"token_usage": {"prompt_tokens": 100, "completion_tokens": 20}}
+                    },
+                    "child_runs": []
+                },
+                {
+                    "id": "9d3e2c1b-5a4f-4e3d-8c2b-7a6f5e4d3c2b",
+                    "name": "order_lookup",
+                    "run_type": "tool",
+                    "start_time": "2024-05-01T12:00:01.200000",
+                    "end_time": "2024-05-01T12:00:01.400000",
+                    "inputs": {"input": "A-17"},
+                    "outputs": {"output": "shipping"},
+                    "error": "timeout"
+                }
+            ]
+        });
+
+        let spans = spans_from_langsmith(&tree).unwrap();
+        assert_eq!(spans.len(), 3);
+        let (chain, llm, tool) = (&spans[0], &spans[1], &spans[2]);
+
+        assert_eq!(chain.span_id, "6f1c1a3e8d2b4c4e9a431d2f0b7c5e10");
+        assert_eq!(chain.trace_id, chain.span_id);
+        assert_eq!(chain.service_name, "support-bot");
+        assert_eq!(chain.session_id.as_deref(), Some("t-1"));
+        assert_eq!(chain.prompt_preview.as_deref(), Some("Where is my order?"));
+        // Rolled-up totals stay off chain runs
+        assert_eq!(chain.tokens_in, None);
+
+        assert_eq!(llm.parent_span_id.as_deref(), Some(chain.span_id.as_str()));
+        assert_eq!(llm.trace_id, chain.trace_id);
+        assert_eq!(llm.span_kind, SpanKind::Client);
+        assert_eq!(llm.model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(llm.model_provider.as_deref(), Some("openai"));
+        assert_eq!(
+            (llm.tokens_in, llm.tokens_out, llm.tokens_cached_in),
+            (Some(100), Some(20), Some(64))
+        );
+        assert_eq!(
+            llm.prompt_preview.as_deref(),
+            Some("system: Be brief.\nuser: Where is my order?")
+        );
+        assert_eq!(llm.completion_preview.as_deref(), Some("Let me check."));
+
+        assert_eq!(tool.tool_name.as_deref(), Some("order_lookup"));
+        assert_eq!(tool.tool_input, Some(json!({"input": "A-17"})));
+        assert_eq!(tool.status, SpanStatus::Error);
+        assert_eq!(tool.status_message.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn test_batch_with_patches() {
+        let batch = json!({
+            "post": [
+                {
+                    "id": "11111111-1111-4111-8111-111111111111",
+                    "name": "RunnableSequence",
+                    "run_type": "chain",
+                    "start_time": "2024-05-01T12:00:00Z",
+                    "dotted_order": "20240501T120000000000Z11111111-1111-4111-8111-111111111111"
+                },
+                {
+                    "id": "22222222-2222-4222-8222-222222222222",
+                    "parent_run_id": "11111111-1111-4111-8111-111111111111",
+                    "name": "ChatAnthropic",
+                    "run_type": "llm",
+                    "start_time": "2024-05-01T12:00:00.5Z"
+                }
+            ],
+            "patch": [
+                {
+                    "id": "22222222-2222-4222-8222-222222222222",
+                    "trace_id": "11111111-1111-4111-8111-111111111111",
+                    "end_time": "2024-05-01T12:00:02Z",
+                    "outputs": {"llm_output": {"usage": {"input_tokens": 30, "output_tokens": 5}}}
+                }
+            ]
+        });
+
+        let spans = spans_from_langsmith(&batch).unwrap();
+        assert_eq!(spans.len(), 3);
+        let root = "11111111111141118111111111111111";
+        assert!(spans.iter().all(|span| span.trace_id == root));
+
+        let (started, finished) = (&spans[1], &spans[2]);
+        assert_eq!(started.status, SpanStatus::InProgress);
+        assert_eq!(started.parent_span_id.as_deref(), Some(root));
+        assert_eq!(finished.span_id, started.span_id);
+        assert_eq!(finished.status, SpanStatus::Ok);
+        assert_eq!((finished.tokens_in, finished.tokens_out), (Some(30), Some(5)));
+        // Left empty so the posted run's values are kept on merge
+        assert!(finished.operation_name.is_empty());
+        assert!(finished.service_name.is_empty());
+
+        // A patch finishing a run posted in another payload names no trace
+        let patch = json!({"patch": [{"id": "33333333-3333-4333-8333-333333333333", "end_time": "2024-05-01T12:00:03Z"}]});
+        let spans = spans_from_langsmith(&patch).unwrap();
+        assert_eq!(spans[0].span_id, "33333333333343338333333333333333");
+        assert!(spans[0].trace_id.is_empty());
+
+        assert!(spans_from_langsmith(&json!([{"name": "no id", "start_time": "2024-05-01T12:00:00Z"}])).is_err());
+        assert!(spans_from_langsmith(&json!("runs")).is_err());
+    }
+}
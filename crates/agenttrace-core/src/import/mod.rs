@@ -0,0 +1,613 @@
+//! Importing spans from other tools' files
+//!
+//! `agenttrace import` reads JSONL, CSV or OTLP files and turns each record
+//! into a span. JSONL and CSV records are mapped field by field with a
+//! [`FieldMapping`], so logs from a homegrown logger can be imported without
+//! rewriting them first:
+//!
+//! ```toml
+//! [fields]
+//! trace_id = "run_id"
+//! operation_name = "event"
+//! started_at = "ts"
+//! duration_ms = "latency_ms"
+//! model_name = "llm.model"          # dotted paths reach into nested objects
+//! tokens_in = "usage.prompt_tokens"
+//!
+//! [defaults]
+//! service_name = "legacy-logger"
+//!
+//! error_values = ["failed", "exception"]
+//! ```
+//!
+//! Span fields without a mapping are read from a source field of the same
+//! name, so AgentTrace's own JSONL exports import without one. OTLP files
+//! carry their own field names and ignore the mapping.
+
+mod langsmith;
+mod otlp;
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{Span, SpanEvent, SpanKind, SpanLink, SpanPriority, SpanStatus};
+
+pub use langsmith::spans_from_langsmith;
+pub use otlp::spans_from_otlp;
+
+/// Span fields a mapping can set
+pub const SPAN_FIELDS: &[&str] = &[
+    "trace_id",
+    "span_id",
+    "parent_span_id",
+    "session_id",
+    "user_id",
+    "operation_name",
+    "service_name",
+    "span_kind",
+    "started_at",
+    "ended_at",
+    "duration_ms",
+    "status",
+    "status_message",
+    "model_name",
+    "model_provider",
+    "tokens_in",
+    "tokens_cached_in",
+    "tokens_out",
+    "tokens_reasoning",
+    "cost_usd",
+    "tool_name",
+    "tool_input",
+    "tool_output",
+    "tool_duration_ms",
+    "prompt_preview",
+    "completion_preview",
+    "attributes",
+    "events",
+    "links",
+    "priority",
+];
+
+/// Source values read as an error status
+const ERROR_VALUES: &[&str] = &["error", "err", "failed", "failure", "fatal"];
+
+/// Source values read as an ok status
+const OK_VALUES: &[&str] = &["ok", "success", "succeeded", "info", "200"];
+
+/// File formats that can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// OTLP/JSON `ExportTraceServiceRequest`s, one per file or per line
+    OtlpJson,
+    /// A protobuf `ExportTraceServiceRequest`
+    OtlpProtobuf,
+}
+
+impl ImportFormat {
+    /// Parse a format name; `otlp` means OTLP/JSON
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "otlp" | "otlp-json" => Ok(Self::OtlpJson),
+            "otlp-proto" | "otlp-protobuf" => Ok(Self::OtlpProtobuf),
+            other => Err(Error::validation(format!(
+                "Unknown import format '{}': expected jsonl, csv, otlp or otlp-proto",
+                other
+            ))),
+        }
+    }
+
+    /// Guess the format from a file name
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "jsonl" | "ndjson" | "log" => Some(Self::Jsonl),
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::OtlpJson),
+            "pb" | "protobuf" => Some(Self::OtlpProtobuf),
+            _ => None,
+        }
+    }
+}
+
+/// How source records map onto span fields
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldMapping {
+    /// Span field to source field; dotted paths reach into nested objects
+    pub fields: HashMap<String, String>,
+    /// Values for span fields a record doesn't have
+    pub defaults: HashMap<String, Value>,
+    /// Source status values read as errors, besides the usual ones
+    pub error_values: Vec<String>,
+    /// Copy source fields that aren't mapped into the span's attributes
+    pub keep_unmapped: bool,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            fields: HashMap::new(),
+            defaults: HashMap::new(),
+            error_values: Vec::new(),
+            keep_unmapped: true,
+        }
+    }
+}
+
+impl FieldMapping {
+    /// Read a mapping from a TOML file
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::config(format!("Can't read mapping {}: {}", path, e)))?;
+        let mapping: Self =
+            toml::from_str(&text).map_err(|e| Error::config(format!("Invalid mapping {}: {}", path, e)))?;
+        mapping.validate()?;
+        Ok(mapping)
+    }
+
+    /// Check that every mapped and defaulted field is a span field
+    pub fn validate(&self) -> Result<()> {
+        for (section, field) in self
+            .fields
+            .keys()
+            .map(|f| ("fields", f))
+            .chain(self.defaults.keys().map(|f| ("defaults", f)))
+        {
+            if !SPAN_FIELDS.contains(&field.as_str()) {
+                return Err(Error::config(format!(
+                    "{}.{}: not a span field; expected one of {}",
+                    section,
+                    field,
+                    SPAN_FIELDS.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Source field a span field is read from
+    fn source<'a>(&'a self, field: &'a str) -> &'a str {
+        self.fields.get(field).map_or(field, String::as_str)
+    }
+
+    /// Value of a span field in a record, falling back to its default
+    fn get<'a>(&'a self, record: &'a Value, field: &'a str) -> Option<&'a Value> {
+        lookup(record, self.source(field))
+            .filter(|v| !v.is_null() && v.as_str() != Some(""))
+            .or_else(|| self.defaults.get(field))
+    }
+
+    fn string(&self, record: &Value, field: &str) -> Option<String> {
+        self.get(record, field).and_then(|v| match v {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        })
+    }
+
+    fn number(&self, record: &Value, field: &str) -> Result<Option<f64>> {
+        match self.get(record, field) {
+            None => Ok(None),
+            Some(Value::Number(n)) => Ok(n.as_f64()),
+            Some(Value::String(s)) => s
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| invalid(field, self.source(field), "a number")),
+            Some(_) => Err(invalid(field, self.source(field), "a number")),
+        }
+    }
+
+    fn integer(&self, record: &Value, field: &str) -> Result<Option<i32>> {
+        Ok(self.number(record, field)?.map(|n| n.round() as i32))
+    }
+
+    fn timestamp(&self, record: &Value, field: &str) -> Result<Option<DateTime<Utc>>> {
+        self.get(record, field)
+            .map(|v| parse_timestamp(v).ok_or_else(|| invalid(field, self.source(field), "a timestamp")))
+            .transpose()
+    }
+
+    /// A structured field, parsing JSON held in a string (as CSV cells are)
+    fn json(&self, record: &Value, field: &str) -> Option<Value> {
+        self.get(record, field).map(|v| match v {
+            Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| v.clone()),
+            _ => v.clone(),
+        })
+    }
+
+    fn status(&self, record: &Value) -> SpanStatus {
+        let Some(value) = self.get(record, "status") else {
+            return SpanStatus::Unset;
+        };
+        let value = match value {
+            Value::Bool(true) => return SpanStatus::Ok,
+            Value::Bool(false) => return SpanStatus::Error,
+            Value::String(s) => s.trim().to_lowercase(),
+            other => other.to_string(),
+        };
+        let is = |values: &[&str]| values.contains(&value.as_str());
+        if is(ERROR_VALUES) || self.error_values.iter().any(|e| e.eq_ignore_ascii_case(&value)) {
+            SpanStatus::Error
+        } else if is(OK_VALUES) {
+            SpanStatus::Ok
+        } else {
+            SpanStatus::Unset
+        }
+    }
+
+    /// Convert a JSONL or CSV record into a span
+    ///
+    /// Records without a span ID get a random one, and records without a
+    /// trace ID become single-span traces.
+    pub fn to_span(&self, record: &Value) -> Result<Span> {
+        if !record.is_object() {
+            return Err(Error::validation("record is not a JSON object"));
+        }
+
+        let started_at = self
+            .timestamp(record, "started_at")?
+            .ok_or_else(|| Error::validation(format!("missing started_at (field '{}')", self.source("started_at"))))?;
+        let duration_ms = self.number(record, "duration_ms")?;
+        let ended_at = match self.timestamp(record, "ended_at")? {
+            Some(ended_at) => Some(ended_at),
+            None => duration_ms.map(|ms| started_at + chrono::Duration::microseconds((ms * 1000.0) as i64)),
+        };
+
+        let span_id = self
+            .string(record, "span_id")
+            .unwrap_or_else(|| Uuid::new_v4().simple().to_string()[..16].to_string());
+        let trace_id = self.string(record, "trace_id").unwrap_or_else(|| span_id.clone());
+
+        let mut attributes = match self.json(record, "attributes") {
+            Some(Value::Object(attributes)) => attributes,
+            _ => Map::new(),
+        };
+        if self.keep_unmapped {
+            attributes.extend(self.unmapped(record));
+        }
+
+        Ok(Span {
+            id: Uuid::new_v4(),
+            span_id,
+            trace_id,
+            parent_span_id: self.string(record, "parent_span_id"),
+            session_id: self.string(record, "session_id"),
+            user_id: self.string(record, "user_id"),
+            operation_name: self
+                .string(record, "operation_name")
+                .ok_or_else(|| {
+                    Error::validation(format!("missing operation_name (field '{}')", self.source("operation_name")))
+                })?,
+            service_name: self.string(record, "service_name").unwrap_or_else(|| "unknown".to_string()),
+            span_kind: self
+                .json(record, "span_kind")
+                .and_then(|v| serde_json::from_value::<SpanKind>(lowercase(v)).ok())
+                .unwrap_or_default(),
+            started_at,
+            ended_at,
+            duration_ms: None,
+            status: self.status(record),
+            status_message: self.string(record, "status_message"),
+            model_name: self.string(record, "model_name"),
+            model_provider: self.string(record, "model_provider"),
+            tokens_in: self.integer(record, "tokens_in")?,
+            tokens_cached_in: self.integer(record, "tokens_cached_in")?,
+            tokens_out: self.integer(record, "tokens_out")?,
+            tokens_reasoning: self.integer(record, "tokens_reasoning")?,
+            cost_usd: self.number(record, "cost_usd")?,
+            tool_name: self.string(record, "tool_name"),
+            tool_input: self.json(record, "tool_input"),
+            tool_output: self.json(record, "tool_output"),
+            tool_duration_ms: self.number(record, "tool_duration_ms")?,
+            prompt_preview: self.string(record, "prompt_preview"),
+            completion_preview: self.string(record, "completion_preview"),
+            attributes: Value::Object(attributes),
+            events: self
+                .json(record, "events")
+                .and_then(|v| serde_json::from_value::<Vec<SpanEvent>>(v).ok())
+                .unwrap_or_default(),
+            links: self
+                .json(record, "links")
+                .and_then(|v| serde_json::from_value::<Vec<SpanLink>>(v).ok())
+                .unwrap_or_default(),
+            priority: self
+                .json(record, "priority")
+                .and_then(|v| serde_json::from_value::<SpanPriority>(lowercase(v)).ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Top-level source fields no span field is read from
+    fn unmapped(&self, record: &Value) -> Map<String, Value> {
+        let used: Vec<&str> = SPAN_FIELDS
+            .iter()
+            .map(|field| self.source(field).split('.').next().unwrap_or_default())
+            .collect();
+
+        record
+            .as_object()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .filter(|(key, value)| !used.contains(&key.as_str()) && !value.is_null())
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn invalid(field: &str, source: &str, expected: &str) -> Error {
+    Error::validation(format!("{} (field '{}') is not {}", field, source, expected))
+}
+
+fn lowercase(value: Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(s.to_lowercase()),
+        other => other,
+    }
+}
+
+/// A field by name, or by a dotted path into nested objects
+fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    if let Some(value) = record.get(path) {
+        return Some(value);
+    }
+    path.split('.').try_fold(record, |value, key| value.get(key))
+}
+
+/// Parse RFC 3339, `YYYY-MM-DD HH:MM:SS[.f]` (as UTC) or a Unix time in
+/// seconds, milliseconds, microseconds or nanoseconds
+fn parse_timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    let epoch = |n: f64| {
+        // Pick the unit from the magnitude; 1e11 seconds is the year 5138
+        let nanos = match n.abs() {
+            n if n < 1e11 => n * 1e9,
+            n if n < 1e14 => n * 1e6,
+            n if n < 1e17 => n * 1e3,
+            _ => n,
+        };
+        Some(Utc.timestamp_nanos(nanos as i64))
+    };
+
+    match value {
+        Value::Number(n) => epoch(n.as_f64()?),
+        Value::String(s) => {
+            let s = s.trim();
+            DateTime::parse_from_rfc3339(s)
+                .map(|t| t.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+                        .iter()
+                        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+                        .map(|t| Utc.from_utc_datetime(&t))
+                })
+                .or_else(|| s.parse::<f64>().ok().and_then(epoch))
+        }
+        _ => None,
+    }
+}
+
+/// Reads JSONL or CSV records one at a time, numbering them by line
+pub struct RecordReader<R> {
+    lines: std::io::Lines<R>,
+    format: ImportFormat,
+    header: Option<Vec<String>>,
+    line: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Read records of `format`, which must be JSONL or CSV
+    pub fn new(reader: R, format: ImportFormat) -> Result<Self> {
+        if !matches!(format, ImportFormat::Jsonl | ImportFormat::Csv) {
+            return Err(Error::validation("Only JSONL and CSV are read record by record"));
+        }
+        Ok(Self {
+            lines: reader.lines(),
+            format,
+            header: None,
+            line: 0,
+        })
+    }
+
+    fn next_line(&mut self) -> Option<Result<String>> {
+        self.line += 1;
+        self.lines
+            .next()
+            .map(|line| line.map_err(|e| Error::validation(format!("line {}: {}", self.line, e))))
+    }
+
+    /// The next CSV row, joining lines while a quoted field is open
+    fn next_row(&mut self) -> Option<Result<(usize, Vec<String>)>> {
+        let first = self.line + 1;
+        let mut text = match self.next_line()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e)),
+        };
+        while text.matches('"').count() % 2 == 1 {
+            match self.next_line() {
+                Some(Ok(line)) => {
+                    text.push('\n');
+                    text.push_str(&line);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => return Some(Err(Error::validation(format!("line {}: unterminated quoted field", first)))),
+            }
+        }
+        Some(Ok((first, split_csv_row(&text))))
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    /// Line number and record
+    type Item = Result<(usize, Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.format {
+                ImportFormat::Csv => {
+                    let (line, row) = match self.next_row()? {
+                        Ok(row) => row,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    if row.iter().all(String::is_empty) {
+                        continue;
+                    }
+                    let Some(header) = &self.header else {
+                        self.header = Some(row.into_iter().map(|h| h.trim().to_string()).collect());
+                        continue;
+                    };
+                    let record = header
+                        .iter()
+                        .zip(row)
+                        .filter(|(_, value)| !value.is_empty())
+                        .map(|(key, value)| (key.clone(), Value::String(value)))
+                        .collect();
+                    return Some(Ok((line, Value::Object(record))));
+                }
+                _ => {
+                    let text = match self.next_line()? {
+                        Ok(text) => text,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    let line = self.line;
+                    return Some(
+                        serde_json::from_str(&text)
+                            .map(|record| (line, record))
+                            .map_err(|e| Error::validation(format!("line {}: invalid JSON: {}", line, e))),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Split a CSV row into fields, unquoting `"..."` and `""`
+fn split_csv_row(row: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek().is_none() => {}
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mapping_reads_nested_and_default_fields() {
+        let mapping: FieldMapping = toml::from_str(
+            r#"
+            error_values = ["exception"]
+
+            [fields]
+            trace_id = "run_id"
+            operation_name = "event"
+            started_at = "ts"
+            duration_ms = "latency_ms"
+            model_name = "llm.model"
+            tokens_in = "usage.prompt_tokens"
+            status = "level"
+
+            [defaults]
+            service_name = "legacy-logger"
+            "#,
+        )
+        .unwrap();
+        mapping.validate().unwrap();
+
+        let span = mapping
+            .to_span(&json!({
+                "run_id": "run-1",
+                "event": "llm.call",
+                "ts": 1_717_200_000_123_i64,
+                "latency_ms": "250",
+                "llm": { "model": "gpt-4o" },
+                "usage": { "prompt_tokens": 812 },
+                "level": "EXCEPTION",
+                "user": "u-7",
+            }))
+            .unwrap();
+
+        assert_eq!(span.trace_id, "run-1");
+        assert_eq!(span.service_name, "legacy-logger");
+        assert_eq!(span.started_at.timestamp_millis(), 1_717_200_000_123);
+        assert_eq!((span.ended_at.unwrap() - span.started_at).num_milliseconds(), 250);
+        assert_eq!(span.model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(span.tokens_in, Some(812));
+        assert_eq!(span.status, SpanStatus::Error);
+        assert_eq!(span.attributes["user"], "u-7");
+        assert!(span.attributes.get("usage").is_none());
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let mapping = FieldMapping::default();
+        let err = mapping.to_span(&json!({ "operation_name": "x" })).unwrap_err();
+        assert!(err.to_string().contains("started_at"));
+
+        let err = mapping
+            .to_span(&json!({ "operation_name": "x", "started_at": "2024-06-01T00:00:00Z", "tokens_in": "many" }))
+            .unwrap_err();
+        assert!(err.to_string().contains("tokens_in"));
+
+        let mapping: FieldMapping = toml::from_str("[fields]\nmodel = \"m\"").unwrap();
+        assert!(mapping.validate().is_err());
+    }
+
+    #[test]
+    fn test_csv_records() {
+        let csv = "operation_name,started_at,prompt_preview\n\
+                   plan,2024-06-01 10:00:00,\"Say \"\"hi\"\",\nthen stop\"\n\
+                   \n\
+                   act,2024-06-01 10:00:01,\n";
+        let records: Vec<_> = RecordReader::new(csv.as_bytes(), ImportFormat::Csv)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].0, 2);
+        assert_eq!(records[0].1["prompt_preview"], "Say \"hi\",\nthen stop");
+        assert_eq!(records[1].0, 5);
+        assert!(records[1].1.get("prompt_preview").is_none());
+
+        let span = FieldMapping::default().to_span(&records[1].1).unwrap();
+        assert_eq!(span.operation_name, "act");
+    }
+}
@@ -0,0 +1,242 @@
+//! OTLP trace files
+//!
+//! Reads `ExportTraceServiceRequest`s in the OTLP/JSON mapping, as written by
+//! the OpenTelemetry Collector's file exporter (one request per line) or by
+//! `agenttrace traces export --format otlp` (one per file), and protobuf
+//! requests, which are converted to the JSON mapping first.
+//!
+//! The `gen_ai.*` and `agenttrace.*` attributes written on export are read
+//! back into span fields, so an exported trace imports unchanged.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::ImportFormat;
+use crate::error::{Error, Result};
+use crate::export::otlp::{proto, request_json};
+use crate::models::{
+    Span, SpanEvent, SpanKind, SpanLink, SpanPriority, SpanStatus, SESSION_ID_ATTRIBUTE, USER_ID_ATTRIBUTE,
+};
+
+/// Parse the spans in an OTLP file
+pub fn spans_from_otlp(data: &[u8], format: ImportFormat) -> Result<Vec<Span>> {
+    let requests = match format {
+        ImportFormat::OtlpProtobuf => {
+            let request: proto::ExportTraceServiceRequest = prost::Message::decode(data)
+                .map_err(|e| Error::validation(format!("invalid OTLP protobuf: {}", e)))?;
+            vec![request_json(&request)]
+        }
+        ImportFormat::OtlpJson => {
+            let text = std::str::from_utf8(data).map_err(|e| Error::validation(e.to_string()))?;
+            match serde_json::from_str::<Value>(text) {
+                Ok(request) => vec![request],
+                // One request per line
+                Err(_) => text
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .map(|(i, line)| {
+                        serde_json::from_str(line)
+                            .map_err(|e| Error::validation(format!("line {}: invalid JSON: {}", i + 1, e)))
+                    })
+                    .collect::<Result<_>>()?,
+            }
+        }
+        _ => return Err(Error::validation("not an OTLP format")),
+    };
+
+    let mut spans = Vec::new();
+    for request in &requests {
+        let resource_spans = request["resourceSpans"]
+            .as_array()
+            .ok_or_else(|| Error::validation("not an OTLP ExportTraceServiceRequest: no resourceSpans"))?;
+        for resource in resource_spans {
+            let resource_attributes = attributes(&resource["resource"]["attributes"]);
+            let service = resource_attributes
+                .get("service.name")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown");
+
+            for scope in resource["scopeSpans"].as_array().into_iter().flatten() {
+                for span in scope["spans"].as_array().into_iter().flatten() {
+                    spans.push(to_span(span, service)?);
+                }
+            }
+        }
+    }
+    Ok(spans)
+}
+
+fn to_span(span: &Value, service: &str) -> Result<Span> {
+    let mut attrs = attributes(&span["attributes"]);
+    let mut take = |key: &str| attrs.remove(key);
+
+    let started_at =
+        nanos(&span["startTimeUnixNano"]).ok_or_else(|| Error::validation("span without startTimeUnixNano"))?;
+    let ended_at = nanos(&span["endTimeUnixNano"]).filter(|end| *end >= started_at);
+    let string = |value: Option<Value>| value.and_then(|v| v.as_str().map(str::to_string));
+    let integer = |value: Option<Value>| value.and_then(|v| v.as_i64()).map(|n| n as i32);
+
+    // Exported IDs that weren't OTLP-sized keep the original in an attribute
+    let trace_id = string(take("agenttrace.trace_id")).unwrap_or_else(|| hex_id(&span["traceId"]));
+    let span_id = string(take("agenttrace.span_id")).unwrap_or_else(|| hex_id(&span["spanId"]));
+    let parent_span_id = Some(hex_id(&span["parentSpanId"])).filter(|id| !id.is_empty());
+
+    let session_id = string(take(SESSION_ID_ATTRIBUTE));
+    let user_id = string(take(USER_ID_ATTRIBUTE));
+    let model_name = string(take("gen_ai.request.model"));
+    let model_provider = string(take("gen_ai.system"));
+    let tokens_in = integer(take("gen_ai.usage.input_tokens"));
+    let tokens_cached_in = integer(take("gen_ai.usage.cache_read.input_tokens"));
+    let tokens_out = integer(take("gen_ai.usage.output_tokens"));
+    let cost_usd = take("agenttrace.cost_usd").and_then(|v| v.as_f64());
+    let tool_name = string(take("agenttrace.tool_name"));
+    let prompt_preview = string(take("agenttrace.prompt_preview"));
+    let completion_preview = string(take("agenttrace.completion_preview"));
+
+    let status = match span["status"]["code"].as_i64() {
+        Some(1) => SpanStatus::Ok,
+        Some(2) => SpanStatus::Error,
+        _ => SpanStatus::Unset,
+    };
+    let span_kind = match span["kind"].as_i64() {
+        Some(2) => SpanKind::Server,
+        Some(3) => SpanKind::Client,
+        Some(4) => SpanKind::Producer,
+        Some(5) => SpanKind::Consumer,
+        _ => SpanKind::Internal,
+    };
+
+    Ok(Span {
+        id: Uuid::new_v4(),
+        span_id,
+        trace_id,
+        parent_span_id,
+        session_id,
+        user_id,
+        operation_name: span["name"].as_str().unwrap_or_default().to_string(),
+        service_name: service.to_string(),
+        span_kind,
+        started_at,
+        ended_at,
+        duration_ms: None,
+        status,
+        status_message: span["status"]["message"].as_str().filter(|m| !m.is_empty()).map(str::to_string),
+        model_name,
+        model_provider,
+        tokens_in,
+        tokens_cached_in,
+        tokens_out,
+        tokens_reasoning: None,
+        cost_usd,
+        tool_name,
+        tool_input: None,
+        tool_output: None,
+        tool_duration_ms: None,
+        prompt_preview,
+        completion_preview,
+        attributes: Value::Object(attrs),
+        events: span["events"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|event| SpanEvent {
+                name: event["name"].as_str().unwrap_or_default().to_string(),
+                timestamp: nanos(&event["timeUnixNano"]).unwrap_or(started_at),
+                attributes: Value::Object(attributes(&event["attributes"])),
+            })
+            .collect(),
+        links: span["links"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|link| SpanLink {
+                trace_id: hex_id(&link["traceId"]),
+                span_id: hex_id(&link["spanId"]),
+                attributes: Value::Object(attributes(&link["attributes"])),
+            })
+            .collect(),
+        priority: SpanPriority::default(),
+    })
+}
+
+/// Hex IDs are written lowercase on export; some exporters use base64
+fn hex_id(value: &Value) -> String {
+    let id = value.as_str().unwrap_or_default();
+    if hex::decode(id).is_ok() {
+        return id.to_lowercase();
+    }
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(id)
+        .map(hex::encode)
+        .unwrap_or_else(|_| id.to_string())
+}
+
+/// 64-bit integers are strings in OTLP/JSON, but numbers are accepted too
+fn nanos(value: &Value) -> Option<DateTime<Utc>> {
+    let nanos = match value {
+        Value::String(s) => s.parse::<i64>().ok()?,
+        Value::Number(n) => n.as_i64()?,
+        _ => return None,
+    };
+    (nanos > 0).then(|| Utc.timestamp_nanos(nanos))
+}
+
+/// OTLP key/values as a JSON object
+fn attributes(value: &Value) -> Map<String, Value> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|kv| Some((kv["key"].as_str()?.to_string(), any_value(&kv["value"])?)))
+        .collect()
+}
+
+fn any_value(value: &Value) -> Option<Value> {
+    let (kind, inner) = value.as_object()?.iter().next()?;
+    match kind.as_str() {
+        "stringValue" | "boolValue" | "doubleValue" => Some(inner.clone()),
+        "intValue" => match inner {
+            Value::String(s) => s.parse::<i64>().ok().map(Value::from),
+            other => Some(other.clone()),
+        },
+        "arrayValue" => Some(Value::Array(
+            inner["values"].as_array().into_iter().flatten().filter_map(any_value).collect(),
+        )),
+        "kvlistValue" => Some(Value::Object(attributes(&inner["values"]))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SpanGenerator;
+    use crate::export::otlp::{to_otlp_json, to_otlp_protobuf};
+
+    #[test]
+    fn test_exported_traces_round_trip() {
+        let trace = SpanGenerator::new(Some(11)).next_trace();
+
+        let json = serde_json::to_vec(&to_otlp_json(&trace)).unwrap();
+        let protobuf = to_otlp_protobuf(&trace);
+        for imported in [
+            spans_from_otlp(&json, ImportFormat::OtlpJson).unwrap(),
+            spans_from_otlp(&protobuf, ImportFormat::OtlpProtobuf).unwrap(),
+        ] {
+            assert_eq!(imported.len(), trace.len());
+            for span in &trace {
+                let copy = imported.iter().find(|s| s.span_id == span.span_id).unwrap();
+                assert_eq!(copy.trace_id, span.trace_id);
+                assert_eq!(copy.parent_span_id, span.parent_span_id);
+                assert_eq!(copy.service_name, span.service_name);
+                assert_eq!(copy.model_name, span.model_name);
+                assert_eq!(copy.tokens_in, span.tokens_in);
+                assert_eq!(copy.status, span.status);
+                assert_eq!(copy.started_at, span.started_at);
+            }
+        }
+    }
+}
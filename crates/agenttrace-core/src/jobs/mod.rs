@@ -0,0 +1,701 @@
+//! Background jobs for heavy analytics queries
+//!
+//! Multi-month rollups and exports run on a small worker pool instead of in
+//! HTTP handlers. A job walks its range one day at a time, so each step is an
+//! ordinary bounded query for the span store, progress is reported after
+//! every day and a cancellation takes effect before the next one. Jobs are
+//! kept in memory and are lost when the collector restarts.
+//!
+//! Bulk exports write their spans to a CSV, JSONL or Parquet file that is
+//! either uploaded to S3 or spooled to disk until the job is pruned. Span
+//! export results are spooled the same way and read back when the job is
+//! fetched, so finished jobs don't hold their output in memory. Outputs
+//! larger than `max_result_bytes` fail the job.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::archive;
+use crate::config::JobsConfig;
+use crate::db::SpanStore;
+use crate::error::{Error, Result};
+use crate::export::bulk;
+use crate::models::{
+    BulkExportRequest, CostMetric, ExportDestination, ExportFileFormat, JobStatus,
+    OperationEfficiency, PageCursor, QueryJob, QueryJobRequest, Span, SpanQuery,
+};
+
+/// Spans fetched per page while exporting
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Start of spool file names, so leftovers can be told apart from other files
+const SPOOL_PREFIX: &str = "agenttrace-job-";
+
+struct JobEntry {
+    job: QueryJob,
+    cancelled: Arc<AtomicBool>,
+    /// Spooled span export result, or bulk export file awaiting download
+    spooled: Option<PathBuf>,
+}
+
+/// Queue and worker pool for query jobs
+pub struct QueryJobs {
+    store: Arc<dyn SpanStore>,
+    config: JobsConfig,
+    jobs: Mutex<HashMap<Uuid, JobEntry>>,
+    queue: mpsc::Sender<Uuid>,
+    receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<Uuid>>>,
+}
+
+impl QueryJobs {
+    /// Create the job queue; no jobs run until [`QueryJobs::start`]
+    pub fn new(store: Arc<dyn SpanStore>, config: &JobsConfig) -> Self {
+        let (queue, receiver) = mpsc::channel(config.max_queued.max(1));
+
+        Self {
+            store,
+            config: config.clone(),
+            jobs: Mutex::new(HashMap::new()),
+            queue,
+            receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
+        }
+    }
+
+    /// Spawn the workers
+    ///
+    /// Spool files left by a previous run are deleted first, since their jobs
+    /// are gone.
+    pub fn start(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        info!(workers = self.config.workers, "Starting query job workers");
+        if let Ok(entries) = std::fs::read_dir(self.config.spool_dir()) {
+            for entry in entries.flatten() {
+                if entry.file_name().to_string_lossy().starts_with(SPOOL_PREFIX) {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        (0..self.config.workers.max(1))
+            .map(|_| {
+                let jobs = self.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let next = jobs.receiver.lock().await.recv().await;
+                        match next {
+                            Some(id) => jobs.run(id).await,
+                            None => break,
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Queue a job
+    ///
+    /// Fails with [`Error::RateLimit`] when the queue is full.
+    pub fn submit(&self, request: QueryJobRequest) -> Result<QueryJob> {
+        let (since, until) = request.range();
+        if since >= until {
+            return Err(Error::validation("'since' must be before 'until'"));
+        }
+        if until - since > Duration::days(i64::from(self.config.max_range_days)) {
+            return Err(Error::validation(format!(
+                "Jobs can cover at most {} days",
+                self.config.max_range_days
+            )));
+        }
+        match &request {
+            QueryJobRequest::Export { filter: Some(filter), .. } => {
+                SpanQuery::parse(filter)?;
+            }
+            QueryJobRequest::BulkExport(export) => {
+                SpanQuery::parse(export.filter.as_deref().unwrap_or_default())?;
+                if let ExportDestination::S3 { url } = &export.destination {
+                    if !url.starts_with("s3://") {
+                        return Err(Error::validation("S3 destination URL must start with s3://"));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let job = QueryJob::new(request);
+        let mut jobs = self.jobs.lock();
+        self.queue.try_send(job.id).map_err(|_| Error::RateLimit)?;
+        jobs.insert(
+            job.id,
+            JobEntry {
+                job: job.clone(),
+                cancelled: Arc::new(AtomicBool::new(false)),
+                spooled: None,
+            },
+        );
+        let pruned = prune(&mut jobs, self.config.retained);
+        drop(jobs);
+        for path in pruned {
+            remove_spooled(&path);
+        }
+
+        Ok(job)
+    }
+
+    /// Get a job, with its result once completed unless the result was
+    /// spooled to disk; see [`QueryJobs::fetch`]
+    pub fn get(&self, id: Uuid) -> Option<QueryJob> {
+        self.jobs.lock().get(&id).map(|entry| entry.job.clone())
+    }
+
+    /// Get a job with its result, reading a spooled span export back from
+    /// disk
+    pub async fn fetch(&self, id: Uuid) -> Result<Option<QueryJob>> {
+        let (mut job, spooled) = match self.jobs.lock().get(&id) {
+            Some(entry) => (entry.job.clone(), entry.spooled.clone()),
+            None => return Ok(None),
+        };
+        if let (QueryJobRequest::Export { .. }, Some(path)) = (&job.request, spooled) {
+            match tokio::fs::read(&path).await {
+                Ok(data) => job.result = Some(serde_json::from_slice(&data)?),
+                // Pruned while being read
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(Error::internal(format!("Failed to read job result: {}", e))),
+            }
+        }
+        Ok(Some(job))
+    }
+
+    /// The spooled file of a completed bulk export with a download
+    /// destination
+    pub fn download(&self, id: Uuid) -> Option<(ExportFileFormat, PathBuf)> {
+        let jobs = self.jobs.lock();
+        let entry = jobs.get(&id)?;
+        match &entry.job.request {
+            QueryJobRequest::BulkExport(export) => Some((export.format, entry.spooled.clone()?)),
+            _ => None,
+        }
+    }
+
+    /// Cancel a job
+    ///
+    /// Queued jobs are cancelled at once; running jobs stop after the day
+    /// they are working on. Finished jobs are left as they are.
+    pub fn cancel(&self, id: Uuid) -> Option<QueryJob> {
+        let mut jobs = self.jobs.lock();
+        let entry = jobs.get_mut(&id)?;
+
+        entry.cancelled.store(true, Ordering::Relaxed);
+        if entry.job.status == JobStatus::Queued {
+            entry.job.status = JobStatus::Cancelled;
+            entry.job.finished_at = Some(Utc::now());
+        }
+
+        Some(entry.job.clone())
+    }
+
+    async fn run(&self, id: Uuid) {
+        let Some((request, cancelled)) = self.begin(id) else {
+            return;
+        };
+
+        let outcome = match &request {
+            QueryJobRequest::CostRollup { group_by, service, since, until } => {
+                self.cost_rollup(id, group_by, service.as_deref(), *since, *until, &cancelled)
+                    .await
+                    .map(JobOutput::from)
+            }
+            QueryJobRequest::EfficiencyRollup { service, model, since, until } => {
+                let (service, model) = (service.as_deref(), model.as_deref());
+                self.efficiency_rollup(id, service, model, *since, *until, &cancelled)
+                    .await
+                    .map(JobOutput::from)
+            }
+            QueryJobRequest::Export { filter, since, until } => {
+                self.export(id, filter.as_deref(), *since, *until, &cancelled).await
+            }
+            QueryJobRequest::BulkExport(export) => self.bulk_export(id, export, &cancelled).await,
+        };
+
+        let mut jobs = self.jobs.lock();
+        let spooled = outcome.as_ref().ok().and_then(|output| output.spooled.clone());
+        let Some(entry) = jobs.get_mut(&id).filter(|_| !cancelled.load(Ordering::Relaxed)) else {
+            // Cancelled, or pruned while running
+            if let Some(entry) = jobs.get_mut(&id) {
+                entry.job.finished_at = Some(Utc::now());
+                entry.job.status = JobStatus::Cancelled;
+            }
+            drop(jobs);
+            if let Some(path) = spooled {
+                remove_spooled(&path);
+            }
+            return;
+        };
+
+        entry.job.finished_at = Some(Utc::now());
+        match outcome {
+            Ok(JobOutput { result, spooled }) => {
+                entry.job.status = JobStatus::Completed;
+                entry.job.progress = 1.0;
+                entry.job.result = result;
+                entry.spooled = spooled;
+            }
+            Err(e) => {
+                warn!(job = %id, error = %e, "Query job failed");
+                entry.job.status = JobStatus::Failed;
+                entry.job.error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Mark a queued job as running, unless it was cancelled or dropped
+    /// while waiting
+    fn begin(&self, id: Uuid) -> Option<(QueryJobRequest, Arc<AtomicBool>)> {
+        let mut jobs = self.jobs.lock();
+        let entry = jobs.get_mut(&id)?;
+        if entry.job.status != JobStatus::Queued {
+            return None;
+        }
+
+        entry.job.status = JobStatus::Running;
+        entry.job.started_at = Some(Utc::now());
+        Some((entry.job.request.clone(), entry.cancelled.clone()))
+    }
+
+    fn set_progress(&self, id: Uuid, done: usize, total: usize) {
+        if let Some(entry) = self.jobs.lock().get_mut(&id) {
+            entry.job.progress = done as f64 / total.max(1) as f64;
+        }
+    }
+
+    async fn cost_rollup(
+        &self,
+        id: Uuid,
+        group_by: &str,
+        service: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        cancelled: &AtomicBool,
+    ) -> Result<serde_json::Value> {
+        let days = day_chunks(since, until);
+        let mut groups: HashMap<String, CostMetric> = HashMap::new();
+
+        for (i, (start, end)) in days.iter().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            for metric in self.store.get_cost_by_group(service, group_by, *start, *end).await? {
+                let total = groups.entry(metric.group.clone()).or_insert(CostMetric {
+                    group: metric.group,
+                    total_cost_usd: 0.0,
+                    total_tokens: 0,
+                    call_count: 0,
+                });
+                total.total_cost_usd += metric.total_cost_usd;
+                total.total_tokens += metric.total_tokens;
+                total.call_count += metric.call_count;
+            }
+
+            self.set_progress(id, i + 1, days.len());
+        }
+
+        let mut groups: Vec<CostMetric> = groups.into_values().collect();
+        if matches!(group_by, "day" | "hour") {
+            groups.sort_by(|a, b| a.group.cmp(&b.group));
+        } else {
+            groups.sort_by(|a, b| b.total_cost_usd.total_cmp(&a.total_cost_usd));
+        }
+
+        Ok(serde_json::to_value(groups)?)
+    }
+
+    /// Traces that run across midnight are counted once in each day they
+    /// touch, so trace counts can run slightly high
+    async fn efficiency_rollup(
+        &self,
+        id: Uuid,
+        service: Option<&str>,
+        model: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        cancelled: &AtomicBool,
+    ) -> Result<serde_json::Value> {
+        let days = day_chunks(since, until);
+        let mut operations: HashMap<(String, String), OperationEfficiency> = HashMap::new();
+
+        for (i, (start, end)) in days.iter().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            for op in self.store.get_operation_efficiency(service, model, *start, *end).await? {
+                let key = (op.service_name.clone(), op.operation_name.clone());
+                match operations.get_mut(&key) {
+                    Some(total) => {
+                        total.span_count += op.span_count;
+                        total.tokens_in += op.tokens_in;
+                        total.tokens_out += op.tokens_out;
+                        total.total_cost_usd += op.total_cost_usd;
+                        total.error_cost_usd += op.error_cost_usd;
+                        total.trace_count += op.trace_count;
+                        total.successful_traces += op.successful_traces;
+                    }
+                    None => {
+                        operations.insert(key, op);
+                    }
+                }
+            }
+
+            self.set_progress(id, i + 1, days.len());
+        }
+
+        let mut operations: Vec<OperationEfficiency> = operations.into_values().collect();
+        operations.iter_mut().for_each(OperationEfficiency::compute_ratios);
+        operations.sort_by(|a, b| b.total_cost_usd.total_cmp(&a.total_cost_usd));
+
+        Ok(serde_json::to_value(operations)?)
+    }
+
+    /// Spool the matching spans to disk, to be read back by
+    /// [`QueryJobs::fetch`]
+    async fn export(
+        &self,
+        id: Uuid,
+        filter: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        cancelled: &AtomicBool,
+    ) -> Result<JobOutput> {
+        let spans = self.collect_spans(id, filter, since, until, cancelled).await?;
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(serde_json::Value::Null.into());
+        }
+
+        let data = serde_json::to_vec(&serde_json::json!({
+            "count": spans.len(),
+            "spans": spans,
+        }))?;
+        let path = self.spool(id, "json", &data).await?;
+        Ok(JobOutput { result: None, spooled: Some(path) })
+    }
+
+    async fn bulk_export(
+        &self,
+        id: Uuid,
+        export: &BulkExportRequest,
+        cancelled: &AtomicBool,
+    ) -> Result<JobOutput> {
+        let spans = self
+            .collect_spans(id, export.filter.as_deref(), export.since, export.until, cancelled)
+            .await?;
+        if cancelled.load(Ordering::Relaxed) {
+            return Ok(serde_json::Value::Null.into());
+        }
+
+        let data = bulk::encode_spans(&spans, export.format)?;
+        self.check_size(data.len())?;
+        let mut result = serde_json::json!({
+            "count": spans.len(),
+            "format": export.format,
+            "size_bytes": data.len(),
+        });
+
+        match &export.destination {
+            ExportDestination::Download => {
+                let path = self.spool(id, export.format.extension(), &data).await?;
+                result["download_url"] = format!("/api/v1/exports/{}/download", id).into();
+                Ok(JobOutput { result: Some(result), spooled: Some(path) })
+            }
+            ExportDestination::S3 { url } => {
+                let location = object_url(url, id, export.format);
+                let (store, path) = archive::open_store(&location)?;
+                store
+                    .put(&path, Bytes::from(data))
+                    .await
+                    .map_err(|e| Error::internal(format!("Upload to {} failed: {}", location, e)))?;
+
+                result["location"] = location.into();
+                Ok(result.into())
+            }
+        }
+    }
+
+    /// Fail if a job's output is over `max_result_bytes`
+    fn check_size(&self, size: usize) -> Result<()> {
+        if size as u64 > self.config.max_result_bytes {
+            return Err(Error::validation(format!(
+                "Job output is {} bytes, more than the {} byte limit; narrow the filter or range",
+                size, self.config.max_result_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write a job's output to the spool directory, returning its path
+    async fn spool(&self, id: Uuid, extension: &str, data: &[u8]) -> Result<PathBuf> {
+        self.check_size(data.len())?;
+        let dir = self.config.spool_dir();
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let path = dir.join(format!("{}{}.{}", SPOOL_PREFIX, id, extension));
+        tokio::fs::write(&path, data).await?;
+        Ok(path)
+    }
+
+    /// Spans matching `filter` over `[since, until)`, oldest first
+    ///
+    /// Stops early, returning what was collected so far, once the job is
+    /// cancelled.
+    async fn collect_spans(
+        &self,
+        id: Uuid,
+        filter: Option<&str>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        cancelled: &AtomicBool,
+    ) -> Result<Vec<Span>> {
+        let query = SpanQuery::parse(filter.unwrap_or_default())?;
+        let days = day_chunks(since, until);
+        let mut spans: Vec<Span> = Vec::new();
+
+        for (i, (start, end)) in days.iter().enumerate() {
+            let mut cursor: Option<PageCursor> = None;
+            loop {
+                if cancelled.load(Ordering::Relaxed) {
+                    return Ok(spans);
+                }
+
+                let (page, _) = self
+                    .store
+                    .search(
+                        None,
+                        query.service.as_deref(),
+                        query.model.as_deref(),
+                        None,
+                        None,
+                        &[],
+                        query.has_negative_feedback,
+                        None,
+                        None,
+                        None,
+                        None,
+                        Some(*start),
+                        Some(*end),
+                        "started_at",
+                        false,
+                        EXPORT_PAGE_SIZE,
+                        0,
+                        cursor.as_ref(),
+                    )
+                    .await?;
+
+                let full = page.len() as i64 == EXPORT_PAGE_SIZE;
+                cursor = page.last().map(PageCursor::after);
+                spans.extend(page.into_iter().filter(|s| query.matches(s) && s.started_at < *end));
+
+                if spans.len() > self.config.max_export_spans {
+                    return Err(Error::validation(format!(
+                        "Export matches more than {} spans; narrow the filter or range",
+                        self.config.max_export_spans
+                    )));
+                }
+                if !full {
+                    break;
+                }
+            }
+
+            self.set_progress(id, i + 1, days.len());
+        }
+
+        Ok(spans)
+    }
+}
+
+/// What a finished job produced
+struct JobOutput {
+    /// Result kept with the job
+    result: Option<serde_json::Value>,
+    /// Spooled span export result or bulk export file
+    spooled: Option<PathBuf>,
+}
+
+impl From<serde_json::Value> for JobOutput {
+    fn from(result: serde_json::Value) -> Self {
+        Self { result: Some(result), spooled: None }
+    }
+}
+
+fn remove_spooled(path: &std::path::Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!(path = %path.display(), error = %e, "Failed to remove spooled job output");
+    }
+}
+
+/// Object URL a bulk export is uploaded to; a URL ending in `/` names a
+/// directory, which gets a file named after the job
+fn object_url(url: &str, id: Uuid, format: ExportFileFormat) -> String {
+    if url.ends_with('/') {
+        format!("{}agenttrace-export-{}.{}", url, id, format.extension())
+    } else {
+        url.to_string()
+    }
+}
+
+/// Drop the oldest finished jobs beyond `retained`, returning the spool
+/// files they leave behind
+fn prune(jobs: &mut HashMap<Uuid, JobEntry>, retained: usize) -> Vec<PathBuf> {
+    let mut finished: Vec<(DateTime<Utc>, Uuid)> = jobs
+        .values()
+        .filter_map(|e| e.job.finished_at.map(|at| (at, e.job.id)))
+        .collect();
+    if finished.len() <= retained {
+        return Vec::new();
+    }
+
+    finished.sort();
+    finished[..finished.len() - retained]
+        .iter()
+        .filter_map(|(_, id)| jobs.remove(id)?.spooled)
+        .collect()
+}
+
+/// Split `[since, until)` into day-long windows, the last one possibly
+/// shorter
+fn day_chunks(since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut chunks = Vec::new();
+    let mut start = since;
+    while start < until {
+        let end = (start + Duration::days(1)).min(until);
+        chunks.push((start, end));
+        start = end;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::tests::create_test_span;
+    use crate::db::SqliteStore;
+
+    async fn jobs_with_spans(config: JobsConfig) -> (QueryJobs, DateTime<Utc>, DateTime<Utc>) {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let spans: Vec<Span> = (0..3).map(|i| create_test_span("t1", &format!("s{}", i), None, 10.0)).collect();
+        store.insert_batch(&spans).await.unwrap();
+        let until = Utc::now();
+        (QueryJobs::new(Arc::new(store), &config), until - Duration::hours(1), until)
+    }
+
+    #[test]
+    fn test_day_chunks() {
+        let since: DateTime<Utc> = "2025-03-01T06:00:00Z".parse().unwrap();
+        let until: DateTime<Utc> = "2025-03-03T12:00:00Z".parse().unwrap();
+
+        let chunks = day_chunks(since, until);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].0, since);
+        assert_eq!(chunks[2].1, until);
+        assert!(chunks.windows(2).all(|w| w[0].1 == w[1].0));
+
+        assert!(day_chunks(until, since).is_empty());
+    }
+
+    #[test]
+    fn test_job_request_parses() {
+        let request: QueryJobRequest = serde_json::from_value(serde_json::json!({
+            "kind": "cost_rollup",
+            "group_by": "model",
+            "since": "2025-01-01T00:00:00Z",
+            "until": "2025-04-01T00:00:00Z",
+        }))
+        .unwrap();
+
+        let (since, until) = request.range();
+        assert_eq!((until - since).num_days(), 90);
+        assert!(matches!(request, QueryJobRequest::CostRollup { service: None, .. }));
+    }
+
+    #[test]
+    fn test_bulk_export_defaults_to_download() {
+        let request: BulkExportRequest = serde_json::from_value(serde_json::json!({
+            "format": "parquet",
+            "since": "2025-03-01T00:00:00Z",
+            "until": "2025-04-01T00:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(request.destination, ExportDestination::Download);
+
+        let id = Uuid::nil();
+        assert_eq!(
+            object_url("s3://finance/exports/", id, request.format),
+            format!("s3://finance/exports/agenttrace-export-{}.parquet", id)
+        );
+        assert_eq!(object_url("s3://finance/march.csv", id, ExportFileFormat::Csv), "s3://finance/march.csv");
+    }
+
+    #[tokio::test]
+    async fn test_export_results_are_spooled() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = JobsConfig {
+            spool_dir: Some(dir.path().to_string_lossy().into_owned()),
+            retained: 1,
+            ..JobsConfig::default()
+        };
+        let (jobs, since, until) = jobs_with_spans(config).await;
+
+        let job = jobs.submit(QueryJobRequest::Export { filter: None, since, until }).unwrap();
+        jobs.run(job.id).await;
+        assert_eq!(jobs.get(job.id).unwrap().status, JobStatus::Completed);
+        // Only the spool file holds the spans
+        assert!(jobs.get(job.id).unwrap().result.is_none());
+        let spooled = jobs.jobs.lock()[&job.id].spooled.clone().unwrap();
+        assert!(spooled.starts_with(dir.path()));
+
+        let fetched = jobs.fetch(job.id).await.unwrap().unwrap();
+        assert_eq!(fetched.result.unwrap()["count"], 3);
+
+        let export = BulkExportRequest {
+            filter: None,
+            format: ExportFileFormat::Jsonl,
+            destination: ExportDestination::Download,
+            since,
+            until,
+        };
+        let bulk = jobs.submit(QueryJobRequest::BulkExport(export)).unwrap();
+        jobs.run(bulk.id).await;
+        let (_, file) = jobs.download(bulk.id).unwrap();
+        assert_eq!(std::fs::read_to_string(file).unwrap().lines().count(), 3);
+
+        // Pruning the first job removes its spool file
+        jobs.submit(QueryJobRequest::Export { filter: None, since, until }).unwrap();
+        assert!(jobs.get(job.id).is_none());
+        assert!(!spooled.exists());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_results_fail_the_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = JobsConfig {
+            spool_dir: Some(dir.path().to_string_lossy().into_owned()),
+            max_result_bytes: 100,
+            ..JobsConfig::default()
+        };
+        let (jobs, since, until) = jobs_with_spans(config).await;
+
+        let job = jobs.submit(QueryJobRequest::Export { filter: None, since, until }).unwrap();
+        jobs.run(job.id).await;
+        let job = jobs.get(job.id).unwrap();
+        assert_eq!(job.status, JobStatus::Failed);
+        assert!(job.error.unwrap().contains("byte limit"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -8,7 +8,8 @@
 //! ## Architecture
 //!
 //! - **Collector**: High-performance telemetry ingestion via UDP/gRPC
-//! - **Storage**: TimescaleDB for time-series data, Redis for real-time
+//! - **Storage**: TimescaleDB for time-series data, Redis for real-time, or an
+//!   embedded SQLite database with in-process streaming for local development
 //! - **API**: REST API for queries and management
 //! - **TUI**: Terminal-based dashboard
 //!
@@ -20,6 +21,9 @@
 //!
 //! # View the TUI dashboard
 //! agenttrace dashboard
+//!
+//! # Or run everything locally without Docker
+//! agenttrace dev --no-db
 //! ```
 
 #![warn(missing_docs)]
@@ -30,13 +34,31 @@
 #![allow(clippy::missing_panics_doc)]
 
 pub mod alerting;
+pub mod annotations;
 pub mod api;
+pub mod archive;
+pub mod audit;
+pub mod budgets;
 pub mod collector;
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod export;
+pub mod forwarding;
+pub mod import;
+pub mod jobs;
+pub mod locale;
 pub mod models;
+pub mod net;
+pub mod payloads;
+pub mod prometheus;
+pub mod proxy;
+pub mod reports;
+pub mod slos;
+pub mod subscriptions;
 pub mod tui;
+pub mod views;
+pub mod web;
 
 pub use config::Config;
 pub use error::{Error, Result};
@@ -45,7 +67,7 @@ pub use error::{Error, Result};
 pub mod prelude {
     pub use crate::collector::{Collector, Pipeline};
     pub use crate::config::Config;
-    pub use crate::db::Database;
+    pub use crate::db::{Database, SpanStore, Storage};
     pub use crate::error::{Error, Result};
     pub use crate::models::*;
 }
@@ -0,0 +1,203 @@
+//! Locale-aware number and currency formatting for the TUI and CLI
+//!
+//! Only the conventions that matter for metrics are covered: thousands
+//! separators, the decimal mark and where the currency symbol goes. Locales
+//! without a known convention are formatted as `en-US`.
+
+use crate::config::DisplayConfig;
+
+/// Formats counts, decimals and costs for one locale and currency
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    group: &'static str,
+    decimal: char,
+    symbol: String,
+    symbol_after: bool,
+    usd_rate: f64,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::new(&DisplayConfig {
+            locale: "en-US".to_string(),
+            currency: "USD".to_string(),
+            usd_rate: 1.0,
+        })
+    }
+}
+
+impl NumberFormat {
+    /// Build the format for a display configuration
+    ///
+    /// Accepts BCP 47 tags (`de-DE`) as well as POSIX locale names
+    /// (`de_DE.UTF-8`).
+    pub fn new(config: &DisplayConfig) -> Self {
+        let tag = config
+            .locale
+            .split(|c| c == '.' || c == '@')
+            .next()
+            .unwrap_or_default()
+            .replace('_', "-")
+            .to_lowercase();
+        let (language, region) = tag.split_once('-').unwrap_or((tag.as_str(), ""));
+
+        let (group, decimal) = separators(language, region);
+        let symbol = currency_symbol(&config.currency);
+        // Most locales with a decimal comma put the symbol after the amount
+        let symbol_after = decimal == ',' && !matches!((language, region), ("nl", _) | ("pt", "br"));
+
+        Self {
+            group,
+            decimal,
+            symbol,
+            symbol_after,
+            usd_rate: if config.usd_rate > 0.0 { config.usd_rate } else { 1.0 },
+        }
+    }
+
+    /// An integer with thousands separators, e.g. `1.234.567`
+    pub fn integer(&self, n: i64) -> String {
+        self.decimal(n as f64, 0)
+    }
+
+    /// A number rounded to `places` decimals, e.g. `1.234,5`
+    pub fn decimal(&self, value: f64, places: usize) -> String {
+        let formatted = format!("{:.*}", places, value.abs());
+        let (whole, fraction) = formatted.split_once('.').unwrap_or((formatted.as_str(), ""));
+
+        let mut out = String::with_capacity(formatted.len() + whole.len() / 3 * self.group.len() + 1);
+        if value < 0.0 && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                out.push_str(self.group);
+            }
+            out.push(digit);
+        }
+        if !fraction.is_empty() {
+            out.push(self.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// A count abbreviated past a thousand, e.g. `1,2K` or `3,4M`
+    pub fn compact(&self, n: i64) -> String {
+        if n >= 1_000_000 {
+            format!("{}M", self.decimal(n as f64 / 1_000_000.0, 1))
+        } else if n >= 1_000 {
+            format!("{}K", self.decimal(n as f64 / 1_000.0, 1))
+        } else {
+            n.to_string()
+        }
+    }
+
+    /// A percentage, e.g. `12,5%`
+    pub fn percent(&self, value: f64, places: usize) -> String {
+        format!("{}%", self.decimal(value, places))
+    }
+
+    /// A cost recorded in USD, converted to the display currency, e.g.
+    /// `1.234,56 €`
+    pub fn currency(&self, usd: f64, places: usize) -> String {
+        let amount = self.decimal(usd * self.usd_rate, places);
+        if self.symbol_after {
+            return format!("{}\u{a0}{}", amount, self.symbol);
+        }
+
+        // Letter symbols such as CHF, and decimal-comma locales, leave a space
+        if self.decimal == ',' || self.symbol.ends_with(char::is_alphabetic) {
+            format!("{}\u{a0}{}", self.symbol, amount)
+        } else {
+            format!("{}{}", self.symbol, amount)
+        }
+    }
+}
+
+/// Thousands separator and decimal mark for a language and region
+fn separators(language: &str, region: &str) -> (&'static str, char) {
+    match (language, region) {
+        ("de" | "fr" | "it", "ch") => ("\u{2019}", '.'),
+        ("es", "mx") => (",", '.'),
+        ("fr", _) => ("\u{202f}", ','),
+        ("de" | "nl" | "es" | "it" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl" | "sr", _) => {
+            (".", ',')
+        }
+        ("sv" | "nb" | "nn" | "no" | "fi" | "pl" | "cs" | "sk" | "ru" | "uk" | "hu" | "bg" | "lt" | "lv" | "et", _) => {
+            ("\u{a0}", ',')
+        }
+        _ => (",", '.'),
+    }
+}
+
+/// Symbol for an ISO 4217 currency code, or the code itself
+fn currency_symbol(code: &str) -> String {
+    let code = code.trim().to_uppercase();
+    let symbol = match code.as_str() {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" | "CNY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "BRL" => "R$",
+        "CAD" => "CA$",
+        "AUD" => "A$",
+        "SEK" | "NOK" | "DKK" => "kr",
+        "PLN" => "zł",
+        _ => return code,
+    };
+    symbol.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(locale: &str, currency: &str) -> NumberFormat {
+        NumberFormat::new(&DisplayConfig {
+            locale: locale.to_string(),
+            currency: currency.to_string(),
+            usd_rate: 1.0,
+        })
+    }
+
+    #[test]
+    fn test_en_us() {
+        let f = format("en-US", "USD");
+        assert_eq!(f.integer(1_234_567), "1,234,567");
+        assert_eq!(f.decimal(-1234.5, 2), "-1,234.50");
+        assert_eq!(f.currency(1234.56, 2), "$1,234.56");
+        assert_eq!(f.compact(1_500), "1.5K");
+        assert_eq!(f.integer(999), "999");
+    }
+
+    #[test]
+    fn test_european_locales() {
+        let de = format("de_DE.UTF-8", "EUR");
+        assert_eq!(de.currency(1234.56, 2), "1.234,56\u{a0}€");
+        assert_eq!(de.compact(2_300_000), "2,3M");
+        assert_eq!(de.percent(12.5, 1), "12,5%");
+
+        let nl = format("nl-NL", "EUR");
+        assert_eq!(nl.currency(1234.56, 2), "€\u{a0}1.234,56");
+
+        let fr = format("fr-FR", "EUR");
+        assert_eq!(fr.integer(1_234_567), "1\u{202f}234\u{202f}567");
+
+        let ch = format("de-CH", "CHF");
+        assert_eq!(ch.currency(1234.5, 2), "CHF\u{a0}1\u{2019}234.50");
+    }
+
+    #[test]
+    fn test_conversion_and_fallback() {
+        let f = NumberFormat::new(&DisplayConfig {
+            locale: "C".to_string(),
+            currency: "gbp".to_string(),
+            usd_rate: 0.5,
+        });
+        assert_eq!(f.currency(10.0, 2), "£5.00");
+        assert_eq!(f.decimal(-0.001, 2), "0.00");
+    }
+}
@@ -0,0 +1,311 @@
+//! Aggregation queries for custom dashboards
+//!
+//! A query picks one metric and optionally groups it by a span dimension
+//! and/or time bucket:
+//!
+//! ```json
+//! {
+//!   "metric": "p95_latency",
+//!   "group_by": "model",
+//!   "bucket": "1h",
+//!   "filters": [{"field": "service_name", "operator": "eq", "value": "planner"}],
+//!   "since": "2024-06-01T00:00:00Z"
+//! }
+//! ```
+//!
+//! Every part is checked against a fixed set of metrics, groupings and
+//! bucket widths before it reaches the database, and filter values are only
+//! ever sent as bind parameters.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::SearchFilter;
+use crate::error::{Error, Result};
+
+/// Bucket widths a query may use, with their length in seconds
+pub const AGGREGATE_BUCKETS: &[(&str, i64)] = &[
+    ("1m", 60),
+    ("5m", 300),
+    ("15m", 900),
+    ("30m", 1_800),
+    ("1h", 3_600),
+    ("6h", 21_600),
+    ("12h", 43_200),
+    ("1d", 86_400),
+];
+
+/// Most time buckets a query may produce
+pub const MAX_AGGREGATE_BUCKETS: i64 = 1_000;
+
+/// Groups returned when a query doesn't set `limit`
+const DEFAULT_GROUP_LIMIT: i64 = 10;
+
+/// Most groups a query may return
+const MAX_GROUP_LIMIT: i64 = 100;
+
+/// Value computed for each group and bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateMetric {
+    /// Number of spans
+    Count,
+    /// Total cost in USD
+    SumCost,
+    /// Total input and output tokens
+    SumTokens,
+    /// Mean span duration in milliseconds
+    AvgLatency,
+    /// Median span duration in milliseconds
+    P50Latency,
+    /// 95th percentile span duration in milliseconds
+    P95Latency,
+    /// 99th percentile span duration in milliseconds
+    P99Latency,
+    /// Percentage of spans with error status
+    ErrorRate,
+}
+
+impl AggregateMetric {
+    /// Latency percentile for the percentile metrics
+    pub fn percentile(&self) -> Option<f64> {
+        match self {
+            Self::P50Latency => Some(0.5),
+            Self::P95Latency => Some(0.95),
+            Self::P99Latency => Some(0.99),
+            _ => None,
+        }
+    }
+}
+
+/// Span dimension results are grouped by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregateGroup {
+    /// Service name
+    Service,
+    /// Model name
+    Model,
+    /// Operation name
+    Operation,
+    /// Span status
+    Status,
+    /// Value of an attribute, from `attributes.<key>`
+    Attribute(String),
+}
+
+impl AggregateGroup {
+    /// Parse a `group_by` value
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "service" => Ok(Self::Service),
+            "model" => Ok(Self::Model),
+            "operation" => Ok(Self::Operation),
+            "status" => Ok(Self::Status),
+            _ => s
+                .strip_prefix("attributes.")
+                .filter(|key| !key.is_empty())
+                .map(|key| Self::Attribute(key.to_string()))
+                .ok_or_else(|| {
+                    Error::validation(format!(
+                        "Unknown group_by '{}': expected service, model, operation, status or attributes.<key>",
+                        s
+                    ))
+                }),
+        }
+    }
+}
+
+/// Aggregation query as submitted to `POST /api/v1/query`
+#[derive(Debug, Clone, Deserialize)]
+pub struct AggregateRequest {
+    /// Value to compute
+    pub metric: AggregateMetric,
+    /// `service`, `model`, `operation`, `status` or `attributes.<key>`
+    #[serde(default)]
+    pub group_by: Option<String>,
+    /// Time bucket width, e.g. `5m` or `1d`
+    #[serde(default)]
+    pub bucket: Option<String>,
+    /// Conditions spans must match
+    #[serde(default)]
+    pub filters: Vec<SearchFilter>,
+    /// Start of the range
+    pub since: DateTime<Utc>,
+    /// End of the range (default: now)
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Most groups to return, highest values first
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// Validated aggregation query, ready for a span store
+#[derive(Debug, Clone)]
+pub struct AggregateQuery {
+    /// Value to compute
+    pub metric: AggregateMetric,
+    /// Dimension to group by
+    pub group: Option<AggregateGroup>,
+    /// Time bucket width in seconds
+    pub bucket_secs: Option<i64>,
+    /// Conditions spans must match
+    pub filters: Vec<SearchFilter>,
+    /// Start of the range
+    pub since: DateTime<Utc>,
+    /// End of the range
+    pub until: DateTime<Utc>,
+    /// Most groups to return
+    pub limit: i64,
+}
+
+impl AggregateRequest {
+    /// Check the request and resolve its grouping and bucket width
+    pub fn compile(self) -> Result<AggregateQuery> {
+        let until = self.until.unwrap_or_else(Utc::now);
+        if until <= self.since {
+            return Err(Error::validation("'until' must be after 'since'"));
+        }
+
+        let group = self.group_by.as_deref().map(AggregateGroup::parse).transpose()?;
+
+        let bucket_secs = match self.bucket.as_deref() {
+            Some(bucket) => Some(
+                AGGREGATE_BUCKETS
+                    .iter()
+                    .find(|(name, _)| *name == bucket)
+                    .map(|(_, secs)| *secs)
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = AGGREGATE_BUCKETS.iter().map(|(name, _)| *name).collect();
+                        Error::validation(format!(
+                            "Unknown bucket '{}': expected one of {}",
+                            bucket,
+                            names.join(", ")
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+
+        if let Some(secs) = bucket_secs {
+            let buckets = (until - self.since).num_seconds() / secs + 1;
+            if buckets > MAX_AGGREGATE_BUCKETS {
+                return Err(Error::query_limit(
+                    format!(
+                        "Query spans {} buckets, more than the {} bucket limit",
+                        buckets, MAX_AGGREGATE_BUCKETS
+                    ),
+                    "Use a wider bucket or a shorter time range",
+                ));
+            }
+        }
+
+        let limit = self.limit.unwrap_or(DEFAULT_GROUP_LIMIT);
+        if !(1..=MAX_GROUP_LIMIT).contains(&limit) {
+            return Err(Error::validation(format!(
+                "'limit' must be between 1 and {}",
+                MAX_GROUP_LIMIT
+            )));
+        }
+
+        Ok(AggregateQuery {
+            metric: self.metric,
+            group,
+            bucket_secs,
+            filters: self.filters,
+            since: self.since,
+            until,
+            limit,
+        })
+    }
+}
+
+/// One value of an aggregation result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AggregateRow {
+    /// Group value, when the query is grouped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Bucket start, when the query is bucketed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<DateTime<Utc>>,
+    /// The metric's value
+    pub value: f64,
+}
+
+/// Aggregation result
+///
+/// Rows are ordered by bucket, then by value with the highest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct AggregateResponse {
+    /// The metric computed
+    pub metric: AggregateMetric,
+    /// The query's `group_by`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    /// The query's bucket width
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// Start of the range
+    pub since: DateTime<Utc>,
+    /// End of the range
+    pub until: DateTime<Utc>,
+    /// One row per group and bucket
+    pub rows: Vec<AggregateRow>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn request(json: serde_json::Value) -> AggregateRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_compile_resolves_group_and_bucket() {
+        let query = request(serde_json::json!({
+            "metric": "p95_latency",
+            "group_by": "attributes.user.tier",
+            "bucket": "1h",
+            "since": Utc::now() - Duration::days(1),
+        }))
+        .compile()
+        .unwrap();
+
+        assert_eq!(query.metric.percentile(), Some(0.95));
+        assert_eq!(query.group, Some(AggregateGroup::Attribute("user.tier".to_string())));
+        assert_eq!(query.bucket_secs, Some(3_600));
+        assert_eq!(query.limit, DEFAULT_GROUP_LIMIT);
+    }
+
+    #[test]
+    fn test_compile_rejects_unsafe_or_unbounded_queries() {
+        let since = Utc::now() - Duration::days(30);
+        let compile = |extra: serde_json::Value| {
+            let mut json = serde_json::json!({"metric": "count", "since": since});
+            json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            request(json).compile()
+        };
+
+        assert!(compile(serde_json::json!({"group_by": "service_name; DROP TABLE spans"})).is_err());
+        assert!(compile(serde_json::json!({"group_by": "attributes."})).is_err());
+        assert!(compile(serde_json::json!({"bucket": "7m"})).is_err());
+        assert!(compile(serde_json::json!({"limit": 0})).is_err());
+        assert!(compile(serde_json::json!({"until": since - Duration::hours(1)})).is_err());
+        assert!(matches!(
+            compile(serde_json::json!({"bucket": "1m"})),
+            Err(Error::QueryLimit { .. })
+        ));
+        assert!(compile(serde_json::json!({"bucket": "1h"})).is_ok());
+    }
+
+    #[test]
+    fn test_unknown_metric_is_rejected() {
+        let parsed = serde_json::from_value::<AggregateRequest>(serde_json::json!({
+            "metric": "max_cost",
+            "since": Utc::now(),
+        }));
+        assert!(parsed.is_err());
+    }
+}
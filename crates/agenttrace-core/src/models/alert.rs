@@ -1,14 +1,24 @@
 //! Alert data models
 
-use chrono::{DateTime, Utc};
+use std::fmt;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use super::audit::AuditAction;
+use super::budget::BudgetAlert;
+use super::slo::{SloBurnAlert, SloResolution};
+use crate::alerting::Digest;
+use crate::error::{Error, Result};
+
 /// Type of alert condition
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum ConditionType {
     /// Simple threshold comparison
+    #[default]
     Threshold,
     /// Statistical anomaly detection
     Anomaly,
@@ -16,6 +26,8 @@ pub enum ConditionType {
     RateChange,
     /// Absence of data
     Absence,
+    /// Per-trace total, checked by the pipeline as spans arrive
+    Trace,
 }
 
 /// Comparison operator
@@ -36,8 +48,8 @@ pub enum Operator {
     Ne,
 }
 
-/// Alert severity level
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+/// Alert severity level, ordered from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum Severity {
     /// Informational
@@ -107,6 +119,9 @@ pub struct AlertRule {
     /// Number of consecutive failures before alerting
     pub consecutive_failures: i32,
 
+    /// When the rule is evaluated (None = always)
+    pub schedule: Option<RuleSchedule>,
+
     // Notification
     /// Alert severity
     pub severity: Severity,
@@ -114,6 +129,15 @@ pub struct AlertRule {
     /// Notification channels
     pub notification_channels: Vec<NotificationChannel>,
 
+    /// Further channels notified while an alert stays unacknowledged
+    #[serde(default)]
+    pub escalation: Vec<EscalationStep>,
+
+    /// Handlebars template for the alert message (None = the configured
+    /// default, or the built-in message)
+    #[serde(default)]
+    pub message_template: Option<String>,
+
     // State
     /// Whether the rule is enabled
     pub enabled: bool,
@@ -147,6 +171,58 @@ pub enum NotificationChannel {
     Webhook { url: String, headers: Option<serde_json::Value> },
     /// PagerDuty
     PagerDuty { routing_key: String },
+    /// Opsgenie alerts API; `api_url` defaults to the US instance
+    /// (`https://api.eu.opsgenie.com` for EU accounts)
+    Opsgenie { api_key: String, api_url: Option<String> },
+    /// Splunk On-Call (formerly VictorOps) REST endpoint integration
+    SplunkOnCall { api_key: String, routing_key: String },
+}
+
+impl NotificationChannel {
+    /// Short name of the channel's type, as recorded with notifications
+    pub fn channel_type(&self) -> &'static str {
+        match self {
+            NotificationChannel::Slack { .. } => "slack",
+            NotificationChannel::Webhook { .. } => "webhook",
+            NotificationChannel::PagerDuty { .. } => "pagerduty",
+            NotificationChannel::Email { .. } => "email",
+            NotificationChannel::Opsgenie { .. } => "opsgenie",
+            NotificationChannel::SplunkOnCall { .. } => "splunk_on_call",
+        }
+    }
+
+    /// Whether the channel keeps open incidents, which are closed when
+    /// their alert resolves
+    pub fn tracks_incidents(&self) -> bool {
+        matches!(
+            self,
+            NotificationChannel::PagerDuty { .. }
+                | NotificationChannel::Opsgenie { .. }
+                | NotificationChannel::SplunkOnCall { .. }
+        )
+    }
+
+    /// User-supplied URL the channel posts to, if it has one
+    pub fn target_url(&self) -> Option<&str> {
+        match self {
+            NotificationChannel::Slack { webhook_url, .. } => Some(webhook_url),
+            NotificationChannel::Webhook { url, .. } => Some(url),
+            NotificationChannel::Opsgenie { api_url, .. } => api_url.as_deref(),
+            NotificationChannel::Email { .. }
+            | NotificationChannel::PagerDuty { .. }
+            | NotificationChannel::SplunkOnCall { .. } => None,
+        }
+    }
+}
+
+/// A later notification step, sent if an alert is still unacknowledged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EscalationStep {
+    /// Minutes after the alert triggers
+    pub after_minutes: u32,
+
+    /// Channels notified at this step
+    pub channels: Vec<NotificationChannel>,
 }
 
 /// An alert event (triggered alert)
@@ -190,6 +266,145 @@ pub struct AlertEvent {
 
     /// Additional metadata
     pub metadata: serde_json::Value,
+
+    /// Incident the event was grouped into with concurrent events for the
+    /// same service
+    #[serde(default)]
+    pub incident_id: Option<Uuid>,
+
+    /// Who acknowledged the alert, with their comment and assignee
+    #[serde(default)]
+    pub acknowledgement: Option<Acknowledgement>,
+}
+
+impl AlertEvent {
+    /// Provider incidents that overlapped the alert's window when it triggered
+    pub fn upstream_incidents(&self) -> Vec<super::Annotation> {
+        self.metadata
+            .get(super::UPSTREAM_INCIDENTS_METADATA)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Longest acknowledgement comment accepted
+const MAX_ACK_COMMENT_LEN: usize = 2_000;
+
+/// Longest assignee name accepted
+const MAX_ASSIGNEE_LEN: usize = 100;
+
+/// Who acknowledged an alert, and what they said
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Acknowledgement {
+    /// Who acknowledged the alert, from the `X-AgentTrace-Actor` header
+    pub acknowledged_by: String,
+
+    /// When the alert was acknowledged
+    pub acknowledged_at: DateTime<Utc>,
+
+    /// Note left for whoever else is looking at the alert
+    pub comment: Option<String>,
+
+    /// Who is handling the alert
+    pub assignee: Option<String>,
+}
+
+impl Acknowledgement {
+    /// One-line account for notifications, e.g. `acked by dana, assigned
+    /// to sam: rolling back the prompt change`
+    pub fn summary(&self) -> String {
+        let mut summary = format!("acked by {}", self.acknowledged_by);
+        if let Some(assignee) = &self.assignee {
+            summary.push_str(&format!(", assigned to {}", assignee));
+        }
+        if let Some(comment) = &self.comment {
+            summary.push_str(&format!(": {}", comment));
+        }
+        summary
+    }
+}
+
+/// Request to acknowledge an alert or incident; the body is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AcknowledgeRequest {
+    /// Note left with the acknowledgement
+    #[serde(default)]
+    pub comment: Option<String>,
+
+    /// Who is handling the alert
+    #[serde(default)]
+    pub assignee: Option<String>,
+}
+
+impl AcknowledgeRequest {
+    /// Acknowledgement by `actor` at `now`, with blank fields dropped
+    pub fn acknowledgement(self, actor: String, now: DateTime<Utc>) -> Result<Acknowledgement> {
+        let non_blank = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let comment = non_blank(self.comment);
+        let assignee = non_blank(self.assignee);
+
+        if comment.as_ref().is_some_and(|c| c.chars().count() > MAX_ACK_COMMENT_LEN) {
+            return Err(Error::validation(format!(
+                "Comment must be at most {} characters",
+                MAX_ACK_COMMENT_LEN
+            )));
+        }
+        if assignee.as_ref().is_some_and(|a| a.chars().count() > MAX_ASSIGNEE_LEN) {
+            return Err(Error::validation(format!(
+                "Assignee must be at most {} characters",
+                MAX_ASSIGNEE_LEN
+            )));
+        }
+
+        Ok(Acknowledgement {
+            acknowledged_by: actor,
+            acknowledged_at: now,
+            comment,
+            assignee,
+        })
+    }
+}
+
+/// Alert events for one service that fired within the correlation window of
+/// each other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertIncident {
+    /// Unique identifier, kept as events join
+    pub id: Uuid,
+
+    /// Service the events were for
+    pub service_name: Option<String>,
+
+    /// Resolved once all of its events are
+    pub status: AlertStatus,
+
+    /// Most severe of its events
+    pub severity: Severity,
+
+    /// When the first event triggered
+    pub started_at: DateTime<Utc>,
+
+    /// When the latest event triggered
+    pub last_event_at: DateTime<Utc>,
+
+    /// When the last open event resolved
+    pub resolved_at: Option<DateTime<Utc>>,
+
+    /// Number of events grouped into the incident
+    pub event_count: i32,
+}
+
+/// What the evaluator remembers about a rule between evaluations
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RuleState {
+    /// Rule the state belongs to
+    pub rule_id: Uuid,
+
+    /// Breaching evaluations in a row
+    pub consecutive_failures: i32,
+
+    /// Most severe value seen while the rule's alert is active
+    pub peak_value: Option<f64>,
 }
 
 /// Record of a sent notification
@@ -206,29 +421,357 @@ pub struct NotificationRecord {
 
     /// Error message if failed
     pub error: Option<String>,
+
+    /// Delivery attempt, for notifications that were retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
 }
 
+/// A failed notification waiting to be sent again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRetry {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// What is being notified
+    pub message: RetryMessage,
+
+    /// Channel the notification failed on
+    pub channel: NotificationChannel,
+
+    /// Attempts made so far, including the first send
+    pub attempts: u32,
+
+    /// When to try again
+    pub next_attempt_at: DateTime<Utc>,
+
+    /// Error from the last attempt
+    pub last_error: Option<String>,
+
+    /// When the first send failed
+    pub created_at: DateTime<Utc>,
+}
+
+/// What a queued retry sends
+///
+/// Alerts and their resolutions are stored by reference and sent as they
+/// are when retried; other notifications are stored whole.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RetryMessage {
+    /// An alert event, sent while it is active
+    Alert { rule_id: Uuid, event_id: Uuid },
+    /// An alert event resolving, sent while it stays resolved
+    Resolution { rule_id: Uuid, event_id: Uuid },
+    /// A digest of batched alerts
+    Digest(Digest),
+    /// A digest's incident closing once every alert in it resolved
+    DigestResolution(Digest),
+    /// A budget crossing a threshold, sent while the budget exists
+    BudgetAlert(BudgetAlert),
+    /// An SLO burn-rate alert, sent while it is firing
+    SloAlert(SloBurnAlert),
+    /// An SLO burn-rate alert clearing, sent unless it fired again
+    SloResolution(SloResolution),
+}
+
+impl RetryMessage {
+    /// Retry of an alert event
+    pub fn alert(event: &AlertEvent) -> Self {
+        Self::Alert {
+            rule_id: event.rule_id,
+            event_id: event.id,
+        }
+    }
+
+    /// Retry of an alert event's resolution
+    pub fn resolution(event: &AlertEvent) -> Self {
+        Self::Resolution {
+            rule_id: event.rule_id,
+            event_id: event.id,
+        }
+    }
+}
+
+/// Evaluation window of rules that don't set one
+pub const DEFAULT_WINDOW_MINUTES: i32 = 5;
+
+/// Evaluation interval of rules that don't set one
+pub const DEFAULT_EVALUATION_INTERVAL_SECONDS: i32 = 60;
+
+/// Breaching evaluations in a row before rules that don't set it alert
+pub const DEFAULT_CONSECUTIVE_FAILURES: i32 = 1;
+
 /// Input for creating a new alert rule
+///
+/// Also the form rules take in rule files, so fields left unset are not
+/// written out.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlertRuleInput {
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub service_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub environment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model_name: Option<String>,
+    #[serde(default)]
     pub condition_type: ConditionType,
     pub metric: String,
     pub operator: Operator,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_minutes: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evaluation_interval_seconds: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consecutive_failures: Option<i32>,
+    /// Times the rule is evaluated; always when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<RuleSchedule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification_channels: Option<Vec<NotificationChannel>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub escalation: Option<Vec<EscalationStep>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+impl AlertRuleInput {
+    /// Fill in the values a new rule gets for fields left unset
+    pub fn with_defaults(mut self) -> Self {
+        self.window_minutes.get_or_insert(DEFAULT_WINDOW_MINUTES);
+        self.evaluation_interval_seconds.get_or_insert(DEFAULT_EVALUATION_INTERVAL_SECONDS);
+        self.consecutive_failures.get_or_insert(DEFAULT_CONSECUTIVE_FAILURES);
+        self.severity.get_or_insert_with(Severity::default);
+        self.notification_channels.get_or_insert_with(Vec::new);
+        self.escalation.get_or_insert_with(Vec::new);
+        self.enabled.get_or_insert(true);
+        self
+    }
+}
+
+impl From<&AlertRule> for AlertRuleInput {
+    fn from(rule: &AlertRule) -> Self {
+        Self {
+            name: rule.name.clone(),
+            description: rule.description.clone(),
+            service_name: rule.service_name.clone(),
+            environment: rule.environment.clone(),
+            model_name: rule.model_name.clone(),
+            condition_type: rule.condition_type,
+            metric: rule.metric.clone(),
+            operator: rule.operator,
+            threshold: rule.threshold,
+            window_minutes: Some(rule.window_minutes),
+            evaluation_interval_seconds: Some(rule.evaluation_interval_seconds),
+            consecutive_failures: Some(rule.consecutive_failures),
+            schedule: rule.schedule.clone(),
+            severity: Some(rule.severity),
+            notification_channels: Some(rule.notification_channels.clone()),
+            escalation: Some(rule.escalation.clone()),
+            message_template: rule.message_template.clone(),
+            enabled: Some(rule.enabled),
+        }
+    }
+}
+
+/// Changes to an existing alert rule
+///
+/// Takes the same fields as [`AlertRuleInput`]. A field left out keeps its
+/// stored value, while an optional field set to `null` is cleared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRuleUpdate {
+    /// New name
+    pub name: String,
+    /// New description
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub description: FieldUpdate<String>,
+    /// Only evaluate spans from this service
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub service_name: FieldUpdate<String>,
+    /// Only evaluate spans from this environment
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub environment: FieldUpdate<String>,
+    /// Only evaluate spans from this model
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub model_name: FieldUpdate<String>,
+    /// How the metric is compared
+    #[serde(default)]
+    pub condition_type: ConditionType,
+    /// Metric to monitor
+    pub metric: String,
+    /// Comparison against the threshold
+    pub operator: Operator,
+    /// Value the metric is compared with
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub threshold: FieldUpdate<f64>,
+    /// Evaluation window in minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub window_minutes: Option<i32>,
+    /// Seconds between evaluations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub evaluation_interval_seconds: Option<i32>,
+    /// Breaching evaluations in a row before the rule alerts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub consecutive_failures: Option<i32>,
+    /// Times the rule is evaluated; clearing it evaluates the rule always
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub schedule: FieldUpdate<RuleSchedule>,
+    /// Severity of the alerts the rule raises
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub severity: Option<Severity>,
+    /// Where alerts go
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub notification_channels: Option<Vec<NotificationChannel>>,
+    /// `null` clears the escalation, like an empty list
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub escalation: FieldUpdate<Vec<EscalationStep>>,
+    /// Template for alert messages
+    #[serde(default, skip_serializing_if = "FieldUpdate::is_keep")]
+    pub message_template: FieldUpdate<String>,
+    /// Whether the rule is evaluated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub enabled: Option<bool>,
 }
 
+/// A clearable field of a partial update
+///
+/// Tells a field left out of the JSON from one set to `null`: a missing
+/// field deserializes as [`FieldUpdate::Keep`] through `#[serde(default)]`,
+/// `null` as [`FieldUpdate::Clear`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldUpdate<T> {
+    /// Left out; the stored value is kept
+    Keep,
+    /// Set to `null`; the stored value is cleared
+    Clear,
+    /// Set to a new value
+    Set(T),
+}
+
+impl<T> FieldUpdate<T> {
+    /// Whether the field was left out
+    pub fn is_keep(&self) -> bool {
+        matches!(self, Self::Keep)
+    }
+
+    /// The value set, if any
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Self::Set(value) => Some(value),
+            Self::Keep | Self::Clear => None,
+        }
+    }
+
+    /// The value set, if any, consuming the update
+    pub fn into_value(self) -> Option<T> {
+        match self {
+            Self::Set(value) => Some(value),
+            Self::Keep | Self::Clear => None,
+        }
+    }
+}
+
+impl<T> Default for FieldUpdate<T> {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// An update that replaces the field with `value`, clearing it when None
+impl<T> From<Option<T>> for FieldUpdate<T> {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => Self::Set(value),
+            None => Self::Clear,
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for FieldUpdate<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Set(value) => serializer.serialize_some(value),
+            Self::Keep | Self::Clear => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FieldUpdate<T> {
+    /// Only called for fields present in the input, so `null` clears
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Option::<T>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl From<AlertRuleInput> for AlertRuleUpdate {
+    /// An update that makes the rule match `input` exactly, clearing the
+    /// optional fields it leaves unset
+    fn from(input: AlertRuleInput) -> Self {
+        Self {
+            name: input.name,
+            description: input.description.into(),
+            service_name: input.service_name.into(),
+            environment: input.environment.into(),
+            model_name: input.model_name.into(),
+            condition_type: input.condition_type,
+            metric: input.metric,
+            operator: input.operator,
+            threshold: input.threshold.into(),
+            window_minutes: input.window_minutes,
+            evaluation_interval_seconds: input.evaluation_interval_seconds,
+            consecutive_failures: input.consecutive_failures,
+            schedule: input.schedule.into(),
+            severity: input.severity,
+            notification_channels: input.notification_channels,
+            escalation: input.escalation.into(),
+            message_template: input.message_template.into(),
+            enabled: input.enabled,
+        }
+    }
+}
+
 impl AlertRule {
+    /// An enabled threshold rule over all services with the default window,
+    /// interval and severity, and no notification channels
+    pub fn new(name: impl Into<String>, metric: impl Into<String>, operator: Operator, threshold: f64) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            description: None,
+            service_name: None,
+            environment: None,
+            model_name: None,
+            condition_type: ConditionType::Threshold,
+            metric: metric.into(),
+            operator,
+            threshold: Some(threshold),
+            window_minutes: DEFAULT_WINDOW_MINUTES,
+            evaluation_interval_seconds: DEFAULT_EVALUATION_INTERVAL_SECONDS,
+            consecutive_failures: DEFAULT_CONSECUTIVE_FAILURES,
+            schedule: None,
+            severity: Severity::default(),
+            notification_channels: vec![],
+            escalation: vec![],
+            message_template: None,
+            enabled: true,
+            last_evaluated_at: None,
+            last_triggered_at: None,
+            created_at: now,
+            updated_at: now,
+            created_by: None,
+        }
+    }
+
     /// Check if a value triggers this alert
     pub fn check(&self, value: f64) -> bool {
         let threshold = match self.threshold {
@@ -245,4 +788,429 @@ impl AlertRule {
             Operator::Ne => (value - threshold).abs() >= f64::EPSILON,
         }
     }
+
+    /// Whether the rule is outside its active schedule
+    pub fn is_scheduled_off(&self, at: DateTime<Utc>) -> bool {
+        self.schedule.as_ref().is_some_and(|s| !s.is_active_at(at))
+    }
+
+    /// The more severe of two breaching values: the highest for upper
+    /// bounds, the lowest for lower bounds and the furthest from the
+    /// threshold otherwise
+    pub fn worse_value(&self, a: f64, b: f64) -> f64 {
+        match self.operator {
+            Operator::Gt | Operator::Gte => a.max(b),
+            Operator::Lt | Operator::Lte => a.min(b),
+            Operator::Eq | Operator::Ne => {
+                let threshold = self.threshold.unwrap_or(0.0);
+                if (b - threshold).abs() > (a - threshold).abs() {
+                    b
+                } else {
+                    a
+                }
+            }
+        }
+    }
+}
+
+/// Summary of an incident, attached to an alert event when it resolves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IncidentMetrics {
+    /// Time from trigger to resolution in seconds
+    pub duration_secs: i64,
+
+    /// Most severe metric value seen while the alert was active
+    pub peak_value: f64,
+
+    /// Traces in the rule's scope during the incident
+    pub affected_traces: i64,
+
+    /// Span search pre-filtered to the rule's scope and the incident window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_url: Option<String>,
+}
+
+/// A recorded change to an alert rule
+///
+/// Revisions are numbered from 1 per rule and kept after the rule is
+/// deleted, so it can be restored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRuleRevision {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// The rule that changed
+    pub rule_id: Uuid,
+
+    /// Revision number, counting up from 1 for the rule's creation
+    pub revision: i32,
+
+    /// Whether the rule was created, updated or deleted
+    pub action: AuditAction,
+
+    /// Who made the change, from the `X-AgentTrace-Actor` header
+    pub actor: String,
+
+    /// When the change was made
+    pub changed_at: DateTime<Utc>,
+
+    /// The rule before the change, unless this created it
+    pub before: Option<serde_json::Value>,
+
+    /// The rule after the change, unless this deleted it
+    pub after: Option<serde_json::Value>,
+
+    /// Fields that changed, as `{"field": {"before": ..., "after": ...}}`
+    pub changes: Option<serde_json::Value>,
+
+    /// The revision this change reverted the rule to, if it was a revert
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverted_to: Option<i32>,
+}
+
+impl AlertRuleRevision {
+    /// The rule as this revision left it, for reverting to
+    ///
+    /// Fails for a revision that deleted the rule.
+    pub fn restored_rule(&self) -> Result<AlertRule> {
+        let snapshot = self.after.clone().ok_or_else(|| {
+            Error::validation(format!(
+                "Revision {} deleted the rule; revert to the revision before it",
+                self.revision
+            ))
+        })?;
+        serde_json::from_value(snapshot)
+            .map_err(|e| Error::validation(format!("Revision {} can't be restored: {}", self.revision, e)))
+    }
+}
+
+/// Request to revert an alert rule to an earlier revision
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevertRuleRequest {
+    /// Revision whose resulting rule is restored
+    pub revision: i32,
+}
+
+/// An alert rule with its current schedule state, as shown in listings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRuleListing {
+    /// The rule
+    #[serde(flatten)]
+    pub rule: AlertRule,
+
+    /// Whether the rule is currently outside its active schedule
+    pub scheduled_off: bool,
+}
+
+/// When an alert rule is evaluated
+///
+/// Outside all of its windows a rule is "scheduled off": the evaluator skips
+/// it, so business-hours rules stay quiet overnight and at weekends.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSchedule {
+    /// IANA timezone the windows are written in
+    #[serde(default = "default_schedule_timezone")]
+    pub timezone: String,
+
+    /// Windows during which the rule is active, e.g. `"mon-fri 09:00-18:00"`
+    pub windows: Vec<ScheduleWindow>,
+}
+
+fn default_schedule_timezone() -> String {
+    "UTC".to_string()
+}
+
+impl RuleSchedule {
+    /// Check that the timezone is known and there is at least one window
+    pub fn validate(&self) -> Result<()> {
+        self.tz()?;
+        if self.windows.is_empty() {
+            return Err(Error::validation("Schedule must have at least one window"));
+        }
+        Ok(())
+    }
+
+    /// Whether a point in time falls inside one of the windows
+    pub fn is_active_at(&self, at: DateTime<Utc>) -> bool {
+        // An unknown timezone falls back to UTC rather than silencing the rule
+        let local = match self.tz() {
+            Ok(tz) => at.with_timezone(&tz).naive_local(),
+            Err(_) => at.naive_utc(),
+        };
+        self.windows.iter().any(|w| w.contains(local))
+    }
+
+    fn tz(&self) -> Result<Tz> {
+        self.timezone
+            .parse()
+            .map_err(|_| Error::validation(format!("Unknown timezone '{}'", self.timezone)))
+    }
+}
+
+/// A recurring window of days and hours, written as `"<days> <start>-<end>"`
+///
+/// Days are a comma-separated list of weekdays or ranges (`mon-fri`,
+/// `sat,sun`) and may be left out to mean every day; times alone may be left
+/// out to mean the whole day. A window whose end is not after its start runs
+/// past midnight, so `fri 22:00-06:00` covers Friday night into Saturday.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ScheduleWindow {
+    /// Days the window starts on
+    pub days: Vec<Weekday>,
+
+    /// Local start time (inclusive)
+    pub start: NaiveTime,
+
+    /// Local end time (exclusive)
+    pub end: NaiveTime,
+}
+
+const ALL_WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+impl ScheduleWindow {
+    /// Parse a window such as `"mon-fri 09:00-18:00"`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (days, times) = match spec.rsplit_once(char::is_whitespace) {
+            Some((days, times)) => (parse_weekdays(days.trim())?, times),
+            None if spec.contains(':') => (ALL_WEEKDAYS.to_vec(), spec),
+            None => (parse_weekdays(spec)?, "00:00-00:00"),
+        };
+
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| Error::validation(format!("Invalid schedule window '{}'", spec)))?;
+
+        Ok(Self {
+            days,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    fn contains(&self, local: NaiveDateTime) -> bool {
+        let (day, time) = (local.weekday(), local.time());
+
+        if self.start < self.end {
+            self.days.contains(&day) && time >= self.start && time < self.end
+        } else {
+            (self.days.contains(&day) && time >= self.start)
+                || (self.days.contains(&day.pred()) && time < self.end)
+        }
+    }
+}
+
+impl fmt::Display for ScheduleWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days.len() < ALL_WEEKDAYS.len() {
+            let days: Vec<String> = self.days.iter().map(|d| d.to_string().to_lowercase()).collect();
+            write!(f, "{} ", days.join(","))?;
+        }
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+impl TryFrom<String> for ScheduleWindow {
+    type Error = Error;
+
+    fn try_from(spec: String) -> Result<Self> {
+        Self::parse(&spec)
+    }
+}
+
+impl From<ScheduleWindow> for String {
+    fn from(window: ScheduleWindow) -> Self {
+        window.to_string()
+    }
+}
+
+fn parse_weekdays(spec: &str) -> Result<Vec<Weekday>> {
+    let parse_day = |s: &str| {
+        s.trim()
+            .parse::<Weekday>()
+            .map_err(|_| Error::validation(format!("Unknown weekday '{}'", s.trim())))
+    };
+
+    let mut days = Vec::new();
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (mut day, last) = (parse_day(from)?, parse_day(to)?);
+                while day != last {
+                    days.push(day);
+                    day = day.succ();
+                }
+                days.push(last);
+            }
+            None => days.push(parse_day(part)?),
+        }
+    }
+
+    days.sort_by_key(Weekday::num_days_from_monday);
+    days.dedup();
+    Ok(days)
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .map_err(|_| Error::validation(format!("Invalid time '{}' (expected HH:MM)", s.trim())))
+}
+
+/// Level of a rule lint finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    /// Worth knowing, but the rule works as written
+    Info,
+    /// The rule is unlikely to behave as intended
+    Warning,
+}
+
+/// A single finding produced by linting an alert rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleLintWarning {
+    /// Stable identifier for the check (e.g., "unreachable_threshold")
+    pub code: String,
+
+    /// How serious the finding is
+    pub level: LintLevel,
+
+    /// What is wrong with the rule
+    pub message: String,
+
+    /// What to change to fix it
+    pub recommendation: String,
+
+    /// Other rule involved in the finding (duplicates/overlaps)
+    pub related_rule_id: Option<Uuid>,
+}
+
+/// Result of linting an alert rule against recent data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleLintReport {
+    /// The rule that was linted
+    pub rule_id: Uuid,
+
+    /// Name of the rule
+    pub rule_name: String,
+
+    /// When the lint ran
+    pub checked_at: DateTime<Utc>,
+
+    /// How far back recent data was inspected, in minutes
+    pub lookback_minutes: i64,
+
+    /// Findings, empty if the rule looks healthy
+    pub warnings: Vec<RuleLintWarning>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(timezone: &str, windows: &[&str]) -> RuleSchedule {
+        RuleSchedule {
+            timezone: timezone.to_string(),
+            windows: windows.iter().map(|w| ScheduleWindow::parse(w).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_schedule_window() {
+        let window = ScheduleWindow::parse("mon-fri 09:00-18:00").unwrap();
+        assert_eq!(window.days.len(), 5);
+        assert_eq!(window.to_string(), "mon,tue,wed,thu,fri 09:00-18:00");
+
+        let window = ScheduleWindow::parse("sat,sun").unwrap();
+        assert_eq!(window.days, vec![Weekday::Sat, Weekday::Sun]);
+        assert_eq!(window.start, window.end);
+
+        assert_eq!(ScheduleWindow::parse("08:00-20:00").unwrap().days.len(), 7);
+        assert!(ScheduleWindow::parse("weekdays 09:00-18:00").is_err());
+        assert!(ScheduleWindow::parse("mon 9am-5pm").is_err());
+    }
+
+    #[test]
+    fn test_business_hours_schedule() {
+        let schedule = schedule("America/New_York", &["mon-fri 09:00-18:00"]);
+
+        // Tuesday 10:00 in New York
+        assert!(schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 13, 14, 0, 0).unwrap()));
+        // Tuesday 03:00 in New York
+        assert!(!schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 13, 7, 0, 0).unwrap()));
+        // Saturday 10:00 in New York
+        assert!(!schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 17, 14, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let schedule = schedule("UTC", &["fri 22:00-06:00"]);
+
+        assert!(schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 16, 23, 0, 0).unwrap()));
+        assert!(schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 17, 5, 0, 0).unwrap()));
+        assert!(!schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 17, 7, 0, 0).unwrap()));
+        assert!(!schedule.is_active_at(Utc.with_ymd_and_hms(2026, 10, 16, 5, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn test_acknowledgement_summary() {
+        let request = AcknowledgeRequest {
+            comment: Some("  rolling back the prompt change ".to_string()),
+            assignee: Some("sam".to_string()),
+        };
+        let ack = request.acknowledgement("dana".to_string(), Utc::now()).unwrap();
+        assert_eq!(ack.summary(), "acked by dana, assigned to sam: rolling back the prompt change");
+
+        let blank = AcknowledgeRequest { comment: Some("  ".to_string()), assignee: None };
+        let ack = blank.acknowledgement("dana".to_string(), Utc::now()).unwrap();
+        assert_eq!(ack.comment, None);
+        assert_eq!(ack.summary(), "acked by dana");
+
+        let long = AcknowledgeRequest { comment: None, assignee: Some("x".repeat(101)) };
+        assert!(long.acknowledgement("dana".to_string(), Utc::now()).is_err());
+    }
+
+    #[test]
+    fn test_rule_update_tells_null_from_missing() {
+        let update: AlertRuleUpdate = serde_json::from_value(serde_json::json!({
+            "name": "Errors",
+            "metric": "error_rate",
+            "operator": "gt",
+            "schedule": null,
+            "escalation": null,
+            "message_template": "{{rule.name}}",
+        }))
+        .unwrap();
+
+        assert!(matches!(update.schedule, FieldUpdate::Clear));
+        assert!(matches!(update.escalation, FieldUpdate::Clear));
+        assert_eq!(update.message_template, FieldUpdate::Set("{{rule.name}}".to_string()));
+        assert_eq!(update.description, FieldUpdate::Keep);
+        assert_eq!(update.threshold, FieldUpdate::Keep);
+    }
+
+    #[test]
+    fn test_worse_value() {
+        let rule = |operator, threshold: f64| AlertRule::new("Error rate", "error_rate", operator, threshold);
+
+        assert_eq!(rule(Operator::Gt, 1.0).worse_value(2.0, 3.0), 3.0);
+        assert_eq!(rule(Operator::Gte, 1.0).worse_value(3.0, 2.0), 3.0);
+        assert_eq!(rule(Operator::Lt, 1.0).worse_value(0.5, 0.2), 0.2);
+        assert_eq!(rule(Operator::Lte, 1.0).worse_value(0.2, 0.5), 0.2);
+
+        // Equality rules keep whichever is furthest from the threshold
+        assert_eq!(rule(Operator::Ne, 10.0).worse_value(12.0, 5.0), 5.0);
+        assert_eq!(rule(Operator::Ne, 10.0).worse_value(5.0, 14.0), 5.0);
+        assert_eq!(rule(Operator::Eq, 10.0).worse_value(11.0, 9.5), 11.0);
+    }
 }
@@ -0,0 +1,225 @@
+//! Timeline and trace annotation models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// Key under which overlapping provider incidents are recorded in alert
+/// event metadata
+pub const UPSTREAM_INCIDENTS_METADATA: &str = "upstream_incidents";
+
+/// What an annotation marks on the timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    /// An incident reported on an LLM provider's status page
+    ProviderIncident,
+}
+
+impl AnnotationKind {
+    /// Name as stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ProviderIncident => "provider_incident",
+        }
+    }
+
+    /// Parse a stored kind name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "provider_incident" => Some(Self::ProviderIncident),
+            _ => None,
+        }
+    }
+}
+
+/// A global window of time worth knowing about when reading metrics,
+/// such as an upstream provider outage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// What the annotation marks
+    pub kind: AnnotationKind,
+
+    /// Where it came from (e.g., "openai", "anthropic")
+    pub source: String,
+
+    /// Identifier in the source system, unique per source
+    pub external_id: String,
+
+    /// Short description (e.g., "Elevated error rates on GPT-4o")
+    pub title: String,
+
+    /// Impact reported by the source (e.g., "minor", "major", "critical")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impact: Option<String>,
+
+    /// Link to the details
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// When the window started
+    pub starts_at: DateTime<Utc>,
+
+    /// When the window ended, or `None` while it is ongoing
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl Annotation {
+    /// Whether the annotation's window overlaps `[since, until)`
+    pub fn overlaps(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> bool {
+        self.starts_at < until && self.ends_at.map_or(true, |end| end > since)
+    }
+
+    /// One-line description for notifications
+    pub fn summary(&self) -> String {
+        match &self.impact {
+            Some(impact) => format!("{} ({}): {}", self.source, impact, self.title),
+            None => format!("{}: {}", self.source, self.title),
+        }
+    }
+}
+
+/// Longest allowed trace annotation text
+const MAX_NOTE_LEN: usize = 4000;
+
+/// Most labels on one trace annotation
+const MAX_LABELS: usize = 20;
+
+/// A note left on a trace, or one of its spans, during review
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceAnnotation {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Trace the note is on
+    pub trace_id: String,
+
+    /// Span the note is about, if it's about one span
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub span_id: Option<String>,
+
+    /// Who wrote the note
+    pub author: String,
+
+    /// The note, e.g. "root cause: tool timeout"
+    pub text: String,
+
+    /// Labels to find the note by, e.g. `root-cause`
+    pub labels: Vec<String>,
+
+    /// When the note was written
+    pub created_at: DateTime<Utc>,
+
+    /// When the note was last edited
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Input for writing or editing a trace annotation
+#[derive(Debug, Clone, Deserialize)]
+pub struct TraceAnnotationInput {
+    pub span_id: Option<String>,
+    pub author: String,
+    pub text: String,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl TraceAnnotationInput {
+    /// Check the author, text and labels, trimming and de-duplicating labels
+    pub fn validate(&mut self) -> Result<()> {
+        if self.author.trim().is_empty() {
+            return Err(Error::validation("Annotation author is required"));
+        }
+        if self.text.trim().is_empty() || self.text.chars().count() > MAX_NOTE_LEN {
+            return Err(Error::validation(format!(
+                "Annotation text must be 1 to {} characters",
+                MAX_NOTE_LEN
+            )));
+        }
+
+        let mut labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty())
+            .collect();
+        labels.sort();
+        labels.dedup();
+        if labels.len() > MAX_LABELS {
+            return Err(Error::validation(format!("At most {} labels are allowed", MAX_LABELS)));
+        }
+        if let Some(label) = labels.iter().find(|l| l.chars().count() > 50 || l.contains(char::is_whitespace)) {
+            return Err(Error::validation(format!(
+                "Label '{}' must be at most 50 characters with no spaces",
+                label
+            )));
+        }
+        self.labels = labels;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn incident(starts_at: DateTime<Utc>, ends_at: Option<DateTime<Utc>>) -> Annotation {
+        Annotation {
+            id: Uuid::new_v4(),
+            kind: AnnotationKind::ProviderIncident,
+            source: "openai".to_string(),
+            external_id: "abc123".to_string(),
+            title: "Elevated error rates".to_string(),
+            impact: Some("major".to_string()),
+            url: None,
+            starts_at,
+            ends_at,
+        }
+    }
+
+    #[test]
+    fn test_annotation_overlaps() {
+        let now = Utc::now();
+        let hour = Duration::hours(1);
+
+        assert!(incident(now - hour, Some(now)).overlaps(now - hour * 2, now));
+        assert!(!incident(now - hour * 3, Some(now - hour * 2)).overlaps(now - hour, now));
+        assert!(!incident(now, None).overlaps(now - hour, now));
+
+        // Ongoing incidents overlap everything after they started
+        assert!(incident(now - hour * 5, None).overlaps(now - hour, now));
+    }
+
+    #[test]
+    fn test_trace_annotation_input_validation() {
+        let mut input = TraceAnnotationInput {
+            span_id: None,
+            author: "dana".to_string(),
+            text: "root cause: tool timeout".to_string(),
+            labels: vec![" Root-Cause".to_string(), "root-cause".to_string(), "".to_string()],
+        };
+        input.validate().unwrap();
+        assert_eq!(input.labels, vec!["root-cause"]);
+
+        input.labels = vec!["two words".to_string()];
+        assert!(input.validate().is_err());
+
+        input.labels = vec![];
+        input.text = "  ".to_string();
+        assert!(input.validate().is_err());
+
+        // Limits count characters, not bytes
+        input.text = "é".repeat(MAX_NOTE_LEN);
+        input.labels = vec!["ü".repeat(50)];
+        input.validate().unwrap();
+        input.text.push('é');
+        assert!(input.validate().is_err());
+    }
+}
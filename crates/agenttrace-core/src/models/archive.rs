@@ -0,0 +1,40 @@
+//! Cold-storage archive models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A partition of spans exported to cold storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Start of the archived time range (inclusive)
+    pub partition_start: DateTime<Utc>,
+
+    /// End of the archived time range (exclusive)
+    pub partition_end: DateTime<Utc>,
+
+    /// Object path of the Parquet file, relative to the archive URL
+    pub location: String,
+
+    /// Number of spans in the file
+    pub span_count: i64,
+
+    /// Size of the file in bytes
+    pub size_bytes: i64,
+
+    /// When the partition was archived
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of rehydrating archived spans
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreSummary {
+    /// Archive partitions read
+    pub partitions: usize,
+
+    /// Spans inserted back into the spans table
+    pub spans_restored: usize,
+}
@@ -0,0 +1,246 @@
+//! Audit log models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Actor recorded when a request doesn't name one
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+
+/// What a mutating request did to a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A resource was created
+    Create,
+    /// A resource was replaced or changed
+    Update,
+    /// A resource was deleted
+    Delete,
+    /// An alert event or incident was acknowledged
+    Acknowledge,
+    /// A job was cancelled
+    Cancel,
+}
+
+impl AuditAction {
+    /// Name as stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Acknowledge => "acknowledge",
+            Self::Cancel => "cancel",
+        }
+    }
+
+    /// Parse a stored action name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "create" => Some(Self::Create),
+            "update" => Some(Self::Update),
+            "delete" => Some(Self::Delete),
+            "acknowledge" => Some(Self::Acknowledge),
+            "cancel" => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+}
+
+/// A change made by an API handler, before the request's actor and ID are
+/// known
+///
+/// Handlers attach one to their response; the audit middleware completes it
+/// into an [`AuditEntry`] and stores it.
+#[derive(Debug, Clone)]
+pub struct AuditChange {
+    /// What was done
+    pub action: AuditAction,
+    /// Kind of resource, e.g. `alert_rule`
+    pub resource_type: &'static str,
+    /// ID of the resource
+    pub resource_id: String,
+    /// State before the change, for updates and deletions
+    pub before: Option<Value>,
+    /// State after the change, for creations and updates
+    pub after: Option<Value>,
+}
+
+impl AuditChange {
+    /// A change to a resource, with its state before and after
+    pub fn new<B: Serialize, A: Serialize>(
+        action: AuditAction,
+        resource_type: &'static str,
+        resource_id: impl ToString,
+        before: Option<&B>,
+        after: Option<&A>,
+    ) -> Self {
+        Self {
+            action,
+            resource_type,
+            resource_id: resource_id.to_string(),
+            before: before.and_then(|b| serde_json::to_value(b).ok()),
+            after: after.and_then(|a| serde_json::to_value(a).ok()),
+        }
+    }
+
+    /// A resource was created
+    pub fn created<T: Serialize>(resource_type: &'static str, resource_id: impl ToString, after: &T) -> Self {
+        Self::new(AuditAction::Create, resource_type, resource_id, None::<&Value>, Some(after))
+    }
+
+    /// A resource was replaced
+    pub fn updated<T: Serialize>(
+        resource_type: &'static str,
+        resource_id: impl ToString,
+        before: &T,
+        after: &T,
+    ) -> Self {
+        Self::new(AuditAction::Update, resource_type, resource_id, Some(before), Some(after))
+    }
+
+    /// A resource was deleted
+    pub fn deleted<T: Serialize>(resource_type: &'static str, resource_id: impl ToString, before: &T) -> Self {
+        Self::new(AuditAction::Delete, resource_type, resource_id, Some(before), None::<&Value>)
+    }
+
+    /// Fields that differ between the before and after states, as
+    /// `{"field": {"before": ..., "after": ...}}`
+    ///
+    /// Fields missing on one side are reported as `null` there, so a
+    /// creation lists every field and a deletion every field it removed.
+    /// Returns None when nothing changed.
+    pub fn diff(&self) -> Option<Value> {
+        let empty = Map::new();
+        let as_object = |state: &Option<Value>| match state {
+            Some(Value::Object(fields)) => Some(fields.clone()),
+            _ => None,
+        };
+        let before = as_object(&self.before);
+        let after = as_object(&self.after);
+
+        // Non-object states (or none at all) are compared whole
+        if before.is_none() && after.is_none() {
+            return (self.before != self.after).then(|| {
+                serde_json::json!({ "value": { "before": self.before, "after": self.after } })
+            });
+        }
+
+        let before = before.as_ref().unwrap_or(&empty);
+        let after = after.as_ref().unwrap_or(&empty);
+        let mut changes = Map::new();
+        for key in before.keys().chain(after.keys().filter(|k| !before.contains_key(*k))) {
+            let (old, new) = (before.get(key), after.get(key));
+            if old != new {
+                changes.insert(
+                    key.clone(),
+                    serde_json::json!({ "before": old, "after": new }),
+                );
+            }
+        }
+
+        (!changes.is_empty()).then_some(Value::Object(changes))
+    }
+}
+
+/// A recorded change to a resource
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// When the change was made
+    pub occurred_at: DateTime<Utc>,
+
+    /// Who made it, from the request's `X-AgentTrace-Actor` header
+    pub actor: String,
+
+    /// What was done
+    pub action: AuditAction,
+
+    /// Kind of resource, e.g. `alert_rule`, `export` or `subscription`
+    pub resource_type: String,
+
+    /// ID of the resource
+    pub resource_id: String,
+
+    /// Changed fields with their values before and after
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<Value>,
+
+    /// ID of the request that made the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Audit log filters
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    /// Only changes by this actor
+    pub actor: Option<String>,
+    /// Only changes of this kind
+    pub action: Option<AuditAction>,
+    /// Only changes to this kind of resource
+    pub resource_type: Option<String>,
+    /// Only changes to the resource with this ID
+    pub resource_id: Option<String>,
+    /// Only changes made at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Only changes made before this time
+    pub until: Option<DateTime<Utc>>,
+    /// Most entries to return, newest first (default: 100)
+    pub limit: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_lists_changed_fields() {
+        let change = AuditChange::updated(
+            "alert_rule",
+            "r1",
+            &json!({ "name": "errors", "threshold": 5, "enabled": true }),
+            &json!({ "name": "errors", "threshold": 10, "enabled": true }),
+        );
+        assert_eq!(
+            change.diff(),
+            Some(json!({ "threshold": { "before": 5, "after": 10 } }))
+        );
+
+        let unchanged = AuditChange::updated("alert_rule", "r1", &json!({ "a": 1 }), &json!({ "a": 1 }));
+        assert_eq!(unchanged.diff(), None);
+    }
+
+    #[test]
+    fn test_diff_of_create_and_delete() {
+        let created = AuditChange::created("view", "v1", &json!({ "name": "slow" }));
+        assert_eq!(
+            created.diff(),
+            Some(json!({ "name": { "before": null, "after": "slow" } }))
+        );
+
+        let deleted = AuditChange::deleted("view", "v1", &json!({ "name": "slow" }));
+        assert_eq!(
+            deleted.diff(),
+            Some(json!({ "name": { "before": "slow", "after": null } }))
+        );
+    }
+
+    #[test]
+    fn test_action_round_trips() {
+        for action in [
+            AuditAction::Create,
+            AuditAction::Update,
+            AuditAction::Delete,
+            AuditAction::Acknowledge,
+            AuditAction::Cancel,
+        ] {
+            assert_eq!(AuditAction::parse(action.as_str()), Some(action));
+        }
+    }
+}
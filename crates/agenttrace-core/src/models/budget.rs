@@ -0,0 +1,419 @@
+//! Spend budget models
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::alert::{NotificationChannel, Severity};
+use crate::error::{Error, Result};
+
+/// Span attribute naming the project a span's cost counts against
+pub const PROJECT_ATTRIBUTE: &str = "agenttrace.project";
+
+/// Percentages of the budget notified when no thresholds are given
+pub const DEFAULT_BUDGET_THRESHOLDS: [u32; 3] = [50, 80, 100];
+
+/// Longest allowed budget name
+const MAX_NAME_LEN: usize = 100;
+
+/// Highest threshold accepted, to catch overspend by this much
+const MAX_THRESHOLD_PERCENT: u32 = 1_000;
+
+/// Calendar period a budget resets on, in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    /// Monday to Sunday
+    Weekly,
+    /// First to last day of the month
+    #[default]
+    Monthly,
+}
+
+impl BudgetPeriod {
+    /// Lowercase name, as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+        }
+    }
+
+    /// Parse a stored period name
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "weekly" => Ok(Self::Weekly),
+            "monthly" => Ok(Self::Monthly),
+            other => Err(Error::validation(format!("Unknown budget period '{}'", other))),
+        }
+    }
+
+    /// Start and end of the period containing `at`
+    pub fn bounds(&self, at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        let date = at.date_naive();
+        let (start, end) = match self {
+            Self::Weekly => {
+                let start = date - Duration::days(i64::from(date.weekday().num_days_from_monday()));
+                (start, start + Duration::days(7))
+            }
+            Self::Monthly => {
+                let start = NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap_or(date);
+                let end = if date.month() == 12 {
+                    NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1)
+                };
+                (start, end.unwrap_or(start + Duration::days(31)))
+            }
+        };
+
+        let midnight = |d: NaiveDate| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc();
+        (midnight(start), midnight(end))
+    }
+}
+
+/// A spend limit for a calendar period, optionally scoped to a service,
+/// model or project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Budget {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Unique name, e.g. `support-bot-monthly`
+    pub name: String,
+
+    /// Optional description
+    pub description: Option<String>,
+
+    /// Only count spans from this service
+    pub service_name: Option<String>,
+
+    /// Only count spans from this model
+    pub model_name: Option<String>,
+
+    /// Only count spans whose `agenttrace.project` attribute is this
+    pub project: Option<String>,
+
+    /// Period the budget resets on
+    pub period: BudgetPeriod,
+
+    /// Spend allowed per period, in USD
+    pub amount_usd: f64,
+
+    /// Percentages of the amount that notify when crossed, ascending
+    pub thresholds: Vec<u32>,
+
+    /// Where threshold notifications go
+    pub notification_channels: Vec<NotificationChannel>,
+
+    /// Whether thresholds are checked
+    pub enabled: bool,
+
+    /// When the budget was created
+    pub created_at: DateTime<Utc>,
+
+    /// When the budget was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Budget {
+    /// Thresholds reached by `spent_usd`
+    pub fn thresholds_crossed(&self, spent_usd: f64) -> Vec<u32> {
+        let percent = percent_of(spent_usd, self.amount_usd);
+        self.thresholds
+            .iter()
+            .copied()
+            .filter(|t| percent >= f64::from(*t))
+            .collect()
+    }
+}
+
+/// Input for creating or replacing a budget
+#[derive(Debug, Clone, Deserialize)]
+pub struct BudgetInput {
+    /// Unique name
+    pub name: String,
+    /// Optional description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Only count spans from this service
+    #[serde(default)]
+    pub service_name: Option<String>,
+    /// Only count spans from this model
+    #[serde(default)]
+    pub model_name: Option<String>,
+    /// Only count spans whose `agenttrace.project` attribute is this
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Period the budget resets on; defaults to monthly
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    /// Spend allowed per period, in USD
+    pub amount_usd: f64,
+    /// Percentages that notify when crossed, ascending; defaults to 50, 80 and 100
+    #[serde(default = "default_thresholds")]
+    pub thresholds: Vec<u32>,
+    /// Where threshold notifications go
+    #[serde(default)]
+    pub notification_channels: Vec<NotificationChannel>,
+    /// Whether thresholds are checked; defaults to true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_thresholds() -> Vec<u32> {
+    DEFAULT_BUDGET_THRESHOLDS.to_vec()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl BudgetInput {
+    /// Check the name, amount and thresholds
+    pub fn validate(&self) -> Result<()> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(Error::validation(format!(
+                "Budget name must be 1 to {} characters",
+                MAX_NAME_LEN
+            )));
+        }
+
+        if !self.amount_usd.is_finite() || self.amount_usd <= 0.0 {
+            return Err(Error::validation("Budget amount must be greater than zero"));
+        }
+
+        if self.thresholds.is_empty() {
+            return Err(Error::validation("Budget needs at least one threshold"));
+        }
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            if *threshold == 0 || *threshold > MAX_THRESHOLD_PERCENT {
+                return Err(Error::validation(format!(
+                    "Budget thresholds must be 1 to {} percent",
+                    MAX_THRESHOLD_PERCENT
+                )));
+            }
+            if i > 0 && *threshold <= self.thresholds[i - 1] {
+                return Err(Error::validation("Budget thresholds must be in increasing order"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A budget's spend so far in its current period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    /// The budget, flattened into the status
+    #[serde(flatten)]
+    pub budget: Budget,
+    /// Start of the current period
+    pub period_start: DateTime<Utc>,
+    /// End of the current period
+    pub period_end: DateTime<Utc>,
+    /// Spend so far this period, in USD
+    pub spent_usd: f64,
+    /// Spend as a percentage of the amount
+    pub percent_used: f64,
+    /// Spend by the end of the period if it continues at the rate so far
+    pub projected_usd: f64,
+    /// Thresholds crossed this period
+    pub thresholds_crossed: Vec<u32>,
+}
+
+impl BudgetStatus {
+    /// Status of a budget that has spent `spent_usd` of the period
+    /// containing `now`
+    pub fn new(budget: Budget, spent_usd: f64, now: DateTime<Utc>) -> Self {
+        let (period_start, period_end) = budget.period.bounds(now);
+        let elapsed = (now - period_start).num_seconds().max(1) as f64;
+        let length = (period_end - period_start).num_seconds() as f64;
+
+        Self {
+            period_start,
+            period_end,
+            spent_usd,
+            percent_used: percent_of(spent_usd, budget.amount_usd),
+            projected_usd: spent_usd * (length / elapsed).max(1.0),
+            thresholds_crossed: budget.thresholds_crossed(spent_usd),
+            budget,
+        }
+    }
+}
+
+/// Notification that a budget crossed a threshold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetAlert {
+    /// Budget that crossed the threshold
+    pub budget_id: Uuid,
+    /// Name of the budget
+    pub budget_name: String,
+    /// Service the budget covers
+    pub service_name: Option<String>,
+    /// Model the budget covers
+    pub model_name: Option<String>,
+    /// Project the budget covers
+    pub project: Option<String>,
+    /// Period the budget resets on
+    pub period: BudgetPeriod,
+    /// Start of the period
+    pub period_start: DateTime<Utc>,
+    /// End of the period
+    pub period_end: DateTime<Utc>,
+    /// The threshold crossed, as a percentage of the amount
+    pub threshold_percent: u32,
+    /// Spend so far this period, in USD
+    pub spent_usd: f64,
+    /// Spend allowed per period, in USD
+    pub amount_usd: f64,
+    /// Spend as a percentage of the amount
+    pub percent_used: f64,
+    /// Human-readable summary
+    pub message: String,
+    /// When the threshold was crossed
+    pub triggered_at: DateTime<Utc>,
+}
+
+impl BudgetAlert {
+    /// Alert for a status crossing `threshold_percent`
+    pub fn new(status: &BudgetStatus, threshold_percent: u32, now: DateTime<Utc>) -> Self {
+        let budget = &status.budget;
+        Self {
+            budget_id: budget.id,
+            budget_name: budget.name.clone(),
+            service_name: budget.service_name.clone(),
+            model_name: budget.model_name.clone(),
+            project: budget.project.clone(),
+            period: budget.period,
+            period_start: status.period_start,
+            period_end: status.period_end,
+            threshold_percent,
+            spent_usd: status.spent_usd,
+            amount_usd: budget.amount_usd,
+            percent_used: status.percent_used,
+            message: format!(
+                "{} has spent ${:.2} of its {} ${:.2} budget ({:.0}%), crossing {}%",
+                budget.name,
+                status.spent_usd,
+                budget.period.as_str(),
+                budget.amount_usd,
+                status.percent_used,
+                threshold_percent
+            ),
+            triggered_at: now,
+        }
+    }
+
+    /// Critical once the budget is spent, a warning from 80%
+    pub fn severity(&self) -> Severity {
+        match self.threshold_percent {
+            100.. => Severity::Critical,
+            80..=99 => Severity::Warning,
+            _ => Severity::Info,
+        }
+    }
+
+    /// Key identifying the alert to incident tools; one per budget,
+    /// period and threshold
+    pub fn key(&self) -> String {
+        format!(
+            "budget:{}:{}:{}",
+            self.budget_id,
+            self.period_start.format("%Y-%m-%d"),
+            self.threshold_percent
+        )
+    }
+}
+
+fn percent_of(spent_usd: f64, amount_usd: f64) -> f64 {
+    if amount_usd <= 0.0 {
+        return 0.0;
+    }
+    spent_usd * 100.0 / amount_usd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn input() -> BudgetInput {
+        BudgetInput {
+            name: "support-bot-monthly".to_string(),
+            description: None,
+            service_name: Some("support-bot".to_string()),
+            model_name: None,
+            project: None,
+            period: BudgetPeriod::Monthly,
+            amount_usd: 2000.0,
+            thresholds: default_thresholds(),
+            notification_channels: vec![],
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let at = Utc.with_ymd_and_hms(2024, 12, 18, 15, 30, 0).unwrap();
+
+        let (start, end) = BudgetPeriod::Monthly.bounds(at);
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap());
+
+        // 2024-12-18 is a Wednesday
+        let (start, end) = BudgetPeriod::Weekly.bounds(at);
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 12, 16, 0, 0, 0).unwrap());
+        assert_eq!(end, Utc.with_ymd_and_hms(2024, 12, 23, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_status_thresholds_and_projection() {
+        let input = input();
+        let budget = Budget {
+            id: Uuid::new_v4(),
+            name: input.name,
+            description: None,
+            service_name: input.service_name,
+            model_name: None,
+            project: None,
+            period: input.period,
+            amount_usd: input.amount_usd,
+            thresholds: input.thresholds,
+            notification_channels: vec![],
+            enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+
+        // Halfway through a 30-day month with $1,700 spent
+        let now = Utc.with_ymd_and_hms(2024, 11, 16, 0, 0, 0).unwrap();
+        let status = BudgetStatus::new(budget, 1700.0, now);
+
+        assert_eq!(status.thresholds_crossed, vec![50, 80]);
+        assert_eq!(status.percent_used, 85.0);
+        assert_eq!(status.projected_usd, 3400.0);
+
+        let alert = BudgetAlert::new(&status, 80, now);
+        assert_eq!(alert.severity(), Severity::Warning);
+        assert_eq!(alert.key(), format!("budget:{}:2024-11-01:80", alert.budget_id));
+    }
+
+    #[test]
+    fn test_validate_budget_input() {
+        assert!(input().validate().is_ok());
+
+        let mut zero = input();
+        zero.amount_usd = 0.0;
+        assert!(zero.validate().is_err());
+
+        let mut unordered = input();
+        unordered.thresholds = vec![80, 50];
+        assert!(unordered.validate().is_err());
+
+        let mut none = input();
+        none.thresholds.clear();
+        assert!(none.validate().is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Debug streaming session models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A time-boxed window during which matching spans are streamed in full
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugSession {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Only stream spans from this service
+    pub service_name: Option<String>,
+
+    /// Only stream spans whose trace ID matches this pattern (`*` is a wildcard)
+    pub trace_id_pattern: Option<String>,
+
+    /// Redis channel the full span payloads are published to
+    pub channel: String,
+
+    /// When the session was started
+    pub created_at: DateTime<Utc>,
+
+    /// When the session ends and normal capture resumes
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Input for starting a debug session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugSessionInput {
+    /// Only stream spans from this service
+    pub service_name: Option<String>,
+
+    /// Only stream spans whose trace ID matches this pattern (`*` is a wildcard)
+    pub trace_id_pattern: Option<String>,
+
+    /// How long the session lasts (defaults to 10 minutes)
+    pub duration_minutes: Option<i64>,
+}
@@ -0,0 +1,210 @@
+//! End-user feedback models
+//!
+//! Feedback comes from the product UI an agent is embedded in: a thumbs up
+//! or down and/or a comment on the answer a trace produced.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+
+/// Longest allowed feedback comment, in characters
+const MAX_COMMENT_LEN: usize = 4000;
+
+/// Longest trace ID, as stored
+const MAX_TRACE_ID_LEN: usize = 32;
+
+/// Thumbs up or down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackRating {
+    Positive,
+    Negative,
+}
+
+impl FeedbackRating {
+    /// Name as stored in the database
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Positive => "positive",
+            Self::Negative => "negative",
+        }
+    }
+
+    /// Parse a stored rating name
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "positive" => Some(Self::Positive),
+            "negative" => Some(Self::Negative),
+            _ => None,
+        }
+    }
+}
+
+/// Feedback an end user left on a trace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceFeedback {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Trace the feedback is about
+    pub trace_id: String,
+
+    /// Thumbs up or down, if given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<FeedbackRating>,
+
+    /// Free-text comment, if given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+
+    /// End user who left it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+
+    /// When it was left
+    pub created_at: DateTime<Utc>,
+}
+
+/// Input for recording feedback
+#[derive(Debug, Clone, Deserialize)]
+pub struct FeedbackInput {
+    pub trace_id: String,
+    pub rating: Option<FeedbackRating>,
+    pub comment: Option<String>,
+    pub user_id: Option<String>,
+}
+
+impl FeedbackInput {
+    /// Check the input, trimming the comment and dropping it when blank
+    ///
+    /// Feedback needs a rating, a comment or both.
+    pub fn validate(&mut self) -> Result<()> {
+        let trace_id = self.trace_id.trim();
+        if trace_id.is_empty() {
+            return Err(Error::validation("Feedback needs a trace_id"));
+        }
+        if trace_id.chars().count() > MAX_TRACE_ID_LEN {
+            return Err(Error::validation(format!(
+                "trace_id can be at most {} characters",
+                MAX_TRACE_ID_LEN
+            )));
+        }
+
+        self.comment = self
+            .comment
+            .as_deref()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::to_string);
+        if self.comment.as_ref().is_some_and(|c| c.chars().count() > MAX_COMMENT_LEN) {
+            return Err(Error::validation(format!(
+                "Feedback comments can be at most {} characters",
+                MAX_COMMENT_LEN
+            )));
+        }
+        if self.rating.is_none() && self.comment.is_none() {
+            return Err(Error::validation("Feedback needs a rating, a comment or both"));
+        }
+
+        Ok(())
+    }
+
+    /// Feedback record for validated input, left now
+    pub fn into_feedback(self) -> TraceFeedback {
+        TraceFeedback {
+            id: Uuid::new_v4(),
+            trace_id: self.trace_id.trim().to_string(),
+            rating: self.rating,
+            comment: self.comment,
+            user_id: self.user_id.filter(|u| !u.trim().is_empty()),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Traces that got one rating, with what they cost and how long they took
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeedbackGroup {
+    /// Ratings given
+    pub feedback_count: i64,
+    /// Distinct traces rated
+    pub trace_count: i64,
+    /// Average over the rated traces; None without any
+    pub avg_cost_usd: Option<f64>,
+    pub avg_duration_ms: Option<f64>,
+    pub avg_tokens: Option<f64>,
+}
+
+/// Feedback on traces started in a window, to correlate cost and latency
+/// with user satisfaction
+///
+/// A trace rated both ways by different users counts in both groups.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FeedbackMetrics {
+    pub positive: FeedbackGroup,
+    pub negative: FeedbackGroup,
+    /// Feedback with a comment, rated or not
+    pub comment_count: i64,
+    /// Percentage of ratings that were positive; None without ratings
+    pub satisfaction_rate: Option<f64>,
+}
+
+impl FeedbackMetrics {
+    /// Fill in the satisfaction rate from the rating counts
+    pub fn compute_ratios(&mut self) {
+        let rated = self.positive.feedback_count + self.negative.feedback_count;
+        self.satisfaction_rate =
+            (rated > 0).then(|| self.positive.feedback_count as f64 / rated as f64 * 100.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_input_validation() {
+        let mut input = FeedbackInput {
+            trace_id: "abc123".to_string(),
+            rating: None,
+            comment: Some("  ".to_string()),
+            user_id: None,
+        };
+        assert!(input.validate().is_err());
+
+        input.rating = Some(FeedbackRating::Negative);
+        input.validate().unwrap();
+        assert_eq!(input.comment, None);
+
+        input.comment = Some("x".repeat(MAX_COMMENT_LEN + 1));
+        assert!(input.validate().is_err());
+
+        // The limit counts characters, not bytes
+        input.comment = Some("é".repeat(MAX_COMMENT_LEN));
+        input.validate().unwrap();
+
+        input.comment = Some(" wrong answer ".to_string());
+        input.validate().unwrap();
+        assert_eq!(input.comment.as_deref(), Some("wrong answer"));
+
+        input.trace_id = "a".repeat(MAX_TRACE_ID_LEN + 1);
+        assert!(input.validate().is_err());
+
+        input.trace_id = " ".to_string();
+        assert!(input.validate().is_err());
+    }
+
+    #[test]
+    fn test_satisfaction_rate() {
+        let mut metrics = FeedbackMetrics::default();
+        metrics.compute_ratios();
+        assert_eq!(metrics.satisfaction_rate, None);
+
+        metrics.positive.feedback_count = 3;
+        metrics.negative.feedback_count = 1;
+        metrics.compute_ratios();
+        assert_eq!(metrics.satisfaction_rate, Some(75.0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Background query job models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Where a query job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a worker
+    Queued,
+    /// Being worked on
+    Running,
+    /// Finished with a result
+    Completed,
+    /// Stopped by an error
+    Failed,
+    /// Stopped on request
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job has stopped for good
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Work a query job performs over `[since, until)`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryJobRequest {
+    /// Cost per group, as from `/api/v1/metrics/costs`
+    CostRollup {
+        /// Group by model, service, operation, day, hour or `attr:<key>`
+        group_by: String,
+        service: Option<String>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    },
+    /// Token and cost efficiency per operation, as from
+    /// `/api/v1/metrics/efficiency`
+    EfficiencyRollup {
+        service: Option<String>,
+        model: Option<String>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    },
+    /// Every span matching a search query, oldest first
+    Export {
+        /// Search query, e.g. `service:planner status:error timeout`
+        #[serde(default)]
+        filter: Option<String>,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    },
+    /// Every span matching a search query, written to a CSV, JSONL or
+    /// Parquet file
+    BulkExport(BulkExportRequest),
+}
+
+impl QueryJobRequest {
+    /// Time range the job covers
+    pub fn range(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        match self {
+            Self::CostRollup { since, until, .. }
+            | Self::EfficiencyRollup { since, until, .. }
+            | Self::Export { since, until, .. } => (*since, *until),
+            Self::BulkExport(export) => (export.since, export.until),
+        }
+    }
+}
+
+/// File format of a bulk export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFileFormat {
+    /// One row per span with the commonly used fields
+    Csv,
+    /// One span as JSON per line
+    Jsonl,
+    /// The archive's Parquet layout: typed columns plus the full span
+    Parquet,
+}
+
+impl ExportFileFormat {
+    /// File name extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+            Self::Parquet => "parquet",
+        }
+    }
+
+    /// MIME type of the exported file
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Jsonl => "application/x-ndjson",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// Where a bulk export's file goes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportDestination {
+    /// Kept by the collector for `GET /api/v1/exports/{id}/download`
+    #[default]
+    Download,
+    /// Uploaded to S3
+    S3 {
+        /// Object URL, e.g. `s3://bucket/exports/march.parquet`; a URL ending
+        /// in `/` gets a file named after the job
+        url: String,
+    },
+}
+
+/// Bulk export as submitted to `POST /api/v1/exports`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkExportRequest {
+    /// Search query, e.g. `service:planner model:gpt-4o`
+    #[serde(default)]
+    pub filter: Option<String>,
+    pub format: ExportFileFormat,
+    #[serde(default)]
+    pub destination: ExportDestination,
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+/// A query job as reported by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryJob {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// What the job does
+    pub request: QueryJobRequest,
+
+    /// Where the job is in its lifecycle
+    pub status: JobStatus,
+
+    /// Fraction of the work done, from 0 to 1
+    pub progress: f64,
+
+    /// When the job was submitted
+    pub created_at: DateTime<Utc>,
+
+    /// When a worker picked the job up
+    pub started_at: Option<DateTime<Utc>>,
+
+    /// When the job stopped
+    pub finished_at: Option<DateTime<Utc>>,
+
+    /// Why the job failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// Output of a completed job
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+impl QueryJob {
+    /// A newly submitted job
+    pub fn new(request: QueryJobRequest) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            request,
+            status: JobStatus::Queued,
+            progress: 0.0,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            error: None,
+            result: None,
+        }
+    }
+}
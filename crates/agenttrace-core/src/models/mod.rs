@@ -4,10 +4,38 @@ pub mod span;
 pub mod trace;
 pub mod metrics;
 pub mod alert;
+pub mod debug;
 pub mod query;
+pub mod schema;
+pub mod archive;
+pub mod annotation;
+pub mod job;
+pub mod view;
+pub mod aggregate;
+pub mod subscription;
+pub mod audit;
+pub mod budget;
+pub mod slo;
+pub mod session;
+pub mod feedback;
+pub mod payload;
 
 pub use span::*;
 pub use trace::*;
 pub use metrics::*;
 pub use alert::*;
+pub use debug::*;
 pub use query::*;
+pub use schema::*;
+pub use archive::*;
+pub use annotation::*;
+pub use job::*;
+pub use view::*;
+pub use aggregate::*;
+pub use subscription::*;
+pub use audit::*;
+pub use budget::*;
+pub use slo::*;
+pub use session::*;
+pub use feedback::*;
+pub use payload::*;
@@ -0,0 +1,35 @@
+//! Full prompt/completion payload models
+//!
+//! Spans only keep a short preview of their prompt and completion. When
+//! payload storage is enabled, the full text is stored separately, redacted
+//! and size-limited, and loaded on demand.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Full prompt and completion of a span
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpanPayload {
+    /// Trace the span belongs to
+    pub trace_id: String,
+
+    /// Span the payload was captured from
+    pub span_id: String,
+
+    /// Full prompt, redacted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    /// Full completion, redacted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion: Option<String>,
+
+    /// Whether the prompt or completion was cut at `payloads.max_bytes`
+    pub truncated: bool,
+
+    /// Size of the redacted prompt and completion before truncation
+    pub size_bytes: i64,
+
+    /// When the payload was captured
+    pub created_at: DateTime<Utc>,
+}
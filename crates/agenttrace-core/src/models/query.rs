@@ -1,7 +1,12 @@
 //! Query and response types shared between API and database layers
 
-use chrono::{DateTime, Utc};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
 
 /// Search filter for advanced queries
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -11,6 +16,34 @@ pub struct SearchFilter {
     pub value: serde_json::Value,
 }
 
+impl SearchFilter {
+    /// Attribute key for `attributes.<key>` filters
+    ///
+    /// Keys are matched as a whole, so `attributes.user.id` filters on an
+    /// attribute named `user.id`.
+    pub fn attribute_key(&self) -> Option<&str> {
+        self.field
+            .strip_prefix("attributes.")
+            .filter(|key| !key.is_empty())
+    }
+}
+
+/// Values an attribute filter value matches by equality
+///
+/// A string also matches the number or boolean it spells, so `"2"` finds
+/// spans tagged with either `"2"` or `2`, as in compact `attr.` filters.
+pub fn attribute_match_values(value: &serde_json::Value) -> Vec<serde_json::Value> {
+    let mut values = vec![value.clone()];
+    if let Some(parsed) = value
+        .as_str()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(s).ok())
+        .filter(|v| v.is_number() || v.is_boolean())
+    {
+        values.push(parsed);
+    }
+    values
+}
+
 /// Sort configuration
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SortConfig {
@@ -18,6 +51,288 @@ pub struct SortConfig {
     pub descending: bool,
 }
 
+/// Trace summary fields trace lists can be sorted by
+pub const TRACE_SORT_FIELDS: &[&str] = &[
+    "started_at",
+    "duration_ms",
+    "span_count",
+    "error_count",
+    "total_tokens",
+    "total_cost_usd",
+];
+
+impl SortConfig {
+    /// Resolve the order of a trace list to a trace summary field and
+    /// whether it's descending, or None for the default newest first
+    ///
+    /// Only the default order pages with cursors.
+    pub fn trace_order(sort: Option<&Self>, cursor: Option<&PageCursor>) -> Result<Option<(&'static str, bool)>> {
+        let Some(sort) = sort.filter(|s| !(s.field == "started_at" && s.descending)) else {
+            return Ok(None);
+        };
+        let field = TRACE_SORT_FIELDS
+            .iter()
+            .find(|f| **f == sort.field)
+            .copied()
+            .ok_or_else(|| Error::validation(format!("Cannot sort traces by '{}'", sort.field)))?;
+        if cursor.is_some() {
+            return Err(Error::validation("Cursors can only be used when listing traces newest first"));
+        }
+        Ok(Some((field, sort.descending)))
+    }
+}
+
+/// Span filter parsed from a compact query string
+///
+/// Terms are separated by whitespace and values may be double-quoted:
+///
+/// ```text
+/// operation:retrieve status:error min_duration:500 attr.user.id:"u 42" timeout
+/// ```
+///
+/// Supported keys are `operation`, `service`, `model`, `status`, `priority`,
+/// `min_duration`, `max_duration`, `min_cost` and `max_cost`, plus
+/// `attr.<key>` for exact attribute matches and `has_negative_feedback`
+/// (`true` or `false`) for spans of traces an end user rated down. Bare
+/// words are matched as free text against the operation name and
+/// prompt/completion previews, as in global search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpanQuery {
+    pub text: Option<String>,
+    pub operation: Option<String>,
+    pub service: Option<String>,
+    pub model: Option<String>,
+    pub status: Option<String>,
+    pub priority: Option<super::SpanPriority>,
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    pub min_cost: Option<f64>,
+    pub max_cost: Option<f64>,
+    pub attributes: Vec<(String, String)>,
+    /// Whether the span's trace got a negative rating
+    pub has_negative_feedback: Option<bool>,
+}
+
+impl SpanQuery {
+    /// Parse a query string
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut query = Self::default();
+        let mut words = Vec::new();
+
+        for term in tokenize(input)? {
+            let Some((key, value)) = term.split_once(':') else {
+                words.push(term);
+                continue;
+            };
+
+            if value.is_empty() {
+                return Err(Error::validation(format!("Missing value for '{}'", key)));
+            }
+
+            if let Some(attr) = key.strip_prefix("attr.") {
+                if attr.is_empty() {
+                    return Err(Error::validation("Attribute filters need a key, e.g. attr.user.id:42"));
+                }
+                query.attributes.push((attr.to_string(), value.to_string()));
+                continue;
+            }
+
+            match key {
+                "operation" => query.operation = Some(value.to_string()),
+                "service" => query.service = Some(value.to_string()),
+                "model" => query.model = Some(value.to_string()),
+                "status" => query.status = Some(value.to_lowercase()),
+                "priority" => {
+                    query.priority = Some(super::SpanPriority::parse(value).ok_or_else(|| {
+                        Error::validation(format!(
+                            "'priority' expects high, normal or low, got '{}'",
+                            value
+                        ))
+                    })?);
+                }
+                "min_duration" => query.min_duration = Some(parse_number(key, value)?),
+                "max_duration" => query.max_duration = Some(parse_number(key, value)?),
+                "min_cost" => query.min_cost = Some(parse_number(key, value)?),
+                "max_cost" => query.max_cost = Some(parse_number(key, value)?),
+                "has_negative_feedback" => {
+                    query.has_negative_feedback = Some(value.parse().map_err(|_| {
+                        Error::validation(format!(
+                            "'has_negative_feedback' expects true or false, got '{}'",
+                            value
+                        ))
+                    })?);
+                }
+                other => return Err(Error::validation(format!("Unknown filter field: {}", other))),
+            }
+        }
+
+        if !words.is_empty() {
+            query.text = Some(words.join(" "));
+        }
+
+        Ok(query)
+    }
+
+    /// Reject filters that can't be checked against a span on its own, for
+    /// queries evaluated on spans as they arrive
+    ///
+    /// Feedback is left on a trace after the fact, so only stored spans can
+    /// be filtered by it.
+    pub fn check_live(&self) -> Result<()> {
+        if self.has_negative_feedback.is_some() {
+            return Err(Error::validation(
+                "'has_negative_feedback' only applies to stored spans, not live filters",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check whether a span matches the query
+    ///
+    /// Mirrors the SQL filter: text and operation match case-insensitively as
+    /// substrings, and attribute values compare against their text form.
+    /// Feedback filters are left to the store, which knows the trace's
+    /// feedback.
+    pub fn matches(&self, span: &super::Span) -> bool {
+        if let Some(text) = &self.text {
+            let text = text.to_lowercase();
+            let contains = |field: Option<&str>| {
+                field.is_some_and(|f| f.to_lowercase().contains(&text))
+            };
+            if !contains(Some(&span.operation_name))
+                && !contains(span.prompt_preview.as_deref())
+                && !contains(span.completion_preview.as_deref())
+            {
+                return false;
+            }
+        }
+
+        if let Some(op) = &self.operation {
+            if !span.operation_name.to_lowercase().contains(&op.to_lowercase()) {
+                return false;
+            }
+        }
+
+        if self.service.as_ref().is_some_and(|s| s != &span.service_name)
+            || self.model.as_ref().is_some_and(|m| span.model_name.as_ref() != Some(m))
+        {
+            return false;
+        }
+
+        if let Some(status) = &self.status {
+            let span_status = serde_json::to_value(span.status).unwrap_or_default();
+            if span_status.as_str() != Some(status.as_str()) {
+                return false;
+            }
+        }
+
+        if self.priority.is_some_and(|p| p != span.priority) {
+            return false;
+        }
+
+        let in_range = |value: Option<f64>, min: Option<f64>, max: Option<f64>| {
+            match value {
+                Some(v) => min.map_or(true, |m| v >= m) && max.map_or(true, |m| v <= m),
+                None => min.is_none() && max.is_none(),
+            }
+        };
+        if !in_range(span.duration_ms, self.min_duration, self.max_duration)
+            || !in_range(span.cost_usd, self.min_cost, self.max_cost)
+        {
+            return false;
+        }
+
+        self.attributes.iter().all(|(key, value)| match span.attributes.get(key) {
+            Some(serde_json::Value::String(s)) => s == value,
+            Some(serde_json::Value::Null) | None => false,
+            Some(other) => &other.to_string() == value,
+        })
+    }
+}
+
+/// Split on whitespace, keeping double-quoted sections together
+fn tokenize(input: &str) -> Result<Vec<String>> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(Error::validation("Unterminated quote in filter"));
+    }
+    if !current.is_empty() {
+        terms.push(current);
+    }
+
+    Ok(terms)
+}
+
+fn parse_number(key: &str, value: &str) -> Result<f64> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite())
+        .ok_or_else(|| Error::validation(format!("'{}' expects a number, got '{}'", key, value)))
+}
+
+/// Keyset position of the last row on a page, ordered by start time then
+/// span ID
+///
+/// Unlike offsets, cursors stay cheap on deep pages and don't skip or repeat
+/// rows when new spans arrive between requests. Clients pass the encoded
+/// token back as-is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub started_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl PageCursor {
+    /// Cursor positioned after a span
+    pub fn after(span: &super::Span) -> Self {
+        Self {
+            started_at: span.started_at,
+            id: span.id,
+        }
+    }
+
+    /// Encode as an opaque, URL-safe token
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}|{}",
+            self.started_at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+            self.id
+        ))
+    }
+
+    /// Decode a token produced by [`PageCursor::encode`]
+    pub fn decode(token: &str) -> Result<Self> {
+        let invalid = || Error::validation("Invalid pagination cursor");
+
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+        let (started_at, id) = text.split_once('|').ok_or_else(invalid)?;
+
+        Ok(Self {
+            started_at: DateTime::parse_from_rfc3339(started_at)
+                .map_err(|_| invalid())?
+                .with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
 /// Trace summary
 #[derive(Debug, Clone, Serialize)]
 pub struct TraceSummary {
@@ -30,6 +345,172 @@ pub struct TraceSummary {
     pub error_count: i64,
     pub total_tokens: i64,
     pub total_cost_usd: f64,
+    pub time_breakdown: Option<TimeBreakdown>,
+}
+
+impl TraceSummary {
+    /// Value of a [`TRACE_SORT_FIELDS`] field to sort by, None when it's
+    /// missing
+    pub fn sort_value(&self, field: &str) -> Option<f64> {
+        match field {
+            "started_at" => Some(self.started_at.timestamp_micros() as f64),
+            "duration_ms" => self.duration_ms,
+            "span_count" => Some(self.span_count as f64),
+            "error_count" => Some(self.error_count as f64),
+            "total_tokens" => Some(self.total_tokens as f64),
+            "total_cost_usd" => Some(self.total_cost_usd),
+            _ => None,
+        }
+    }
+
+    /// Summarize a trace from its spans
+    ///
+    /// Root fields come from the first span without a parent.
+    pub fn from_spans(trace_id: &str, spans: &[super::Span]) -> Self {
+        let root = spans.iter().find(|s| s.parent_span_id.is_none());
+
+        Self {
+            trace_id: trace_id.to_string(),
+            root_operation: root.map(|s| s.operation_name.clone()).unwrap_or_default(),
+            service_name: root.map(|s| s.service_name.clone()).unwrap_or_default(),
+            started_at: root.map(|s| s.started_at).unwrap_or_else(Utc::now),
+            duration_ms: root.and_then(|s| s.duration_ms),
+            span_count: spans.len() as i64,
+            error_count: spans
+                .iter()
+                .filter(|s| s.status == super::SpanStatus::Error)
+                .count() as i64,
+            total_tokens: spans
+                .iter()
+                .map(|s| i64::from(s.tokens_in.unwrap_or(0)) + i64::from(s.tokens_out.unwrap_or(0)))
+                .sum(),
+            total_cost_usd: spans.iter().filter_map(|s| s.cost_usd).sum(),
+            time_breakdown: root
+                .and_then(|s| s.duration_ms)
+                .map(|d| TimeBreakdown::from_spans(d, spans)),
+        }
+    }
+}
+
+/// Traces to find by what their spans contain, as in Jaeger's trace search
+///
+/// A trace matches when its root started within `[since, until]`, its
+/// duration is within bounds, one span has `service` and exactly
+/// `operation`, and every query in `spans` matches at least one span.
+#[derive(Debug, Clone, Default)]
+pub struct TraceSearch {
+    /// A span must come from this service
+    pub service: Option<String>,
+    /// The same span must have exactly this operation name
+    pub operation: Option<String>,
+    /// Each query must match at least one span
+    pub spans: Vec<SpanQuery>,
+    /// Earliest root span start
+    pub since: Option<DateTime<Utc>>,
+    /// Latest root span start
+    pub until: Option<DateTime<Utc>>,
+    /// Shortest trace duration in milliseconds
+    pub min_duration: Option<f64>,
+    /// Longest trace duration in milliseconds
+    pub max_duration: Option<f64>,
+    /// Most traces to return, most recent first
+    pub limit: i64,
+}
+
+impl TraceSearch {
+    /// Whether a trace's summary and spans match
+    pub fn matches(&self, summary: &TraceSummary, spans: &[super::Span]) -> bool {
+        let duration = summary.duration_ms.unwrap_or(0.0);
+        let named = |s: &super::Span| {
+            self.service.as_ref().is_none_or(|svc| svc == &s.service_name)
+                && self.operation.as_ref().is_none_or(|op| op == &s.operation_name)
+        };
+
+        self.since.is_none_or(|since| summary.started_at >= since)
+            && self.until.is_none_or(|until| summary.started_at <= until)
+            && self.min_duration.is_none_or(|min| duration >= min)
+            && self.max_duration.is_none_or(|max| duration <= max)
+            && spans.iter().any(named)
+            && self.spans.iter().all(|query| spans.iter().any(|s| query.matches(s)))
+    }
+}
+
+/// Where a trace's wall-clock time went
+///
+/// LLM and tool time are summed over spans, so concurrent calls each count in
+/// full; overhead is whatever remains of the trace duration and never goes
+/// below zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TimeBreakdown {
+    pub total_ms: f64,
+    pub llm_ms: f64,
+    pub tool_ms: f64,
+    pub overhead_ms: f64,
+}
+
+impl TimeBreakdown {
+    /// Split a trace duration given its LLM and tool time
+    pub fn new(total_ms: f64, llm_ms: f64, tool_ms: f64) -> Self {
+        Self {
+            total_ms,
+            llm_ms,
+            tool_ms,
+            overhead_ms: (total_ms - llm_ms - tool_ms).max(0.0),
+        }
+    }
+
+    /// Compute the breakdown for a trace from its spans
+    ///
+    /// Spans with a model count as LLM time; other spans with a tool count as
+    /// tool time, preferring `tool_duration_ms` over the span duration.
+    pub fn from_spans(total_ms: f64, spans: &[super::Span]) -> Self {
+        let llm_ms = spans
+            .iter()
+            .filter(|s| s.is_llm_call())
+            .filter_map(|s| s.duration_ms)
+            .sum();
+        let tool_ms = spans
+            .iter()
+            .filter(|s| s.is_tool_call() && !s.is_llm_call())
+            .filter_map(|s| s.tool_duration_ms.or(s.duration_ms))
+            .sum();
+
+        Self::new(total_ms, llm_ms, tool_ms)
+    }
+}
+
+/// Aggregate time breakdown across traces
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeBreakdownMetric {
+    pub trace_count: i64,
+    pub total: TimeBreakdown,
+    pub avg: TimeBreakdown,
+    pub llm_share: f64,
+    pub tool_share: f64,
+    pub overhead_share: f64,
+}
+
+impl TimeBreakdownMetric {
+    /// Build the aggregate from summed per-trace breakdowns
+    pub fn from_totals(trace_count: i64, total: TimeBreakdown) -> Self {
+        let n = trace_count.max(1) as f64;
+        let busy = total.llm_ms + total.tool_ms + total.overhead_ms;
+        let share = |v: f64| if busy > 0.0 { v / busy } else { 0.0 };
+
+        Self {
+            trace_count,
+            total,
+            avg: TimeBreakdown {
+                total_ms: total.total_ms / n,
+                llm_ms: total.llm_ms / n,
+                tool_ms: total.tool_ms / n,
+                overhead_ms: total.overhead_ms / n,
+            },
+            llm_share: share(total.llm_ms),
+            tool_share: share(total.tool_ms),
+            overhead_share: share(total.overhead_ms),
+        }
+    }
 }
 
 /// Summary metrics response
@@ -45,6 +526,113 @@ pub struct MetricsSummaryResponse {
     pub p50_latency_ms: f64,
     pub p95_latency_ms: f64,
     pub p99_latency_ms: f64,
+    /// Attainment of registered latency targets (PostgreSQL storage only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_targets: Option<LatencyTargetSummary>,
+    /// Provider incidents overlapping the range (PostgreSQL storage only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub upstream_incidents: Vec<super::Annotation>,
+}
+
+/// How an operation's latency compares with its registered target
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationTargetStatus {
+    pub service_name: String,
+    pub operation: String,
+    pub percentile: f64,
+    pub target_ms: f64,
+    pub observed_ms: Option<f64>,
+    pub span_count: i64,
+    /// None when the operation had no spans in the range
+    pub meeting_target: Option<bool>,
+}
+
+/// Latency target attainment across operations with registered targets
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyTargetSummary {
+    /// Operations with targets that had spans in the range
+    pub operations_measured: usize,
+    pub operations_meeting_target: usize,
+    /// Percentage of measured operations meeting their target (100 when none were measured)
+    pub percent_meeting_target: f64,
+    pub operations: Vec<OperationTargetStatus>,
+}
+
+impl LatencyTargetSummary {
+    /// Summarize per-operation target statuses
+    pub fn from_statuses(operations: Vec<OperationTargetStatus>) -> Self {
+        let operations_measured = operations.iter().filter(|o| o.meeting_target.is_some()).count();
+        let operations_meeting_target = operations
+            .iter()
+            .filter(|o| o.meeting_target == Some(true))
+            .count();
+
+        Self {
+            operations_measured,
+            operations_meeting_target,
+            percent_meeting_target: if operations_measured > 0 {
+                operations_meeting_target as f64 / operations_measured as f64 * 100.0
+            } else {
+                100.0
+            },
+            operations,
+        }
+    }
+}
+
+/// A span that took longer than its operation's latency target
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetBreach {
+    pub span_id: String,
+    pub operation_name: String,
+    pub duration_ms: f64,
+    pub target_ms: f64,
+}
+
+/// Highlighted excerpt of a span field that matched a text search
+#[derive(Debug, Clone, Serialize)]
+pub struct TextHighlight {
+    pub field: String,
+    pub fragment: String,
+}
+
+/// A span matching a full-text search, with its relevance
+#[derive(Debug, Clone, Serialize)]
+pub struct TextSearchHit {
+    pub span: super::Span,
+    pub rank: f64,
+    pub highlights: Vec<TextHighlight>,
+}
+
+/// Number of matching spans with one facet value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Span counts per service, model, status and operation for a search, as
+/// shown in a filter sidebar
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchFacets {
+    pub total: i64,
+    pub services: Vec<FacetCount>,
+    pub models: Vec<FacetCount>,
+    pub statuses: Vec<FacetCount>,
+    pub operations: Vec<FacetCount>,
+}
+
+impl SearchFacets {
+    /// Values kept per facet
+    pub const LIMIT: usize = 25;
+
+    /// Order each facet by count, then value, and keep the most common values
+    pub fn truncate(&mut self) {
+        for facet in [&mut self.services, &mut self.models, &mut self.statuses, &mut self.operations] {
+            facet.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+            facet.truncate(Self::LIMIT);
+        }
+    }
 }
 
 /// Cost metrics by group
@@ -56,6 +644,120 @@ pub struct CostMetric {
     pub call_count: i64,
 }
 
+/// A service seen in a time window
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceSummary {
+    pub service_name: String,
+    pub last_seen: DateTime<Utc>,
+    pub span_count: i64,
+    /// Spans per minute over the window
+    pub span_rate: f64,
+    /// Percentage of spans with an error status
+    pub error_rate: f64,
+    pub total_cost_usd: f64,
+}
+
+impl ServiceSummary {
+    /// Derive rates from a service's totals over `window`
+    pub fn new(
+        service_name: String,
+        last_seen: DateTime<Utc>,
+        span_count: i64,
+        error_count: i64,
+        total_cost_usd: f64,
+        window: chrono::Duration,
+    ) -> Self {
+        let minutes = (window.num_seconds() as f64 / 60.0).max(1.0);
+
+        Self {
+            service_name,
+            last_seen,
+            span_count,
+            span_rate: span_count as f64 / minutes,
+            error_rate: if span_count > 0 {
+                error_count as f64 / span_count as f64 * 100.0
+            } else {
+                0.0
+            },
+            total_cost_usd,
+        }
+    }
+}
+
+/// Token and cost efficiency of one operation
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationEfficiency {
+    pub service_name: String,
+    pub operation_name: String,
+    pub span_count: i64,
+    pub tokens_in: i64,
+    pub tokens_out: i64,
+    /// Output tokens per input token; None without input tokens
+    pub output_input_ratio: Option<f64>,
+    pub total_cost_usd: f64,
+    /// Cost of the operation's spans that ended in error
+    pub error_cost_usd: f64,
+    /// Traces the operation ran in
+    pub trace_count: i64,
+    /// Traces the operation ran in that had no errors
+    pub successful_traces: i64,
+    /// Total cost spread over the successful traces; None when none succeeded
+    pub cost_per_successful_trace: Option<f64>,
+}
+
+impl OperationEfficiency {
+    /// Fill in the ratios from the totals
+    pub fn compute_ratios(&mut self) {
+        self.output_input_ratio =
+            (self.tokens_in > 0).then(|| self.tokens_out as f64 / self.tokens_in as f64);
+        self.cost_per_successful_trace =
+            (self.successful_traces > 0).then(|| self.total_cost_usd / self.successful_traces as f64);
+    }
+}
+
+/// Token usage efficiency across all spans in a window
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TokenEfficiency {
+    pub span_count: i64,
+    pub trace_count: i64,
+    /// Traces with no errors
+    pub successful_traces: i64,
+    pub tokens_in: i64,
+    /// Input tokens read from the prompt cache
+    pub tokens_cached_in: i64,
+    pub tokens_out: i64,
+    pub tokens_reasoning: i64,
+    pub total_cost_usd: f64,
+    /// Total cost spread over the successful traces; None when none succeeded
+    pub cost_per_successful_trace: Option<f64>,
+    /// Input, output and reasoning tokens per trace; None without traces
+    pub tokens_per_trace: Option<f64>,
+    /// Percentage of input tokens read from the prompt cache; None without
+    /// input tokens
+    pub cached_hit_rate: Option<f64>,
+    /// Percentage of generated tokens spent reasoning; None without output
+    pub reasoning_share: Option<f64>,
+    /// Output tokens per input token; None without input tokens
+    pub output_input_ratio: Option<f64>,
+}
+
+impl TokenEfficiency {
+    /// Fill in the derived metrics from the totals
+    pub fn compute_ratios(&mut self) {
+        let generated = self.tokens_out + self.tokens_reasoning;
+        self.cost_per_successful_trace =
+            (self.successful_traces > 0).then(|| self.total_cost_usd / self.successful_traces as f64);
+        self.tokens_per_trace =
+            (self.trace_count > 0).then(|| (self.tokens_in + generated) as f64 / self.trace_count as f64);
+        self.cached_hit_rate =
+            (self.tokens_in > 0).then(|| self.tokens_cached_in as f64 / self.tokens_in as f64 * 100.0);
+        self.reasoning_share =
+            (generated > 0).then(|| self.tokens_reasoning as f64 / generated as f64 * 100.0);
+        self.output_input_ratio =
+            (self.tokens_in > 0).then(|| self.tokens_out as f64 / self.tokens_in as f64);
+    }
+}
+
 /// Latency metrics over time
 #[derive(Debug, Clone, Serialize)]
 pub struct LatencyMetric {
@@ -83,3 +785,199 @@ pub struct ErrorStats {
     pub total: i64,
     pub sample_trace_ids: Vec<String>,
 }
+
+/// Alerting metrics for one window of a series, for anomaly baselines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowStats {
+    pub window_start: DateTime<Utc>,
+    pub total: i64,
+    pub error_count: i64,
+    pub latency_avg: Option<f64>,
+    pub latency_p50: Option<f64>,
+    pub latency_p95: Option<f64>,
+    pub latency_p99: Option<f64>,
+    pub cost_sum: f64,
+    pub token_sum: i64,
+}
+
+/// Ingest volume for a single day
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyIngest {
+    /// Start of the UTC day
+    pub day: DateTime<Utc>,
+    /// Spans started that day
+    pub span_count: i64,
+    /// Raw size of those spans
+    pub bytes: i64,
+}
+
+/// Storage used by a single span column
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnStorage {
+    /// Column name
+    pub column: String,
+    /// Raw size of the column over the lookback window
+    pub bytes: i64,
+    /// Percentage of the spans' raw size
+    pub share: f64,
+}
+
+/// Storage usage and growth projection for the spans table
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageReport {
+    /// Days of ingest the averages are taken over
+    pub lookback_days: i64,
+    /// On-disk size of the spans hypertable
+    pub table_bytes: i64,
+    /// Spans stored, as estimated by TimescaleDB
+    pub approximate_span_count: i64,
+    /// Age at which span chunks are dropped, if a retention policy is set
+    pub retention_days: Option<f64>,
+    /// Ingest per day over the lookback window, oldest first
+    pub daily_ingest: Vec<DailyIngest>,
+    /// Mean raw size of a span ingested over the lookback window
+    pub avg_span_bytes: f64,
+    /// Percentage of the raw size taken by `attributes`
+    pub attribute_share: f64,
+    /// Raw size per column, largest first
+    pub columns: Vec<ColumnStorage>,
+    /// Mean on-disk growth per day
+    pub avg_daily_growth_bytes: f64,
+    /// Days ahead the size is projected
+    pub projection_days: i64,
+    /// Projected on-disk size after `projection_days`
+    pub projected_table_bytes: i64,
+    /// Size the table levels off at under the retention policy
+    pub steady_state_bytes: Option<i64>,
+}
+
+/// Retention policy for a hypertable or continuous aggregate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub table: String,
+    pub drop_after_days: Option<f64>,
+    pub schedule_interval: Option<String>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_run_status: Option<String>,
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Compression policy and savings for a hypertable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionStats {
+    pub table: String,
+    pub compress_after_days: Option<f64>,
+    pub total_chunks: i64,
+    pub compressed_chunks: i64,
+    pub before_bytes: Option<i64>,
+    pub after_bytes: Option<i64>,
+    pub compression_ratio: Option<f64>,
+}
+
+/// On-disk size of a table; chunk counts are set for hypertables
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSize {
+    pub table: String,
+    pub total_bytes: i64,
+    pub chunk_count: Option<i64>,
+}
+
+/// Number of spans started on a single day
+#[derive(Debug, Clone, Serialize)]
+pub struct DailySpanCount {
+    pub day: DateTime<Utc>,
+    pub span_count: i64,
+}
+
+/// Overview of the database for `agenttrace db stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStats {
+    pub schema_version: Option<i64>,
+    pub latest_migration: Option<i64>,
+    pub tables: Vec<TableSize>,
+    pub approximate_span_count: i64,
+    pub oldest_span: Option<DateTime<Utc>>,
+    pub newest_span: Option<DateTime<Utc>>,
+    pub daily_spans: Vec<DailySpanCount>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_cursor_roundtrip() {
+        let cursor = PageCursor {
+            started_at: "2025-03-04T09:55:00.123456789Z".parse().unwrap(),
+            id: Uuid::new_v4(),
+        };
+
+        let token = cursor.encode();
+        assert!(!token.contains(['+', '/', '=']));
+        assert_eq!(PageCursor::decode(&token).unwrap(), cursor);
+
+        assert!(PageCursor::decode("not a cursor").is_err());
+        assert!(PageCursor::decode(&URL_SAFE_NO_PAD.encode("2025-03-04|nope")).is_err());
+    }
+
+    #[test]
+    fn test_parse_span_query() {
+        let query = SpanQuery::parse(
+            r#"operation:retrieve status:ERROR min_duration:500 attr.user.id:"u 42" slow timeout"#,
+        )
+        .unwrap();
+
+        assert_eq!(query.operation.as_deref(), Some("retrieve"));
+        assert_eq!(query.status.as_deref(), Some("error"));
+        assert_eq!(query.priority, None);
+        assert_eq!(query.min_duration, Some(500.0));
+        assert_eq!(query.attributes, vec![("user.id".to_string(), "u 42".to_string())]);
+        assert_eq!(query.text.as_deref(), Some("slow timeout"));
+    }
+
+    #[test]
+    fn test_time_breakdown_overhead_never_negative() {
+        let breakdown = TimeBreakdown::new(1000.0, 700.0, 200.0);
+        assert_eq!(breakdown.overhead_ms, 100.0);
+
+        // Concurrent LLM calls can add up to more than the trace duration
+        let breakdown = TimeBreakdown::new(1000.0, 1500.0, 0.0);
+        assert_eq!(breakdown.overhead_ms, 0.0);
+    }
+
+    #[test]
+    fn test_time_breakdown_metric_shares() {
+        let metric = TimeBreakdownMetric::from_totals(2, TimeBreakdown::new(2000.0, 1000.0, 500.0));
+        assert_eq!(metric.avg.llm_ms, 500.0);
+        assert_eq!(metric.llm_share, 0.5);
+        assert_eq!(metric.tool_share, 0.25);
+        assert_eq!(metric.overhead_share, 0.25);
+    }
+
+    #[test]
+    fn test_parse_empty_span_query() {
+        assert_eq!(SpanQuery::parse("  ").unwrap(), SpanQuery::default());
+    }
+
+    #[test]
+    fn test_parse_span_query_errors() {
+        assert!(SpanQuery::parse("min_duration:fast").is_err());
+        assert!(SpanQuery::parse("color:red").is_err());
+        assert!(SpanQuery::parse("status:").is_err());
+        assert!(SpanQuery::parse("priority:urgent").is_err());
+        assert_eq!(
+            SpanQuery::parse("priority:HIGH").unwrap().priority,
+            Some(crate::models::SpanPriority::High)
+        );
+        assert!(SpanQuery::parse(r#"operation:"unterminated"#).is_err());
+        assert!(SpanQuery::parse("has_negative_feedback:maybe").is_err());
+    }
+
+    #[test]
+    fn test_feedback_filter_is_not_live() {
+        let query = SpanQuery::parse("service:planner has_negative_feedback:true").unwrap();
+        assert_eq!(query.has_negative_feedback, Some(true));
+        assert!(query.check_live().is_err());
+        assert!(SpanQuery::parse("service:planner").unwrap().check_live().is_ok());
+    }
+}
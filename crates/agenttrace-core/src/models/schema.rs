@@ -0,0 +1,187 @@
+//! Attribute schema models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Attribute key under which schema violations are recorded on a span
+pub const SCHEMA_VIOLATIONS_ATTRIBUTE: &str = "agenttrace.schema_violations";
+
+/// Expected type of a span attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    /// UTF-8 string
+    String,
+    /// Whole number
+    Integer,
+    /// Floating point number (integers are accepted)
+    Float,
+    /// true/false
+    Boolean,
+    /// JSON object
+    Object,
+    /// JSON array
+    Array,
+}
+
+impl AttributeType {
+    /// Name of the type as used in schemas and violation reports
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Float => "float",
+            Self::Boolean => "boolean",
+            Self::Object => "object",
+            Self::Array => "array",
+        }
+    }
+}
+
+/// Expected type and presence of a single attribute
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeSpec {
+    /// Attribute key (e.g., "user.id", "agent.step")
+    pub key: String,
+
+    /// Expected type
+    #[serde(rename = "type")]
+    pub attr_type: AttributeType,
+
+    /// Whether spans must carry this attribute
+    #[serde(default)]
+    pub required: bool,
+
+    /// What the attribute means
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// Expected latency for one of a service's operations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyTarget {
+    /// Operation name
+    pub operation: String,
+
+    /// Percentile the target applies to (e.g., 95 for p95)
+    #[serde(default = "default_target_percentile")]
+    pub percentile: f64,
+
+    /// Latency the percentile should stay within, in milliseconds
+    pub target_ms: f64,
+}
+
+fn default_target_percentile() -> f64 {
+    95.0
+}
+
+/// Registered attribute schema and latency targets for a service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeSchema {
+    /// Service the schema applies to
+    pub service_name: String,
+
+    /// Expected attributes
+    pub attributes: Vec<AttributeSpec>,
+
+    /// Expected latency per operation
+    #[serde(default)]
+    pub latency_targets: Vec<LatencyTarget>,
+
+    /// When the schema was first registered
+    pub created_at: DateTime<Utc>,
+
+    /// When the schema was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AttributeSchema {
+    /// Look up the spec for an attribute key
+    pub fn spec(&self, key: &str) -> Option<&AttributeSpec> {
+        self.attributes.iter().find(|a| a.key == key)
+    }
+
+    /// Look up the latency target for an operation
+    pub fn latency_target(&self, operation: &str) -> Option<&LatencyTarget> {
+        self.latency_targets.iter().find(|t| t.operation == operation)
+    }
+}
+
+/// Input for registering or replacing a service's attribute schema
+///
+/// Attributes and latency targets are both replaced; either may be omitted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeSchemaInput {
+    /// Expected attributes
+    #[serde(default)]
+    pub attributes: Vec<AttributeSpec>,
+
+    /// Expected latency per operation
+    #[serde(default)]
+    pub latency_targets: Vec<LatencyTarget>,
+}
+
+/// Kind of schema violation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// A required attribute was absent
+    Missing,
+    /// The attribute had a type that could not be coerced
+    TypeMismatch,
+}
+
+/// A single attribute that did not match the registered schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// Attribute key
+    pub key: String,
+
+    /// What was wrong
+    pub kind: ViolationKind,
+
+    /// Type the schema expects
+    pub expected: AttributeType,
+
+    /// JSON type that was received (for type mismatches)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+}
+
+/// Violation count for one attribute
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeViolationCount {
+    /// Attribute key
+    pub key: String,
+
+    /// What was wrong
+    pub kind: ViolationKind,
+
+    /// Number of spans with this violation
+    pub count: i64,
+}
+
+/// Schema compliance for a service over a time range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaComplianceReport {
+    /// Service name
+    pub service_name: String,
+
+    /// Start of the time range
+    pub since: DateTime<Utc>,
+
+    /// End of the time range
+    pub until: DateTime<Utc>,
+
+    /// Spans received in the range
+    pub total_spans: i64,
+
+    /// Spans without any violations
+    pub compliant_spans: i64,
+
+    /// Percentage of compliant spans (100 when there are no spans)
+    pub compliance_rate: f64,
+
+    /// Violations broken down by attribute, most frequent first
+    pub violations: Vec<AttributeViolationCount>,
+}
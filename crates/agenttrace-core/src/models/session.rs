@@ -0,0 +1,73 @@
+//! Session models
+//!
+//! A session groups the traces of one multi-turn conversation, identified by
+//! the `session_id` its spans carry.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::query::TraceSummary;
+use super::span::{Span, SpanStatus};
+
+/// Totals for a session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    /// Session ID
+    pub session_id: String,
+    /// End user the session belongs to, from the first span that named one
+    pub user_id: Option<String>,
+    /// Start of the session's earliest span
+    pub started_at: DateTime<Utc>,
+    /// End (or start, if still open) of its latest span
+    pub last_seen: DateTime<Utc>,
+    /// Traces in the session
+    pub trace_count: i64,
+    /// Spans in the session
+    pub span_count: i64,
+    /// Spans with an error status
+    pub error_count: i64,
+    /// Input and output tokens
+    pub total_tokens: i64,
+    /// Cost in USD
+    pub total_cost_usd: f64,
+}
+
+impl SessionSummary {
+    /// Summarize a session from its spans
+    ///
+    /// In-progress spans are left out, as they are from stored totals until
+    /// their final update arrives. Returns None when no span is left.
+    pub fn from_spans(session_id: &str, spans: &[&Span]) -> Option<Self> {
+        let spans: Vec<&Span> = spans.iter().copied().filter(|s| !s.is_in_progress()).collect();
+        let started_at = spans.iter().map(|s| s.started_at).min()?;
+        let last_seen = spans.iter().map(|s| s.ended_at.unwrap_or(s.started_at)).max()?;
+        let mut traces: Vec<&str> = spans.iter().map(|s| s.trace_id.as_str()).collect();
+        traces.sort_unstable();
+        traces.dedup();
+
+        Some(Self {
+            session_id: session_id.to_string(),
+            user_id: spans.iter().find_map(|s| s.user_id.clone()),
+            started_at,
+            last_seen,
+            trace_count: traces.len() as i64,
+            span_count: spans.len() as i64,
+            error_count: spans.iter().filter(|s| s.status == SpanStatus::Error).count() as i64,
+            total_tokens: spans
+                .iter()
+                .map(|s| i64::from(s.tokens_in.unwrap_or(0)) + i64::from(s.tokens_out.unwrap_or(0)))
+                .sum(),
+            total_cost_usd: spans.iter().filter_map(|s| s.cost_usd).sum(),
+        })
+    }
+}
+
+/// A session with its traces, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionDetail {
+    /// The session's totals, flattened
+    #[serde(flatten)]
+    pub session: SessionSummary,
+    /// The session's traces
+    pub traces: Vec<TraceSummary>,
+}
@@ -0,0 +1,634 @@
+//! Service level objective models
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::alert::{NotificationChannel, Severity};
+use crate::error::{Error, Result};
+
+/// Rolling window an SLO is measured over when none is given
+pub const DEFAULT_SLO_WINDOW_DAYS: u32 = 30;
+
+/// Longest allowed SLO name
+const MAX_NAME_LEN: usize = 100;
+
+/// Longest rolling window accepted, in days
+const MAX_WINDOW_DAYS: u32 = 90;
+
+/// What makes an event good
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SloIndicator {
+    /// The span didn't end in error
+    #[default]
+    Availability,
+    /// The span finished within `latency_threshold_ms`
+    Latency,
+}
+
+impl SloIndicator {
+    /// Lowercase name, as stored
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Availability => "availability",
+            Self::Latency => "latency",
+        }
+    }
+
+    /// Parse a stored indicator name
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "availability" => Ok(Self::Availability),
+            "latency" => Ok(Self::Latency),
+            other => Err(Error::validation(format!("Unknown SLO indicator '{}'", other))),
+        }
+    }
+}
+
+/// A multi-window burn-rate alert
+///
+/// Fires when the error budget is burning at least `burn_rate` times faster
+/// than it can be sustained over both windows: the long window shows the
+/// burn is significant, the short one that it is still happening, so the
+/// alert clears soon after the problem does.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BurnRateAlert {
+    /// Window showing the burn is significant
+    pub long_window_minutes: u32,
+    /// Window showing the burn is still happening
+    pub short_window_minutes: u32,
+    /// Burn rate both windows must reach
+    pub burn_rate: f64,
+    /// Severity of the alert when it fires
+    #[serde(default)]
+    pub severity: Severity,
+}
+
+impl BurnRateAlert {
+    fn new(long_window_minutes: u32, short_window_minutes: u32, burn_rate: f64, severity: Severity) -> Self {
+        Self {
+            long_window_minutes,
+            short_window_minutes,
+            burn_rate,
+            severity,
+        }
+    }
+}
+
+/// The alerts recommended for a 30-day SLO: 2% of the budget spent in an
+/// hour or 5% in six hours pages, 10% in three days raises a warning
+pub fn default_burn_rate_alerts() -> Vec<BurnRateAlert> {
+    vec![
+        BurnRateAlert::new(60, 5, 14.4, Severity::Critical),
+        BurnRateAlert::new(360, 30, 6.0, Severity::Critical),
+        BurnRateAlert::new(4320, 360, 1.0, Severity::Warning),
+    ]
+}
+
+/// An objective for the share of good spans over a rolling window, e.g. 99%
+/// of `support-bot` runs succeed, or 95% finish within 30 seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Slo {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Unique name, e.g. `support-bot-availability`
+    pub name: String,
+
+    /// Optional description
+    pub description: Option<String>,
+
+    /// Only count spans from this service
+    pub service_name: Option<String>,
+
+    /// Only count spans with this operation name
+    pub operation_name: Option<String>,
+
+    /// Only count root spans, i.e. one event per agent run
+    pub root_spans_only: bool,
+
+    /// What makes a span good
+    pub indicator: SloIndicator,
+
+    /// Slowest duration that counts as good, for latency SLOs
+    pub latency_threshold_ms: Option<f64>,
+
+    /// Percentage of spans that should be good, e.g. 99.9
+    pub objective_percent: f64,
+
+    /// Rolling window the objective is measured over
+    pub window_days: u32,
+
+    /// Burn-rate alerts, checked in the background
+    pub burn_rate_alerts: Vec<BurnRateAlert>,
+
+    /// Where burn-rate alerts go
+    pub notification_channels: Vec<NotificationChannel>,
+
+    /// Whether burn-rate alerts are checked
+    pub enabled: bool,
+
+    /// When the SLO was created
+    pub created_at: DateTime<Utc>,
+
+    /// When the SLO was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Slo {
+    /// Share of spans allowed to be bad, e.g. 0.001 for 99.9%
+    pub fn error_budget(&self) -> f64 {
+        1.0 - self.objective_percent / 100.0
+    }
+
+    /// How many times faster than sustainable the budget burned in a window
+    ///
+    /// 1.0 spends exactly the budget over the SLO window; a window without
+    /// spans burns nothing.
+    pub fn burn_rate(&self, counts: &SloCounts) -> f64 {
+        let budget = self.error_budget();
+        if budget <= 0.0 {
+            return 0.0;
+        }
+        counts.error_rate() / budget
+    }
+
+    /// Length of the rolling window
+    pub fn window(&self) -> Duration {
+        Duration::days(i64::from(self.window_days))
+    }
+}
+
+/// Input for creating or replacing an SLO
+#[derive(Debug, Clone, Deserialize)]
+pub struct SloInput {
+    /// Unique name
+    pub name: String,
+    /// Optional description
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Only count spans from this service
+    #[serde(default)]
+    pub service_name: Option<String>,
+    /// Only count spans with this operation name
+    #[serde(default)]
+    pub operation_name: Option<String>,
+    /// Only count root spans; defaults to true
+    #[serde(default = "default_true")]
+    pub root_spans_only: bool,
+    /// What makes a span good; defaults to availability
+    #[serde(default)]
+    pub indicator: SloIndicator,
+    /// Slowest duration that counts as good, required for latency SLOs
+    #[serde(default)]
+    pub latency_threshold_ms: Option<f64>,
+    /// Percentage of spans that should be good, between 0 and 100
+    pub objective_percent: f64,
+    /// Rolling window in days; defaults to 30
+    #[serde(default = "default_window_days")]
+    pub window_days: u32,
+    /// Burn-rate alerts; defaults to [`default_burn_rate_alerts`]
+    #[serde(default = "default_burn_rate_alerts")]
+    pub burn_rate_alerts: Vec<BurnRateAlert>,
+    /// Where burn-rate alerts go
+    #[serde(default)]
+    pub notification_channels: Vec<NotificationChannel>,
+    /// Whether burn-rate alerts are checked; defaults to true
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_window_days() -> u32 {
+    DEFAULT_SLO_WINDOW_DAYS
+}
+
+impl SloInput {
+    /// Check the name, objective, window and alerts
+    pub fn validate(&self) -> Result<()> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(Error::validation(format!(
+                "SLO name must be 1 to {} characters",
+                MAX_NAME_LEN
+            )));
+        }
+
+        if !self.objective_percent.is_finite() || self.objective_percent <= 0.0 || self.objective_percent >= 100.0 {
+            return Err(Error::validation("SLO objective must be between 0 and 100 percent"));
+        }
+
+        match (self.indicator, self.latency_threshold_ms) {
+            (SloIndicator::Latency, Some(ms)) if ms.is_finite() && ms > 0.0 => {}
+            (SloIndicator::Latency, _) => {
+                return Err(Error::validation(
+                    "Latency SLOs need a latency_threshold_ms greater than zero",
+                ));
+            }
+            (SloIndicator::Availability, Some(_)) => {
+                return Err(Error::validation(
+                    "latency_threshold_ms only applies to latency SLOs",
+                ));
+            }
+            (SloIndicator::Availability, None) => {}
+        }
+
+        if self.window_days == 0 || self.window_days > MAX_WINDOW_DAYS {
+            return Err(Error::validation(format!(
+                "SLO window must be 1 to {} days",
+                MAX_WINDOW_DAYS
+            )));
+        }
+
+        let window_minutes = self.window_days * 24 * 60;
+        for alert in &self.burn_rate_alerts {
+            if alert.short_window_minutes == 0 || alert.short_window_minutes >= alert.long_window_minutes {
+                return Err(Error::validation(
+                    "Burn-rate alert short window must be shorter than its long window",
+                ));
+            }
+            if alert.long_window_minutes > window_minutes {
+                return Err(Error::validation(
+                    "Burn-rate alert long window can't be longer than the SLO window",
+                ));
+            }
+            if !alert.burn_rate.is_finite() || alert.burn_rate <= 0.0 {
+                return Err(Error::validation("Burn-rate alert threshold must be greater than zero"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the window fits in the spans kept, if spans expire
+    pub fn validate_retention(&self, span_retention_days: Option<u32>) -> Result<()> {
+        match span_retention_days {
+            Some(days) if self.window_days > days => Err(Error::validation(format!(
+                "SLO window of {} days is longer than the {} days spans are kept",
+                self.window_days, days
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Good and total spans counted for an SLO over a window
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SloCounts {
+    /// Spans counted
+    pub total: i64,
+    /// Spans that were good
+    pub good: i64,
+}
+
+impl SloCounts {
+    /// Spans that weren't good
+    pub fn bad(&self) -> i64 {
+        (self.total - self.good).max(0)
+    }
+
+    /// Share of spans that weren't good, 0 without spans
+    pub fn error_rate(&self) -> f64 {
+        if self.total <= 0 {
+            return 0.0;
+        }
+        self.bad() as f64 / self.total as f64
+    }
+}
+
+/// Burn rate over one window ending now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BurnRate {
+    /// Window length
+    pub window_minutes: u32,
+    /// Spans counted in the window
+    pub total_events: i64,
+    /// Good spans in the window
+    pub good_events: i64,
+    /// How many times faster than sustainable the budget burned
+    pub burn_rate: f64,
+}
+
+impl BurnRate {
+    /// Burn rate of `slo` from `counts` over a window
+    pub fn new(slo: &Slo, window_minutes: u32, counts: SloCounts) -> Self {
+        Self {
+            window_minutes,
+            total_events: counts.total,
+            good_events: counts.good,
+            burn_rate: slo.burn_rate(&counts),
+        }
+    }
+}
+
+/// An SLO's performance over its rolling window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloStatus {
+    /// The SLO, flattened into the status
+    #[serde(flatten)]
+    pub slo: Slo,
+    /// Start of the rolling window
+    pub window_start: DateTime<Utc>,
+    /// Spans counted in the window
+    pub total_events: i64,
+    /// Good spans in the window
+    pub good_events: i64,
+    /// Percentage of good spans, None without spans
+    pub sli_percent: Option<f64>,
+    /// Percentage of the window's error budget spent; over 100 once the
+    /// objective is missed
+    pub budget_consumed_percent: f64,
+    /// Percentage of the error budget left, negative once overspent
+    pub budget_remaining_percent: f64,
+    /// Burn rates over each alert's windows, shortest first
+    pub burn_rates: Vec<BurnRate>,
+}
+
+impl SloStatus {
+    /// Status of an SLO with `counts` over the window ending at `now`
+    pub fn new(slo: Slo, counts: SloCounts, burn_rates: Vec<BurnRate>, now: DateTime<Utc>) -> Self {
+        let consumed = slo.burn_rate(&counts) * 100.0;
+
+        Self {
+            window_start: now - slo.window(),
+            total_events: counts.total,
+            good_events: counts.good,
+            sli_percent: (counts.total > 0).then(|| counts.good as f64 * 100.0 / counts.total as f64),
+            budget_consumed_percent: consumed,
+            budget_remaining_percent: 100.0 - consumed,
+            burn_rates,
+            slo,
+        }
+    }
+}
+
+/// Notification that an SLO's error budget is burning too fast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloBurnAlert {
+    /// Unique identifier
+    pub id: Uuid,
+    /// SLO whose budget is burning
+    pub slo_id: Uuid,
+    /// Name of the SLO
+    pub slo_name: String,
+    /// Service the SLO covers
+    pub service_name: Option<String>,
+    /// Operation the SLO covers
+    pub operation_name: Option<String>,
+    /// What makes a span good
+    pub indicator: SloIndicator,
+    /// The SLO's objective
+    pub objective_percent: f64,
+    /// The alert's long window
+    pub long_window_minutes: u32,
+    /// The alert's short window
+    pub short_window_minutes: u32,
+    /// The alert's threshold
+    pub threshold: f64,
+    /// Burn rate over the long window
+    pub long_burn_rate: f64,
+    /// Burn rate over the short window
+    pub short_burn_rate: f64,
+    /// Percentage of the SLO window's error budget spent in the long window
+    pub budget_spent_percent: f64,
+    /// The alert's severity
+    pub severity: Severity,
+    /// Human-readable summary
+    pub message: String,
+    /// When the alert fired
+    pub triggered_at: DateTime<Utc>,
+}
+
+impl SloBurnAlert {
+    /// Alert for `slo` burning at `long` and `short` over an alert's windows
+    pub fn new(slo: &Slo, alert: &BurnRateAlert, long: f64, short: f64, now: DateTime<Utc>) -> Self {
+        let window_minutes = f64::from(slo.window_days * 24 * 60);
+        let budget_spent_percent = long * f64::from(alert.long_window_minutes) * 100.0 / window_minutes;
+
+        Self {
+            id: Uuid::new_v4(),
+            slo_id: slo.id,
+            slo_name: slo.name.clone(),
+            service_name: slo.service_name.clone(),
+            operation_name: slo.operation_name.clone(),
+            indicator: slo.indicator,
+            objective_percent: slo.objective_percent,
+            long_window_minutes: alert.long_window_minutes,
+            short_window_minutes: alert.short_window_minutes,
+            threshold: alert.burn_rate,
+            long_burn_rate: long,
+            short_burn_rate: short,
+            budget_spent_percent,
+            severity: alert.severity,
+            message: format!(
+                "{} is burning its error budget {:.1}x too fast ({:.1}% of its {}-day budget in the last {}), \
+                 alerting at {}x",
+                slo.name,
+                long,
+                budget_spent_percent,
+                slo.window_days,
+                format_minutes(alert.long_window_minutes),
+                alert.burn_rate
+            ),
+            triggered_at: now,
+        }
+    }
+
+    /// Key identifying the alert to incident tools; one per SLO and
+    /// window pair
+    pub fn key(&self) -> String {
+        burn_alert_key(self.slo_id, self.long_window_minutes, self.short_window_minutes)
+    }
+}
+
+/// Notification that a firing burn-rate alert cleared, because the burn
+/// rate dropped or the SLO or alert was disabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SloResolution {
+    /// The burn-rate alert that cleared
+    pub alert_id: Uuid,
+    /// SLO the alert belonged to
+    pub slo_id: Uuid,
+    /// Name of the SLO
+    pub slo_name: String,
+    /// Service the SLO covers
+    pub service_name: Option<String>,
+    /// The alert's long window
+    pub long_window_minutes: u32,
+    /// The alert's short window
+    pub short_window_minutes: u32,
+    /// When the alert fired
+    pub triggered_at: DateTime<Utc>,
+    /// When the alert cleared
+    pub resolved_at: DateTime<Utc>,
+    /// Human-readable summary
+    pub message: String,
+}
+
+impl SloResolution {
+    /// Resolution of `slo`'s alert over a window pair that fired at
+    /// `triggered_at`
+    pub fn new(
+        slo: &Slo,
+        alert_id: Uuid,
+        (long_window_minutes, short_window_minutes): (u32, u32),
+        triggered_at: DateTime<Utc>,
+        resolved_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            alert_id,
+            slo_id: slo.id,
+            slo_name: slo.name.clone(),
+            service_name: slo.service_name.clone(),
+            long_window_minutes,
+            short_window_minutes,
+            triggered_at,
+            resolved_at,
+            message: format!(
+                "{} is no longer burning its error budget too fast over the last {}",
+                slo.name,
+                format_minutes(long_window_minutes)
+            ),
+        }
+    }
+
+    /// Key of the alert that cleared, as given to incident tools
+    pub fn key(&self) -> String {
+        burn_alert_key(self.slo_id, self.long_window_minutes, self.short_window_minutes)
+    }
+}
+
+fn burn_alert_key(slo_id: Uuid, long_window_minutes: u32, short_window_minutes: u32) -> String {
+    format!("slo:{}:{}m:{}m", slo_id, long_window_minutes, short_window_minutes)
+}
+
+/// A window length as minutes, hours or days, whichever is whole
+pub fn format_minutes(minutes: u32) -> String {
+    match minutes {
+        m if m >= 1440 && m % 1440 == 0 => format!("{}d", m / 1440),
+        m if m >= 60 && m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn input() -> SloInput {
+        SloInput {
+            name: "support-bot-availability".to_string(),
+            description: None,
+            service_name: Some("support-bot".to_string()),
+            operation_name: None,
+            root_spans_only: true,
+            indicator: SloIndicator::Availability,
+            latency_threshold_ms: None,
+            objective_percent: 99.0,
+            window_days: DEFAULT_SLO_WINDOW_DAYS,
+            burn_rate_alerts: default_burn_rate_alerts(),
+            notification_channels: vec![],
+            enabled: true,
+        }
+    }
+
+    fn slo() -> Slo {
+        let input = input();
+        Slo {
+            id: Uuid::new_v4(),
+            name: input.name,
+            description: None,
+            service_name: input.service_name,
+            operation_name: None,
+            root_spans_only: true,
+            indicator: input.indicator,
+            latency_threshold_ms: None,
+            objective_percent: input.objective_percent,
+            window_days: input.window_days,
+            burn_rate_alerts: input.burn_rate_alerts,
+            notification_channels: vec![],
+            enabled: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_burn_rate_and_status() {
+        let slo = slo();
+
+        // 2% errors against a 1% budget burns twice as fast as sustainable
+        let counts = SloCounts { total: 1000, good: 980 };
+        assert!((slo.burn_rate(&counts) - 2.0).abs() < 1e-9);
+        assert_eq!(slo.burn_rate(&SloCounts::default()), 0.0);
+
+        let now = Utc.with_ymd_and_hms(2024, 11, 16, 0, 0, 0).unwrap();
+        let status = SloStatus::new(slo, SloCounts { total: 1000, good: 995 }, vec![], now);
+        assert_eq!(status.window_start, Utc.with_ymd_and_hms(2024, 10, 17, 0, 0, 0).unwrap());
+        assert_eq!(status.sli_percent, Some(99.5));
+        assert!((status.budget_consumed_percent - 50.0).abs() < 1e-9);
+        assert!((status.budget_remaining_percent - 50.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_burn_alert_budget_spent() {
+        let slo = slo();
+        let alert = &slo.burn_rate_alerts[0];
+
+        // Burning 14.4x for an hour spends 2% of a 30-day budget
+        let burn = SloBurnAlert::new(&slo, alert, 14.4, 20.0, Utc::now());
+        assert!((burn.budget_spent_percent - 2.0).abs() < 1e-9);
+        assert_eq!(burn.severity, Severity::Critical);
+        assert_eq!(burn.key(), format!("slo:{}:60m:5m", slo.id));
+        assert!(burn.message.contains("last 1h"));
+    }
+
+    #[test]
+    fn test_resolution_closes_the_alert_key() {
+        let slo = slo();
+        let alert = &slo.burn_rate_alerts[0];
+        let burn = SloBurnAlert::new(&slo, alert, 14.4, 20.0, Utc::now());
+
+        let resolution = SloResolution::new(&slo, burn.id, (60, 5), burn.triggered_at, Utc::now());
+        assert_eq!(resolution.key(), burn.key());
+        assert!(resolution.message.contains("last 1h"));
+    }
+
+    #[test]
+    fn test_window_must_fit_span_retention() {
+        let input = input();
+
+        assert!(input.validate_retention(None).is_ok());
+        assert!(input.validate_retention(Some(30)).is_ok());
+        let err = input.validate_retention(Some(7)).unwrap_err();
+        assert!(err.to_string().contains("7 days"));
+    }
+
+    #[test]
+    fn test_validate_slo_input() {
+        assert!(input().validate().is_ok());
+
+        let mut perfect = input();
+        perfect.objective_percent = 100.0;
+        assert!(perfect.validate().is_err());
+
+        let mut latency = input();
+        latency.indicator = SloIndicator::Latency;
+        assert!(latency.validate().is_err());
+        latency.latency_threshold_ms = Some(30_000.0);
+        assert!(latency.validate().is_ok());
+
+        let mut short = input();
+        short.window_days = 1;
+        assert!(short.validate().is_err(), "3-day alert window exceeds a 1-day SLO");
+
+        let mut inverted = input();
+        inverted.burn_rate_alerts = vec![BurnRateAlert::new(5, 60, 14.4, Severity::Critical)];
+        assert!(inverted.validate().is_err());
+    }
+}
@@ -4,6 +4,16 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// OpenTelemetry attribute carrying a span's session ID
+pub const SESSION_ID_ATTRIBUTE: &str = "session.id";
+
+/// OpenTelemetry attribute carrying a span's end user ID
+pub const USER_ID_ATTRIBUTE: &str = "user.id";
+
+/// Status message of an `in_progress` span closed because its final update
+/// never came
+pub const STALE_IN_PROGRESS_MESSAGE: &str = "Span never completed";
+
 /// Status of a span
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -15,6 +25,22 @@ pub enum SpanStatus {
     Error,
     /// Status not set
     Unset,
+    /// Operation has started but not finished yet, e.g. a streaming LLM call;
+    /// a later update for the same span replaces it
+    #[serde(rename = "in_progress")]
+    InProgress,
+}
+
+impl SpanStatus {
+    /// Name as stored and accepted by the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SpanStatus::Ok => "ok",
+            SpanStatus::Error => "error",
+            SpanStatus::Unset => "unset",
+            SpanStatus::InProgress => "in_progress",
+        }
+    }
 }
 
 /// Kind of span
@@ -34,6 +60,38 @@ pub enum SpanKind {
     Consumer,
 }
 
+/// Priority of a span for load shedding and retention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanPriority {
+    /// Critical spans, e.g. audit records: never shed, retained longest
+    High,
+    /// Regular spans
+    #[default]
+    Normal,
+    /// Chatty debug spans: shed first under load, pruned early
+    Low,
+}
+
+impl SpanPriority {
+    /// All priorities, highest first
+    pub const ALL: [SpanPriority; 3] = [SpanPriority::High, SpanPriority::Normal, SpanPriority::Low];
+
+    /// Lowercase name as stored and accepted by the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SpanPriority::High => "high",
+            SpanPriority::Normal => "normal",
+            SpanPriority::Low => "low",
+        }
+    }
+
+    /// Parse a priority name, case-insensitively
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
 /// A span represents a single operation within a trace
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Span {
@@ -49,6 +107,14 @@ pub struct Span {
     /// Parent span ID (if any)
     pub parent_span_id: Option<String>,
 
+    /// Session (multi-turn conversation) the span's trace belongs to
+    #[serde(default)]
+    pub session_id: Option<String>,
+
+    /// End user the session belongs to
+    #[serde(default)]
+    pub user_id: Option<String>,
+
     /// Name of the operation
     pub operation_name: String,
 
@@ -83,6 +149,9 @@ pub struct Span {
     /// Input tokens
     pub tokens_in: Option<i32>,
 
+    /// Input tokens read from the prompt cache (a subset of `tokens_in`)
+    pub tokens_cached_in: Option<i32>,
+
     /// Output tokens
     pub tokens_out: Option<i32>,
 
@@ -120,6 +189,10 @@ pub struct Span {
 
     /// Links to other spans
     pub links: Vec<SpanLink>,
+
+    /// Priority for load shedding and retention
+    #[serde(default)]
+    pub priority: SpanPriority,
 }
 
 /// An event that occurred during a span
@@ -132,6 +205,7 @@ pub struct SpanEvent {
     pub timestamp: DateTime<Utc>,
 
     /// Event attributes
+    #[serde(default)]
     pub attributes: serde_json::Value,
 }
 
@@ -145,6 +219,7 @@ pub struct SpanLink {
     pub span_id: String,
 
     /// Link attributes
+    #[serde(default)]
     pub attributes: serde_json::Value,
 }
 
@@ -154,6 +229,10 @@ pub struct SpanInput {
     pub span_id: String,
     pub trace_id: String,
     pub parent_span_id: Option<String>,
+    /// Session the span's trace belongs to; defaults to the `session.id` attribute
+    pub session_id: Option<String>,
+    /// End user the session belongs to; defaults to the `user.id` attribute
+    pub user_id: Option<String>,
     pub operation_name: String,
     pub service_name: Option<String>,
     pub span_kind: Option<SpanKind>,
@@ -164,6 +243,7 @@ pub struct SpanInput {
     pub model_name: Option<String>,
     pub model_provider: Option<String>,
     pub tokens_in: Option<i32>,
+    pub tokens_cached_in: Option<i32>,
     pub tokens_out: Option<i32>,
     pub tokens_reasoning: Option<i32>,
     pub tool_name: Option<String>,
@@ -173,6 +253,7 @@ pub struct SpanInput {
     pub completion_preview: Option<String>,
     pub attributes: Option<serde_json::Value>,
     pub events: Option<Vec<SpanEvent>>,
+    pub priority: Option<SpanPriority>,
 }
 
 impl Span {
@@ -200,4 +281,91 @@ impl Span {
             + self.tokens_out.unwrap_or(0)
             + self.tokens_reasoning.unwrap_or(0)
     }
+
+    /// Fill `session_id` and `user_id` from their OpenTelemetry attributes
+    /// when they weren't set directly
+    pub fn fill_session_from_attributes(&mut self) {
+        let attribute = |key: &str| {
+            self.attributes
+                .get(key)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        if self.session_id.is_none() {
+            self.session_id = attribute(SESSION_ID_ATTRIBUTE);
+        }
+        if self.user_id.is_none() {
+            self.user_id = attribute(USER_ID_ATTRIBUTE);
+        }
+    }
+
+    /// Check if this span is a start event still waiting for its final update
+    pub fn is_in_progress(&self) -> bool {
+        self.status == SpanStatus::InProgress
+    }
+
+    /// Close an in-progress span whose final update never came, as an error
+    pub fn close_stale(&mut self) {
+        self.status = SpanStatus::Error;
+        if self.status_message.is_none() {
+            self.status_message = Some(STALE_IN_PROGRESS_MESSAGE.to_string());
+        }
+    }
+
+    /// Apply a later update for the same span on top of this one
+    ///
+    /// Fields the update sets replace the stored ones and fields it leaves
+    /// empty keep their earlier values, so a completion that only carries
+    /// token counts doesn't erase the model or prompt sent with the start
+    /// event. Attributes are merged key by key.
+    pub fn apply_update(&mut self, update: Span) {
+        fn keep<T>(field: &mut Option<T>, update: Option<T>) {
+            if update.is_some() {
+                *field = update;
+            }
+        }
+
+        if !update.operation_name.is_empty() {
+            self.operation_name = update.operation_name;
+        }
+        if !update.service_name.is_empty() {
+            self.service_name = update.service_name;
+        }
+        self.status = update.status;
+        keep(&mut self.parent_span_id, update.parent_span_id);
+        keep(&mut self.session_id, update.session_id);
+        keep(&mut self.user_id, update.user_id);
+        keep(&mut self.ended_at, update.ended_at);
+        keep(&mut self.duration_ms, update.duration_ms);
+        keep(&mut self.status_message, update.status_message);
+        keep(&mut self.model_name, update.model_name);
+        keep(&mut self.model_provider, update.model_provider);
+        keep(&mut self.tokens_in, update.tokens_in);
+        keep(&mut self.tokens_cached_in, update.tokens_cached_in);
+        keep(&mut self.tokens_out, update.tokens_out);
+        keep(&mut self.tokens_reasoning, update.tokens_reasoning);
+        keep(&mut self.cost_usd, update.cost_usd);
+        keep(&mut self.tool_name, update.tool_name);
+        keep(&mut self.tool_input, update.tool_input);
+        keep(&mut self.tool_output, update.tool_output);
+        keep(&mut self.tool_duration_ms, update.tool_duration_ms);
+        keep(&mut self.prompt_preview, update.prompt_preview);
+        keep(&mut self.completion_preview, update.completion_preview);
+
+        match (&mut self.attributes, update.attributes) {
+            (serde_json::Value::Object(current), serde_json::Value::Object(new)) => current.extend(new),
+            (_, serde_json::Value::Null) => {}
+            (current, new) => *current = new,
+        }
+        if !update.events.is_empty() {
+            self.events = update.events;
+        }
+        if !update.links.is_empty() {
+            self.links = update.links;
+        }
+        if update.priority != SpanPriority::default() {
+            self.priority = update.priority;
+        }
+    }
 }
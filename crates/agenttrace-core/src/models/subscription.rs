@@ -0,0 +1,175 @@
+//! Webhook subscription models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{Span, SpanQuery};
+use crate::error::{Error, Result};
+
+/// Longest allowed subscription name
+const MAX_NAME_LEN: usize = 100;
+
+/// Shortest allowed signing secret
+const MIN_SECRET_LEN: usize = 16;
+
+/// A stored span filter whose matching traces are posted to a URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Unique name, e.g. `expensive-errors`
+    pub name: String,
+
+    /// Span search query, e.g. `status:error min_cost:1`
+    pub filter: String,
+
+    /// URL payloads are posted to
+    pub url: String,
+
+    /// Key payloads are signed with; never returned by the API
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+
+    /// Whether matching spans are delivered
+    pub enabled: bool,
+
+    /// When the subscription was created
+    pub created_at: DateTime<Utc>,
+
+    /// When the subscription was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Input for creating or replacing a webhook subscription
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookSubscriptionInput {
+    /// Unique name
+    pub name: String,
+    /// Span search query; must not be empty
+    pub filter: String,
+    /// http or https URL payloads are posted to
+    pub url: String,
+    /// Key payloads are signed with, at least 16 characters
+    pub secret: String,
+    /// Whether matching spans are delivered; defaults to true
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl WebhookSubscriptionInput {
+    /// Check the name, filter, URL and secret
+    pub fn validate(&self) -> Result<()> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(Error::validation(format!(
+                "Subscription name must be 1 to {} characters",
+                MAX_NAME_LEN
+            )));
+        }
+
+        // An empty filter would post every span
+        if self.filter.trim().is_empty() {
+            return Err(Error::validation("Subscription filter can't be empty"));
+        }
+        SpanQuery::parse(&self.filter)?.check_live()?;
+
+        let url = url::Url::parse(&self.url)
+            .map_err(|e| Error::validation(format!("Invalid webhook URL '{}': {}", self.url, e)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::validation("Webhook URL must use http or https"));
+        }
+
+        if self.secret.len() < MIN_SECRET_LEN {
+            return Err(Error::validation(format!(
+                "Webhook secret must be at least {} characters",
+                MIN_SECRET_LEN
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Body posted to a subscription's URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    /// Always `trace.matched`
+    pub event: String,
+    /// Subscription whose filter matched
+    pub subscription_id: Uuid,
+    /// Name of the subscription
+    pub subscription_name: String,
+    /// Trace that matched
+    pub trace_id: String,
+    /// First span of the trace that matched the filter
+    pub span: Span,
+    /// When the span matched and the payload was queued
+    pub sent_at: DateTime<Utc>,
+}
+
+/// Outcome of delivering one payload, after any retries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// Unique identifier
+    pub id: Uuid,
+    /// Subscription the payload was sent for
+    pub subscription_id: Uuid,
+    /// Trace that matched
+    pub trace_id: String,
+    /// Span that matched
+    pub span_id: String,
+    /// Requests made, including retries
+    pub attempts: i32,
+    /// Whether an attempt got a 2xx response
+    pub success: bool,
+    /// HTTP status of the last attempt, if a response arrived
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<i32>,
+    /// Why the last attempt failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When delivery finished
+    pub delivered_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> WebhookSubscriptionInput {
+        WebhookSubscriptionInput {
+            name: "expensive-errors".to_string(),
+            filter: "status:error min_cost:1".to_string(),
+            url: "https://hooks.example.com/agenttrace".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_validate_subscription_input() {
+        assert!(input().validate().is_ok());
+
+        let mut empty_filter = input();
+        empty_filter.filter = " ".to_string();
+        assert!(empty_filter.validate().is_err());
+
+        let mut bad_filter = input();
+        bad_filter.filter = "min_cost:lots".to_string();
+        assert!(bad_filter.validate().is_err());
+
+        let mut bad_url = input();
+        bad_url.url = "ftp://example.com".to_string();
+        assert!(bad_url.validate().is_err());
+
+        let mut short_secret = input();
+        short_secret.secret = "hunter2".to_string();
+        assert!(short_secret.validate().is_err());
+    }
+}
@@ -1,9 +1,13 @@
 //! Trace data model
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::error::{Error, Result};
+
 /// Status of a trace
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -149,3 +153,364 @@ impl Trace {
         self.status != TraceStatus::InProgress
     }
 }
+
+/// Traces to delete, e.g. for a customer's data erasure request
+///
+/// A trace is selected when one of its spans matches every criterion given:
+/// one of `trace_ids`, all of `attributes`, and started before `older_than`.
+/// Whole traces are deleted, so no child span is left holding data its root
+/// was selected for.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TraceDeletion {
+    /// Traces to delete
+    #[serde(default)]
+    pub trace_ids: Vec<String>,
+
+    /// Attribute values a span must carry, e.g. `customer_id = 123`
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+
+    /// Only select spans that started before this
+    pub older_than: Option<DateTime<Utc>>,
+
+    /// Count what would be deleted without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl TraceDeletion {
+    /// Check the deletion names traces or attributes, so a bare age can't
+    /// wipe every trace
+    pub fn validate(&self) -> Result<()> {
+        if self.trace_ids.is_empty() && self.attributes.is_empty() {
+            return Err(Error::validation("Name the traces to delete by trace ID or attribute"));
+        }
+        if self.trace_ids.iter().any(|id| id.trim().is_empty()) {
+            return Err(Error::validation("Trace IDs can't be empty"));
+        }
+        if self.attributes.keys().any(|key| key.trim().is_empty()) {
+            return Err(Error::validation("Attribute keys can't be empty"));
+        }
+        Ok(())
+    }
+
+    /// The criteria as one line, e.g. `customer_id=123 older_than=...`,
+    /// naming the deletion in the audit log
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.trace_ids.is_empty() {
+            parts.push(format!("trace_id={}", self.trace_ids.join(",")));
+        }
+        for (key, value) in &self.attributes {
+            parts.push(format!("{}={}", key, value));
+        }
+        if let Some(older_than) = self.older_than {
+            parts.push(format!("older_than={}", older_than.to_rfc3339()));
+        }
+        parts.join(" ")
+    }
+}
+
+/// What a [`TraceDeletion`] removed, or would remove on a dry run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraceDeletionResult {
+    /// Nothing was deleted
+    pub dry_run: bool,
+
+    /// Traces selected
+    pub traces: i64,
+
+    /// Spans in those traces
+    pub spans: i64,
+
+    /// IDs of the traces selected
+    pub trace_ids: Vec<String>,
+}
+
+/// How a span in one trace lines up with the other in a comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanChange {
+    /// The span is in both traces
+    Matched,
+    /// The span is only in the second trace
+    Added,
+    /// The span is only in the first trace
+    Removed,
+}
+
+/// A value in each of two traces and how it moved from the first to the
+/// second
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ValueDelta {
+    /// Value in the first trace
+    pub a: Option<f64>,
+    /// Value in the second trace
+    pub b: Option<f64>,
+    /// `b - a`, when both are known
+    pub delta: Option<f64>,
+}
+
+impl ValueDelta {
+    /// Compare two optional values
+    pub fn new(a: Option<f64>, b: Option<f64>) -> Self {
+        Self {
+            a,
+            b,
+            delta: a.zip(b).map(|(a, b)| b - a),
+        }
+    }
+}
+
+/// A span aligned across two traces
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpanDiff {
+    /// Operation names from the root down to the span, joined with ` > `
+    pub path: String,
+
+    /// Which occurrence of `path` this is, in start order, from 0
+    pub position: usize,
+
+    /// The span's operation name
+    pub operation_name: String,
+
+    /// Whether the span is in one trace or both
+    pub change: SpanChange,
+
+    /// Span ID in the first trace
+    pub a_span_id: Option<String>,
+
+    /// Span ID in the second trace
+    pub b_span_id: Option<String>,
+
+    /// Duration in milliseconds
+    pub duration_ms: ValueDelta,
+
+    /// Input plus output tokens
+    pub tokens: ValueDelta,
+
+    /// Cost in USD
+    pub cost_usd: ValueDelta,
+
+    /// Status in the first trace
+    pub a_status: Option<super::SpanStatus>,
+
+    /// Status in the second trace
+    pub b_status: Option<super::SpanStatus>,
+
+    /// Whether a matched span's status differs between the traces
+    pub status_changed: bool,
+}
+
+/// Two traces with their spans aligned, for finding regressions
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceComparison {
+    /// Summary of the first trace
+    pub a: super::TraceSummary,
+
+    /// Summary of the second trace
+    pub b: super::TraceSummary,
+
+    /// Trace duration in milliseconds
+    pub duration_ms: ValueDelta,
+
+    /// Total tokens
+    pub tokens: ValueDelta,
+
+    /// Total cost in USD
+    pub cost_usd: ValueDelta,
+
+    /// Aligned spans: the first trace's in start order, then spans only in
+    /// the second
+    pub spans: Vec<SpanDiff>,
+}
+
+impl TraceComparison {
+    /// Align two traces' spans by their operation path from the root and
+    /// their position among spans with the same path
+    pub fn new(
+        a: super::TraceSummary,
+        a_spans: &[super::Span],
+        b: super::TraceSummary,
+        b_spans: &[super::Span],
+    ) -> Self {
+        let a_keyed = keyed_spans(a_spans);
+        let mut b_keyed: Vec<Option<(String, usize, &super::Span)>> =
+            keyed_spans(b_spans).into_iter().map(Some).collect();
+
+        let mut spans = Vec::with_capacity(a_keyed.len().max(b_keyed.len()));
+        for (path, position, a_span) in a_keyed {
+            let matched = b_keyed
+                .iter_mut()
+                .find(|entry| matches!(entry, Some((p, i, _)) if *p == path && *i == position))
+                .and_then(Option::take)
+                .map(|(_, _, span)| span);
+            spans.push(SpanDiff::new(path, position, Some(a_span), matched));
+        }
+        for (path, position, b_span) in b_keyed.into_iter().flatten() {
+            spans.push(SpanDiff::new(path, position, None, Some(b_span)));
+        }
+
+        Self {
+            duration_ms: ValueDelta::new(a.duration_ms, b.duration_ms),
+            tokens: ValueDelta::new(Some(a.total_tokens as f64), Some(b.total_tokens as f64)),
+            cost_usd: ValueDelta::new(Some(a.total_cost_usd), Some(b.total_cost_usd)),
+            a,
+            b,
+            spans,
+        }
+    }
+}
+
+impl SpanDiff {
+    fn new(path: String, position: usize, a: Option<&super::Span>, b: Option<&super::Span>) -> Self {
+        let change = match (a, b) {
+            (Some(_), Some(_)) => SpanChange::Matched,
+            (None, _) => SpanChange::Added,
+            (Some(_), None) => SpanChange::Removed,
+        };
+        let tokens = |s: &super::Span| {
+            (s.tokens_in.is_some() || s.tokens_out.is_some())
+                .then(|| f64::from(s.tokens_in.unwrap_or(0) + s.tokens_out.unwrap_or(0)))
+        };
+        let a_status = a.map(|s| s.status);
+        let b_status = b.map(|s| s.status);
+
+        Self {
+            operation_name: a.or(b).map(|s| s.operation_name.clone()).unwrap_or_default(),
+            path,
+            position,
+            change,
+            a_span_id: a.map(|s| s.span_id.clone()),
+            b_span_id: b.map(|s| s.span_id.clone()),
+            duration_ms: ValueDelta::new(a.and_then(|s| s.duration_ms), b.and_then(|s| s.duration_ms)),
+            tokens: ValueDelta::new(a.and_then(tokens), b.and_then(tokens)),
+            cost_usd: ValueDelta::new(a.and_then(|s| s.cost_usd), b.and_then(|s| s.cost_usd)),
+            status_changed: a_status.is_some() && b_status.is_some() && a_status != b_status,
+            a_status,
+            b_status,
+        }
+    }
+}
+
+/// Spans in start order with their operation path and its occurrence index
+fn keyed_spans(spans: &[super::Span]) -> Vec<(String, usize, &super::Span)> {
+    let by_id: std::collections::HashMap<&str, &super::Span> =
+        spans.iter().map(|s| (s.span_id.as_str(), s)).collect();
+
+    let mut ordered: Vec<&super::Span> = spans.iter().collect();
+    ordered.sort_by_key(|s| s.started_at);
+
+    let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    ordered
+        .into_iter()
+        .map(|span| {
+            let mut names = vec![span.operation_name.as_str()];
+            let mut parent = span.parent_span_id.as_deref();
+            while let Some(p) = parent.and_then(|id| by_id.get(id)) {
+                // A parent cycle would otherwise never end
+                if names.len() > spans.len() {
+                    break;
+                }
+                names.push(p.operation_name.as_str());
+                parent = p.parent_span_id.as_deref();
+            }
+            names.reverse();
+
+            let path = names.join(" > ");
+            let position = seen.entry(path.clone()).or_insert(0);
+            let key = (path, *position, span);
+            *position += 1;
+            key
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Span, SpanKind, SpanPriority, SpanStatus, TraceSummary};
+    use chrono::Duration;
+
+    fn create_test_span(
+        span_id: &str,
+        parent: Option<&str>,
+        operation: &str,
+        offset_ms: i64,
+        duration_ms: f64,
+    ) -> Span {
+        let started_at = Utc::now() - Duration::minutes(5) + Duration::milliseconds(offset_ms);
+        Span {
+            id: Uuid::new_v4(),
+            span_id: span_id.to_string(),
+            trace_id: "t".to_string(),
+            parent_span_id: parent.map(str::to_string),
+            session_id: None,
+            user_id: None,
+            operation_name: operation.to_string(),
+            service_name: "agent".to_string(),
+            span_kind: SpanKind::Internal,
+            started_at,
+            ended_at: Some(started_at + Duration::milliseconds(duration_ms as i64)),
+            duration_ms: Some(duration_ms),
+            status: SpanStatus::Ok,
+            status_message: None,
+            model_name: None,
+            model_provider: None,
+            tokens_in: None,
+            tokens_cached_in: None,
+            tokens_out: None,
+            tokens_reasoning: None,
+            cost_usd: None,
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: None,
+            completion_preview: None,
+            attributes: serde_json::json!({}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::Normal,
+        }
+    }
+
+    #[test]
+    fn test_compare_aligns_by_path_and_position() {
+        let a = vec![
+            create_test_span("a1", None, "run", 0, 1000.0),
+            create_test_span("a2", Some("a1"), "llm.chat", 10, 300.0),
+            create_test_span("a3", Some("a1"), "llm.chat", 400, 200.0),
+        ];
+        let mut b = vec![
+            create_test_span("b1", None, "run", 0, 1500.0),
+            create_test_span("b2", Some("b1"), "llm.chat", 10, 350.0),
+            create_test_span("b3", Some("b1"), "tool.search", 400, 100.0),
+        ];
+        b[2].status = SpanStatus::Error;
+
+        let comparison = TraceComparison::new(
+            TraceSummary::from_spans("a", &a),
+            &a,
+            TraceSummary::from_spans("b", &b),
+            &b,
+        );
+
+        assert_eq!(comparison.duration_ms.delta, Some(500.0));
+        assert_eq!(comparison.spans.len(), 4);
+
+        let second_llm = &comparison.spans[2];
+        assert_eq!(second_llm.path, "run > llm.chat");
+        assert_eq!(second_llm.position, 1);
+        assert_eq!(second_llm.change, SpanChange::Removed);
+
+        let first_llm = &comparison.spans[1];
+        assert_eq!(first_llm.b_span_id.as_deref(), Some("b2"));
+        assert_eq!(first_llm.duration_ms.delta, Some(50.0));
+
+        let tool = &comparison.spans[3];
+        assert_eq!(tool.change, SpanChange::Added);
+        assert_eq!(tool.b_status, Some(SpanStatus::Error));
+        assert!(!tool.status_changed);
+    }
+}
@@ -0,0 +1,200 @@
+//! Saved view models
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{SortConfig, SpanQuery};
+use crate::error::{Error, Result};
+
+/// Trace summary fields a view can sort by
+pub const VIEW_SORT_FIELDS: &[&str] = &[
+    "started_at",
+    "duration_ms",
+    "span_count",
+    "error_count",
+    "total_tokens",
+    "total_cost_usd",
+];
+
+/// Longest allowed view name
+const MAX_NAME_LEN: usize = 100;
+
+/// Trace filters a view applies
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewFilters {
+    /// Service name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+
+    /// Trace status (ok, error)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+
+    /// Minimum trace duration in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_duration_ms: Option<f64>,
+
+    /// How far back to look, e.g. `1h` or `7d`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+
+    /// Span search query, e.g. `model:gpt-4o timeout`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+impl ViewFilters {
+    /// The filters as a single search query, as typed in the TUI
+    pub fn to_query_string(&self) -> String {
+        let quote = |v: &str| {
+            if v.contains(char::is_whitespace) {
+                format!("\"{}\"", v)
+            } else {
+                v.to_string()
+            }
+        };
+
+        let mut terms = Vec::new();
+        if let Some(service) = &self.service {
+            terms.push(format!("service:{}", quote(service)));
+        }
+        if let Some(status) = &self.status {
+            terms.push(format!("status:{}", status));
+        }
+        if let Some(min) = self.min_duration_ms {
+            terms.push(format!("min_duration:{}", min));
+        }
+        if let Some(query) = self.query.as_deref().filter(|q| !q.trim().is_empty()) {
+            terms.push(query.trim().to_string());
+        }
+        terms.join(" ")
+    }
+}
+
+/// A named, shareable set of trace filters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedView {
+    /// Unique identifier
+    pub id: Uuid,
+
+    /// Unique name used to load the view, e.g. `planner-errors`
+    pub name: String,
+
+    /// What the view is for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Filters the view applies
+    pub filters: ViewFilters,
+
+    /// Order of the listed traces
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SortConfig>,
+
+    /// Who created the view
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    /// When the view was created
+    pub created_at: DateTime<Utc>,
+
+    /// When the view was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Input for creating or replacing a saved view
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedViewInput {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub filters: ViewFilters,
+    pub sort: Option<SortConfig>,
+    pub owner: Option<String>,
+}
+
+impl SavedViewInput {
+    /// Check the name, sort field and search query
+    pub fn validate(&self) -> Result<()> {
+        let name = self.name.trim();
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(Error::validation(format!(
+                "View name must be 1 to {} characters",
+                MAX_NAME_LEN
+            )));
+        }
+        // Names are used in URLs and on the command line
+        if name.contains('/') || name.parse::<Uuid>().is_ok() {
+            return Err(Error::validation("View name can't contain '/' or be a UUID"));
+        }
+
+        if let Some(sort) = &self.sort {
+            if !VIEW_SORT_FIELDS.contains(&sort.field.as_str()) {
+                return Err(Error::validation(format!(
+                    "Unknown sort field '{}'; expected one of {}",
+                    sort.field,
+                    VIEW_SORT_FIELDS.join(", ")
+                )));
+            }
+        }
+
+        if let Some(status) = &self.filters.status {
+            if !matches!(status.as_str(), "ok" | "error") {
+                return Err(Error::validation(format!("Unknown status '{}'; expected ok or error", status)));
+            }
+        }
+        if let Some(query) = &self.filters.query {
+            SpanQuery::parse(query)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(name: &str) -> SavedViewInput {
+        SavedViewInput {
+            name: name.to_string(),
+            description: None,
+            filters: ViewFilters::default(),
+            sort: None,
+            owner: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_view_input() {
+        assert!(input("planner-errors").validate().is_ok());
+        assert!(input("  ").validate().is_err());
+        assert!(input("a/b").validate().is_err());
+        assert!(input(&Uuid::new_v4().to_string()).validate().is_err());
+
+        let mut bad_sort = input("slow");
+        bad_sort.sort = Some(SortConfig { field: "color".to_string(), descending: true });
+        assert!(bad_sort.validate().is_err());
+
+        let mut bad_query = input("slow");
+        bad_query.filters.query = Some("min_duration:fast".to_string());
+        assert!(bad_query.validate().is_err());
+    }
+
+    #[test]
+    fn test_filters_to_query_string() {
+        let filters = ViewFilters {
+            service: Some("support agent".to_string()),
+            status: Some("error".to_string()),
+            min_duration_ms: Some(500.0),
+            last: Some("1h".to_string()),
+            query: Some(" timeout ".to_string()),
+        };
+        assert_eq!(
+            filters.to_query_string(),
+            r#"service:"support agent" status:error min_duration:500 timeout"#
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Checks on URLs the server sends requests to on a caller's behalf
+//!
+//! Endpoints that post to a caller-supplied URL could otherwise be used to
+//! reach services only the server can see, like cloud metadata endpoints or
+//! databases on the private network.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use url::{Host, Url};
+
+use crate::error::{Error, Result};
+
+/// Fail unless `url` is http(s) and every address its host resolves to is
+/// public
+pub async fn ensure_public_url(url: &str) -> Result<()> {
+    let parsed = Url::parse(url).map_err(|e| Error::validation(format!("Invalid URL '{}': {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(Error::validation(format!("URL '{}' must be http:// or https://", url)));
+    }
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let addresses: Vec<IpAddr> = match parsed.host() {
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| Error::validation(format!("Can't resolve {}: {}", domain, e)))?
+            .map(|addr| addr.ip())
+            .collect(),
+        None => return Err(Error::validation(format!("URL '{}' has no host", url))),
+    };
+    if addresses.is_empty() {
+        return Err(Error::validation(format!("{} has no addresses", parsed.host_str().unwrap_or(url))));
+    }
+
+    match addresses.into_iter().find(|ip| !is_public(*ip)) {
+        Some(ip) => Err(Error::validation(format!(
+            "{} resolves to {}, which is not a public address",
+            parsed.host_str().unwrap_or(url),
+            ip
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether an address is routable on the public internet
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        // Includes 169.254.169.254, the cloud metadata endpoint
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b == 18 || b == 19))
+        // Reserved, 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_private_hosts_are_rejected() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should not be public", ip);
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+
+        assert!(ensure_public_url("http://127.0.0.1:8080/hook").await.is_err());
+        assert!(ensure_public_url("http://[::1]/hook").await.is_err());
+        assert!(ensure_public_url("http://localhost/hook").await.is_err());
+        assert!(ensure_public_url("file:///etc/passwd").await.is_err());
+        assert!(ensure_public_url("https://1.1.1.1/hook").await.is_ok());
+    }
+}
@@ -0,0 +1,360 @@
+//! Full prompt/completion payload storage
+//!
+//! Spans keep 500-character previews of their prompt and completion, which
+//! is not enough to debug long prompts. With `[payloads] enabled`, the
+//! pipeline captures the full text before previews are cut, redacts it, caps
+//! it at `max_bytes` and stores it apart from the span: in the
+//! `span_payloads` table, or as `{trace_id}/{span_id}.json` objects on S3,
+//! GCS or the local filesystem when `url` is set. Payloads are loaded on
+//! demand, and deleted with their trace or after `retention_days`, whatever
+//! the span retention.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::{ObjectMeta, ObjectStore};
+use regex::Regex;
+
+use crate::archive::open_store;
+use crate::config::PayloadsConfig;
+use crate::db::SpanStore;
+use crate::error::{Error, Result};
+use crate::models::{Span, SpanPayload};
+
+/// What redacted text is replaced with
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Where payloads are kept
+#[derive(Clone)]
+enum Backend {
+    Database(Arc<dyn SpanStore>),
+    Object { store: Arc<dyn ObjectStore>, prefix: Path },
+}
+
+/// Captures, stores and loads full span payloads
+#[derive(Clone)]
+pub struct PayloadStore {
+    backend: Backend,
+    redactor: Redactor,
+    max_bytes: usize,
+    retention_days: u32,
+}
+
+impl PayloadStore {
+    /// Create a payload store for the configured backend
+    ///
+    /// S3 and GCS credentials are read from the standard `AWS_*` and
+    /// `GOOGLE_*` environment variables.
+    pub fn new(config: &PayloadsConfig, spans: Arc<dyn SpanStore>) -> Result<Self> {
+        let backend = match &config.url {
+            Some(url) => {
+                let (store, prefix) = open_store(url)?;
+                Backend::Object { store, prefix }
+            }
+            None => Backend::Database(spans),
+        };
+
+        Ok(Self {
+            backend,
+            redactor: Redactor::new(&config.redact_patterns)?,
+            max_bytes: config.max_bytes,
+            retention_days: config.retention_days,
+        })
+    }
+
+    /// Redact a span's prompt and completion in place and take its full
+    /// payload, before previews are truncated
+    ///
+    /// Returns None for spans with neither a prompt nor a completion.
+    pub fn capture(&self, span: &mut Span) -> Option<SpanPayload> {
+        if span.prompt_preview.is_none() && span.completion_preview.is_none() {
+            return None;
+        }
+
+        for text in [&mut span.prompt_preview, &mut span.completion_preview].into_iter().flatten() {
+            *text = self.redactor.redact(text);
+        }
+        let mut prompt = span.prompt_preview.clone();
+        let mut completion = span.completion_preview.clone();
+        let size_bytes = prompt.as_ref().map_or(0, String::len) + completion.as_ref().map_or(0, String::len);
+        let mut truncated = false;
+        for text in [&mut prompt, &mut completion].into_iter().flatten() {
+            truncated |= truncate_to(text, self.max_bytes);
+        }
+
+        Some(SpanPayload {
+            trace_id: span.trace_id.clone(),
+            span_id: span.span_id.clone(),
+            prompt,
+            completion,
+            truncated,
+            size_bytes: size_bytes as i64,
+            created_at: Utc::now(),
+        })
+    }
+
+    /// Store payloads, replacing any already stored for the same span,
+    /// returning how many were written
+    pub async fn put_batch(&self, payloads: &[SpanPayload]) -> Result<usize> {
+        match &self.backend {
+            Backend::Database(spans) => spans.put_payloads(payloads).await,
+            Backend::Object { store, prefix } => {
+                // Only the latest payload of each span is worth uploading
+                let mut latest: HashMap<(&str, &str), &SpanPayload> = HashMap::with_capacity(payloads.len());
+                for payload in payloads {
+                    latest.insert((payload.trace_id.as_str(), payload.span_id.as_str()), payload);
+                }
+
+                for payload in latest.values() {
+                    let data = serde_json::to_vec(payload)?;
+                    store
+                        .put(&object_path(prefix, &payload.trace_id, &payload.span_id), Bytes::from(data))
+                        .await
+                        .map_err(|e| Error::internal(format!("Failed to store payload: {}", e)))?;
+                }
+                Ok(latest.len())
+            }
+        }
+    }
+
+    /// Get the payload stored for a span
+    pub async fn get(&self, trace_id: &str, span_id: &str) -> Result<Option<SpanPayload>> {
+        match &self.backend {
+            Backend::Database(spans) => spans.get_payload(trace_id, span_id).await,
+            Backend::Object { store, prefix } => {
+                let result = match store.get(&object_path(prefix, trace_id, span_id)).await {
+                    Ok(result) => result,
+                    Err(object_store::Error::NotFound { .. }) => return Ok(None),
+                    Err(e) => return Err(Error::internal(format!("Failed to load payload: {}", e))),
+                };
+                let data = result
+                    .bytes()
+                    .await
+                    .map_err(|e| Error::internal(format!("Failed to load payload: {}", e)))?;
+                Ok(Some(serde_json::from_slice(&data)?))
+            }
+        }
+    }
+
+    /// Delete the payloads stored for a trace, returning how many were
+    /// deleted
+    ///
+    /// Payloads in the database go with the trace's rows when it is deleted,
+    /// so only objects under `{trace_id}/` need removing here.
+    pub async fn delete_trace(&self, trace_id: &str) -> Result<u64> {
+        match &self.backend {
+            Backend::Database(_) => Ok(0),
+            Backend::Object { store, prefix } => {
+                let objects: Vec<ObjectMeta> = store
+                    .list(Some(&prefix.child(trace_id)))
+                    .try_collect()
+                    .await
+                    .map_err(|e| Error::internal(format!("Failed to list payloads: {}", e)))?;
+
+                for object in &objects {
+                    store
+                        .delete(&object.location)
+                        .await
+                        .map_err(|e| Error::internal(format!("Failed to delete payload: {}", e)))?;
+                }
+                Ok(objects.len() as u64)
+            }
+        }
+    }
+
+    /// Delete payloads older than `retention_days`, returning how many were
+    /// deleted
+    pub async fn prune(&self) -> Result<u64> {
+        let cutoff = Utc::now() - Duration::days(i64::from(self.retention_days));
+
+        match &self.backend {
+            Backend::Database(spans) => spans.prune_payloads(cutoff).await,
+            Backend::Object { store, prefix } => {
+                let objects: Vec<ObjectMeta> = store
+                    .list(Some(prefix))
+                    .try_collect()
+                    .await
+                    .map_err(|e| Error::internal(format!("Failed to list payloads: {}", e)))?;
+                let expired: Vec<&ObjectMeta> = objects.iter().filter(|o| o.last_modified < cutoff).collect();
+
+                for object in &expired {
+                    store
+                        .delete(&object.location)
+                        .await
+                        .map_err(|e| Error::internal(format!("Failed to delete payload: {}", e)))?;
+                }
+                Ok(expired.len() as u64)
+            }
+        }
+    }
+}
+
+fn object_path(prefix: &Path, trace_id: &str, span_id: &str) -> Path {
+    prefix.child(trace_id).child(format!("{}.json", span_id))
+}
+
+/// Cut a string to at most `max_bytes`, on a character boundary, returning
+/// whether anything was cut
+fn truncate_to(text: &mut String, max_bytes: usize) -> bool {
+    if text.len() <= max_bytes {
+        return false;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    true
+}
+
+/// Replaces matches of the configured patterns with [`REDACTED`]
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    /// Compile redaction patterns
+    pub fn new(patterns: &[String]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| Error::config(format!("Invalid redaction pattern '{}': {}", p, e))))
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Text with every match replaced
+    pub fn redact(&self, text: &str) -> String {
+        let mut redacted = text.to_string();
+        for pattern in &self.patterns {
+            if pattern.is_match(&redacted) {
+                redacted = pattern.replace_all(&redacted, REDACTED).into_owned();
+            }
+        }
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::memory::InMemory;
+    use serde_json::json;
+
+    fn span(trace_id: &str, span_id: &str, prompt: &str) -> Span {
+        serde_json::from_value(json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "span_id": span_id,
+            "trace_id": trace_id,
+            "parent_span_id": null,
+            "operation_name": "chat",
+            "service_name": "agent",
+            "span_kind": "client",
+            "started_at": "2026-01-01T00:00:00Z",
+            "ended_at": "2026-01-01T00:00:01Z",
+            "duration_ms": 1000.0,
+            "status": "ok",
+            "status_message": null,
+            "model_name": "gpt-4o",
+            "model_provider": null,
+            "tokens_in": 100,
+            "tokens_cached_in": null,
+            "tokens_out": 20,
+            "tokens_reasoning": null,
+            "cost_usd": null,
+            "tool_name": null,
+            "tool_input": null,
+            "tool_output": null,
+            "tool_duration_ms": null,
+            "prompt_preview": prompt,
+            "completion_preview": null,
+            "attributes": {},
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    fn object_payloads(max_bytes: usize) -> PayloadStore {
+        PayloadStore {
+            backend: Backend::Object {
+                store: Arc::new(InMemory::new()),
+                prefix: Path::from("payloads"),
+            },
+            redactor: Redactor::new(&PayloadsConfig::default().redact_patterns).unwrap(),
+            max_bytes,
+            retention_days: 7,
+        }
+    }
+
+    #[test]
+    fn test_capture_keeps_full_text() {
+        let payloads = object_payloads(1000);
+        let prompt = format!("{} mail jane.doe@example.com", "x".repeat(600));
+        let mut span = span("t1", "a1", &prompt);
+
+        let payload = payloads.capture(&mut span).unwrap();
+        let expected = format!("{} mail {}", "x".repeat(600), REDACTED);
+        assert_eq!(payload.prompt.as_deref(), Some(expected.as_str()));
+        assert_eq!(payload.completion, None);
+        assert_eq!(payload.size_bytes, expected.len() as i64);
+        assert!(!payload.truncated);
+        // The span keeps the redacted text for the pipeline to cut to a preview
+        assert_eq!(span.prompt_preview.as_deref(), Some(expected.as_str()));
+
+        let payload = object_payloads(100).capture(&mut span).unwrap();
+        assert_eq!(payload.prompt.map(|p| p.len()), Some(100));
+        assert!(payload.truncated);
+
+        let mut empty = span.clone();
+        empty.prompt_preview = None;
+        assert!(payloads.capture(&mut empty).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_object_backend() {
+        let payloads = object_payloads(1000);
+        let captured: Vec<SpanPayload> = [("t1", "a1"), ("t1", "a2"), ("t2", "b1")]
+            .into_iter()
+            .map(|(trace_id, span_id)| payloads.capture(&mut span(trace_id, span_id, "hello")).unwrap())
+            .collect();
+        assert_eq!(payloads.put_batch(&captured).await.unwrap(), 3);
+
+        let loaded = payloads.get("t1", "a2").await.unwrap().unwrap();
+        assert_eq!(loaded.prompt.as_deref(), Some("hello"));
+        assert!(payloads.get("t1", "zz").await.unwrap().is_none());
+
+        assert_eq!(payloads.delete_trace("t1").await.unwrap(), 2);
+        assert!(payloads.get("t1", "a1").await.unwrap().is_none());
+        assert!(payloads.get("t2", "b1").await.unwrap().is_some());
+        assert_eq!(payloads.delete_trace("t1").await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_default_patterns_redact() {
+        let redactor = Redactor::new(&PayloadsConfig::default().redact_patterns).unwrap();
+        let text = "Mail jane.doe@example.com with key sk-abcdefghijklmnop1234 and Authorization: Bearer eyJhbGciOi.x-y";
+        assert_eq!(
+            redactor.redact(text),
+            "Mail [REDACTED] with key [REDACTED] and Authorization: [REDACTED]"
+        );
+        assert_eq!(redactor.redact("nothing to hide"), "nothing to hide");
+
+        assert!(Redactor::new(&["(unclosed".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        let mut text = "héllo".to_string();
+        assert!(truncate_to(&mut text, 2));
+        assert_eq!(text, "h");
+
+        let mut text = "short".to_string();
+        assert!(!truncate_to(&mut text, 5));
+        assert_eq!(text, "short");
+    }
+}
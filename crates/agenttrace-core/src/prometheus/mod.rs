@@ -0,0 +1,314 @@
+//! Span metrics for Prometheus
+//!
+//! The pipeline records every finished span in [`SpanMetrics`], which keeps
+//! running totals per service and model:
+//!
+//! | Metric | Type | Labels |
+//! |--------|------|--------|
+//! | `agenttrace_spans_total` | counter | `service`, `model`, `status` (`ok` or `error`) |
+//! | `agenttrace_span_duration_seconds` | histogram | `service`, `model` |
+//! | `agenttrace_cost_usd_total` | counter | `service`, `model` |
+//! | `agenttrace_tokens_total` | counter | `service`, `model`, `direction` (`input` or `output`) |
+//!
+//! Request and error rates come from `rate()` over the span counter and
+//! latency percentiles from `histogram_quantile()` over the buckets, so
+//! existing Grafana dashboards and alert rules work on them unchanged. They
+//! are served as OpenMetrics at `/metrics` and pushed to a remote-write
+//! endpoint by [`RemoteWriter`].
+//!
+//! Totals are kept in memory from collector start; Prometheus treats a
+//! restart as a counter reset.
+
+mod remote_write;
+
+pub use remote_write::RemoteWriter;
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::models::{Span, SpanStatus};
+
+/// Content type of [`SpanMetrics::openmetrics`]
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Most service and model combinations tracked; spans of further
+/// combinations are counted under [`OVERFLOW_LABEL`]
+const MAX_SERIES: usize = 10_000;
+
+/// Service and model label of spans past [`MAX_SERIES`]
+const OVERFLOW_LABEL: &str = "_other";
+
+/// Running span totals per service and model
+#[derive(Clone)]
+pub struct SpanMetrics {
+    buckets: Arc<[f64]>,
+    series: Arc<Mutex<BTreeMap<SeriesKey, Series>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SeriesKey {
+    service: String,
+    model: String,
+}
+
+#[derive(Debug, Default)]
+struct Series {
+    ok: u64,
+    errors: u64,
+    /// Spans per latency bucket, not cumulative
+    bucket_counts: Vec<u64>,
+    duration_count: u64,
+    duration_sum_secs: f64,
+    cost_usd: f64,
+    tokens_in: u64,
+    tokens_out: u64,
+}
+
+/// A metric and its current samples
+struct MetricFamily {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    samples: Vec<Sample>,
+}
+
+/// One value of a metric, e.g. a histogram's `_bucket` for one `le`
+struct Sample {
+    suffix: &'static str,
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+impl SpanMetrics {
+    /// Create empty totals with the given latency bucket upper bounds
+    /// (seconds, increasing)
+    pub fn new(latency_buckets_secs: &[f64]) -> Self {
+        Self {
+            buckets: latency_buckets_secs.into(),
+            series: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Add a span to the totals; in-progress spans are counted once they end
+    #[allow(clippy::cast_sign_loss)]
+    pub fn record(&self, span: &Span) {
+        if span.is_in_progress() {
+            return;
+        }
+
+        let mut series = self.series.lock();
+        let mut key = SeriesKey {
+            service: span.service_name.clone(),
+            model: span.model_name.clone().unwrap_or_default(),
+        };
+        if series.len() >= MAX_SERIES && !series.contains_key(&key) {
+            key = SeriesKey {
+                service: OVERFLOW_LABEL.to_string(),
+                model: OVERFLOW_LABEL.to_string(),
+            };
+        }
+        let totals = series.entry(key).or_insert_with(|| Series {
+            bucket_counts: vec![0; self.buckets.len()],
+            ..Series::default()
+        });
+
+        if span.status == SpanStatus::Error {
+            totals.errors += 1;
+        } else {
+            totals.ok += 1;
+        }
+        if let Some(duration_ms) = span.duration_ms.filter(|ms| *ms >= 0.0) {
+            let secs = duration_ms / 1000.0;
+            if let Some(bucket) = self.buckets.iter().position(|le| secs <= *le) {
+                totals.bucket_counts[bucket] += 1;
+            }
+            totals.duration_count += 1;
+            totals.duration_sum_secs += secs;
+        }
+        totals.cost_usd += span.cost_usd.unwrap_or(0.0);
+        totals.tokens_in += span.tokens_in.unwrap_or(0).max(0) as u64;
+        totals.tokens_out += span.tokens_out.unwrap_or(0).max(0) as u64;
+    }
+
+    /// The current totals in the OpenMetrics text format
+    pub fn openmetrics(&self) -> String {
+        let mut out = String::new();
+        for family in self.families() {
+            let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind);
+            let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+            for sample in &family.samples {
+                out.push_str(family.name);
+                out.push_str(sample.suffix);
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+                    .collect();
+                let _ = writeln!(out, "{{{}}} {}", labels.join(","), sample.value);
+            }
+        }
+        out.push_str("# EOF\n");
+        out
+    }
+
+    /// Every metric with its current samples
+    #[allow(clippy::cast_precision_loss)]
+    fn families(&self) -> Vec<MetricFamily> {
+        let series = self.series.lock();
+        let sample = |suffix, labels, value| Sample { suffix, labels, value };
+        let labels = |key: &SeriesKey| vec![("service", key.service.clone()), ("model", key.model.clone())];
+        let with = |key: &SeriesKey, name, value: String| {
+            let mut labels = labels(key);
+            labels.push((name, value));
+            labels
+        };
+
+        let mut spans = Vec::new();
+        let mut durations = Vec::new();
+        let mut costs = Vec::new();
+        let mut tokens = Vec::new();
+        for (key, totals) in series.iter() {
+            spans.push(sample(
+                "_total",
+                with(key, "status", "ok".to_string()),
+                totals.ok as f64,
+            ));
+            spans.push(sample(
+                "_total",
+                with(key, "status", "error".to_string()),
+                totals.errors as f64,
+            ));
+
+            let mut cumulative = 0;
+            for (le, count) in self.buckets.iter().zip(&totals.bucket_counts) {
+                cumulative += count;
+                durations.push(sample(
+                    "_bucket",
+                    with(key, "le", format!("{:?}", le)),
+                    cumulative as f64,
+                ));
+            }
+            durations.push(sample(
+                "_bucket",
+                with(key, "le", "+Inf".to_string()),
+                totals.duration_count as f64,
+            ));
+            durations.push(sample("_count", labels(key), totals.duration_count as f64));
+            durations.push(sample("_sum", labels(key), totals.duration_sum_secs));
+
+            costs.push(sample("_total", labels(key), totals.cost_usd));
+            tokens.push(sample(
+                "_total",
+                with(key, "direction", "input".to_string()),
+                totals.tokens_in as f64,
+            ));
+            tokens.push(sample(
+                "_total",
+                with(key, "direction", "output".to_string()),
+                totals.tokens_out as f64,
+            ));
+        }
+
+        vec![
+            MetricFamily {
+                name: "agenttrace_spans",
+                kind: "counter",
+                help: "Finished spans by status",
+                samples: spans,
+            },
+            MetricFamily {
+                name: "agenttrace_span_duration_seconds",
+                kind: "histogram",
+                help: "Span duration",
+                samples: durations,
+            },
+            MetricFamily {
+                name: "agenttrace_cost_usd",
+                kind: "counter",
+                help: "Cost of LLM calls in USD",
+                samples: costs,
+            },
+            MetricFamily {
+                name: "agenttrace_tokens",
+                kind: "counter",
+                help: "LLM tokens by direction",
+                samples: tokens,
+            },
+        ]
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn span(service: &str, model: Option<&str>, status: &str, duration_ms: f64) -> Span {
+        serde_json::from_value(json!({
+            "id": "6f1c1a3e-8d2b-4c4e-9a43-1d2f0b7c5e10",
+            "span_id": "a1",
+            "trace_id": "t1",
+            "parent_span_id": null,
+            "operation_name": "chat",
+            "service_name": service,
+            "span_kind": "client",
+            "started_at": "2026-01-01T00:00:00Z",
+            "ended_at": "2026-01-01T00:00:01Z",
+            "duration_ms": duration_ms,
+            "status": status,
+            "status_message": null,
+            "model_name": model,
+            "model_provider": null,
+            "tokens_in": 100,
+            "tokens_cached_in": null,
+            "tokens_out": 20,
+            "tokens_reasoning": null,
+            "cost_usd": 0.5,
+            "tool_name": null,
+            "tool_input": null,
+            "tool_output": null,
+            "tool_duration_ms": null,
+            "prompt_preview": null,
+            "completion_preview": null,
+            "attributes": {},
+            "events": [],
+            "links": []
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_openmetrics() {
+        let metrics = SpanMetrics::new(&[0.5, 1.0]);
+        metrics.record(&span("agent", Some("gpt-4o"), "ok", 250.0));
+        metrics.record(&span("agent", Some("gpt-4o"), "error", 750.0));
+        metrics.record(&span("agent", Some("gpt-4o"), "ok", 5000.0));
+        metrics.record(&span("agent", Some("gpt-4o"), "in_progress", 10.0));
+        metrics.record(&span("say \"hi\"", None, "ok", 100.0));
+
+        let text = metrics.openmetrics();
+        let labels = r#"service="agent",model="gpt-4o""#;
+        for line in [
+            format!("agenttrace_spans_total{{{},status=\"ok\"}} 2", labels),
+            format!("agenttrace_spans_total{{{},status=\"error\"}} 1", labels),
+            format!("agenttrace_span_duration_seconds_bucket{{{},le=\"0.5\"}} 1", labels),
+            format!("agenttrace_span_duration_seconds_bucket{{{},le=\"1.0\"}} 2", labels),
+            format!("agenttrace_span_duration_seconds_bucket{{{},le=\"+Inf\"}} 3", labels),
+            format!("agenttrace_span_duration_seconds_sum{{{}}} 6", labels),
+            format!("agenttrace_cost_usd_total{{{}}} 1.5", labels),
+            format!("agenttrace_tokens_total{{{},direction=\"input\"}} 300", labels),
+            r#"agenttrace_spans_total{service="say \"hi\"",model="",status="ok"} 1"#.to_string(),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {}\n{}", line, text);
+        }
+        assert!(text.starts_with("# TYPE agenttrace_spans counter\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
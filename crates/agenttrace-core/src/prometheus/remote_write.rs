@@ -0,0 +1,234 @@
+//! Prometheus remote-write push
+//!
+//! Sends the current totals as a snappy-compressed protobuf `WriteRequest`
+//! (remote-write 1.0), with histograms as their `_bucket`, `_count` and
+//! `_sum` series, as Prometheus itself stores them.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use prost::Message;
+use reqwest::Client;
+use tracing::warn;
+
+use super::{MetricFamily, SpanMetrics};
+use crate::config::PrometheusConfig;
+use crate::error::{Error, Result};
+
+/// Timeout for each push
+const PUSH_TIMEOUT_SECS: u64 = 10;
+
+/// Remote-write 1.0 protobuf messages
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WriteRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub timeseries: Vec<TimeSeries>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TimeSeries {
+        #[prost(message, repeated, tag = "1")]
+        pub labels: Vec<Label>,
+        #[prost(message, repeated, tag = "2")]
+        pub samples: Vec<Sample>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Label {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sample {
+        #[prost(double, tag = "1")]
+        pub value: f64,
+        #[prost(int64, tag = "2")]
+        pub timestamp: i64,
+    }
+}
+
+/// Pushes [`SpanMetrics`] to a remote-write endpoint on an interval
+pub struct RemoteWriter {
+    metrics: SpanMetrics,
+    url: String,
+    bearer_token: Option<String>,
+    labels: Vec<(String, String)>,
+    interval: Duration,
+    client: Client,
+}
+
+impl RemoteWriter {
+    /// Create a writer for `prometheus.remote_write_url`, or None if it is unset
+    pub fn new(config: &PrometheusConfig, metrics: SpanMetrics) -> Option<Self> {
+        let url = config.remote_write_url.clone()?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(PUSH_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Some(Self {
+            metrics,
+            url,
+            bearer_token: config.remote_write_bearer_token.clone(),
+            labels: series_labels(&config.remote_write_labels, hostname),
+            interval: Duration::from_secs(config.remote_write_interval_secs.max(1)),
+            client,
+        })
+    }
+
+    /// Push the totals every interval until the task is dropped
+    ///
+    /// A failed push is logged; the next one carries the same totals, so
+    /// nothing is lost beyond the missed sample.
+    pub async fn start(&self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.push().await {
+                warn!(url = %self.url, error = %e, "Failed to push span metrics");
+            }
+        }
+    }
+
+    /// Push the current totals once
+    pub async fn push(&self) -> Result<()> {
+        let request = write_request(&self.metrics.families(), &self.labels, Utc::now().timestamp_millis());
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .map_err(|e| Error::Internal(e.to_string()))?;
+
+        let mut builder = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/x-protobuf")
+            .header("Content-Encoding", "snappy")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body);
+        if let Some(token) = &self.bearer_token {
+            builder = builder.bearer_auth(token);
+        }
+
+        let response = builder.send().await.map_err(|e| Error::Http(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(Error::Http(format!("{} returned {}", self.url, response.status())));
+        }
+        Ok(())
+    }
+}
+
+/// The configured labels, plus `instance` set to the host name unless
+/// configured, so pushes from several collectors stay separate series
+fn series_labels(configured: &BTreeMap<String, String>, hostname: impl FnOnce() -> String) -> Vec<(String, String)> {
+    let mut labels = configured.clone();
+    labels.entry("instance".to_string()).or_insert_with(hostname);
+    labels.into_iter().collect()
+}
+
+/// This host's name, from `HOSTNAME` or `/etc/hostname`
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "agenttrace".to_string())
+}
+
+/// One time series per sample, labelled with `__name__`, the sample's
+/// labels and `extra_labels`, sorted by name as remote-write requires
+fn write_request(families: &[MetricFamily], extra_labels: &[(String, String)], timestamp: i64) -> proto::WriteRequest {
+    let timeseries = families
+        .iter()
+        .flat_map(|family| {
+            family.samples.iter().map(move |sample| {
+                let mut labels: Vec<proto::Label> =
+                    std::iter::once(("__name__", format!("{}{}", family.name, sample.suffix)))
+                        .chain(sample.labels.iter().map(|(name, value)| (*name, value.clone())))
+                        .chain(extra_labels.iter().map(|(name, value)| (name.as_str(), value.clone())))
+                        .map(|(name, value)| proto::Label {
+                            name: name.to_string(),
+                            value,
+                        })
+                        .collect();
+                labels.sort_by(|a, b| a.name.cmp(&b.name));
+                // A span's own label wins over a configured one of the same name
+                labels.dedup_by(|later, earlier| later.name == earlier.name);
+
+                proto::TimeSeries {
+                    labels,
+                    samples: vec![proto::Sample {
+                        value: sample.value,
+                        timestamp,
+                    }],
+                }
+            })
+        })
+        .collect();
+
+    proto::WriteRequest { timeseries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prometheus::Sample;
+
+    #[test]
+    fn test_write_request() {
+        let families = vec![MetricFamily {
+            name: "agenttrace_spans",
+            kind: "counter",
+            help: "",
+            samples: vec![Sample {
+                suffix: "_total",
+                labels: vec![("service", "agent".to_string()), ("status", "ok".to_string())],
+                value: 3.0,
+            }],
+        }];
+        let extra = vec![
+            ("job".to_string(), "agenttrace".to_string()),
+            ("service".to_string(), "x".to_string()),
+        ];
+
+        let request = write_request(&families, &extra, 1_700_000_000_000);
+        let decoded = proto::WriteRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        let series = &decoded.timeseries[0];
+        let labels: Vec<(&str, &str)> = series
+            .labels
+            .iter()
+            .map(|l| (l.name.as_str(), l.value.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("__name__", "agenttrace_spans_total"),
+                ("job", "agenttrace"),
+                ("service", "agent"),
+                ("status", "ok"),
+            ]
+        );
+        assert_eq!(series.samples[0].value, 3.0);
+        assert_eq!(series.samples[0].timestamp, 1_700_000_000_000);
+    }
+
+    #[test]
+    fn test_instance_label_defaults_to_hostname() {
+        let mut configured = BTreeMap::from([("job".to_string(), "agenttrace".to_string())]);
+        assert_eq!(
+            series_labels(&configured, || "collector-1".to_string()),
+            vec![
+                ("instance".to_string(), "collector-1".to_string()),
+                ("job".to_string(), "agenttrace".to_string()),
+            ]
+        );
+
+        configured.insert("instance".to_string(), "eu-1".to_string());
+        let labels = series_labels(&configured, || unreachable!());
+        assert_eq!(labels[0], ("instance".to_string(), "eu-1".to_string()));
+    }
+}
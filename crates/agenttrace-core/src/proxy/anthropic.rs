@@ -0,0 +1,198 @@
+//! Translation between chat completions and Anthropic's messages API
+//!
+//! Covers text conversations: system, user and assistant messages with
+//! string or text-part content. Requests using anything else (tool
+//! results, images) are rejected rather than silently changed.
+
+use chrono::Utc;
+use serde_json::{json, Value};
+
+/// Output tokens requested when the client sets no limit, since the
+/// messages API requires one
+const DEFAULT_MAX_TOKENS: u64 = 4096;
+
+/// Convert a chat completions request into a messages request
+pub(super) fn to_messages_request(request: &Value) -> Result<Value, String> {
+    let model = request["model"].as_str().ok_or("model is required")?;
+    let messages = request["messages"].as_array().ok_or("messages is required")?;
+
+    let mut system = Vec::new();
+    let mut converted = Vec::new();
+    for message in messages {
+        let content = content_blocks(&message["content"])?;
+        match message["role"].as_str().unwrap_or_default() {
+            "system" | "developer" => system.extend(content),
+            role @ ("user" | "assistant") => converted.push(json!({"role": role, "content": content})),
+            role => {
+                return Err(format!(
+                    "messages with role '{}' aren't supported by the anthropic upstream",
+                    role
+                ))
+            }
+        }
+    }
+
+    let max_tokens = request["max_completion_tokens"]
+        .as_u64()
+        .or_else(|| request["max_tokens"].as_u64())
+        .unwrap_or(DEFAULT_MAX_TOKENS);
+    let mut body = json!({
+        "model": model,
+        "messages": converted,
+        "max_tokens": max_tokens,
+    });
+    if !system.is_empty() {
+        body["system"] = Value::Array(system);
+    }
+    for key in ["temperature", "top_p"] {
+        if let Some(value) = request.get(key).filter(|v| !v.is_null()) {
+            body[key] = value.clone();
+        }
+    }
+    match &request["stop"] {
+        Value::String(stop) => body["stop_sequences"] = json!([stop]),
+        Value::Array(stops) => body["stop_sequences"] = Value::Array(stops.clone()),
+        _ => {}
+    }
+    if let Some(user) = request["user"].as_str() {
+        body["metadata"] = json!({"user_id": user});
+    }
+    Ok(body)
+}
+
+/// Message content as Anthropic text blocks
+fn content_blocks(content: &Value) -> Result<Vec<Value>, String> {
+    match content {
+        Value::String(text) => Ok(vec![json!({"type": "text", "text": text})]),
+        Value::Array(parts) => parts
+            .iter()
+            .map(|part| match part["type"].as_str() {
+                Some("text") => Ok(json!({"type": "text", "text": part["text"]})),
+                Some(other) => Err(format!("'{}' content isn't supported by the anthropic upstream", other)),
+                None => Err("content parts must have a type".to_string()),
+            })
+            .collect(),
+        Value::Null => Ok(Vec::new()),
+        _ => Err("message content must be a string or an array of parts".to_string()),
+    }
+}
+
+/// Convert a messages response into a chat completion
+///
+/// Anthropic counts cached prompt tokens separately from `input_tokens`;
+/// `prompt_tokens` includes them, as OpenAI's does.
+pub(super) fn to_chat_completion(message: &Value) -> Value {
+    let text: String = message["content"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|block| block["type"] == "text")
+        .filter_map(|block| block["text"].as_str())
+        .collect();
+
+    let usage = &message["usage"];
+    let tokens = |key: &str| usage[key].as_i64().unwrap_or(0);
+    let cached = tokens("cache_read_input_tokens");
+    let prompt = tokens("input_tokens") + cached + tokens("cache_creation_input_tokens");
+    let completion = tokens("output_tokens");
+
+    json!({
+        "id": message["id"],
+        "object": "chat.completion",
+        "created": Utc::now().timestamp(),
+        "model": message["model"],
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": text},
+            "finish_reason": finish_reason(message["stop_reason"].as_str()),
+        }],
+        "usage": {
+            "prompt_tokens": prompt,
+            "completion_tokens": completion,
+            "total_tokens": prompt + completion,
+            "prompt_tokens_details": {"cached_tokens": cached},
+        },
+    })
+}
+
+/// Convert a messages API error body into an OpenAI error body
+pub(super) fn to_openai_error(error: &Value) -> Value {
+    json!({
+        "error": {
+            "message": error["error"]["message"].as_str().unwrap_or("upstream request failed"),
+            "type": error["error"]["type"].as_str().unwrap_or("upstream_error"),
+        }
+    })
+}
+
+fn finish_reason(stop_reason: Option<&str>) -> &'static str {
+    match stop_reason {
+        Some("max_tokens") => "length",
+        Some("tool_use") => "tool_calls",
+        _ => "stop",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_translation() {
+        let request = json!({
+            "model": "claude-3-5-sonnet-20241022",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": [{"type": "text", "text": "Hi"}]},
+                {"role": "assistant", "content": "Hello!"},
+                {"role": "user", "content": "Name a color"},
+            ],
+            "max_tokens": 64,
+            "temperature": 0.2,
+            "stop": "\n",
+            "user": "u-1",
+        });
+
+        let body = to_messages_request(&request).unwrap();
+        assert_eq!(body["system"], json!([{"type": "text", "text": "Be brief."}]));
+        assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            body["messages"][1],
+            json!({"role": "assistant", "content": [{"type": "text", "text": "Hello!"}]})
+        );
+        assert_eq!(body["max_tokens"], 64);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["stop_sequences"], json!(["\n"]));
+        assert_eq!(body["metadata"]["user_id"], "u-1");
+
+        let tool_result = json!({"model": "m", "messages": [{"role": "tool", "content": "42"}]});
+        assert!(to_messages_request(&tool_result).is_err());
+        let image = json!({"model": "m", "messages": [{"role": "user", "content": [{"type": "image_url"}]}]});
+        assert!(to_messages_request(&image).is_err());
+    }
+
+    #[test]
+    fn test_response_translation() {
+        let message = json!({
+            "id": "msg_1",
+            "type": "message",
+            "model": "claude-3-5-sonnet-20241022",
+            "content": [{"type": "text", "text": "Blue"}],
+            "stop_reason": "max_tokens",
+            "usage": {"input_tokens": 10, "cache_read_input_tokens": 90, "output_tokens": 1},
+        });
+
+        let completion = to_chat_completion(&message);
+        assert_eq!(completion["choices"][0]["message"]["content"], "Blue");
+        assert_eq!(completion["choices"][0]["finish_reason"], "length");
+        assert_eq!(completion["usage"]["prompt_tokens"], 100);
+        assert_eq!(completion["usage"]["prompt_tokens_details"]["cached_tokens"], 90);
+        assert_eq!(completion["usage"]["total_tokens"], 101);
+
+        let error = json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}});
+        assert_eq!(
+            to_openai_error(&error),
+            json!({"error": {"message": "Overloaded", "type": "overloaded_error"}})
+        );
+    }
+}
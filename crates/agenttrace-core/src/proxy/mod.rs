@@ -0,0 +1,799 @@
+//! OpenAI-compatible LLM proxy
+//!
+//! Serves `/v1/chat/completions`, forwards each request to the configured
+//! upstream and records the call as an LLM span with its model, token
+//! usage, latency, prompt and completion. Pointing an application's OpenAI
+//! base URL at the proxy traces it without code changes.
+//!
+//! OpenAI and Azure requests are passed through unchanged, streaming
+//! included; Anthropic requests are translated to its messages API and the
+//! responses back. Streaming isn't translated, so Anthropic requests with
+//! `stream: true` are rejected with a 400. The client's API key is
+//! forwarded in the header each upstream expects.
+//!
+//! A span starts a new trace unless the request names one with
+//! `x-agenttrace-trace-id` (and optionally `x-agenttrace-parent-span-id`);
+//! `x-agenttrace-session-id` and the request's `user` fill in its session
+//! and user.
+
+mod anthropic;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::Utc;
+use futures::StreamExt;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::collector::Pipeline;
+use crate::config::{ProxyConfig, ProxyUpstream};
+use crate::error::{Error, Result};
+use crate::models::{Span, SpanKind, SpanPriority, SpanStatus};
+
+/// Largest request body accepted
+const MAX_REQUEST_BODY: usize = 32 * 1024 * 1024;
+
+/// Header placing the span in an existing trace
+const TRACE_ID_HEADER: &str = "x-agenttrace-trace-id";
+
+/// Header naming the span's parent within that trace
+const PARENT_SPAN_ID_HEADER: &str = "x-agenttrace-parent-span-id";
+
+/// Header naming the session the call belongs to
+const SESSION_ID_HEADER: &str = "x-agenttrace-session-id";
+
+/// Client headers forwarded to OpenAI as they are
+const OPENAI_HEADERS: &[&str] = &["authorization", "openai-organization", "openai-project"];
+
+/// Tool calls read from one streamed completion
+const MAX_TOOL_CALLS: usize = 128;
+
+struct ProxyState {
+    config: ProxyConfig,
+    base_url: String,
+    client: reqwest::Client,
+    pipeline: Arc<Pipeline>,
+}
+
+/// LLM proxy server
+pub struct ProxyServer {
+    state: Arc<ProxyState>,
+}
+
+impl ProxyServer {
+    /// Create a proxy that records spans through `pipeline`
+    pub fn new(config: ProxyConfig, pipeline: Arc<Pipeline>) -> Result<Self> {
+        let base_url = config
+            .upstream_url
+            .as_deref()
+            .or(config.upstream.default_url())
+            .ok_or_else(|| {
+                Error::config(format!(
+                    "proxy.upstream_url is required for the {} upstream",
+                    config.upstream.as_str()
+                ))
+            })?
+            .trim_end_matches('/')
+            .to_string();
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        Ok(Self {
+            state: Arc::new(ProxyState {
+                config,
+                base_url,
+                client,
+                pipeline,
+            }),
+        })
+    }
+
+    /// Build the router
+    ///
+    /// `/chat/completions` is served too, for clients whose base URL
+    /// leaves out `/v1`.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/v1/chat/completions", post(chat_completions))
+            .route("/chat/completions", post(chat_completions))
+            .layer(DefaultBodyLimit::max(MAX_REQUEST_BODY))
+            .with_state(self.state.clone())
+    }
+
+    /// Serve until the process exits
+    pub async fn serve(self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        info!(
+            "LLM proxy listening on {} (upstream: {} at {})",
+            addr,
+            self.state.config.upstream.as_str(),
+            self.state.base_url
+        );
+
+        axum::serve(listener, self.router())
+            .await
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+async fn chat_completions(State(state): State<Arc<ProxyState>>, headers: HeaderMap, body: Bytes) -> Response {
+    let request: Value = match serde_json::from_slice(&body) {
+        Ok(request @ Value::Object(_)) => request,
+        _ => {
+            return openai_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "request body must be a JSON object",
+            )
+        }
+    };
+    let call = Call::start(&state, &headers, &request);
+
+    match state.config.upstream {
+        ProxyUpstream::OpenAi | ProxyUpstream::Azure => forward_openai(&state, &headers, request, call).await,
+        ProxyUpstream::Anthropic => forward_anthropic(&state, &headers, &request, call).await,
+    }
+}
+
+/// Pass a request through to OpenAI or Azure
+async fn forward_openai(state: &ProxyState, headers: &HeaderMap, mut request: Value, mut call: Call) -> Response {
+    if call.stream && state.config.stream_usage && request.get("stream_options").is_none() {
+        request["stream_options"] = json!({"include_usage": true});
+    }
+
+    let upstream = if state.config.upstream == ProxyUpstream::Azure {
+        let Some(deployment) = call.span.model_name.clone() else {
+            return openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", "model is required");
+        };
+        if !is_deployment_name(&deployment) {
+            return openai_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "model must be an Azure deployment name of letters, digits, '.', '_' and '-'",
+            );
+        }
+        let url = format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            state.base_url, deployment, state.config.azure_api_version
+        );
+        let mut upstream = state.client.post(url).json(&request);
+        if let Some(key) = api_key(headers, "api-key") {
+            upstream = upstream.header("api-key", key);
+        }
+        upstream
+    } else {
+        let mut upstream = state
+            .client
+            .post(format!("{}/chat/completions", state.base_url))
+            .json(&request);
+        for name in OPENAI_HEADERS {
+            if let Some(value) = headers.get(*name) {
+                upstream = upstream.header(*name, value.as_bytes());
+            }
+        }
+        upstream
+    };
+
+    let response = match upstream.send().await {
+        Ok(response) => response,
+        Err(e) => return unreachable_upstream(state, call, &e).await,
+    };
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+
+    let mut builder = Response::builder().status(status);
+    for (name, value) in response.headers() {
+        if is_forwarded_response_header(name.as_str()) {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+    }
+
+    if call.stream && status.is_success() {
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut chunks = response.bytes_stream();
+            let mut reader = StreamReader::default();
+            while let Some(chunk) = chunks.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        reader.push(&bytes);
+                        if tx.send(Ok(bytes)).await.is_err() {
+                            call.span.attributes["proxy.client_disconnected"] = json!(true);
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        call.fail(format!("upstream stream failed: {}", e));
+                        let _ = tx.send(Err(e)).await;
+                        break;
+                    }
+                }
+            }
+            if let Some(message) = reader.error.take() {
+                call.fail(message);
+            }
+            call.complete(reader.completion);
+            call.finish(status).await;
+        });
+        return build(builder, Body::from_stream(ReceiverStream::new(rx)));
+    }
+
+    let body = match response.bytes().await {
+        Ok(body) => body,
+        Err(e) => return unreachable_upstream(state, call, &e).await,
+    };
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(value) if status.is_success() => call.complete(Completion::from_response(&value)),
+        Ok(value) => call.fail_with_body(&value),
+        Err(_) => {}
+    }
+    call.finish(status).await;
+    build(builder, Body::from(body))
+}
+
+/// Translate a request to Anthropic's messages API and the response back
+async fn forward_anthropic(state: &ProxyState, headers: &HeaderMap, request: &Value, mut call: Call) -> Response {
+    if call.stream {
+        return openai_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            "streaming isn't supported with the anthropic upstream",
+        );
+    }
+    let body = match anthropic::to_messages_request(request) {
+        Ok(body) => body,
+        Err(message) => return openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", &message),
+    };
+
+    let version = headers
+        .get("anthropic-version")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(&state.config.anthropic_version);
+    let mut upstream = state
+        .client
+        .post(format!("{}/v1/messages", state.base_url))
+        .header("anthropic-version", version)
+        .json(&body);
+    if let Some(key) = api_key(headers, "x-api-key") {
+        upstream = upstream.header("x-api-key", key);
+    }
+    if let Some(beta) = headers.get("anthropic-beta") {
+        upstream = upstream.header("anthropic-beta", beta.as_bytes());
+    }
+
+    let response = match upstream.send().await {
+        Ok(response) => response,
+        Err(e) => return unreachable_upstream(state, call, &e).await,
+    };
+    let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let message: Value = match response.json().await {
+        Ok(message) => message,
+        Err(e) => return unreachable_upstream(state, call, &e).await,
+    };
+
+    let converted = if status.is_success() {
+        let completion = anthropic::to_chat_completion(&message);
+        call.complete(Completion::from_response(&completion));
+        completion
+    } else {
+        let error = anthropic::to_openai_error(&message);
+        call.fail_with_body(&error);
+        error
+    };
+    call.finish(status).await;
+    (status, Json(converted)).into_response()
+}
+
+/// Record a call that got no usable response and answer with a 502
+async fn unreachable_upstream(state: &ProxyState, mut call: Call, error: &reqwest::Error) -> Response {
+    let message = format!(
+        "{} upstream at {} failed: {}",
+        state.config.upstream.as_str(),
+        state.base_url,
+        error
+    );
+    warn!("{}", message);
+    call.fail(message.clone());
+    call.finish(StatusCode::BAD_GATEWAY).await;
+    openai_error(StatusCode::BAD_GATEWAY, "upstream_error", &message)
+}
+
+/// An LLM call being proxied, recorded as a span once it ends
+struct Call {
+    span: Span,
+    stream: bool,
+    pipeline: Arc<Pipeline>,
+}
+
+impl Call {
+    fn start(state: &ProxyState, headers: &HeaderMap, request: &Value) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let stream = request["stream"].as_bool().unwrap_or(false);
+        let span_id = Uuid::new_v4().simple().to_string()[..16].to_string();
+        let trace_id = header(TRACE_ID_HEADER).unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+
+        let span = Span {
+            id: Uuid::new_v4(),
+            span_id,
+            trace_id,
+            parent_span_id: header(PARENT_SPAN_ID_HEADER),
+            session_id: header(SESSION_ID_HEADER),
+            user_id: request["user"].as_str().map(str::to_string),
+            operation_name: "chat.completions".to_string(),
+            service_name: state.config.service_name.clone(),
+            span_kind: SpanKind::Client,
+            started_at: Utc::now(),
+            ended_at: None,
+            duration_ms: None, // Will be calculated by pipeline
+            status: SpanStatus::Unset,
+            status_message: None,
+            model_name: request["model"].as_str().map(str::to_string),
+            model_provider: Some(state.config.upstream.as_str().to_string()),
+            tokens_in: None,
+            tokens_cached_in: None,
+            tokens_out: None,
+            tokens_reasoning: None,
+            cost_usd: None, // Will be calculated by pipeline
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: prompt_text(request),
+            completion_preview: None,
+            attributes: json!({"llm.stream": stream}),
+            events: vec![],
+            links: vec![],
+            priority: SpanPriority::default(),
+        };
+
+        Self {
+            span,
+            stream,
+            pipeline: Arc::clone(&state.pipeline),
+        }
+    }
+
+    /// Fill in what the upstream reported about the completion
+    fn complete(&mut self, completion: Completion) {
+        let span = &mut self.span;
+        if completion.model.is_some() {
+            span.model_name = completion.model;
+        }
+        if let Some(usage) = &completion.usage {
+            span.tokens_in = count(&usage["prompt_tokens"]);
+            span.tokens_out = count(&usage["completion_tokens"]);
+            span.tokens_cached_in = count(&usage["prompt_tokens_details"]["cached_tokens"]);
+            span.tokens_reasoning = count(&usage["completion_tokens_details"]["reasoning_tokens"]);
+        }
+        if let Some(reason) = completion.finish_reason {
+            span.attributes["llm.finish_reason"] = json!(reason);
+        }
+
+        if !completion.text.is_empty() {
+            span.completion_preview = Some(completion.text);
+        } else if !completion.tool_calls.is_empty() {
+            let calls: Vec<Value> = completion
+                .tool_calls
+                .into_iter()
+                .map(|(name, arguments)| json!({"name": name, "arguments": arguments}))
+                .collect();
+            span.completion_preview = Some(Value::Array(calls).to_string());
+        }
+    }
+
+    fn fail(&mut self, message: String) {
+        self.span.status = SpanStatus::Error;
+        self.span.status_message = Some(message);
+    }
+
+    /// Fail with the message from an OpenAI error body
+    fn fail_with_body(&mut self, body: &Value) {
+        if let Some(message) = body["error"]["message"].as_str() {
+            self.fail(message.to_string());
+        }
+    }
+
+    /// Record the span through the pipeline
+    async fn finish(mut self, status: StatusCode) {
+        self.span.ended_at = Some(Utc::now());
+        self.span.attributes["http.status_code"] = json!(status.as_u16());
+        if self.span.status != SpanStatus::Error {
+            if status.is_success() {
+                self.span.status = SpanStatus::Ok;
+            } else {
+                self.fail(format!("upstream returned {}", status));
+            }
+        }
+
+        debug!(
+            "Proxied {} call for {} ({})",
+            self.span.model_provider.as_deref().unwrap_or("llm"),
+            self.span.model_name.as_deref().unwrap_or("unknown model"),
+            status
+        );
+        if let Err(e) = self.pipeline.submit(self.span).await {
+            warn!("Failed to record proxied call: {}", e);
+        }
+    }
+}
+
+/// What a completion reported, from a full response or a stream
+#[derive(Debug, Default)]
+struct Completion {
+    model: Option<String>,
+    text: String,
+    /// Function names and arguments of requested tool calls
+    tool_calls: Vec<(String, String)>,
+    usage: Option<Value>,
+    finish_reason: Option<String>,
+}
+
+impl Completion {
+    /// Read a chat completion response; only the first choice is recorded
+    fn from_response(response: &Value) -> Self {
+        let choice = &response["choices"][0];
+        let message = &choice["message"];
+        Self {
+            model: response["model"].as_str().map(str::to_string),
+            text: message_text(&message["content"]),
+            tool_calls: message["tool_calls"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|call| {
+                    let function = &call["function"];
+                    (
+                        function["name"].as_str().unwrap_or_default().to_string(),
+                        function["arguments"].as_str().unwrap_or_default().to_string(),
+                    )
+                })
+                .collect(),
+            usage: response.get("usage").filter(|u| u.is_object()).cloned(),
+            finish_reason: choice["finish_reason"].as_str().map(str::to_string),
+        }
+    }
+}
+
+/// Reads a chat completions event stream as it passes through
+#[derive(Default)]
+struct StreamReader {
+    /// Bytes after the last complete line
+    pending: Vec<u8>,
+    completion: Completion,
+    error: Option<String>,
+}
+
+impl StreamReader {
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.read_line(&line);
+        }
+    }
+
+    fn read_line(&mut self, line: &[u8]) {
+        let Ok(line) = std::str::from_utf8(line) else { return };
+        let Some(data) = line.trim_end().strip_prefix("data:") else {
+            return;
+        };
+        // `[DONE]` and anything else that isn't a chunk is skipped
+        let Ok(chunk) = serde_json::from_str::<Value>(data.trim()) else {
+            return;
+        };
+
+        if let Some(message) = chunk["error"]["message"].as_str() {
+            self.error = Some(message.to_string());
+        }
+        let completion = &mut self.completion;
+        if let Some(model) = chunk["model"].as_str() {
+            completion.model = Some(model.to_string());
+        }
+        if chunk["usage"].is_object() {
+            completion.usage = Some(chunk["usage"].clone());
+        }
+
+        let choice = &chunk["choices"][0];
+        if let Some(text) = choice["delta"]["content"].as_str() {
+            completion.text.push_str(text);
+        }
+        for call in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+            let Some(index) = call["index"]
+                .as_u64()
+                .and_then(|i| usize::try_from(i).ok())
+                .filter(|&i| i < MAX_TOOL_CALLS)
+            else {
+                continue;
+            };
+            if completion.tool_calls.len() <= index {
+                completion.tool_calls.resize(index + 1, Default::default());
+            }
+            let (name, arguments) = &mut completion.tool_calls[index];
+            if let Some(part) = call["function"]["name"].as_str() {
+                name.push_str(part);
+            }
+            if let Some(part) = call["function"]["arguments"].as_str() {
+                arguments.push_str(part);
+            }
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            completion.finish_reason = Some(reason.to_string());
+        }
+    }
+}
+
+/// Messages rendered one per line as `role: text`
+fn prompt_text(request: &Value) -> Option<String> {
+    let lines: Vec<String> = request["messages"]
+        .as_array()?
+        .iter()
+        .map(|message| {
+            format!(
+                "{}: {}",
+                message["role"].as_str().unwrap_or("user"),
+                message_text(&message["content"])
+            )
+        })
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Text of message content, whether a string or an array of parts
+fn message_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| part["text"].as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+fn count(value: &Value) -> Option<i32> {
+    value.as_i64().and_then(|n| i32::try_from(n).ok())
+}
+
+/// The API key a client sent, in the upstream's own header or as a bearer
+/// token, which is what OpenAI clients send
+fn api_key<'a>(headers: &'a HeaderMap, key_header: &str) -> Option<&'a str> {
+    headers.get(key_header).and_then(|v| v.to_str().ok()).or_else(|| {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+    })
+}
+
+/// Whether `name` can be an Azure deployment, and so is safe to put in the
+/// upstream URL's path
+fn is_deployment_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+/// Upstream response headers passed back to the client
+fn is_forwarded_response_header(name: &str) -> bool {
+    name == "content-type" || name == "x-request-id" || name.starts_with("openai-") || name.starts_with("x-ratelimit-")
+}
+
+fn build(builder: axum::http::response::Builder, body: Body) -> Response {
+    builder
+        .body(body)
+        .unwrap_or_else(|e| openai_error(StatusCode::INTERNAL_SERVER_ERROR, "proxy_error", &e.to_string()))
+}
+
+/// An error in the shape OpenAI clients expect
+fn openai_error(status: StatusCode, kind: &str, message: &str) -> Response {
+    (status, Json(json!({"error": {"message": message, "type": kind}}))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::PipelineConfig;
+    use crate::db::{LocalPubSub, SpanStore, SqliteStore, Storage};
+
+    /// Answers chat completions and messages requests the way OpenAI and
+    /// Anthropic do
+    async fn mock_upstream() -> String {
+        let router = Router::new()
+            .route(
+                "/chat/completions",
+                post(|Json(request): Json<Value>| async move {
+                    Json(json!({
+                        "model": request["model"],
+                        "choices": [{"message": {"role": "assistant", "content": "Blue"}, "finish_reason": "stop"}],
+                        "usage": {"prompt_tokens": 12, "completion_tokens": 1},
+                    }))
+                }),
+            )
+            .route(
+                "/v1/messages",
+                post(|Json(request): Json<Value>| async move {
+                    Json(json!({
+                        "id": "msg_1",
+                        "model": request["model"],
+                        "content": [{"type": "text", "text": "Red"}],
+                        "stop_reason": "end_turn",
+                        "usage": {"input_tokens": 20, "output_tokens": 2},
+                    }))
+                }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{}", addr)
+    }
+
+    async fn serve_proxy(upstream: ProxyUpstream, upstream_url: String, pipeline: Arc<Pipeline>) -> String {
+        let config = ProxyConfig {
+            upstream,
+            upstream_url: Some(upstream_url),
+            ..ProxyConfig::default()
+        };
+        let router = ProxyServer::new(config, pipeline).unwrap().router();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_proxied_calls_are_recorded() {
+        let store = Arc::new(SqliteStore::open(":memory:").await.unwrap());
+        let storage = Storage {
+            spans: store.clone(),
+            pubsub: Arc::new(LocalPubSub::new()),
+            database: None,
+        };
+        let pipeline_config = PipelineConfig {
+            batch_timeout_ms: 10,
+            enable_redis_streaming: false,
+            ..PipelineConfig::default()
+        };
+        let pipeline = Arc::new(Pipeline::new(pipeline_config, &storage));
+        let running = tokio::spawn({
+            let pipeline = Arc::clone(&pipeline);
+            async move { pipeline.start().await }
+        });
+
+        let upstream = mock_upstream().await;
+        let openai = serve_proxy(ProxyUpstream::OpenAi, upstream.clone(), Arc::clone(&pipeline)).await;
+        let anthropic = serve_proxy(ProxyUpstream::Anthropic, upstream, Arc::clone(&pipeline)).await;
+        let client = reqwest::Client::new();
+        let request = |model: &str| json!({"model": model, "messages": [{"role": "user", "content": "Name a color"}]});
+
+        let response: Value = client
+            .post(format!("{}/v1/chat/completions", openai))
+            .header(TRACE_ID_HEADER, "proxytrace")
+            .json(&request("gpt-4o"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["choices"][0]["message"]["content"], "Blue");
+
+        let response: Value = client
+            .post(format!("{}/v1/chat/completions", anthropic))
+            .header(TRACE_ID_HEADER, "proxytrace")
+            .json(&request("claude-3-5-sonnet-20241022"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["choices"][0]["message"]["content"], "Red");
+        assert_eq!(response["usage"]["prompt_tokens"], 20);
+
+        let mut streamed = request("claude-3-5-sonnet-20241022");
+        streamed["stream"] = json!(true);
+        let response = client
+            .post(format!("{}/v1/chat/completions", anthropic))
+            .json(&streamed)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 400);
+
+        pipeline.shutdown();
+        running.await.unwrap();
+
+        let mut spans = store.get_by_trace_id("proxytrace").await.unwrap();
+        spans.sort_by(|a, b| a.model_provider.cmp(&b.model_provider));
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].model_provider.as_deref(), Some("anthropic"));
+        assert_eq!(spans[0].tokens_in, Some(20));
+        assert_eq!(spans[0].completion_preview.as_deref(), Some("Red"));
+        assert_eq!(spans[1].model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(spans[1].tokens_in, Some(12));
+        assert_eq!(spans[1].tokens_out, Some(1));
+        assert_eq!(spans[1].prompt_preview.as_deref(), Some("user: Name a color"));
+        assert_eq!(spans[1].status, SpanStatus::Ok);
+    }
+
+    #[test]
+    fn test_deployment_names() {
+        assert!(is_deployment_name("gpt-4o_mini.2024"));
+        assert!(!is_deployment_name(""));
+        assert!(!is_deployment_name("../../admin"));
+        assert!(!is_deployment_name("gpt-4o?api-version=1"));
+        assert!(!is_deployment_name("gpt 4o"));
+    }
+
+    #[test]
+    fn test_stream_reader_accumulates_chunks() {
+        let mut reader = StreamReader::default();
+        let stream = concat!(
+            "data: {\"model\":\"gpt-4o-2024-08-06\",\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,\"completion_tokens\":2}}\n\n",
+            "data: [DONE]\n\n",
+        );
+        // Chunks don't line up with events
+        for part in stream.as_bytes().chunks(7) {
+            reader.push(part);
+        }
+
+        let completion = reader.completion;
+        assert_eq!(completion.model.as_deref(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(completion.text, "Hello");
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(completion.usage.unwrap()["prompt_tokens"], 12);
+        assert!(reader.error.is_none());
+    }
+
+    #[test]
+    fn test_stream_reader_joins_tool_call_arguments() {
+        let mut reader = StreamReader::default();
+        reader.push(b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"name\":\"search\",\"arguments\":\"{\\\"q\\\"\"}}]}}]}\n");
+        reader.push(b"data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\":\\\"rust\\\"}\"}}]}}]}\n");
+
+        assert_eq!(
+            reader.completion.tool_calls,
+            vec![("search".to_string(), "{\"q\":\"rust\"}".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_prompt_text() {
+        let request = json!({
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": [{"type": "text", "text": "Hi"}, {"type": "image_url"}]},
+            ]
+        });
+        assert_eq!(prompt_text(&request).as_deref(), Some("system: Be brief.\nuser: Hi"));
+        assert_eq!(prompt_text(&json!({"messages": []})), None);
+    }
+}
@@ -0,0 +1,683 @@
+//! Weekly and monthly cost and usage reports
+//!
+//! A report covers one period of traffic: totals against the period before,
+//! spend per model and service, the daily token trend, the most expensive
+//! traces, the operations with the most errors and how many alerts fired.
+//! It renders as Markdown for chat tools or as a self-contained HTML page
+//! for email. Used by `agenttrace report` and `GET /api/v1/reports/generate`.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::alerting::AlertRepository;
+use crate::db::SpanStore;
+use crate::error::{Error, Result};
+use crate::locale::NumberFormat;
+use crate::models::{
+    AggregateMetric, AggregateRequest, CostMetric, SearchFilter, Severity, TraceSummary,
+};
+
+/// Rows in each ranked section
+const TOP_ROWS: i64 = 10;
+
+/// Length of time a report covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    /// The last 7 days
+    Week,
+    /// The last 30 days
+    Month,
+}
+
+impl ReportPeriod {
+    /// Parse a period name
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "week" | "weekly" => Ok(Self::Week),
+            "month" | "monthly" => Ok(Self::Month),
+            other => Err(Error::validation(format!(
+                "Unknown report period '{}': expected week or month",
+                other
+            ))),
+        }
+    }
+
+    /// Name used in the API's `period` parameter
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// Time covered by one report
+    pub fn duration(&self) -> Duration {
+        match self {
+            Self::Week => Duration::days(7),
+            Self::Month => Duration::days(30),
+        }
+    }
+
+    fn adjective(&self) -> &'static str {
+        match self {
+            Self::Week => "Weekly",
+            Self::Month => "Monthly",
+        }
+    }
+
+}
+
+/// Supported report formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// GitHub-flavoured Markdown with tables
+    Markdown,
+    /// A standalone HTML page with inline styles
+    Html,
+    /// The [`Report`] itself
+    Json,
+}
+
+impl ReportFormat {
+    /// Parse a format name; `md` means Markdown
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "json" => Ok(Self::Json),
+            other => Err(Error::validation(format!(
+                "Unknown report format '{}': expected markdown, html or json",
+                other
+            ))),
+        }
+    }
+
+    /// Name used in the API's `format` parameter
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+
+    /// MIME type of the rendered report
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Markdown => "text/markdown; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+}
+
+/// Headline numbers for one period
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReportTotals {
+    /// Spans recorded
+    pub spans: i64,
+    /// Traces recorded
+    pub traces: i64,
+    /// Input and output tokens
+    pub tokens: i64,
+    /// Spend in USD
+    pub cost_usd: f64,
+    /// Spans with an error status
+    pub error_count: i64,
+    /// Percentage of spans with an error status
+    pub error_rate: f64,
+    /// 95th percentile span duration in milliseconds
+    pub p95_latency_ms: f64,
+}
+
+/// An operation ranked by its error count
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorHotspot {
+    /// Operation name
+    pub operation: String,
+    /// Spans of the operation with an error status
+    pub error_count: i64,
+    /// Spans of the operation
+    pub span_count: i64,
+    /// Percentage of the operation's spans with an error status
+    pub error_rate: f64,
+}
+
+/// Alerts fired in a period, by severity
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct AlertCounts {
+    /// Critical alerts
+    pub critical: i64,
+    /// Warnings
+    pub warning: i64,
+    /// Informational alerts
+    pub info: i64,
+}
+
+impl AlertCounts {
+    /// Alerts of every severity
+    pub fn total(&self) -> i64 {
+        self.critical + self.warning + self.info
+    }
+}
+
+/// A cost and usage report for one period
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Length of time covered
+    pub period: ReportPeriod,
+    /// Start of the period
+    pub since: DateTime<Utc>,
+    /// End of the period
+    pub until: DateTime<Utc>,
+    /// Totals for the period
+    pub totals: ReportTotals,
+    /// Totals for the period of the same length before this one
+    pub previous: ReportTotals,
+    /// Spend per model, most expensive first
+    pub by_model: Vec<CostMetric>,
+    /// Spend per service, most expensive first
+    pub by_service: Vec<CostMetric>,
+    /// Tokens and spend per UTC day, oldest first
+    pub daily: Vec<CostMetric>,
+    /// Most expensive traces started in the period
+    pub top_traces: Vec<TraceSummary>,
+    /// Operations with the most errors
+    pub error_hotspots: Vec<ErrorHotspot>,
+    /// Alerts fired in the period (PostgreSQL storage only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<AlertCounts>,
+}
+
+/// Gather a report for the period ending at `until`
+pub async fn generate(
+    store: &dyn SpanStore,
+    alerts: Option<&AlertRepository>,
+    period: ReportPeriod,
+    until: DateTime<Utc>,
+) -> Result<Report> {
+    let since = until - period.duration();
+    let previous_since = since - period.duration();
+
+    let current = totals(store, since, until).await?;
+    let previous = totals(store, previous_since, since).await?;
+
+    let mut by_model = store.get_cost_by_group(None, "model", since, until).await?;
+    by_model.truncate(TOP_ROWS as usize);
+    let mut by_service = store.get_cost_by_group(None, "service", since, until).await?;
+    by_service.truncate(TOP_ROWS as usize);
+    let daily = store.get_cost_by_group(None, "day", since, until).await?;
+    let top_traces = store.top_traces_by_cost(None, since, until, TOP_ROWS).await?;
+    let error_hotspots = error_hotspots(store, since, until).await?;
+
+    let alerts = match alerts {
+        Some(repo) => {
+            let mut counts = AlertCounts::default();
+            for (severity, count) in repo.count_events_by_severity(since, until).await? {
+                match severity {
+                    Severity::Critical => counts.critical += count,
+                    Severity::Warning => counts.warning += count,
+                    Severity::Info => counts.info += count,
+                }
+            }
+            Some(counts)
+        }
+        None => None,
+    };
+
+    Ok(Report {
+        period,
+        since,
+        until,
+        totals: current,
+        previous,
+        by_model,
+        by_service,
+        daily,
+        top_traces,
+        error_hotspots,
+        alerts,
+    })
+}
+
+async fn totals(
+    store: &dyn SpanStore,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<ReportTotals> {
+    let summary = store.get_metrics_summary(None, None, since, until).await?;
+    Ok(ReportTotals {
+        spans: summary.total_spans,
+        traces: summary.total_traces,
+        tokens: summary.total_tokens,
+        cost_usd: summary.total_cost_usd,
+        error_count: summary.error_count,
+        error_rate: summary.error_rate,
+        p95_latency_ms: summary.p95_latency_ms,
+    })
+}
+
+/// Operations with the most errors, with their share of failed spans
+async fn error_hotspots(
+    store: &dyn SpanStore,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<ErrorHotspot>> {
+    let query = AggregateRequest {
+        metric: AggregateMetric::Count,
+        group_by: Some("operation".to_string()),
+        bucket: None,
+        filters: vec![SearchFilter {
+            field: "status".to_string(),
+            operator: "eq".to_string(),
+            value: serde_json::json!("error"),
+        }],
+        since,
+        until: Some(until),
+        limit: Some(TOP_ROWS),
+    }
+    .compile()?;
+
+    let errors = store.aggregate(&query).await?;
+    if errors.is_empty() {
+        return Ok(Vec::new());
+    }
+    let operations = store.get_cost_by_group(None, "operation", since, until).await?;
+
+    Ok(errors
+        .into_iter()
+        .filter_map(|row| {
+            let operation = row.group?;
+            let error_count = row.value as i64;
+            let span_count = operations
+                .iter()
+                .find(|o| o.group == operation)
+                .map_or(error_count, |o| o.call_count.max(error_count));
+            Some(ErrorHotspot {
+                operation,
+                error_count,
+                span_count,
+                error_rate: if span_count > 0 {
+                    error_count as f64 / span_count as f64 * 100.0
+                } else {
+                    0.0
+                },
+            })
+        })
+        .collect())
+}
+
+/// Render a report in one of the report formats
+pub fn render(report: &Report, format: ReportFormat, numbers: &NumberFormat) -> Result<String> {
+    match format {
+        ReportFormat::Markdown => Ok(render_markdown(report, numbers)),
+        ReportFormat::Html => Ok(render_html(report, numbers)),
+        ReportFormat::Json => {
+            serde_json::to_string_pretty(report).map_err(|e| Error::Serialization(e.to_string()))
+        }
+    }
+}
+
+/// One table of a rendered report
+struct Section {
+    title: &'static str,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    /// Shown instead of an empty table
+    empty: &'static str,
+}
+
+fn headers(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| (*name).to_string()).collect()
+}
+
+fn title(report: &Report) -> String {
+    format!("{} cost & usage report", report.period.adjective())
+}
+
+fn range(report: &Report) -> String {
+    format!(
+        "{} – {} (UTC)",
+        report.since.format("%Y-%m-%d %H:%M"),
+        report.until.format("%Y-%m-%d %H:%M")
+    )
+}
+
+fn sections(report: &Report, numbers: &NumberFormat) -> Vec<Section> {
+    let now = &report.totals;
+    let before = &report.previous;
+    let noun = report.period.as_str();
+    let latency = |ms: f64| format!("{} ms", numbers.decimal(ms, 0));
+
+    let summary = Section {
+        title: "Summary",
+        headers: vec![
+            String::new(),
+            format!("This {}", noun),
+            format!("Previous {}", noun),
+            "Change".to_string(),
+        ],
+        rows: vec![
+            vec![
+                "Spend".to_string(),
+                numbers.currency(now.cost_usd, 2),
+                numbers.currency(before.cost_usd, 2),
+                change(now.cost_usd, before.cost_usd, numbers),
+            ],
+            vec![
+                "Tokens".to_string(),
+                numbers.integer(now.tokens),
+                numbers.integer(before.tokens),
+                change(now.tokens as f64, before.tokens as f64, numbers),
+            ],
+            vec![
+                "Traces".to_string(),
+                numbers.integer(now.traces),
+                numbers.integer(before.traces),
+                change(now.traces as f64, before.traces as f64, numbers),
+            ],
+            vec![
+                "Spans".to_string(),
+                numbers.integer(now.spans),
+                numbers.integer(before.spans),
+                change(now.spans as f64, before.spans as f64, numbers),
+            ],
+            vec![
+                "Error rate".to_string(),
+                numbers.percent(now.error_rate, 2),
+                numbers.percent(before.error_rate, 2),
+                format!("{} pts", signed(now.error_rate - before.error_rate, 2, numbers)),
+            ],
+            vec![
+                "p95 latency".to_string(),
+                latency(now.p95_latency_ms),
+                latency(before.p95_latency_ms),
+                change(now.p95_latency_ms, before.p95_latency_ms, numbers),
+            ],
+        ],
+        empty: "",
+    };
+
+    let spend = |title, dimension: &str, costs: &[CostMetric]| Section {
+        title,
+        headers: headers(&[dimension, "Spend", "Share", "Tokens", "Calls"]),
+        rows: costs
+            .iter()
+            .map(|c| {
+                vec![
+                    c.group.clone(),
+                    numbers.currency(c.total_cost_usd, 2),
+                    numbers.percent(share(c.total_cost_usd, now.cost_usd), 1),
+                    numbers.integer(c.total_tokens),
+                    numbers.integer(c.call_count),
+                ]
+            })
+            .collect(),
+        empty: "No spend recorded.",
+    };
+
+    let peak = report.daily.iter().map(|d| d.total_tokens).max().unwrap_or(0);
+    let daily = Section {
+        title: "Daily tokens",
+        headers: headers(&["Day (UTC)", "Tokens", "Spend", ""]),
+        rows: report
+            .daily
+            .iter()
+            .map(|d| {
+                vec![
+                    d.group.clone(),
+                    numbers.integer(d.total_tokens),
+                    numbers.currency(d.total_cost_usd, 2),
+                    bar(d.total_tokens, peak),
+                ]
+            })
+            .collect(),
+        empty: "No tokens recorded.",
+    };
+
+    let traces = Section {
+        title: "Most expensive traces",
+        headers: headers(&["Trace", "Operation", "Service", "Spend", "Tokens", "Duration"]),
+        rows: report
+            .top_traces
+            .iter()
+            .map(|t| {
+                vec![
+                    t.trace_id.clone(),
+                    t.root_operation.clone(),
+                    t.service_name.clone(),
+                    numbers.currency(t.total_cost_usd, 4),
+                    numbers.integer(t.total_tokens),
+                    t.duration_ms.map(latency).unwrap_or_else(|| "-".to_string()),
+                ]
+            })
+            .collect(),
+        empty: "No traces recorded.",
+    };
+
+    let errors = Section {
+        title: "Error hotspots",
+        headers: headers(&["Operation", "Errors", "Spans", "Error rate"]),
+        rows: report
+            .error_hotspots
+            .iter()
+            .map(|h| {
+                vec![
+                    h.operation.clone(),
+                    numbers.integer(h.error_count),
+                    numbers.integer(h.span_count),
+                    numbers.percent(h.error_rate, 1),
+                ]
+            })
+            .collect(),
+        empty: "No errors recorded.",
+    };
+
+    let mut sections = vec![
+        summary,
+        spend("Spend by model", "Model", &report.by_model),
+        spend("Spend by service", "Service", &report.by_service),
+        daily,
+        traces,
+        errors,
+    ];
+
+    if let Some(alerts) = report.alerts {
+        sections.push(Section {
+            title: "Alerts",
+            headers: headers(&["Severity", "Fired"]),
+            rows: [
+                ("Critical", alerts.critical),
+                ("Warning", alerts.warning),
+                ("Info", alerts.info),
+                ("Total", alerts.total()),
+            ]
+            .iter()
+            .map(|(severity, count)| vec![(*severity).to_string(), numbers.integer(*count)])
+            .collect(),
+            empty: "",
+        });
+    }
+
+    sections
+}
+
+/// Percentage change, or `-` when there's nothing to compare with
+fn change(current: f64, previous: f64, numbers: &NumberFormat) -> String {
+    if previous == 0.0 {
+        return "-".to_string();
+    }
+    format!("{}%", signed((current - previous) / previous * 100.0, 1, numbers))
+}
+
+fn signed(value: f64, places: usize, numbers: &NumberFormat) -> String {
+    let formatted = numbers.decimal(value, places);
+    if formatted.starts_with('-') {
+        formatted
+    } else {
+        format!("+{}", formatted)
+    }
+}
+
+fn share(part: f64, total: f64) -> f64 {
+    if total > 0.0 {
+        part / total * 100.0
+    } else {
+        0.0
+    }
+}
+
+/// A bar of up to 20 blocks, scaled to the peak value
+fn bar(value: i64, peak: i64) -> String {
+    if peak <= 0 {
+        return String::new();
+    }
+    let width = (value as f64 / peak as f64 * 20.0).round() as usize;
+    "█".repeat(width)
+}
+
+/// Render a report as GitHub-flavoured Markdown
+pub fn render_markdown(report: &Report, numbers: &NumberFormat) -> String {
+    let mut out = format!("# {}\n\n_{}_\n", title(report), range(report));
+
+    for section in sections(report, numbers) {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str(&format!("{}\n", section.empty));
+            continue;
+        }
+
+        let cells = |row: &[String]| {
+            let cells: Vec<String> = row.iter().map(|c| c.replace('|', "\\|")).collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        out.push_str(&cells(&section.headers));
+        // First column left-aligned, numbers right-aligned
+        let align: Vec<&str> = (0..section.headers.len())
+            .map(|i| if i == 0 { ":---" } else { "---:" })
+            .collect();
+        out.push_str(&format!("| {} |\n", align.join(" | ")));
+        for row in &section.rows {
+            out.push_str(&cells(row));
+        }
+    }
+
+    out
+}
+
+/// Render a report as a standalone HTML page
+///
+/// Styles are inline so the page survives email clients that strip
+/// `<style>` blocks.
+pub fn render_html(report: &Report, numbers: &NumberFormat) -> String {
+    const CELL: &str = "padding:4px 10px;border-bottom:1px solid #e5e7eb";
+
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+         <body style=\"font-family:-apple-system,Segoe UI,Helvetica,Arial,sans-serif;color:#111827\">\n\
+         <h1>{title}</h1>\n<p style=\"color:#6b7280\">{range}</p>\n",
+        title = escape_html(&title(report)),
+        range = escape_html(&range(report)),
+    );
+
+    for section in sections(report, numbers) {
+        out.push_str(&format!("<h2>{}</h2>\n", escape_html(section.title)));
+        if section.rows.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", escape_html(section.empty)));
+            continue;
+        }
+
+        let row = |cells: &[String], tag: &str| {
+            let cells: String = cells
+                .iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    let align = if i == 0 { "left" } else { "right" };
+                    format!("<{tag} style=\"{CELL};text-align:{align}\">{}</{tag}>", escape_html(cell))
+                })
+                .collect();
+            format!("<tr>{}</tr>\n", cells)
+        };
+        out.push_str("<table style=\"border-collapse:collapse\">\n");
+        out.push_str(&row(&section.headers, "th"));
+        for cells in &section.rows {
+            out.push_str(&row(cells, "td"));
+        }
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{SpanGenerator, SqliteStore};
+
+    async fn seeded_report() -> Report {
+        let store = SqliteStore::open(":memory:").await.unwrap();
+        let mut generator = SpanGenerator::new(Some(5));
+        for _ in 0..20 {
+            store.insert_batch(&generator.next_trace()).await.unwrap();
+        }
+        generate(&store, None, ReportPeriod::Week, Utc::now() + Duration::minutes(1))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_report_totals_and_rankings() {
+        let report = seeded_report().await;
+
+        assert_eq!(report.totals.traces, 20);
+        assert_eq!(report.previous.spans, 0);
+        assert!(report.alerts.is_none());
+
+        let model_spend: f64 = report.by_model.iter().map(|m| m.total_cost_usd).sum();
+        assert!((model_spend - report.totals.cost_usd).abs() < 1e-6);
+        assert!(report
+            .top_traces
+            .windows(2)
+            .all(|pair| pair[0].total_cost_usd >= pair[1].total_cost_usd));
+        assert!(report.error_hotspots.iter().all(|h| h.error_count <= h.span_count));
+    }
+
+    #[tokio::test]
+    async fn test_render_markdown_and_html() {
+        let mut report = seeded_report().await;
+        report.top_traces[0].root_operation = "plan <draft> | review".to_string();
+        let numbers = NumberFormat::default();
+
+        let markdown = render_markdown(&report, &numbers);
+        assert!(markdown.starts_with("# Weekly cost & usage report"));
+        assert!(markdown.contains("## Spend by model"));
+        assert!(markdown.contains("| Spend |"));
+        assert!(markdown.contains("plan <draft> \\| review"));
+        assert!(!markdown.contains("## Alerts"));
+
+        report.alerts = Some(AlertCounts { critical: 1, warning: 2, info: 0 });
+        let html = render_html(&report, &numbers);
+        assert!(html.contains("<h2>Alerts</h2>"));
+        assert!(html.contains("plan &lt;draft&gt; | review"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_change_and_periods() {
+        let numbers = NumberFormat::default();
+        assert_eq!(change(150.0, 100.0, &numbers), "+50.0%");
+        assert_eq!(change(50.0, 100.0, &numbers), "-50.0%");
+        assert_eq!(change(50.0, 0.0, &numbers), "-");
+        assert_eq!(ReportPeriod::parse("monthly").unwrap(), ReportPeriod::Month);
+        assert!(ReportFormat::parse("pdf").is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Service level objectives
+//!
+//! An SLO sets the share of spans that should be good over a rolling
+//! window, e.g. 99% of `support-bot` runs succeed over 30 days, or 95% of
+//! them finish within 30 seconds. The spans allowed to be bad, 1% and 5%
+//! here, are the error budget.
+//!
+//! Rather than alerting on a single error rate, SLOs alert on how fast the
+//! budget is burning, checked over a long and a short window as described
+//! in the Google SRE workbook: by default, burning 14.4 times faster than
+//! sustainable over both the last hour and the last five minutes pages,
+//! as does 6 times over six hours and thirty minutes, while 1 times over
+//! three days and six hours raises a warning. Each alert is notified once
+//! when it starts firing and can fire again once it has cleared; incident
+//! tools are told when it clears, including when its SLO is disabled.
+//! Failed sends are queued with the alert notification retries.
+
+mod repository;
+
+pub use repository::{alert_windows, SloRepository};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{debug, info, warn};
+
+use crate::alerting::{AlertRepository, Notification, NotificationResult, NotificationSender, RetryPolicy};
+use crate::config::SlosConfig;
+use crate::error::Result;
+use crate::models::{
+    BurnRateAlert, NotificationRecord, RetryMessage, Slo, SloBurnAlert, SloResolution,
+};
+
+/// Checks SLOs' burn rates and notifies alerts as they start firing
+pub struct SloEvaluator {
+    repo: SloRepository,
+    notifier: NotificationSender,
+    retries: Option<(AlertRepository, RetryPolicy)>,
+    interval: Duration,
+}
+
+impl SloEvaluator {
+    /// Create an evaluator checking SLOs at the configured interval
+    pub fn new(repo: SloRepository, config: &SlosConfig) -> Self {
+        Self {
+            repo,
+            notifier: NotificationSender::new(),
+            retries: None,
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+        }
+    }
+
+    /// Send notifications with this sender
+    pub fn with_notifier(mut self, notifier: NotificationSender) -> Self {
+        self.notifier = notifier;
+        self
+    }
+
+    /// Queue failed notifications in this repository's retries, with this
+    /// policy
+    pub fn with_retries(mut self, repo: AlertRepository, policy: RetryPolicy) -> Self {
+        self.retries = Some((repo, policy));
+        self
+    }
+
+    /// Check SLOs until the task is dropped
+    pub async fn start(&self) {
+        info!(interval_secs = self.interval.as_secs(), "Starting SLO evaluator");
+
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.evaluate_all().await {
+                warn!("Failed to evaluate SLOs: {}", e);
+            }
+        }
+    }
+
+    /// Check every enabled SLO once, returning how many alerts started
+    /// firing
+    ///
+    /// Alerts of disabled SLOs, and alerts removed from an SLO, are closed.
+    pub async fn evaluate_all(&self) -> Result<usize> {
+        let now = Utc::now();
+        let mut fired = 0;
+
+        for slo in self.repo.list().await? {
+            match self.repo.resolve_unchecked_alerts(&slo, now).await {
+                Ok(resolutions) => {
+                    for resolution in resolutions {
+                        info!(slo = slo.name, "Closed burn-rate alert the SLO no longer checks");
+                        self.notify_resolution(&slo, &resolution).await?;
+                    }
+                }
+                Err(e) => warn!(slo = slo.name, "Failed to close unchecked SLO alerts: {}", e),
+            }
+
+            if !slo.enabled || slo.burn_rate_alerts.is_empty() {
+                continue;
+            }
+            match self.evaluate(&slo, now).await {
+                Ok(count) => fired += count,
+                Err(e) => warn!(slo = slo.name, "Failed to evaluate SLO: {}", e),
+            }
+        }
+
+        Ok(fired)
+    }
+
+    /// Check one SLO's burn-rate alerts, notifying those that started
+    /// firing and closing those that cleared
+    async fn evaluate(&self, slo: &Slo, now: DateTime<Utc>) -> Result<usize> {
+        let rates: HashMap<u32, f64> = self
+            .repo
+            .burn_rates(slo, &alert_windows(&slo.burn_rate_alerts), now)
+            .await?
+            .into_iter()
+            .map(|rate| (rate.window_minutes, rate.burn_rate))
+            .collect();
+
+        let mut fired = 0;
+        for alert in &slo.burn_rate_alerts {
+            let Some((long, short)) = breach(alert, &rates) else {
+                if let Some(resolution) = self.repo.resolve_burn_alert(slo, alert, now).await? {
+                    info!(
+                        slo = slo.name,
+                        long_window_minutes = alert.long_window_minutes,
+                        "SLO burn rate back within threshold"
+                    );
+                    self.notify_resolution(slo, &resolution).await?;
+                }
+                continue;
+            };
+
+            let burn = SloBurnAlert::new(slo, alert, long, short, now);
+            if !self.repo.claim_burn_alert(&burn).await? {
+                debug!(slo = slo.name, long_burn_rate = long, "SLO burn-rate alert already firing");
+                continue;
+            }
+
+            info!(
+                slo = slo.name,
+                long_window_minutes = alert.long_window_minutes,
+                long_burn_rate = long,
+                short_burn_rate = short,
+                "SLO burn-rate alert firing"
+            );
+
+            let results = self
+                .notifier
+                .send_all(&slo.notification_channels, &Notification::slo_alert(&burn))
+                .await;
+            self.queue_retries(RetryMessage::SloAlert(burn.clone()), &results).await;
+            let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+            self.repo.record_notifications(&burn, &records).await?;
+            fired += 1;
+        }
+
+        Ok(fired)
+    }
+
+    /// Tell an SLO's incident tools that one of its alerts cleared
+    async fn notify_resolution(&self, slo: &Slo, resolution: &SloResolution) -> Result<()> {
+        let results = self
+            .notifier
+            .send_all(&slo.notification_channels, &Notification::slo_resolution(resolution))
+            .await;
+        if results.is_empty() {
+            return Ok(());
+        }
+
+        self.queue_retries(RetryMessage::SloResolution(resolution.clone()), &results).await;
+        let records: Vec<NotificationRecord> = results.into_iter().map(Into::into).collect();
+        self.repo.append_notifications(resolution.alert_id, &records).await
+    }
+
+    /// Queue failed notifications for retry; the first attempt is recorded
+    /// either way, so failing to queue is only logged
+    async fn queue_retries(&self, message: RetryMessage, results: &[NotificationResult]) {
+        let Some((repo, policy)) = &self.retries else {
+            return;
+        };
+        if let Err(e) = policy.queue_failures(repo, &message, results).await {
+            warn!(error = %e, "Failed to queue SLO notification retries");
+        }
+    }
+}
+
+/// Burn rates over an alert's long and short windows, if both are at or
+/// above its threshold
+fn breach(alert: &BurnRateAlert, rates: &HashMap<u32, f64>) -> Option<(f64, f64)> {
+    let long = rates.get(&alert.long_window_minutes).copied().unwrap_or_default();
+    let short = rates.get(&alert.short_window_minutes).copied().unwrap_or_default();
+
+    (long >= alert.burn_rate && short >= alert.burn_rate).then_some((long, short))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::default_burn_rate_alerts;
+
+    #[test]
+    fn test_alert_fires_only_when_both_windows_burn() {
+        let page = &default_burn_rate_alerts()[0];
+        let rates = |long: f64, short: f64| {
+            HashMap::from([(page.long_window_minutes, long), (page.short_window_minutes, short)])
+        };
+
+        assert_eq!(breach(page, &rates(15.0, 20.0)), Some((15.0, 20.0)));
+        assert_eq!(breach(page, &rates(14.4, 14.4)), Some((14.4, 14.4)));
+        // A spike that already stopped, or one that hasn't lasted
+        assert_eq!(breach(page, &rates(15.0, 2.0)), None);
+        assert_eq!(breach(page, &rates(3.0, 20.0)), None);
+        // No spans in a window burns nothing
+        assert_eq!(breach(page, &HashMap::new()), None);
+    }
+}
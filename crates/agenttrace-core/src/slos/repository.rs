@@ -0,0 +1,448 @@
+//! SLO repository
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{
+    BurnRate, BurnRateAlert, NotificationChannel, NotificationRecord, Slo, SloBurnAlert, SloCounts,
+    SloIndicator, SloInput, SloResolution, SloStatus,
+};
+
+const SLO_COLUMNS: &str = "id, name, description, service_name, operation_name, root_spans_only, \
+     indicator, latency_threshold_ms, objective_percent, window_days, burn_rate_alerts, \
+     notification_channels, enabled, created_at, updated_at";
+
+/// Repository for SLOs and their burn-rate alerts
+#[derive(Clone)]
+pub struct SloRepository {
+    pool: PgPool,
+    span_retention_days: Option<u32>,
+}
+
+impl SloRepository {
+    /// Create a new SLO repository
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            span_retention_days: None,
+        }
+    }
+
+    /// Reject SLO windows longer than spans are kept, since SLOs are
+    /// counted from raw spans
+    pub fn with_span_retention(mut self, days: Option<u32>) -> Self {
+        self.span_retention_days = days;
+        self
+    }
+
+    /// List SLOs by name
+    pub async fn list(&self) -> Result<Vec<Slo>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM slos ORDER BY name", SLO_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter().map(row_to_slo).collect()
+    }
+
+    /// Get an SLO by ID
+    pub async fn get(&self, id: Uuid) -> Result<Option<Slo>> {
+        let row = sqlx::query(&format!("SELECT {} FROM slos WHERE id = $1", SLO_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.as_ref().map(row_to_slo).transpose()
+    }
+
+    /// Create an SLO
+    pub async fn create(&self, input: SloInput) -> Result<Slo> {
+        input.validate()?;
+        input.validate_retention(self.span_retention_days)?;
+
+        let now = Utc::now();
+        let slo = Slo {
+            id: Uuid::new_v4(),
+            name: input.name.trim().to_string(),
+            description: input.description,
+            service_name: input.service_name,
+            operation_name: input.operation_name,
+            root_spans_only: input.root_spans_only,
+            indicator: input.indicator,
+            latency_threshold_ms: input.latency_threshold_ms,
+            objective_percent: input.objective_percent,
+            window_days: input.window_days,
+            burn_rate_alerts: input.burn_rate_alerts,
+            notification_channels: input.notification_channels,
+            enabled: input.enabled,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO slos (
+                id, name, description, service_name, operation_name, root_spans_only,
+                indicator, latency_threshold_ms, objective_percent, window_days,
+                burn_rate_alerts, notification_channels, enabled, created_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+            "#,
+        )
+        .bind(slo.id)
+        .bind(&slo.name)
+        .bind(&slo.description)
+        .bind(&slo.service_name)
+        .bind(&slo.operation_name)
+        .bind(slo.root_spans_only)
+        .bind(slo.indicator.as_str())
+        .bind(slo.latency_threshold_ms)
+        .bind(slo.objective_percent)
+        .bind(i32::try_from(slo.window_days).unwrap_or(i32::MAX))
+        .bind(serde_json::to_value(&slo.burn_rate_alerts)?)
+        .bind(serde_json::to_value(&slo.notification_channels)?)
+        .bind(slo.enabled)
+        .bind(slo.created_at)
+        .bind(slo.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &slo.name))?;
+
+        Ok(slo)
+    }
+
+    /// Replace an SLO's settings
+    ///
+    /// Returns None if the SLO doesn't exist. Firing burn-rate alerts stay
+    /// open until they clear, or the evaluator's next check if the update
+    /// disabled the SLO or removed the alert.
+    pub async fn update(&self, id: Uuid, input: SloInput) -> Result<Option<Slo>> {
+        input.validate()?;
+        input.validate_retention(self.span_retention_days)?;
+        let name = input.name.trim().to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE slos SET
+                name = $2,
+                description = $3,
+                service_name = $4,
+                operation_name = $5,
+                root_spans_only = $6,
+                indicator = $7,
+                latency_threshold_ms = $8,
+                objective_percent = $9,
+                window_days = $10,
+                burn_rate_alerts = $11,
+                notification_channels = $12,
+                enabled = $13,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&name)
+        .bind(&input.description)
+        .bind(&input.service_name)
+        .bind(&input.operation_name)
+        .bind(input.root_spans_only)
+        .bind(input.indicator.as_str())
+        .bind(input.latency_threshold_ms)
+        .bind(input.objective_percent)
+        .bind(i32::try_from(input.window_days).unwrap_or(i32::MAX))
+        .bind(serde_json::to_value(&input.burn_rate_alerts)?)
+        .bind(serde_json::to_value(&input.notification_channels)?)
+        .bind(input.enabled)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &name))?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    /// Delete an SLO and its alert history
+    pub async fn delete(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM slos WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Good and total spans an SLO covers in `[since, until)`
+    ///
+    /// Streaming spans still in progress are not counted, and latency SLOs
+    /// only count spans that have a duration.
+    pub async fn counts(&self, slo: &Slo, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<SloCounts> {
+        let row = sqlx::query(
+            r#"
+            SELECT
+                COUNT(*) AS total,
+                COUNT(*) FILTER (
+                    WHERE CASE WHEN $5 = 'latency' THEN duration_ms <= $6 ELSE status <> 'error' END
+                ) AS good
+            FROM spans
+            WHERE started_at >= $1 AND started_at < $2
+              AND status <> 'in_progress'
+              AND ($3::text IS NULL OR service_name = $3)
+              AND ($4::text IS NULL OR operation_name = $4)
+              AND (NOT $7 OR parent_span_id IS NULL)
+              AND ($5 <> 'latency' OR duration_ms IS NOT NULL)
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .bind(&slo.service_name)
+        .bind(&slo.operation_name)
+        .bind(slo.indicator.as_str())
+        .bind(slo.latency_threshold_ms.unwrap_or(0.0))
+        .bind(slo.root_spans_only)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(SloCounts {
+            total: row.try_get("total")?,
+            good: row.try_get("good")?,
+        })
+    }
+
+    /// Burn rates over windows of `window_minutes` ending at `now`
+    pub async fn burn_rates(&self, slo: &Slo, window_minutes: &[u32], now: DateTime<Utc>) -> Result<Vec<BurnRate>> {
+        let mut rates = Vec::with_capacity(window_minutes.len());
+        for minutes in window_minutes {
+            let counts = self
+                .counts(slo, now - Duration::minutes(i64::from(*minutes)), now)
+                .await?;
+            rates.push(BurnRate::new(slo, *minutes, counts));
+        }
+
+        Ok(rates)
+    }
+
+    /// An SLO's performance over its window ending at `now`, with burn
+    /// rates over each of its alerts' windows
+    pub async fn status(&self, slo: Slo, now: DateTime<Utc>) -> Result<SloStatus> {
+        let counts = self.counts(&slo, now - slo.window(), now).await?;
+        let burn_rates = self.burn_rates(&slo, &alert_windows(&slo.burn_rate_alerts), now).await?;
+
+        Ok(SloStatus::new(slo, counts, burn_rates, now))
+    }
+
+    /// Record that a burn-rate alert started firing
+    ///
+    /// Returns false if it is already firing, so each is notified once
+    /// until it clears even with several collectors running.
+    pub async fn claim_burn_alert(&self, alert: &SloBurnAlert) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO slo_burn_alerts (
+                id, slo_id, long_window_minutes, short_window_minutes, burn_rate, triggered_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (slo_id, long_window_minutes, short_window_minutes)
+                WHERE resolved_at IS NULL DO NOTHING
+            "#,
+        )
+        .bind(alert.id)
+        .bind(alert.slo_id)
+        .bind(i32::try_from(alert.long_window_minutes).unwrap_or(i32::MAX))
+        .bind(i32::try_from(alert.short_window_minutes).unwrap_or(i32::MAX))
+        .bind(alert.long_burn_rate)
+        .bind(alert.triggered_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Close a firing burn-rate alert, returning its resolution if one
+    /// was open
+    pub async fn resolve_burn_alert(
+        &self,
+        slo: &Slo,
+        alert: &BurnRateAlert,
+        now: DateTime<Utc>,
+    ) -> Result<Option<SloResolution>> {
+        let row = sqlx::query(
+            r#"
+            UPDATE slo_burn_alerts SET resolved_at = $4
+            WHERE slo_id = $1 AND long_window_minutes = $2 AND short_window_minutes = $3
+              AND resolved_at IS NULL
+            RETURNING id, long_window_minutes, short_window_minutes, triggered_at
+            "#,
+        )
+        .bind(slo.id)
+        .bind(i32::try_from(alert.long_window_minutes).unwrap_or(i32::MAX))
+        .bind(i32::try_from(alert.short_window_minutes).unwrap_or(i32::MAX))
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| row_to_resolution(&row, slo, now)).transpose()
+    }
+
+    /// Close an SLO's firing alerts that it no longer checks, because it
+    /// was disabled or the alert was removed, returning their resolutions
+    pub async fn resolve_unchecked_alerts(&self, slo: &Slo, now: DateTime<Utc>) -> Result<Vec<SloResolution>> {
+        let checked: &[BurnRateAlert] = if slo.enabled { &slo.burn_rate_alerts } else { &[] };
+        let longs: Vec<i32> = checked
+            .iter()
+            .map(|a| i32::try_from(a.long_window_minutes).unwrap_or(i32::MAX))
+            .collect();
+        let shorts: Vec<i32> = checked
+            .iter()
+            .map(|a| i32::try_from(a.short_window_minutes).unwrap_or(i32::MAX))
+            .collect();
+
+        let rows = sqlx::query(
+            r#"
+            UPDATE slo_burn_alerts SET resolved_at = $4
+            WHERE slo_id = $1 AND resolved_at IS NULL
+              AND (long_window_minutes, short_window_minutes) NOT IN (
+                  SELECT * FROM UNNEST($2::int[], $3::int[])
+              )
+            RETURNING id, long_window_minutes, short_window_minutes, triggered_at
+            "#,
+        )
+        .bind(slo.id)
+        .bind(&longs)
+        .bind(&shorts)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(|row| row_to_resolution(row, slo, now)).collect()
+    }
+
+    /// The firing alert over an SLO's window pair, if there is one
+    pub async fn firing_alert(
+        &self,
+        slo_id: Uuid,
+        long_window_minutes: u32,
+        short_window_minutes: u32,
+    ) -> Result<Option<Uuid>> {
+        let id = sqlx::query_scalar(
+            r#"
+            SELECT id FROM slo_burn_alerts
+            WHERE slo_id = $1 AND long_window_minutes = $2 AND short_window_minutes = $3
+              AND resolved_at IS NULL
+            "#,
+        )
+        .bind(slo_id)
+        .bind(i32::try_from(long_window_minutes).unwrap_or(i32::MAX))
+        .bind(i32::try_from(short_window_minutes).unwrap_or(i32::MAX))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Record the notifications sent for a burn-rate alert
+    pub async fn record_notifications(&self, alert: &SloBurnAlert, records: &[NotificationRecord]) -> Result<()> {
+        sqlx::query("UPDATE slo_burn_alerts SET notifications_sent = $2 WHERE id = $1")
+            .bind(alert.id)
+            .bind(serde_json::to_value(records)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add notifications sent later for a burn-rate alert, like retries
+    /// and its resolution
+    pub async fn append_notifications(&self, alert_id: Uuid, records: &[NotificationRecord]) -> Result<()> {
+        sqlx::query("UPDATE slo_burn_alerts SET notifications_sent = notifications_sent || $2 WHERE id = $1")
+            .bind(alert_id)
+            .bind(serde_json::to_value(records)?)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Every window the alerts look at, shortest first, without duplicates
+pub fn alert_windows(alerts: &[BurnRateAlert]) -> Vec<u32> {
+    let mut windows: Vec<u32> = alerts
+        .iter()
+        .flat_map(|a| [a.short_window_minutes, a.long_window_minutes])
+        .collect();
+    windows.sort_unstable();
+    windows.dedup();
+    windows
+}
+
+/// Report a duplicate name as a validation error
+fn name_conflict(e: sqlx::Error, name: &str) -> Error {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            Error::validation(format!("An SLO named '{}' already exists", name))
+        }
+        _ => e.into(),
+    }
+}
+
+fn row_to_resolution(row: &PgRow, slo: &Slo, resolved_at: DateTime<Utc>) -> Result<SloResolution> {
+    let long: i32 = row.try_get("long_window_minutes")?;
+    let short: i32 = row.try_get("short_window_minutes")?;
+
+    Ok(SloResolution::new(
+        slo,
+        row.try_get("id")?,
+        (u32::try_from(long).unwrap_or(0), u32::try_from(short).unwrap_or(0)),
+        row.try_get("triggered_at")?,
+        resolved_at,
+    ))
+}
+
+fn row_to_slo(row: &PgRow) -> Result<Slo> {
+    let indicator: String = row.try_get("indicator")?;
+    let window_days: i32 = row.try_get("window_days")?;
+    let alerts: serde_json::Value = row.try_get("burn_rate_alerts")?;
+    let channels: serde_json::Value = row.try_get("notification_channels")?;
+    let notification_channels: Vec<NotificationChannel> = serde_json::from_value(channels).unwrap_or_default();
+
+    Ok(Slo {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        service_name: row.try_get("service_name")?,
+        operation_name: row.try_get("operation_name")?,
+        root_spans_only: row.try_get("root_spans_only")?,
+        indicator: SloIndicator::parse(&indicator)?,
+        latency_threshold_ms: row.try_get("latency_threshold_ms")?,
+        objective_percent: row.try_get("objective_percent")?,
+        window_days: u32::try_from(window_days).unwrap_or(1),
+        burn_rate_alerts: serde_json::from_value(alerts).unwrap_or_default(),
+        notification_channels,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::default_burn_rate_alerts;
+
+    #[test]
+    fn test_alert_windows_are_deduplicated() {
+        let mut alerts = default_burn_rate_alerts();
+        alerts.push(BurnRateAlert {
+            long_window_minutes: 60,
+            short_window_minutes: 5,
+            burn_rate: 20.0,
+            ..alerts[0].clone()
+        });
+
+        assert_eq!(alert_windows(&alerts), vec![5, 30, 60, 360, 4320]);
+        assert!(alert_windows(&[]).is_empty());
+    }
+}
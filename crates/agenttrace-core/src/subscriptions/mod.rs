@@ -0,0 +1,453 @@
+//! Webhook subscriptions
+//!
+//! A subscription is a span search query (e.g. `status:error min_cost:1`)
+//! and a URL. The pipeline checks every processed span against the enabled
+//! subscriptions; the first matching span of a trace is posted to the
+//! subscription's URL as a JSON [`WebhookPayload`], so downstream automation
+//! can react to the traces it cares about without polling.
+//!
+//! Each request carries an `X-AgentTrace-Signature: sha256=<hex>` header: an
+//! HMAC-SHA256, keyed with the subscription's secret, of
+//! `<X-AgentTrace-Timestamp>.<body>`. Failed deliveries are retried with
+//! backoff, and every delivery's outcome is recorded for
+//! `GET /api/v1/subscriptions/{id}/deliveries`; only the latest
+//! [`DELIVERY_HISTORY`] deliveries of each subscription are kept.
+//!
+//! Subscription URLs must resolve to public addresses, so subscriptions
+//! can't be used to reach services on the server's network.
+
+mod repository;
+
+pub use repository::{SubscriptionRepository, DELIVERY_HISTORY};
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use parking_lot::{Mutex, RwLock};
+use reqwest::Client;
+use sha2::Sha256;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::models::{
+    Span, SpanQuery, WebhookDelivery, WebhookPayload, WebhookSubscription, WebhookSubscriptionInput,
+};
+
+/// Payloads waiting for delivery before new matches are dropped
+const QUEUE_CAPACITY: usize = 1_000;
+
+/// Deliveries in flight at once
+const MAX_CONCURRENT_DELIVERIES: usize = 8;
+
+/// Requests made per payload before giving up
+const MAX_ATTEMPTS: i32 = 3;
+
+/// Timeout for a single request
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+
+/// (subscription, trace) pairs remembered so a trace is delivered only once
+const RECENT_MATCHES: usize = 10_000;
+
+/// Event name sent with every payload
+const MATCHED_EVENT: &str = "trace.matched";
+
+struct Delivery {
+    subscription: WebhookSubscription,
+    payload: WebhookPayload,
+}
+
+/// Recently delivered (subscription, trace) pairs, oldest dropped first
+#[derive(Default)]
+struct RecentMatches {
+    seen: HashSet<(Uuid, String)>,
+    order: VecDeque<(Uuid, String)>,
+}
+
+impl RecentMatches {
+    /// Remember a pair, returning false if it was already remembered
+    fn insert(&mut self, subscription_id: Uuid, trace_id: &str) -> bool {
+        let key = (subscription_id, trace_id.to_string());
+        if !self.seen.insert(key.clone()) {
+            return false;
+        }
+
+        self.order.push_back(key);
+        if self.order.len() > RECENT_MATCHES {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// In-memory cache of subscriptions, backed by the database, with the queue
+/// their payloads are delivered from
+#[derive(Clone)]
+pub struct SubscriptionRegistry {
+    repo: SubscriptionRepository,
+    subscriptions: Arc<RwLock<Vec<(WebhookSubscription, SpanQuery)>>>,
+    recent: Arc<Mutex<RecentMatches>>,
+    queue: mpsc::Sender<Delivery>,
+    receiver: Arc<Mutex<Option<mpsc::Receiver<Delivery>>>>,
+    client: Client,
+}
+
+impl SubscriptionRegistry {
+    /// Create an empty registry; call [`reload`](Self::reload) to load
+    /// subscriptions and [`start`](Self::start) to deliver payloads
+    pub fn new(repo: SubscriptionRepository) -> Self {
+        let (queue, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            repo,
+            subscriptions: Arc::new(RwLock::new(Vec::new())),
+            recent: Arc::new(Mutex::new(RecentMatches::default())),
+            queue,
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+            client,
+        }
+    }
+
+    /// Reload all subscriptions from the database, returning how many were
+    /// loaded
+    ///
+    /// A stored filter that no longer parses is logged and skipped.
+    pub async fn reload(&self) -> Result<usize> {
+        let loaded: Vec<(WebhookSubscription, SpanQuery)> = self
+            .repo
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|s| match SpanQuery::parse(&s.filter) {
+                Ok(query) => Some((s, query)),
+                Err(e) => {
+                    warn!("Skipping webhook subscription {}: {}", s.name, e);
+                    None
+                }
+            })
+            .collect();
+        let count = loaded.len();
+
+        *self.subscriptions.write() = loaded;
+        Ok(count)
+    }
+
+    /// List subscriptions, ordered by name
+    pub fn list(&self) -> Vec<WebhookSubscription> {
+        self.subscriptions.read().iter().map(|(s, _)| s.clone()).collect()
+    }
+
+    /// Get a subscription by ID
+    pub fn get(&self, id: Uuid) -> Option<WebhookSubscription> {
+        self.subscriptions
+            .read()
+            .iter()
+            .find(|(s, _)| s.id == id)
+            .map(|(s, _)| s.clone())
+    }
+
+    /// Create a subscription
+    pub async fn create(&self, input: WebhookSubscriptionInput) -> Result<WebhookSubscription> {
+        crate::net::ensure_public_url(&input.url).await?;
+        let subscription = self.repo.create(input).await?;
+        self.cache(subscription.clone())?;
+        Ok(subscription)
+    }
+
+    /// Replace a subscription, returning None if it doesn't exist
+    pub async fn update(&self, id: Uuid, input: WebhookSubscriptionInput) -> Result<Option<WebhookSubscription>> {
+        crate::net::ensure_public_url(&input.url).await?;
+        let Some(subscription) = self.repo.update(id, input).await? else {
+            return Ok(None);
+        };
+        self.cache(subscription.clone())?;
+        Ok(Some(subscription))
+    }
+
+    /// Delete a subscription and its delivery history
+    pub async fn remove(&self, id: Uuid) -> Result<bool> {
+        let deleted = self.repo.delete(id).await?;
+        self.subscriptions.write().retain(|(s, _)| s.id != id);
+        Ok(deleted)
+    }
+
+    /// A subscription's most recent deliveries, newest first
+    pub async fn deliveries(&self, id: Uuid, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        self.repo.list_deliveries(id, limit).await
+    }
+
+    fn cache(&self, subscription: WebhookSubscription) -> Result<()> {
+        let query = SpanQuery::parse(&subscription.filter)?;
+        let mut subscriptions = self.subscriptions.write();
+        subscriptions.retain(|(s, _)| s.id != subscription.id);
+        subscriptions.push((subscription, query));
+        subscriptions.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        Ok(())
+    }
+
+    /// Queue a payload for every enabled subscription the span matches,
+    /// unless the span's trace was already delivered to it; returns how many
+    /// were queued
+    ///
+    /// Never waits: when the delivery queue is full the payload is dropped
+    /// and logged, so slow webhook targets can't hold up ingestion.
+    pub fn dispatch(&self, span: &Span) -> usize {
+        let matched: Vec<WebhookSubscription> = self
+            .subscriptions
+            .read()
+            .iter()
+            .filter(|(s, query)| s.enabled && query.matches(span))
+            .map(|(s, _)| s.clone())
+            .collect();
+
+        let mut queued = 0;
+        for subscription in matched {
+            if !self.recent.lock().insert(subscription.id, &span.trace_id) {
+                continue;
+            }
+
+            let payload = WebhookPayload {
+                event: MATCHED_EVENT.to_string(),
+                subscription_id: subscription.id,
+                subscription_name: subscription.name.clone(),
+                trace_id: span.trace_id.clone(),
+                span: span.clone(),
+                sent_at: Utc::now(),
+            };
+
+            match self.queue.try_send(Delivery { subscription, payload }) {
+                Ok(()) => queued += 1,
+                Err(e) => warn!("Dropping webhook delivery, queue is full or closed: {}", e),
+            }
+        }
+
+        queued
+    }
+
+    /// Spawn the delivery worker
+    ///
+    /// Returns None if the worker was already started.
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        let mut receiver = self.receiver.lock().take()?;
+        let registry = self.clone();
+        let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_DELIVERIES));
+
+        Some(tokio::spawn(async move {
+            while let Some(delivery) = receiver.recv().await {
+                let Ok(permit) = permits.clone().acquire_owned().await else {
+                    break;
+                };
+                let registry = registry.clone();
+                tokio::spawn(async move {
+                    registry.deliver(delivery).await;
+                    drop(permit);
+                });
+            }
+        }))
+    }
+
+    /// Post a payload, retrying server errors and failed requests with
+    /// backoff, and record the outcome
+    async fn deliver(&self, delivery: Delivery) {
+        let Delivery { subscription, payload } = delivery;
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+
+        let mut attempts = 0;
+        let mut status_code = None;
+        let mut error = None;
+        let mut success = false;
+
+        // The host may have been repointed since the subscription was saved
+        if let Err(e) = crate::net::ensure_public_url(&subscription.url).await {
+            attempts = MAX_ATTEMPTS;
+            error = Some(e.to_string());
+        }
+
+        while attempts < MAX_ATTEMPTS {
+            if attempts > 0 {
+                tokio::time::sleep(Duration::from_secs(1 << (attempts - 1))).await;
+            }
+            attempts += 1;
+
+            let timestamp = Utc::now().timestamp().to_string();
+            let mut message = format!("{}.", timestamp).into_bytes();
+            message.extend_from_slice(&body);
+
+            let result = self
+                .client
+                .post(&subscription.url)
+                .header("Content-Type", "application/json")
+                .header("X-AgentTrace-Event", MATCHED_EVENT)
+                .header("X-AgentTrace-Timestamp", &timestamp)
+                .header("X-AgentTrace-Signature", format!("sha256={}", sign(&subscription.secret, &message)))
+                .body(body.clone())
+                .send()
+                .await;
+
+            match result {
+                Ok(resp) if resp.status().is_success() => {
+                    status_code = Some(i32::from(resp.status().as_u16()));
+                    error = None;
+                    success = true;
+                    break;
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    status_code = Some(i32::from(status.as_u16()));
+                    error = Some(format!("Webhook returned {}", status));
+                    // Other client errors won't succeed on retry
+                    if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    status_code = None;
+                    error = Some(e.to_string());
+                }
+            }
+        }
+
+        if success {
+            debug!("Delivered trace {} to webhook {}", payload.trace_id, subscription.name);
+        } else {
+            warn!(
+                "Webhook {} failed for trace {} after {} attempts: {}",
+                subscription.name,
+                payload.trace_id,
+                attempts,
+                error.as_deref().unwrap_or("unknown error")
+            );
+        }
+
+        let record = WebhookDelivery {
+            id: Uuid::new_v4(),
+            subscription_id: subscription.id,
+            trace_id: payload.trace_id,
+            span_id: payload.span.span_id,
+            attempts,
+            success,
+            status_code,
+            error,
+            delivered_at: Utc::now(),
+        };
+        if let Err(e) = self.repo.record_delivery(&record).await {
+            warn!("Failed to record webhook delivery: {}", e);
+        }
+    }
+}
+
+/// Hex HMAC-SHA256 of a message
+fn sign(secret: &str, message: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::tests::create_test_span;
+    use crate::models::SpanStatus;
+    use sqlx::PgPool;
+
+    fn subscription(name: &str, filter: &str, enabled: bool) -> WebhookSubscription {
+        WebhookSubscription {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            filter: filter.to_string(),
+            url: "https://hooks.example.com/agenttrace".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            enabled,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_sign_matches_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_recent_matches_deliver_each_trace_once() {
+        let mut recent = RecentMatches::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(recent.insert(a, "t1"));
+        assert!(!recent.insert(a, "t1"));
+        assert!(recent.insert(b, "t1"));
+        assert!(recent.insert(a, "t2"));
+
+        for i in 0..RECENT_MATCHES {
+            recent.insert(a, &format!("filler-{}", i));
+        }
+        assert_eq!(recent.order.len(), RECENT_MATCHES);
+        assert!(recent.insert(a, "t1"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_queues_matching_subscriptions_once_per_trace() {
+        let pool = PgPool::connect_lazy("postgres://localhost/agenttrace").unwrap();
+        let registry = SubscriptionRegistry::new(SubscriptionRepository::new(pool));
+        let errors = subscription("errors", "status:error", true);
+        registry.cache(errors.clone()).unwrap();
+        registry.cache(subscription("all-disabled", "", false)).unwrap();
+        registry.cache(subscription("other-service", "service:billing status:error", true)).unwrap();
+
+        let mut ok = create_test_span("t1", "s1", None, 100.0);
+        ok.service_name = "agent".to_string();
+        let mut failed = create_test_span("t1", "s2", Some("s1"), 100.0);
+        failed.service_name = "agent".to_string();
+        failed.status = SpanStatus::Error;
+
+        assert_eq!(registry.dispatch(&ok), 0);
+        assert_eq!(registry.dispatch(&failed), 1);
+        // Later matches in the same trace aren't delivered again
+        assert_eq!(registry.dispatch(&failed), 0);
+
+        let mut receiver = registry.receiver.lock().take().unwrap();
+        let delivery = receiver.try_recv().unwrap();
+        assert_eq!(delivery.subscription.id, errors.id);
+        assert_eq!(delivery.payload.event, MATCHED_EVENT);
+        assert_eq!(delivery.payload.trace_id, "t1");
+        assert_eq!(delivery.payload.span.span_id, "s2");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_private_urls_are_rejected() {
+        let pool = PgPool::connect_lazy("postgres://localhost/agenttrace").unwrap();
+        let registry = SubscriptionRegistry::new(SubscriptionRepository::new(pool));
+        let input = WebhookSubscriptionInput {
+            name: "metadata".to_string(),
+            filter: "status:error".to_string(),
+            url: "http://169.254.169.254/latest/meta-data".to_string(),
+            secret: "0123456789abcdef".to_string(),
+            enabled: true,
+        };
+
+        assert!(registry.create(input.clone()).await.is_err());
+        assert!(registry.update(Uuid::new_v4(), input).await.is_err());
+        assert!(registry.list().is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+//! Webhook subscription repository
+
+use chrono::Utc;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{WebhookDelivery, WebhookSubscription, WebhookSubscriptionInput};
+
+const SUBSCRIPTION_COLUMNS: &str = "id, name, filter, url, secret, enabled, created_at, updated_at";
+
+/// Deliveries kept per subscription; older ones are pruned
+pub const DELIVERY_HISTORY: i64 = 1_000;
+
+/// Repository for webhook subscriptions and their delivery history
+#[derive(Clone)]
+pub struct SubscriptionRepository {
+    pool: PgPool,
+}
+
+impl SubscriptionRepository {
+    /// Create a new subscription repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// List subscriptions by name
+    pub async fn list(&self) -> Result<Vec<WebhookSubscription>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM webhook_subscriptions ORDER BY name",
+            SUBSCRIPTION_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_subscription).collect()
+    }
+
+    /// Get a subscription by ID
+    pub async fn get(&self, id: Uuid) -> Result<Option<WebhookSubscription>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM webhook_subscriptions WHERE id = $1",
+            SUBSCRIPTION_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(row_to_subscription).transpose()
+    }
+
+    /// Create a subscription
+    pub async fn create(&self, input: WebhookSubscriptionInput) -> Result<WebhookSubscription> {
+        input.validate()?;
+
+        let now = Utc::now();
+        let subscription = WebhookSubscription {
+            id: Uuid::new_v4(),
+            name: input.name.trim().to_string(),
+            filter: input.filter,
+            url: input.url,
+            secret: input.secret,
+            enabled: input.enabled,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_subscriptions (id, name, filter, url, secret, enabled, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(subscription.id)
+        .bind(&subscription.name)
+        .bind(&subscription.filter)
+        .bind(&subscription.url)
+        .bind(&subscription.secret)
+        .bind(subscription.enabled)
+        .bind(subscription.created_at)
+        .bind(subscription.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &subscription.name))?;
+
+        Ok(subscription)
+    }
+
+    /// Replace a subscription's name, filter, URL, secret and enabled flag
+    ///
+    /// Returns None if the subscription doesn't exist.
+    pub async fn update(&self, id: Uuid, input: WebhookSubscriptionInput) -> Result<Option<WebhookSubscription>> {
+        input.validate()?;
+        let name = input.name.trim().to_string();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE webhook_subscriptions SET
+                name = $2,
+                filter = $3,
+                url = $4,
+                secret = $5,
+                enabled = $6,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(&name)
+        .bind(&input.filter)
+        .bind(&input.url)
+        .bind(&input.secret)
+        .bind(input.enabled)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &name))?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    /// Delete a subscription and its delivery history
+    pub async fn delete(&self, id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhook_subscriptions WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record the outcome of a delivery
+    pub async fn record_delivery(&self, delivery: &WebhookDelivery) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (
+                id, subscription_id, trace_id, span_id, attempts, success, status_code, error, delivered_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(delivery.id)
+        .bind(delivery.subscription_id)
+        .bind(&delivery.trace_id)
+        .bind(&delivery.span_id)
+        .bind(delivery.attempts)
+        .bind(delivery.success)
+        .bind(delivery.status_code)
+        .bind(&delivery.error)
+        .bind(delivery.delivered_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete all but each subscription's latest [`DELIVERY_HISTORY`]
+    /// deliveries, returning how many were deleted
+    pub async fn prune_deliveries(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM webhook_deliveries
+            WHERE id IN (
+                SELECT id FROM (
+                    SELECT id, row_number() OVER (PARTITION BY subscription_id ORDER BY delivered_at DESC) AS n
+                    FROM webhook_deliveries
+                ) ranked
+                WHERE n > $1
+            )
+            "#,
+        )
+        .bind(DELIVERY_HISTORY)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// A subscription's most recent deliveries, newest first
+    pub async fn list_deliveries(&self, subscription_id: Uuid, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, subscription_id, trace_id, span_id, attempts, success, status_code, error, delivered_at
+            FROM webhook_deliveries
+            WHERE subscription_id = $1
+            ORDER BY delivered_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(WebhookDelivery {
+                    id: row.try_get("id")?,
+                    subscription_id: row.try_get("subscription_id")?,
+                    trace_id: row.try_get("trace_id")?,
+                    span_id: row.try_get("span_id")?,
+                    attempts: row.try_get("attempts")?,
+                    success: row.try_get("success")?,
+                    status_code: row.try_get("status_code")?,
+                    error: row.try_get("error")?,
+                    delivered_at: row.try_get("delivered_at")?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Report a duplicate name as a validation error
+fn name_conflict(e: sqlx::Error, name: &str) -> Error {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            Error::validation(format!("A subscription named '{}' already exists", name))
+        }
+        _ => e.into(),
+    }
+}
+
+fn row_to_subscription(row: &PgRow) -> Result<WebhookSubscription> {
+    Ok(WebhookSubscription {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        filter: row.try_get("filter")?,
+        url: row.try_get("url")?,
+        secret: row.try_get("secret")?,
+        enabled: row.try_get("enabled")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
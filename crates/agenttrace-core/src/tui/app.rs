@@ -1,11 +1,21 @@
 //! Main TUI application state and logic
 
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use crossterm::event::{KeyCode, KeyModifiers};
 use ratatui::widgets::TableState;
 
-use crate::models::{Span, SpanStatus};
+use crate::annotations::AnnotationRepository;
+use crate::db::SpanStore;
+use crate::locale::NumberFormat;
+use crate::models::{
+    PageCursor, SavedView, SessionSummary, Span, SpanKind, SpanStatus, TokenEfficiency, TraceAnnotation,
+};
+
+/// Traces listed at a time; `n` loads another page
+const TRACE_PAGE_SIZE: i64 = 50;
 
 /// Active view/tab in the TUI
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -16,6 +26,7 @@ pub enum ActiveTab {
     Costs,
     Alerts,
     Search,
+    Sessions,
 }
 
 impl ActiveTab {
@@ -25,17 +36,19 @@ impl ActiveTab {
             Self::Traces => Self::Costs,
             Self::Costs => Self::Alerts,
             Self::Alerts => Self::Search,
-            Self::Search => Self::Overview,
+            Self::Search => Self::Sessions,
+            Self::Sessions => Self::Overview,
         }
     }
 
     pub fn prev(self) -> Self {
         match self {
-            Self::Overview => Self::Search,
+            Self::Overview => Self::Sessions,
             Self::Traces => Self::Overview,
             Self::Costs => Self::Traces,
             Self::Alerts => Self::Costs,
             Self::Search => Self::Alerts,
+            Self::Sessions => Self::Search,
         }
     }
 
@@ -46,6 +59,7 @@ impl ActiveTab {
             Self::Costs => 2,
             Self::Alerts => 3,
             Self::Search => 4,
+            Self::Sessions => 5,
         }
     }
 }
@@ -82,6 +96,12 @@ pub struct AlertDisplay {
     pub message: String,
     pub triggered_at: String,
     pub status: String,
+    /// Incident the alert was grouped into with others for its service
+    pub incident_id: Option<String>,
+    /// Who acknowledged the alert
+    pub acknowledged_by: Option<String>,
+    /// Who is handling the alert
+    pub assignee: Option<String>,
 }
 
 /// Trace summary for list display
@@ -96,6 +116,36 @@ pub struct TraceSummary {
     pub cost_usd: f64,
     pub status: SpanStatus,
     pub started_at: String,
+    /// Review notes left on the trace
+    pub annotations: Vec<String>,
+}
+
+/// Session summary for list display
+#[derive(Debug, Clone)]
+pub struct SessionDisplay {
+    pub session_id: String,
+    pub user_id: Option<String>,
+    pub trace_count: u32,
+    pub span_count: u32,
+    pub tokens: u64,
+    pub cost_usd: f64,
+    pub error_count: u32,
+    pub last_seen: String,
+}
+
+impl From<&SessionSummary> for SessionDisplay {
+    fn from(session: &SessionSummary) -> Self {
+        Self {
+            session_id: session.session_id.clone(),
+            user_id: session.user_id.clone(),
+            trace_count: session.trace_count as u32,
+            span_count: session.span_count as u32,
+            tokens: session.total_tokens as u64,
+            cost_usd: session.total_cost_usd,
+            error_count: session.error_count as u32,
+            last_seen: relative_time(session.last_seen),
+        }
+    }
 }
 
 /// Recent span for real-time display
@@ -111,6 +161,77 @@ pub struct RecentSpan {
     pub timestamp: String,
 }
 
+impl From<&Span> for RecentSpan {
+    fn from(span: &Span) -> Self {
+        let span_type = if span.model_name.is_some() {
+            "llm"
+        } else if span.tool_name.is_some() {
+            "tool"
+        } else if span.span_kind == SpanKind::Server {
+            "agent"
+        } else {
+            "span"
+        };
+        let tokens = match (span.tokens_in, span.tokens_out) {
+            (None, None) => None,
+            (tokens_in, tokens_out) => Some(tokens_in.unwrap_or(0) + tokens_out.unwrap_or(0)),
+        };
+
+        Self {
+            span_id: span.span_id.clone(),
+            trace_id: span.trace_id.clone(),
+            operation: span.operation_name.clone(),
+            span_type: span_type.to_string(),
+            duration_ms: span.duration_ms,
+            tokens: tokens.map(|t| t as u32),
+            status: span.status,
+            timestamp: relative_time(span.ended_at.unwrap_or(span.started_at)),
+        }
+    }
+}
+
+/// A trace note as shown in the trace view, e.g.
+/// `alice: flaky fixture, retry passed [known-issue]`
+fn note_line(annotation: &TraceAnnotation) -> String {
+    let mut line = format!("{}: {}", annotation.author, annotation.text);
+    if let Some(span_id) = &annotation.span_id {
+        line.push_str(&format!(" (span {})", span_id));
+    }
+    if !annotation.labels.is_empty() {
+        line.push_str(&format!(" [{}]", annotation.labels.join(", ")));
+    }
+    line
+}
+
+impl From<&crate::models::TraceSummary> for TraceSummary {
+    fn from(trace: &crate::models::TraceSummary) -> Self {
+        Self {
+            trace_id: trace.trace_id.clone(),
+            operation: trace.root_operation.clone(),
+            service: trace.service_name.clone(),
+            duration_ms: trace.duration_ms.unwrap_or(0.0),
+            span_count: trace.span_count as u32,
+            tokens: trace.total_tokens as u32,
+            cost_usd: trace.total_cost_usd,
+            status: if trace.error_count > 0 { SpanStatus::Error } else { SpanStatus::Ok },
+            started_at: relative_time(trace.started_at),
+            annotations: Vec::new(),
+        }
+    }
+}
+
+/// How long ago a time was, e.g. `5s ago` or `2h ago`
+fn relative_time(at: DateTime<Utc>) -> String {
+    let secs = (Utc::now() - at).num_seconds().max(0);
+    match secs {
+        0..=1 => "just now".to_string(),
+        2..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86_399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
 /// Main TUI application state
 pub struct App {
     /// Whether the app should quit
@@ -121,8 +242,16 @@ pub struct App {
     pub metrics: MetricsSummary,
     /// Cost breakdown by model
     pub costs_by_model: Vec<CostByModel>,
+    /// Token usage efficiency over the time range
+    pub token_efficiency: TokenEfficiency,
     /// Recent traces
     pub traces: Vec<TraceSummary>,
+    /// Cursor for the page after the loaded traces, if there is one
+    pub traces_next_cursor: Option<String>,
+    /// Set when the next page of traces has been requested
+    pub load_more_traces: bool,
+    /// Recent sessions, latest activity first
+    pub sessions: Vec<SessionDisplay>,
     /// Recent spans (live feed)
     pub recent_spans: Vec<RecentSpan>,
     /// Active alerts
@@ -133,6 +262,10 @@ pub struct App {
     pub search_results: Vec<TraceSummary>,
     /// Is search input focused
     pub search_focused: bool,
+    /// Saved views that can be loaded into the search
+    pub saved_views: Vec<SavedView>,
+    /// Index of the saved view last loaded into the search
+    pub active_view: Option<usize>,
     /// Traces table state
     pub traces_state: TableState,
     /// Spans table state
@@ -141,6 +274,8 @@ pub struct App {
     pub alerts_state: TableState,
     /// Search results table state
     pub search_state: TableState,
+    /// Sessions table state
+    pub sessions_state: TableState,
     /// Last update time
     pub last_update: Instant,
     /// Refresh rate
@@ -157,6 +292,12 @@ pub struct App {
     pub tokens_sparkline: Vec<u64>,
     /// Sparkline data for cost/hour
     pub cost_sparkline: Vec<f64>,
+    /// Number and currency formatting
+    pub numbers: NumberFormat,
+    /// Span storage to read from; sample data is shown without one
+    store: Option<Arc<dyn SpanStore>>,
+    /// Where trace review notes are read from (PostgreSQL storage only)
+    annotations: Option<AnnotationRepository>,
 }
 
 impl Default for App {
@@ -173,16 +314,23 @@ impl App {
             active_tab: ActiveTab::default(),
             metrics: MetricsSummary::default(),
             costs_by_model: Vec::new(),
+            token_efficiency: TokenEfficiency::default(),
             traces: Vec::new(),
+            traces_next_cursor: None,
+            load_more_traces: false,
+            sessions: Vec::new(),
             recent_spans: Vec::new(),
             alerts: Vec::new(),
             search_query: String::new(),
             search_results: Vec::new(),
             search_focused: false,
+            saved_views: Vec::new(),
+            active_view: None,
             traces_state: TableState::default(),
             spans_state: TableState::default(),
             alerts_state: TableState::default(),
             search_state: TableState::default(),
+            sessions_state: TableState::default(),
             last_update: Instant::now(),
             refresh_rate: Duration::from_secs(1),
             time_range: "1h".to_string(),
@@ -191,6 +339,9 @@ impl App {
             connected: false,
             tokens_sparkline: vec![0; 60],
             cost_sparkline: vec![0.0; 24],
+            numbers: NumberFormat::default(),
+            store: None,
+            annotations: None,
         }
     }
 
@@ -206,6 +357,30 @@ impl App {
         self
     }
 
+    /// Set number and currency formatting
+    pub fn with_number_format(mut self, numbers: NumberFormat) -> Self {
+        self.numbers = numbers;
+        self
+    }
+
+    /// Offer saved views in the Search tab
+    pub fn with_saved_views(mut self, views: Vec<SavedView>) -> Self {
+        self.saved_views = views;
+        self
+    }
+
+    /// Read live data from span storage instead of showing sample data
+    pub fn with_store(mut self, store: Arc<dyn SpanStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Show the review notes left on traces
+    pub fn with_annotations(mut self, annotations: AnnotationRepository) -> Self {
+        self.annotations = Some(annotations);
+        self
+    }
+
     /// Handle key events
     pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
         // Global shortcuts
@@ -247,6 +422,9 @@ impl App {
             (KeyCode::Char('5'), KeyModifiers::NONE) if !self.search_focused => {
                 self.active_tab = ActiveTab::Search;
             }
+            (KeyCode::Char('6'), KeyModifiers::NONE) if !self.search_focused => {
+                self.active_tab = ActiveTab::Sessions;
+            }
             (KeyCode::Char('/'), KeyModifiers::NONE) if !self.search_focused => {
                 self.active_tab = ActiveTab::Search;
                 self.search_focused = true;
@@ -263,6 +441,7 @@ impl App {
             ActiveTab::Traces => self.handle_traces_key(code),
             ActiveTab::Alerts => self.handle_alerts_key(code),
             ActiveTab::Search => self.handle_search_key(code),
+            ActiveTab::Sessions => self.handle_sessions_key(code),
             _ => {}
         }
     }
@@ -295,12 +474,62 @@ impl App {
                     }
                 }
             }
+            KeyCode::Char('n') => {
+                if self.traces_next_cursor.is_some() {
+                    self.load_more_traces = true;
+                    self.set_status("Loading more traces...".to_string());
+                } else {
+                    self.set_status("No more traces".to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_sessions_key(&mut self, code: KeyCode) {
+        let len = self.sessions.len();
+        if len == 0 {
+            return;
+        }
+
+        match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                let i = self.sessions_state.selected().unwrap_or(0);
+                self.sessions_state.select(Some(i.saturating_sub(1)));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let i = self.sessions_state.selected().unwrap_or(0);
+                self.sessions_state.select(Some((i + 1).min(len - 1)));
+            }
+            KeyCode::Enter => {
+                if let Some(session) = self.sessions_state.selected().and_then(|i| self.sessions.get(i)) {
+                    self.set_status(format!("Selected session: {}", session.session_id));
+                }
+            }
             _ => {}
         }
     }
 
+    /// Alerts grouped by incident, in order of each incident's first alert;
+    /// alerts without an incident stand alone
+    pub fn alert_incidents(&self) -> Vec<Vec<&AlertDisplay>> {
+        let mut groups: Vec<Vec<&AlertDisplay>> = Vec::new();
+        for alert in &self.alerts {
+            let existing = alert.incident_id.as_ref().and_then(|id| {
+                groups
+                    .iter_mut()
+                    .find(|group| group[0].incident_id.as_ref() == Some(id))
+            });
+            match existing {
+                Some(group) => group.push(alert),
+                None => groups.push(vec![alert]),
+            }
+        }
+        groups
+    }
+
     fn handle_alerts_key(&mut self, code: KeyCode) {
-        let len = self.alerts.len();
+        let len = self.alert_incidents().len();
         if len == 0 {
             return;
         }
@@ -333,6 +562,7 @@ impl App {
                 }
                 KeyCode::Char(c) => {
                     self.search_query.push(c);
+                    self.active_view = None;
                 }
                 _ => {}
             }
@@ -341,6 +571,7 @@ impl App {
                 KeyCode::Char('i') | KeyCode::Char('/') => {
                     self.search_focused = true;
                 }
+                KeyCode::Char('v') => self.load_next_view(),
                 KeyCode::Up | KeyCode::Char('k') => {
                     let len = self.search_results.len();
                     if len > 0 {
@@ -360,6 +591,24 @@ impl App {
         }
     }
 
+    /// Load the next saved view's filters into the search
+    fn load_next_view(&mut self) {
+        if self.saved_views.is_empty() {
+            self.set_status("No saved views".to_string());
+            return;
+        }
+
+        let index = self.active_view.map_or(0, |i| (i + 1) % self.saved_views.len());
+        let view = &self.saved_views[index];
+        self.search_query = view.filters.to_query_string();
+        if let Some(last) = &view.filters.last {
+            self.time_range = last.clone();
+        }
+        let message = format!("Loaded view '{}'", view.name);
+        self.active_view = Some(index);
+        self.set_status(message);
+    }
+
     /// Set a status message that expires after 3 seconds
     pub fn set_status(&mut self, message: String) {
         self.status_message = Some((message, Instant::now()));
@@ -377,12 +626,27 @@ impl App {
     }
 
     /// Update with new span data
+    ///
+    /// An update for a span already shown (an in-progress span completing)
+    /// replaces its row instead of adding another.
     pub fn add_span(&mut self, span: RecentSpan) {
+        match self.recent_spans.iter().position(|s| s.span_id == span.span_id) {
+            Some(pos) => {
+                self.recent_spans.remove(pos);
+            }
+            None => self.metrics.total_spans += 1,
+        }
         self.recent_spans.insert(0, span);
         if self.recent_spans.len() > 100 {
             self.recent_spans.pop();
         }
-        self.metrics.total_spans += 1;
+    }
+
+    /// Append a page of traces fetched with `traces_next_cursor`
+    pub fn append_traces(&mut self, traces: Vec<TraceSummary>, next_cursor: Option<String>) {
+        self.traces.extend(traces);
+        self.traces_next_cursor = next_cursor;
+        self.load_more_traces = false;
     }
 
     /// Update metrics
@@ -396,6 +660,113 @@ impl App {
         self.last_update.elapsed() >= self.refresh_rate
     }
 
+    /// Start of the selected time range, e.g. an hour ago for `1h`
+    fn time_range_start(&self) -> DateTime<Utc> {
+        let range = self.time_range.trim();
+        let (amount, unit) = range.split_at(range.len().saturating_sub(1));
+        let amount: i64 = amount.parse().unwrap_or(1);
+        let window = match unit {
+            "m" => chrono::Duration::minutes(amount),
+            "d" => chrono::Duration::days(amount),
+            _ => chrono::Duration::hours(amount),
+        };
+        Utc::now() - window
+    }
+
+    /// Reload metrics, costs, traces and recent spans from span storage
+    pub async fn refresh_from_store(&mut self) -> crate::error::Result<()> {
+        let Some(store) = self.store.clone() else {
+            return Ok(());
+        };
+        let since = self.time_range_start();
+        let until = Utc::now();
+
+        let summary = store.get_metrics_summary(None, None, since, until).await?;
+        let minutes = ((until - since).num_seconds() as f64 / 60.0).max(1.0);
+        self.update_metrics(MetricsSummary {
+            total_traces: summary.total_traces as u64,
+            total_spans: summary.total_spans as u64,
+            total_tokens: summary.total_tokens as u64,
+            total_cost_usd: summary.total_cost_usd,
+            error_count: summary.error_count as u64,
+            avg_latency_ms: summary.avg_latency_ms,
+            p99_latency_ms: summary.p99_latency_ms,
+            spans_per_minute: summary.total_spans as f64 / minutes,
+        });
+
+        self.costs_by_model = store
+            .get_cost_by_group(None, "model", since, until)
+            .await?
+            .into_iter()
+            .map(|cost| CostByModel {
+                provider: String::new(),
+                model: cost.group,
+                tokens: cost.total_tokens as u64,
+                cost_usd: cost.total_cost_usd,
+                call_count: cost.call_count as u64,
+            })
+            .collect();
+        self.token_efficiency = store.get_token_efficiency(None, None, since, until).await?;
+
+        // Keep the pages loaded with `n`
+        let limit = (self.traces.len() as i64).max(TRACE_PAGE_SIZE);
+        let (traces, next_cursor) = store.list_traces(None, None, Some(since), None, limit, None).await?;
+        self.traces = traces.iter().map(TraceSummary::from).collect();
+        self.traces_next_cursor = next_cursor.map(|c| c.encode());
+        self.load_trace_annotations().await?;
+
+        self.recent_spans = store.get_recent(100).await?.iter().map(RecentSpan::from).collect();
+
+        let (sessions, _) = store.list_sessions(None, Some(since), 50).await?;
+        self.sessions = sessions.iter().map(SessionDisplay::from).collect();
+
+        if self.traces_state.selected().is_none() && !self.traces.is_empty() {
+            self.traces_state.select(Some(0));
+        }
+        if self.sessions_state.selected().is_none() && !self.sessions.is_empty() {
+            self.sessions_state.select(Some(0));
+        }
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Append the page of traces after `traces_next_cursor`
+    async fn load_next_traces(&mut self) -> crate::error::Result<()> {
+        let (Some(store), Some(cursor)) = (self.store.clone(), self.traces_next_cursor.as_deref()) else {
+            self.load_more_traces = false;
+            return Ok(());
+        };
+        let cursor = PageCursor::decode(cursor)?;
+        let since = self.time_range_start();
+
+        let (traces, next_cursor) = store
+            .list_traces(None, None, Some(since), None, TRACE_PAGE_SIZE, Some(&cursor))
+            .await?;
+        let loaded = traces.len();
+        self.append_traces(traces.iter().map(TraceSummary::from).collect(), next_cursor.map(|c| c.encode()));
+        self.load_trace_annotations().await?;
+        self.set_status(format!("Loaded {} more traces", loaded));
+        Ok(())
+    }
+
+    /// Fill in the notes left on the listed traces
+    async fn load_trace_annotations(&mut self) -> crate::error::Result<()> {
+        let Some(repo) = &self.annotations else {
+            return Ok(());
+        };
+        let trace_ids: Vec<String> = self.traces.iter().map(|t| t.trace_id.clone()).collect();
+        let annotations = repo.list_for_traces(&trace_ids).await?;
+
+        for trace in &mut self.traces {
+            trace.annotations = annotations
+                .iter()
+                .filter(|a| a.trace_id == trace.trace_id)
+                .map(note_line)
+                .collect();
+        }
+        Ok(())
+    }
+
     /// Load sample data for demo
     pub fn load_demo_data(&mut self) {
         self.connected = true;
@@ -436,6 +807,18 @@ impl App {
                 call_count: 123,
             },
         ];
+        self.token_efficiency = TokenEfficiency {
+            span_count: 45_678,
+            trace_count: 1_234,
+            successful_traces: 1_211,
+            tokens_in: 1_890_000,
+            tokens_cached_in: 1_020_600,
+            tokens_out: 410_000,
+            tokens_reasoning: 45_678,
+            total_cost_usd: 127.45,
+            ..TokenEfficiency::default()
+        };
+        self.token_efficiency.compute_ratios();
 
         // Sample traces
         self.traces = vec![
@@ -449,6 +832,7 @@ impl App {
                 cost_usd: 0.89,
                 status: SpanStatus::Ok,
                 started_at: "2 min ago".to_string(),
+                annotations: Vec::new(),
             },
             TraceSummary {
                 trace_id: "def456".to_string(),
@@ -460,6 +844,7 @@ impl App {
                 cost_usd: 2.34,
                 status: SpanStatus::Ok,
                 started_at: "5 min ago".to_string(),
+                annotations: Vec::new(),
             },
             TraceSummary {
                 trace_id: "ghi789".to_string(),
@@ -471,6 +856,33 @@ impl App {
                 cost_usd: 0.45,
                 status: SpanStatus::Error,
                 started_at: "8 min ago".to_string(),
+                annotations: vec![
+                    "alice: flaky fixture, retry passed [known-issue]".to_string(),
+                ],
+            },
+        ];
+
+        // Sample sessions
+        self.sessions = vec![
+            SessionDisplay {
+                session_id: "chat-7f3a".to_string(),
+                user_id: Some("user-42".to_string()),
+                trace_count: 6,
+                span_count: 84,
+                tokens: 56_320,
+                cost_usd: 3.68,
+                error_count: 1,
+                last_seen: "2 min ago".to_string(),
+            },
+            SessionDisplay {
+                session_id: "chat-19bc".to_string(),
+                user_id: None,
+                trace_count: 2,
+                span_count: 17,
+                tokens: 9_412,
+                cost_usd: 0.41,
+                error_count: 0,
+                last_seen: "25 min ago".to_string(),
             },
         ];
 
@@ -507,6 +919,31 @@ impl App {
                 message: "Error rate above 5% for review-agent".to_string(),
                 triggered_at: "10 min ago".to_string(),
                 status: "active".to_string(),
+                incident_id: Some("incident1".to_string()),
+                acknowledged_by: None,
+                assignee: None,
+            },
+            AlertDisplay {
+                id: "alert2".to_string(),
+                rule_name: "Slow LLM Calls".to_string(),
+                severity: "critical".to_string(),
+                message: "p95 LLM latency above 8s for review-agent".to_string(),
+                triggered_at: "12 min ago".to_string(),
+                status: "acknowledged".to_string(),
+                incident_id: Some("incident1".to_string()),
+                acknowledged_by: Some("dana".to_string()),
+                assignee: Some("sam".to_string()),
+            },
+            AlertDisplay {
+                id: "alert3".to_string(),
+                rule_name: "Daily Cost Budget".to_string(),
+                severity: "info".to_string(),
+                message: "Cost above $50 today for code-agent".to_string(),
+                triggered_at: "3 hours ago".to_string(),
+                status: "resolved".to_string(),
+                incident_id: None,
+                acknowledged_by: None,
+                assignee: None,
             },
         ];
 
@@ -527,6 +964,7 @@ impl App {
         ];
 
         self.traces_state.select(Some(0));
+        self.sessions_state.select(Some(0));
     }
 
     /// Run the TUI application
@@ -547,8 +985,12 @@ impl App {
         let mut terminal = Terminal::new(backend)
             .map_err(|e| crate::error::Error::Tui(e.to_string()))?;
 
-        // Load demo data for now
-        self.load_demo_data();
+        // Without storage to read from, show sample data
+        if self.store.is_some() {
+            self.refresh_live().await;
+        } else {
+            self.load_demo_data();
+        }
 
         // Create event handler
         let mut events = super::EventHandler::new(self.refresh_rate.as_millis() as u64);
@@ -566,9 +1008,15 @@ impl App {
                 match event {
                     super::Event::Key(key) => {
                         self.handle_key(key.code, key.modifiers);
+                        if self.load_more_traces {
+                            if let Err(e) = self.load_next_traces().await {
+                                self.load_more_traces = false;
+                                self.set_status(format!("Loading traces failed: {}", e));
+                            }
+                        }
                     }
-                    super::Event::Tick => {
-                        // Periodic updates would go here
+                    super::Event::Tick if self.store.is_some() && self.needs_refresh() => {
+                        self.refresh_live().await;
                     }
                     super::Event::Resize(_, _) => {
                         // Terminal handles resize automatically
@@ -587,4 +1035,13 @@ impl App {
 
         Ok(())
     }
+
+    /// Refresh from storage, reporting failures in the status bar
+    async fn refresh_live(&mut self) {
+        if let Err(e) = self.refresh_from_store().await {
+            self.connected = false;
+            self.last_update = Instant::now();
+            self.set_status(format!("Refresh failed: {}", e));
+        }
+    }
 }
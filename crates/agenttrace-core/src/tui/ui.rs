@@ -13,6 +13,7 @@ use ratatui::{
 };
 
 use super::app::{ActiveTab, App};
+use crate::locale::NumberFormat;
 use crate::models::SpanStatus;
 
 /// Main colors
@@ -61,7 +62,7 @@ fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(logo, chunks[0]);
 
     // Tabs
-    let tabs = vec!["Overview", "Traces", "Costs", "Alerts", "Search"];
+    let tabs = ["Overview", "Traces", "Costs", "Alerts", "Search", "Sessions"];
     let tab_titles: Vec<Line> = tabs
         .iter()
         .enumerate()
@@ -102,6 +103,7 @@ fn draw_content(frame: &mut Frame, app: &App, area: Rect) {
         ActiveTab::Costs => draw_costs(frame, app, area),
         ActiveTab::Alerts => draw_alerts(frame, app, area),
         ActiveTab::Search => draw_search(frame, app, area),
+        ActiveTab::Sessions => draw_sessions(frame, app, area),
     }
 }
 
@@ -144,11 +146,11 @@ fn draw_metric_cards(frame: &mut Frame, app: &App, area: Rect) {
         .split(area);
 
     let cards = [
-        ("Traces", format!("{}", app.metrics.total_traces), PRIMARY),
-        ("Tokens", format_number(app.metrics.total_tokens), SECONDARY),
-        ("Cost", format!("${:.2}", app.metrics.total_cost_usd), SUCCESS),
-        ("Errors", format!("{}", app.metrics.error_count), if app.metrics.error_count > 0 { ERROR } else { MUTED }),
-        ("Avg Latency", format!("{:.0}ms", app.metrics.avg_latency_ms), WARNING),
+        ("Traces", app.numbers.integer(app.metrics.total_traces as i64), PRIMARY),
+        ("Tokens", app.numbers.compact(app.metrics.total_tokens as i64), SECONDARY),
+        ("Cost", app.numbers.currency(app.metrics.total_cost_usd, 2), SUCCESS),
+        ("Errors", app.numbers.integer(app.metrics.error_count as i64), if app.metrics.error_count > 0 { ERROR } else { MUTED }),
+        ("Avg Latency", format!("{}ms", app.numbers.decimal(app.metrics.avg_latency_ms, 0)), WARNING),
     ];
 
     for (i, (title, value, color)) in cards.iter().enumerate() {
@@ -218,14 +220,15 @@ fn draw_recent_spans(frame: &mut Frame, app: &App, area: Rect) {
             let status_style = match span.status {
                 SpanStatus::Ok => Style::default().fg(SUCCESS),
                 SpanStatus::Error => Style::default().fg(ERROR),
+                SpanStatus::InProgress => Style::default().fg(WARNING),
                 _ => Style::default().fg(MUTED),
             };
 
             Row::new(vec![
                 Cell::from(truncate(&span.operation, 20)),
                 Cell::from(span.span_type.clone()),
-                Cell::from(span.duration_ms.map_or("-".to_string(), |d| format!("{:.0}ms", d))),
-                Cell::from(span.tokens.map_or("-".to_string(), |t| t.to_string())),
+                Cell::from(span.duration_ms.map_or("-".to_string(), |d| format!("{}ms", app.numbers.decimal(d, 0)))),
+                Cell::from(span.tokens.map_or("-".to_string(), |t| app.numbers.integer(i64::from(t)))),
                 Cell::from(format!("{:?}", span.status)).style(status_style),
             ])
         })
@@ -269,7 +272,7 @@ fn draw_costs_summary(frame: &mut Frame, app: &App, area: Rect) {
 
             Row::new(vec![
                 Cell::from(truncate(&cost.model, 15)),
-                Cell::from(format!("${:.2}", cost.cost_usd)),
+                Cell::from(app.numbers.currency(cost.cost_usd, 2)),
                 Cell::from(bar).style(Style::default().fg(SECONDARY)),
             ])
         })
@@ -288,9 +291,86 @@ fn draw_costs_summary(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(table, area);
 }
 
+/// Derived token metrics that flag prompt bloat
+fn draw_token_efficiency(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .title("Token Efficiency")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(MUTED));
+
+    let efficiency = &app.token_efficiency;
+    let numbers = &app.numbers;
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let metrics = [
+        (
+            "Cost/Successful Trace: ",
+            or_dash(efficiency.cost_per_successful_trace.map(|c| numbers.currency(c, 4))),
+            SUCCESS,
+        ),
+        (
+            "Tokens/Trace: ",
+            or_dash(efficiency.tokens_per_trace.map(|t| numbers.compact(t.round() as i64))),
+            PRIMARY,
+        ),
+        (
+            "Cache Hit Rate: ",
+            or_dash(efficiency.cached_hit_rate.map(|r| numbers.percent(r, 1))),
+            SECONDARY,
+        ),
+        (
+            "Reasoning Share: ",
+            or_dash(efficiency.reasoning_share.map(|r| numbers.percent(r, 1))),
+            WARNING,
+        ),
+        (
+            "Output/Input: ",
+            or_dash(efficiency.output_input_ratio.map(|r| numbers.decimal(r, 2))),
+            PRIMARY,
+        ),
+    ];
+
+    let lines: Vec<Line> = metrics
+        .into_iter()
+        .map(|(label, value, color)| {
+            Line::from(vec![Span::raw(label), Span::styled(value, Style::default().fg(color))])
+        })
+        .collect();
+
+    let panel = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+    frame.render_widget(panel, area);
+}
+
 fn draw_traces(frame: &mut Frame, app: &App, area: Rect) {
+    let notes = app
+        .traces_state
+        .selected()
+        .and_then(|i| app.traces.get(i))
+        .map(|trace| trace.annotations.as_slice())
+        .unwrap_or_default();
+
+    let area = if notes.is_empty() {
+        area
+    } else {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(5), Constraint::Length(notes.len().min(5) as u16 + 2)])
+            .split(area);
+
+        let lines: Vec<Line> = notes.iter().map(|note| Line::from(format!(" {}", note))).collect();
+        let panel = Paragraph::new(lines).block(
+            Block::default()
+                .title(format!("Annotations ({})", notes.len()))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(MUTED)),
+        );
+        frame.render_widget(panel, chunks[1]);
+
+        chunks[0]
+    };
+
+    let more = if app.traces_next_cursor.is_some() { " · n for more" } else { "" };
     let block = Block::default()
-        .title(format!("Traces (last {})", app.time_range))
+        .title(format!("Traces (last {}){}", app.time_range, more))
         .borders(Borders::ALL)
         .border_style(Style::default().fg(MUTED));
 
@@ -312,10 +392,10 @@ fn draw_traces(frame: &mut Frame, app: &App, area: Rect) {
                 Cell::from(truncate(&trace.trace_id, 10)),
                 Cell::from(truncate(&trace.operation, 15)),
                 Cell::from(truncate(&trace.service, 12)),
-                Cell::from(format_duration(trace.duration_ms)),
-                Cell::from(trace.span_count.to_string()),
-                Cell::from(format_number(trace.tokens as u64)),
-                Cell::from(format!("${:.2}", trace.cost_usd)),
+                Cell::from(format_duration(&app.numbers, trace.duration_ms)),
+                Cell::from(app.numbers.integer(i64::from(trace.span_count))),
+                Cell::from(app.numbers.compact(i64::from(trace.tokens))),
+                Cell::from(app.numbers.currency(trace.cost_usd, 2)),
                 Cell::from(format!("{:?}", trace.status)).style(status_style),
             ])
         })
@@ -341,11 +421,68 @@ fn draw_traces(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_stateful_widget(table, area, &mut app.traces_state.clone());
 }
 
+fn draw_sessions(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .title(format!("Sessions (last {})", app.time_range))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(MUTED));
+
+    let header = Row::new(vec!["Session", "User", "Traces", "Spans", "Tokens", "Cost", "Errors", "Last Seen"])
+        .style(Style::default().fg(PRIMARY).bold())
+        .height(1);
+
+    let rows: Vec<Row> = app
+        .sessions
+        .iter()
+        .map(|session| {
+            let errors_style = if session.error_count > 0 {
+                Style::default().fg(ERROR)
+            } else {
+                Style::default().fg(MUTED)
+            };
+
+            Row::new(vec![
+                Cell::from(truncate(&session.session_id, 16)),
+                Cell::from(truncate(session.user_id.as_deref().unwrap_or("-"), 12)),
+                Cell::from(app.numbers.integer(i64::from(session.trace_count))),
+                Cell::from(app.numbers.integer(i64::from(session.span_count))),
+                Cell::from(app.numbers.compact(session.tokens as i64)),
+                Cell::from(app.numbers.currency(session.cost_usd, 2)),
+                Cell::from(app.numbers.integer(i64::from(session.error_count))).style(errors_style),
+                Cell::from(session.last_seen.clone()).style(Style::default().fg(MUTED)),
+            ])
+        })
+        .collect();
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(18),
+            Constraint::Percentage(14),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(9),
+            Constraint::Percentage(17),
+        ],
+    )
+    .header(header)
+    .block(block)
+    .highlight_style(Style::default().bg(Color::DarkGray));
+
+    frame.render_stateful_widget(table, area, &mut app.sessions_state.clone());
+}
+
 fn draw_costs(frame: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(8), Constraint::Min(10)])
         .split(area);
+    let top = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(chunks[0]);
 
     // Summary
     let summary_block = Block::default()
@@ -360,20 +497,20 @@ fn draw_costs(frame: &mut Frame, app: &App, area: Rect) {
     let summary_text = vec![
         Line::from(vec![
             Span::raw("Total Cost: "),
-            Span::styled(format!("${:.2}", total_cost), Style::default().fg(SUCCESS).bold()),
+            Span::styled(app.numbers.currency(total_cost, 2), Style::default().fg(SUCCESS).bold()),
         ]),
         Line::from(vec![
             Span::raw("Total Tokens: "),
-            Span::styled(format_number(total_tokens), Style::default().fg(PRIMARY)),
+            Span::styled(app.numbers.compact(total_tokens as i64), Style::default().fg(PRIMARY)),
         ]),
         Line::from(vec![
             Span::raw("Total Calls: "),
-            Span::styled(format_number(total_calls), Style::default().fg(SECONDARY)),
+            Span::styled(app.numbers.compact(total_calls as i64), Style::default().fg(SECONDARY)),
         ]),
         Line::from(vec![
             Span::raw("Avg Cost/Call: "),
             Span::styled(
-                format!("${:.4}", if total_calls > 0 { total_cost / total_calls as f64 } else { 0.0 }),
+                app.numbers.currency(if total_calls > 0 { total_cost / total_calls as f64 } else { 0.0 }, 4),
                 Style::default().fg(WARNING),
             ),
         ]),
@@ -383,7 +520,8 @@ fn draw_costs(frame: &mut Frame, app: &App, area: Rect) {
         .block(summary_block)
         .wrap(Wrap { trim: true });
 
-    frame.render_widget(summary, chunks[0]);
+    frame.render_widget(summary, top[0]);
+    draw_token_efficiency(frame, app, top[1]);
 
     // Detail table
     let detail_block = Block::default()
@@ -408,10 +546,10 @@ fn draw_costs(frame: &mut Frame, app: &App, area: Rect) {
             Row::new(vec![
                 Cell::from(cost.model.clone()),
                 Cell::from(cost.provider.clone()),
-                Cell::from(format_number(cost.tokens)),
-                Cell::from(cost.call_count.to_string()),
-                Cell::from(format!("${:.2}", cost.cost_usd)),
-                Cell::from(format!("{:.1}%", percentage)),
+                Cell::from(app.numbers.compact(cost.tokens as i64)),
+                Cell::from(app.numbers.integer(cost.call_count as i64)),
+                Cell::from(app.numbers.currency(cost.cost_usd, 2)),
+                Cell::from(app.numbers.percent(percentage, 1)),
             ])
         })
         .collect();
@@ -439,8 +577,12 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
         .constraints([Constraint::Length(3), Constraint::Min(10)])
         .split(area);
 
-    // Alert summary
-    let active_count = app.alerts.iter().filter(|a| a.status == "active").count();
+    // Alerts that fired together count once, as their incident
+    let incidents = app.alert_incidents();
+    let active_count = incidents
+        .iter()
+        .filter(|group| group.iter().any(|a| a.status == "active"))
+        .count();
     let summary_style = if active_count > 0 {
         Style::default().fg(WARNING)
     } else {
@@ -448,7 +590,7 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
     };
 
     let summary_text = if active_count > 0 {
-        format!("⚠ {} active alert(s)", active_count)
+        format!("⚠ {} active incident(s)", active_count)
     } else {
         "✓ No active alerts".to_string()
     };
@@ -470,21 +612,43 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
         .borders(Borders::ALL)
         .border_style(Style::default().fg(MUTED));
 
-    let header = Row::new(vec!["Rule", "Severity", "Message", "Triggered", "Status"])
+    let header = Row::new(vec!["Rule", "Severity", "Message", "Triggered", "Status", "Owner"])
         .style(Style::default().fg(PRIMARY).bold())
         .height(1);
 
-    let rows: Vec<Row> = app
-        .alerts
+    let rows: Vec<Row> = incidents
         .iter()
-        .map(|alert| {
-            let severity_style = match alert.severity.as_str() {
+        .map(|group| {
+            let alert = group[0];
+            let severity = group
+                .iter()
+                .map(|a| a.severity.as_str())
+                .max_by_key(|s| severity_rank(s))
+                .unwrap_or_default();
+            let status = if group.iter().any(|a| a.status == "active") {
+                "active"
+            } else {
+                alert.status.as_str()
+            };
+            // Whoever is handling the incident, else whoever acknowledged it
+            let owner = group
+                .iter()
+                .find_map(|a| a.assignee.as_ref())
+                .or_else(|| group.iter().find_map(|a| a.acknowledged_by.as_ref()))
+                .map_or("-", String::as_str);
+            let rule = if group.len() > 1 {
+                format!("{} +{}", alert.rule_name, group.len() - 1)
+            } else {
+                alert.rule_name.clone()
+            };
+
+            let severity_style = match severity {
                 "critical" => Style::default().fg(ERROR).bold(),
                 "warning" => Style::default().fg(WARNING),
                 _ => Style::default().fg(MUTED),
             };
 
-            let status_style = match alert.status.as_str() {
+            let status_style = match status {
                 "active" => Style::default().fg(ERROR),
                 "acknowledged" => Style::default().fg(WARNING),
                 "resolved" => Style::default().fg(SUCCESS),
@@ -492,11 +656,12 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
             };
 
             Row::new(vec![
-                Cell::from(alert.rule_name.clone()),
-                Cell::from(alert.severity.clone()).style(severity_style),
+                Cell::from(rule),
+                Cell::from(severity.to_string()).style(severity_style),
                 Cell::from(truncate(&alert.message, 40)),
                 Cell::from(alert.triggered_at.clone()),
-                Cell::from(alert.status.clone()).style(status_style),
+                Cell::from(status.to_string()).style(status_style),
+                Cell::from(owner.to_string()),
             ])
         })
         .collect();
@@ -505,10 +670,11 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
         rows,
         [
             Constraint::Percentage(18),
+            Constraint::Percentage(10),
+            Constraint::Percentage(34),
+            Constraint::Percentage(13),
+            Constraint::Percentage(13),
             Constraint::Percentage(12),
-            Constraint::Percentage(40),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
         ],
     )
     .header(header)
@@ -518,6 +684,15 @@ fn draw_alerts(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_stateful_widget(table, area, &mut app.alerts_state.clone());
 }
 
+/// Ordering of alert severities, least severe first
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "critical" => 2,
+        "warning" => 1,
+        _ => 0,
+    }
+}
+
 fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -534,11 +709,18 @@ fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
     let cursor = if app.search_focused { "▌" } else { "" };
     let search_text = format!("🔍 {}{}", app.search_query, cursor);
 
+    let title = match app.active_view.and_then(|i| app.saved_views.get(i)) {
+        Some(view) if !app.search_focused => format!("Search: view '{}' (v for next view)", view.name),
+        _ if app.search_focused => "Search (Enter to search, Esc to cancel)".to_string(),
+        _ if !app.saved_views.is_empty() => "Search (/ to focus, v to load a saved view)".to_string(),
+        _ => "Search (/ to focus)".to_string(),
+    };
+
     let search_input = Paragraph::new(search_text)
         .style(search_style)
         .block(
             Block::default()
-                .title(if app.search_focused { "Search (Enter to search, Esc to cancel)" } else { "Search (/ to focus)" })
+                .title(title)
                 .borders(Borders::ALL)
                 .border_style(search_style),
         );
@@ -552,7 +734,7 @@ fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
             Line::from(""),
             Line::from("  service:my-agent     Filter by service name"),
             Line::from("  model:claude-opus    Filter by model"),
-            Line::from("  status:error         Filter by status (ok, error)"),
+            Line::from("  status:error         Filter by status (ok, error, in_progress)"),
             Line::from("  duration:>1000       Filter by duration (ms)"),
             Line::from("  cost:>0.1            Filter by cost (USD)"),
             Line::from("  operation:llm_call   Filter by operation name"),
@@ -595,8 +777,8 @@ fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
                     Cell::from(truncate(&trace.trace_id, 12)),
                     Cell::from(truncate(&trace.operation, 20)),
                     Cell::from(truncate(&trace.service, 15)),
-                    Cell::from(format_duration(trace.duration_ms)),
-                    Cell::from(format!("${:.2}", trace.cost_usd)),
+                    Cell::from(format_duration(&app.numbers, trace.duration_ms)),
+                    Cell::from(app.numbers.currency(trace.cost_usd, 2)),
                     Cell::from(format!("{:?}", trace.status)).style(status_style),
                 ])
             })
@@ -657,13 +839,17 @@ fn draw_help_overlay(frame: &mut Frame) {
         Line::from(""),
         Line::from("Navigation:").style(Style::default().fg(SECONDARY)),
         Line::from("  Tab / Shift+Tab    Switch between tabs"),
-        Line::from("  1-5                Jump to specific tab"),
+        Line::from("  1-6                Jump to specific tab"),
         Line::from("  j/k or ↑/↓         Navigate lists"),
         Line::from("  Enter              Select item"),
         Line::from(""),
         Line::from("Search:").style(Style::default().fg(SECONDARY)),
         Line::from("  /                  Focus search"),
         Line::from("  Esc                Cancel search"),
+        Line::from("  v                  Load the next saved view"),
+        Line::from(""),
+        Line::from("Traces:").style(Style::default().fg(SECONDARY)),
+        Line::from("  n                  Load the next page"),
         Line::from(""),
         Line::from("Alerts:").style(Style::default().fg(SECONDARY)),
         Line::from("  a                  Acknowledge selected alert"),
@@ -697,23 +883,13 @@ fn truncate(s: &str, max_len: usize) -> String {
     }
 }
 
-fn format_number(n: u64) -> String {
-    if n >= 1_000_000 {
-        format!("{:.1}M", n as f64 / 1_000_000.0)
-    } else if n >= 1_000 {
-        format!("{:.1}K", n as f64 / 1_000.0)
-    } else {
-        n.to_string()
-    }
-}
-
-fn format_duration(ms: f64) -> String {
+fn format_duration(numbers: &NumberFormat, ms: f64) -> String {
     if ms >= 60_000.0 {
-        format!("{:.1}m", ms / 60_000.0)
+        format!("{}m", numbers.decimal(ms / 60_000.0, 1))
     } else if ms >= 1_000.0 {
-        format!("{:.1}s", ms / 1_000.0)
+        format!("{}s", numbers.decimal(ms / 1_000.0, 1))
     } else {
-        format!("{:.0}ms", ms)
+        format!("{}ms", numbers.decimal(ms, 0))
     }
 }
 
@@ -0,0 +1,175 @@
+//! Saved views
+//!
+//! A view is a named set of trace filters and a sort order, so the filter
+//! combinations people keep re-typing during an investigation can be shared
+//! and loaded by name from the API, `agenttrace traces list --view` and the
+//! TUI's Search tab.
+
+use chrono::Utc;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::models::{SavedView, SavedViewInput};
+
+const VIEW_COLUMNS: &str = "id, name, description, filters, sort, owner, created_at, updated_at";
+
+/// Repository for saved views
+#[derive(Clone)]
+pub struct ViewRepository {
+    pool: PgPool,
+}
+
+impl ViewRepository {
+    /// Create a new view repository
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// List views by name, optionally only one owner's
+    pub async fn list(&self, owner: Option<&str>) -> Result<Vec<SavedView>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM saved_views WHERE ($1::text IS NULL OR owner = $1) ORDER BY name",
+            VIEW_COLUMNS
+        ))
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter().map(row_to_view).collect()
+    }
+
+    /// Get a view by ID, or by name when `key` isn't a UUID
+    pub async fn get(&self, key: &str) -> Result<Option<SavedView>> {
+        let row = match key.parse::<Uuid>() {
+            Ok(id) => {
+                sqlx::query(&format!("SELECT {} FROM saved_views WHERE id = $1", VIEW_COLUMNS))
+                    .bind(id)
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+            Err(_) => {
+                sqlx::query(&format!("SELECT {} FROM saved_views WHERE name = $1", VIEW_COLUMNS))
+                    .bind(key)
+                    .fetch_optional(&self.pool)
+                    .await?
+            }
+        };
+
+        row.as_ref().map(row_to_view).transpose()
+    }
+
+    /// Create a view
+    pub async fn create(&self, input: SavedViewInput) -> Result<SavedView> {
+        input.validate()?;
+
+        let now = Utc::now();
+        let view = SavedView {
+            id: Uuid::new_v4(),
+            name: input.name.trim().to_string(),
+            description: input.description,
+            filters: input.filters,
+            sort: input.sort,
+            owner: input.owner,
+            created_at: now,
+            updated_at: now,
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO saved_views (id, name, description, filters, sort, owner, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+        )
+        .bind(view.id)
+        .bind(&view.name)
+        .bind(&view.description)
+        .bind(serde_json::to_value(&view.filters)?)
+        .bind(view.sort.as_ref().map(serde_json::to_value).transpose()?)
+        .bind(&view.owner)
+        .bind(view.created_at)
+        .bind(view.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &view.name))?;
+
+        Ok(view)
+    }
+
+    /// Replace a view's name, description, filters, sort and owner
+    ///
+    /// Returns None if no view matches `key`.
+    pub async fn update(&self, key: &str, input: SavedViewInput) -> Result<Option<SavedView>> {
+        input.validate()?;
+
+        let Some(existing) = self.get(key).await? else {
+            return Ok(None);
+        };
+        let name = input.name.trim().to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE saved_views SET
+                name = $2,
+                description = $3,
+                filters = $4,
+                sort = $5,
+                owner = $6,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(existing.id)
+        .bind(&name)
+        .bind(&input.description)
+        .bind(serde_json::to_value(&input.filters)?)
+        .bind(input.sort.as_ref().map(serde_json::to_value).transpose()?)
+        .bind(&input.owner)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| name_conflict(e, &name))?;
+
+        self.get(&existing.id.to_string()).await
+    }
+
+    /// Delete a view by ID or name
+    pub async fn delete(&self, key: &str) -> Result<bool> {
+        let Some(existing) = self.get(key).await? else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query("DELETE FROM saved_views WHERE id = $1")
+            .bind(existing.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Report a duplicate name as a validation error
+fn name_conflict(e: sqlx::Error, name: &str) -> Error {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            Error::validation(format!("A view named '{}' already exists", name))
+        }
+        _ => e.into(),
+    }
+}
+
+fn row_to_view(row: &PgRow) -> Result<SavedView> {
+    let filters: serde_json::Value = row.try_get("filters")?;
+    let sort: Option<serde_json::Value> = row.try_get("sort")?;
+
+    Ok(SavedView {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        filters: serde_json::from_value(filters)?,
+        sort: sort.map(serde_json::from_value).transpose()?,
+        owner: row.try_get("owner")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
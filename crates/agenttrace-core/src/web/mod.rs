@@ -0,0 +1,442 @@
+//! Web dashboard server
+//!
+//! Serves the exported Next.js dashboard and proxies `/api` and `/health` to
+//! the collector's HTTP API, so the dashboard and its API share an origin.
+//! WebSocket upgrades, such as `/api/v1/stream/ws`, are passed through to
+//! the API when it is reached over plain `http://`.
+//! Assets come from a directory (`agenttrace web --static-dir`) or, with the
+//! `embedded-dashboard` feature, from the binary itself.
+//!
+//! Paths without a file fall back the way the static export expects: `x`
+//! serves `x.html` or `x/index.html`, a path under a dynamic route such as
+//! `/traces/<id>` serves that route's `_` placeholder page, and anything else
+//! serves `index.html` so client-side routing can take over.
+
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use axum::Router;
+use hyper_util::rt::TokioIo;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::api::{ApiError, ErrorCode};
+use crate::error::{Error, Result};
+
+/// Largest request body forwarded to the API
+const MAX_PROXY_BODY: usize = 64 * 1024 * 1024;
+
+/// Page name used for a dynamic route's single pre-rendered page
+const DYNAMIC_PLACEHOLDER: &str = "_";
+
+/// Headers that apply to one connection and aren't forwarded
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+];
+
+#[cfg(feature = "embedded-dashboard")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "../../dashboard/out"]
+struct EmbeddedDashboard;
+
+/// Where dashboard assets are read from
+#[derive(Debug, Clone)]
+pub enum Assets {
+    /// An exported dashboard on disk
+    Dir(PathBuf),
+    /// The dashboard embedded at build time
+    #[cfg(feature = "embedded-dashboard")]
+    Embedded,
+}
+
+impl Assets {
+    /// Assets in a directory containing an exported dashboard
+    pub fn dir(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.join("index.html").is_file() {
+            return Err(Error::config(format!(
+                "{} has no index.html; build the dashboard with `npm run export` in dashboard/",
+                path.display()
+            )));
+        }
+        Ok(Self::Dir(path))
+    }
+
+    /// The embedded dashboard, if this build has one
+    pub fn embedded() -> Option<Self> {
+        #[cfg(feature = "embedded-dashboard")]
+        if EmbeddedDashboard::get("index.html").is_some() {
+            return Some(Self::Embedded);
+        }
+        None
+    }
+
+    /// Describe the source for logs
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Dir(path) => path.display().to_string(),
+            #[cfg(feature = "embedded-dashboard")]
+            Self::Embedded => "embedded assets".to_string(),
+        }
+    }
+
+    async fn load(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        match self {
+            Self::Dir(root) => {
+                let file = root.join(path);
+                if !file.is_file() {
+                    return None;
+                }
+                tokio::fs::read(file).await.ok().map(Cow::Owned)
+            }
+            #[cfg(feature = "embedded-dashboard")]
+            Self::Embedded => EmbeddedDashboard::get(path).map(|file| file.data),
+        }
+    }
+
+    /// Load the first file that exists for a request path
+    async fn resolve(&self, request_path: &str) -> Option<(String, Cow<'static, [u8]>)> {
+        for candidate in candidates(request_path) {
+            if let Some(data) = self.load(&candidate).await {
+                return Some((candidate, data));
+            }
+        }
+        None
+    }
+}
+
+/// Files to try for a request path, most specific first
+///
+/// Paths that try to leave the asset root only get `index.html`.
+fn candidates(request_path: &str) -> Vec<String> {
+    let path = request_path.trim_matches('/');
+    let safe = Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if path.is_empty() || !safe {
+        return vec!["index.html".to_string()];
+    }
+
+    let mut files = vec![
+        path.to_string(),
+        format!("{}.html", path),
+        format!("{}/index.html", path),
+    ];
+    if let Some((parent, _)) = path.rsplit_once('/') {
+        files.push(format!("{}/{}.html", parent, DYNAMIC_PLACEHOLDER));
+    }
+    files.push("index.html".to_string());
+    files
+}
+
+struct WebState {
+    assets: Assets,
+    api_url: String,
+    client: reqwest::Client,
+}
+
+/// Dashboard server
+pub struct WebServer {
+    state: Arc<WebState>,
+}
+
+impl WebServer {
+    /// Create a server for `assets`, proxying API requests to `api_url`
+    /// (e.g. `http://127.0.0.1:8080`)
+    pub fn new(assets: Assets, api_url: impl Into<String>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(5))
+            .build()
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        Ok(Self {
+            state: Arc::new(WebState {
+                assets,
+                api_url: api_url.into().trim_end_matches('/').to_string(),
+                client,
+            }),
+        })
+    }
+
+    /// Build the router
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/health", any(proxy))
+            .route("/api/*path", any(proxy))
+            .fallback(static_asset)
+            .with_state(self.state.clone())
+    }
+
+    /// Serve until the process exits
+    pub async fn serve(self, addr: &str) -> Result<()> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        info!(
+            "Web dashboard listening on {} (assets: {}, API: {})",
+            addr,
+            self.state.assets.describe(),
+            self.state.api_url
+        );
+
+        axum::serve(listener, self.router())
+            .await
+            .map_err(|e| Error::internal(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+async fn static_asset(State(state): State<Arc<WebState>>, uri: Uri) -> Response {
+    let Some((file, data)) = state.assets.resolve(uri.path()).await else {
+        return (StatusCode::NOT_FOUND, "Dashboard assets not found").into_response();
+    };
+
+    let content_type = mime_guess::from_path(&file).first_or_octet_stream();
+    // Exported bundles are content-hashed; pages must be revalidated
+    let cache_control = if file.starts_with("_next/static/") {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type.as_ref().to_string()),
+            (header::CACHE_CONTROL, cache_control.to_string()),
+        ],
+        Body::from(data),
+    )
+        .into_response()
+}
+
+/// Forward a request to the collector's API, streaming the response back
+/// so server-sent event streams work through the proxy
+async fn proxy(State(state): State<Arc<WebState>>, request: Request) -> Response {
+    let result = if request.headers().contains_key(header::UPGRADE) {
+        forward_upgrade(&state, request).await
+    } else {
+        forward(&state, request).await
+    };
+    match result {
+        Ok(response) => response,
+        Err(message) => {
+            warn!("API proxy error: {}", message);
+            let mut error = ApiError::new(ErrorCode::Unavailable, message);
+            error.status = StatusCode::BAD_GATEWAY;
+            error.into_response()
+        }
+    }
+}
+
+async fn forward(state: &WebState, request: Request) -> std::result::Result<Response, String> {
+    let (parts, body) = request.into_parts();
+    let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+    let url = format!("{}{}", state.api_url, path);
+
+    let method = reqwest::Method::from_bytes(parts.method.as_str().as_bytes()).map_err(|e| e.to_string())?;
+    let body = to_bytes(body, MAX_PROXY_BODY).await.map_err(|e| e.to_string())?;
+
+    let mut upstream = state.client.request(method, &url).body(body);
+    for (name, value) in &parts.headers {
+        if !is_hop_by_hop(name.as_str()) {
+            upstream = upstream.header(name.as_str(), value.as_bytes());
+        }
+    }
+
+    let resp = upstream
+        .send()
+        .await
+        .map_err(|e| format!("API at {} is unreachable: {}", state.api_url, e))?;
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).map_err(|e| e.to_string())?;
+    let mut response = Response::builder().status(status);
+    for (name, value) in resp.headers() {
+        if is_hop_by_hop(name.as_str()) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_str().as_bytes()),
+            HeaderValue::from_bytes(value.as_bytes()),
+        ) {
+            response = response.header(name, value);
+        }
+    }
+
+    response
+        .body(Body::from_stream(resp.bytes_stream()))
+        .map_err(|e| e.to_string())
+}
+
+/// Forward a protocol upgrade such as a WebSocket handshake to the API and,
+/// once the API switches protocols, copy bytes both ways until either side
+/// closes
+async fn forward_upgrade(state: &WebState, mut request: Request) -> std::result::Result<Response, String> {
+    let api = url::Url::parse(&state.api_url).map_err(|e| e.to_string())?;
+    if api.scheme() != "http" {
+        return Err(format!("WebSocket proxying needs an http:// API URL, not {}", state.api_url));
+    }
+    let host = api.host_str().unwrap_or("localhost");
+    let authority = match api.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+
+    let stream = TcpStream::connect((host, api.port_or_known_default().unwrap_or(80)))
+        .await
+        .map_err(|e| format!("API at {} is unreachable: {}", state.api_url, e))?;
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.with_upgrades().await {
+            debug!("API upgrade connection closed: {}", e);
+        }
+    });
+
+    let downstream = hyper::upgrade::on(&mut request);
+    let (parts, _) = request.into_parts();
+    let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+
+    // Connection and Upgrade are what ask the API to switch protocols
+    let mut upstream = axum::http::Request::builder()
+        .method(parts.method)
+        .uri(format!("{}{}", api.path().trim_end_matches('/'), path))
+        .header(header::HOST, authority)
+        .header(header::CONNECTION, "upgrade");
+    for (name, value) in &parts.headers {
+        if !is_hop_by_hop(name.as_str()) || name == header::UPGRADE {
+            upstream = upstream.header(name, value);
+        }
+    }
+    let upstream = upstream.body(Body::empty()).map_err(|e| e.to_string())?;
+
+    let mut response = sender.send_request(upstream).await.map_err(|e| e.to_string())?;
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        // The API turned the upgrade down; pass its answer on
+        return Ok(response.map(Body::new));
+    }
+
+    let upstream = hyper::upgrade::on(&mut response);
+    tokio::spawn(async move {
+        match tokio::try_join!(downstream, upstream) {
+            Ok((downstream, upstream)) => {
+                let (mut downstream, mut upstream) = (TokioIo::new(downstream), TokioIo::new(upstream));
+                if let Err(e) = tokio::io::copy_bidirectional(&mut downstream, &mut upstream).await {
+                    debug!("Proxied upgrade closed: {}", e);
+                }
+            }
+            Err(e) => warn!("API proxy upgrade failed: {}", e),
+        }
+    });
+
+    // The API's 101 carries the headers that complete the handshake
+    Ok(response.map(|_| Body::empty()))
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        assert_eq!(candidates("/"), vec!["index.html"]);
+        assert_eq!(
+            candidates("/metrics"),
+            vec!["metrics", "metrics.html", "metrics/index.html", "index.html"]
+        );
+        assert_eq!(
+            candidates("/traces/abc123"),
+            vec![
+                "traces/abc123",
+                "traces/abc123.html",
+                "traces/abc123/index.html",
+                "traces/_.html",
+                "index.html",
+            ]
+        );
+        assert_eq!(candidates("/../etc/passwd"), vec!["index.html"]);
+    }
+
+    async fn spawn(router: Router) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_proxies_websocket_upgrades() {
+        use axum::extract::ws::WebSocketUpgrade;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // An API that echoes the first message back
+        let api = Router::new().route(
+            "/api/v1/stream/ws",
+            any(|ws: WebSocketUpgrade| async {
+                ws.on_upgrade(|mut socket| async move {
+                    if let Some(Ok(message)) = socket.recv().await {
+                        let _ = socket.send(message).await;
+                    }
+                })
+            }),
+        );
+        let api_addr = spawn(api).await;
+
+        let assets = std::env::temp_dir().join(format!("agenttrace-web-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&assets).unwrap();
+        std::fs::write(assets.join("index.html"), "<html></html>").unwrap();
+        let web = WebServer::new(Assets::dir(&assets).unwrap(), format!("http://{}", api_addr)).unwrap();
+        let web_addr = spawn(web.router()).await;
+
+        let mut stream = TcpStream::connect(web_addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /api/v1/stream/ws HTTP/1.1\r\nHost: dashboard\r\nConnection: Upgrade\r\n\
+                  Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap().to_lowercase();
+        assert!(head.starts_with("http/1.1 101"), "{}", head);
+        assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="), "{}", head);
+
+        // A masked text frame from the client, echoed back unmasked
+        let mask = [1u8, 2, 3, 4];
+        let mut frame = vec![0x81, 0x80 | 2];
+        frame.extend(mask);
+        frame.extend(b"hi".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        stream.write_all(&frame).await.unwrap();
+
+        let mut echo = [0u8; 4];
+        stream.read_exact(&mut echo).await.unwrap();
+        assert_eq!(echo, [0x81, 2, b'h', b'i']);
+
+        std::fs::remove_dir_all(assets).unwrap();
+    }
+}
@@ -0,0 +1,22 @@
+//! Error types for the SDK
+
+use thiserror::Error;
+
+/// Result type alias
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// SDK errors
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Collector endpoint is not a valid URL
+    #[error("Invalid collector endpoint '{endpoint}': {message}")]
+    InvalidEndpoint { endpoint: String, message: String },
+
+    /// HTTP client could not be created
+    #[error("HTTP client error: {0}")]
+    Client(String),
+
+    /// A tracer was built outside a Tokio runtime
+    #[error("Tracers must be built within a Tokio runtime")]
+    NoRuntime,
+}
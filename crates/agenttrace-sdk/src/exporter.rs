@@ -0,0 +1,132 @@
+//! Background task batching spans to the collector
+//!
+//! Batches are posted to `/api/v1/spans/batch`. Connection errors, 429s and
+//! 5xx responses are retried with exponential backoff; other responses mean
+//! the spans won't be accepted, so the batch is dropped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+use tracing::{debug, warn};
+
+use crate::span::SpanData;
+use crate::tracer::Command;
+
+/// Wait before the first retry; doubled for each one after
+const INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Longest wait between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Body of a batch ingest request
+#[derive(Serialize)]
+struct BatchRequest<'a> {
+    spans: &'a [SpanData],
+}
+
+pub(crate) struct Exporter {
+    pub(crate) client: reqwest::Client,
+    pub(crate) url: Url,
+    pub(crate) batch_size: usize,
+    pub(crate) flush_interval: Duration,
+    pub(crate) max_retries: u32,
+    pub(crate) dropped: Arc<AtomicU64>,
+}
+
+impl Exporter {
+    /// Send queued spans until every tracer is dropped
+    pub(crate) async fn run(self, mut rx: mpsc::Receiver<Command>) {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut flush = interval(self.flush_interval.max(Duration::from_millis(1)));
+        flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                command = rx.recv() => match command {
+                    Some(Command::Export(span)) => {
+                        batch.push(*span);
+                        if batch.len() >= self.batch_size {
+                            self.send(&mut batch).await;
+                        }
+                    }
+                    Some(Command::Flush(done)) => {
+                        self.send(&mut batch).await;
+                        let _ = done.send(());
+                    }
+                    None => {
+                        self.send(&mut batch).await;
+                        break;
+                    }
+                },
+                _ = flush.tick() => self.send(&mut batch).await,
+            }
+        }
+    }
+
+    /// Post a batch, retrying transient failures, and clear it
+    async fn send(&self, batch: &mut Vec<SpanData>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let request = BatchRequest { spans: batch };
+        let mut attempt = 0;
+        let error = loop {
+            let error = match self.client.post(self.url.clone()).json(&request).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Sent {} spans to {}", batch.len(), self.url);
+                    batch.clear();
+                    return;
+                }
+                Ok(response) if !is_retryable(response.status()) => {
+                    break format!("collector returned {}", response.status());
+                }
+                Ok(response) => format!("collector returned {}", response.status()),
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= self.max_retries {
+                break error;
+            }
+            attempt += 1;
+            sleep(backoff(attempt)).await;
+        };
+
+        warn!("Dropping {} spans: {}", batch.len(), error);
+        self.dropped.fetch_add(batch.len() as u64, Ordering::Relaxed);
+        batch.clear();
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Wait before retry `attempt`, counting from 1
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        assert_eq!(backoff(1), Duration::from_millis(200));
+        assert_eq!(backoff(2), Duration::from_millis(400));
+        assert_eq!(backoff(4), Duration::from_millis(1600));
+        assert_eq!(backoff(40), MAX_BACKOFF);
+
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
+}
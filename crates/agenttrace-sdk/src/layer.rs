@@ -0,0 +1,254 @@
+//! `tracing-subscriber` layer sending instrumented spans to AgentTrace
+//!
+//! Each `tracing` span becomes an AgentTrace span when it closes, parented
+//! on the nearest enclosing span that was converted. Well-known fields fill
+//! in the matching span fields, by their own name or the OpenTelemetry
+//! GenAI convention:
+//!
+//! | Field | Span field |
+//! |-------|------------|
+//! | `model`, `gen_ai.request.model` | `model_name` |
+//! | `provider`, `gen_ai.system` | `model_provider` |
+//! | `tokens_in`, `gen_ai.usage.input_tokens` | `tokens_in` |
+//! | `tokens_out`, `gen_ai.usage.output_tokens` | `tokens_out` |
+//! | `tool`, `tool_name` | `tool_name` |
+//! | `session_id`, `user_id` | `session_id`, `user_id` |
+//! | `prompt`, `completion` | `prompt_preview`, `completion_preview` |
+//!
+//! Other fields become attributes. An `ERROR` event inside a span marks it
+//! failed with the event's message.
+
+use std::fmt;
+
+use serde_json::Value;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::span::{SpanData, SpanStatus};
+use crate::tracer::Tracer;
+
+/// Targets whose spans are never converted: this SDK and the HTTP stack it
+/// sends with, whose spans would otherwise be exported in a loop
+const IGNORED_TARGETS: &[&str] = &[
+    "agenttrace_sdk",
+    "reqwest",
+    "hyper",
+    "h2",
+    "tower",
+    "tokio",
+    "rustls",
+    "want",
+    "mio",
+];
+
+/// Converts `tracing` spans into AgentTrace spans
+pub struct AgentTraceLayer {
+    tracer: Tracer,
+}
+
+impl AgentTraceLayer {
+    /// Send converted spans through a tracer
+    pub fn new(tracer: Tracer) -> Self {
+        Self { tracer }
+    }
+}
+
+impl<S> Layer<S> for AgentTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        if is_ignored(span.metadata().target()) {
+            return;
+        }
+
+        let mut data = SpanData::new(self.tracer.service_name(), span.name());
+        let parent = span
+            .scope()
+            .skip(1)
+            .find_map(|ancestor| ancestor.extensions().get::<SpanData>().map(SpanData::context));
+        if let Some(parent) = parent {
+            data.trace_id = parent.trace_id;
+            data.parent_span_id = Some(parent.span_id);
+        }
+        attrs.record(&mut FieldVisitor(&mut data));
+
+        span.extensions_mut().insert(data);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            values.record(&mut FieldVisitor(data));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let Some(span) = ctx.event_span(event) else { return };
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else { return };
+
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        data.status = SpanStatus::Error;
+        data.status_message = message.0;
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(mut data) = span.extensions_mut().remove::<SpanData>() else { return };
+        data.finish();
+        self.tracer.export(data);
+    }
+}
+
+fn is_ignored(target: &str) -> bool {
+    IGNORED_TARGETS.iter().any(|ignored| {
+        target
+            .strip_prefix(ignored)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+/// Fills span fields from `tracing` fields
+struct FieldVisitor<'a>(&'a mut SpanData);
+
+impl FieldVisitor<'_> {
+    fn set(&mut self, field: &Field, value: Value) {
+        let data = &mut *self.0;
+        let text = || match &value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        let count = || value.as_i64().and_then(|n| i32::try_from(n).ok());
+
+        match field.name() {
+            "model" | "gen_ai.request.model" => data.model_name = Some(text()),
+            "provider" | "gen_ai.system" => data.model_provider = Some(text()),
+            "tokens_in" | "gen_ai.usage.input_tokens" => data.tokens_in = count(),
+            "tokens_out" | "gen_ai.usage.output_tokens" => data.tokens_out = count(),
+            "tool" | "tool_name" => data.tool_name = Some(text()),
+            "session_id" => data.session_id = Some(text()),
+            "user_id" => data.user_id = Some(text()),
+            "prompt" => data.prompt_preview = Some(text()),
+            "completion" => data.completion_preview = Some(text()),
+            name => {
+                data.attributes.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.set(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.set(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.set(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.set(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set(field, Value::from(format!("{:?}", value)));
+    }
+}
+
+/// Takes an event's message
+#[derive(Default)]
+struct MessageVisitor(Option<String>);
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::Command;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_layer_converts_nested_spans() {
+        let (tracer, mut rx) = Tracer::channel("agent");
+        let subscriber = tracing_subscriber::registry().with(AgentTraceLayer::new(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let run = tracing::info_span!(target: "agent", "run", session_id = "s1", attempt = 2);
+            let _entered = run.enter();
+
+            let call = tracing::info_span!(
+                target: "agent",
+                "chat",
+                gen_ai.request.model = "gpt-4o",
+                tokens_out = tracing::field::Empty
+            );
+            call.in_scope(|| {
+                call.record("tokens_out", 42);
+                tracing::error!("rate limited");
+            });
+            drop(call);
+
+            // Spans from the export path are never converted
+            tracing::info_span!(target: "hyper::client", "connect").in_scope(|| {});
+        });
+
+        let mut spans = Vec::new();
+        while let Ok(Command::Export(span)) = rx.try_recv() {
+            spans.push(span);
+        }
+        assert_eq!(spans.len(), 2);
+        let (chat, run) = (&spans[0], &spans[1]);
+
+        assert_eq!(chat.operation_name, "chat");
+        assert_eq!(chat.model_name.as_deref(), Some("gpt-4o"));
+        assert_eq!(chat.tokens_out, Some(42));
+        assert_eq!(chat.status, SpanStatus::Error);
+        assert_eq!(chat.status_message.as_deref(), Some("rate limited"));
+        assert_eq!(chat.trace_id, run.trace_id);
+        assert_eq!(chat.parent_span_id.as_deref(), Some(run.span_id.as_str()));
+
+        assert_eq!(run.status, SpanStatus::Ok);
+        assert_eq!(run.session_id.as_deref(), Some("s1"));
+        assert_eq!(run.attributes["attempt"], 2);
+        assert!(run.parent_span_id.is_none());
+    }
+
+    #[test]
+    fn test_ignored_targets() {
+        assert!(is_ignored("hyper"));
+        assert!(is_ignored("hyper::client::pool"));
+        assert!(!is_ignored("hyperion"));
+        assert!(!is_ignored("my_agent::tools"));
+    }
+}
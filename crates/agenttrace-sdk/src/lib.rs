@@ -0,0 +1,44 @@
+//! # AgentTrace SDK
+//!
+//! Rust client for sending spans to an AgentTrace collector. Spans are
+//! queued and posted in batches from a background task, with retries on
+//! transient failures.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//!
+//! use agenttrace_sdk::{TokenUsage, Tracer};
+//! use serde_json::json;
+//!
+//! # async fn run() -> agenttrace_sdk::Result<()> {
+//! let tracer = Tracer::builder("my-agent")
+//!     .endpoint("http://localhost:8080")
+//!     .build()?;
+//!
+//! let mut task = tracer.span("answer_question").session("session-42").start();
+//! task.trace_llm_call("claude-3-5-sonnet", TokenUsage::new(1200, 300), Duration::from_millis(900));
+//! task.trace_tool("web_search", json!({"q": "rust"}), json!(["..."]), Duration::from_millis(250));
+//! task.set_attribute("answered", true);
+//! task.end();
+//!
+//! // Send what is still queued before exiting
+//! tracer.flush().await;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Code already instrumented with `tracing` can be traced by adding an
+//! [`AgentTraceLayer`] to its subscriber instead.
+
+mod error;
+mod exporter;
+mod layer;
+mod span;
+mod tracer;
+
+pub use error::{Error, Result};
+pub use layer::AgentTraceLayer;
+pub use span::{ActiveSpan, SpanBuilder, SpanContext, SpanData, SpanPriority, SpanStatus, TokenUsage};
+pub use tracer::{Tracer, TracerBuilder, DEFAULT_ENDPOINT};
@@ -0,0 +1,437 @@
+//! Spans and how they are built
+//!
+//! [`SpanData`] is what the collector's ingest endpoint accepts. Spans are
+//! described with a [`SpanBuilder`] and either recorded at once, for work
+//! that already finished, or started as an [`ActiveSpan`] that is sent when
+//! it ends.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::tracer::Tracer;
+
+/// How a span ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanStatus {
+    #[default]
+    Unset,
+    Ok,
+    Error,
+    /// A streaming call still running; a later version of the span with the
+    /// same ID replaces it
+    InProgress,
+}
+
+/// How the collector treats a span under load and in retention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanPriority {
+    /// Never shed under load, and kept longest if high-priority retention
+    /// is configured
+    High,
+    #[default]
+    Normal,
+    /// Shed first under load, and pruned early if low-priority retention
+    /// is configured
+    Low,
+}
+
+/// Tokens used by an LLM call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input: i32,
+    pub output: i32,
+    /// Input tokens read from the provider's prompt cache
+    pub cached_input: Option<i32>,
+    /// Output tokens spent on hidden reasoning
+    pub reasoning: Option<i32>,
+}
+
+impl TokenUsage {
+    /// Input and output token counts
+    pub fn new(input: i32, output: i32) -> Self {
+        Self {
+            input,
+            output,
+            ..Self::default()
+        }
+    }
+}
+
+/// Identifies a span, to start children under it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+/// A span as sent to the collector
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpanData {
+    pub span_id: String,
+    pub trace_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    pub operation_name: String,
+    pub service_name: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<DateTime<Utc>>,
+    pub status: SpanStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_in: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_cached_in: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_out: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_reasoning: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_input: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_output: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_duration_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_preview: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_preview: Option<String>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub attributes: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<SpanPriority>,
+}
+
+impl SpanData {
+    /// A span starting now at the root of a new trace
+    pub fn new(service_name: impl Into<String>, operation_name: impl Into<String>) -> Self {
+        Self {
+            span_id: new_span_id(),
+            trace_id: new_trace_id(),
+            parent_span_id: None,
+            session_id: None,
+            user_id: None,
+            operation_name: operation_name.into(),
+            service_name: service_name.into(),
+            started_at: Utc::now(),
+            ended_at: None,
+            status: SpanStatus::Unset,
+            status_message: None,
+            model_name: None,
+            model_provider: None,
+            tokens_in: None,
+            tokens_cached_in: None,
+            tokens_out: None,
+            tokens_reasoning: None,
+            tool_name: None,
+            tool_input: None,
+            tool_output: None,
+            tool_duration_ms: None,
+            prompt_preview: None,
+            completion_preview: None,
+            attributes: Map::new(),
+            priority: None,
+        }
+    }
+
+    /// The span's trace and span IDs
+    pub fn context(&self) -> SpanContext {
+        SpanContext {
+            trace_id: self.trace_id.clone(),
+            span_id: self.span_id.clone(),
+        }
+    }
+
+    fn set_tokens(&mut self, tokens: TokenUsage) {
+        self.tokens_in = Some(tokens.input);
+        self.tokens_out = Some(tokens.output);
+        self.tokens_cached_in = tokens.cached_input;
+        self.tokens_reasoning = tokens.reasoning;
+    }
+
+    /// End the span now, as ok unless it already failed
+    pub(crate) fn finish(&mut self) {
+        let ended_at = Utc::now();
+        if self.tool_name.is_some() {
+            self.tool_duration_ms = (ended_at - self.started_at)
+                .num_microseconds()
+                .map(|us| us as f64 / 1000.0);
+        }
+        self.ended_at = Some(ended_at);
+        if self.status == SpanStatus::Unset {
+            self.status = SpanStatus::Ok;
+        }
+    }
+}
+
+/// 32 hex characters, as OpenTelemetry trace IDs
+pub(crate) fn new_trace_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// 16 hex characters, as OpenTelemetry span IDs
+pub(crate) fn new_span_id() -> String {
+    let mut id = Uuid::new_v4().simple().to_string();
+    id.truncate(16);
+    id
+}
+
+/// Describes a span before it is started or recorded
+#[must_use]
+pub struct SpanBuilder {
+    tracer: Tracer,
+    data: SpanData,
+}
+
+impl SpanBuilder {
+    pub(crate) fn new(tracer: Tracer, operation_name: impl Into<String>) -> Self {
+        let data = SpanData::new(tracer.service_name(), operation_name);
+        Self { tracer, data }
+    }
+
+    /// Start the span under a parent instead of in a new trace
+    pub fn child_of(mut self, parent: &SpanContext) -> Self {
+        self.data.trace_id = parent.trace_id.clone();
+        self.data.parent_span_id = Some(parent.span_id.clone());
+        self
+    }
+
+    /// Session (multi-turn conversation) the trace belongs to
+    pub fn session(mut self, session_id: impl Into<String>) -> Self {
+        self.data.session_id = Some(session_id.into());
+        self
+    }
+
+    /// End user the session belongs to
+    pub fn user(mut self, user_id: impl Into<String>) -> Self {
+        self.data.user_id = Some(user_id.into());
+        self
+    }
+
+    /// Model called; the collector prices the call from it
+    pub fn model(mut self, model_name: impl Into<String>) -> Self {
+        self.data.model_name = Some(model_name.into());
+        self
+    }
+
+    /// Provider of the model, e.g. `anthropic` or `openai`
+    pub fn provider(mut self, provider: impl Into<String>) -> Self {
+        self.data.model_provider = Some(provider.into());
+        self
+    }
+
+    pub fn tokens(mut self, tokens: TokenUsage) -> Self {
+        self.data.set_tokens(tokens);
+        self
+    }
+
+    /// Tool called, with its input
+    pub fn tool(mut self, tool_name: impl Into<String>, input: Value) -> Self {
+        self.data.tool_name = Some(tool_name.into());
+        self.data.tool_input = Some(input);
+        self
+    }
+
+    /// Prompt sent; the collector keeps a preview
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.data.prompt_preview = Some(prompt.into());
+        self
+    }
+
+    /// Completion received; the collector keeps a preview
+    pub fn completion(mut self, completion: impl Into<String>) -> Self {
+        self.data.completion_preview = Some(completion.into());
+        self
+    }
+
+    pub fn attribute(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.data.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Priority for load shedding and retention; normal if not set
+    pub fn priority(mut self, priority: SpanPriority) -> Self {
+        self.data.priority = Some(priority);
+        self
+    }
+
+    /// Start the span now; it is sent when it ends
+    pub fn start(mut self) -> ActiveSpan {
+        self.data.started_at = Utc::now();
+        ActiveSpan {
+            tracer: self.tracer,
+            data: Some(self.data),
+        }
+    }
+
+    /// Send the span as finished now after running for `duration`
+    pub fn record(mut self, duration: Duration) -> SpanContext {
+        self.data.started_at = Utc::now() - chrono::Duration::from_std(duration).unwrap_or_default();
+        self.data.finish();
+
+        let context = self.data.context();
+        self.tracer.export(self.data);
+        context
+    }
+}
+
+/// A started span, sent to the collector when it ends
+///
+/// Dropping the span ends it, as ok unless [`ActiveSpan::set_error`] was
+/// called.
+pub struct ActiveSpan {
+    tracer: Tracer,
+    data: Option<SpanData>,
+}
+
+impl ActiveSpan {
+    fn data(&mut self) -> &mut SpanData {
+        self.data.as_mut().expect("span data is only taken on drop")
+    }
+
+    pub fn context(&self) -> SpanContext {
+        self.data.as_ref().expect("span data is only taken on drop").context()
+    }
+
+    /// Describe a child span
+    pub fn child(&self, operation_name: impl Into<String>) -> SpanBuilder {
+        self.tracer.span(operation_name).child_of(&self.context())
+    }
+
+    pub fn set_tokens(&mut self, tokens: TokenUsage) {
+        self.data().set_tokens(tokens);
+    }
+
+    pub fn set_completion(&mut self, completion: impl Into<String>) {
+        self.data().completion_preview = Some(completion.into());
+    }
+
+    pub fn set_tool_output(&mut self, output: Value) {
+        self.data().tool_output = Some(output);
+    }
+
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.data().attributes.insert(key.into(), value.into());
+    }
+
+    /// Priority for load shedding and retention, e.g. raised once a run
+    /// turns out to matter
+    pub fn set_priority(&mut self, priority: SpanPriority) {
+        self.data().priority = Some(priority);
+    }
+
+    /// Mark the span as failed
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        let data = self.data();
+        data.status = SpanStatus::Error;
+        data.status_message = Some(message.into());
+    }
+
+    /// Record a finished LLM call under this span
+    pub fn trace_llm_call(&self, model: impl Into<String>, tokens: TokenUsage, duration: Duration) -> SpanContext {
+        llm_call(self.child("llm_call"), model, tokens, duration)
+    }
+
+    /// Record a finished tool call under this span
+    pub fn trace_tool(
+        &self,
+        tool_name: impl Into<String>,
+        input: Value,
+        output: Value,
+        duration: Duration,
+    ) -> SpanContext {
+        tool_call(self.child("tool_call"), tool_name, input, output, duration)
+    }
+
+    /// End the span now
+    pub fn end(self) {}
+}
+
+impl Drop for ActiveSpan {
+    fn drop(&mut self) {
+        if let Some(mut data) = self.data.take() {
+            data.finish();
+            self.tracer.export(data);
+        }
+    }
+}
+
+pub(crate) fn llm_call(
+    builder: SpanBuilder,
+    model: impl Into<String>,
+    tokens: TokenUsage,
+    duration: Duration,
+) -> SpanContext {
+    builder.model(model).tokens(tokens).record(duration)
+}
+
+pub(crate) fn tool_call(
+    builder: SpanBuilder,
+    tool_name: impl Into<String>,
+    input: Value,
+    output: Value,
+    duration: Duration,
+) -> SpanContext {
+    let mut builder = builder.tool(tool_name, input);
+    builder.data.tool_output = Some(output);
+    builder.record(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tracer::{Command, Tracer};
+
+    #[tokio::test]
+    async fn test_spans_nest_and_serialize() {
+        let (tracer, mut rx) = Tracer::channel("agent");
+
+        let mut root = tracer.span("plan").session("s1").priority(SpanPriority::Low).start();
+        let llm = root.trace_llm_call("claude-3-5-sonnet", TokenUsage::new(120, 30), Duration::from_millis(800));
+        root.set_error("gave up");
+        root.set_priority(SpanPriority::High);
+        let root_context = root.context();
+        root.end();
+
+        let Some(Command::Export(call)) = rx.recv().await else { panic!("expected the LLM call") };
+        let Some(Command::Export(plan)) = rx.recv().await else { panic!("expected the root span") };
+
+        assert_eq!(call.context(), llm);
+        assert_eq!(call.trace_id, root_context.trace_id);
+        assert_eq!(call.parent_span_id.as_deref(), Some(root_context.span_id.as_str()));
+        assert_eq!(call.status, SpanStatus::Ok);
+        assert_eq!(plan.status, SpanStatus::Error);
+        assert_eq!((plan.trace_id.len(), plan.span_id.len()), (32, 16));
+
+        let json = serde_json::to_value(&call).unwrap();
+        assert_eq!(json["model_name"], "claude-3-5-sonnet");
+        assert_eq!(json["tokens_in"], 120);
+        assert_eq!(json["service_name"], "agent");
+        assert_eq!(json["status"], "ok");
+        assert!(json.get("tool_name").is_none());
+        assert!(json.get("attributes").is_none());
+        assert!(json.get("priority").is_none());
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["session_id"], "s1");
+        assert_eq!(json["status_message"], "gave up");
+        assert_eq!(json["priority"], "high");
+    }
+}
@@ -0,0 +1,203 @@
+//! The tracer spans are created through and sent from
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::Url;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::error::{Error, Result};
+use crate::exporter::Exporter;
+use crate::span::{llm_call, tool_call, SpanBuilder, SpanContext, SpanData, TokenUsage};
+
+/// Collector the tracer sends to when no endpoint is given
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:8080";
+
+/// Work for the export task
+pub(crate) enum Command {
+    Export(Box<SpanData>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Creates spans for one service and sends them to the collector
+///
+/// Spans are queued and sent in batches from a background task, so
+/// recording a span never waits on the network. Clones share the queue.
+#[derive(Clone)]
+pub struct Tracer {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    service_name: String,
+    tx: mpsc::Sender<Command>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Tracer {
+    /// Configure a tracer for a service
+    pub fn builder(service_name: impl Into<String>) -> TracerBuilder {
+        TracerBuilder::new(service_name)
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.inner.service_name
+    }
+
+    /// Describe a span at the root of a new trace
+    pub fn span(&self, operation_name: impl Into<String>) -> SpanBuilder {
+        SpanBuilder::new(self.clone(), operation_name)
+    }
+
+    /// Record a finished LLM call as its own trace
+    pub fn trace_llm_call(&self, model: impl Into<String>, tokens: TokenUsage, duration: Duration) -> SpanContext {
+        llm_call(self.span("llm_call"), model, tokens, duration)
+    }
+
+    /// Record a finished tool call as its own trace
+    pub fn trace_tool(
+        &self,
+        tool_name: impl Into<String>,
+        input: Value,
+        output: Value,
+        duration: Duration,
+    ) -> SpanContext {
+        tool_call(self.span("tool_call"), tool_name, input, output, duration)
+    }
+
+    /// Spans dropped because the queue was full or the collector kept
+    /// failing
+    pub fn dropped_spans(&self) -> u64 {
+        self.inner.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Send every queued span now, waiting until the collector has them
+    ///
+    /// Call before the process exits so the last batch isn't lost.
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.inner.tx.send(Command::Flush(done_tx)).await.is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+
+    /// Queue a finished span for export, dropping it if the queue is full
+    pub(crate) fn export(&self, span: SpanData) {
+        if self.inner.tx.try_send(Command::Export(Box::new(span))).is_err() {
+            self.inner.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// A tracer whose queue is read by the caller instead of an export task
+    #[cfg(test)]
+    pub(crate) fn channel(service_name: &str) -> (Self, mpsc::Receiver<Command>) {
+        let (tx, rx) = mpsc::channel(100);
+        let inner = Inner {
+            service_name: service_name.to_string(),
+            tx,
+            dropped: Arc::default(),
+        };
+        (Self { inner: Arc::new(inner) }, rx)
+    }
+}
+
+/// Tracer configuration
+#[must_use]
+pub struct TracerBuilder {
+    service_name: String,
+    endpoint: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_retries: u32,
+    queue_size: usize,
+    timeout: Duration,
+}
+
+impl TracerBuilder {
+    fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            batch_size: 100,
+            flush_interval: Duration::from_secs(5),
+            max_retries: 3,
+            queue_size: 10_000,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Base URL of the collector's HTTP API
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// Spans sent per request
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Longest a span waits in a partial batch
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Times a failed batch is retried, with exponential backoff, before
+    /// it is dropped
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Spans queued before new ones are dropped
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size.max(1);
+        self
+    }
+
+    /// Timeout for each request to the collector
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Create the tracer and start its export task
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn build(self) -> Result<Tracer> {
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| Error::NoRuntime)?;
+        let url = Url::parse(&format!("{}/api/v1/spans/batch", self.endpoint.trim_end_matches('/')))
+            .map_err(|e| Error::InvalidEndpoint {
+                endpoint: self.endpoint.clone(),
+                message: e.to_string(),
+            })?;
+        let client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .build()
+            .map_err(|e| Error::Client(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel(self.queue_size);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let exporter = Exporter {
+            client,
+            url,
+            batch_size: self.batch_size,
+            flush_interval: self.flush_interval,
+            max_retries: self.max_retries,
+            dropped: dropped.clone(),
+        };
+        runtime.spawn(exporter.run(rx));
+
+        Ok(Tracer {
+            inner: Arc::new(Inner {
+                service_name: self.service_name,
+                tx,
+                dropped,
+            }),
+        })
+    }
+}
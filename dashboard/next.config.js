@@ -1,6 +1,9 @@
 /** @type {import('next').NextConfig} */
 const nextConfig = {
   reactStrictMode: true,
+  // `npm run export` writes a static build to out/ for `agenttrace web`,
+  // which serves it and proxies /api itself
+  output: process.env.NEXT_OUTPUT === 'export' ? 'export' : undefined,
   async rewrites() {
     return [
       {
@@ -10,6 +10,7 @@ import {
   Circle,
   AlertCircle,
   CheckCircle,
+  Loader2,
 } from 'lucide-react';
 import type { Span, SpanType } from '@/types';
 import { getSpanType } from '@/types';
@@ -99,7 +100,7 @@ export function SpanIcon({ span, size = 'md', showLabel = false }: SpanIconProps
 }
 
 interface StatusIconProps {
-  status: 'ok' | 'error' | 'unset';
+  status: 'ok' | 'error' | 'unset' | 'in_progress';
   size?: 'sm' | 'md' | 'lg';
 }
 
@@ -118,5 +119,9 @@ export function StatusIcon({ status, size = 'md' }: StatusIconProps) {
     return <CheckCircle className={cn(sizeClasses[size], 'text-green-500')} />;
   }
 
+  if (status === 'in_progress') {
+    return <Loader2 className={cn(sizeClasses[size], 'text-yellow-500 animate-spin')} />;
+  }
+
   return <Circle className={cn(sizeClasses[size], 'text-gray-400')} />;
 }
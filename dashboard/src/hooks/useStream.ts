@@ -11,6 +11,16 @@ interface UseStreamOptions {
   traceId?: string;
   /** Channel to subscribe to: "spans", "llm" */
   channel?: 'spans' | 'llm';
+  /** Only stream spans from this service (filtered server-side) */
+  service?: string;
+  /** Only stream spans for this model */
+  model?: string;
+  /** Only stream spans with this status */
+  status?: 'ok' | 'error' | 'unset' | 'in_progress';
+  /** Only stream spans costing at least this much (USD) */
+  minCost?: number;
+  /** Only stream spans lasting at least this long (ms) */
+  minDuration?: number;
 }
 
 interface StreamState {
@@ -22,7 +32,16 @@ interface StreamState {
 }
 
 export function useStream(options: UseStreamOptions = {}) {
-  const { autoConnect = true, traceId, channel = 'spans' } = options;
+  const {
+    autoConnect = true,
+    traceId,
+    channel = 'spans',
+    service,
+    model,
+    status,
+    minCost,
+    minDuration,
+  } = options;
   const queryClient = useQueryClient();
   const eventSourceRef = useRef<EventSource | null>(null);
   const [state, setState] = useState<StreamState>({
@@ -43,6 +62,11 @@ export function useStream(options: UseStreamOptions = {}) {
     const params = new URLSearchParams();
     if (traceId) params.set('trace_id', traceId);
     else if (channel) params.set('channel', channel);
+    if (service) params.set('service', service);
+    if (model) params.set('model', model);
+    if (status) params.set('status', status);
+    if (minCost !== undefined) params.set('min_cost', String(minCost));
+    if (minDuration !== undefined) params.set('min_duration', String(minDuration));
 
     const url = `/api/v1/stream${params.toString() ? `?${params}` : ''}`;
 
@@ -121,7 +145,7 @@ export function useStream(options: UseStreamOptions = {}) {
         error: e instanceof Error ? e.message : 'Failed to connect',
       }));
     }
-  }, [traceId, channel, queryClient]);
+  }, [traceId, channel, service, model, status, minCost, minDuration, queryClient]);
 
   const disconnect = useCallback(() => {
     if (eventSourceRef.current) {
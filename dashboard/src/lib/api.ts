@@ -1,7 +1,27 @@
-import type { Span, SpanListResponse, HealthResponse, Trace } from '@/types';
+import type { Span, SpanListResponse, HealthResponse, ServiceSummary, Trace } from '@/types';
 
 const API_BASE = '/api/v1';
 
+interface ApiErrorBody {
+  code: string;
+  message: string;
+  details?: { hint?: string } & Record<string, unknown>;
+  request_id?: string;
+}
+
+/** Failed API request, with the server's error code and request ID */
+export class ApiError extends Error {
+  constructor(
+    message: string,
+    public status: number,
+    public code?: string,
+    public requestId?: string,
+  ) {
+    super(message);
+    this.name = 'ApiError';
+  }
+}
+
 async function fetchAPI<T>(endpoint: string, options?: RequestInit): Promise<T> {
   const res = await fetch(`${API_BASE}${endpoint}`, {
     ...options,
@@ -12,7 +32,14 @@ async function fetchAPI<T>(endpoint: string, options?: RequestInit): Promise<T>
   });
 
   if (!res.ok) {
-    throw new Error(`API error: ${res.status} ${res.statusText}`);
+    // Structured API error: { error: { code, message, details, request_id } }
+    const body = await res.json().catch(() => null);
+    const error: ApiErrorBody | undefined = body?.error;
+    if (error?.message) {
+      const hint = error.details?.hint ? `. ${error.details.hint}.` : '';
+      throw new ApiError(`${error.message}${hint}`, res.status, error.code, error.request_id);
+    }
+    throw new ApiError(`API error: ${res.status} ${res.statusText}`, res.status);
   }
 
   return res.json();
@@ -37,11 +64,31 @@ export async function getSpans(params?: {
   return fetchAPI<SpanListResponse>(`/spans${query ? `?${query}` : ''}`);
 }
 
+export async function getServices(params?: {
+  since?: string;
+  until?: string;
+}): Promise<ServiceSummary[]> {
+  const searchParams = new URLSearchParams();
+  if (params?.since) searchParams.set('since', params.since);
+  if (params?.until) searchParams.set('until', params.until);
+
+  const query = searchParams.toString();
+  const response = await fetchAPI<{ services: ServiceSummary[] }>(
+    `/services${query ? `?${query}` : ''}`
+  );
+  return response.services;
+}
+
 export async function getSpan(spanId: string): Promise<Span> {
   return fetchAPI<Span>(`/spans/${spanId}`);
 }
 
-export async function getTraceSpans(traceId: string): Promise<Span[]> {
+export async function getTraceSpans(traceId: string, filter?: string): Promise<Span[]> {
+  if (filter) {
+    return fetchAPI<Span[]>(
+      `/traces/${traceId}/spans?filter=${encodeURIComponent(filter)}`
+    );
+  }
   const response = await getSpans({ trace_id: traceId, limit: 1000 });
   return response.spans;
 }
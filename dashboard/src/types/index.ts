@@ -3,17 +3,20 @@ export interface Span {
   span_id: string;
   trace_id: string;
   parent_span_id: string | null;
+  session_id: string | null;
+  user_id: string | null;
   operation_name: string;
   service_name: string;
   span_kind: 'internal' | 'client' | 'server' | 'producer' | 'consumer';
   started_at: string;
   ended_at: string | null;
   duration_ms: number | null;
-  status: 'ok' | 'error' | 'unset';
+  status: 'ok' | 'error' | 'unset' | 'in_progress';
   status_message: string | null;
   model_name: string | null;
   model_provider: string | null;
   tokens_in: number | null;
+  tokens_cached_in: number | null;
   tokens_out: number | null;
   tokens_reasoning: number | null;
   cost_usd: number | null;
@@ -61,6 +64,15 @@ export interface HealthResponse {
   version: string;
 }
 
+export interface ServiceSummary {
+  service_name: string;
+  last_seen: string;
+  span_count: number;
+  span_rate: number;
+  error_rate: number;
+  total_cost_usd: number;
+}
+
 export type SpanType = 'llm_call' | 'tool_call' | 'agent_step' | 'retrieval' | 'embedding' | 'chain' | 'custom';
 
 export function getSpanType(span: Span): SpanType {
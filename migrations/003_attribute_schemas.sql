@@ -0,0 +1,14 @@
+-- Attribute schemas registered per service
+CREATE TABLE IF NOT EXISTS attribute_schemas (
+    service_name TEXT PRIMARY KEY,
+    attributes JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER attribute_schemas_updated_at BEFORE UPDATE ON attribute_schemas
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at();
+
+-- Compliance queries look for spans tagged with schema violations
+CREATE INDEX IF NOT EXISTS idx_spans_schema_violations ON spans (service_name, started_at DESC)
+    WHERE attributes ? 'agenttrace.schema_violations';
@@ -0,0 +1,2 @@
+-- Look up spans that link to a given trace/span
+CREATE INDEX IF NOT EXISTS idx_spans_links ON spans USING GIN (links jsonb_path_ops);
@@ -0,0 +1,17 @@
+-- Native compression for span chunks that are no longer queried raw
+ALTER TABLE spans SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'service_name',
+    timescaledb.compress_orderby = 'started_at DESC, span_id'
+);
+
+ALTER TABLE span_events SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'trace_id',
+    timescaledb.compress_orderby = 'timestamp DESC, id'
+);
+
+-- Compress chunks older than a week by default; the collector adjusts these
+-- from the [compression] config on startup
+SELECT add_compression_policy('spans', INTERVAL '7 days', if_not_exists => TRUE);
+SELECT add_compression_policy('span_events', INTERVAL '7 days', if_not_exists => TRUE);
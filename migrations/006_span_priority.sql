@@ -0,0 +1,7 @@
+-- Span priority: high spans are never shed and outlive the normal retention
+-- window, low spans are shed first and pruned early
+ALTER TABLE spans ADD COLUMN IF NOT EXISTS priority VARCHAR(10) NOT NULL DEFAULT 'normal';
+
+-- Most spans are normal priority, so only index the others
+CREATE INDEX IF NOT EXISTS idx_spans_priority ON spans (priority, started_at DESC)
+    WHERE priority <> 'normal';
@@ -0,0 +1,13 @@
+-- Manifest of span partitions exported to cold storage. Each row covers one
+-- hour of spans written to a single Parquet object.
+CREATE TABLE IF NOT EXISTS span_archives (
+    id UUID PRIMARY KEY,
+    partition_start TIMESTAMPTZ NOT NULL UNIQUE,
+    partition_end TIMESTAMPTZ NOT NULL,
+    location TEXT NOT NULL,
+    span_count BIGINT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_span_archives_range ON span_archives (partition_start, partition_end);
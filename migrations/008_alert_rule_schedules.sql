@@ -0,0 +1,2 @@
+-- Optional active schedule per alert rule; rules are skipped outside it
+ALTER TABLE alert_rules ADD COLUMN IF NOT EXISTS schedule JSONB;
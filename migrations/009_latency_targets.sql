@@ -0,0 +1,2 @@
+-- Expected latency per operation, registered alongside a service's attribute schema
+ALTER TABLE attribute_schemas ADD COLUMN IF NOT EXISTS latency_targets JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,8 @@
+-- Full-text search over operation names, prompts and completions. An
+-- expression index rather than a stored tsvector column keeps compressed
+-- chunks small; queries must use the identical expression to hit it.
+CREATE INDEX IF NOT EXISTS idx_spans_text_search ON spans USING GIN ((
+    setweight(to_tsvector('english', coalesce(operation_name, '')), 'A')
+    || setweight(to_tsvector('english', coalesce(prompt_preview, '')), 'B')
+    || setweight(to_tsvector('english', coalesce(completion_preview, '')), 'B')
+));
@@ -0,0 +1,19 @@
+-- Global annotations on the timeline, such as incidents polled from LLM
+-- provider status pages. Metrics and alerts that overlap an annotation are
+-- marked so an upstream outage isn't mistaken for an agent bug.
+CREATE TABLE IF NOT EXISTS annotations (
+    id UUID PRIMARY KEY,
+    kind TEXT NOT NULL,
+    source TEXT NOT NULL,
+    external_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    impact TEXT,
+    url TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (source, external_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_annotations_window ON annotations (starts_at, ends_at);
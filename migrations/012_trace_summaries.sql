@@ -0,0 +1,66 @@
+-- Per-trace rollups kept up to date as spans are ingested, so listing traces
+-- doesn't aggregate the whole spans table. Root columns are filled in once
+-- the trace's root span arrives; later root spans don't replace it.
+CREATE TABLE IF NOT EXISTS trace_summaries (
+    trace_id VARCHAR(32) PRIMARY KEY,
+    root_id UUID,
+    root_operation VARCHAR(255),
+    service_name VARCHAR(255),
+    status VARCHAR(20),
+    started_at TIMESTAMPTZ,
+    duration_ms DOUBLE PRECISION,
+    span_count BIGINT NOT NULL DEFAULT 0,
+    error_count BIGINT NOT NULL DEFAULT 0,
+    total_tokens BIGINT NOT NULL DEFAULT 0,
+    total_cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    llm_ms DOUBLE PRECISION NOT NULL DEFAULT 0,
+    tool_ms DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_trace_summaries_started
+    ON trace_summaries (started_at DESC, root_id DESC) WHERE root_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_trace_summaries_service
+    ON trace_summaries (service_name, started_at DESC) WHERE root_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_trace_summaries_updated ON trace_summaries (updated_at);
+
+-- Backfill from spans already stored
+INSERT INTO trace_summaries (
+    trace_id, root_id, root_operation, service_name, status, started_at, duration_ms,
+    span_count, error_count, total_tokens, total_cost_usd, llm_ms, tool_ms
+)
+SELECT
+    stats.trace_id,
+    r.id,
+    r.operation_name,
+    r.service_name,
+    r.status,
+    r.started_at,
+    r.duration_ms,
+    stats.span_count,
+    stats.error_count,
+    stats.total_tokens,
+    stats.total_cost_usd,
+    stats.llm_ms,
+    stats.tool_ms
+FROM (
+    SELECT
+        trace_id,
+        COUNT(*) as span_count,
+        COUNT(*) FILTER (WHERE status = 'error') as error_count,
+        SUM(COALESCE(tokens_in, 0) + COALESCE(tokens_out, 0)) as total_tokens,
+        SUM(COALESCE(cost_usd, 0))::DOUBLE PRECISION as total_cost_usd,
+        COALESCE(SUM(duration_ms) FILTER (WHERE model_name IS NOT NULL), 0) as llm_ms,
+        COALESCE(SUM(COALESCE(tool_duration_ms, duration_ms))
+            FILTER (WHERE tool_name IS NOT NULL AND model_name IS NULL), 0) as tool_ms
+    FROM spans
+    GROUP BY trace_id
+) stats
+LEFT JOIN LATERAL (
+    SELECT id, operation_name, service_name, status, started_at, duration_ms
+    FROM spans
+    WHERE trace_id = stats.trace_id AND parent_span_id IS NULL
+    ORDER BY started_at, id
+    LIMIT 1
+) r ON TRUE
+ON CONFLICT (trace_id) DO NOTHING;
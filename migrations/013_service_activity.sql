@@ -0,0 +1,35 @@
+-- Hourly per-service activity kept up to date as spans are ingested, so the
+-- service catalog doesn't run DISTINCT over the spans hypertable
+CREATE TABLE IF NOT EXISTS service_activity (
+    bucket TIMESTAMPTZ NOT NULL,
+    service_name VARCHAR(255) NOT NULL,
+    span_count BIGINT NOT NULL DEFAULT 0,
+    error_count BIGINT NOT NULL DEFAULT 0,
+    cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    last_seen TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (service_name, bucket)
+);
+
+SELECT create_hypertable('service_activity', 'bucket',
+    chunk_time_interval => INTERVAL '7 days',
+    if_not_exists => TRUE
+);
+
+CREATE INDEX IF NOT EXISTS idx_service_activity_bucket ON service_activity (bucket DESC);
+
+-- Backfill from spans already stored
+INSERT INTO service_activity (bucket, service_name, span_count, error_count, cost_usd, last_seen)
+SELECT
+    date_trunc('hour', started_at),
+    service_name,
+    COUNT(*),
+    COUNT(*) FILTER (WHERE status = 'error'),
+    COALESCE(SUM(cost_usd), 0)::DOUBLE PRECISION,
+    MAX(started_at)
+FROM spans
+GROUP BY 1, 2
+ON CONFLICT (service_name, bucket) DO NOTHING;
+
+-- Kept as long as the other aggregates; the collector adjusts this from the
+-- [retention] config on startup
+SELECT add_retention_policy('service_activity', INTERVAL '395 days', if_not_exists => TRUE);
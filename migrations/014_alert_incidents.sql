@@ -0,0 +1,22 @@
+-- Alert events that fire together for the same service are grouped into an
+-- incident, so one outage tripping several rules shows up, and is
+-- acknowledged, as a single problem
+CREATE TABLE IF NOT EXISTS alert_incidents (
+    id UUID PRIMARY KEY,
+    service_name TEXT,
+    status TEXT NOT NULL DEFAULT 'active',
+    severity TEXT NOT NULL DEFAULT 'warning',
+    started_at TIMESTAMPTZ NOT NULL,
+    last_event_at TIMESTAMPTZ NOT NULL,
+    resolved_at TIMESTAMPTZ,
+    event_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_incidents_started_at ON alert_incidents (started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_alert_incidents_open
+    ON alert_incidents (service_name, last_event_at DESC) WHERE status <> 'resolved';
+
+ALTER TABLE alert_events ADD COLUMN IF NOT EXISTS incident_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_alert_events_incident
+    ON alert_events (incident_id, triggered_at DESC) WHERE incident_id IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Named trace filters, so the filter combinations people reach for during
+-- an investigation can be loaded by name from the API, CLI and TUI
+CREATE TABLE IF NOT EXISTS saved_views (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    filters JSONB NOT NULL DEFAULT '{}'::jsonb,
+    sort JSONB,
+    owner TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_saved_views_owner ON saved_views (owner, name);
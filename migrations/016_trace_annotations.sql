@@ -0,0 +1,15 @@
+-- Notes people leave on a trace or one of its spans during incident review,
+-- e.g. "root cause: tool timeout", with labels to find them again
+CREATE TABLE IF NOT EXISTS trace_annotations (
+    id UUID PRIMARY KEY,
+    trace_id TEXT NOT NULL,
+    span_id TEXT,
+    author TEXT NOT NULL,
+    text TEXT NOT NULL,
+    labels TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_trace_annotations_trace ON trace_annotations (trace_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_trace_annotations_labels ON trace_annotations USING GIN (labels);
@@ -0,0 +1,9 @@
+-- Evaluator state for each rule, so a collector restart keeps counting
+-- consecutive breaches and remembers how bad active alerts got instead of
+-- starting over
+CREATE TABLE IF NOT EXISTS alert_rule_state (
+    rule_id UUID PRIMARY KEY REFERENCES alert_rules(id) ON DELETE CASCADE,
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    peak_value DOUBLE PRECISION,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
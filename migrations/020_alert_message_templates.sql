@@ -0,0 +1,2 @@
+-- Optional Handlebars template per alert rule for its notification message
+ALTER TABLE alert_rules ADD COLUMN IF NOT EXISTS message_template TEXT;
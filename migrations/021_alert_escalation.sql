@@ -0,0 +1,3 @@
+-- Later notification steps per alert rule, sent while an alert stays
+-- unacknowledged: [{"after_minutes": 15, "channels": [...]}, ...]
+ALTER TABLE alert_rules ADD COLUMN IF NOT EXISTS escalation JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,33 @@
+-- Spend budgets per calendar period, optionally scoped to a service, model
+-- or project, and the thresholds each has crossed in each period
+CREATE TABLE IF NOT EXISTS budgets (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    service_name VARCHAR(255),
+    model_name VARCHAR(100),
+    project TEXT,
+    period VARCHAR(20) NOT NULL CHECK (period IN ('weekly', 'monthly')),
+    amount_usd DOUBLE PRECISION NOT NULL CHECK (amount_usd > 0),
+    thresholds INTEGER[] NOT NULL DEFAULT '{50,80,100}',
+    notification_channels JSONB NOT NULL DEFAULT '[]',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row per threshold crossed, so each is notified once per period
+CREATE TABLE IF NOT EXISTS budget_alerts (
+    budget_id UUID NOT NULL REFERENCES budgets (id) ON DELETE CASCADE,
+    period_start TIMESTAMPTZ NOT NULL,
+    threshold_percent INTEGER NOT NULL,
+    spent_usd DOUBLE PRECISION NOT NULL,
+    notifications_sent JSONB NOT NULL DEFAULT '[]',
+    triggered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (budget_id, period_start, threshold_percent)
+);
+
+-- Project-scoped budgets sum spans by this attribute
+CREATE INDEX IF NOT EXISTS idx_spans_project
+    ON spans ((attributes->>'agenttrace.project'), started_at DESC)
+    WHERE attributes ? 'agenttrace.project';
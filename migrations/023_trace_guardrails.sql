@@ -0,0 +1,5 @@
+-- Per-trace guardrail rules: condition_type 'trace' with a trace_cost,
+-- trace_tokens or trace_span_count metric, checked by the pipeline
+ALTER TABLE alert_rules DROP CONSTRAINT IF EXISTS alert_rules_condition_type_check;
+ALTER TABLE alert_rules ADD CONSTRAINT alert_rules_condition_type_check
+    CHECK (condition_type IN ('threshold', 'anomaly', 'rate_change', 'absence', 'trace'));
@@ -0,0 +1,15 @@
+-- Alert notifications waiting to be retried after a failed send. alert_events
+-- is a hypertable, so rows reference their rule rather than the event.
+CREATE TABLE IF NOT EXISTS notification_retries (
+    id UUID PRIMARY KEY,
+    rule_id UUID NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    event_id UUID NOT NULL,
+    channel JSONB NOT NULL,
+    channel_type TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    next_attempt_at TIMESTAMPTZ NOT NULL,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_notification_retries_due ON notification_retries (next_attempt_at);
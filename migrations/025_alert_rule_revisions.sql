@@ -0,0 +1,15 @@
+-- Every change to an alert rule, with the rule as JSON before and after.
+-- Not tied to alert_rules so a deleted rule's history can still be read and
+-- the rule restored from it.
+CREATE TABLE IF NOT EXISTS alert_rule_revisions (
+    id UUID PRIMARY KEY,
+    rule_id UUID NOT NULL,
+    revision INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    before JSONB,
+    after JSONB,
+    reverted_to INTEGER,
+    UNIQUE (rule_id, revision)
+);
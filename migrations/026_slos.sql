@@ -0,0 +1,38 @@
+-- Service level objectives over a rolling window, and the burn-rate alerts
+-- each has fired
+CREATE TABLE IF NOT EXISTS slos (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    service_name VARCHAR(255),
+    operation_name VARCHAR(255),
+    root_spans_only BOOLEAN NOT NULL DEFAULT TRUE,
+    indicator VARCHAR(20) NOT NULL CHECK (indicator IN ('availability', 'latency')),
+    latency_threshold_ms DOUBLE PRECISION,
+    objective_percent DOUBLE PRECISION NOT NULL
+        CHECK (objective_percent > 0 AND objective_percent < 100),
+    window_days INTEGER NOT NULL DEFAULT 30 CHECK (window_days > 0),
+    burn_rate_alerts JSONB NOT NULL DEFAULT '[]',
+    notification_channels JSONB NOT NULL DEFAULT '[]',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One row each time a burn-rate alert starts firing; resolved_at is set
+-- once it clears, so it can fire again
+CREATE TABLE IF NOT EXISTS slo_burn_alerts (
+    id UUID PRIMARY KEY,
+    slo_id UUID NOT NULL REFERENCES slos (id) ON DELETE CASCADE,
+    long_window_minutes INTEGER NOT NULL,
+    short_window_minutes INTEGER NOT NULL,
+    burn_rate DOUBLE PRECISION NOT NULL,
+    notifications_sent JSONB NOT NULL DEFAULT '[]',
+    triggered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ
+);
+
+-- At most one firing alert per SLO and window pair
+CREATE UNIQUE INDEX IF NOT EXISTS idx_slo_burn_alerts_firing
+    ON slo_burn_alerts (slo_id, long_window_minutes, short_window_minutes)
+    WHERE resolved_at IS NULL;
@@ -0,0 +1,5 @@
+-- Who acknowledged an alert event, their comment, and who is handling it
+ALTER TABLE alert_events ADD COLUMN IF NOT EXISTS acknowledged_by TEXT;
+ALTER TABLE alert_events ADD COLUMN IF NOT EXISTS acknowledged_at TIMESTAMPTZ;
+ALTER TABLE alert_events ADD COLUMN IF NOT EXISTS ack_comment TEXT;
+ALTER TABLE alert_events ADD COLUMN IF NOT EXISTS assignee TEXT;
@@ -0,0 +1,2 @@
+-- Input tokens served from the provider's prompt cache, billed at the cached rate
+ALTER TABLE spans ADD COLUMN IF NOT EXISTS tokens_cached_in INTEGER;
@@ -0,0 +1,5 @@
+-- Streaming calls store a span as 'in_progress' first; the collector updates
+-- it in place when the final span arrives
+ALTER TABLE spans DROP CONSTRAINT IF EXISTS spans_status_check;
+ALTER TABLE spans ADD CONSTRAINT spans_status_check
+    CHECK (status IN ('ok', 'error', 'unset', 'in_progress'));
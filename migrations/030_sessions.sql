@@ -0,0 +1,33 @@
+-- Sessions group the traces of a multi-turn conversation. Spans carry the
+-- session and end user; per-session rollups are kept up to date as spans are
+-- ingested, like trace_summaries, and session_traces lists each session's
+-- traces so a trace is only counted once.
+ALTER TABLE spans ADD COLUMN IF NOT EXISTS session_id VARCHAR(255);
+ALTER TABLE spans ADD COLUMN IF NOT EXISTS user_id VARCHAR(255);
+CREATE INDEX IF NOT EXISTS idx_spans_session
+    ON spans (session_id, started_at DESC) WHERE session_id IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id VARCHAR(255) PRIMARY KEY,
+    user_id VARCHAR(255),
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    trace_count BIGINT NOT NULL DEFAULT 0,
+    span_count BIGINT NOT NULL DEFAULT 0,
+    error_count BIGINT NOT NULL DEFAULT 0,
+    total_tokens BIGINT NOT NULL DEFAULT 0,
+    total_cost_usd DOUBLE PRECISION NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_last_seen ON sessions (last_seen DESC);
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id, last_seen DESC);
+
+CREATE TABLE IF NOT EXISTS session_traces (
+    session_id VARCHAR(255) NOT NULL,
+    trace_id VARCHAR(32) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (session_id, trace_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_traces_trace ON session_traces (trace_id);
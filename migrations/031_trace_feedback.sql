@@ -0,0 +1,16 @@
+-- Thumbs up/down and comments end users leave on a trace from the product
+-- UI. Search filters on negative ratings and metrics compare rated traces'
+-- cost and latency, joining through trace_id.
+CREATE TABLE IF NOT EXISTS trace_feedback (
+    id UUID PRIMARY KEY,
+    trace_id VARCHAR(32) NOT NULL,
+    rating VARCHAR(16) CHECK (rating IN ('positive', 'negative')),
+    comment TEXT,
+    user_id VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (rating IS NOT NULL OR comment IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_trace_feedback_trace ON trace_feedback (trace_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_trace_feedback_negative
+    ON trace_feedback (trace_id) WHERE rating = 'negative';
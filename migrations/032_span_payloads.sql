@@ -0,0 +1,15 @@
+-- Full prompts and completions of spans, when payload storage keeps them in
+-- the database. Spans only carry 500-character previews; the full text is
+-- loaded on demand and expires on its own retention, by created_at.
+CREATE TABLE IF NOT EXISTS span_payloads (
+    trace_id VARCHAR(32) NOT NULL,
+    span_id VARCHAR(32) NOT NULL,
+    prompt TEXT,
+    completion TEXT,
+    truncated BOOLEAN NOT NULL DEFAULT FALSE,
+    size_bytes BIGINT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (trace_id, span_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_span_payloads_created ON span_payloads (created_at);
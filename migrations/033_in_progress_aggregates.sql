@@ -0,0 +1,39 @@
+-- Streaming spans stored as 'in_progress' are counted once they finish.
+-- The 5-minute rollups are rebuilt without them, and refreshed over the whole
+-- 7 days they cover so buckets change when a start event is updated or closed;
+-- the first refresh also fills in the history dropped with the old view.
+DROP MATERIALIZED VIEW IF EXISTS metrics_5min CASCADE;
+
+CREATE MATERIALIZED VIEW metrics_5min
+WITH (timescaledb.continuous) AS
+SELECT
+    time_bucket('5 minutes', started_at) AS bucket,
+    service_name,
+    model_name,
+    model_provider,
+    COUNT(*) as request_count,
+    SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
+    SUM(COALESCE(tokens_in, 0)) as tokens_in_sum,
+    SUM(COALESCE(tokens_out, 0)) as tokens_out_sum,
+    SUM(COALESCE(cost_usd, 0)) as cost_sum,
+    AVG(duration_ms) as latency_avg_ms,
+    MIN(duration_ms) as latency_min_ms,
+    MAX(duration_ms) as latency_max_ms,
+    percentile_cont(0.50) WITHIN GROUP (ORDER BY duration_ms) as latency_p50_ms,
+    percentile_cont(0.90) WITHIN GROUP (ORDER BY duration_ms) as latency_p90_ms,
+    percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms) as latency_p95_ms,
+    percentile_cont(0.99) WITHIN GROUP (ORDER BY duration_ms) as latency_p99_ms
+FROM spans
+WHERE status <> 'in_progress'
+GROUP BY bucket, service_name, model_name, model_provider
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('metrics_5min',
+    start_offset => INTERVAL '7 days',
+    end_offset => INTERVAL '5 minutes',
+    schedule_interval => INTERVAL '5 minutes',
+    if_not_exists => TRUE
+);
+
+-- Final updates look up their stored start event by span ID
+CREATE INDEX IF NOT EXISTS idx_spans_in_progress ON spans (span_id) WHERE status = 'in_progress';
@@ -0,0 +1,25 @@
+-- Hourly cost per service, model and project, so budgets sum a month of spend
+-- without scanning spans. Real-time aggregation adds the spans since the last
+-- refresh, and the policy refreshes a month back so late or finished
+-- streaming spans are counted in their hour.
+CREATE MATERIALIZED VIEW IF NOT EXISTS cost_hourly
+WITH (timescaledb.continuous, timescaledb.materialized_only = false) AS
+SELECT
+    time_bucket('1 hour', started_at) AS bucket,
+    service_name,
+    model_name,
+    attributes->>'agenttrace.project' AS project,
+    SUM(COALESCE(cost_usd, 0)) AS cost_usd
+FROM spans
+WHERE status <> 'in_progress'
+GROUP BY bucket, service_name, model_name, project
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('cost_hourly',
+    start_offset => INTERVAL '32 days',
+    end_offset => INTERVAL '1 hour',
+    schedule_interval => INTERVAL '15 minutes',
+    if_not_exists => TRUE
+);
+
+CREATE INDEX IF NOT EXISTS idx_cost_hourly_service ON cost_hourly (service_name, bucket DESC);
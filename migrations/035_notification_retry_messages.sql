@@ -0,0 +1,9 @@
+-- Retries of notifications other than alert events, like SLO burn-rate
+-- alerts, store the whole message instead of referencing an event
+ALTER TABLE notification_retries ALTER COLUMN rule_id DROP NOT NULL;
+ALTER TABLE notification_retries ALTER COLUMN event_id DROP NOT NULL;
+ALTER TABLE notification_retries ADD COLUMN IF NOT EXISTS message JSONB;
+
+ALTER TABLE notification_retries DROP CONSTRAINT IF EXISTS notification_retries_message_check;
+ALTER TABLE notification_retries ADD CONSTRAINT notification_retries_message_check
+    CHECK ((rule_id IS NOT NULL AND event_id IS NOT NULL) OR message IS NOT NULL);
@@ -0,0 +1,11 @@
+-- Span priorities are matched by name when pruning, so reject any other
+-- value rather than keep it forever
+DO $$
+BEGIN
+    IF NOT EXISTS (
+        SELECT 1 FROM pg_constraint WHERE conname = 'spans_priority_check'
+    ) THEN
+        ALTER TABLE spans ADD CONSTRAINT spans_priority_check
+            CHECK (priority IN ('high', 'normal', 'low'));
+    END IF;
+END $$;
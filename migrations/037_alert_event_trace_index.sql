@@ -0,0 +1,4 @@
+-- Events are looked up by the traces they sampled, for a trace's alerts in
+-- the API and GraphQL
+CREATE INDEX IF NOT EXISTS idx_alert_events_trace_ids
+    ON alert_events USING GIN (trace_ids jsonb_path_ops);
@@ -0,0 +1,2 @@
+-- Feedback is pruned by when it was left, alongside the traces it rates
+CREATE INDEX IF NOT EXISTS idx_trace_feedback_created ON trace_feedback (created_at);
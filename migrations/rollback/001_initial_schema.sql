@@ -0,0 +1,17 @@
+-- Revert 001_initial_schema.sql: drops every table, view and function it
+-- created. All span, trace and alert data is lost.
+DROP MATERIALIZED VIEW IF EXISTS metrics_5min CASCADE;
+
+DROP TABLE IF EXISTS api_keys CASCADE;
+DROP TABLE IF EXISTS services CASCADE;
+DROP TABLE IF EXISTS model_pricing CASCADE;
+DROP TABLE IF EXISTS alert_events CASCADE;
+DROP TABLE IF EXISTS alert_rules CASCADE;
+DROP TABLE IF EXISTS metrics_hourly CASCADE;
+DROP TABLE IF EXISTS span_events CASCADE;
+DROP TABLE IF EXISTS spans CASCADE;
+DROP TABLE IF EXISTS traces CASCADE;
+
+DROP FUNCTION IF EXISTS update_updated_at() CASCADE;
+DROP FUNCTION IF EXISTS update_trace_aggregates(VARCHAR);
+DROP FUNCTION IF EXISTS calculate_span_cost CASCADE;
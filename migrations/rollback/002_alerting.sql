@@ -0,0 +1,13 @@
+-- Revert 002_alerting.sql. The alert tables themselves come from 001, so
+-- only the indexes this migration added are dropped.
+DROP INDEX IF EXISTS idx_alert_rules_metric;
+DROP INDEX IF EXISTS idx_alert_events_rule_id;
+DROP INDEX IF EXISTS idx_alert_events_triggered_at;
+
+DROP INDEX IF EXISTS idx_spans_operation_name_gin;
+DROP INDEX IF EXISTS idx_spans_service_name_gin;
+DROP INDEX IF EXISTS idx_spans_fulltext;
+DROP INDEX IF EXISTS idx_spans_cost;
+DROP INDEX IF EXISTS idx_spans_duration;
+DROP INDEX IF EXISTS idx_spans_service_status_time;
+DROP INDEX IF EXISTS idx_spans_model_time;
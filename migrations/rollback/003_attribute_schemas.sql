@@ -0,0 +1,3 @@
+-- Revert 003_attribute_schemas.sql
+DROP INDEX IF EXISTS idx_spans_schema_violations;
+DROP TABLE IF EXISTS attribute_schemas;
@@ -0,0 +1,2 @@
+-- Revert 004_span_links.sql
+DROP INDEX IF EXISTS idx_spans_links;
@@ -0,0 +1,10 @@
+-- Revert 005_compression.sql. Compressed chunks are decompressed first,
+-- which needs enough free disk space for the uncompressed data.
+SELECT remove_compression_policy('spans', if_exists => TRUE);
+SELECT remove_compression_policy('span_events', if_exists => TRUE);
+
+SELECT decompress_chunk(c, if_compressed => TRUE) FROM show_chunks('spans') c;
+SELECT decompress_chunk(c, if_compressed => TRUE) FROM show_chunks('span_events') c;
+
+ALTER TABLE spans SET (timescaledb.compress = false);
+ALTER TABLE span_events SET (timescaledb.compress = false);
@@ -0,0 +1,3 @@
+-- Revert 006_span_priority.sql
+DROP INDEX IF EXISTS idx_spans_priority;
+ALTER TABLE spans DROP COLUMN IF EXISTS priority;
@@ -0,0 +1,3 @@
+-- Revert 007_span_archives.sql. Archived Parquet files are left in place
+-- but can no longer be restored through the manifest.
+DROP TABLE IF EXISTS span_archives;
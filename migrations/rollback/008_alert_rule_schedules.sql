@@ -0,0 +1,2 @@
+-- Revert 008_alert_rule_schedules.sql
+ALTER TABLE alert_rules DROP COLUMN IF EXISTS schedule;
@@ -0,0 +1,2 @@
+-- Revert 009_latency_targets.sql
+ALTER TABLE attribute_schemas DROP COLUMN IF EXISTS latency_targets;
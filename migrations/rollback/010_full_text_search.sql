@@ -0,0 +1,2 @@
+-- Revert 010_full_text_search.sql
+DROP INDEX IF EXISTS idx_spans_text_search;
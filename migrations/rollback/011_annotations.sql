@@ -0,0 +1,2 @@
+-- Revert 011_annotations.sql
+DROP TABLE IF EXISTS annotations;
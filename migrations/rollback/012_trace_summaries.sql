@@ -0,0 +1,2 @@
+-- Revert 012_trace_summaries.sql
+DROP TABLE IF EXISTS trace_summaries;
@@ -0,0 +1,2 @@
+-- Revert 013_service_activity.sql
+DROP TABLE IF EXISTS service_activity;
@@ -0,0 +1,4 @@
+-- Revert 014_alert_incidents.sql
+DROP INDEX IF EXISTS idx_alert_events_incident;
+ALTER TABLE alert_events DROP COLUMN IF EXISTS incident_id;
+DROP TABLE IF EXISTS alert_incidents;
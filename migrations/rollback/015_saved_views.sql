@@ -0,0 +1,2 @@
+-- Revert 015_saved_views.sql
+DROP TABLE IF EXISTS saved_views;
@@ -0,0 +1,2 @@
+-- Revert 016_trace_annotations.sql
+DROP TABLE IF EXISTS trace_annotations;
@@ -0,0 +1,3 @@
+-- Revert 017_webhook_subscriptions.sql
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhook_subscriptions;
@@ -0,0 +1,2 @@
+-- Revert 018_audit_log.sql
+DROP TABLE IF EXISTS audit_log;
@@ -0,0 +1,2 @@
+-- Revert 019_alert_rule_state.sql
+DROP TABLE IF EXISTS alert_rule_state;
@@ -0,0 +1,2 @@
+-- Revert 020_alert_message_templates.sql
+ALTER TABLE alert_rules DROP COLUMN IF EXISTS message_template;
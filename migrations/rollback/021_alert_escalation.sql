@@ -0,0 +1,2 @@
+-- Revert 021_alert_escalation.sql
+ALTER TABLE alert_rules DROP COLUMN IF EXISTS escalation;
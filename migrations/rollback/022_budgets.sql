@@ -0,0 +1,4 @@
+-- Revert 022_budgets.sql
+DROP INDEX IF EXISTS idx_spans_project;
+DROP TABLE IF EXISTS budget_alerts;
+DROP TABLE IF EXISTS budgets;
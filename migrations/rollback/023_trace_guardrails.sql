@@ -0,0 +1,5 @@
+-- Revert 023_trace_guardrails.sql
+DELETE FROM alert_rules WHERE condition_type = 'trace';
+ALTER TABLE alert_rules DROP CONSTRAINT IF EXISTS alert_rules_condition_type_check;
+ALTER TABLE alert_rules ADD CONSTRAINT alert_rules_condition_type_check
+    CHECK (condition_type IN ('threshold', 'anomaly', 'rate_change', 'absence'));
@@ -0,0 +1,2 @@
+-- Revert 024_notification_retries.sql
+DROP TABLE IF EXISTS notification_retries;
@@ -0,0 +1,2 @@
+-- Revert 025_alert_rule_revisions.sql
+DROP TABLE IF EXISTS alert_rule_revisions;
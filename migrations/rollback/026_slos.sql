@@ -0,0 +1,3 @@
+-- Revert 026_slos.sql
+DROP TABLE IF EXISTS slo_burn_alerts;
+DROP TABLE IF EXISTS slos;
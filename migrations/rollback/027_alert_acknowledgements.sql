@@ -0,0 +1,5 @@
+-- Revert 027_alert_acknowledgements.sql
+ALTER TABLE alert_events DROP COLUMN IF EXISTS assignee;
+ALTER TABLE alert_events DROP COLUMN IF EXISTS ack_comment;
+ALTER TABLE alert_events DROP COLUMN IF EXISTS acknowledged_at;
+ALTER TABLE alert_events DROP COLUMN IF EXISTS acknowledged_by;
@@ -0,0 +1,2 @@
+-- Revert 028_span_cached_tokens.sql
+ALTER TABLE spans DROP COLUMN IF EXISTS tokens_cached_in;
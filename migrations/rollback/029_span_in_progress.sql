@@ -0,0 +1,5 @@
+-- Revert 029_span_in_progress.sql
+UPDATE spans SET status = 'unset' WHERE status = 'in_progress';
+ALTER TABLE spans DROP CONSTRAINT IF EXISTS spans_status_check;
+ALTER TABLE spans ADD CONSTRAINT spans_status_check
+    CHECK (status IN ('ok', 'error', 'unset'));
@@ -0,0 +1,6 @@
+-- Revert 030_sessions.sql
+DROP TABLE IF EXISTS session_traces;
+DROP TABLE IF EXISTS sessions;
+DROP INDEX IF EXISTS idx_spans_session;
+ALTER TABLE spans DROP COLUMN IF EXISTS user_id;
+ALTER TABLE spans DROP COLUMN IF EXISTS session_id;
@@ -0,0 +1,2 @@
+-- Revert 031_trace_feedback.sql
+DROP TABLE IF EXISTS trace_feedback;
@@ -0,0 +1,2 @@
+-- Revert 032_span_payloads.sql
+DROP TABLE IF EXISTS span_payloads;
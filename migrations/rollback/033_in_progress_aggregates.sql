@@ -0,0 +1,35 @@
+-- Revert 033_in_progress_aggregates.sql
+DROP INDEX IF EXISTS idx_spans_in_progress;
+
+DROP MATERIALIZED VIEW IF EXISTS metrics_5min CASCADE;
+
+CREATE MATERIALIZED VIEW metrics_5min
+WITH (timescaledb.continuous) AS
+SELECT
+    time_bucket('5 minutes', started_at) AS bucket,
+    service_name,
+    model_name,
+    model_provider,
+    COUNT(*) as request_count,
+    SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) as error_count,
+    SUM(COALESCE(tokens_in, 0)) as tokens_in_sum,
+    SUM(COALESCE(tokens_out, 0)) as tokens_out_sum,
+    SUM(COALESCE(cost_usd, 0)) as cost_sum,
+    AVG(duration_ms) as latency_avg_ms,
+    MIN(duration_ms) as latency_min_ms,
+    MAX(duration_ms) as latency_max_ms,
+    percentile_cont(0.50) WITHIN GROUP (ORDER BY duration_ms) as latency_p50_ms,
+    percentile_cont(0.90) WITHIN GROUP (ORDER BY duration_ms) as latency_p90_ms,
+    percentile_cont(0.95) WITHIN GROUP (ORDER BY duration_ms) as latency_p95_ms,
+    percentile_cont(0.99) WITHIN GROUP (ORDER BY duration_ms) as latency_p99_ms
+FROM spans
+WHERE started_at > NOW() - INTERVAL '7 days'
+GROUP BY bucket, service_name, model_name, model_provider
+WITH NO DATA;
+
+SELECT add_continuous_aggregate_policy('metrics_5min',
+    start_offset => INTERVAL '1 hour',
+    end_offset => INTERVAL '5 minutes',
+    schedule_interval => INTERVAL '5 minutes',
+    if_not_exists => TRUE
+);
@@ -0,0 +1,2 @@
+-- Revert 034_cost_hourly.sql
+DROP MATERIALIZED VIEW IF EXISTS cost_hourly CASCADE;
@@ -0,0 +1,6 @@
+-- Revert 035_notification_retry_messages.sql
+DELETE FROM notification_retries WHERE message IS NOT NULL;
+ALTER TABLE notification_retries DROP CONSTRAINT IF EXISTS notification_retries_message_check;
+ALTER TABLE notification_retries DROP COLUMN IF EXISTS message;
+ALTER TABLE notification_retries ALTER COLUMN event_id SET NOT NULL;
+ALTER TABLE notification_retries ALTER COLUMN rule_id SET NOT NULL;
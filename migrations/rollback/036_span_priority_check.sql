@@ -0,0 +1,2 @@
+-- Revert 036_span_priority_check.sql
+ALTER TABLE spans DROP CONSTRAINT IF EXISTS spans_priority_check;
@@ -0,0 +1,2 @@
+-- Revert 037_alert_event_trace_index.sql
+DROP INDEX IF EXISTS idx_alert_events_trace_ids;
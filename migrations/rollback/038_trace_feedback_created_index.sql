@@ -0,0 +1,2 @@
+-- Revert 038_trace_feedback_created_index.sql
+DROP INDEX IF EXISTS idx_trace_feedback_created;
@@ -58,6 +58,7 @@ class ConsoleExporter(Exporter):
             "ok": green,
             "error": red,
             "unset": dim,
+            "in_progress": yellow,
         }.get(span.status.value, dim)
 
         # Header
@@ -26,6 +26,7 @@ class SpanStatus(str, Enum):
     UNSET = "unset"
     OK = "ok"
     ERROR = "error"
+    IN_PROGRESS = "in_progress"
 
 
 class SpanType(str, Enum):
@@ -54,6 +55,8 @@ class Span(BaseModel):
     span_id: str = Field(default_factory=lambda: uuid4().hex[:16])
     trace_id: str = Field(default_factory=lambda: uuid4().hex)
     parent_span_id: str | None = None
+    session_id: str | None = None
+    user_id: str | None = None
 
     operation_name: str
     service_name: str = "default"
@@ -70,6 +73,7 @@ class Span(BaseModel):
     model_name: str | None = None
     model_provider: str | None = None
     tokens_in: int | None = None
+    tokens_cached_in: int | None = None
     tokens_out: int | None = None
     tokens_reasoning: int | None = None
 
@@ -130,6 +134,8 @@ class Span(BaseModel):
             "span_id": self.span_id,
             "trace_id": self.trace_id,
             "parent_span_id": self.parent_span_id,
+            "session_id": self.session_id,
+            "user_id": self.user_id,
             "operation_name": self.operation_name,
             "service_name": self.service_name,
             "started_at": self.started_at.isoformat(),
@@ -139,6 +145,7 @@ class Span(BaseModel):
             "model_name": self.model_name,
             "model_provider": self.model_provider,
             "tokens_in": self.tokens_in,
+            "tokens_cached_in": self.tokens_cached_in,
             "tokens_out": self.tokens_out,
             "tokens_reasoning": self.tokens_reasoning,
             "tool_name": self.tool_name,